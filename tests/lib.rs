@@ -1,2 +1,3 @@
 // This file intentionally left blank.
 pub mod mock_io;
+pub mod synthetic_graph;
@@ -4,15 +4,15 @@ use std::{
     ffi::{OsStr, OsString},
     pin::Pin,
     sync::{atomic::AtomicU64, Arc, OnceLock},
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 
 use parking_lot::Mutex;
 use werk_fs::Absolute;
 use werk_parser::parser::{Offset, Span};
 use werk_runner::{
-    globset, BuildStatus, DirEntry, Env, Error, GlobSettings, Io, Metadata, Outdatedness,
-    ShellCommandLine, TaskId, WhichError, WorkspaceSettings,
+    globset, BuildStatus, DirEntry, Echo, Env, Error, GlobSettings, Io, Metadata, Outdatedness,
+    Redirection, ShellCommandLine, TaskId, WhichError, WorkspaceSettings,
 };
 use werk_util::{Diagnostic as _, DiagnosticError, DiagnosticSource};
 use winnow::stream::Offset as _;
@@ -39,6 +39,20 @@ pub fn empty_program_output() -> std::process::Output {
     }
 }
 
+/// Output of a program that exited with status 1.
+#[must_use]
+pub fn failed_program_output() -> std::process::Output {
+    #[cfg(unix)]
+    let status = std::os::unix::process::ExitStatusExt::from_raw(1 << 8);
+    #[cfg(windows)]
+    let status = std::os::windows::process::ExitStatusExt::from_raw(1);
+    std::process::Output {
+        status,
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+    }
+}
+
 pub struct TestBuilder<'a> {
     /// Note: Path in the mocked filesystem, not the actual filesystem.
     /// `canonicalize()` won't work etc.
@@ -142,7 +156,7 @@ impl<'a> TestBuilder<'a> {
                     let contents = env
                         .get(&varname)
                         .cloned()
-                        .or_else(|| std::env::var_os(&varname))
+                        .or_else(|| (!env.clear).then(|| std::env::var_os(&varname)).flatten())
                         .unwrap_or_default();
                     let contents: String = contents.into_string().unwrap();
                     insert_fs(
@@ -243,7 +257,7 @@ impl<'a> Test<'a> {
 
                 if let Some(captures) = regexes.file.captures(line) {
                     let filename = captures.get(1).unwrap().as_str();
-                    let content = captures.get(2).unwrap().as_str();
+                    let content = unescape_pragma_content(captures.get(2).unwrap().as_str());
                     let path = self.workspace_path(filename.split('/'));
                     insert_fs(
                         &mut fs,
@@ -264,7 +278,7 @@ impl<'a> Test<'a> {
                     create_dirs(&mut fs, &path).unwrap();
                 } else if let Some(captures) = regexes.assert_file.captures(line) {
                     let filename = captures.get(1).unwrap().as_str();
-                    let content = captures.get(2).unwrap().as_str();
+                    let content = unescape_pragma_content(captures.get(2).unwrap().as_str());
                     self.pragma_check_files.push((
                         span,
                         filename.to_owned(),
@@ -295,13 +309,8 @@ impl<'a> Test<'a> {
         Ok(())
     }
 
-    pub fn create_workspace<'b>(
-        &'b self,
-        defines: &[(&str, &str)],
-    ) -> Result<
-        werk_runner::Workspace<'b>,
-        DiagnosticError<'b, werk_runner::Error, &'b werk_parser::Document<'b>>,
-    > {
+    /// The default workspace settings for this test.
+    pub fn workspace_settings(&self) -> WorkspaceSettings {
         let mut settings = WorkspaceSettings::new(self.output_dir.clone());
 
         // Normally this would be covered by `.gitignore`, but we don't have that,
@@ -314,16 +323,42 @@ impl<'a> Test<'a> {
                 .unwrap(),
         );
 
+        // Pools, the shell, and source directories are declared in config,
+        // which is normally read by the front-end.
+        if let Ok(config) = werk_runner::ir::Config::new(&self.ast) {
+            settings.apply_config(&config);
+        }
+
+        settings
+    }
+
+    pub fn create_workspace<'b>(
+        &'b self,
+        defines: &[(&str, &str)],
+    ) -> Result<
+        werk_runner::Workspace<'b>,
+        DiagnosticError<'b, werk_runner::Error, &'b werk_parser::Document<'b>>,
+    > {
+        let mut settings = self.workspace_settings();
         for (key, value) in defines {
             settings.define(*key, *value);
         }
+        self.create_workspace_with_settings(&settings)
+    }
 
+    pub fn create_workspace_with_settings<'b>(
+        &'b self,
+        settings: &WorkspaceSettings,
+    ) -> Result<
+        werk_runner::Workspace<'b>,
+        DiagnosticError<'b, werk_runner::Error, &'b werk_parser::Document<'b>>,
+    > {
         werk_runner::Workspace::new_with_diagnostics(
             &self.ast,
             &*self.io,
             &*self.render,
             self.workspace_dir.clone(),
-            &settings,
+            settings,
         )
     }
 
@@ -487,7 +522,9 @@ pub struct MockRender {
 pub enum MockRenderEvent {
     WillBuild(TaskId, usize, Outdatedness),
     DidBuild(TaskId, Result<BuildStatus, Error>),
-    WillExecute(TaskId, ShellCommandLine, usize, usize),
+    /// Task, command, step, number of steps, and whether the command is
+    /// printed.
+    WillExecute(TaskId, ShellCommandLine, usize, usize, Echo),
     DidExecute(
         TaskId,
         ShellCommandLine,
@@ -495,8 +532,15 @@ pub enum MockRenderEvent {
         usize,
         usize,
     ),
+    /// Task, command, failed attempt, and maximum number of attempts.
+    WillRetry(TaskId, ShellCommandLine, usize, usize),
     Message(Option<TaskId>, String),
     Warning(Option<TaskId>, String),
+    /// Task, variable name, and the value formatted with `Display`.
+    EvaluateVariable(Option<TaskId>, String, String),
+    /// An interactive command of the task took over the terminal.
+    Suspend(TaskId),
+    Resume(TaskId),
 }
 
 impl MockRender {
@@ -526,12 +570,14 @@ impl werk_runner::Render for MockRender {
         command: &ShellCommandLine,
         step: usize,
         num_steps: usize,
+        echo: Echo,
     ) {
         self.log.lock().push(MockRenderEvent::WillExecute(
             task_id,
             command.clone(),
             step,
             num_steps,
+            echo,
         ));
     }
 
@@ -552,6 +598,23 @@ impl werk_runner::Render for MockRender {
         ));
     }
 
+    fn will_retry(
+        &self,
+        task_id: TaskId,
+        command: &ShellCommandLine,
+        _status: std::process::ExitStatus,
+        attempt: usize,
+        max_attempts: usize,
+        _delay: std::time::Duration,
+    ) {
+        self.log.lock().push(MockRenderEvent::WillRetry(
+            task_id,
+            command.clone(),
+            attempt,
+            max_attempts,
+        ));
+    }
+
     fn message(&self, task_id: Option<TaskId>, message: &str) {
         tracing::trace!(
             "info({}) {message}",
@@ -567,6 +630,27 @@ impl werk_runner::Render for MockRender {
             .lock()
             .push(MockRenderEvent::Warning(task_id, message.to_string()));
     }
+
+    fn did_evaluate_variable(
+        &self,
+        task_id: Option<TaskId>,
+        name: &str,
+        value: &werk_runner::Value,
+    ) {
+        self.log.lock().push(MockRenderEvent::EvaluateVariable(
+            task_id,
+            name.to_string(),
+            value.to_string(),
+        ));
+    }
+
+    fn suspend(&self, task_id: TaskId) {
+        self.log.lock().push(MockRenderEvent::Suspend(task_id));
+    }
+
+    fn resume(&self, task_id: TaskId) {
+        self.log.lock().push(MockRenderEvent::Resume(task_id));
+    }
 }
 
 pub type MockDir = HashMap<OsString, MockDirEntry>;
@@ -579,6 +663,9 @@ pub enum MockDirEntry {
 }
 
 pub type Program = Box<dyn FnMut(&ShellCommandLine, &mut MockDir, &Env) -> ProgramResult + Send>;
+pub type CommandDuration = Box<dyn Fn(&ShellCommandLine) -> Duration + Send>;
+/// The paths and contents of the files in an archive.
+pub type ArchiveFiles = Vec<(String, Vec<u8>)>;
 
 #[derive(Default)]
 pub struct MockIo {
@@ -586,8 +673,16 @@ pub struct MockIo {
     pub which: Mutex<HashMap<String, Absolute<std::path::PathBuf>>>,
     pub programs: Mutex<HashMap<Absolute<std::path::PathBuf>, Program>>,
     pub env: Mutex<Env>,
-    pub oplog: Mutex<Vec<MockIoOp>>,
+    pub oplog: Arc<Mutex<Vec<MockIoOp>>>,
     pub now: AtomicU64,
+    /// How long recipe commands take to finish. When unset, they finish
+    /// immediately.
+    pub command_duration: Mutex<Option<CommandDuration>>,
+    /// Files served by `download`, by URL.
+    pub downloads: Mutex<HashMap<String, Vec<u8>>>,
+    /// The files in the archives understood by `extract_archive`, by the
+    /// contents of the archive.
+    pub archives: Mutex<HashMap<Vec<u8>, ArchiveFiles>>,
 }
 
 impl MockIo {
@@ -599,6 +694,10 @@ impl MockIo {
 #[derive(Clone, Debug, PartialEq)]
 pub enum MockIoOp {
     RunDuringBuild(ShellCommandLine),
+    /// A command started by `RunDuringBuild` exited.
+    FinishDuringBuild(ShellCommandLine),
+    /// A command started by `RunDuringBuild` was killed before it exited.
+    KillDuringBuild(ShellCommandLine),
     RunDuringEval(ShellCommandLine),
     Which(String),
     ReadFile(Absolute<std::path::PathBuf>),
@@ -606,6 +705,12 @@ pub enum MockIoOp {
     CopyFile(Absolute<std::path::PathBuf>, Absolute<std::path::PathBuf>),
     DeleteFile(Absolute<std::path::PathBuf>),
     CreateParentDirs(Absolute<std::path::PathBuf>),
+    CreateDir(Absolute<std::path::PathBuf>),
+    DeleteDir(Absolute<std::path::PathBuf>),
+    Touch(Absolute<std::path::PathBuf>),
+    /// A download of a URL, and whether it resumed an existing file.
+    Download(String, Absolute<std::path::PathBuf>, bool),
+    ExtractArchive(Absolute<std::path::PathBuf>, Absolute<std::path::PathBuf>),
     ReadEnv(String),
 }
 
@@ -890,11 +995,37 @@ impl MockIo {
         self
     }
 
+    pub fn set_download(&self, url: impl Into<String>, data: impl Into<Vec<u8>>) {
+        self.downloads.lock().insert(url.into(), data.into());
+    }
+
+    /// Make `extract_archive` understand an archive with the given contents,
+    /// containing files at paths separated by `/`.
+    pub fn set_archive<'a>(
+        &self,
+        archive: impl Into<Vec<u8>>,
+        files: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) {
+        let files = files
+            .into_iter()
+            .map(|(path, data)| (path.to_owned(), data.as_bytes().to_vec()))
+            .collect();
+        self.archives.lock().insert(archive.into(), files);
+    }
+
     pub fn delete_file(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
         let mut fs = self.filesystem.lock();
         remove_fs(&mut fs, path.as_ref())
     }
 
+    /// Simulate the time it takes to run recipe commands.
+    pub fn set_command_duration(
+        &self,
+        duration: impl Fn(&ShellCommandLine) -> Duration + Send + 'static,
+    ) {
+        *self.command_duration.lock() = Some(Box::new(duration));
+    }
+
     pub fn clear_oplog(&self) {
         self.oplog.lock().clear();
     }
@@ -917,11 +1048,17 @@ impl MockIo {
 }
 
 struct MockChild {
+    command_line: ShellCommandLine,
+    oplog: Arc<Mutex<Vec<MockIoOp>>>,
     stdout: Option<Pin<Box<futures::io::Cursor<Vec<u8>>>>>,
     stderr: Option<Pin<Box<futures::io::Cursor<Vec<u8>>>>>,
-    status: Option<Pin<Box<futures::future::Ready<std::io::Result<std::process::ExitStatus>>>>>,
+    status: Option<MockStatus>,
 }
 
+type MockStatus = Pin<
+    Box<dyn std::future::Future<Output = std::io::Result<std::process::ExitStatus>> + Send + Sync>,
+>;
+
 impl werk_runner::Child for MockChild {
     fn stdin(
         self: std::pin::Pin<&mut Self>,
@@ -958,15 +1095,24 @@ impl werk_runner::Child for MockChild {
     > {
         self.status.take().unwrap()
     }
+
+    fn kill(&mut self) -> std::io::Result<()> {
+        self.oplog
+            .lock()
+            .push(MockIoOp::KillDuringBuild(self.command_line.clone()));
+        Ok(())
+    }
 }
 
 impl werk_runner::Io for MockIo {
     fn run_recipe_command(
         &self,
         command_line: &ShellCommandLine,
-        _working_dir: &Absolute<std::path::Path>,
+        working_dir: &Absolute<std::path::Path>,
         env: &Env,
+        redirection: &Redirection,
         forward_stdout: bool,
+        _process_group: bool,
     ) -> std::io::Result<Box<dyn werk_runner::Child>> {
         tracing::trace!("run during build: {}", command_line);
         self.oplog
@@ -981,25 +1127,105 @@ impl werk_runner::Io for MockIo {
             ));
         };
         let mut fs = self.filesystem.lock();
-        let mut global_env = self.env.lock().clone();
+        let mut global_env = if env.clear {
+            Env::default()
+        } else {
+            self.env.lock().clone()
+        };
         global_env.merge_from(env);
+        // Programs see their working directory as `PWD`, like in a shell.
+        global_env.env("PWD", working_dir);
         let std::process::Output {
             status,
             stderr,
             stdout,
         } = program(command_line, &mut fs, &global_env)?;
 
+        // Programs don't read their stdin, but it must exist.
+        if let Some(ref stdin) = redirection.stdin {
+            read_fs(&fs, stdin)?;
+        }
+        let stdout = match redirection.stdout {
+            Some(ref path) => {
+                insert_fs(
+                    &mut fs,
+                    path,
+                    (
+                        Metadata {
+                            mtime: self.now(),
+                            is_file: true,
+                            is_symlink: false,
+                        },
+                        stdout,
+                    ),
+                )?;
+                Vec::new()
+            }
+            None => stdout,
+        };
+
+        let duration = self
+            .command_duration
+            .lock()
+            .as_ref()
+            .map_or(Duration::ZERO, |duration| duration(command_line));
+        let oplog = self.oplog.clone();
+        let finished_command_line = command_line.clone();
+        let status: MockStatus = Box::pin(async move {
+            if !duration.is_zero() {
+                smol::Timer::after(duration).await;
+            }
+            oplog
+                .lock()
+                .push(MockIoOp::FinishDuringBuild(finished_command_line));
+            Ok(status)
+        });
+
         Ok(Box::new(MockChild {
+            command_line: command_line.clone(),
+            oplog: self.oplog.clone(),
             stderr: Some(Box::pin(futures::io::Cursor::new(stderr))),
             stdout: if forward_stdout {
                 Some(Box::pin(futures::io::Cursor::new(stdout)))
             } else {
                 None
             },
-            status: Some(Box::pin(futures::future::ready(Ok(status)))),
+            status: Some(status),
         }))
     }
 
+    fn run_recipe_pipeline(
+        &self,
+        command_lines: &[ShellCommandLine],
+        working_dir: &Absolute<std::path::Path>,
+        env: &Env,
+        redirection: &Redirection,
+        forward_stdout: bool,
+        process_group: bool,
+    ) -> std::io::Result<Vec<Box<dyn werk_runner::Child>>> {
+        // Programs don't read their stdin, so each command runs on its own, and
+        // only the ends of the pipeline are redirected.
+        let last = command_lines.len() - 1;
+        command_lines
+            .iter()
+            .enumerate()
+            .map(|(index, command_line)| {
+                let redirection = Redirection {
+                    stdin: redirection.stdin.clone().filter(|_| index == 0),
+                    stdout: redirection.stdout.clone().filter(|_| index == last),
+                };
+                self.run_recipe_command(
+                    command_line,
+                    working_dir,
+                    env,
+                    &redirection,
+                    forward_stdout && index == last,
+                    process_group,
+                )
+            })
+            .collect()
+    }
+
     fn run_during_eval(
         &self,
         command_line: &ShellCommandLine,
@@ -1093,6 +1319,13 @@ impl werk_runner::Io for MockIo {
 
     fn metadata(&self, path: &Absolute<std::path::Path>) -> Result<Metadata, Error> {
         let fs = self.filesystem.lock();
+        if let Some(MockDirEntry::Dir(_)) = get_fs(&fs, path) {
+            return Ok(Metadata {
+                mtime: SystemTime::UNIX_EPOCH,
+                is_file: false,
+                is_symlink: false,
+            });
+        }
         read_fs(&fs, path)
             .map(|(entry, _)| entry.metadata)
             .map_err(Into::into)
@@ -1165,6 +1398,132 @@ impl werk_runner::Io for MockIo {
         create_parent_dirs(&mut fs, path)
     }
 
+    fn create_dir(&self, path: &Absolute<std::path::Path>) -> Result<(), std::io::Error> {
+        self.oplog
+            .lock()
+            .push(MockIoOp::CreateDir(path.to_path_buf()));
+
+        let mut fs = self.filesystem.lock();
+        create_dirs(&mut fs, path)
+    }
+
+    fn delete_dir(&self, path: &Absolute<std::path::Path>) -> Result<(), std::io::Error> {
+        self.oplog
+            .lock()
+            .push(MockIoOp::DeleteDir(path.to_path_buf()));
+
+        let mut fs = self.filesystem.lock();
+        remove_fs(&mut fs, path)
+    }
+
+    fn touch(&self, path: &Absolute<std::path::Path>) -> Result<(), std::io::Error> {
+        self.oplog.lock().push(MockIoOp::Touch(path.to_path_buf()));
+
+        let mut fs = self.filesystem.lock();
+        let data = match read_fs(&fs, path) {
+            Ok((_, data)) => data.to_vec(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        insert_fs(
+            &mut fs,
+            path,
+            (
+                Metadata {
+                    mtime: self.now(),
+                    is_file: true,
+                    is_symlink: false,
+                },
+                data,
+            ),
+        )
+    }
+
+    fn download(
+        &self,
+        url: &str,
+        path: &Absolute<std::path::Path>,
+        resume: bool,
+    ) -> Result<(), std::io::Error> {
+        self.oplog.lock().push(MockIoOp::Download(
+            url.to_string(),
+            path.to_path_buf(),
+            resume,
+        ));
+
+        let Some(remote) = self.downloads.lock().get(url).cloned() else {
+            return Err(std::io::Error::other("404 Not Found"));
+        };
+        let mut fs = self.filesystem.lock();
+        // Like a server that supports range requests, send only the bytes
+        // after the existing part of the file.
+        let mut data = match read_fs(&fs, path) {
+            Ok((_, data)) if resume && data.len() < remote.len() => data.to_vec(),
+            _ => Vec::new(),
+        };
+        data.extend_from_slice(&remote[data.len()..]);
+        insert_fs(
+            &mut fs,
+            path,
+            (
+                Metadata {
+                    mtime: self.now(),
+                    is_file: true,
+                    is_symlink: false,
+                },
+                data,
+            ),
+        )
+    }
+
+    fn extract_archive(
+        &self,
+        archive: &Absolute<std::path::Path>,
+        dir: &Absolute<std::path::Path>,
+    ) -> Result<(), std::io::Error> {
+        self.oplog.lock().push(MockIoOp::ExtractArchive(
+            archive.to_path_buf(),
+            dir.to_path_buf(),
+        ));
+
+        let mut fs = self.filesystem.lock();
+        let (_, data) = read_fs(&fs, archive)?;
+        let Some(files) = self.archives.lock().get(data).cloned() else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "not an archive",
+            ));
+        };
+        for (path, data) in files {
+            let path = dir
+                .join(path.split('/').collect::<std::path::PathBuf>())
+                .unwrap();
+            create_dirs(&mut fs, path.parent().unwrap())?;
+            insert_fs(
+                &mut fs,
+                &path,
+                (
+                    Metadata {
+                        mtime: self.now(),
+                        is_file: true,
+                        is_symlink: false,
+                    },
+                    data,
+                ),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn is_same_file(
+        &self,
+        a: &Absolute<std::path::Path>,
+        b: &Absolute<std::path::Path>,
+    ) -> Result<bool, std::io::Error> {
+        // The mock file system is case-sensitive and has no links.
+        Ok(a == b)
+    }
+
     fn read_env(&self, name: &str) -> Option<String> {
         self.oplog.lock().push(MockIoOp::ReadEnv(name.to_string()));
         self.env
@@ -1220,6 +1579,12 @@ impl Default for PragmaRegexes {
     }
 }
 
+/// File contents in pragmas are on a single line, so line breaks are written
+/// as `\n` and `\r`.
+fn unescape_pragma_content(content: &str) -> String {
+    content.replace("\\n", "\n").replace("\\r", "\r")
+}
+
 fn regexes() -> &'static PragmaRegexes {
     static REGEXES: OnceLock<PragmaRegexes> = OnceLock::new();
     REGEXES.get_or_init(PragmaRegexes::default)
@@ -0,0 +1,314 @@
+//! Synthetic dependency graphs for stress and regression tests of the
+//! scheduler.
+//!
+//! A [`GraphSpec`] describes the shape of a random (but deterministic) DAG of
+//! build recipes. [`SyntheticGraph`] turns it into a Werkfile where every node
+//! runs the mock `node` program, and provides assertions over the operation log
+//! of a build.
+
+use std::{collections::BTreeSet, fmt::Write as _, sync::Arc};
+
+use werk_fs::Absolute;
+use werk_runner::{Metadata, ShellCommandLine};
+
+use crate::mock_io::{empty_program_output, insert_fs, program_path, read_fs, MockIoOp, Test};
+
+/// The shape of a synthetic dependency graph.
+#[derive(Clone, Copy, Debug)]
+pub struct GraphSpec {
+    /// Number of build recipes.
+    pub nodes: usize,
+    /// Number of source files in the workspace.
+    pub sources: usize,
+    /// Maximum number of inputs of each build recipe.
+    pub max_fan_in: usize,
+    /// Maximum number of build recipes using each node or source file as an
+    /// input.
+    pub max_fan_out: usize,
+    /// Seed of the random number generator.
+    pub seed: u64,
+}
+
+/// An input of a node in a synthetic graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Input {
+    /// The source file `s{index}.txt` in the workspace.
+    Source(usize),
+    /// The output `n{index}` of another build recipe.
+    Node(usize),
+}
+
+pub struct SyntheticGraph {
+    pub spec: GraphSpec,
+    /// The inputs of each node. Nodes only depend on nodes with a lower index.
+    pub inputs: Vec<Vec<Input>>,
+    /// Nodes that no other node depends on. These are built by `task all`.
+    pub roots: Vec<usize>,
+    /// The generated Werkfile.
+    pub werkfile: String,
+}
+
+impl SyntheticGraph {
+    #[must_use]
+    pub fn generate(spec: GraphSpec) -> Self {
+        let mut rng = XorShift(spec.seed.max(1));
+        let mut fan_out = std::collections::HashMap::<Input, usize>::new();
+        let mut inputs = Vec::with_capacity(spec.nodes);
+
+        for node in 0..spec.nodes {
+            let candidates = (0..spec.sources)
+                .map(Input::Source)
+                .chain((0..node).map(Input::Node))
+                .collect::<Vec<_>>();
+            let mut node_inputs = Vec::new();
+            if !candidates.is_empty() {
+                let wanted = 1 + rng.below(spec.max_fan_in.max(1));
+                // Give up after a bounded number of attempts when most
+                // candidates have reached their maximum fan-out.
+                for _ in 0..wanted * 4 {
+                    if node_inputs.len() == wanted {
+                        break;
+                    }
+                    let input = candidates[rng.below(candidates.len())];
+                    let uses = fan_out.entry(input).or_default();
+                    if *uses >= spec.max_fan_out || node_inputs.contains(&input) {
+                        continue;
+                    }
+                    *uses += 1;
+                    node_inputs.push(input);
+                }
+            }
+            node_inputs.sort();
+            inputs.push(node_inputs);
+        }
+
+        let roots = (0..spec.nodes)
+            .filter(|node| !fan_out.contains_key(&Input::Node(*node)))
+            .collect::<Vec<_>>();
+
+        let mut werkfile = String::from("let node = which \"node\"\n");
+        for (node, node_inputs) in inputs.iter().enumerate() {
+            _ = write!(werkfile, "\n#[restat]\nbuild \"n{node}\" {{\n    from [");
+            for (index, input) in node_inputs.iter().enumerate() {
+                if index != 0 {
+                    werkfile.push_str(", ");
+                }
+                match input {
+                    Input::Source(source) => _ = write!(werkfile, "\"s{source}.txt\""),
+                    Input::Node(dependency) => _ = write!(werkfile, "\"n{dependency}\""),
+                }
+            }
+            werkfile.push_str("]\n    run \"{node} <out> <in*>\"\n}\n");
+        }
+        werkfile.push_str("\ntask all {\n    build [");
+        for (index, root) in roots.iter().enumerate() {
+            if index != 0 {
+                werkfile.push_str(", ");
+            }
+            _ = write!(werkfile, "\"n{root}\"");
+        }
+        werkfile.push_str("]\n}\n");
+
+        Self {
+            spec,
+            inputs,
+            roots,
+            werkfile,
+        }
+    }
+
+    /// Create the source files in the workspace, and register the `node`
+    /// program, which writes the contents of its inputs to its output file
+    /// with the current mock time.
+    pub fn install(&self, test: &Test) {
+        for source in 0..self.spec.sources {
+            test.set_workspace_file(&[&format!("s{source}.txt")], source.to_string())
+                .unwrap();
+        }
+        // Outputs must be strictly newer than their inputs to be up to date.
+        test.io.tick();
+
+        let io = Arc::downgrade(&test.io);
+        test.io
+            .set_program("node", program_path("node"), move |cmd, fs, _env| {
+                let io = io.upgrade().unwrap();
+                let mut contents = Vec::new();
+                for input in &cmd.arguments[1..] {
+                    let path = Absolute::new(std::path::PathBuf::from(input)).unwrap();
+                    let (_, data) = read_fs(fs, &path)?;
+                    contents.extend_from_slice(data);
+                    contents.push(b'\n');
+                }
+                insert_fs(
+                    fs,
+                    std::path::Path::new(&cmd.arguments[0]),
+                    (
+                        Metadata {
+                            mtime: io.now(),
+                            is_file: true,
+                            is_symlink: false,
+                        },
+                        contents,
+                    ),
+                )
+                .unwrap();
+                Ok(empty_program_output())
+            });
+    }
+
+    /// Modify a source file, making it newer than all outputs built so far.
+    pub fn modify_source(&self, test: &Test, source: usize) {
+        test.io.tick();
+        let contents = format!("{source} modified at {:?}", test.io.now());
+        test.set_workspace_file(&[&format!("s{source}.txt")], contents)
+            .unwrap();
+        test.io.tick();
+    }
+
+    /// Touch a source file without changing its contents, making it newer than
+    /// all outputs built so far.
+    pub fn touch_source(&self, test: &Test, source: usize) {
+        let path = test.workspace_path([format!("s{source}.txt")]);
+        let contents = read_fs(&test.io.filesystem.lock(), &path)
+            .unwrap()
+            .1
+            .to_vec();
+        test.io.tick();
+        test.set_workspace_file(&[&format!("s{source}.txt")], contents)
+            .unwrap();
+        test.io.tick();
+    }
+
+    /// The nodes that use `input` directly.
+    #[must_use]
+    pub fn direct_dependents(&self, input: Input) -> BTreeSet<usize> {
+        (0..self.spec.nodes)
+            .filter(|node| self.inputs[*node].contains(&input))
+            .collect()
+    }
+
+    /// The node that a command line builds, if it is the command of a node.
+    #[must_use]
+    pub fn node_of(&self, command_line: &ShellCommandLine) -> Option<usize> {
+        if command_line.program != program_path("node") {
+            return None;
+        }
+        let out = std::path::Path::new(command_line.arguments.first()?);
+        let node = out.file_name()?.to_str()?.strip_prefix('n')?.parse().ok()?;
+        (node < self.spec.nodes).then_some(node)
+    }
+
+    /// All nodes that transitively depend on `input`.
+    #[must_use]
+    pub fn dependents(&self, input: Input) -> BTreeSet<usize> {
+        let mut dependents = BTreeSet::new();
+        // Nodes only depend on nodes with a lower index, so a single pass in
+        // index order visits dependencies before their dependents.
+        for (node, node_inputs) in self.inputs.iter().enumerate() {
+            let is_dependent = node_inputs.iter().any(|node_input| {
+                *node_input == input
+                    || matches!(node_input, Input::Node(dependency) if dependents.contains(dependency))
+            });
+            if is_dependent {
+                dependents.insert(node);
+            }
+        }
+        dependents
+    }
+
+    /// The nodes whose commands were executed, in the order they started.
+    ///
+    /// # Panics
+    ///
+    /// If a node was executed more than once.
+    #[must_use]
+    pub fn executed(&self, oplog: &[MockIoOp]) -> Vec<usize> {
+        let mut seen = BTreeSet::new();
+        let mut executed = Vec::new();
+        for op in oplog {
+            if let MockIoOp::RunDuringBuild(command_line) = op {
+                if let Some(node) = self.node_of(command_line) {
+                    assert!(seen.insert(node), "node n{node} was executed twice");
+                    executed.push(node);
+                }
+            }
+        }
+        executed
+    }
+
+    /// Check that no command was started before the commands of all of its
+    /// inputs that were executed in the same build had finished.
+    ///
+    /// # Panics
+    ///
+    /// If a node was started before one of its inputs finished.
+    pub fn assert_dependency_order(&self, oplog: &[MockIoOp]) {
+        let mut started = BTreeSet::new();
+        let mut finished = BTreeSet::new();
+        let executed = self.executed(oplog).into_iter().collect::<BTreeSet<_>>();
+
+        for op in oplog {
+            match op {
+                MockIoOp::RunDuringBuild(command_line) => {
+                    let Some(node) = self.node_of(command_line) else {
+                        continue;
+                    };
+                    for input in &self.inputs[node] {
+                        let Input::Node(dependency) = *input else {
+                            continue;
+                        };
+                        if executed.contains(&dependency) {
+                            assert!(
+                                finished.contains(&dependency),
+                                "n{node} was started before its input n{dependency} finished"
+                            );
+                        }
+                    }
+                    started.insert(node);
+                }
+                MockIoOp::FinishDuringBuild(command_line) => {
+                    if let Some(node) = self.node_of(command_line) {
+                        assert!(started.contains(&node), "n{node} finished before starting");
+                        finished.insert(node);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// The maximum number of recipe commands that were running at the same time.
+#[must_use]
+pub fn max_parallelism(oplog: &[MockIoOp]) -> usize {
+    let mut running = 0usize;
+    let mut max = 0;
+    for op in oplog {
+        match op {
+            MockIoOp::RunDuringBuild(_) => {
+                running += 1;
+                max = max.max(running);
+            }
+            MockIoOp::FinishDuringBuild(_) => running -= 1,
+            _ => (),
+        }
+    }
+    max
+}
+
+/// Small deterministic random number generator (xorshift64*), so graphs are
+/// reproducible from their seed.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
@@ -97,18 +97,39 @@ macro_rules! error_case {
 
 success_case!(map);
 success_case!(match_expr);
+success_case!(match_regex);
 success_case!(flatten);
 success_case!(join);
+success_case!(path_join);
 success_case!(split);
 success_case!(discard);
 success_case!(filter);
 success_case!(write);
 success_case!(copy);
 success_case!(read);
+success_case!(hash);
 success_case!(env);
 success_case!(string_interp);
+success_case!(arith);
+success_case!(conditions);
 success_case!(dedup);
+success_case!(priority);
+success_case!(exclude);
+success_case!(glob);
+success_case!(glob_out);
+success_case!(profile);
 
 error_case!(ambiguous_build_recipe);
 error_case!(ambiguous_path_resolution);
 error_case!(capture_group_out_of_bounds);
+error_case!(unknown_attribute);
+error_case!(cache_in_recipe);
+error_case!(profile_unknown_variable);
+error_case!(always_run_on_task);
+error_case!(invalid_retry);
+error_case!(invalid_timeout);
+error_case!(unknown_pool);
+error_case!(strict_undefined_identifier);
+error_case!(missing_input);
+error_case!(expected_integer);
+error_case!(unexpected_volatile);
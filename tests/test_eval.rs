@@ -1,6 +1,8 @@
 use tests::mock_io::*;
 use werk_parser::parser::{parse, Input};
-use werk_runner::{eval, RootScope, ShellCommandLine};
+use werk_parser::parser::{Span, Spanned as _};
+use werk_runner::{eval, ImportSources, RootScope, ShellCommandLine, Value};
+use werk_util::{Diagnostic as _, Symbol};
 use winnow::Parser as _;
 
 #[test]
@@ -76,6 +78,19 @@ fn command_argument_splitting() {
         }
     );
 
+    // A join separator pastes the list into the current argument.
+    let expr = parse
+        .parse(Input::new(r#""a -Wl,{abc:join=","} --x={abc,*}""#))
+        .unwrap();
+    let cmd = eval::eval_shell_command(&RootScope::new(&workspace), &expr).unwrap();
+    assert_eq!(
+        cmd.value,
+        ShellCommandLine {
+            program: program_path("a"),
+            arguments: vec![String::from("-Wl,a,b,c"), String::from("--x=a,b,c")],
+        }
+    );
+
     // ... or the argument is quoted.
     let expr = parse.parse(Input::new(r#""a \"{abc*}\"""#)).unwrap();
     let cmd = eval::eval_shell_command(&RootScope::new(&workspace), &expr).unwrap();
@@ -120,3 +135,232 @@ fn command_argument_splitting() {
         }
     );
 }
+
+#[test]
+fn imports() {
+    let mut test = Test::new(
+        r#"
+import "lib/lib.werk" as lib
+let flags = lib.cflags
+let nested = lib.util.name
+task build {
+    build "lib.hello"
+}
+"#,
+    )
+    .unwrap();
+    test.set_workspace_file(
+        &["lib", "lib.werk"],
+        r#"
+import "util.werk" as util
+let cflags = ["-Wall", util.name]
+task hello {
+    build "other"
+}
+task other {}
+"#,
+    )
+    .unwrap();
+    test.set_workspace_file(&["lib", "util.werk"], r#"let name = "util""#)
+        .unwrap();
+
+    let sources = ImportSources::load(&*test.io, &test.ast, &test.workspace_dir).unwrap();
+    assert_eq!(sources.paths().count(), 2);
+    test.ast.imports = sources.parse().unwrap();
+    let workspace = test.create_workspace(&[]).unwrap();
+
+    let globals = &workspace.manifest.globals;
+    assert_eq!(
+        globals.get(&Symbol::new("flags")).unwrap().value.value,
+        Value::List(vec![Value::from("-Wall"), Value::from("util")])
+    );
+    assert_eq!(
+        globals.get(&Symbol::new("nested")).unwrap().value.value,
+        Value::from("util")
+    );
+    assert!(globals.contains_key(&Symbol::new("lib.util.name")));
+
+    let hello = workspace.manifest.match_task_recipe("lib.hello").unwrap();
+    assert_eq!(hello.namespace, Some(Symbol::new("lib")));
+    assert!(workspace.manifest.match_task_recipe("lib.other").is_some());
+    assert!(workspace.manifest.match_task_recipe("hello").is_none());
+}
+
+#[test]
+fn standalone_expression() {
+    let test = Test::new(r#"let srcs = ["a.c", "b.c"]"#).unwrap();
+    let workspace = test.create_workspace(&[]).unwrap();
+    let scope = RootScope::new(&workspace);
+
+    let expr = werk_parser::parse_expr(r#" srcs | map "{:.c=.o}" | join " " "#).unwrap();
+    let value = eval::eval_chain(&scope, &expr).unwrap();
+    assert_eq!(value.value, Value::from("a.o b.o"));
+
+    // Trailing garbage is an error.
+    assert!(werk_parser::parse_expr(r#""a" "b""#).is_err());
+
+    let expr = werk_parser::parse_expr("missing").unwrap();
+    assert!(eval::eval_chain(&scope, &expr).is_err());
+}
+
+#[test]
+fn is_overridden() {
+    let mut test = Test::new(
+        r#"
+import "lib.werk" as lib
+let profile = "debug"
+let profile-overridden = is-overridden profile
+let opt-overridden = is-overridden lib.opt
+let lib-overridden = lib.overridden
+"#,
+    )
+    .unwrap();
+    test.set_workspace_file(
+        &["lib.werk"],
+        r#"
+let opt = "2"
+let overridden = is-overridden opt
+"#,
+    )
+    .unwrap();
+    let sources = ImportSources::load(&*test.io, &test.ast, &test.workspace_dir).unwrap();
+    test.ast.imports = sources.parse().unwrap();
+
+    let names = ["profile-overridden", "opt-overridden", "lib-overridden"];
+    let workspace = test.create_workspace(&[]).unwrap();
+    for name in names {
+        assert_eq!(
+            workspace.manifest.globals[&Symbol::new(name)].value.value,
+            Value::from("0")
+        );
+    }
+
+    let workspace = test
+        .create_workspace(&[("profile", "release"), ("lib.opt", "3")])
+        .unwrap();
+    for name in names {
+        assert_eq!(
+            workspace.manifest.globals[&Symbol::new(name)].value.value,
+            Value::from("1")
+        );
+    }
+}
+
+#[test]
+fn strict() {
+    let source = r#"
+import "lib.werk" as lib
+let cflags = [lib.opt]
+let unused = "1"
+task build {
+    let shadowed = "a"
+    let shadowed = "{shadowed}b"
+    let local = "c"
+    run "cc {cflags*} {shadowed}"
+}
+"#;
+    let mut test = Test::new(source).unwrap();
+    test.set_workspace_file(
+        &["lib.werk"],
+        r#"
+let opt = "-O2"
+let exported = "1"
+"#,
+    )
+    .unwrap();
+    let sources = ImportSources::load(&*test.io, &test.ast, &test.workspace_dir).unwrap();
+    test.ast.imports = sources.parse().unwrap();
+
+    let mut settings = test.workspace_settings();
+    settings.strict = true;
+    let workspace = test.create_workspace_with_settings(&settings).unwrap();
+
+    // Shadowed variables that are used, and globals of imported Werkfiles,
+    // are not reported.
+    let warnings = workspace
+        .warnings
+        .iter()
+        .map(|warning| (warning.to_string(), warning.span()))
+        .collect::<Vec<_>>();
+    let ident_span = |name: &str| {
+        let start = source.find(&format!("let {name} ")).unwrap() + "let ".len();
+        Span::from(start..start + name.len())
+    };
+    assert_eq!(
+        warnings,
+        [
+            (
+                String::from("unused variable `unused`"),
+                ident_span("unused")
+            ),
+            (String::from("unused variable `local`"), ident_span("local")),
+        ]
+    );
+}
+
+#[test]
+fn check_warnings() {
+    let test = Test::new(
+        r#"
+let srcs = ["a.c", "b.c"]
+let flags = "debug" | match {
+    "%" => "-O2"
+    "debug" => "-g"
+    [] => ""
+}
+let opt = "-O2" | match {
+    "-O2" if "{srcs*}" == "" => ""
+    "-O2" => "-O3"
+    "-O2" => "-O1"
+}
+build "%.o" {
+    run "cc {srcs} {srcs*} {srcs,*} {flags} {opt} <in>"
+}
+build "gen/%.o" {
+    run "true"
+}
+build "gen//%.c" {
+    run "true"
+}
+build "gen/%" {
+    priority 1
+    run "true"
+}
+"#,
+    )
+    .unwrap();
+
+    let mut settings = test.workspace_settings();
+    settings.strict = true;
+    let workspace = test.create_workspace_with_settings(&settings).unwrap();
+    let ids = workspace
+        .warnings
+        .iter()
+        .map(|warning| (warning.id(), warning.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        ids,
+        [
+            (2, String::from("unreachable match arm")),
+            (2, String::from("unreachable match arm")),
+            (
+                7,
+                String::from("`match` has no catch-all arm; values that no arm matches are passed through unchanged")
+            ),
+            (
+                3,
+                String::from(
+                    "list `srcs` is interpolated without a separator; only its first item is used"
+                )
+            ),
+            (
+                5,
+                String::from("build recipe is never used; a recipe with a higher priority matches all of its targets")
+            ),
+            (
+                4,
+                String::from("build recipe pattern can never match a target path")
+            ),
+        ]
+    );
+}
@@ -1,9 +1,14 @@
+use std::time::Duration;
+
 use macro_rules_attribute::apply;
 use tests::mock_io;
 
 use mock_io::*;
 use werk_fs::{Absolute, Path};
-use werk_runner::{BuildStatus, Outdatedness, Reason, ShellCommandLine, TaskId};
+use werk_runner::{
+    BuildStatus, Echo, Outdatedness, PlanNodeKind, Reason, ReasonValue, RunCommand,
+    ShellCommandLine, TaskId,
+};
 use werk_util::Symbol;
 
 static WERK: &str = r#"
@@ -451,6 +456,95 @@ async fn test_outdated_define() -> anyhow::Result<()> {
     Ok(())
 }
 
+static WERK_PROFILE: &str = r#"
+let cflags = "-O0"
+
+profile release {
+    let cflags = "-O2"
+}
+
+build "output" {
+    run {
+        write cflags to "{out}"
+    }
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_outdated_profile() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_PROFILE)?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("output")?)
+        .await
+        .map_err(anyhow_msg)?;
+    workspace.finalize().await?;
+    std::mem::drop(runner);
+    {
+        let fs = test.io.filesystem.lock();
+        let (_, data) = read_fs(&fs, &test.output_path(["output"]))?;
+        assert_eq!(data, b"-O0");
+    }
+
+    // Selecting the profile replaces the variable, so the output is outdated.
+    let mut settings = test.workspace_settings();
+    settings.profile("release");
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let status = runner
+        .build_file(Path::new("output")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(
+        status,
+        BuildStatus::Complete(
+            TaskId::build(Absolute::try_from("/output").unwrap()),
+            Outdatedness::new([Reason::GlobalChanged(Symbol::from("cflags"))])
+        )
+    );
+    workspace.finalize().await?;
+    std::mem::drop(runner);
+    {
+        let fs = test.io.filesystem.lock();
+        let (_, data) = read_fs(&fs, &test.output_path(["output"]))?;
+        assert_eq!(data, b"-O2");
+    }
+
+    // Building with the same profile again does nothing.
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let status = runner
+        .build_file(Path::new("output")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(
+        status,
+        BuildStatus::Complete(
+            TaskId::build(Absolute::try_from("/output").unwrap()),
+            Outdatedness::unchanged()
+        )
+    );
+
+    // Unknown profiles are rejected.
+    settings.profile("asan");
+    let Err(err) = test.create_workspace_with_settings(&settings) else {
+        panic!("expected an error");
+    };
+    assert_eq!(
+        err.error,
+        werk_runner::Error::NoSuchProfile(String::from("asan"))
+    );
+
+    Ok(())
+}
+
 #[apply(smol_macros::test)]
 async fn test_outdated_global_constant() -> anyhow::Result<()> {
     _ = tracing_subscriber::fmt::try_init();
@@ -496,3 +590,1618 @@ async fn test_outdated_global_constant() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+static WERK_PLAN: &str = r#"
+let profile = env "PROFILE"
+let cc = which "clang"
+
+build "%.o" {
+    from "{%}.c"
+    run "{cc} -c -D{profile} <in> -o <out>"
+}
+
+build "program" {
+    from ["a.o", "b.o"]
+    run "{cc} <in*> -o <out>"
+}
+
+task build {
+    build "program"
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_plan() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_PLAN)?;
+    test.set_workspace_file(&["a.c"], "void foo() {}").unwrap();
+    test.set_workspace_file(&["b.c"], "int main() { return 0; }\n")
+        .unwrap();
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+
+    let plan = runner.plan("build").map_err(anyhow_msg)?;
+    assert_eq!(plan.root, TaskId::command("build"));
+
+    // Dependencies come before dependents.
+    let order = plan
+        .iter()
+        .map(|node| node.task_id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(order, ["/a.c", "/a.o", "/b.c", "/b.o", "/program", "build"]);
+
+    let a_c = TaskId::try_build("/a.c").unwrap();
+    let a_o = TaskId::try_build("/a.o").unwrap();
+    let b_o = TaskId::try_build("/b.o").unwrap();
+    let program = TaskId::try_build("/program").unwrap();
+    assert!(plan.edges().any(|edge| edge == (a_o, a_c)));
+    assert_eq!(plan.get(program).unwrap().dependencies, [a_o, b_o]);
+    assert_eq!(
+        plan.get(TaskId::command("build")).unwrap().dependencies,
+        [program]
+    );
+
+    assert_eq!(
+        plan.get(a_c).unwrap().kind,
+        PlanNodeKind::Exists(default_mtime())
+    );
+    let a_o_node = plan.get(a_o).unwrap();
+    assert_eq!(a_o_node.kind, PlanNodeKind::Build);
+    assert_eq!(
+        a_o_node.commands,
+        [RunCommand::Shell(ShellCommandLine {
+            program: program_path("clang"),
+            arguments: vec![
+                "-c".into(),
+                "-Ddebug".into(),
+                test.workspace_path_str(["a.c"]),
+                "-o".into(),
+                test.output_path_str(["a.o"]),
+            ],
+        })]
+    );
+    assert!(plan
+        .get(TaskId::command("build"))
+        .unwrap()
+        .fingerprint
+        .is_none());
+    let a_o_fingerprint = a_o_node.fingerprint.unwrap();
+
+    // Planning does not run anything.
+    assert!(!test.did_run_during_build(&ShellCommandLine {
+        program: program_path("clang"),
+        arguments: vec![
+            test.output_path_str(["a.o"]),
+            test.output_path_str(["b.o"]),
+            "-o".into(),
+            test.output_path_str(["program"]),
+        ],
+    }));
+    assert!(!test.io.contains_file(test.output_path(["a.o"])));
+
+    // Building runs exactly the planned commands.
+    runner.run_command("build").await.map_err(anyhow_msg)?;
+    for node in &plan {
+        for command in &node.commands {
+            let RunCommand::Shell(command_line) = command else {
+                panic!("unexpected command: {command}");
+            };
+            assert!(test.did_run_during_build(command_line), "{command_line}");
+        }
+    }
+
+    std::mem::drop(runner);
+    std::mem::drop(workspace);
+
+    // Fingerprints are stable between runs, but change with the environment.
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let plan = runner.plan("/a.o").map_err(anyhow_msg)?;
+    assert_eq!(plan.get(a_o).unwrap().fingerprint, Some(a_o_fingerprint));
+    std::mem::drop(runner);
+    std::mem::drop(workspace);
+
+    test.io.set_env("PROFILE", "release");
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let plan = runner.plan("/a.o").map_err(anyhow_msg)?;
+    assert_ne!(plan.get(a_o).unwrap().fingerprint, Some(a_o_fingerprint));
+
+    Ok(())
+}
+
+static WERK_GLOB_TARGET: &str = r#"
+let cc = which "clang"
+
+build "assets/%.ktx" {
+    from "assets/{%}.png"
+    run "{cc} <in> -o <out>"
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_build_glob() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_GLOB_TARGET)?;
+    test.set_workspace_file(&["assets", "a.png"], "").unwrap();
+    test.set_workspace_file(&["assets", "b.png"], "").unwrap();
+    test.set_workspace_file(&["assets", "c.jpg"], "").unwrap();
+    test.set_workspace_file(&["other", "d.png"], "").unwrap();
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+
+    // Only outputs whose inputs exist are included.
+    let targets = runner
+        .expand_target_glob("assets/*.ktx")
+        .map_err(anyhow_msg)?;
+    let targets = targets.iter().map(|path| path.as_str()).collect::<Vec<_>>();
+    assert_eq!(targets, ["/assets/a.ktx", "/assets/b.ktx"]);
+    let Err(err) = runner.expand_target_glob("other/*.ktx") else {
+        panic!("expected error")
+    };
+    assert_eq!(
+        err.error,
+        werk_runner::Error::NoRuleToBuildTarget("/other/*.ktx".into())
+    );
+
+    let statuses = runner
+        .build_glob("assets/*.ktx")
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(statuses.len(), 2);
+    for name in ["a", "b"] {
+        assert!(test.did_run_during_build(&ShellCommandLine {
+            program: program_path("clang"),
+            arguments: vec![
+                test.workspace_path_str(["assets", &format!("{name}.png")]),
+                "-o".into(),
+                test.output_path_str(["assets", &format!("{name}.ktx")]),
+            ],
+        }));
+    }
+
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_consulted_files() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_GLOB_TARGET)?;
+    test.set_workspace_file(&["assets", "a.png"], "").unwrap();
+    test.set_workspace_file(&["assets", "b.png"], "").unwrap();
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_or_run("assets/a.ktx")
+        .await
+        .map_err(anyhow_msg)?;
+
+    // Only the inputs of recipes that ran are consulted.
+    let consulted = workspace
+        .consulted_files()
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(consulted, [test.workspace_path_str(["assets", "a.png"])]);
+
+    Ok(())
+}
+
+static WERK_LIST_OUTPUTS: &str = r#"
+let cc = which "clang"
+
+build "assets/%.ktx" {
+    from "assets/{%}.png"
+    run "{cc} <in> -o <out>"
+}
+
+build "gen/version.h" {
+    run "{cc} -o <out>"
+}
+
+build "gen/%.h.gz" {
+    from "gen/{%}.h"
+    run "{cc} <in> -o <out>"
+}
+
+build "%.o" {
+    from "{%}.c"
+    run "{cc} -c <in> -o <out>"
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_list_outputs() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_LIST_OUTPUTS)?;
+    test.set_workspace_file(&["assets", "b.png"], "").unwrap();
+    test.set_workspace_file(&["assets", "a.png"], "").unwrap();
+    test.set_workspace_file(&["assets", "c.jpg"], "").unwrap();
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+
+    // Outputs are also inferred from the targets of other recipes.
+    let outputs = runner
+        .list_outputs()
+        .into_iter()
+        .map(|(recipe, outputs)| {
+            (
+                recipe.pattern.string.as_str(),
+                outputs
+                    .iter()
+                    .map(|path| path.as_str().to_owned())
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        outputs,
+        [
+            (
+                "/assets/%.ktx",
+                vec!["/assets/a.ktx".into(), "/assets/b.ktx".into()]
+            ),
+            ("/gen/version.h", vec!["/gen/version.h".into()]),
+            ("/gen/%.h.gz", vec!["/gen/version.h.gz".into()]),
+            ("/%.o", vec![]),
+        ]
+    );
+
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_outdated_forced() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_GLOBAL)?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("output")?)
+        .await
+        .map_err(anyhow_msg)?;
+    workspace.finalize().await?;
+    std::mem::drop(runner);
+
+    // Up to date, but forced.
+    let mut settings = test.workspace_settings();
+    settings.force_target(Absolute::try_from("/output")?);
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let status = runner
+        .build_file(Path::new("output")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(
+        status,
+        BuildStatus::Complete(
+            TaskId::build(Absolute::try_from("/output").unwrap()),
+            Outdatedness::new([Reason::Forced])
+        )
+    );
+    workspace.finalize().await?;
+    std::mem::drop(runner);
+
+    // Forcing a different target does not affect this one.
+    let mut settings = test.workspace_settings();
+    settings.force_target(Absolute::try_from("/other")?);
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let status = runner
+        .build_file(Path::new("output")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(
+        status,
+        BuildStatus::Complete(
+            TaskId::build(Absolute::try_from("/output").unwrap()),
+            Outdatedness::unchanged()
+        )
+    );
+
+    Ok(())
+}
+
+static WERK_VARIABLES: &str = r#"
+let greeting = "hello"
+
+build "message.txt" {
+    let message = "{greeting} world"
+    run {
+        write message to "{out}"
+    }
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_evaluated_variables() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_VARIABLES)?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("message.txt")?)
+        .await
+        .map_err(anyhow_msg)?;
+    std::mem::drop(runner);
+
+    assert!(test.render.did_see(&MockRenderEvent::EvaluateVariable(
+        None,
+        "greeting".into(),
+        "hello".into()
+    )));
+    let task_id = TaskId::build(Absolute::try_from("/message.txt")?);
+    assert!(test.render.did_see(&MockRenderEvent::EvaluateVariable(
+        Some(task_id),
+        "message".into(),
+        "hello world".into()
+    )));
+
+    Ok(())
+}
+
+static WERK_ATTRIBUTES: &str = r#"
+let cc = which "clang"
+
+#[always-run]
+#[restat]
+build "stamp" {
+    #[silent]
+    run "{cc} --stamp"
+    #[print-commands]
+    run "{cc} --version"
+    run {
+        write "stamp" to "{out}"
+    }
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_attributes() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_ATTRIBUTES)?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("stamp")?)
+        .await
+        .map_err(anyhow_msg)?;
+    workspace.finalize().await?;
+    std::mem::drop(runner);
+
+    let stamp = TaskId::build(Absolute::try_from("/stamp")?);
+    let command_line = ShellCommandLine {
+        program: program_path("clang"),
+        arguments: vec!["--stamp".into()],
+    };
+    assert!(test.render.did_see(&MockRenderEvent::WillExecute(
+        stamp,
+        command_line,
+        0,
+        3,
+        Echo::Never
+    )));
+    let command_line = ShellCommandLine {
+        program: program_path("clang"),
+        arguments: vec!["--version".into()],
+    };
+    assert!(test.render.did_see(&MockRenderEvent::WillExecute(
+        stamp,
+        command_line,
+        1,
+        3,
+        Echo::Always
+    )));
+
+    // Nothing changed, but the recipe always runs. The output is identical, so
+    // dependents are not rebuilt.
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let status = runner
+        .build_file(Path::new("stamp")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(
+        status,
+        BuildStatus::Unchanged(stamp, Outdatedness::new([Reason::AlwaysRun]))
+    );
+
+    Ok(())
+}
+
+static WERK_RESTAT: &str = r#"
+#[always-run]
+#[restat]
+build "restat.h" {
+    run {
+        write "header" to "{out}"
+    }
+}
+
+#[always-run]
+build "plain.h" {
+    run {
+        write "header" to "{out}"
+    }
+}
+
+build "%.o" {
+    from "{%}.h"
+    run {
+        write "object" to "{out}"
+    }
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_restat() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_RESTAT)?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    for target in ["restat.o", "plain.o"] {
+        runner
+            .build_file(Path::new(target)?)
+            .await
+            .map_err(anyhow_msg)?;
+    }
+    workspace.finalize().await?;
+    std::mem::drop(runner);
+
+    // Both headers are rebuilt with identical contents, but only the recipe
+    // with `#[restat]` keeps its dependent from being rebuilt.
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let restat_o = TaskId::build(Absolute::try_from("/restat.o")?);
+    let status = runner
+        .build_file(Path::new("restat.o")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(
+        status,
+        BuildStatus::Complete(restat_o, Outdatedness::unchanged())
+    );
+
+    let plain_h = TaskId::build(Absolute::try_from("/plain.h")?);
+    let plain_o = TaskId::build(Absolute::try_from("/plain.o")?);
+    let status = runner
+        .build_file(Path::new("plain.o")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(
+        status,
+        BuildStatus::Complete(plain_o, Outdatedness::new([Reason::Rebuilt(plain_h)]))
+    );
+
+    Ok(())
+}
+
+static WERK_VOLATILE: &str = r#"
+#[uncached]
+build "version.h" {
+    run {
+        write "version" to "{out}"
+    }
+}
+
+build "main.o" {
+    from "version.h" | volatile
+    run {
+        write "main" to "{out}"
+    }
+}
+
+#[restat]
+build "version.o" {
+    from "version.h"
+    run {
+        write "version" to "{out}"
+    }
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_volatile() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_VOLATILE)?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("main.o")?)
+        .await
+        .map_err(anyhow_msg)?;
+    runner
+        .build_file(Path::new("version.o")?)
+        .await
+        .map_err(anyhow_msg)?;
+    workspace.finalize().await?;
+    std::mem::drop(runner);
+
+    // The uncached recipe runs every time, and its dependents are always
+    // rebuilt, unless they depend on it through a volatile input.
+    let version_h = TaskId::build(Absolute::try_from("/version.h")?);
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let status = runner
+        .build_file(Path::new("version.h")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(
+        status,
+        BuildStatus::Complete(version_h, Outdatedness::new([Reason::Uncached]))
+    );
+
+    let main_o = TaskId::build(Absolute::try_from("/main.o")?);
+    let status = runner
+        .build_file(Path::new("main.o")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(
+        status,
+        BuildStatus::Complete(main_o, Outdatedness::unchanged())
+    );
+    let BuildStatus::Complete(_, outdatedness) = status else {
+        unreachable!()
+    };
+    assert_eq!(
+        outdatedness.ignored,
+        [Reason::Rebuilt(version_h)].into_iter().collect()
+    );
+
+    let version_o = TaskId::build(Absolute::try_from("/version.o")?);
+    let status = runner
+        .build_file(Path::new("version.o")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(
+        status,
+        BuildStatus::Unchanged(version_o, Outdatedness::new([Reason::Rebuilt(version_h)]))
+    );
+
+    Ok(())
+}
+
+static WERK_INTERACTIVE: &str = r#"
+let cc = which "clang"
+
+task setup {
+    #[interactive]
+    run "{cc} --setup"
+    run "{cc} --version"
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_interactive() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_INTERACTIVE)?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("setup").await.map_err(anyhow_msg)?;
+    std::mem::drop(runner);
+
+    // Only the interactive command suspends the renderer.
+    let setup = TaskId::command("setup");
+    let log = test.render.log.lock();
+    let suspended = log
+        .iter()
+        .filter(|event| {
+            matches!(
+                event,
+                MockRenderEvent::Suspend(_) | MockRenderEvent::Resume(_)
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        suspended,
+        [
+            &MockRenderEvent::Suspend(setup),
+            &MockRenderEvent::Resume(setup)
+        ]
+    );
+    assert!(test.did_run_during_build(&ShellCommandLine {
+        program: program_path("clang"),
+        arguments: vec!["--setup".into()],
+    }));
+
+    Ok(())
+}
+
+static WERK_RETRY: &str = r#"
+let fetch = which "fetch"
+
+build "flaky" {
+    #[retry = 2]
+    run "{fetch}"
+}
+
+#[retry = 1]
+build "broken" {
+    run "{fetch}"
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_retry() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_RETRY)?;
+    // Fails twice before succeeding.
+    let mut failures = 2;
+    test.io
+        .set_program("fetch", program_path("fetch"), move |_, _, _| {
+            if failures > 0 {
+                failures -= 1;
+                Ok(failed_program_output())
+            } else {
+                Ok(empty_program_output())
+            }
+        });
+
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("flaky")?)
+        .await
+        .map_err(anyhow_msg)?;
+
+    let flaky = TaskId::build(Absolute::try_from("/flaky")?);
+    let command_line = ShellCommandLine {
+        program: program_path("fetch"),
+        arguments: vec![],
+    };
+    assert!(test.render.did_see(&MockRenderEvent::WillRetry(
+        flaky,
+        command_line.clone(),
+        1,
+        3
+    )));
+    assert!(test
+        .render
+        .did_see(&MockRenderEvent::WillRetry(flaky, command_line, 2, 3)));
+
+    // Fails twice, but only one retry is allowed.
+    test.io
+        .set_program("fetch", program_path("fetch"), |_, _, _| {
+            Ok(failed_program_output())
+        });
+    let err = runner.build_file(Path::new("broken")?).await.unwrap_err();
+    assert!(matches!(err.error, werk_runner::Error::CommandFailed(..)));
+    let num_runs = test
+        .io
+        .oplog
+        .lock()
+        .iter()
+        .filter(
+            |op| matches!(op, MockIoOp::RunDuringBuild(c) if c.program == program_path("fetch")),
+        )
+        .count();
+    assert_eq!(num_runs, 5);
+
+    Ok(())
+}
+
+static WERK_TIMEOUT: &str = r#"
+let sleep = which "sleep"
+
+build "slow" {
+    timeout "50ms"
+    run "{sleep} slow"
+}
+
+task fast {
+    run {
+        timeout "10s"
+        shell "{sleep} fast"
+    }
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_timeout() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_TIMEOUT)?;
+    test.io
+        .set_program("sleep", program_path("sleep"), |_, _, _| {
+            Ok(empty_program_output())
+        });
+    test.io.set_command_duration(|command_line| {
+        if command_line.arguments[0] == "slow" {
+            Duration::from_secs(10)
+        } else {
+            Duration::from_millis(1)
+        }
+    });
+
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("fast").await.map_err(anyhow_msg)?;
+
+    let err = runner.build_file(Path::new("slow")?).await.unwrap_err();
+    let werk_runner::Error::CommandTimedOut(elapsed, _) = err.error else {
+        panic!("expected timeout, got {err}");
+    };
+    assert!(elapsed >= Duration::from_millis(50));
+    assert!(elapsed < Duration::from_secs(10));
+
+    let slow = ShellCommandLine {
+        program: program_path("sleep"),
+        arguments: vec!["slow".into()],
+    };
+    let oplog = test.io.oplog.lock();
+    assert!(oplog.contains(&MockIoOp::KillDuringBuild(slow.clone())));
+    assert!(!oplog.contains(&MockIoOp::FinishDuringBuild(slow)));
+
+    Ok(())
+}
+
+static WERK_REDIRECT: &str = r#"
+let gzip = which "gzip"
+
+build "%.gz" {
+    from "{%}"
+    run "{gzip} -c" < "{in}" > "{out}"
+}
+
+build "missing.gz" {
+    run {
+        shell "{gzip} -c" < "missing"
+    }
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_redirect() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_REDIRECT)?;
+    test.io
+        .set_program("gzip", program_path("gzip"), |_, _, _| {
+            Ok(std::process::Output {
+                stdout: b"compressed".to_vec(),
+                ..empty_program_output()
+            })
+        });
+    test.set_workspace_file(&["data.txt"], "data")?;
+
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("data.txt.gz")?)
+        .await
+        .map_err(anyhow_msg)?;
+
+    // The command line does not include the redirections.
+    assert!(test.did_run_during_build(&ShellCommandLine {
+        program: program_path("gzip"),
+        arguments: vec!["-c".into()],
+    }));
+    {
+        let fs = test.io.filesystem.lock();
+        let (_, data) = read_fs(&fs, &test.output_path(["data.txt.gz"]))?;
+        assert_eq!(data, b"compressed");
+    }
+
+    // Standard input must exist.
+    let err = runner
+        .build_file(Path::new("missing.gz")?)
+        .await
+        .unwrap_err();
+    let werk_runner::Error::Io(err) = err.error else {
+        panic!("expected I/O error, got {err}");
+    };
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+    Ok(())
+}
+
+static WERK_PIPELINE: &str = r#"
+let sort = which "sort"
+let uniq = which "uniq"
+
+build "words.txt" {
+    from "input.txt"
+    run "{sort}" < "{in}" | "{uniq} -c" > "{out}"
+}
+
+build "failed.txt" {
+    from "input.txt"
+    run "{sort} --fail" < "{in}" | "{uniq} -c" > "{out}"
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_pipeline() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_PIPELINE)?;
+    test.io
+        .set_program("sort", program_path("sort"), |cmd, _, _| {
+            if cmd.arguments.is_empty() {
+                Ok(std::process::Output {
+                    stdout: b"sorted".to_vec(),
+                    ..empty_program_output()
+                })
+            } else {
+                Ok(failed_program_output())
+            }
+        });
+    test.io
+        .set_program("uniq", program_path("uniq"), |_, _, _| {
+            Ok(std::process::Output {
+                stdout: b"counted".to_vec(),
+                ..empty_program_output()
+            })
+        });
+    test.set_workspace_file(&["input.txt"], "b\na\nb")?;
+
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("words.txt")?)
+        .await
+        .map_err(anyhow_msg)?;
+
+    assert!(test.did_run_during_build(&ShellCommandLine {
+        program: program_path("sort"),
+        arguments: vec![],
+    }));
+    assert!(test.did_run_during_build(&ShellCommandLine {
+        program: program_path("uniq"),
+        arguments: vec!["-c".into()],
+    }));
+    {
+        // Only the output of the last command is written to the file.
+        let fs = test.io.filesystem.lock();
+        let (_, data) = read_fs(&fs, &test.output_path(["words.txt"]))?;
+        assert_eq!(data, b"counted");
+    }
+
+    // The pipeline fails when any command fails, even if the last one
+    // succeeds.
+    let err = runner
+        .build_file(Path::new("failed.txt")?)
+        .await
+        .unwrap_err();
+    let werk_runner::Error::CommandFailed(_, Some(location)) = err.error else {
+        panic!("expected command failure, got {err}");
+    };
+
+    // The error points at the `run` statement that produced the command.
+    let run_stmt = r#"run "{sort} --fail" < "{in}" | "{uniq} -c" > "{out}""#;
+    let run_start = WERK_PIPELINE.find(run_stmt).unwrap();
+    assert_eq!(
+        location.stmt.start.0 as usize..location.stmt.end.0 as usize,
+        run_start..run_start + run_stmt.len()
+    );
+    assert_eq!(
+        location.recipe.start.0 as usize,
+        WERK_PIPELINE.find(r#""failed.txt""#).unwrap()
+    );
+    assert!(location.evaluated.contains("--fail"));
+
+    Ok(())
+}
+
+static WERK_RUN_SHELL: &str = r#"
+config shell = "bash -c"
+
+let name = "world"
+
+task greet {
+    run-shell "echo {name} | tr a-z A-Z"
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_run_shell() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_RUN_SHELL)?;
+    test.io
+        .set_program("bash", program_path("bash"), |_, _, _| {
+            Ok(empty_program_output())
+        });
+
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("greet").await.map_err(anyhow_msg)?;
+
+    // The script is passed as a single argument to the interpreter.
+    assert!(test.did_run_during_build(&ShellCommandLine {
+        program: program_path("bash"),
+        arguments: vec!["-c".into(), "echo world | tr a-z A-Z".into()],
+    }));
+
+    Ok(())
+}
+
+static WERK_SAME_COMMANDS: &str = r#"
+let cc = which "clang"
+
+build "gen.h" {
+    run "{cc} --generate"
+}
+
+build "gen.c" {
+    run "{cc} --generate"
+}
+
+task build {
+    build ["gen.h", "gen.c"]
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_same_commands() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_SAME_COMMANDS)?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+
+    let gen_h = TaskId::try_build("/gen.h").unwrap();
+    let gen_c = TaskId::try_build("/gen.c").unwrap();
+    let plan = runner.plan("build").map_err(anyhow_msg)?;
+    assert_eq!(plan.get(gen_h).unwrap().same_commands_as, None);
+    assert_eq!(plan.get(gen_c).unwrap().same_commands_as, Some(gen_h));
+
+    runner.build_or_run("build").await.map_err(anyhow_msg)?;
+
+    // The command is only executed once.
+    let command_line = ShellCommandLine {
+        program: program_path("clang"),
+        arguments: vec!["--generate".into()],
+    };
+    let num_runs = test
+        .io
+        .oplog
+        .lock()
+        .iter()
+        .filter(|op| matches!(op, MockIoOp::RunDuringBuild(c) if *c == command_line))
+        .count();
+    assert_eq!(num_runs, 1);
+
+    // The recipe that did not run the command is counted as a cache hit.
+    let summary = runner.summary();
+    assert_eq!(summary.executed, 2);
+    assert_eq!(summary.up_to_date, 0);
+    assert_eq!(summary.cache_hits, 1);
+    assert_eq!(summary.slowest_commands.len(), 1);
+    assert_eq!(
+        summary.slowest_commands[0].command,
+        command_line.to_string()
+    );
+
+    Ok(())
+}
+
+static WERK_SHELL_OUTPUT: &str = r#"
+let version = shell "describe"
+let write = which "write"
+
+build "version.txt" {
+    run "{write} {version} <out>"
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_outdated_commands_changed() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    fn describe(
+        version: &'static str,
+    ) -> impl FnMut(&ShellCommandLine, &mut MockDir, &werk_runner::Env) -> ProgramResult {
+        move |_cmd, _fs, _env| {
+            Ok(std::process::Output {
+                status: Default::default(),
+                stdout: version.as_bytes().to_vec(),
+                stderr: Default::default(),
+            })
+        }
+    }
+
+    let test = Test::new(WERK_SHELL_OUTPUT)?;
+    test.io
+        .set_program("describe", program_path("describe"), describe("v1"));
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let status = runner
+        .build_file(Path::new("version.txt")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(
+        status,
+        BuildStatus::Complete(
+            TaskId::try_build("/version.txt").unwrap(),
+            Outdatedness::missing(Absolute::try_from("/version.txt")?)
+        )
+    );
+    workspace.finalize().await.unwrap();
+    std::mem::drop(runner);
+
+    // The output of the `shell` expression is not tracked by anything else,
+    // but it changes the command line.
+    test.io
+        .set_program("describe", program_path("describe"), describe("v2"));
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let status = runner
+        .build_file(Path::new("version.txt")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(
+        status,
+        BuildStatus::Complete(
+            TaskId::try_build("/version.txt").unwrap(),
+            Outdatedness::new([Reason::RecipeChanged])
+        )
+    );
+    workspace.finalize().await.unwrap();
+    std::mem::drop(runner);
+
+    // Nothing changed.
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let status = runner
+        .build_file(Path::new("version.txt")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(
+        status,
+        BuildStatus::Complete(
+            TaskId::try_build("/version.txt").unwrap(),
+            Outdatedness::unchanged()
+        )
+    );
+
+    Ok(())
+}
+
+static WERK_CACHED_SHELL: &str = r#"
+#[cache = "1h"]
+#[cache-key = "v1"]
+let version = shell "describe"
+let uncached = shell "describe --uncached"
+"#;
+
+static WERK_CACHED_SHELL_NEW_KEY: &str = r#"
+#[cache = "1h"]
+#[cache-key = "v2"]
+let version = shell "describe"
+let uncached = shell "describe --uncached"
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_cached_shell() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    fn describe(
+        version: &'static str,
+    ) -> impl FnMut(&ShellCommandLine, &mut MockDir, &werk_runner::Env) -> ProgramResult {
+        move |_cmd, _fs, _env| {
+            Ok(std::process::Output {
+                status: Default::default(),
+                stdout: version.as_bytes().to_vec(),
+                stderr: Default::default(),
+            })
+        }
+    }
+
+    fn global(workspace: &werk_runner::Workspace, name: &str) -> String {
+        match workspace.manifest.globals[&Symbol::new(name)].value.value {
+            werk_runner::Value::String(ref value) => value.clone(),
+            ref other => panic!("expected string, got {other:?}"),
+        }
+    }
+
+    let mut test = Test::new(WERK_CACHED_SHELL)?;
+    let num_runs = |test: &Test, arguments: &[&str]| {
+        let command_line = ShellCommandLine {
+            program: program_path("describe"),
+            arguments: arguments.iter().map(|arg| String::from(*arg)).collect(),
+        };
+        test.io
+            .oplog
+            .lock()
+            .iter()
+            .filter(|op| matches!(op, MockIoOp::RunDuringEval(c) if *c == command_line))
+            .count()
+    };
+
+    test.io
+        .set_program("describe", program_path("describe"), describe("v1"));
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    assert_eq!(global(&workspace, "version"), "v1");
+    workspace.finalize().await?;
+    std::mem::drop(workspace);
+
+    // The recorded output is used, even though the command would now produce
+    // something else. Variables without `#[cache]` are evaluated again.
+    test.io
+        .set_program("describe", program_path("describe"), describe("v2"));
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    assert_eq!(global(&workspace, "version"), "v1");
+    assert_eq!(global(&workspace, "uncached"), "v2");
+    workspace.finalize().await?;
+    std::mem::drop(workspace);
+    assert_eq!(num_runs(&test, &[]), 1);
+    assert_eq!(num_runs(&test, &["--uncached"]), 2);
+
+    // Changing the key discards the recorded output.
+    test.reload(WERK_CACHED_SHELL_NEW_KEY)?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    assert_eq!(global(&workspace, "version"), "v2");
+    std::mem::drop(workspace);
+    assert_eq!(num_runs(&test, &[]), 2);
+
+    Ok(())
+}
+
+static WERK_ENV_IN_RECIPE: &str = r#"
+let write = which "write"
+
+build "flags.txt" {
+    let flags = env "CFLAGS"
+    run "{write} flags={flags} <out>"
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_outdated_env_in_recipe() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_ENV_IN_RECIPE)?;
+    test.io.set_env("CFLAGS", "-O2");
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("flags.txt")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert!(test.did_read_env("CFLAGS"));
+    workspace.finalize().await.unwrap();
+    std::mem::drop(runner);
+
+    // Unsetting the variable is also a change.
+    test.io.env.lock().env_remove("CFLAGS");
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let status = runner
+        .build_file(Path::new("flags.txt")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(
+        status,
+        BuildStatus::Complete(
+            TaskId::try_build("/flags.txt").unwrap(),
+            Outdatedness::new([Reason::Env(Symbol::from("CFLAGS"))])
+        )
+    );
+
+    Ok(())
+}
+
+static WERK_GLOB_GLOBAL: &str = r#"
+let write = which "write"
+let sources = glob "*.c"
+
+build "app" {
+    from sources
+    run "{write} {in,*} <out>"
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_outdated_glob_added() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_GLOB_GLOBAL)?;
+    test.set_workspace_file(&["a.c"], "void foo() {}").unwrap();
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("app")?)
+        .await
+        .map_err(anyhow_msg)?;
+    workspace.finalize().await.unwrap();
+    std::mem::drop(runner);
+
+    // Add a source file that is older than the output, so only the result of
+    // the glob changed.
+    test.set_workspace_file(&["b.c"], "int main() { return 0; }\n")
+        .unwrap();
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let status = runner
+        .build_file(Path::new("app")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert_eq!(
+        status,
+        BuildStatus::Complete(
+            TaskId::try_build("/app").unwrap(),
+            Outdatedness::new([Reason::Glob(Symbol::from("/*.c"))])
+        )
+    );
+
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_outdated_reason_details() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_ENV_IN_RECIPE)?;
+    test.io.set_env("CFLAGS", "-O2");
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("flags.txt")?)
+        .await
+        .map_err(anyhow_msg)?;
+    workspace.finalize().await.unwrap();
+    std::mem::drop(runner);
+
+    test.io.set_env("CFLAGS", "-O3");
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let status = runner
+        .build_file(Path::new("flags.txt")?)
+        .await
+        .map_err(anyhow_msg)?;
+    let BuildStatus::Complete(_, outdatedness) = status else {
+        panic!("expected a rebuild, got {status:?}");
+    };
+
+    // The details carry the old and new hashes of the environment variable,
+    // and the location of the recipe that used it.
+    let reason = Reason::Env(Symbol::from("CFLAGS"));
+    assert_eq!(reason.kind(), "env");
+    let detail = outdatedness.detail(&reason).expect("no details");
+    let (Some(ReasonValue::Hash(old)), Some(ReasonValue::Hash(new))) = (detail.old, detail.new)
+    else {
+        panic!("expected old and new hashes, got {detail:?}");
+    };
+    assert_ne!(old, new);
+    let location = detail.location.as_ref().expect("no location");
+    let span = location.span;
+    let recipe = &WERK_ENV_IN_RECIPE[span.start.0 as usize..span.end.0 as usize];
+    assert!(recipe.starts_with("build \"flags.txt\""), "{recipe}");
+    assert_eq!(location.file, "INPUT");
+    assert_eq!(location.range.start.line, 4);
+    assert_eq!(location.range.start.column, 1);
+
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_outdated_location_in_import() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let lib = WERK_ENV_IN_RECIPE.replace("let write", "\nlet write");
+    let mut test = Test::new("import \"lib.werk\" as lib\n")?;
+    test.set_workspace_file(&["lib.werk"], &lib)?;
+    let sources = werk_runner::ImportSources::load(&*test.io, &test.ast, &test.workspace_dir)
+        .map_err(anyhow_msg)?;
+    test.ast.imports = sources.parse().map_err(anyhow_msg)?;
+
+    test.io.set_env("CFLAGS", "-O2");
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("flags.txt")?)
+        .await
+        .map_err(anyhow_msg)?;
+    workspace.finalize().await.unwrap();
+    std::mem::drop(runner);
+
+    test.io.set_env("CFLAGS", "-O3");
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let status = runner
+        .build_file(Path::new("flags.txt")?)
+        .await
+        .map_err(anyhow_msg)?;
+    let BuildStatus::Complete(_, outdatedness) = status else {
+        panic!("expected a rebuild, got {status:?}");
+    };
+
+    // The location is in the imported Werkfile, not the main Werkfile.
+    let detail = outdatedness
+        .detail(&Reason::Env(Symbol::from("CFLAGS")))
+        .expect("no details");
+    let location = detail.location.as_ref().expect("no location");
+    assert_eq!(
+        location.file,
+        native_path(["workspace", "lib.werk"]).display().to_string()
+    );
+    assert_eq!(location.range.start.line, 5);
+    assert_eq!(location.range.start.column, 1);
+
+    Ok(())
+}
+
+static WERK_TMP_DIR: &str = r#"
+let cc = which "clang"
+
+build "main.o" {
+    run "{cc} -c -o {tmp}/main.o"
+}
+
+task broken {
+    run "{cc} {tmp}"
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_tmp_dir() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_TMP_DIR)?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+
+    // The temporary directory is created for the recipe, and deleted when it
+    // succeeds.
+    runner
+        .build_file(Path::new("main.o")?)
+        .await
+        .map_err(anyhow_msg)?;
+    let tmp_dir = workspace.recipe_tmp_dir(TaskId::build(Absolute::try_from("/main.o")?));
+    assert!(tmp_dir.starts_with(test.output_path([".werk-tmp"])));
+    assert!(test.did_run_during_build(&ShellCommandLine {
+        program: program_path("clang"),
+        arguments: vec![
+            "-c".into(),
+            "-o".into(),
+            format!("{}/main.o", tmp_dir.display()),
+        ],
+    }));
+    assert!(test
+        .io
+        .oplog
+        .lock()
+        .contains(&MockIoOp::CreateDir(tmp_dir.clone())));
+    assert!(!test.io.contains_dir(&tmp_dir));
+
+    // The temporary directory is kept when the recipe fails.
+    test.io
+        .set_program("clang", program_path("clang"), |_, _, _| {
+            Ok(failed_program_output())
+        });
+    let broken = TaskId::command("broken");
+    let err = runner.build_or_run("broken").await.unwrap_err();
+    assert!(matches!(err.error, werk_runner::Error::CommandFailed(..)));
+    let tmp_dir = workspace.recipe_tmp_dir(broken);
+    assert!(test.io.contains_dir(&tmp_dir));
+    assert!(test.render.did_see(&MockRenderEvent::Warning(
+        Some(broken),
+        format!(
+            "Keeping temporary directory for debugging: {}",
+            tmp_dir.display()
+        ),
+    )));
+
+    Ok(())
+}
+
+static WERK_OUT_STMT: &str = r#"
+let cc = which "clang"
+
+build "%.o" {
+    out "objs/{%}.o"
+    from "{%}.c"
+    run "{cc} -c <in> -o <out>"
+}
+
+build "app" {
+    from "main.o"
+    run "{cc} -o <out> <in>"
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_out_stmt() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_OUT_STMT)?;
+    test.set_workspace_file(&["main.c"], "int main() { return 0; }\n")?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+
+    // The object file is written to `objs/`, and dependents find it there.
+    runner
+        .build_file(Path::new("app")?)
+        .await
+        .map_err(anyhow_msg)?;
+    let object = test.output_path(["objs", "main.o"]);
+    assert!(test.did_create_parent_dirs(&object));
+    assert!(test.did_run_during_build(&ShellCommandLine {
+        program: program_path("clang"),
+        arguments: vec![
+            "-c".into(),
+            test.workspace_path(["main.c"]).display().to_string(),
+            "-o".into(),
+            object.display().to_string(),
+        ],
+    }));
+    assert!(test.did_run_during_build(&ShellCommandLine {
+        program: program_path("clang"),
+        arguments: vec![
+            "-o".into(),
+            test.output_path(["app"]).display().to_string(),
+            object.display().to_string(),
+        ],
+    }));
+
+    Ok(())
+}
+
+static WERK_CREATE_DIRS: &str = r#"
+let cc = which "clang"
+
+build "objs/%.o" {
+    run "{cc} -c -o <out>"
+}
+
+task generate {
+    run {
+        write "hello" to "gen/hello.txt"
+        copy "gen/hello.txt" to "copies/hello.txt"
+    }
+}
+"#;
+
+static WERK_NO_CREATE_DIRS: &str = r#"
+config create-dirs = false
+
+let cc = which "clang"
+
+build "objs/%.o" {
+    run "{cc} -c -o <out>"
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_create_dirs() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    // Parent directories are created by default.
+    let test = Test::new(WERK_CREATE_DIRS)?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("objs/main.o")?)
+        .await
+        .map_err(anyhow_msg)?;
+    runner.build_or_run("generate").await.map_err(anyhow_msg)?;
+    assert!(test.did_create_parent_dirs(&test.output_path(["objs", "main.o"])));
+    assert!(test.did_create_parent_dirs(&test.output_path(["gen", "hello.txt"])));
+    assert!(test.did_create_parent_dirs(&test.output_path(["copies", "hello.txt"])));
+
+    // Disabled with `config create-dirs = false`.
+    let test = Test::new(WERK_NO_CREATE_DIRS)?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("objs/main.o")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert!(!test.did_create_parent_dirs(&test.output_path(["objs", "main.o"])));
+
+    Ok(())
+}
+
+static WERK_MKDIR_DELETE: &str = r#"
+config create-dirs = false
+
+task make-dirs {
+    run {
+        mkdir ["objs/debug", "objs/release"]
+        write "hello" to "objs/debug/hello.txt"
+    }
+}
+
+task clean {
+    run {
+        delete "objs"
+    }
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_mkdir_delete() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_MKDIR_DELETE)?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+
+    runner.build_or_run("make-dirs").await.map_err(anyhow_msg)?;
+    assert!(test.io.contains_dir(test.output_path(["objs", "debug"])));
+    assert!(test.io.contains_dir(test.output_path(["objs", "release"])));
+    assert!(test
+        .io
+        .contains_file(test.output_path(["objs", "debug", "hello.txt"])));
+
+    // Deleting a directory deletes everything in it.
+    runner.build_or_run("clean").await.map_err(anyhow_msg)?;
+    assert!(!test.io.contains_dir(test.output_path(["objs"])));
+
+    Ok(())
+}
+
+static WERK_STAMP: &str = r#"
+let cc = which "clang"
+
+#[stamp]
+build "tests.stamp" {
+    from "main.c"
+    run "{cc} --test <in>"
+}
+
+task touch-marker {
+    run {
+        touch "markers/done"
+    }
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_stamp() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_STAMP)?;
+    test.set_workspace_file(&["main.c"], "int main() { return 0; }\n")?;
+    let test_command = ShellCommandLine {
+        program: program_path("clang"),
+        arguments: vec!["--test".into(), test.workspace_path_str(["main.c"])],
+    };
+
+    // The stamp file is created when the commands succeed.
+    test.io.tick();
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("tests.stamp")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert!(test.did_run_during_build(&test_command));
+    assert!(test.io.contains_file(test.output_path(["tests.stamp"])));
+    workspace.finalize().await.unwrap();
+
+    // The stamp file makes the recipe up to date.
+    test.io.clear_oplog();
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(Path::new("tests.stamp")?)
+        .await
+        .map_err(anyhow_msg)?;
+    assert!(!test.did_run_during_build(&test_command));
+
+    // `touch` creates files in the output directory.
+    runner
+        .build_or_run("touch-marker")
+        .await
+        .map_err(anyhow_msg)?;
+    assert!(test.io.contains_file(test.output_path(["markers", "done"])));
+
+    Ok(())
+}
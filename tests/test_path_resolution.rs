@@ -155,3 +155,196 @@ task build {
         Err(err) => panic!("unexpected error: {err}"),
     }
 }
+
+#[test]
+fn test_path_values() {
+    static WERK: &str = r#"
+let sources = glob "src/*.c"
+let first = "{sources}"
+let resolved = "<sources*>"
+let objects = sources | map "{:.c=.o}"
+let filtered = sources | match {
+    "%.c" => "{}"
+}
+"#;
+
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK).unwrap();
+    test.set_workspace_file(&["src", "a.c"], "").unwrap();
+    test.set_workspace_file(&["src", "b.c"], "").unwrap();
+    let workspace = test.create_workspace(&[]).unwrap();
+    let globals = &workspace.manifest.globals;
+    let get = |name: &str| &globals.get(&Symbol::new(name)).unwrap().value.value;
+
+    // `glob` produces abstract paths.
+    let Value::List(sources) = get("sources") else {
+        panic!("expected list")
+    };
+    assert!(sources.iter().all(|value| matches!(value, Value::Path(_))));
+    assert_eq!(
+        sources[0],
+        Value::Path(Absolute::try_from("/src/a.c").unwrap())
+    );
+    // Paths compare equal to strings with the same abstract path.
+    assert_eq!(*get("sources"), ["/src/a.c", "/src/b.c"]);
+
+    // Interpolation produces the abstract path, or the native path when
+    // resolved.
+    assert_eq!(*get("first"), Value::from("/src/a.c"));
+    assert_eq!(
+        *get("resolved"),
+        Value::String(format!(
+            "{} {}",
+            test.workspace_path_str(["src", "a.c"]),
+            test.workspace_path_str(["src", "b.c"])
+        ))
+    );
+
+    // String operations produce strings.
+    assert_eq!(*get("objects"), ["/src/a.o", "/src/b.o"]);
+    let Value::List(filtered) = get("filtered") else {
+        panic!("expected list")
+    };
+    assert!(filtered
+        .iter()
+        .all(|value| matches!(value, Value::String(_))));
+}
+
+#[test]
+fn test_source_dirs() {
+    static WERK: &str = r#"
+config source-dirs = ["src", "vendor/foo"]
+
+let sources = glob "*.c"
+let a-path = "a.c"
+let b-path = "b.c"
+let common-path = "common.h"
+let top-path = "top.c"
+let missing-path = "missing.c"
+
+let a = "<a-path>"
+let b = "<b-path>"
+let common = "<common-path>"
+let top = "<top-path>"
+let missing = "<missing-path:workspace>"
+"#;
+
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK).unwrap();
+    test.set_workspace_file(&["top.c"], "").unwrap();
+    test.set_workspace_file(&["src", "a.c"], "").unwrap();
+    test.set_workspace_file(&["src", "common.h"], "").unwrap();
+    test.set_workspace_file(&["vendor", "foo", "b.c"], "")
+        .unwrap();
+    test.set_workspace_file(&["vendor", "foo", "common.h"], "")
+        .unwrap();
+    let workspace = test.create_workspace(&[]).unwrap();
+    let globals = &workspace.manifest.globals;
+    let get = |name: &str| &globals.get(&Symbol::new(name)).unwrap().value.value;
+
+    // Files in all source directories are in the root of the workspace, and
+    // the workspace directory itself is not a source directory.
+    assert_eq!(*get("sources"), ["/a.c", "/b.c"]);
+    assert_eq!(
+        *get("a"),
+        Value::String(test.workspace_path_str(["src", "a.c"]))
+    );
+    assert_eq!(
+        *get("b"),
+        Value::String(test.workspace_path_str(["vendor", "foo", "b.c"]))
+    );
+    // Earlier source directories take precedence.
+    assert_eq!(
+        *get("common"),
+        Value::String(test.workspace_path_str(["src", "common.h"]))
+    );
+    assert_eq!(*get("top"), Value::String(test.output_path_str(["top.c"])));
+    assert_eq!(
+        *get("missing"),
+        Value::String(test.workspace_path_str(["src", "missing.c"]))
+    );
+}
+
+#[test]
+fn test_source_dirs_invalid() {
+    let test = Test::new(r#"config source-dirs = ["src", "nope"]"#).unwrap();
+    test.set_workspace_dir(&["src"]).unwrap();
+    let Err(err) = test.create_workspace(&[]) else {
+        panic!("expected error")
+    };
+    assert_eq!(
+        err.error,
+        werk_runner::Error::InvalidSourceDir(String::from("nope"))
+    );
+}
+
+#[test]
+fn test_tool_paths() {
+    static WERK: &str = r#"
+config tool-paths = ["missing", "tools/bin"]
+
+let cc = which "clang"
+let ld = which "ld"
+let gzip = which "gzip"
+"#;
+
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK).unwrap();
+    let clang = format!("clang{}", std::env::consts::EXE_SUFFIX);
+    test.set_workspace_file(&["tools", "bin", &clang], "")
+        .unwrap();
+    test.io
+        .set_program("clang", program_path("clang"), |_, _, _| {
+            Ok(empty_program_output())
+        });
+    test.io
+        .set_program("gzip", program_path("gzip"), |_, _, _| {
+            Ok(empty_program_output())
+        });
+
+    let mut settings = test.workspace_settings();
+    settings.tool("ld", program_path("my-ld"));
+
+    for _ in 0..2 {
+        let workspace = test.create_workspace_with_settings(&settings).unwrap();
+        let globals = &workspace.manifest.globals;
+        let get = |name: &str| &globals.get(&Symbol::new(name)).unwrap().value.value;
+
+        // Tool paths are searched before `PATH`, and `--tool` overrides are
+        // not searched for at all.
+        assert_eq!(
+            *get("cc"),
+            Value::String(test.workspace_path_str(["tools", "bin", &clang]))
+        );
+        assert_eq!(
+            *get("ld"),
+            Value::String(program_path("my-ld").display().to_string())
+        );
+        assert_eq!(
+            *get("gzip"),
+            Value::String(program_path("gzip").display().to_string())
+        );
+
+        // The programs looked up while evaluating, as reported by `werk
+        // doctor`.
+        let programs = workspace.programs();
+        let names = programs.iter().map(|(name, _)| &**name).collect::<Vec<_>>();
+        assert_eq!(names, ["clang", "gzip", "ld"]);
+        assert!(programs.iter().all(|(_, result)| result.is_ok()));
+    }
+
+    // Workspaces created from the same settings share the results of
+    // searching `PATH`.
+    let lookups = test
+        .io
+        .oplog
+        .lock()
+        .iter()
+        .filter(|op| matches!(op, MockIoOp::Which(_)))
+        .count();
+    assert_eq!(lookups, 1);
+    assert!(test.did_which("gzip"));
+}
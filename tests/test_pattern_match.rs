@@ -1,3 +1,4 @@
+use tests::mock_io::Test;
 use werk_runner::{Pattern, PatternMatchData};
 
 #[test]
@@ -93,3 +94,73 @@ fn test_capture_groups() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_char_classes_and_named_captures() -> anyhow::Result<()> {
+    let class = Pattern::parse("%.[ch]")?;
+    assert_eq!(
+        class.match_whole_string("main.h"),
+        Some(PatternMatchData::new(Some("main"), [String::from("h")]))
+    );
+    assert_eq!(class.match_whole_string("main.cc"), None);
+    assert_eq!(
+        Pattern::parse("v[0-9].[a-z](x|y)")?.expand().unwrap().len(),
+        10 * 26 * 2
+    );
+
+    let named = Pattern::parse("(%dir)/%.(%ext)")?;
+    let data = named.match_whole_string("src/lib/main.c").unwrap();
+    assert_eq!(data.stem(), Some("main"));
+    assert_eq!(data.named_capture("dir".into()), Some("src/lib"));
+    assert_eq!(data.named_capture("ext".into()), Some("c"));
+    assert_eq!(data.stem_len(), "src/lib".len() + "main".len() + "c".len());
+    assert!(!data.is_verbatim());
+    assert_eq!(named.expand(), None);
+    assert_eq!(named.match_whole_string("main.c"), None);
+
+    Ok(())
+}
+
+static WERK_SPECIFICITY: &str = r#"
+build "%.o" {}
+build "foo/%.o" {}
+build "foo/bar.o" {}
+build "%.(c|cpp)" {}
+build "%.c" {}
+"#;
+
+#[test]
+fn test_recipe_specificity() -> anyhow::Result<()> {
+    let test = Test::new(WERK_SPECIFICITY).map_err(|err| anyhow::Error::msg(err.to_string()))?;
+    let workspace = test
+        .create_workspace(&[])
+        .map_err(|err| anyhow::Error::msg(err.to_string()))?;
+
+    let matched_pattern = |path: &str| -> anyhow::Result<String> {
+        let path = werk_fs::Path::new(path)?.absolutize(werk_fs::Path::ROOT)?;
+        let recipe_match = workspace
+            .manifest
+            .match_build_recipe(&path)?
+            .expect("no recipe matched");
+        Ok(recipe_match.recipe.pattern.string.clone())
+    };
+
+    // The shortest stem wins.
+    assert_eq!(matched_pattern("baz.o")?, "/%.o");
+    assert_eq!(matched_pattern("foo/baz.o")?, "/foo/%.o");
+    // Patterns without a stem win.
+    assert_eq!(matched_pattern("foo/bar.o")?, "/foo/bar.o");
+    // Equally long stems; the pattern with more literal characters wins.
+    assert_eq!(matched_pattern("main.c")?, "/%.c");
+    assert_eq!(matched_pattern("main.cpp")?, "/%.(c|cpp)");
+
+    let candidates = workspace
+        .manifest
+        .build_recipe_candidates(&werk_fs::Absolute::try_from("/foo/bar.o")?)
+        .into_iter()
+        .map(|candidate| candidate.recipe.pattern.string.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(candidates, ["/foo/bar.o", "/foo/%.o", "/%.o"]);
+
+    Ok(())
+}
@@ -0,0 +1,548 @@
+use std::time::Duration;
+
+use macro_rules_attribute::apply;
+use tests::{
+    mock_io::{empty_program_output, failed_program_output, program_path, MockIoOp, Test},
+    synthetic_graph::{max_parallelism, GraphSpec, Input, SyntheticGraph},
+};
+
+const SPEC: GraphSpec = GraphSpec {
+    nodes: 120,
+    sources: 16,
+    max_fan_in: 4,
+    max_fan_out: 6,
+    seed: 0x5eed,
+};
+
+fn anyhow_msg<E: ToString>(err: E) -> anyhow::Error {
+    anyhow::Error::msg(err.to_string())
+}
+
+#[apply(smol_macros::test)]
+async fn test_synthetic_graph_schedule() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let graph = SyntheticGraph::generate(SPEC);
+    let test = Test::new(&graph.werkfile).map_err(anyhow_msg)?;
+    graph.install(&test);
+    // Commands take between 1 and 3 ms, so independent commands overlap.
+    test.io.set_command_duration(move |command_line| {
+        let len = command_line.arguments.len() as u64;
+        Duration::from_millis(1 + len % 3)
+    });
+
+    let mut settings = test.workspace_settings();
+    settings.jobs = 4;
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("all").await.map_err(anyhow_msg)?;
+
+    let oplog = test.io.oplog.lock();
+    let executed = graph.executed(&oplog);
+    assert_eq!(executed.len(), SPEC.nodes);
+    graph.assert_dependency_order(&oplog);
+
+    let parallelism = max_parallelism(&oplog);
+    assert!(parallelism <= 4, "ran {parallelism} commands in parallel");
+    assert!(parallelism > 1, "commands were not run in parallel");
+
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_synthetic_graph_serial() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let graph = SyntheticGraph::generate(GraphSpec { seed: 7, ..SPEC });
+    let test = Test::new(&graph.werkfile).map_err(anyhow_msg)?;
+    graph.install(&test);
+    test.io.set_command_duration(|_| Duration::from_micros(100));
+
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("all").await.map_err(anyhow_msg)?;
+
+    let oplog = test.io.oplog.lock();
+    assert_eq!(graph.executed(&oplog).len(), SPEC.nodes);
+    graph.assert_dependency_order(&oplog);
+    assert_eq!(max_parallelism(&oplog), 1);
+
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_synthetic_graph_summary() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let graph = SyntheticGraph::generate(GraphSpec { seed: 3, ..SPEC });
+    let test = Test::new(&graph.werkfile).map_err(anyhow_msg)?;
+    graph.install(&test);
+    test.io.set_command_duration(move |command_line| {
+        let len = command_line.arguments.len() as u64;
+        Duration::from_millis(1 + len % 3)
+    });
+
+    let mut settings = test.workspace_settings();
+    settings.jobs = 8;
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("all").await.map_err(anyhow_msg)?;
+
+    // Every node and the `all` task ran.
+    let summary = runner.summary();
+    assert_eq!(summary.executed, SPEC.nodes + 1);
+    assert_eq!(summary.up_to_date, 0);
+    assert_eq!(summary.cache_hits, 0);
+    assert!(summary.critical_path_time >= Duration::from_millis(1));
+    assert!(summary.critical_path_time <= summary.total_time);
+    assert_eq!(summary.slowest_commands.len(), 3);
+    assert!(summary
+        .slowest_commands
+        .windows(2)
+        .all(|pair| pair[0].duration >= pair[1].duration));
+    workspace.finalize().await.map_err(anyhow_msg)?;
+    std::mem::drop(runner);
+
+    // Nothing changed, so only the task runs.
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("all").await.map_err(anyhow_msg)?;
+    let summary = runner.summary();
+    assert_eq!(summary.executed, 1);
+    assert_eq!(summary.up_to_date, SPEC.nodes);
+    assert!(summary.slowest_commands.is_empty());
+    assert_eq!(summary.critical_path_time, Duration::ZERO);
+
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_synthetic_graph_outdatedness() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let graph = SyntheticGraph::generate(SPEC);
+    let test = Test::new(&graph.werkfile).map_err(anyhow_msg)?;
+    graph.install(&test);
+
+    let mut settings = test.workspace_settings();
+    settings.jobs = 8;
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("all").await.map_err(anyhow_msg)?;
+    workspace.finalize().await.map_err(anyhow_msg)?;
+    std::mem::drop(runner);
+
+    // Nothing changed, so nothing is rebuilt.
+    test.io.clear_oplog();
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("all").await.map_err(anyhow_msg)?;
+    assert!(graph.executed(&test.io.oplog.lock()).is_empty());
+    workspace.finalize().await.map_err(anyhow_msg)?;
+    std::mem::drop(runner);
+
+    // Only the nodes that transitively depend on a modified source file are
+    // rebuilt.
+    for source in [0, 5, 11] {
+        graph.modify_source(&test, source);
+        test.io.clear_oplog();
+        let workspace = test
+            .create_workspace_with_settings(&settings)
+            .map_err(anyhow_msg)?;
+        let runner = werk_runner::Runner::new(&workspace);
+        runner.build_or_run("all").await.map_err(anyhow_msg)?;
+
+        {
+            let oplog = test.io.oplog.lock();
+            let executed = graph.executed(&oplog).into_iter().collect();
+            assert_eq!(graph.dependents(Input::Source(source)), executed);
+            graph.assert_dependency_order(&oplog);
+        }
+
+        workspace.finalize().await.map_err(anyhow_msg)?;
+    }
+
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_synthetic_graph_restat() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let graph = SyntheticGraph::generate(SPEC);
+    let test = Test::new(&graph.werkfile).map_err(anyhow_msg)?;
+    graph.install(&test);
+
+    let mut settings = test.workspace_settings();
+    settings.jobs = 8;
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("all").await.map_err(anyhow_msg)?;
+    workspace.finalize().await.map_err(anyhow_msg)?;
+    std::mem::drop(runner);
+
+    // Touching a source file rebuilds the nodes using it, but their outputs
+    // are identical, so nothing else is rebuilt.
+    graph.touch_source(&test, 3);
+    test.io.clear_oplog();
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("all").await.map_err(anyhow_msg)?;
+
+    let oplog = test.io.oplog.lock();
+    let executed = graph.executed(&oplog).into_iter().collect();
+    assert_eq!(graph.direct_dependents(Input::Source(3)), executed);
+
+    Ok(())
+}
+
+static WERK_POOLS: &str = r#"
+config pool.link = 2
+
+let cc = which "cc"
+let link = which "link"
+
+task link-a {
+    pool "link"
+    run "{link} a"
+}
+
+task link-b {
+    pool "link"
+    run "{link} b"
+}
+
+task link-c {
+    pool "link"
+    run "{link} c"
+}
+
+task link-d {
+    pool "link"
+    run "{link} d"
+}
+
+task cc-a {
+    run "{cc} a"
+}
+
+task cc-b {
+    run "{cc} b"
+}
+
+task cc-c {
+    run "{cc} c"
+}
+
+task all {
+    build ["link-a", "link-b", "link-c", "link-d", "cc-a", "cc-b", "cc-c"]
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_pools() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_POOLS).map_err(anyhow_msg)?;
+    for program in ["cc", "link"] {
+        test.io
+            .set_program(program, program_path(program), |_, _, _| {
+                Ok(empty_program_output())
+            });
+    }
+    test.io.set_command_duration(|_| Duration::from_millis(5));
+
+    let mut settings = test.workspace_settings();
+    settings.jobs = 8;
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("all").await.map_err(anyhow_msg)?;
+
+    let oplog = test.io.oplog.lock();
+    let link = program_path("link");
+    let links = oplog
+        .iter()
+        .filter(|op| {
+            matches!(op, MockIoOp::RunDuringBuild(cmd) | MockIoOp::FinishDuringBuild(cmd) if cmd.program == link)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    assert_eq!(links.len(), 8);
+    let parallelism = max_parallelism(&links);
+    assert_eq!(
+        parallelism, 2,
+        "ran {parallelism} commands in the pool in parallel"
+    );
+    assert!(
+        max_parallelism(&oplog) > 2,
+        "the pool limited other commands"
+    );
+
+    Ok(())
+}
+
+static WERK_CRITICAL_PATH: &str = r#"
+let tool = which "tool"
+
+build "x1" {
+    run "{tool} x1"
+}
+
+build "x2" {
+    run "{tool} x2"
+}
+
+build "x3" {
+    run "{tool} x3"
+}
+
+build "y" {
+    run "{tool} y"
+}
+
+build "z" {
+    from "y"
+    run "{tool} z"
+}
+
+task all {
+    build ["x1", "x2", "x3", "z"]
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_critical_path_priority() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_CRITICAL_PATH).map_err(anyhow_msg)?;
+    test.io
+        .set_program("tool", program_path("tool"), |_, _, _| {
+            Ok(empty_program_output())
+        });
+    test.io.set_command_duration(|command_line| {
+        if command_line.arguments == ["z"] {
+            Duration::from_millis(20)
+        } else {
+            Duration::from_millis(5)
+        }
+    });
+
+    let mut settings = test.workspace_settings();
+    settings.jobs = 1;
+
+    // The first run records the durations of the commands.
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("all").await.map_err(anyhow_msg)?;
+    workspace.finalize().await.map_err(anyhow_msg)?;
+    std::mem::drop(runner);
+
+    // The outputs were never written, so everything runs again. `y` is on
+    // the critical path, so it gets the job slot before the other recipes
+    // waiting for it, even though it was the last to ask for one.
+    test.io.clear_oplog();
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("all").await.map_err(anyhow_msg)?;
+
+    let order = test
+        .io
+        .oplog
+        .lock()
+        .iter()
+        .filter_map(|op| match op {
+            MockIoOp::RunDuringBuild(command_line) => Some(command_line.arguments[0].clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(order.len(), 5);
+    assert_eq!(order[1], "y", "order: {order:?}");
+
+    Ok(())
+}
+
+static WERK_FAILURES: &str = r#"
+let ok = which "ok"
+let fail = which "fail"
+
+build "a" {
+    run "{fail} a"
+}
+
+build "b" {
+    run "{ok} b"
+}
+
+build "c" {
+    run "{fail} c"
+}
+
+build "d" {
+    from "a"
+    run "{ok} d"
+}
+
+task all {
+    build ["a", "b", "c", "d"]
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_keep_going() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_FAILURES).map_err(anyhow_msg)?;
+    test.io.set_program("ok", program_path("ok"), |_, _, _| {
+        Ok(empty_program_output())
+    });
+    test.io
+        .set_program("fail", program_path("fail"), |command_line, _, _| {
+            let mut output = failed_program_output();
+            output.stderr = format!("{} failed\n", command_line.arguments[0]).into_bytes();
+            Ok(output)
+        });
+    let executed = || {
+        test.io
+            .oplog
+            .lock()
+            .iter()
+            .filter_map(|op| match op {
+                MockIoOp::RunDuringBuild(command_line) => Some(command_line.arguments[0].clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut settings = test.workspace_settings();
+    settings.jobs = 1;
+
+    // No new commands are started after the first failure.
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    assert!(runner.build_or_run("all").await.is_err());
+    assert_eq!(executed(), ["a"]);
+    let results = runner.results();
+    assert_eq!(results.failures().count(), 1);
+    assert!(!results.is_success());
+    std::mem::drop(runner);
+
+    // Everything that doesn't depend on a failed task is built, and all
+    // failures are reported.
+    test.io.clear_oplog();
+    settings.keep_going = true;
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    assert!(runner.build_or_run("all").await.is_err());
+    assert_eq!(executed(), ["a", "b", "c"]);
+    let results = runner.results();
+    let failures = results
+        .failures()
+        .map(|(task, failure)| (task.task.as_str(), failure.output.as_slice()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        failures,
+        [("/a", &b"a failed\n"[..]), ("/c", &b"c failed\n"[..])]
+    );
+    let outcome = |name: &str| {
+        results
+            .tasks
+            .iter()
+            .find(|task| task.task == name)
+            .map(|task| task.outcome.clone())
+    };
+    assert!(matches!(
+        outcome("/b"),
+        Some(werk_runner::TaskOutcome::Executed)
+    ));
+    assert!(matches!(
+        outcome("/d"),
+        Some(werk_runner::TaskOutcome::DependencyFailed)
+    ));
+    assert!(matches!(
+        outcome("all"),
+        Some(werk_runner::TaskOutcome::DependencyFailed)
+    ));
+
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_task_log() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_FAILURES).map_err(anyhow_msg)?;
+    test.io.set_program("ok", program_path("ok"), |_, _, _| {
+        Ok(empty_program_output())
+    });
+    test.io
+        .set_program("fail", program_path("fail"), |command_line, _, _| {
+            let mut output = failed_program_output();
+            output.stderr = format!("{} failed\n", command_line.arguments[0]).into_bytes();
+            Ok(output)
+        });
+
+    let mut settings = test.workspace_settings();
+    settings.keep_going = true;
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    assert!(runner.build_or_run("all").await.is_err());
+    let results = runner.results();
+    std::mem::drop(runner);
+
+    // Tasks that printed something have a log file.
+    let log_file = |name: &str| {
+        results
+            .tasks
+            .iter()
+            .find(|task| task.task == name)
+            .and_then(|task| task.log_file.clone())
+    };
+    let a_log = workspace.task_log_file(werk_runner::TaskId::try_build("/a")?);
+    assert_eq!(log_file("/a").as_ref(), Some(&a_log));
+    assert_eq!(log_file("/b"), None);
+    assert_eq!(
+        werk_runner::Io::read_file(&*test.io, &a_log)?,
+        b"a failed\n"
+    );
+
+    // The previous log is kept when the task runs again.
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    assert!(runner.build_or_run("all").await.is_err());
+    let rotated = a_log.parent().unwrap().join(format!(
+        "{}.1",
+        a_log.file_name().unwrap().to_string_lossy()
+    ))?;
+    assert!(test.did_copy_file(&a_log, &rotated));
+    assert!(test.io.contains_file(&rotated));
+
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use futures::future::LocalBoxFuture;
+use macro_rules_attribute::apply;
+use tests::mock_io;
+
+use mock_io::*;
+use werk_fs::Absolute;
+use werk_runner::{
+    BuildStatus, CancellationToken, Error, Session, SessionErrorKind, ShellCommandLine, TaskId,
+    Watcher,
+};
+
+static WERK: &str = r#"
+let cc = which "clang"
+
+build "%.o" {
+    from "{%}.c"
+    run "{cc} -c <in> -o <out>"
+}
+"#;
+
+static WERK_CHANGED: &str = r#"
+let cc = which "clang"
+
+build "%.o" {
+    from "{%}.c"
+    run "{cc} -c -O2 <in> -o <out>"
+}
+"#;
+
+fn anyhow_msg<E: ToString>(err: E) -> anyhow::Error {
+    anyhow::Error::msg(err.to_string())
+}
+
+fn open_session(test: &Test) -> Result<Session, werk_runner::SessionError> {
+    Session::open(
+        test.io.clone(),
+        test.render.clone(),
+        test.workspace_path(["Werkfile"]),
+        test.workspace_settings(),
+    )
+}
+
+fn compile_command(test: &Test, flags: &[&str]) -> ShellCommandLine {
+    let mut arguments = vec!["-c".into()];
+    arguments.extend(flags.iter().map(|&flag| flag.into()));
+    arguments.extend([
+        test.workspace_path_str(["a.c"]),
+        "-o".into(),
+        test.output_path_str(["a.o"]),
+    ]);
+    ShellCommandLine {
+        program: program_path("clang"),
+        arguments,
+    }
+}
+
+#[apply(smol_macros::test)]
+async fn test_session_build() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK)?;
+    test.set_workspace_file(&["Werkfile"], WERK)?;
+    test.set_workspace_file(&["a.c"], "void foo() {}")?;
+    let mut session = open_session(&test).map_err(anyhow_msg)?;
+
+    let plan = session.plan("a.o").await.map_err(anyhow_msg)?;
+    assert_eq!(plan.root, TaskId::try_build("/a.o").unwrap());
+    assert!(!test.did_run_during_build(&compile_command(&test, &[])));
+
+    let statuses = session.build(&["a.o"]).await.map_err(anyhow_msg)?;
+    assert!(matches!(statuses[..], [BuildStatus::Complete(..)]));
+    assert!(test.did_run_during_build(&compile_command(&test, &[])));
+
+    // Reloading picks up changes to the Werkfile.
+    assert!(!session.reload().map_err(anyhow_msg)?);
+    test.set_workspace_file(&["Werkfile"], WERK_CHANGED)?;
+    assert!(session.reload().map_err(anyhow_msg)?);
+    session.build(&["a.o"]).await.map_err(anyhow_msg)?;
+    assert!(test.did_run_during_build(&compile_command(&test, &["-O2"])));
+
+    // Errors carry the rendered diagnostic.
+    let Err(err) = session.build(&["b.o"]).await else {
+        panic!("expected error");
+    };
+    assert!(matches!(err.kind, SessionErrorKind::Runner(_)));
+    assert!(err.diagnostic.contains("b.c"), "{}", err.diagnostic);
+
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_session_cancel() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK)?;
+    test.set_workspace_file(&["Werkfile"], WERK)?;
+    test.set_workspace_file(&["a.c"], "void foo() {}")?;
+    let session = open_session(&test).map_err(anyhow_msg)?;
+
+    session.cancellation_token().cancel();
+    let Err(err) = session.build(&["a.o"]).await else {
+        panic!("expected error");
+    };
+    let SessionErrorKind::Runner(Error::Cancelled(task_id)) = err.kind else {
+        panic!("expected cancellation, got {err:?}");
+    };
+    assert_eq!(task_id, TaskId::try_build("/a.o").unwrap());
+    assert!(!test.did_run_during_build(&compile_command(&test, &[])));
+
+    Ok(())
+}
+
+/// Changes the Werkfile when first asked to wait, and cancels the session the
+/// second time.
+struct EditingWatcher<'a> {
+    test: &'a Test<'a>,
+    cancellation: CancellationToken,
+    waits: usize,
+    watched: Vec<Vec<Absolute<std::path::PathBuf>>>,
+}
+
+impl Watcher for EditingWatcher<'_> {
+    fn wait_for_change<'w>(
+        &'w mut self,
+        paths: &'w [Absolute<std::path::PathBuf>],
+    ) -> LocalBoxFuture<'w, std::io::Result<()>> {
+        self.watched.push(paths.to_vec());
+        self.waits += 1;
+        Box::pin(async move {
+            if self.waits == 1 {
+                self.test.set_workspace_file(&["Werkfile"], WERK_CHANGED)
+            } else {
+                self.cancellation.cancel();
+                std::future::pending().await
+            }
+        })
+    }
+}
+
+#[apply(smol_macros::test)]
+async fn test_session_watch() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK)?;
+    test.set_workspace_file(&["Werkfile"], WERK)?;
+    test.set_workspace_file(&["a.c"], "void foo() {}")?;
+    let mut session = open_session(&test).map_err(anyhow_msg)?;
+    let mut watcher = EditingWatcher {
+        test: &test,
+        cancellation: session.cancellation_token(),
+        waits: 0,
+        watched: Vec::new(),
+    };
+
+    let mut results = Vec::new();
+    session
+        .watch(&["a.o"], &mut watcher, |result| results.push(result))
+        .await
+        .map_err(anyhow_msg)?;
+
+    // Built once initially, and once after the change.
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(Result::is_ok));
+    assert!(test.did_run_during_build(&compile_command(&test, &[])));
+    assert!(test.did_run_during_build(&compile_command(&test, &["-O2"])));
+
+    // The Werkfile and the source files are watched.
+    assert_eq!(watcher.waits, 2);
+    let watched = &watcher.watched[0];
+    assert!(watched.contains(&test.workspace_path(["Werkfile"])));
+    assert!(watched.contains(&test.workspace_path(["a.c"])));
+
+    Ok(())
+}
@@ -0,0 +1,904 @@
+use macro_rules_attribute::apply;
+use tests::mock_io::*;
+use werk_runner::{BuildStatus, Error, Member, MemberSources, OutputStatus, TaskId, Workspace};
+use werk_util::Symbol;
+
+fn anyhow_msg<E: ToString>(err: E) -> anyhow::Error {
+    anyhow::Error::msg(err.to_string())
+}
+
+static WERK_ROOT: &str = r#"
+member "app"
+member "lib/"
+
+task build {
+    build "//app:hello"
+}
+"#;
+
+static WERK_APP: &str = r#"
+config default = "hello"
+
+task hello {
+    build "//lib:greeting.txt"
+    info "hello"
+}
+
+task cycle {
+    build "//lib:cycle"
+}
+"#;
+
+static WERK_LIB: &str = r#"
+build "greeting.txt" {
+    run {
+        write "hello" to "{out}"
+    }
+}
+
+task cycle {
+    build "//app:cycle"
+}
+"#;
+
+#[apply(smol_macros::test)]
+async fn test_members() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(WERK_ROOT)?;
+    test.set_workspace_file(&["app", "Werkfile"], WERK_APP)?;
+    test.set_workspace_file(&["lib", "build.werk"], WERK_LIB)?;
+
+    let sources =
+        MemberSources::load(&*test.io, &test.ast, &test.workspace_dir).map_err(anyhow_msg)?;
+    let names = sources.iter().map(|(name, ..)| name).collect::<Vec<_>>();
+    assert_eq!(names, [Symbol::new("app"), Symbol::new("lib")]);
+
+    test.set_workspace_dir(&["app", "output"])?;
+    test.set_workspace_dir(&["lib", "output"])?;
+    let asts = sources.parse().map_err(anyhow_msg)?;
+    let mut members = Vec::new();
+    for ((name, dir, _), ast) in sources.iter().zip(&asts) {
+        let settings = werk_runner::WorkspaceSettings::new(dir.join("output").unwrap());
+        let workspace = Workspace::new(ast, &*test.io, &*test.render, dir.to_owned(), &settings)
+            .map_err(anyhow_msg)?;
+        members.push(Member {
+            name,
+            workspace,
+            default_target: (name == "app").then(|| String::from("hello")),
+        });
+    }
+
+    let mut workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    workspace.members = &members;
+    let runner = werk_runner::Runner::new(&workspace);
+
+    let status = runner.build_or_run("build").await.map_err(anyhow_msg)?;
+    assert!(
+        matches!(status, BuildStatus::Complete(task_id, _) if task_id == TaskId::command("build"))
+    );
+    // Outputs of members are written to their own output directory.
+    assert!(test
+        .io
+        .contains_file(native_path(["workspace", "lib", "output", "greeting.txt"])));
+
+    // The default target of a member.
+    let plan = runner.plan("//app").map_err(anyhow_msg)?;
+    assert!(plan.get(TaskId::command("//app:hello")).is_some());
+
+    let err = runner.build_or_run("//nope:hello").await.unwrap_err();
+    assert_eq!(err.error, Error::NoSuchMember(String::from("nope")));
+    let err = runner.build_or_run("//lib:cycle").await.unwrap_err();
+    assert_eq!(
+        err.error,
+        Error::CircularMemberDependency(String::from("lib -> app -> lib"))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn duplicate_member() {
+    let test = Test::new("member \"app\"\nmember \"app/\"").unwrap();
+    test.set_workspace_file(&["app", "Werkfile"], "").unwrap();
+    assert!(MemberSources::load(&*test.io, &test.ast, &test.workspace_dir).is_err());
+}
+
+#[test]
+fn glob_ignore_config() {
+    let test = Test::new("config glob-ignore = \"werk\"").unwrap();
+    let settings = test.workspace_settings();
+    assert!(!settings.glob.git_ignore);
+    assert!(!settings.glob.git_ignore_global);
+    assert!(!settings.glob.dot_ignore);
+    assert!(settings.glob.werk_ignore);
+
+    let test = Test::new("config glob-ignore = \"git\"").unwrap();
+    let settings = test.workspace_settings();
+    assert!(settings.glob.git_ignore);
+    assert!(settings.glob.dot_ignore);
+    assert!(settings.glob.werk_ignore);
+
+    let test = Test::new("config glob-ignore = \"hg\"").unwrap();
+    assert!(matches!(
+        werk_runner::ir::Config::new(&test.ast),
+        Err(werk_runner::EvalError::InvalidGlobIgnore(_, name)) if name == "hg"
+    ));
+}
+
+#[test]
+fn echo_template_config() {
+    let test =
+        Test::new(r#"config echo-template = "[{step}/{steps}] {task}: {command} {{}""#).unwrap();
+    let config = werk_runner::ir::Config::new(&test.ast).unwrap();
+    let template = config.echo_template.unwrap();
+    assert_eq!(
+        template.render(2, 5, "build", &"cc -c main.c"),
+        "[2/5] build: cc -c main.c {}"
+    );
+
+    let test = Test::new(r#"config echo-template = "{step} {cmd}""#).unwrap();
+    assert!(matches!(
+        werk_runner::ir::Config::new(&test.ast),
+        Err(werk_runner::EvalError::InvalidEchoTemplate(_, placeholder)) if placeholder == "{cmd}"
+    ));
+}
+
+#[test]
+fn ambiguous_member_werkfile() {
+    let test = Test::new("member \"app\"").unwrap();
+    test.set_workspace_file(&["app", "Werkfile"], "").unwrap();
+    test.set_workspace_file(&["app", "build.werk"], "").unwrap();
+    let Err(err) = MemberSources::load(&*test.io, &test.ast, &test.workspace_dir) else {
+        panic!("expected an error");
+    };
+    assert!(matches!(
+        err.error,
+        werk_runner::EvalError::AmbiguousWerkfile(_, ref first, ref second)
+            if first == "Werkfile" && second == "build.werk"
+    ));
+}
+
+#[test]
+fn select_werkfile() {
+    let names = ["Werkfile", "werkfile", "build.werk"].map(String::from);
+    let different = |_: &str, _: &str| false;
+    assert_eq!(
+        werk_runner::select_werkfile(&names, |name| name == "build.werk", different),
+        Ok(Some("build.werk"))
+    );
+    assert_eq!(
+        werk_runner::select_werkfile(&names, |_| false, different),
+        Ok(None)
+    );
+    // Names that only differ by case are the same file on case-insensitive
+    // file systems, and different files on case-sensitive file systems.
+    let case_insensitive = |a: &str, b: &str| a.eq_ignore_ascii_case(b);
+    assert_eq!(
+        werk_runner::select_werkfile(&names, |name| name != "build.werk", case_insensitive),
+        Ok(Some("Werkfile"))
+    );
+    assert_eq!(
+        werk_runner::select_werkfile(&names, |name| name != "build.werk", different),
+        Err(["Werkfile", "werkfile"])
+    );
+    assert_eq!(
+        werk_runner::select_werkfile(&names, |name| name != "werkfile", case_insensitive),
+        Err(["Werkfile", "build.werk"])
+    );
+}
+
+#[test]
+fn werkfile_names_env() {
+    let test = Test::new("member \"app\"").unwrap();
+    test.set_workspace_file(&["app", "Werkfile"], "").unwrap();
+    test.set_workspace_file(&["app", "werkfile"], "").unwrap();
+    // Both files exist on the case-sensitive mock file system.
+    let Err(err) = MemberSources::load(&*test.io, &test.ast, &test.workspace_dir) else {
+        panic!("expected an error");
+    };
+    assert!(matches!(
+        err.error,
+        werk_runner::EvalError::AmbiguousWerkfile(_, ref first, ref second)
+            if first == "Werkfile" && second == "werkfile"
+    ));
+
+    // The names are read from the environment of the `Io`.
+    test.io.set_env("WERKFILE_NAMES", "werkfile");
+    assert_eq!(werk_runner::werkfile_names(&*test.io), ["werkfile"]);
+    assert!(MemberSources::load(&*test.io, &test.ast, &test.workspace_dir).is_ok());
+}
+
+#[apply(smol_macros::test)]
+async fn test_in_dir() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(
+        r#"
+build "root.txt" {
+    run "write-env PWD <out>"
+}
+
+build "tools.txt" {
+    in-dir "tools"
+    run "write-env PWD <out>"
+}
+
+task all {
+    build ["root.txt", "tools.txt"]
+    let output = "task.txt"
+    in-dir "tools/bin"
+    run "write-env PWD <output:out-dir>"
+}
+"#,
+    )
+    .map_err(anyhow_msg)?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("all").await.map_err(anyhow_msg)?;
+
+    let fs = test.io.filesystem.lock();
+    let contents = |file: &str| {
+        let (_, data) = read_fs(&fs, &native_path(["workspace", "output", file])).unwrap();
+        String::from_utf8(data.to_vec()).unwrap()
+    };
+    let dir = |path: &[&str]| native_path(path).display().to_string();
+    assert_eq!(contents("root.txt"), dir(&["workspace"]));
+    assert_eq!(contents("tools.txt"), dir(&["workspace", "tools"]));
+    assert_eq!(contents("task.txt"), dir(&["workspace", "tools", "bin"]));
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_response_file() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let objs = (0..20_000)
+        .map(|i| format!("\"obj {i}.o\""))
+        .collect::<Vec<_>>()
+        .join(", ");
+    // Too long for a single argument or the whole command line on any OS.
+    let huge = "x".repeat(3 * 1024 * 1024);
+    let werkfile = format!(
+        r#"
+let objs = [{objs}]
+let huge = "{huge}"
+
+task long {{
+    #[response-file = "gnu"]
+    run "link {{objs*}}"
+}}
+
+task short {{
+    #[response-file = "gnu"]
+    run "link a.o b.o"
+}}
+
+task no-response-file {{
+    run "link {{huge}}"
+}}
+"#
+    );
+    let test = Test::new(&werkfile).map_err(anyhow_msg)?;
+    let response_files = std::sync::Arc::new(parking_lot::Mutex::new(Vec::new()));
+    let seen = response_files.clone();
+    test.io
+        .set_program("link", program_path("link"), move |cmdline, fs, _| {
+            if let [arg] = &*cmdline.arguments {
+                if let Some(path) = arg.strip_prefix('@') {
+                    let path = werk_fs::Absolute::new(std::path::PathBuf::from(path)).unwrap();
+                    let (_, data) = read_fs(fs, &path)?;
+                    seen.lock().push(String::from_utf8(data.to_vec()).unwrap());
+                }
+            }
+            Ok(empty_program_output())
+        });
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+
+    runner.build_or_run("long").await.map_err(anyhow_msg)?;
+    {
+        let response_files = response_files.lock();
+        assert_eq!(response_files.len(), 1);
+        let mut lines = response_files[0].lines();
+        assert_eq!(lines.next(), Some("obj\\ 0.o"));
+        assert_eq!(lines.count(), 19_999);
+    }
+    // The response file is deleted when the command succeeds.
+    assert!(test.io.oplog.lock().iter().any(|op| matches!(
+        op,
+        MockIoOp::DeleteFile(path) if path.extension() == Some("rsp".as_ref())
+    )));
+
+    runner.build_or_run("short").await.map_err(anyhow_msg)?;
+    assert_eq!(response_files.lock().len(), 1);
+
+    // Without a response file, the command is not spawned at all.
+    let err = runner.build_or_run("no-response-file").await.unwrap_err();
+    assert!(matches!(
+        err.error,
+        werk_runner::Error::CommandLineTooLong(_, Some(_))
+    ));
+    assert!(!test.did_run_during_build(&werk_runner::ShellCommandLine {
+        program: program_path("link"),
+        arguments: vec![huge],
+    }));
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_args() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(
+        r#"
+let flags = ["-O2", ["-D NAME=a b"]]
+
+task compile {
+    let output = "my file.o"
+    run args ["cc", "-c", flags, "", "-o", "<output:out-dir>", " src/main.c "]
+}
+"#,
+    )
+    .map_err(anyhow_msg)?;
+    test.io.set_program("cc", program_path("cc"), |_, _, _| {
+        Ok(empty_program_output())
+    });
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("compile").await.map_err(anyhow_msg)?;
+
+    // Each string is one argument, including whitespace and empty strings.
+    let output = native_path(["workspace", "output", "my file.o"]);
+    assert!(test.did_run_during_build(&werk_runner::ShellCommandLine {
+        program: program_path("cc"),
+        arguments: [
+            "-c",
+            "-O2",
+            "-D NAME=a b",
+            "",
+            "-o",
+            &output.display().to_string(),
+            " src/main.c ",
+        ]
+        .map(String::from)
+        .to_vec(),
+    }));
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_secret() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(
+        r#"
+let token = env "TOKEN" | secret
+let header = "Bearer {token}"
+
+task deploy {
+    info "deploying with {header}"
+    run "upload --token={token}"
+}
+
+task fail {
+    run "upload --token={token} --fail"
+}
+"#,
+    )
+    .map_err(anyhow_msg)?;
+    test.io.set_env("TOKEN", "hunter2");
+    test.io
+        .set_program("upload", program_path("upload"), |cmdline, _, _| {
+            if cmdline.arguments.iter().any(|arg| arg == "--fail") {
+                Ok(failed_program_output())
+            } else {
+                Ok(empty_program_output())
+            }
+        });
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("deploy").await.map_err(anyhow_msg)?;
+
+    // The command still receives the secret.
+    assert!(test.did_run_during_build(&werk_runner::ShellCommandLine {
+        program: program_path("upload"),
+        arguments: vec!["--token=hunter2".into()],
+    }));
+
+    // Everything shown to the user is redacted, including derived values.
+    let task_id = TaskId::command("deploy");
+    assert!(test.render.did_see(&MockRenderEvent::EvaluateVariable(
+        None,
+        "header".into(),
+        "Bearer ***".into()
+    )));
+    assert!(test.render.did_see(&MockRenderEvent::Message(
+        Some(task_id),
+        "deploying with Bearer ***".into()
+    )));
+    assert!(test.render.did_see(&MockRenderEvent::WillExecute(
+        task_id,
+        werk_runner::ShellCommandLine {
+            program: program_path("upload"),
+            arguments: vec!["--token=***".into()],
+        },
+        1,
+        2,
+        werk_runner::Echo::Default,
+    )));
+    assert!(!format!("{:?}", test.render.log.lock()).contains("hunter2"));
+
+    let err = runner.build_or_run("fail").await.unwrap_err();
+    let Error::CommandFailed(_, Some(location)) = err.error else {
+        panic!("expected a failed command");
+    };
+    assert!(location.evaluated.contains("--token=***"));
+    assert!(!location.evaluated.contains("hunter2"));
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_fetch() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(
+        r#"
+let url = "https://example.com/sdk.txt"
+let digest = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03"
+
+task sdk {
+    run fetch "{url}" sha256 "{digest}" to "sdk.txt"
+}
+
+task bad {
+    run fetch "{url}" sha256 "0000000000000000000000000000000000000000000000000000000000000000" to "bad.txt"
+}
+"#,
+    )
+    .map_err(anyhow_msg)?;
+    let url = "https://example.com/sdk.txt";
+    let digest = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+    test.io.set_download(url, "hello\n");
+    let sdk = test.output_path(["sdk.txt"]);
+    let part = test.output_path(["sdk.txt.part"]);
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+
+    // A stale partial download is discarded when it does not verify.
+    werk_runner::Io::create_parent_dirs(&*test.io, &part)?;
+    werk_runner::Io::write_file(&*test.io, &part, b"stale")?;
+    runner.build_or_run("sdk").await.map_err(anyhow_msg)?;
+    {
+        let oplog = test.io.oplog.lock();
+        assert!(oplog.contains(&MockIoOp::Download(url.into(), part.clone(), true)));
+        assert!(oplog.contains(&MockIoOp::Download(url.into(), part.clone(), false)));
+        let fs = test.io.filesystem.lock();
+        assert_eq!(read_fs(&fs, &sdk).unwrap().1, b"hello\n");
+    }
+    assert!(!test.io.contains_file(&part));
+
+    // The file is not downloaded again when it has the expected digest.
+    std::mem::drop(runner);
+    test.io.clear_oplog();
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("sdk").await.map_err(anyhow_msg)?;
+    assert!(!test
+        .io
+        .oplog
+        .lock()
+        .iter()
+        .any(|op| matches!(op, MockIoOp::Download(..))));
+
+    let err = runner.build_or_run("bad").await.unwrap_err();
+    let Error::ChecksumMismatch(mismatch, Some(_)) = err.error else {
+        panic!("expected a checksum mismatch");
+    };
+    assert_eq!(mismatch.actual, digest);
+    assert!(!test.io.contains_file(test.output_path(["bad.txt"])));
+    assert!(!test.io.contains_file(test.output_path(["bad.txt.part"])));
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_extract() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(
+        r#"
+build "sdk" {
+    from "sdk.tar.gz"
+    run extract "{in}" to "{out}"
+}
+
+task broken {
+    run extract "broken.zip" to "broken"
+}
+"#,
+    )
+    .map_err(anyhow_msg)?;
+    test.set_workspace_file(&["sdk.tar.gz"], "sdk-1.0")?;
+    test.set_workspace_file(&["broken.zip"], "not an archive")?;
+    test.io.set_archive(
+        "sdk-1.0",
+        [("bin/tool", "#!/bin/sh"), ("include/sdk.h", "int sdk;")],
+    );
+    // Files from a previous extraction are removed.
+    let stale = test.output_path(["sdk", "old.h"]);
+    werk_runner::Io::create_parent_dirs(&*test.io, &stale)?;
+    werk_runner::Io::write_file(&*test.io, &stale, b"int old;")?;
+    test.io.tick();
+
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("sdk").await.map_err(anyhow_msg)?;
+
+    assert!(test.io.oplog.lock().contains(&MockIoOp::ExtractArchive(
+        test.workspace_path(["sdk.tar.gz"]),
+        test.output_path(["sdk"]),
+    )));
+    assert!(test
+        .io
+        .contains_file(test.output_path(["sdk", "bin", "tool"])));
+    assert!(test
+        .io
+        .contains_file(test.output_path(["sdk", "include", "sdk.h"])));
+    assert!(!test.io.contains_file(&stale));
+
+    // An archive in an unknown format fails the recipe.
+    let err = runner.build_or_run("broken").await.unwrap_err();
+    assert!(matches!(err.error, Error::ExtractFailed(_, _, Some(_))));
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_verify_outputs() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(
+        r#"
+build "%.txt" {
+    run write "{%}" to "{out}"
+}
+
+#[stamp]
+build "setup.stamp" {
+    run write "" to "{out}"
+}
+
+task build {
+    build ["a.txt", "b.txt", "c.txt", "setup.stamp"]
+}
+"#,
+    )
+    .map_err(anyhow_msg)?;
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("build").await.map_err(anyhow_msg)?;
+    std::mem::drop(runner);
+    workspace.finalize().await.map_err(anyhow_msg)?;
+
+    werk_runner::Io::write_file(&*test.io, &test.output_path(["b.txt"]), b"tampered")?;
+    test.io.delete_file(test.output_path(["c.txt"]))?;
+
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let path = |path: &str| werk_fs::Absolute::try_from(path).unwrap();
+    assert_eq!(
+        workspace.verify_outputs(),
+        [
+            (path("/a.txt"), OutputStatus::Unchanged),
+            (path("/b.txt"), OutputStatus::Modified),
+            (path("/c.txt"), OutputStatus::Missing),
+        ]
+    );
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_reproducible() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(
+        r#"
+config reproducible = true
+
+build "secret.txt" {
+    run "write-env WERK_TEST_SECRET <out>"
+}
+
+build "epoch.txt" {
+    run "write-env SOURCE_DATE_EPOCH <out>"
+}
+
+build "own.txt" {
+    env "WERK_TEST_OWN" = "own"
+    run "write-env WERK_TEST_OWN <out>"
+}
+
+task build {
+    build ["secret.txt", "epoch.txt", "own.txt"]
+}
+"#,
+    )
+    .map_err(anyhow_msg)?;
+    test.io.set_env("WERK_TEST_SECRET", "hunter2");
+    let mut settings = test.workspace_settings();
+    settings.force_all();
+    let workspace = test
+        .create_workspace_with_settings(&settings)
+        .map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("build").await.map_err(anyhow_msg)?;
+    std::mem::drop(runner);
+
+    {
+        let fs = test.io.filesystem.lock();
+        let contents = |file: &str| {
+            let (_, data) = read_fs(&fs, &native_path(["workspace", "output", file])).unwrap();
+            String::from_utf8(data.to_vec()).unwrap()
+        };
+        assert_eq!(contents("secret.txt"), "");
+        assert_eq!(
+            contents("epoch.txt"),
+            werk_runner::DEFAULT_SOURCE_DATE_EPOCH
+        );
+        assert_eq!(contents("own.txt"), "own");
+    }
+
+    // Forced recipes run again after forgetting the builds of the workspace.
+    let hashes = workspace.output_hashes();
+    assert_eq!(hashes.len(), 3);
+    test.io.clear_oplog();
+    workspace.forget_builds();
+    let runner = werk_runner::Runner::new(&workspace);
+    runner.build_or_run("build").await.map_err(anyhow_msg)?;
+    assert!(test
+        .io
+        .oplog
+        .lock()
+        .iter()
+        .any(|op| matches!(op, MockIoOp::RunDuringBuild(_))));
+    assert_eq!(workspace.output_hashes(), hashes);
+    Ok(())
+}
+
+// Remote hosts are Unix machines, and the expected command lines use Unix
+// paths.
+#[cfg(unix)]
+#[apply(smol_macros::test)]
+async fn test_remote_host() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(
+        r#"
+config host.builder1 = "ci@builder1:/srv/werk/"
+
+build "%.o" {
+    host "builder1"
+    from "{%}.c"
+    run "clang -c <in> -o <out>"
+}
+"#,
+    )
+    .map_err(anyhow_msg)?;
+    test.set_workspace_file(&["main.c"], "int main() {}")?;
+    test.io
+        .set_program("ssh", program_path("ssh"), |_cmd, _fs, _env| {
+            Ok(empty_program_output())
+        });
+    // Copying the output back creates it locally.
+    test.io
+        .set_program("rsync", program_path("rsync"), |cmd, fs, _env| {
+            if cmd.arguments[1] == "--no-times" {
+                let file = std::path::Path::new(cmd.arguments[3].as_str());
+                insert_fs(
+                    fs,
+                    file,
+                    (
+                        werk_runner::Metadata {
+                            mtime: make_mtime(2),
+                            is_file: true,
+                            is_symlink: false,
+                        },
+                        b"remote".into(),
+                    ),
+                )
+                .unwrap();
+            }
+            Ok(empty_program_output())
+        });
+
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(werk_fs::Path::new("main.o")?)
+        .await
+        .map_err(anyhow_msg)?;
+
+    let ssh = |script: &str| werk_runner::ShellCommandLine {
+        program: program_path("ssh"),
+        arguments: vec![
+            "-o".into(),
+            "BatchMode=yes".into(),
+            "ci@builder1".into(),
+            script.into(),
+        ],
+    };
+    let rsync = |arguments: &[&str]| werk_runner::ShellCommandLine {
+        program: program_path("rsync"),
+        arguments: arguments.iter().map(|arg| String::from(*arg)).collect(),
+    };
+    assert!(test.did_run_during_build(&ssh("mkdir -p /srv/werk/output /srv/werk/workspace")));
+    assert!(test.did_run_during_build(&rsync(&[
+        "-as",
+        "-R",
+        "/workspace/./main.c",
+        "ci@builder1:/srv/werk/workspace/"
+    ])));
+    assert!(test.did_run_during_build(&ssh(
+        "cd /srv/werk/workspace && clang -c /srv/werk/workspace/main.c -o /srv/werk/output/main.o"
+    )));
+    assert!(test.did_run_during_build(&rsync(&[
+        "-as",
+        "--no-times",
+        "ci@builder1:/srv/werk/output/main.o",
+        "/workspace/output/main.o"
+    ])));
+    assert!(!test.did_run_during_build(&werk_runner::ShellCommandLine {
+        program: program_path("clang"),
+        arguments: vec![
+            "-c".into(),
+            "/workspace/main.c".into(),
+            "-o".into(),
+            "/workspace/output/main.o".into(),
+        ],
+    }));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[apply(smol_macros::test)]
+async fn test_remote_host_builtin() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    // `write` would create the file locally, where `run` on the host can't
+    // see it.
+    let test = Test::new(
+        r#"
+config host.builder1 = "ci@builder1:/srv/werk/"
+
+build "main.o" {
+    host "builder1"
+    run {
+        write "int x;" to "main.c"
+        "clang -c main.c -o <out>"
+    }
+}
+"#,
+    )
+    .map_err(anyhow_msg)?;
+
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    let err = runner
+        .build_file(werk_fs::Path::new("main.o")?)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.error,
+        Error::Eval(werk_runner::EvalError::BuiltinOnHost(_))
+    ));
+    assert!(!test
+        .io
+        .oplog
+        .lock()
+        .iter()
+        .any(|op| matches!(op, MockIoOp::RunDuringBuild(_))));
+    Ok(())
+}
+
+#[apply(smol_macros::test)]
+async fn test_hash_output_file() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(
+        r#"
+config hash-algorithm = "sha256"
+
+build "data.txt" {
+    run {
+        write "hello" to "data.txt"
+    }
+}
+
+build "data.sha256" {
+    let digest = hash "data.txt"
+    run {
+        write digest to "data.sha256"
+    }
+}
+"#,
+    )
+    .map_err(anyhow_msg)?;
+
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(werk_fs::Path::new("data.txt")?)
+        .await
+        .map_err(anyhow_msg)?;
+    // "data.txt" only exists in the output directory.
+    runner
+        .build_file(werk_fs::Path::new("data.sha256")?)
+        .await
+        .map_err(anyhow_msg)?;
+
+    let fs = test.io.filesystem.lock();
+    let (_, digest) = read_fs(&fs, &native_path(["workspace", "output", "data.sha256"]))?;
+    assert_eq!(
+        digest,
+        b"2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+    );
+    Ok(())
+}
+
+// Containers are only supported on Unix, and the expected command line uses
+// Unix paths.
+#[cfg(unix)]
+#[apply(smol_macros::test)]
+async fn test_container() -> anyhow::Result<()> {
+    _ = tracing_subscriber::fmt::try_init();
+
+    let test = Test::new(
+        r#"
+config container-runtime = "podman"
+config container-pull = "always"
+
+build "%.o" {
+    container "alpine:3.21"
+    from "{%}.c"
+    env "CFLAGS" = "-O2"
+    run "cc -c <in> -o <out>"
+}
+"#,
+    )
+    .map_err(anyhow_msg)?;
+    test.set_workspace_file(&["main.c"], "int main() {}")?;
+    test.io
+        .set_program("podman", program_path("podman"), |_cmd, _fs, _env| {
+            Ok(empty_program_output())
+        });
+
+    let workspace = test.create_workspace(&[]).map_err(anyhow_msg)?;
+    let runner = werk_runner::Runner::new(&workspace);
+    runner
+        .build_file(werk_fs::Path::new("main.o")?)
+        .await
+        .map_err(anyhow_msg)?;
+
+    // `cc` is not installed on the host, and is looked up in the image.
+    let arguments = [
+        "run",
+        "--rm",
+        "--pull=always",
+        "--userns=keep-id",
+        "-v",
+        "/workspace:/workspace",
+        "-w",
+        "/workspace",
+        "-e",
+        "CFLAGS=-O2",
+        "alpine:3.21",
+        "/usr/bin/env",
+        "cc",
+        "-c",
+        "/workspace/main.c",
+        "-o",
+        "/workspace/output/main.o",
+    ];
+    assert!(test.did_run_during_build(&werk_runner::ShellCommandLine {
+        program: program_path("podman"),
+        arguments: arguments.iter().map(|arg| String::from(*arg)).collect(),
+    }));
+    Ok(())
+}
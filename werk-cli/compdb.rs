@@ -0,0 +1,111 @@
+//! Generate a clang compilation database (`compile_commands.json`).
+//!
+//! Build recipes are resolved to their command lines without running them. A
+//! command is included when one of its arguments is the path to a C-like
+//! source file that the recipe depends on.
+
+use std::path::Path;
+
+use werk_runner::{Plan, PlanNodeKind, RunCommand, TaskId, Workspace};
+
+use crate::Error;
+
+/// File extensions of source files that clang tooling understands.
+const SOURCE_EXTENSIONS: &[&str] = &[
+    "c", "cc", "cpp", "cxx", "c++", "m", "mm", "cu", "C", "i", "ii",
+];
+
+/// An entry in a compilation database, as specified by
+/// <https://clang.llvm.org/docs/JSONCompilationDatabase.html>.
+#[derive(Debug, serde::Serialize)]
+pub struct CompileCommand {
+    pub directory: String,
+    pub file: String,
+    pub arguments: Vec<String>,
+    pub output: String,
+}
+
+/// Run the `werk compdb` command.
+pub fn run(workspace: &Workspace<'_>, plans: &[Plan], output: Option<&Path>) -> Result<(), Error> {
+    let commands = compile_commands(workspace, plans);
+    let mut json = serde_json::to_string_pretty(&commands).map_err(std::io::Error::from)?;
+    json.push('\n');
+
+    match output {
+        Some(path) if path == Path::new("-") => print!("{json}"),
+        Some(path) => std::fs::write(path, json)?,
+        None => {
+            let path = Path::join(workspace.project_root(), "compile_commands.json");
+            std::fs::write(&path, json)?;
+            eprintln!("Wrote {} entries to {}", commands.len(), path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Find the commands that compile C-like source files in the plans.
+#[must_use]
+pub fn compile_commands(workspace: &Workspace<'_>, plans: &[Plan]) -> Vec<CompileCommand> {
+    let directory = workspace.project_root().display().to_string();
+    let mut seen = ahash::HashSet::default();
+    let mut entries = Vec::new();
+
+    for node in plans.iter().flat_map(Plan::iter) {
+        if node.kind != PlanNodeKind::Build || !seen.insert(node.task_id) {
+            continue;
+        }
+        let Some(output) = os_path(workspace, node.task_id) else {
+            continue;
+        };
+
+        let sources = node
+            .dependencies
+            .iter()
+            .filter(|dependency| {
+                dependency.as_path().is_some_and(|path| {
+                    path.extension()
+                        .is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext))
+                })
+            })
+            .filter_map(|dependency| os_path(workspace, *dependency))
+            .collect::<Vec<_>>();
+        if sources.is_empty() {
+            continue;
+        }
+
+        for command in &node.commands {
+            let RunCommand::Shell(command_line) = command else {
+                continue;
+            };
+            let Some(file) = sources
+                .iter()
+                .find(|source| command_line.arguments.contains(source))
+            else {
+                continue;
+            };
+
+            let mut arguments = Vec::with_capacity(command_line.arguments.len() + 1);
+            arguments.push(command_line.program.display().to_string());
+            arguments.extend(command_line.arguments.iter().cloned());
+            entries.push(CompileCommand {
+                directory: directory.clone(),
+                file: file.clone(),
+                arguments,
+                output: output.clone(),
+            });
+        }
+    }
+
+    entries
+}
+
+/// The native OS path of a file in the plan, either in the workspace or the
+/// output directory.
+fn os_path(workspace: &Workspace<'_>, task_id: TaskId) -> Option<String> {
+    let path = task_id.as_path()?;
+    let os_path = match workspace.get_project_file(path) {
+        Some(entry) => entry.path.clone().into_inner(),
+        None => workspace.get_output_file_path(path).ok()?.into_inner(),
+    };
+    Some(os_path.display().to_string())
+}
@@ -1,5 +1,5 @@
 use clap::{CommandFactory, FromArgMatches};
-use clap_complete::CompletionCandidate;
+use clap_complete::{CompletionCandidate, Shell};
 use werk_fs::Normalize;
 use werk_runner::Workspace;
 
@@ -18,32 +18,58 @@ fn with_werk<T: Default>(f: impl FnOnce(Workspace<'_>) -> Result<T, anyhow::Erro
             .ignore_errors(true)
             .try_get_matches_from(args)?;
         let args = Args::from_arg_matches(&arg_matches)?;
+        load_workspace(&args, f)
+    })();
 
-        let werkfile = match &args.file {
-            Some(file) => file.clone().normalize()?,
-            _ => find_werkfile()?,
-        };
+    result.unwrap_or_default()
+}
 
-        let source_code = std::fs::read_to_string(&werkfile)?;
-        let ast = werk_parser::parse_werk(&werkfile, &source_code)?;
-        let config = werk_runner::ir::Config::new(&ast)?;
+fn load_workspace<T>(
+    args: &Args,
+    f: impl FnOnce(Workspace<'_>) -> Result<T, anyhow::Error>,
+) -> Result<T, anyhow::Error> {
+    let werkfile = match &args.file {
+        Some(file) => file.clone().normalize()?,
+        _ => find_werkfile()?,
+    };
 
-        let io = DryRun::new();
-        let renderer = NullRender;
+    let source_code = std::fs::read_to_string(&werkfile)?;
+    let ast = werk_parser::parse_werk(&werkfile, &source_code)?;
+    let config = werk_runner::ir::Config::new(&ast)?;
 
-        let workspace_dir = get_workspace_dir(&args, &werkfile)?;
-        let settings =
-            get_workspace_settings(&config, &args, &workspace_dir, ColorOutputKind::Never)?;
+    let io = DryRun::new();
+    let renderer = NullRender;
 
-        let workspace =
-            Workspace::new(&ast, &io, &renderer, workspace_dir.into_owned(), &settings)?;
+    let workspace_dir = get_workspace_dir(args, &werkfile)?;
+    let settings = get_workspace_settings(&config, args, &workspace_dir, ColorOutputKind::Never)?;
 
-        let result = f(workspace)?;
+    let workspace = Workspace::new(&ast, &io, &renderer, workspace_dir.into_owned(), &settings)?;
 
-        Ok(result)
-    })();
+    f(workspace)
+}
 
-    result.unwrap_or_default()
+/// Print the names of all tasks and non-pattern build targets, one per line.
+/// Errors are ignored, because this is only used to drive shell completion.
+pub fn print_targets(args: &Args, out: &mut dyn std::io::Write) {
+    let targets = load_workspace(args, |workspace| {
+        let tasks = workspace
+            .manifest
+            .task_recipes
+            .keys()
+            .map(|name| (*name).to_owned());
+        let builds = workspace
+            .manifest
+            .build_recipes
+            .iter()
+            .filter(|recipe| recipe.pattern.fragments().is_none())
+            .map(|recipe| recipe.pattern.string.clone());
+        Ok(tasks.chain(builds).collect::<Vec<_>>())
+    })
+    .unwrap_or_default();
+
+    for target in targets {
+        _ = writeln!(out, "{target}");
+    }
 }
 
 pub fn targets() -> Vec<CompletionCandidate> {
@@ -68,6 +94,20 @@ pub fn targets() -> Vec<CompletionCandidate> {
     })
 }
 
+pub fn tasks() -> Vec<CompletionCandidate> {
+    with_werk(|workspace| {
+        let tasks = workspace
+            .manifest
+            .task_recipes
+            .into_iter()
+            .map(|(name, recipe)| {
+                CompletionCandidate::new(name).help(Some(recipe.doc_comment.into()))
+            });
+
+        Ok(tasks.collect())
+    })
+}
+
 pub fn defines() -> Vec<CompletionCandidate> {
     with_werk(|workspace| {
         let defines = workspace.manifest.globals.iter().map(|(symbol, global)| {
@@ -78,3 +118,92 @@ pub fn defines() -> Vec<CompletionCandidate> {
         Ok(defines.collect())
     })
 }
+
+/// The profiles declared in the Werkfile. The Werkfile is only parsed, so
+/// completion works even when the profile being completed is incomplete.
+pub fn profiles() -> Vec<CompletionCandidate> {
+    let result = (|| -> Result<Vec<CompletionCandidate>, anyhow::Error> {
+        let werkfile = find_werkfile()?;
+        let source_code = std::fs::read_to_string(&werkfile)?;
+        let ast = werk_parser::parse_werk(&werkfile, &source_code)?;
+        Ok(ast
+            .profile_stmts()
+            .map(|profile| CompletionCandidate::new(profile.name.ident.as_str()))
+            .collect())
+    })();
+
+    result.unwrap_or_default()
+}
+
+/// Print a static completion script for `shell`. The generated script is
+/// extended to complete the target argument with the output of `werk
+/// __complete`, so the list of targets is always up to date with the Werkfile.
+pub fn print_completion_script(shell: Shell, out: &mut dyn std::io::Write) {
+    let bin_name = env!("CARGO_BIN_NAME");
+    let mut command = Args::command().name(bin_name);
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut command, bin_name, &mut script);
+    let script = String::from_utf8(script).expect("completion script is not UTF-8");
+    _ = out.write_all(inject_target_completion(shell, script, bin_name).as_bytes());
+}
+
+/// Hook `werk __complete` into the completion of the positional target
+/// argument. If the generated script does not have the expected shape, it is
+/// returned unchanged.
+fn inject_target_completion(shell: Shell, mut script: String, bin_name: &str) -> String {
+    let list_targets = format!("{bin_name} __complete");
+
+    match shell {
+        Shell::Bash => {
+            // The options of the root command are completed from a variable
+            // that is evaluated at completion time.
+            let root_opts = format!("\n        {bin_name})\n            opts=\"");
+            if let Some(pos) = script.find(&root_opts) {
+                script.insert_str(
+                    pos + root_opts.len(),
+                    &format!("$({list_targets} 2>/dev/null) "),
+                );
+            }
+        }
+        Shell::Zsh => {
+            let target_spec = "'::target -- The target to build:_default'";
+            let compdef = format!("\nif [ \"$funcstack[1]\" = \"_{bin_name}\" ]; then");
+            if script.contains(target_spec) && script.contains(&compdef) {
+                script = script.replace(
+                    target_spec,
+                    &format!("'::target -- The target to build:_{bin_name}_targets'"),
+                );
+                script = script.replace(
+                    &compdef,
+                    &format!(
+                        "\n(( $+functions[_{bin_name}_targets] )) ||\n\
+                         _{bin_name}_targets() {{\n    \
+                         compadd -- ${{(f)\"$({list_targets} 2>/dev/null)\"}}\n\
+                         }}\n{compdef}"
+                    ),
+                );
+            }
+        }
+        Shell::Fish => {
+            script.push_str(&format!(
+                "complete -c {bin_name} -n \"__fish_{bin_name}_needs_command\" -f -a \"({list_targets} 2>/dev/null)\"\n"
+            ));
+        }
+        Shell::PowerShell => {
+            let root_case = format!("\n        '{bin_name}' {{\n");
+            if let Some(pos) = script.find(&root_case) {
+                script.insert_str(
+                    pos + root_case.len(),
+                    &format!(
+                        "            {list_targets} 2>$null | ForEach-Object {{\n                \
+                         [CompletionResult]::new($_, $_, [CompletionResultType]::ParameterValue, $_)\n            \
+                         }}\n"
+                    ),
+                );
+            }
+        }
+        _ => (),
+    }
+
+    script
+}
@@ -0,0 +1,316 @@
+//! Diagnose the environment that `werk` runs in, for bug reports and for
+//! debugging builds in CI.
+//!
+//! Checks that the output directory is writable, that the clocks of the
+//! source and output filesystems agree, and that the programs used by the
+//! Werkfile can be found. Terminal capabilities are reported as-is.
+
+use std::{
+    io::{IsTerminal as _, Write},
+    time::{Duration, SystemTime},
+};
+
+use owo_colors::OwoColorize as _;
+use werk_runner::Workspace;
+
+use crate::{
+    dry_run,
+    render::{stderr_width, ColorOutputKind, Taskbar, TtyWidth},
+    Error,
+};
+
+/// Clock differences smaller than this are within the modification time
+/// resolution of common filesystems, and are not reported.
+const CLOCK_TOLERANCE: Duration = Duration::from_secs(2);
+
+/// Name of the file written to the output directory to check that it is
+/// writable. It is removed again immediately.
+const PROBE_FILE_NAME: &str = ".werk-doctor";
+
+enum Status {
+    Info,
+    Ok,
+    Warn,
+    Fail,
+}
+
+struct Report<'a> {
+    out: &'a mut dyn Write,
+    num_problems: usize,
+}
+
+impl Report<'_> {
+    fn section(&mut self, title: &str) -> std::io::Result<()> {
+        writeln!(self.out, "{}", title.bright_purple())
+    }
+
+    fn line(&mut self, status: Status, message: std::fmt::Arguments) -> std::io::Result<()> {
+        match status {
+            Status::Info => write!(self.out, "  {}  ", "    ".dimmed())?,
+            Status::Ok => write!(self.out, "  {}  ", "ok  ".bright_green())?,
+            Status::Warn => write!(self.out, "  {}  ", "warn".bright_yellow())?,
+            Status::Fail => {
+                self.num_problems += 1;
+                write!(self.out, "  {}  ", "FAIL".bright_red().bold())?;
+            }
+        }
+        writeln!(self.out, "{message}")
+    }
+}
+
+/// Run the `werk doctor` command. Fails if any check fails; warnings and
+/// terminal capabilities are only reported.
+pub fn run(
+    workspace: &Workspace<'_>,
+    color_stderr: ColorOutputKind,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let mut report = Report {
+        out,
+        num_problems: 0,
+    };
+
+    writeln!(
+        report.out,
+        "werk {} ({} {})",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )?;
+    writeln!(
+        report.out,
+        "{} {}",
+        "Werkfile:".dimmed(),
+        workspace.werkfile_path.display()
+    )?;
+    writeln!(
+        report.out,
+        "{} {}",
+        "Output directory:".dimmed(),
+        workspace.output_directory().display()
+    )?;
+
+    check_terminal(&mut report, color_stderr)?;
+    let probe_mtime = check_output_directory(&mut report, workspace)?;
+    check_clocks(&mut report, workspace, probe_mtime)?;
+    check_programs(&mut report, workspace)?;
+
+    if report.num_problems == 0 {
+        writeln!(report.out, "{}", "No problems found".bright_green())?;
+        Ok(())
+    } else {
+        Err(Error::Problems(report.num_problems))
+    }
+}
+
+fn check_terminal(report: &mut Report, color_stderr: ColorOutputKind) -> std::io::Result<()> {
+    report.section("Terminal")?;
+    let yes_no = |is_terminal: bool| {
+        if is_terminal {
+            "a terminal"
+        } else {
+            "not a terminal"
+        }
+    };
+    report.line(
+        Status::Info,
+        format_args!("stdout is {}", yes_no(std::io::stdout().is_terminal())),
+    )?;
+    report.line(
+        Status::Info,
+        format_args!("stderr is {}", yes_no(std::io::stderr().is_terminal())),
+    )?;
+    match stderr_width() {
+        TtyWidth::NoTty => (),
+        TtyWidth::Guess(width) => report.line(
+            Status::Info,
+            format_args!("terminal width is unknown, assuming {width} columns"),
+        )?,
+        TtyWidth::Known(width) => {
+            report.line(
+                Status::Info,
+                format_args!("terminal width is {width} columns"),
+            )?;
+        }
+    }
+    let term = std::env::var("TERM").unwrap_or_default();
+    report.line(
+        Status::Info,
+        format_args!(
+            "TERM is {}",
+            if term.is_empty() { "not set" } else { &term }
+        ),
+    )?;
+    let (color, progress) = match color_stderr {
+        ColorOutputKind::Never => ("disabled", "disabled"),
+        ColorOutputKind::Ansi(false) => ("enabled", "disabled"),
+        ColorOutputKind::Ansi(true) => ("enabled", "enabled"),
+    };
+    report.line(Status::Info, format_args!("color output is {color}"))?;
+    report.line(Status::Info, format_args!("progress display is {progress}"))?;
+    report.line(
+        Status::Info,
+        format_args!(
+            "taskbar progress (OSC 9;4) is {}",
+            if Taskbar::is_supported() {
+                "supported"
+            } else {
+                "not supported"
+            }
+        ),
+    )
+}
+
+/// Write and remove a file in the output directory. Returns the modification
+/// time of the file, as seen by the output filesystem.
+fn check_output_directory(
+    report: &mut Report,
+    workspace: &Workspace<'_>,
+) -> std::io::Result<Option<SystemTime>> {
+    report.section("Output directory")?;
+    let out_dir = workspace.output_directory();
+    // Directories that don't exist yet, innermost first.
+    let created = out_dir
+        .ancestors()
+        .take_while(|dir| !dir.exists())
+        .collect::<Vec<_>>();
+    let probe = out_dir
+        .join(PROBE_FILE_NAME)
+        .expect("probe file name is a plain file name");
+
+    let result = std::fs::create_dir_all(out_dir)
+        .and_then(|()| std::fs::write(&probe, b"werk doctor\n"))
+        .and_then(|()| std::fs::metadata(&probe)?.modified());
+    _ = std::fs::remove_file(&probe);
+    // Leave the filesystem as it was.
+    for dir in created {
+        _ = std::fs::remove_dir(dir);
+    }
+
+    match result {
+        Ok(mtime) => {
+            report.line(
+                Status::Ok,
+                format_args!("{} is writable", out_dir.display()),
+            )?;
+            Ok(Some(mtime))
+        }
+        Err(err) => {
+            report.line(
+                Status::Fail,
+                format_args!("{} is not writable: {err}", out_dir.display()),
+            )?;
+            Ok(None)
+        }
+    }
+}
+
+/// Compare the clock of the output filesystem with the system clock, and
+/// look for source files modified in the future. Either makes outputs look
+/// older or newer than they are, so they are rebuilt too often or not at all.
+fn check_clocks(
+    report: &mut Report,
+    workspace: &Workspace<'_>,
+    probe_mtime: Option<SystemTime>,
+) -> std::io::Result<()> {
+    report.section("Clocks")?;
+    let now = SystemTime::now();
+
+    if let Some(mtime) = probe_mtime {
+        match mtime.duration_since(now) {
+            Ok(ahead) if ahead > CLOCK_TOLERANCE => report.line(
+                Status::Warn,
+                format_args!(
+                    "the output filesystem clock is {:.1}s ahead of the system clock",
+                    ahead.as_secs_f64()
+                ),
+            )?,
+            Err(err) if err.duration() > CLOCK_TOLERANCE => report.line(
+                Status::Warn,
+                format_args!(
+                    "the output filesystem clock is {:.1}s behind the system clock",
+                    err.duration().as_secs_f64()
+                ),
+            )?,
+            _ => report.line(
+                Status::Ok,
+                format_args!("the output filesystem clock agrees with the system clock"),
+            )?,
+        }
+    }
+
+    let mut num_future = 0;
+    let mut furthest: Option<(Duration, &std::path::Path)> = None;
+    for (_, entry) in workspace.workspace_files() {
+        if !entry.metadata.is_file {
+            continue;
+        }
+        let Ok(ahead) = entry.metadata.mtime.duration_since(now) else {
+            continue;
+        };
+        if ahead <= CLOCK_TOLERANCE {
+            continue;
+        }
+        num_future += 1;
+        if furthest.is_none_or(|(furthest, _)| ahead > furthest) {
+            furthest = Some((ahead, &entry.path));
+        }
+    }
+    match furthest {
+        Some((ahead, path)) => report.line(
+            Status::Warn,
+            format_args!(
+                "{num_future} source file(s) were modified in the future, up to {:.1}s ahead: {}",
+                ahead.as_secs_f64(),
+                path.display()
+            ),
+        ),
+        None => report.line(
+            Status::Ok,
+            format_args!("no source files were modified in the future"),
+        ),
+    }
+}
+
+fn check_programs(report: &mut Report, workspace: &Workspace<'_>) -> std::io::Result<()> {
+    report.section("Programs")?;
+    for dir in workspace.tool_paths() {
+        if dir.is_dir() {
+            report.line(
+                Status::Ok,
+                format_args!("tool path {} exists", dir.display()),
+            )?;
+        } else {
+            report.line(
+                Status::Warn,
+                format_args!("tool path {} is not a directory", dir.display()),
+            )?;
+        }
+    }
+
+    let programs = workspace.programs();
+    if programs.is_empty() {
+        return report.line(
+            Status::Info,
+            format_args!("the Werkfile does not look up any programs with `which`"),
+        );
+    }
+    let width = programs
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    for (name, result) in programs {
+        match result {
+            Ok(path) if dry_run::is_placeholder_program(&path) => {
+                report.line(Status::Fail, format_args!("{name:width$}  not found"))?;
+            }
+            Ok(path) => report.line(
+                Status::Ok,
+                format_args!("{name:width$}  {}", path.display()),
+            )?,
+            Err(err) => report.line(Status::Fail, format_args!("{name:width$}  {err}"))?,
+        }
+    }
+    Ok(())
+}
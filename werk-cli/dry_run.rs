@@ -1,9 +1,12 @@
 use std::{future::Future, pin::Pin};
 
 use werk_fs::Absolute;
-use werk_runner::{Child, DirEntry, Env, Error, ShellCommandLine};
+use werk_runner::{Child, DirEntry, Env, Error, Redirection, ShellCommandLine};
 
-pub struct DryRun(werk_runner::RealSystem);
+pub struct DryRun {
+    real: werk_runner::RealSystem,
+    pure: bool,
+}
 
 impl Default for DryRun {
     fn default() -> Self {
@@ -13,10 +16,41 @@ impl Default for DryRun {
 
 impl DryRun {
     pub fn new() -> Self {
-        Self(werk_runner::RealSystem::new())
+        Self {
+            real: werk_runner::RealSystem::new(),
+            pure: false,
+        }
+    }
+
+    /// Dry run that also never runs programs while evaluating the Werkfile.
+    /// `shell` expressions produce a placeholder, and programs that cannot be
+    /// found by `which` get a placeholder path, so the Werkfile can be
+    /// evaluated on machines without the toolchain.
+    pub fn pure() -> Self {
+        Self {
+            real: werk_runner::RealSystem::new(),
+            pure: true,
+        }
     }
 }
 
+/// The directory of placeholder paths for programs that were not found in
+/// pure dry-run mode.
+fn placeholder_program_dir() -> &'static Absolute<std::path::Path> {
+    let dir = if cfg!(windows) {
+        "C:\\dry-run\\bin"
+    } else {
+        "/dry-run/bin"
+    };
+    Absolute::new_ref(std::path::Path::new(dir)).expect("placeholder path is absolute")
+}
+
+/// Whether a program path is a placeholder for a program that was not found
+/// in pure dry-run mode.
+pub fn is_placeholder_program(path: &std::path::Path) -> bool {
+    path.starts_with(placeholder_program_dir())
+}
+
 #[derive(Default)]
 #[expect(clippy::box_collection, clippy::redundant_allocation)]
 struct DryRunChild {
@@ -54,6 +88,10 @@ impl Child for DryRunChild {
     {
         Box::pin(std::future::ready(Ok(std::process::ExitStatus::default())))
     }
+
+    fn kill(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
 }
 
 impl werk_runner::Io for DryRun {
@@ -62,30 +100,70 @@ impl werk_runner::Io for DryRun {
         command_line: &ShellCommandLine,
         _working_dir: &Absolute<std::path::Path>,
         _env: &Env,
+        redirection: &Redirection,
         _forward_stdout: bool,
+        _process_group: bool,
     ) -> std::io::Result<Box<dyn Child>> {
-        tracing::info!("[DRY-RUN] Would run: {}", command_line);
+        tracing::info!("[DRY-RUN] Would run: {}{}", command_line, redirection);
         Ok(Box::new(DryRunChild::default()))
     }
 
+    fn run_recipe_pipeline(
+        &self,
+        command_lines: &[ShellCommandLine],
+        _working_dir: &Absolute<std::path::Path>,
+        _env: &Env,
+        redirection: &Redirection,
+        _forward_stdout: bool,
+        _process_group: bool,
+    ) -> std::io::Result<Vec<Box<dyn Child>>> {
+        let pipeline = command_lines
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" | ");
+        tracing::info!("[DRY-RUN] Would run: {}{}", pipeline, redirection);
+        Ok(command_lines
+            .iter()
+            .map(|_| Box::new(DryRunChild::default()) as Box<dyn Child>)
+            .collect())
+    }
+
     fn run_during_eval(
         &self,
         command_line: &ShellCommandLine,
         working_dir: &Absolute<std::path::Path>,
         env: &Env,
     ) -> Result<std::process::Output, std::io::Error> {
+        if self.pure {
+            tracing::info!("[DRY-RUN] Would run during evaluation: {}", command_line);
+            return Ok(std::process::Output {
+                status: std::process::ExitStatus::default(),
+                stdout: format!("[dry-run: {command_line}]").into_bytes(),
+                stderr: Vec::new(),
+            });
+        }
+
         tracing::warn!(
             "[DRY-MODE] Running executable, despite dry-run mode: {}",
             command_line
         );
-        self.0.run_during_eval(command_line, working_dir, env)
+        self.real.run_during_eval(command_line, working_dir, env)
     }
 
     fn which(
         &self,
         command: &str,
     ) -> Result<Absolute<std::path::PathBuf>, werk_runner::WhichError> {
-        self.0.which(command)
+        match self.real.which(command) {
+            Err(_) if self.pure => {
+                tracing::info!("[DRY-RUN] Program not found, using a placeholder: {command}");
+                placeholder_program_dir()
+                    .join(command)
+                    .map_err(|_| werk_runner::WhichError::CannotFindBinaryPath)
+            }
+            result => result,
+        }
     }
 
     fn glob_workspace(
@@ -93,15 +171,15 @@ impl werk_runner::Io for DryRun {
         path: &Absolute<std::path::Path>,
         settings: &werk_runner::GlobSettings,
     ) -> Result<Vec<DirEntry>, Error> {
-        self.0.glob_workspace(path, settings)
+        self.real.glob_workspace(path, settings)
     }
 
     fn metadata(&self, path: &Absolute<std::path::Path>) -> Result<werk_runner::Metadata, Error> {
-        self.0.metadata(path)
+        self.real.metadata(path)
     }
 
     fn read_file(&self, path: &Absolute<std::path::Path>) -> Result<Vec<u8>, std::io::Error> {
-        self.0.read_file(path)
+        self.real.read_file(path)
     }
 
     fn write_file(
@@ -143,11 +221,94 @@ impl werk_runner::Io for DryRun {
         Ok(())
     }
 
+    fn create_dir(&self, path: &Absolute<std::path::Path>) -> Result<(), std::io::Error> {
+        tracing::info!("[DRY-RUN] Would create directory '{}'", path.display());
+        Ok(())
+    }
+
+    fn delete_dir(&self, path: &Absolute<std::path::Path>) -> Result<(), std::io::Error> {
+        tracing::info!("[DRY-RUN] Would delete directory '{}'", path.display());
+        Ok(())
+    }
+
+    fn touch(&self, path: &Absolute<std::path::Path>) -> Result<(), std::io::Error> {
+        tracing::info!("[DRY-RUN] Would touch file '{}'", path.display());
+        Ok(())
+    }
+
+    fn download(
+        &self,
+        url: &str,
+        path: &Absolute<std::path::Path>,
+        _resume: bool,
+    ) -> Result<(), std::io::Error> {
+        tracing::info!("[DRY-RUN] Would download '{url}' to '{}'", path.display());
+        Ok(())
+    }
+
+    fn extract_archive(
+        &self,
+        archive: &Absolute<std::path::Path>,
+        dir: &Absolute<std::path::Path>,
+    ) -> Result<(), std::io::Error> {
+        tracing::info!(
+            "[DRY-RUN] Would extract '{}' to '{}'",
+            archive.display(),
+            dir.display()
+        );
+        Ok(())
+    }
+
+    fn is_same_file(
+        &self,
+        a: &Absolute<std::path::Path>,
+        b: &Absolute<std::path::Path>,
+    ) -> Result<bool, std::io::Error> {
+        self.real.is_same_file(a, b)
+    }
+
     fn read_env(&self, name: &str) -> Option<String> {
-        self.0.read_env(name)
+        self.real.read_env(name)
     }
 
     fn is_dry_run(&self) -> bool {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use werk_runner::Io as _;
+
+    use super::*;
+
+    #[test]
+    fn pure_never_runs_programs() {
+        let io = DryRun::pure();
+        let program = io.which("definitely-not-a-program-werk").unwrap();
+        assert_eq!(
+            program,
+            placeholder_program_dir()
+                .join("definitely-not-a-program-werk")
+                .unwrap()
+        );
+
+        let command_line = ShellCommandLine {
+            program: program.clone(),
+            arguments: vec!["--version".into()],
+        };
+        let output = io
+            .run_during_eval(&command_line, placeholder_program_dir(), &Env::default())
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            format!("[dry-run: {command_line}]")
+        );
+
+        // Only pure dry runs use placeholders.
+        assert!(DryRun::new()
+            .which("definitely-not-a-program-werk")
+            .is_err());
+    }
+}
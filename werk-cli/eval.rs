@@ -0,0 +1,114 @@
+//! Evaluate expressions in the root scope of the Werkfile, to debug
+//! expressions and patterns without adding them to the Werkfile.
+
+use std::{
+    io::{BufRead as _, IsTerminal as _, Write},
+    path::Path,
+};
+
+use owo_colors::OwoColorize as _;
+use werk_runner::{eval, RootScope, Value, Workspace};
+use werk_util::{Diagnostic as _, DiagnosticSource};
+
+use crate::{print_eval_error, print_parse_error, Error};
+
+/// Shown as the file name in diagnostics about the expression.
+const ORIGIN: &str = "<eval>";
+
+/// Run the `werk eval` command. Without an expression, expressions are read
+/// from stdin, one per line, until the end of input.
+pub fn run(
+    workspace: &Workspace<'_>,
+    expr: Option<&str>,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    if let Some(expr) = expr {
+        return eval_and_print(workspace, expr, out);
+    }
+
+    let interactive = std::io::stdin().is_terminal();
+    let mut line = String::new();
+    loop {
+        if interactive {
+            eprint!("{} ", ">".bright_purple());
+        }
+        line.clear();
+        if std::io::stdin().lock().read_line(&mut line)? == 0 {
+            if interactive {
+                eprintln!();
+            }
+            return Ok(());
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        // Errors are printed, and evaluation continues with the next line.
+        match eval_and_print(workspace, &line, out) {
+            Ok(()) | Err(Error::Parse | Error::Eval) => (),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn eval_and_print(
+    workspace: &Workspace<'_>,
+    source: &str,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let source_ref = DiagnosticSource::new(Path::new(ORIGIN), source);
+    let expr = werk_parser::parse_expr(source)
+        .map_err(|err| print_parse_error(err.into_diagnostic_error(source_ref)))?;
+    let scope = RootScope::new(workspace);
+    let value = eval::eval_chain(&scope, &expr)
+        .map_err(|err| print_eval_error(err.into_diagnostic_error(source_ref)))?;
+    write_value(out, &workspace.redact_value(&value.value), 0)?;
+    writeln!(out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Write a value in the syntax of the Werkfile language, with one list item
+/// per line.
+fn write_value(out: &mut dyn Write, value: &Value, indent: usize) -> std::io::Result<()> {
+    match value {
+        Value::List(items) if items.is_empty() => write!(out, "[]"),
+        Value::List(items) => {
+            writeln!(out, "[")?;
+            for item in items {
+                write!(out, "{:w$}", "", w = indent + 2)?;
+                write_value(out, item, indent + 2)?;
+                writeln!(out, ",")?;
+            }
+            write!(out, "{:w$}]", "", w = indent)
+        }
+        Value::String(string) => write!(
+            out,
+            "{}",
+            format_args!("\"{}\"", string.escape_default()).bright_green()
+        ),
+        // Abstract paths, like the results of `glob`.
+        Value::Path(path) => write!(
+            out,
+            "{}",
+            format_args!("\"{}\"", path.as_str().escape_default()).bright_cyan()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tests::mock_io::Test;
+
+    #[test]
+    fn redacts_secrets() -> anyhow::Result<()> {
+        let test = Test::new(r#"let token = "hunter2SECRET" | secret"#)?;
+        let workspace = test.create_workspace(&[]).unwrap();
+        let mut out = Vec::new();
+        super::eval_and_print(&workspace, "token", &mut out)?;
+        super::eval_and_print(&workspace, r#"["Bearer {token}"]"#, &mut out)?;
+        let out = String::from_utf8(out)?;
+        assert!(!out.contains("hunter2SECRET"), "{out}");
+        assert!(out.contains("Bearer ***"), "{out}");
+        Ok(())
+    }
+}
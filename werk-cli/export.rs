@@ -0,0 +1,23 @@
+//! Generate build files for other build systems from a Werkfile.
+
+pub mod ninja;
+
+use werk_runner::{Plan, Workspace};
+
+use crate::{Error, ExportFormat};
+
+/// Run the `werk export` command.
+///
+/// The plans are resolved without running any recipe commands, so only
+/// dependencies that are known before building (including existing depfiles)
+/// are exported.
+pub fn run(workspace: &Workspace<'_>, plans: &[Plan], format: &ExportFormat) -> Result<(), Error> {
+    let ExportFormat::Ninja { output, .. } = format;
+    let exported = ninja::export_ninja(workspace, plans);
+
+    match output {
+        Some(path) => std::fs::write(path, exported)?,
+        None => print!("{exported}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,377 @@
+//! Export build plans as a Ninja build file.
+//!
+//! Every planned task and build recipe becomes a `build` statement using a
+//! single generic rule, with the fully evaluated command line stored in a
+//! variable of the build statement. Commands are run through a POSIX shell by
+//! Ninja, so the built-in recipe commands (`write`, `copy`, `delete`, and `env`)
+//! are translated to their shell equivalents.
+
+use std::fmt::Write as _;
+
+use ahash::HashSet;
+use werk_runner::{
+    Env, Plan, PlanNode, PlanNodeKind, RunCommand, ShellCommandLine, TaskId, Workspace,
+};
+
+/// Generate the source code of a `build.ninja` file that builds the roots of
+/// `plans`.
+#[must_use]
+pub fn export_ninja(workspace: &Workspace<'_>, plans: &[Plan]) -> String {
+    let mut out = String::new();
+    out.push_str("# Generated by `werk export ninja`. Do not edit.\n\n");
+    out.push_str("rule werk\n");
+    out.push_str("  command = $cmd\n");
+    out.push_str("  description = $desc\n");
+    out.push_str("  depfile = $depfile\n");
+
+    let mut visited = HashSet::default();
+    for node in plans.iter().flat_map(Plan::iter) {
+        if !visited.insert(node.task_id) {
+            continue;
+        }
+        write_node(&mut out, workspace, node);
+    }
+
+    let mut defaults = Vec::new();
+    for plan in plans {
+        if let Some(path) = node_path(workspace, plan.root) {
+            if !defaults.contains(&path) {
+                defaults.push(path);
+            }
+        }
+    }
+    if !defaults.is_empty() {
+        _ = writeln!(out, "\ndefault {}", defaults.join(" "));
+    }
+
+    out
+}
+
+fn write_node(out: &mut String, workspace: &Workspace<'_>, node: &PlanNode) {
+    match node.kind {
+        PlanNodeKind::Task | PlanNodeKind::Build => (),
+        // Source files are referenced directly as inputs. Files that are only
+        // known from a stale depfile are left out, because Ninja would refuse
+        // to build anything that depends on them.
+        PlanNodeKind::Exists(_) | PlanNodeKind::Missing => return,
+    }
+
+    let Some(output) = node_path(workspace, node.task_id) else {
+        return;
+    };
+    let inputs = node
+        .dependencies
+        .iter()
+        .filter_map(|dependency| node_path(workspace, *dependency))
+        .collect::<Vec<_>>();
+
+    out.push('\n');
+    if node.commands.is_empty() {
+        // Task recipes without commands only group their dependencies.
+        _ = write!(out, "build {output}: phony");
+        for input in &inputs {
+            _ = write!(out, " {input}");
+        }
+        out.push('\n');
+        return;
+    }
+
+    let (command, description) = shell_command(workspace, &node.env, &node.commands);
+    _ = write!(out, "build {output}: werk");
+    for input in &inputs {
+        _ = write!(out, " {input}");
+    }
+    out.push('\n');
+    _ = writeln!(out, "  cmd = {}", escape_value(&command));
+    let description = description.unwrap_or_else(|| match node.task_id {
+        TaskId::Task(_) => format!("task {}", node.task_id),
+        TaskId::Build(_) => format!("build {}", node.task_id),
+    });
+    _ = writeln!(out, "  desc = {}", escape_value(&description));
+    if let Some(depfile) = node
+        .depfile
+        .as_ref()
+        .and_then(|depfile| workspace.get_output_file_path(depfile).ok())
+    {
+        _ = writeln!(
+            out,
+            "  depfile = {}",
+            escape_value(&depfile.display().to_string())
+        );
+    }
+}
+
+/// The Ninja path of a node in the plan: the name of task recipes, or the
+/// native OS path of files.
+fn node_path(workspace: &Workspace<'_>, task_id: TaskId) -> Option<String> {
+    match task_id {
+        TaskId::Task(name) => Some(escape_path(name.as_str())),
+        TaskId::Build(_) => {
+            let path = task_id.as_path()?;
+            let os_path = match workspace.get_project_file(path) {
+                Some(entry) => entry.path.clone().into_inner(),
+                None => workspace.get_output_file_path(path).ok()?.into_inner(),
+            };
+            Some(escape_path(&os_path.display().to_string()))
+        }
+    }
+}
+
+/// Translate the commands of a recipe to a single shell command line, along
+/// with the first `info` message, which is used as the description.
+fn shell_command(
+    workspace: &Workspace<'_>,
+    recipe_env: &Env,
+    commands: &[RunCommand],
+) -> (String, Option<String>) {
+    let mut env = recipe_env.clone();
+    let mut description = None;
+    let mut steps = Vec::new();
+
+    for command in commands {
+        match command {
+            RunCommand::Shell(command_line) => {
+                steps.push(shell_step(&env, command_line));
+            }
+            RunCommand::Pipeline(command_lines, redirection) => {
+                let mut step = String::new();
+                for (i, command_line) in command_lines.iter().enumerate() {
+                    if i != 0 {
+                        step.push_str(" | ");
+                    }
+                    step.push_str(&shell_step(&env, command_line));
+                    if let (0, Some(stdin)) = (i, &redirection.stdin) {
+                        _ = write!(step, " < {}", shell_quote(&stdin.display().to_string()));
+                    }
+                }
+                if let Some(ref stdout) = redirection.stdout {
+                    _ = write!(step, " > {}", shell_quote(&stdout.display().to_string()));
+                }
+                steps.push(step);
+            }
+            RunCommand::Write(path, data) => {
+                steps.push(format!(
+                    "printf '{}' > {}",
+                    printf_escape(data),
+                    shell_quote(&path.display().to_string())
+                ));
+            }
+            RunCommand::Copy(from, to) => {
+                let from = match workspace.get_project_file(from) {
+                    Some(entry) => entry.path.clone().into_inner(),
+                    None => match workspace.get_output_file_path(from) {
+                        Ok(path) => path.into_inner(),
+                        Err(_) => continue,
+                    },
+                };
+                steps.push(format!(
+                    "cp {} {}",
+                    shell_quote(&from.display().to_string()),
+                    shell_quote(&to.display().to_string())
+                ));
+            }
+            RunCommand::Extract(archive, dir) => {
+                let archive = match workspace.get_project_file(archive) {
+                    Some(entry) => entry.path.clone().into_inner(),
+                    None => match workspace.get_output_file_path(archive) {
+                        Ok(path) => path.into_inner(),
+                        Err(_) => continue,
+                    },
+                };
+                let is_zip = archive
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+                let archive = shell_quote(&archive.display().to_string());
+                let dir = shell_quote(&dir.display().to_string());
+                steps.push(format!("rm -rf {dir}"));
+                steps.push(format!("mkdir -p {dir}"));
+                if is_zip {
+                    steps.push(format!("unzip -q {archive} -d {dir}"));
+                } else {
+                    steps.push(format!("tar -xmf {archive} -C {dir}"));
+                }
+            }
+            RunCommand::Fetch(url, sha256, dest) => {
+                let dest = dest.display().to_string();
+                steps.push(format!(
+                    "curl -fsSL -o {} {}",
+                    shell_quote(&dest),
+                    shell_quote(url)
+                ));
+                steps.push(format!(
+                    "echo {} | sha256sum -c -",
+                    shell_quote(&format!("{sha256}  {dest}"))
+                ));
+            }
+            RunCommand::Delete(paths) => {
+                if !paths.is_empty() {
+                    let mut step = String::from("rm -rf");
+                    for path in paths {
+                        step.push(' ');
+                        step.push_str(&shell_quote(&path.display().to_string()));
+                    }
+                    steps.push(step);
+                }
+            }
+            RunCommand::Touch(paths) => {
+                if !paths.is_empty() {
+                    let mut step = String::from("touch");
+                    for path in paths {
+                        step.push(' ');
+                        step.push_str(&shell_quote(&path.display().to_string()));
+                    }
+                    steps.push(step);
+                }
+            }
+            RunCommand::CreateDir(paths) => {
+                if !paths.is_empty() {
+                    let mut step = String::from("mkdir -p");
+                    for path in paths {
+                        step.push(' ');
+                        step.push_str(&shell_quote(&path.display().to_string()));
+                    }
+                    steps.push(step);
+                }
+            }
+            RunCommand::Info(message) => {
+                if description.is_none() {
+                    description = Some(message.clone());
+                }
+            }
+            RunCommand::Warn(message) => {
+                steps.push(format!("echo {} >&2", shell_quote(message)));
+            }
+            RunCommand::SetEnv(key, value) => {
+                env.env(key, value);
+            }
+            RunCommand::RemoveEnv(key) => {
+                env.env_remove(key);
+            }
+            // The steps are joined with `&&`, so the following steps run in
+            // the directory.
+            RunCommand::SetWorkingDir(dir) => {
+                steps.push(format!("cd {}", shell_quote(&dir.display().to_string())));
+            }
+            // Ninja always captures the output of commands, and never prints
+            // the command lines. It has no support for timeouts.
+            RunCommand::SetCapture(_)
+            | RunCommand::SetTimeout(_)
+            | RunCommand::PushAttributes(_)
+            | RunCommand::PopAttributes => (),
+        }
+    }
+
+    if steps.is_empty() {
+        // Recipes that only print messages.
+        steps.push(String::from(":"));
+    }
+
+    (steps.join(" && "), description)
+}
+
+/// A command line, prefixed by the environment of the recipe.
+fn shell_step(env: &Env, command_line: &ShellCommandLine) -> String {
+    let mut step = env_prefix(env);
+    step.push_str(&shell_quote(&command_line.program.display().to_string()));
+    for arg in &command_line.arguments {
+        step.push(' ');
+        step.push_str(&shell_quote(arg));
+    }
+    step
+}
+
+/// `env -u REMOVED KEY=VALUE ` for the environment of a recipe.
+fn env_prefix(env: &Env) -> String {
+    if env.env.is_empty() && env.env_remove.is_empty() {
+        return String::new();
+    }
+
+    let mut prefix = String::from("env ");
+    for key in &env.env_remove {
+        _ = write!(prefix, "-u {} ", shell_quote(&key.to_string_lossy()));
+    }
+    for (key, value) in &env.env {
+        let assignment = format!("{}={}", key.to_string_lossy(), value.to_string_lossy());
+        prefix.push_str(&shell_quote(&assignment));
+        prefix.push(' ');
+    }
+    prefix
+}
+
+/// Quote an argument for a POSIX shell, if necessary.
+fn shell_quote(arg: &str) -> String {
+    let is_safe = |ch: char| {
+        ch.is_ascii_alphanumeric()
+            || matches!(
+                ch,
+                '_' | '-' | '.' | '/' | '=' | ':' | ',' | '+' | '@' | '%'
+            )
+    };
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Escape data as the format string of `printf`, inside single quotes.
+/// Non-printable bytes are written as octal escapes, because Ninja variables
+/// cannot contain newlines.
+fn printf_escape(data: &[u8]) -> String {
+    let mut escaped = String::with_capacity(data.len());
+    for &byte in data {
+        match byte {
+            b'\\' => escaped.push_str("\\\\"),
+            b'%' => escaped.push_str("%%"),
+            b'\'' => escaped.push_str("'\\''"),
+            b' '..=b'~' => escaped.push(char::from(byte)),
+            _ => _ = write!(escaped, "\\{byte:03o}"),
+        }
+    }
+    escaped
+}
+
+/// Escape a path in a `build` or `default` statement.
+fn escape_path(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for ch in path.chars() {
+        match ch {
+            '$' | ' ' | ':' => {
+                escaped.push('$');
+                escaped.push(ch);
+            }
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Escape the value of a variable binding.
+fn escape_value(value: &str) -> String {
+    value.replace('$', "$$").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoting() {
+        assert_eq!(shell_quote("-O2"), "-O2");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(printf_escape(b"50%\n"), "50%%\\012");
+        assert_eq!(escape_path("C:\\a b$"), "C$:\\a$ b$$");
+        assert_eq!(escape_value("echo $HOME"), "echo $$HOME");
+    }
+
+    #[test]
+    fn env_prefix_order() {
+        let mut env = Env::default();
+        assert_eq!(env_prefix(&env), "");
+        env.env("CC", "clang");
+        env.env_remove("CFLAGS");
+        assert_eq!(env_prefix(&env), "env -u CFLAGS CC=clang ");
+    }
+}
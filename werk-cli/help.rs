@@ -0,0 +1,121 @@
+//! Show the documentation of a task, along with the global variables that it
+//! uses and the commands that it would run, without running any recipe
+//! commands.
+
+use std::io::Write;
+
+use owo_colors::OwoColorize as _;
+use werk_parser::Document;
+use werk_runner::{eval::UsedVariable, Plan, Workspace};
+
+use crate::{query, which_recipe, Error};
+
+/// Run the `werk help` command for a task that has already been planned.
+pub fn run(
+    workspace: &Workspace<'_>,
+    document: &Document<'_>,
+    plan: &Plan,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let Some(node) = plan.get(plan.root) else {
+        return Ok(());
+    };
+    let name = node.task_id.as_str();
+    // The caller checks that the task exists.
+    let Some(recipe) = workspace.manifest.match_task_recipe(name) else {
+        return Ok(());
+    };
+
+    writeln!(
+        out,
+        "task {} {}",
+        recipe.name.bright_cyan(),
+        format_args!(
+            "({})",
+            which_recipe::location(workspace, recipe.file, recipe.span)
+        )
+        .dimmed(),
+    )?;
+
+    let doc_comment = document.task_doc_comment(name).unwrap_or_default();
+    if !doc_comment.is_empty() {
+        writeln!(out)?;
+        for line in doc_comment.lines() {
+            if line.is_empty() {
+                writeln!(out)?;
+            } else {
+                writeln!(out, "  {line}")?;
+            }
+        }
+        writeln!(out)?;
+    }
+
+    // Global variables used by the task, which can be overridden with `-D`, in
+    // the order they are declared.
+    let used = node
+        .used
+        .iter()
+        .filter_map(|used| match used {
+            UsedVariable::Global(name, _) => Some(name),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let parameters = workspace
+        .manifest
+        .globals
+        .iter()
+        .filter(|(name, _)| used.contains(name))
+        .collect::<Vec<_>>();
+    if !parameters.is_empty() {
+        let max_name_len = parameters
+            .iter()
+            .map(|(name, _)| name.as_str().len())
+            .max()
+            .unwrap_or(0);
+        writeln!(out, "  {}", "parameters:".bright_purple())?;
+        for (name, global) in parameters {
+            writeln!(
+                out,
+                "    {} = {}",
+                format_args!("{: <w$}", name, w = max_name_len).bright_yellow(),
+                workspace.redact_value(&global.value).display_friendly(80),
+            )?;
+        }
+    }
+
+    query::write_dependencies_and_commands(workspace, out, plan, node)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tests::mock_io::{empty_program_output, program_path, Test};
+    use werk_runner::Runner;
+
+    #[test]
+    fn redacts_secrets() -> anyhow::Result<()> {
+        let test = Test::new(
+            r#"
+let token = "hunter2SECRET" | secret
+
+# Deploy with {token}.
+task deploy {
+    run "deploy --token={token}"
+}
+"#,
+        )?;
+        test.io
+            .set_program("deploy", program_path("deploy"), |_, _, _| {
+                Ok(empty_program_output())
+            });
+        let workspace = test.create_workspace(&[]).unwrap();
+        let plan = Runner::new(&workspace).plan("deploy").unwrap();
+        let mut out = Vec::new();
+        super::run(&workspace, &test.ast, &plan, &mut out)?;
+        let out = String::from_utf8(out)?;
+        assert!(!out.contains("hunter2SECRET"), "{out}");
+        assert!(out.contains("--token=***"), "{out}");
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+//! Converters from other build systems to Werkfiles.
+
+pub mod make;
+pub mod ninja;
+
+use std::borrow::Cow;
+
+use werk_parser::ast::{
+    Interpolation, InterpolationOp, InterpolationOptions, InterpolationStem, PatternExpr,
+    PatternFragment, StringExpr, StringFragment,
+};
+use werk_util::Symbol;
+
+use crate::{Error, ImportFormat};
+
+/// The result of converting a foreign build file into a Werkfile.
+pub struct Imported {
+    /// The source code of the generated Werkfile.
+    pub werkfile: String,
+    /// Constructs that could not be translated. These are also listed in a
+    /// comment at the top of the generated Werkfile.
+    pub warnings: Vec<String>,
+}
+
+impl Imported {
+    fn new(body: &str, warnings: Vec<String>) -> Self {
+        let mut werkfile = String::new();
+        if !warnings.is_empty() {
+            werkfile.push_str("# The following could not be imported automatically:\n");
+            for warning in &warnings {
+                werkfile.push_str("#   - ");
+                werkfile.push_str(warning);
+                werkfile.push('\n');
+            }
+            werkfile.push('\n');
+        }
+        werkfile.push_str(body);
+        Self { werkfile, warnings }
+    }
+}
+
+/// Run the `werk import` command.
+pub fn run(format: &ImportFormat) -> Result<(), Error> {
+    let (imported, output) = match format {
+        ImportFormat::Make { file, output } => {
+            let source = std::fs::read_to_string(file)?;
+            (make::import_makefile(&source), output)
+        }
+        ImportFormat::Ninja { file, output } => {
+            let source = std::fs::read_to_string(file)?;
+            (ninja::import_ninja(&source), output)
+        }
+    };
+
+    for warning in &imported.warnings {
+        eprintln!("warning: {warning}");
+    }
+
+    match output {
+        Some(path) => std::fs::write(path, imported.werkfile)?,
+        None => print!("{}", imported.werkfile),
+    }
+    Ok(())
+}
+
+/// Format string fragments as a quoted Werk string literal.
+fn string_literal(fragments: Vec<StringFragment<'_>>) -> String {
+    let expr = StringExpr {
+        fragments,
+        ..Default::default()
+    };
+    format!("\"{expr}\"")
+}
+
+/// Format pattern fragments as a quoted Werk pattern literal.
+fn pattern_literal(fragments: Vec<PatternFragment<'_>>) -> String {
+    let expr = PatternExpr {
+        fragments,
+        ..Default::default()
+    };
+    format!("\"{expr}\"")
+}
+
+/// Build an interpolation fragment, like `{name*}` or `<out:dir>`.
+fn interpolation(
+    stem: InterpolationStem,
+    join: bool,
+    ops: Vec<InterpolationOp<'static>>,
+) -> Interpolation<'static> {
+    let options = if join || !ops.is_empty() {
+        Some(Box::new(InterpolationOptions {
+            ops,
+            join: join.then_some(Cow::Borrowed(" ")),
+        }))
+    } else {
+        None
+    };
+    Interpolation { stem, options }
+}
+
+/// `{name*}`
+fn variable_interpolation(name: &str) -> Interpolation<'static> {
+    interpolation(
+        InterpolationStem::Ident(Symbol::new(name)),
+        true,
+        Vec::new(),
+    )
+}
+
+/// `<name>` or `<name*>`, with optional extra operations.
+fn path_interpolation(
+    name: &str,
+    join: bool,
+    mut ops: Vec<InterpolationOp<'static>>,
+) -> Interpolation<'static> {
+    ops.push(InterpolationOp::ResolveOsPath);
+    interpolation(InterpolationStem::Ident(Symbol::new(name)), join, ops)
+}
+
+/// Convert a variable name from another build system to a Werk identifier.
+/// Names are lowercased, and names that would shadow variables that are
+/// built into recipes get a trailing underscore.
+fn werk_ident(name: &str) -> String {
+    let mut ident = name
+        .chars()
+        .map(|ch| {
+            if ch.is_alphanumeric() || ch == '_' || ch == '-' {
+                ch.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if !ident.starts_with(char::is_alphabetic) {
+        ident.insert_str(0, "v_");
+    }
+    if matches!(ident.as_str(), "in" | "out" | "depfile" | "let") {
+        ident.push('_');
+    }
+    ident
+}
+
+/// True if `command` relies on features of a shell (pipes, redirection,
+/// control flow, etc.), so it must be run through `sh -c`. The command should
+/// only contain the literal parts of the command line.
+fn needs_shell(command: &str) -> bool {
+    const SHELL_BUILTINS: &[&str] = &[
+        "cd", "if", "for", "while", "case", "export", "set", "exit", "test", "[", ".", "source",
+    ];
+
+    let first_word = command.split_whitespace().next().unwrap_or_default();
+    SHELL_BUILTINS.contains(&first_word)
+        // Environment variable assignment, like `FOO=bar cmd`.
+        || first_word.contains('=')
+        || command.contains(|ch| {
+            matches!(
+                ch,
+                '|' | '&' | ';' | '<' | '>' | '(' | ')' | '\'' | '"' | '`' | '$' | '*' | '?'
+                    | '[' | ']' | '~' | '\\' | '#'
+            )
+        })
+}
+
+/// Wrap the (already translated) fragments of a command in `sh -c '...'`.
+/// Backslashes are escape characters in Werk command lines, even inside
+/// quotes, so they are escaped as well.
+fn wrap_in_shell<'a>(command: &str, fragments: Vec<StringFragment<'a>>) -> Vec<StringFragment<'a>> {
+    // Prefer single quotes, but fall back to double quotes (with escaping) if
+    // the command contains single quotes.
+    let quote = if command.contains('\'') { '"' } else { '\'' };
+
+    let mut wrapped = Vec::with_capacity(fragments.len() + 2);
+    wrapped.push(StringFragment::Literal(format!("sh -c {quote}").into()));
+    for fragment in fragments {
+        match fragment {
+            StringFragment::Literal(s) => {
+                let mut escaped = s.replace('\\', "\\\\");
+                if quote == '"' {
+                    escaped = escaped.replace('"', "\\\"");
+                }
+                wrapped.push(StringFragment::Literal(escaped.into()));
+            }
+            interp @ StringFragment::Interpolation(_) => wrapped.push(interp),
+        }
+    }
+    wrapped.push(StringFragment::Literal(quote.to_string().into()));
+    wrapped
+}
+
+/// Format the fragments of a command line as a string literal for a `run`
+/// statement, passing it through `sh -c` if it needs a shell.
+fn command_literal(fragments: Vec<StringFragment<'_>>) -> String {
+    let literal_text = fragments
+        .iter()
+        .filter_map(|fragment| match fragment {
+            StringFragment::Literal(s) => Some(&**s),
+            StringFragment::Interpolation(_) => None,
+        })
+        .collect::<String>();
+    if needs_shell(&literal_text) {
+        string_literal(wrap_in_shell(&literal_text, fragments))
+    } else {
+        string_literal(fragments)
+    }
+}
+
+/// Write a `run` statement inside a recipe body, using the block form when
+/// there is more than one command.
+fn write_run_statement(out: &mut String, commands: &[String]) {
+    match commands {
+        [] => (),
+        [command] => {
+            out.push_str("    run ");
+            out.push_str(command);
+            out.push('\n');
+        }
+        commands => {
+            out.push_str("    run {\n");
+            for command in commands {
+                out.push_str("        ");
+                out.push_str(command);
+                out.push('\n');
+            }
+            out.push_str("    }\n");
+        }
+    }
+}
+
+/// Format a list of expressions, or a single expression if there is only one.
+fn list_or_single(items: &[String]) -> String {
+    match items {
+        [item] => item.clone(),
+        items => format!("[{}]", items.join(", ")),
+    }
+}
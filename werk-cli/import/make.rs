@@ -0,0 +1,1313 @@
+//! Import GNU Makefiles.
+//!
+//! Only a subset of GNU Make is supported: variables, explicit rules, pattern
+//! rules, and `.PHONY` targets. Everything else is reported as a warning and
+//! left out of the generated Werkfile.
+
+use std::fmt::Write as _;
+
+use ahash::HashSet;
+use indexmap::{IndexMap, IndexSet};
+use werk_parser::ast::{InterpolationOp, InterpolationStem, PatternFragment, StringFragment};
+use werk_util::Symbol;
+
+use super::{
+    command_literal, interpolation, list_or_single, path_interpolation, pattern_literal,
+    string_literal, variable_interpolation, werk_ident, write_run_statement, Imported,
+};
+
+/// Variables that GNU Make defines implicitly, and the values used when the
+/// Makefile does not define them.
+const BUILTIN_VARIABLES: &[(&str, &[&str])] = &[
+    ("CC", &["cc"]),
+    ("CXX", &["g++"]),
+    ("AR", &["ar"]),
+    ("LD", &["ld"]),
+    ("RM", &["rm", "-f"]),
+    ("MAKE", &["make"]),
+];
+
+/// Convert the source code of a Makefile to a Werkfile.
+#[must_use]
+pub fn import_makefile(source: &str) -> Imported {
+    let mut parser = Parser::default();
+    for (line_number, line) in logical_lines(source) {
+        parser.line(line_number, &line);
+    }
+    parser.end_rule();
+
+    let idents = Idents::new(parser.makefile.variables.keys());
+    let mut emitter = Emitter {
+        makefile: parser.makefile,
+        warnings: parser.warnings,
+        idents,
+        variables: IndexMap::new(),
+        rules: Vec::new(),
+    };
+    let body = emitter.emit();
+    Imported::new(&body, emitter.warnings)
+}
+
+enum Value {
+    /// A value in Make syntax, which may reference other variables.
+    Text(String),
+    /// The output of a shell command, from `!=` or `$(shell ...)`.
+    Shell(String),
+}
+
+struct Variable {
+    line: usize,
+    value: Value,
+    comments: Vec<String>,
+}
+
+struct Rule {
+    line: usize,
+    prerequisites: Vec<String>,
+    commands: Vec<String>,
+    comments: Vec<String>,
+}
+
+#[derive(Default)]
+struct Makefile {
+    variables: IndexMap<String, Variable>,
+    rules: IndexMap<String, Rule>,
+    phony: HashSet<String>,
+}
+
+#[derive(Default)]
+struct Parser {
+    makefile: Makefile,
+    warnings: Vec<String>,
+    /// Comments preceding the next variable or rule.
+    comments: Vec<String>,
+    /// True after a rule, until the next line that is not part of its recipe.
+    in_recipe: bool,
+    /// Targets of the rule that recipe lines currently belong to.
+    current_targets: Vec<String>,
+    /// Recipe lines seen for the current rule.
+    current_commands: Option<Vec<String>>,
+    /// For each enclosing conditional, whether its lines are being imported.
+    conditionals: Vec<bool>,
+    /// True inside a `define ... endef` block.
+    in_define: bool,
+}
+
+impl Parser {
+    fn warn(&mut self, line: usize, message: impl std::fmt::Display) {
+        self.warnings.push(format!("line {line}: {message}"));
+    }
+
+    fn skipping(&self) -> bool {
+        self.conditionals.contains(&false)
+    }
+
+    fn line(&mut self, line_number: usize, line: &str) {
+        if self.in_define {
+            if line.trim_start().starts_with("endef") {
+                self.in_define = false;
+            }
+            return;
+        }
+
+        if let Some(recipe) = line.strip_prefix('\t') {
+            if self.in_recipe {
+                if !self.skipping() {
+                    self.recipe_line(line_number, recipe);
+                }
+                return;
+            }
+        }
+
+        let (code, comment) = strip_comment(line);
+        let code = code.trim();
+        if code.is_empty() {
+            if let Some(comment) = comment {
+                if !self.skipping() {
+                    self.comments.push(comment.to_owned());
+                }
+            }
+            return;
+        }
+
+        let (keyword, rest) = code
+            .split_once(char::is_whitespace)
+            .map_or((code, ""), |(keyword, rest)| (keyword, rest.trim()));
+
+        match keyword {
+            "ifeq" | "ifneq" | "ifdef" | "ifndef" => {
+                let taken = !self.skipping();
+                if taken {
+                    self.warn(
+                        line_number,
+                        format_args!(
+                            "conditional `{code}` was imported as if its first branch was taken"
+                        ),
+                    );
+                }
+                self.conditionals.push(taken);
+                return;
+            }
+            "else" => {
+                if let Some(taken) = self.conditionals.last_mut() {
+                    *taken = false;
+                }
+                return;
+            }
+            "endif" => {
+                self.conditionals.pop();
+                return;
+            }
+            _ => (),
+        }
+
+        if self.skipping() {
+            return;
+        }
+
+        self.end_rule();
+        let comments = std::mem::take(&mut self.comments);
+
+        match keyword {
+            "define" => {
+                self.warn(
+                    line_number,
+                    format_args!("multi-line variable `{rest}` is not supported"),
+                );
+                self.in_define = true;
+            }
+            "include" | "-include" | "sinclude" => {
+                self.warn(
+                    line_number,
+                    format_args!("`{code}`: included Makefiles are not imported"),
+                );
+            }
+            "vpath" => self.warn(
+                line_number,
+                format_args!("`{code}`: search paths are not supported"),
+            ),
+            "export" | "unexport" | "override" => {
+                if let Statement::Assignment { name, .. } = classify(rest) {
+                    self.warn(
+                        line_number,
+                        format_args!("`{keyword}` was ignored for variable `{name}`"),
+                    );
+                    self.statement(line_number, rest, comments);
+                } else {
+                    self.warn(line_number, format_args!("`{code}` was ignored"));
+                }
+            }
+            _ => self.statement(line_number, code, comments),
+        }
+    }
+
+    fn statement(&mut self, line_number: usize, code: &str, comments: Vec<String>) {
+        match classify(code) {
+            Statement::Assignment { name, op, value } => {
+                self.assignment(line_number, name, op, value, comments);
+            }
+            Statement::Rule { targets, rest } => {
+                self.rule(line_number, targets, rest, &comments);
+            }
+            Statement::Other => {
+                self.warn(line_number, format_args!("`{code}` was not recognized"));
+            }
+        }
+    }
+
+    fn assignment(
+        &mut self,
+        line_number: usize,
+        name: &str,
+        op: &str,
+        value: &str,
+        comments: Vec<String>,
+    ) {
+        if name.is_empty() || name.contains(|ch: char| ch.is_whitespace() || ch == '$') {
+            self.warn(
+                line_number,
+                format_args!("computed variable name `{name}` is not supported"),
+            );
+            return;
+        }
+
+        let value = if op == "!=" {
+            Value::Shell(value.to_owned())
+        } else if let Some(command) = value
+            .strip_prefix("$(shell ")
+            .filter(|_| closing_paren(value, 1) == Some(value.len() - 1))
+        {
+            Value::Shell(command[..command.len() - 1].trim().to_owned())
+        } else {
+            Value::Text(value.to_owned())
+        };
+
+        let existing = self.makefile.variables.get_mut(name);
+        match (op, existing, value) {
+            ("?=", Some(_), _) => (),
+            ("+=", Some(existing), Value::Text(value)) => match existing.value {
+                Value::Text(ref mut text) => {
+                    if !text.is_empty() {
+                        text.push(' ');
+                    }
+                    text.push_str(&value);
+                }
+                Value::Shell(_) => self.warnings.push(format!(
+                    "line {line_number}: appending to the shell output in `{name}` is not supported"
+                )),
+            },
+            (":=" | "::=", Some(existing), Value::Text(value)) => {
+                // Simply expanded variables may refer to their own previous
+                // value, which is substituted here.
+                if let Value::Text(ref previous) = existing.value {
+                    let value = value
+                        .replace(&format!("$({name})"), previous)
+                        .replace(&format!("${{{name}}}"), previous);
+                    existing.value = Value::Text(value);
+                } else {
+                    existing.value = Value::Text(value);
+                }
+                existing.line = line_number;
+            }
+            (_, Some(existing), value) => {
+                existing.value = value;
+                existing.line = line_number;
+            }
+            (_, None, value) => {
+                self.makefile.variables.insert(
+                    name.to_owned(),
+                    Variable {
+                        line: line_number,
+                        value,
+                        comments,
+                    },
+                );
+            }
+        }
+    }
+
+    fn rule(&mut self, line_number: usize, targets: &str, rest: &str, comments: &[String]) {
+        // Recipe lines of unsupported rules are skipped as well.
+        self.in_recipe = true;
+
+        let (rest, inline_command) = match rest.split_once(';') {
+            Some((rest, command)) => (rest, Some(command.trim())),
+            None => (rest, None),
+        };
+
+        match classify(rest) {
+            Statement::Assignment { .. } => {
+                self.warn(
+                    line_number,
+                    format_args!("target-specific variable `{targets}: {rest}` is not supported"),
+                );
+                return;
+            }
+            Statement::Rule { .. } => {
+                self.warn(
+                    line_number,
+                    format_args!("static pattern rule `{targets}: {rest}` is not supported"),
+                );
+                return;
+            }
+            Statement::Other => (),
+        }
+
+        let Some(targets) = self.expand(targets) else {
+            self.warn(
+                line_number,
+                format_args!("targets `{targets}` could not be expanded"),
+            );
+            return;
+        };
+
+        let (normal, order_only) = rest.split_once('|').unwrap_or((rest, ""));
+        if !order_only.trim().is_empty() {
+            self.warn(
+                line_number,
+                format_args!(
+                    "order-only prerequisites of `{targets}` were dropped, because Werk creates output directories automatically"
+                ),
+            );
+        }
+
+        let prerequisites = split_words(normal);
+        let mut current_targets = Vec::new();
+        for target in targets.split_whitespace() {
+            if target == ".PHONY" {
+                for prerequisite in &prerequisites {
+                    match self.expand(prerequisite) {
+                        Some(expanded) => self
+                            .makefile
+                            .phony
+                            .extend(expanded.split_whitespace().map(str::to_owned)),
+                        None => self.warn(
+                            line_number,
+                            format_args!("phony target `{prerequisite}` could not be expanded"),
+                        ),
+                    }
+                }
+            } else if target.starts_with('.') && !target.contains('/') {
+                self.warn(
+                    line_number,
+                    format_args!("special target or suffix rule `{target}` is not supported"),
+                );
+            } else {
+                let rule = self
+                    .makefile
+                    .rules
+                    .entry(target.to_owned())
+                    .or_insert_with(|| Rule {
+                        line: line_number,
+                        prerequisites: Vec::new(),
+                        commands: Vec::new(),
+                        comments: comments.to_vec(),
+                    });
+                rule.prerequisites.extend(
+                    prerequisites
+                        .iter()
+                        .map(|&prerequisite| prerequisite.to_owned()),
+                );
+                current_targets.push(target.to_owned());
+            }
+        }
+
+        self.current_targets = current_targets;
+        self.current_commands = inline_command.map(|command| vec![command.to_owned()]);
+    }
+
+    fn recipe_line(&mut self, line_number: usize, line: &str) {
+        let mut command = line.trim();
+        loop {
+            if let Some(rest) = command.strip_prefix(['@', '+']) {
+                command = rest.trim_start();
+            } else if let Some(rest) = command.strip_prefix('-') {
+                self.warn(
+                    line_number,
+                    format_args!("errors from `{}` are no longer ignored", rest.trim_start()),
+                );
+                command = rest.trim_start();
+            } else {
+                break;
+            }
+        }
+
+        if command.is_empty() || command.starts_with('#') {
+            return;
+        }
+
+        self.current_commands
+            .get_or_insert_with(Vec::new)
+            .push(command.to_owned());
+    }
+
+    /// Called when the recipe of the current rule has ended. The recipe
+    /// replaces any earlier recipe for the same targets.
+    fn end_rule(&mut self) {
+        self.in_recipe = false;
+        let targets = std::mem::take(&mut self.current_targets);
+        if let Some(commands) = self.current_commands.take() {
+            for target in &targets {
+                if let Some(rule) = self.makefile.rules.get_mut(target) {
+                    rule.commands.clone_from(&commands);
+                }
+            }
+        }
+    }
+
+    /// Expand variable references at import time. Only works for variables
+    /// that are defined with plain text.
+    fn expand(&self, text: &str) -> Option<String> {
+        self.expand_recursive(text, 0)
+    }
+
+    fn expand_recursive(&self, text: &str, depth: usize) -> Option<String> {
+        if depth > 32 {
+            return None;
+        }
+
+        let mut result = String::new();
+        let mut rest = text;
+        while let Some(dollar) = rest.find('$') {
+            result.push_str(&rest[..dollar]);
+            let after = &rest[dollar + 1..];
+            let (name, consumed) = match after.chars().next()? {
+                '$' => {
+                    result.push('$');
+                    rest = &after[1..];
+                    continue;
+                }
+                '(' | '{' => {
+                    let close = closing_paren(after, 0)?;
+                    (&after[1..close], close + 1)
+                }
+                ch => (&after[..ch.len_utf8()], ch.len_utf8()),
+            };
+            let Value::Text(ref value) = self.makefile.variables.get(name)?.value else {
+                return None;
+            };
+            result.push_str(&self.expand_recursive(value, depth + 1)?);
+            rest = &after[consumed..];
+        }
+        result.push_str(rest);
+        Some(result)
+    }
+}
+
+enum Statement<'a> {
+    Assignment {
+        name: &'a str,
+        op: &'a str,
+        value: &'a str,
+    },
+    Rule {
+        targets: &'a str,
+        rest: &'a str,
+    },
+    Other,
+}
+
+/// Determine if a line is a variable assignment or a rule, based on whether
+/// an assignment operator or a colon comes first.
+fn classify(code: &str) -> Statement<'_> {
+    let bytes = code.as_bytes();
+    let mut depth = 0usize;
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'$' => {
+                if matches!(bytes.get(index + 1), Some(b'(' | b'{')) {
+                    depth += 1;
+                }
+                index += 2;
+                continue;
+            }
+            b'(' | b'{' if depth > 0 => depth += 1,
+            b')' | b'}' if depth > 0 => depth -= 1,
+            b'=' if depth == 0 => {
+                let (name_end, op) = match index.checked_sub(1).map(|prev| bytes[prev]) {
+                    Some(b':') if index >= 2 && bytes[index - 2] == b':' => (index - 2, "::="),
+                    Some(b':' | b'?' | b'+' | b'!') => (index - 1, &code[index - 1..=index]),
+                    _ => (index, "="),
+                };
+                return Statement::Assignment {
+                    name: code[..name_end].trim(),
+                    op,
+                    value: code[index + 1..].trim(),
+                };
+            }
+            b':' if depth == 0 => {
+                let after = &code[index..];
+                if after.starts_with(":=") || after.starts_with("::=") {
+                    index += 1;
+                    continue;
+                }
+                // Double-colon rules are treated like normal rules.
+                let rest = &code[index + 1..];
+                return Statement::Rule {
+                    targets: code[..index].trim(),
+                    rest: rest.strip_prefix(':').unwrap_or(rest).trim(),
+                };
+            }
+            _ => (),
+        }
+        index += 1;
+    }
+    Statement::Other
+}
+
+/// Join lines ending with a backslash with the following line.
+fn logical_lines(source: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut pending: Option<(usize, String)> = None;
+    for (index, line) in source.lines().enumerate() {
+        let (line_number, mut text) = match pending.take() {
+            Some((line_number, mut text)) => {
+                text.push(' ');
+                text.push_str(line.trim_start());
+                (line_number, text)
+            }
+            None => (index + 1, line.to_owned()),
+        };
+
+        let trailing_backslashes = text.len() - text.trim_end_matches('\\').len();
+        if trailing_backslashes % 2 == 1 {
+            text.pop();
+            text.truncate(text.trim_end().len());
+            pending = Some((line_number, text));
+        } else {
+            lines.push((line_number, text));
+        }
+    }
+    lines.extend(pending);
+    lines
+}
+
+/// Split a line into code and comment.
+fn strip_comment(line: &str) -> (&str, Option<&str>) {
+    let mut escaped = false;
+    for (index, ch) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == '#' {
+            return (&line[..index], Some(line[index + 1..].trim()));
+        }
+    }
+    (line, None)
+}
+
+/// Find the closing parenthesis or brace matching the one at `open`.
+fn closing_paren(text: &str, open: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let (open_char, close_char) = match bytes.get(open)? {
+        b'(' => (b'(', b')'),
+        b'{' => (b'{', b'}'),
+        _ => return None,
+    };
+    let mut depth = 0usize;
+    for (index, &byte) in bytes.iter().enumerate().skip(open) {
+        if byte == open_char {
+            depth += 1;
+        } else if byte == close_char {
+            depth -= 1;
+            if depth == 0 {
+                return Some(index);
+            }
+        }
+    }
+    None
+}
+
+/// Split a value into whitespace-separated words, keeping variable references
+/// like `$(patsubst a, b, c)` intact.
+fn split_words(text: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut depth = 0usize;
+    for (index, ch) in text.char_indices() {
+        match ch {
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth = depth.saturating_sub(1),
+            ch if ch.is_whitespace() && depth == 0 => {
+                if let Some(start) = start.take() {
+                    words.push(&text[start..index]);
+                }
+                continue;
+            }
+            _ => (),
+        }
+        start.get_or_insert(index);
+    }
+    if let Some(start) = start {
+        words.push(&text[start..]);
+    }
+    words
+}
+
+/// Where a piece of Make syntax appears, which determines the meaning of
+/// automatic variables and `%`.
+#[derive(Clone, Copy)]
+enum Context<'a> {
+    /// Variable values in the global scope.
+    Global,
+    /// Prerequisites of a rule, where `%` is the pattern stem.
+    Prerequisite,
+    /// Recipe commands of a rule that becomes a build recipe.
+    Build,
+    /// Recipe commands of a phony target that becomes a task recipe.
+    Task(&'a str),
+}
+
+/// A variable reference in Make syntax that can be expressed as a Werk
+/// expression by itself, like `$(OBJS)` or `$(SRCS:.c=.o)`.
+fn whole_word_reference(
+    word: &str,
+    references: &mut IndexSet<String>,
+    idents: &mut Idents,
+) -> Option<String> {
+    if !word.starts_with("$(") && !word.starts_with("${") {
+        return None;
+    }
+    if closing_paren(word, 1) != Some(word.len() - 1) {
+        return None;
+    }
+    let reference = &word[2..word.len() - 1];
+    match reference.split_once(':') {
+        Some((name, substitution)) if is_variable_name(name) => {
+            let (from, to) = extension_substitution(substitution)?;
+            references.insert(name.to_owned());
+            Some(format!("({} | map \"{{:{from}={to}}}\")", idents.get(name)))
+        }
+        None if is_variable_name(reference) && !is_automatic_variable(reference) => {
+            references.insert(reference.to_owned());
+            Some(idents.get(reference))
+        }
+        _ => None,
+    }
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(|ch: char| ch.is_whitespace() || ch == ',' || ch == '$')
+}
+
+fn is_automatic_variable(name: &str) -> bool {
+    matches!(
+        name,
+        "@" | "<" | "^" | "+" | "*" | "?" | "|" | "@D" | "@F" | "<D" | "<F"
+    )
+}
+
+/// Parse the `.c=.o` part of a substitution reference like `$(SRCS:.c=.o)`.
+/// Only substitutions that replace a file extension are supported.
+fn extension_substitution(substitution: &str) -> Option<(&str, &str)> {
+    let (from, to) = substitution.split_once('=')?;
+    let (from, to) = match (from.strip_prefix('%'), to.strip_prefix('%')) {
+        (Some(from), Some(to)) => (from, to),
+        (None, None) => (from, to),
+        _ => return None,
+    };
+    let is_extension = |ext: &str| {
+        ext.starts_with('.') && ext[1..].chars().all(|ch| ch.is_alphanumeric() || ch == '_')
+    };
+    (is_extension(from) && is_extension(to)).then_some((from, to))
+}
+
+/// Translate Make syntax to the fragments of a Werk string.
+fn translate(
+    text: &str,
+    context: Context<'_>,
+    references: &mut IndexSet<String>,
+    idents: &mut Idents,
+) -> Result<Vec<StringFragment<'static>>, String> {
+    let mut fragments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = text.char_indices();
+    while let Some((index, ch)) = chars.next() {
+        let fragment = match ch {
+            '$' => {
+                let Some((_, next)) = chars.next() else {
+                    literal.push('$');
+                    break;
+                };
+                let reference = match next {
+                    '$' => {
+                        literal.push('$');
+                        continue;
+                    }
+                    '(' | '{' => {
+                        let close = closing_paren(text, index + 1)
+                            .ok_or_else(|| format!("unterminated reference in `{text}`"))?;
+                        while chars.next().is_some_and(|(index, _)| index < close) {}
+                        &text[index + 2..close]
+                    }
+                    next => &text[index + 1..index + 1 + next.len_utf8()],
+                };
+                translate_reference(reference, context, references, idents)?
+            }
+            '%' if matches!(context, Context::Prerequisite) => StringFragment::Interpolation(
+                interpolation(InterpolationStem::PatternCapture, false, Vec::new()),
+            ),
+            ch => {
+                literal.push(ch);
+                continue;
+            }
+        };
+
+        if !literal.is_empty() {
+            fragments.push(StringFragment::Literal(std::mem::take(&mut literal).into()));
+        }
+        fragments.push(fragment);
+    }
+
+    if !literal.is_empty() {
+        fragments.push(StringFragment::Literal(literal.into()));
+    }
+    Ok(fragments)
+}
+
+fn translate_reference(
+    reference: &str,
+    context: Context<'_>,
+    references: &mut IndexSet<String>,
+    idents: &mut Idents,
+) -> Result<StringFragment<'static>, String> {
+    if is_automatic_variable(reference) {
+        return translate_automatic_variable(reference, context);
+    }
+
+    if let Some((name, substitution)) = reference.split_once(':') {
+        let (from, to) = extension_substitution(substitution)
+            .filter(|_| is_variable_name(name))
+            .ok_or_else(|| format!("substitution reference `$({reference})` is not supported"))?;
+        references.insert(name.to_owned());
+        return Ok(StringFragment::Interpolation(interpolation(
+            InterpolationStem::Ident(Symbol::new(&idents.get(name))),
+            true,
+            vec![InterpolationOp::ReplaceExtension {
+                from: from.to_owned().into(),
+                to: to.to_owned().into(),
+            }],
+        )));
+    }
+
+    if !is_variable_name(reference) {
+        let function = reference.split_whitespace().next().unwrap_or(reference);
+        return Err(format!("function `$({function} ...)` is not supported"));
+    }
+
+    references.insert(reference.to_owned());
+    Ok(StringFragment::Interpolation(variable_interpolation(
+        &idents.get(reference),
+    )))
+}
+
+fn translate_automatic_variable(
+    reference: &str,
+    context: Context<'_>,
+) -> Result<StringFragment<'static>, String> {
+    let interp = match (context, reference) {
+        (Context::Task(name), "@") => return Ok(StringFragment::Literal(name.to_owned().into())),
+        (Context::Build, "@") => path_interpolation("out", false, Vec::new()),
+        (Context::Build, "@D") => path_interpolation("out", false, vec![InterpolationOp::Dirname]),
+        (Context::Build, "@F") => interpolation(
+            InterpolationStem::Ident(Symbol::new("out")),
+            false,
+            vec![InterpolationOp::Filename],
+        ),
+        (Context::Build, "<") => path_interpolation("in", false, Vec::new()),
+        (Context::Build, "<D") => path_interpolation("in", false, vec![InterpolationOp::Dirname]),
+        (Context::Build, "<F") => interpolation(
+            InterpolationStem::Ident(Symbol::new("in")),
+            false,
+            vec![InterpolationOp::Filename],
+        ),
+        // `$?` is only the outdated prerequisites in Make, but Werk always
+        // reruns the whole recipe.
+        (Context::Build, "^" | "+" | "?") => path_interpolation("in", true, Vec::new()),
+        (Context::Build, "*") => {
+            interpolation(InterpolationStem::PatternCapture, false, Vec::new())
+        }
+        (Context::Global | Context::Prerequisite, _) => {
+            return Err(format!(
+                "automatic variable `${reference}` is only supported in recipes"
+            ))
+        }
+        _ => {
+            return Err(format!(
+                "automatic variable `${reference}` is not supported here"
+            ))
+        }
+    };
+    Ok(StringFragment::Interpolation(interp))
+}
+
+/// Translate a whitespace-separated value to a Werk expression: a string, an
+/// identifier, or a list of those.
+fn translate_words(
+    text: &str,
+    context: Context<'_>,
+    references: &mut IndexSet<String>,
+    idents: &mut Idents,
+) -> Result<Vec<String>, String> {
+    split_words(text)
+        .into_iter()
+        .map(
+            |word| match whole_word_reference(word, references, idents) {
+                Some(expr) => Ok(expr),
+                None => translate(word, context, references, idents).map(string_literal),
+            },
+        )
+        .collect()
+}
+
+fn translate_command(
+    command: &str,
+    context: Context<'_>,
+    references: &mut IndexSet<String>,
+    idents: &mut Idents,
+) -> Result<String, String> {
+    translate(command, context, references, idents).map(command_literal)
+}
+
+/// The Werk identifiers of Make variables. Identifiers are lowercase, but Make
+/// variable names are case sensitive, so a name that would collide with the
+/// identifier of another variable (like `cc` and `CC`) gets a numeric suffix.
+#[derive(Default)]
+struct Idents {
+    /// Make variable name to Werk identifier.
+    idents: IndexMap<String, String>,
+    /// Werk identifiers that are in use.
+    taken: HashSet<String>,
+    /// Variables that were renamed to avoid a collision.
+    warnings: Vec<String>,
+}
+
+impl Idents {
+    /// Reserve identifiers for the variables defined in the Makefile, in
+    /// order, so they take precedence over undefined variables.
+    fn new<'a>(names: impl IntoIterator<Item = &'a String>) -> Self {
+        let mut idents = Self::default();
+        for name in names {
+            idents.get(name);
+        }
+        idents
+    }
+
+    fn get(&mut self, name: &str) -> String {
+        if let Some(ident) = self.idents.get(name) {
+            return ident.clone();
+        }
+        let base = werk_ident(name);
+        let mut ident = base.clone();
+        let mut suffix = 2;
+        while self.taken.contains(&ident) {
+            ident = format!("{base}_{suffix}");
+            suffix += 1;
+        }
+        if ident != base {
+            let (other, _) = self
+                .idents
+                .iter()
+                .find(|(_, other)| **other == base)
+                .expect("identifier is taken");
+            self.warnings.push(format!(
+                "`{name}` was renamed to `{ident}`, because `{other}` is also named `{base}`"
+            ));
+        }
+        self.taken.insert(ident.clone());
+        self.idents.insert(name.to_owned(), ident.clone());
+        ident
+    }
+}
+
+fn is_task_name(name: &str) -> bool {
+    name.starts_with(char::is_alphabetic)
+        && name
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || ch == '_' || ch == '-')
+}
+
+struct TranslatedVariable {
+    expr: String,
+    references: IndexSet<String>,
+}
+
+struct Emitter {
+    makefile: Makefile,
+    warnings: Vec<String>,
+    idents: Idents,
+    variables: IndexMap<String, TranslatedVariable>,
+    rules: Vec<String>,
+}
+
+impl Emitter {
+    fn emit(&mut self) -> String {
+        let mut references = IndexSet::new();
+        self.translate_variables();
+        let default_target = self.translate_rules(&mut references);
+        for variable in self.variables.values() {
+            references.extend(variable.references.iter().cloned());
+        }
+
+        let mut out = String::new();
+        if let Some(default_target) = default_target {
+            _ = writeln!(
+                out,
+                "config default = {}\n",
+                string_literal(vec![StringFragment::Literal(default_target.into())])
+            );
+        }
+
+        // Variables that are used but not defined in the Makefile.
+        let mut has_undefined = false;
+        for name in &references {
+            if self.variables.contains_key(name) {
+                continue;
+            }
+            has_undefined = true;
+            let ident = self.idents.get(name);
+            if let Some((_, value)) = BUILTIN_VARIABLES
+                .iter()
+                .find(|(builtin, _)| builtin == name)
+            {
+                let words = value
+                    .iter()
+                    .map(|word| string_literal(vec![StringFragment::Literal((*word).into())]))
+                    .collect::<Vec<_>>();
+                _ = writeln!(out, "let {ident} = {}", list_or_single(&words));
+            } else {
+                self.warnings.push(format!(
+                    "`{name}` is not defined in the Makefile, and is read from the environment"
+                ));
+                _ = writeln!(out, "let {ident} = env \"{name}\"");
+            }
+        }
+        if has_undefined {
+            out.push('\n');
+        }
+
+        for name in self.variable_order() {
+            let variable = &self.variables[&name];
+            write_comments(&mut out, &self.makefile.variables[&name].comments);
+            _ = writeln!(out, "let {} = {}", self.idents.get(&name), variable.expr);
+        }
+
+        for rule in &self.rules {
+            out.push('\n');
+            out.push_str(rule);
+        }
+
+        self.warnings.append(&mut self.idents.warnings);
+        out
+    }
+
+    fn translate_variables(&mut self) {
+        for (name, variable) in &self.makefile.variables {
+            let mut references = IndexSet::new();
+            let result = match variable.value {
+                Value::Text(ref text) => {
+                    translate_words(text, Context::Global, &mut references, &mut self.idents).map(
+                        |words| match words.as_slice() {
+                            [] => String::from("[]"),
+                            [word] => word
+                                .strip_prefix('(')
+                                .and_then(|word| word.strip_suffix(')'))
+                                .unwrap_or(word)
+                                .to_owned(),
+                            words => list_or_single(words),
+                        },
+                    )
+                }
+                Value::Shell(ref command) => {
+                    translate_command(command, Context::Global, &mut references, &mut self.idents)
+                        .map(|command| format!("shell {command} | split \" \""))
+                }
+            };
+
+            match result {
+                Ok(expr) => {
+                    self.variables
+                        .insert(name.clone(), TranslatedVariable { expr, references });
+                }
+                Err(err) => {
+                    self.warnings.push(format!(
+                        "line {}: variable `{name}` was defined as an empty list: {err}",
+                        variable.line
+                    ));
+                    self.variables.insert(
+                        name.clone(),
+                        TranslatedVariable {
+                            expr: String::from("[]"),
+                            references,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    /// Translate all rules, returning the default target.
+    fn translate_rules(&mut self, references: &mut IndexSet<String>) -> Option<String> {
+        let mut default_target = None;
+        for (target, rule) in &self.makefile.rules {
+            let is_pattern = target.contains('%');
+            let is_task = self.makefile.phony.contains(target)
+                || (rule.commands.is_empty()
+                    && !rule.prerequisites.is_empty()
+                    && is_task_name(target)
+                    && !is_pattern);
+
+            if is_task && !is_task_name(target) {
+                self.warnings.push(format!(
+                    "line {}: phony target `{target}` is not a valid task name",
+                    rule.line
+                ));
+                continue;
+            }
+
+            if !is_task && rule.commands.is_empty() {
+                let message = if rule.prerequisites.is_empty() {
+                    format!("line {}: `{target}` has no recipe", rule.line)
+                } else {
+                    format!(
+                        "line {}: extra prerequisites of `{target}` without a recipe are not supported; consider using a depfile",
+                        rule.line
+                    )
+                };
+                self.warnings.push(message);
+                continue;
+            }
+
+            let result = if is_task {
+                translate_task(target, rule, references, &mut self.idents)
+            } else {
+                translate_build(target, rule, references, &mut self.idents)
+            };
+
+            match result {
+                Ok(recipe) => {
+                    if default_target.is_none() && !is_pattern {
+                        default_target = Some(target.clone());
+                    }
+                    self.rules.push(recipe);
+                }
+                Err(err) => self.warnings.push(format!(
+                    "line {}: rule for `{target}` was skipped: {err}",
+                    rule.line
+                )),
+            }
+        }
+        default_target
+    }
+
+    /// Order variable definitions so that variables are defined before they
+    /// are referenced.
+    fn variable_order(&self) -> Vec<String> {
+        fn visit(
+            name: &str,
+            variables: &IndexMap<String, TranslatedVariable>,
+            visited: &mut IndexSet<String>,
+        ) {
+            if visited.contains(name) {
+                return;
+            }
+            let Some(variable) = variables.get(name) else {
+                return;
+            };
+            // Insert before visiting dependencies to break cycles.
+            visited.insert(name.to_owned());
+            for reference in &variable.references {
+                visit(reference, variables, visited);
+            }
+            // Move the variable after its dependencies.
+            visited.shift_remove(name);
+            visited.insert(name.to_owned());
+        }
+
+        let mut visited = IndexSet::new();
+        for name in self.variables.keys() {
+            visit(name, &self.variables, &mut visited);
+        }
+        visited.into_iter().collect()
+    }
+}
+
+fn write_comments(out: &mut String, comments: &[String]) {
+    for comment in comments {
+        _ = writeln!(out, "# {comment}");
+    }
+}
+
+fn translate_task(
+    target: &str,
+    rule: &Rule,
+    references: &mut IndexSet<String>,
+    idents: &mut Idents,
+) -> Result<String, String> {
+    let build = translate_words(
+        &rule.prerequisites.join(" "),
+        Context::Prerequisite,
+        references,
+        idents,
+    )?;
+    let commands = rule
+        .commands
+        .iter()
+        .map(|command| translate_command(command, Context::Task(target), references, idents))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut out = String::new();
+    write_comments(&mut out, &rule.comments);
+    _ = writeln!(out, "task {target} {{");
+    if !build.is_empty() {
+        _ = writeln!(out, "    build {}", list_or_single(&build));
+    }
+    write_run_statement(&mut out, &commands);
+    out.push_str("}\n");
+    Ok(out)
+}
+
+fn translate_build(
+    target: &str,
+    rule: &Rule,
+    references: &mut IndexSet<String>,
+    idents: &mut Idents,
+) -> Result<String, String> {
+    let mut pattern = Vec::new();
+    for (index, part) in target.split('%').enumerate() {
+        if index > 0 {
+            pattern.push(PatternFragment::PatternStem);
+        }
+        if !part.is_empty() {
+            pattern.push(PatternFragment::Literal(part.into()));
+        }
+    }
+
+    let from = translate_words(
+        &rule.prerequisites.join(" "),
+        Context::Prerequisite,
+        references,
+        idents,
+    )?;
+    let commands = rule
+        .commands
+        .iter()
+        .map(|command| translate_command(command, Context::Build, references, idents))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut out = String::new();
+    write_comments(&mut out, &rule.comments);
+    _ = writeln!(out, "build {} {{", pattern_literal(pattern));
+    if !from.is_empty() {
+        _ = writeln!(out, "    from {}", list_or_single(&from));
+    }
+    write_run_statement(&mut out, &commands);
+    out.push_str("}\n");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(source: &str) -> Imported {
+        let imported = import_makefile(source);
+        if let Err(err) =
+            werk_parser::parse_werk(std::path::Path::new("Werkfile"), &imported.werkfile)
+        {
+            panic!(
+                "generated Werkfile does not parse: {err:?}\n{}",
+                imported.werkfile
+            );
+        }
+        imported
+    }
+
+    #[test]
+    fn pattern_rules_and_variables() {
+        let imported = import(
+            "\
+CC := gcc
+# Compiler flags
+CFLAGS = -Wall \\
+    -O2
+SRCS = main.c util.c
+OBJS = $(SRCS:.c=.o)
+
+.PHONY: all clean
+
+all: app
+
+app: $(OBJS)
+\t$(CC) -o $@ $^
+
+%.o: %.c
+\t@$(CC) $(CFLAGS) -c $< -o $@
+
+clean:
+\trm -f $(OBJS) app
+",
+        );
+        assert!(imported.warnings.is_empty(), "{:?}", imported.warnings);
+        assert_eq!(
+            imported.werkfile,
+            r#"config default = "all"
+
+let cc = "gcc"
+# Compiler flags
+let cflags = ["-Wall", "-O2"]
+let srcs = ["main.c", "util.c"]
+let objs = srcs | map "{:.c=.o}"
+
+task all {
+    build "app"
+}
+
+build "app" {
+    from objs
+    run "{cc*} -o <out> <in*>"
+}
+
+build "%.o" {
+    from "{%}.c"
+    run "{cc*} {cflags*} -c <in> -o <out>"
+}
+
+task clean {
+    run "rm -f {objs*} app"
+}
+"#
+        );
+    }
+
+    #[test]
+    fn shell_commands() {
+        let imported = import(
+            "\
+VERSION != git describe
+out/%.txt: %.in
+\tcat $< | sed 's/x/y/' > $@
+",
+        );
+        assert!(imported.warnings.is_empty(), "{:?}", imported.warnings);
+        assert_eq!(
+            imported.werkfile,
+            r#"let version = shell "git describe" | split " "
+
+build "out/%.txt" {
+    from "{%}.in"
+    run "sh -c \"cat <in> | sed 's/x/y/' \> <out>\""
+}
+"#
+        );
+    }
+
+    #[test]
+    fn case_collisions() {
+        let imported = import(
+            "\
+CC ?= gcc
+cc = clang
+app: main.c
+\t$(CC) -o $@ $< && $(cc) --version
+",
+        );
+        assert_eq!(
+            imported.warnings,
+            ["`cc` was renamed to `cc_2`, because `CC` is also named `cc`"]
+        );
+        assert!(imported.werkfile.contains("let cc = \"gcc\"\n"));
+        assert!(imported.werkfile.contains("let cc_2 = \"clang\"\n"));
+        assert!(imported
+            .werkfile
+            .contains("{cc*} -o <out> <in> && {cc_2*} --version"));
+    }
+
+    #[test]
+    fn unsupported() {
+        let imported = import(
+            "\
+SRCS = $(wildcard *.c)
+ifeq ($(OS),Windows_NT)
+EXE = app.exe
+else
+EXE = app
+endif
+main.o: main.h
+$(EXE): $(SRCS)
+\t$(CC) -o $@ $^
+",
+        );
+        assert_eq!(
+            imported.warnings,
+            [
+                "line 2: conditional `ifeq ($(OS),Windows_NT)` was imported as if its first branch was taken",
+                "line 1: variable `SRCS` was defined as an empty list: function `$(wildcard ...)` is not supported",
+                "line 7: extra prerequisites of `main.o` without a recipe are not supported; consider using a depfile",
+            ]
+        );
+        assert!(imported.werkfile.contains("build \"app.exe\" {"));
+    }
+}
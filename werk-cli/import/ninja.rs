@@ -0,0 +1,830 @@
+//! Import Ninja build files.
+//!
+//! Ninja files are usually generated, and list every build edge explicitly, so
+//! each `build` statement becomes a build recipe for a single file. Variables
+//! are expanded at import time, except for `$in` and `$out`.
+
+use std::fmt::Write as _;
+
+use ahash::{HashMap, HashSet};
+use indexmap::IndexMap;
+use werk_parser::ast::{PatternFragment, StringFragment};
+
+use super::{
+    command_literal, list_or_single, path_interpolation, pattern_literal, string_literal,
+    write_run_statement, Imported,
+};
+
+/// Convert the source code of a `build.ninja` file to a Werkfile.
+#[must_use]
+pub fn import_ninja(source: &str) -> Imported {
+    let mut parser = Parser::default();
+    for declaration in declarations(source) {
+        parser.declaration(declaration);
+    }
+
+    let mut emitter = Emitter {
+        ninja: parser.ninja,
+        warnings: parser.warnings,
+    };
+    let body = emitter.emit();
+    Imported::new(&body, emitter.warnings)
+}
+
+/// Part of an expanded value.
+#[derive(Clone, Debug, PartialEq)]
+enum Piece {
+    Text(String),
+    /// `$in` or `$in_newline`
+    In,
+    /// `$out`
+    Out,
+}
+
+/// A top-level line, and the indented variable bindings following it.
+struct Declaration {
+    line: usize,
+    head: String,
+    bindings: Vec<(String, String)>,
+}
+
+struct Rule {
+    /// Unexpanded values, which are expanded in the scope of each build
+    /// statement using the rule.
+    bindings: IndexMap<String, String>,
+}
+
+struct Build {
+    line: usize,
+    outputs: Vec<String>,
+    rule: String,
+    inputs: Vec<String>,
+    /// Implicit and order-only dependencies.
+    implicit: Vec<String>,
+    bindings: IndexMap<String, Vec<Piece>>,
+}
+
+#[derive(Default)]
+struct Ninja {
+    variables: HashMap<String, String>,
+    rules: HashMap<String, Rule>,
+    builds: Vec<Build>,
+    defaults: Vec<String>,
+}
+
+#[derive(Default)]
+struct Parser {
+    ninja: Ninja,
+    warnings: Vec<String>,
+}
+
+/// Split the source into declarations, joining lines ending with `$`.
+fn declarations(source: &str) -> Vec<Declaration> {
+    let mut declarations = Vec::<Declaration>::new();
+    let mut pending: Option<(usize, String)> = None;
+    for (index, line) in source.lines().enumerate() {
+        let (line_number, mut text) = match pending.take() {
+            Some((line_number, mut text)) => {
+                text.push_str(line.trim_start());
+                (line_number, text)
+            }
+            None => (index + 1, line.to_owned()),
+        };
+
+        let trailing_dollars = text.len() - text.trim_end_matches('$').len();
+        if trailing_dollars % 2 == 1 {
+            text.pop();
+            pending = Some((line_number, text));
+            continue;
+        }
+
+        let trimmed = text.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if trimmed.len() != text.len() {
+            // Indented variable binding.
+            if let (Some(declaration), Some((name, value))) =
+                (declarations.last_mut(), trimmed.split_once('='))
+            {
+                declaration
+                    .bindings
+                    .push((name.trim().to_owned(), value.trim_start().to_owned()));
+            }
+            continue;
+        }
+
+        declarations.push(Declaration {
+            line: line_number,
+            head: text,
+            bindings: Vec::new(),
+        });
+    }
+    declarations
+}
+
+/// Split a `build` line into words and the separators `:`, `|`, `||`, and
+/// `|@`. Escapes are kept in the words, to be handled by [`expand()`].
+fn split_build_line(line: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut chars = line.char_indices().peekable();
+    while let Some((index, ch)) = chars.next() {
+        match ch {
+            '$' => {
+                start.get_or_insert(index);
+                chars.next();
+            }
+            ':' => {
+                if let Some(start) = start.take() {
+                    words.push(&line[start..index]);
+                }
+                words.push(":");
+            }
+            ch if ch.is_whitespace() => {
+                if let Some(start) = start.take() {
+                    words.push(&line[start..index]);
+                }
+            }
+            _ => {
+                start.get_or_insert(index);
+            }
+        }
+    }
+    if let Some(start) = start {
+        words.push(&line[start..]);
+    }
+    words
+}
+
+/// Expand escapes and variable references in a Ninja value. `lookup` is called
+/// for each variable reference.
+fn expand(value: &str, lookup: &mut dyn FnMut(&str) -> Vec<Piece>) -> Vec<Piece> {
+    fn push_char(pieces: &mut Vec<Piece>, ch: char) {
+        if let Some(Piece::Text(text)) = pieces.last_mut() {
+            text.push(ch);
+        } else {
+            pieces.push(Piece::Text(ch.to_string()));
+        }
+    }
+
+    let mut pieces = Vec::new();
+    let mut chars = value.char_indices().peekable();
+    while let Some((index, ch)) = chars.next() {
+        if ch != '$' {
+            push_char(&mut pieces, ch);
+            continue;
+        }
+
+        let name = match chars.next() {
+            Some((_, escaped @ ('$' | ' ' | ':'))) => {
+                push_char(&mut pieces, escaped);
+                continue;
+            }
+            Some((open, '{')) => {
+                let close = value[open..]
+                    .find('}')
+                    .map_or(value.len(), |close| open + close);
+                while chars.next_if(|&(index, _)| index <= close).is_some() {}
+                &value[open + 1..close]
+            }
+            Some((start, ch)) if ch.is_ascii_alphanumeric() || ch == '_' || ch == '-' => {
+                let mut end = start + 1;
+                while let Some(&(index, ch)) = chars.peek() {
+                    if !(ch.is_ascii_alphanumeric() || ch == '_' || ch == '-') {
+                        break;
+                    }
+                    chars.next();
+                    end = index + 1;
+                }
+                &value[start..end]
+            }
+            _ => &value[index..index],
+        };
+
+        for piece in lookup(name) {
+            match piece {
+                Piece::Text(text) => text.chars().for_each(|ch| push_char(&mut pieces, ch)),
+                piece => pieces.push(piece),
+            }
+        }
+    }
+    pieces
+}
+
+/// Join the text of expanded pieces, ignoring `$in` and `$out`.
+fn to_text(pieces: &[Piece]) -> String {
+    pieces
+        .iter()
+        .filter_map(|piece| match piece {
+            Piece::Text(text) => Some(text.as_str()),
+            Piece::In | Piece::Out => None,
+        })
+        .collect()
+}
+
+impl Parser {
+    fn warn(&mut self, line: usize, message: impl std::fmt::Display) {
+        self.warnings.push(format!("line {line}: {message}"));
+    }
+
+    fn expand_global(&self, value: &str) -> String {
+        to_text(&expand(value, &mut |name| {
+            self.ninja
+                .variables
+                .get(name)
+                .map(|value| vec![Piece::Text(value.clone())])
+                .unwrap_or_default()
+        }))
+    }
+
+    fn declaration(&mut self, declaration: Declaration) {
+        let Declaration {
+            line,
+            head,
+            bindings,
+        } = declaration;
+        let (keyword, rest) = head
+            .split_once(char::is_whitespace)
+            .map_or((head.as_str(), ""), |(keyword, rest)| {
+                (keyword, rest.trim())
+            });
+
+        match keyword {
+            "rule" => {
+                self.ninja.rules.insert(
+                    rest.to_owned(),
+                    Rule {
+                        bindings: bindings.into_iter().collect(),
+                    },
+                );
+            }
+            "build" => self.build(line, rest, bindings),
+            "default" => {
+                let defaults = split_build_line(rest)
+                    .into_iter()
+                    .map(|word| self.expand_global(word))
+                    .collect::<Vec<_>>();
+                self.ninja.defaults.extend(defaults);
+            }
+            "pool" => self.warn(line, format_args!("pool `{rest}` is not supported")),
+            "include" | "subninja" => {
+                self.warn(
+                    line,
+                    format_args!("`{head}`: included files are not imported"),
+                );
+            }
+            _ => match head.split_once('=') {
+                Some((name, value)) => {
+                    let value = self.expand_global(value.trim_start());
+                    self.ninja.variables.insert(name.trim().to_owned(), value);
+                }
+                None => self.warn(line, format_args!("`{head}` was not recognized")),
+            },
+        }
+    }
+
+    fn build(&mut self, line: usize, rest: &str, bindings: Vec<(String, String)>) {
+        // Build-level variables are expanded immediately, in the scope of
+        // the file and the preceding bindings.
+        let mut build_bindings = IndexMap::<String, Vec<Piece>>::new();
+        for (name, value) in bindings {
+            let expanded = expand(&value, &mut |reference| match reference {
+                "in" | "in_newline" => vec![Piece::In],
+                "out" => vec![Piece::Out],
+                _ => build_bindings
+                    .get(reference)
+                    .cloned()
+                    .or_else(|| {
+                        self.ninja
+                            .variables
+                            .get(reference)
+                            .map(|value| vec![Piece::Text(value.clone())])
+                    })
+                    .unwrap_or_default(),
+            });
+            build_bindings.insert(name, expanded);
+        }
+
+        let expand_path = |word: &str| {
+            to_text(&expand(word, &mut |reference| {
+                build_bindings
+                    .get(reference)
+                    .cloned()
+                    .or_else(|| {
+                        self.ninja
+                            .variables
+                            .get(reference)
+                            .map(|value| vec![Piece::Text(value.clone())])
+                    })
+                    .unwrap_or_default()
+            }))
+        };
+
+        let words = split_build_line(rest);
+        let Some(colon) = words.iter().position(|&word| word == ":") else {
+            self.warn(line, "`build` statement without `:`");
+            return;
+        };
+        let (outputs, rest) = words.split_at(colon);
+        let Some((rule, rest)) = rest[1..].split_first() else {
+            self.warn(line, "`build` statement without a rule");
+            return;
+        };
+
+        let mut outputs = outputs.iter().copied();
+        let explicit_outputs = outputs
+            .by_ref()
+            .take_while(|&word| word != "|")
+            .map(expand_path)
+            .collect::<Vec<_>>();
+        let has_implicit_outputs = outputs.next().is_some();
+
+        let mut inputs = Vec::new();
+        let mut implicit = Vec::new();
+        let mut section = 0;
+        for &word in rest {
+            match word {
+                "|" => section = 1,
+                "||" => section = 2,
+                "|@" => section = 3,
+                word => match section {
+                    0 => inputs.push(expand_path(word)),
+                    1 | 2 => implicit.push(expand_path(word)),
+                    // Validations do not affect the build.
+                    _ => (),
+                },
+            }
+        }
+
+        if has_implicit_outputs {
+            self.warn(
+                line,
+                format_args!(
+                    "implicit outputs of `{}` were ignored",
+                    explicit_outputs.join(" ")
+                ),
+            );
+        }
+
+        self.ninja.builds.push(Build {
+            line,
+            outputs: explicit_outputs,
+            rule: (*rule).to_owned(),
+            inputs,
+            implicit,
+            bindings: build_bindings,
+        });
+    }
+}
+
+struct Emitter {
+    ninja: Ninja,
+    warnings: Vec<String>,
+}
+
+/// Normalize a path from a Ninja file to a Werk path, if possible.
+fn werk_path(path: &str) -> Option<&str> {
+    let mut path = path;
+    while let Some(rest) = path.strip_prefix("./") {
+        path = rest;
+    }
+    (!path.starts_with('/') && werk_fs::Path::new(path).is_ok()).then_some(path)
+}
+
+fn is_task_name(name: &str) -> bool {
+    name.starts_with(char::is_alphabetic)
+        && name
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || ch == '_' || ch == '-')
+}
+
+fn path_literal(path: &str) -> String {
+    string_literal(vec![StringFragment::Literal(path.into())])
+}
+
+impl Emitter {
+    fn emit(&mut self) -> String {
+        let builds = std::mem::take(&mut self.ninja.builds);
+        let phony = builds
+            .iter()
+            .filter(|build| build.rule == "phony")
+            .flat_map(|build| {
+                build.outputs.iter().map(|output| {
+                    let mut dependencies = build.inputs.clone();
+                    dependencies.extend(build.implicit.iter().cloned());
+                    (output.clone(), dependencies)
+                })
+            })
+            .collect::<HashMap<_, _>>();
+
+        // Secondary outputs of a build statement are mapped to the first
+        // output, which is the one that gets a build recipe.
+        let mut aliases = HashMap::default();
+        for build in &builds {
+            if let [first, rest @ ..] = build.outputs.as_slice() {
+                for output in rest {
+                    aliases.insert(output.clone(), first.clone());
+                }
+            }
+        }
+
+        let resolver = Resolver {
+            phony: &phony,
+            aliases: &aliases,
+        };
+
+        let mut out = String::new();
+        let mut recipes = String::new();
+        let mut used_as_input = HashSet::default();
+        let mut outputs = Vec::new();
+        let mut tasks = HashSet::default();
+
+        for build in &builds {
+            if build.rule == "phony" {
+                for output in &build.outputs {
+                    if !is_task_name(output) {
+                        // Only used as an alias by other build statements.
+                        continue;
+                    }
+                    let dependencies = resolver.task_dependencies(output);
+                    used_as_input.extend(dependencies.iter().cloned());
+                    tasks.insert(output.clone());
+                    let build_list = dependencies
+                        .iter()
+                        .map(|dependency| path_literal(dependency))
+                        .collect::<Vec<_>>();
+                    _ = writeln!(recipes, "\ntask {output} {{");
+                    if !build_list.is_empty() {
+                        _ = writeln!(recipes, "    build {}", list_or_single(&build_list));
+                    }
+                    recipes.push_str("}\n");
+                }
+                continue;
+            }
+
+            match self.build_recipe(build, &resolver) {
+                Ok(recipe) => {
+                    recipes.push('\n');
+                    recipes.push_str(&recipe);
+                    used_as_input.extend(resolver.files(&build.inputs));
+                    used_as_input.extend(resolver.files(&build.implicit));
+                    outputs.push(build.outputs[0].clone());
+                }
+                Err(err) => self.warnings.push(format!(
+                    "line {}: build statement for `{}` was skipped: {err}",
+                    build.line,
+                    build.outputs.join(" ")
+                )),
+            }
+        }
+
+        // Determine the default target.
+        let defaults = if self.ninja.defaults.is_empty() {
+            None
+        } else {
+            Some(resolver.files_or_tasks(&self.ninja.defaults, &tasks))
+        };
+        match defaults {
+            Some(defaults) if defaults.len() == 1 => {
+                _ = writeln!(out, "config default = {}", path_literal(&defaults[0]));
+            }
+            defaults => {
+                // Without `default` statements, Ninja builds all outputs that
+                // are not used as inputs.
+                let defaults = defaults.unwrap_or_else(|| {
+                    outputs
+                        .iter()
+                        .filter(|output| !used_as_input.contains(*output))
+                        .cloned()
+                        .collect()
+                });
+                let name = ["all", "default", "build-all"]
+                    .into_iter()
+                    .find(|name| !tasks.contains(*name) && !outputs.iter().any(|o| o == name))
+                    .unwrap_or("werk-default");
+                if tasks.contains("all") && self.ninja.defaults.is_empty() {
+                    _ = writeln!(out, "config default = \"all\"");
+                } else {
+                    let build_list = defaults
+                        .iter()
+                        .map(|target| path_literal(target))
+                        .collect::<Vec<_>>();
+                    _ = writeln!(out, "config default = \"{name}\"");
+                    _ = writeln!(recipes, "\ntask {name} {{");
+                    if !build_list.is_empty() {
+                        _ = writeln!(recipes, "    build {}", list_or_single(&build_list));
+                    }
+                    recipes.push_str("}\n");
+                }
+            }
+        }
+
+        out.push_str(&recipes);
+        out
+    }
+
+    fn build_recipe(&mut self, build: &Build, resolver: &Resolver<'_>) -> Result<String, String> {
+        let rule = self
+            .ninja
+            .rules
+            .get(&build.rule)
+            .ok_or_else(|| format!("rule `{}` is not defined", build.rule))?;
+
+        if rule
+            .bindings
+            .get("generator")
+            .is_some_and(|value| !value.trim().is_empty())
+        {
+            return Err(String::from("generator rules are not supported"));
+        }
+
+        let [output, extra_outputs @ ..] = build.outputs.as_slice() else {
+            return Err(String::from("no outputs"));
+        };
+        let target = werk_path(output)
+            .ok_or_else(|| format!("`{output}` is not a path inside the workspace"))?;
+        if !extra_outputs.is_empty() {
+            self.warnings.push(format!(
+                "line {}: only the first output of `{}` is known to Werk",
+                build.line,
+                build.outputs.join(" ")
+            ));
+        }
+
+        let inputs = resolver.files(&build.inputs);
+        let implicit = resolver.files(&build.implicit);
+        for path in inputs.iter().chain(&implicit) {
+            if werk_path(path).is_none() {
+                return Err(format!("`{path}` is not a path inside the workspace"));
+            }
+        }
+
+        let expand_rule_binding = |name: &str| -> Option<Vec<Piece>> {
+            fn expand_in_rule(
+                value: &str,
+                build: &Build,
+                rule: &Rule,
+                variables: &HashMap<String, String>,
+                depth: usize,
+            ) -> Vec<Piece> {
+                expand(value, &mut |reference| match reference {
+                    "in" | "in_newline" => vec![Piece::In],
+                    "out" => vec![Piece::Out],
+                    _ => {
+                        if let Some(pieces) = build.bindings.get(reference) {
+                            pieces.clone()
+                        } else if let Some(value) =
+                            rule.bindings.get(reference).filter(|_| depth < 16)
+                        {
+                            expand_in_rule(value, build, rule, variables, depth + 1)
+                        } else {
+                            variables
+                                .get(reference)
+                                .map(|value| vec![Piece::Text(value.clone())])
+                                .unwrap_or_default()
+                        }
+                    }
+                })
+            }
+
+            if let Some(pieces) = build.bindings.get(name) {
+                return Some(pieces.clone());
+            }
+            let value = rule.bindings.get(name)?;
+            Some(expand_in_rule(value, build, rule, &self.ninja.variables, 0))
+        };
+
+        let command = expand_rule_binding("command")
+            .ok_or_else(|| format!("rule `{}` has no command", build.rule))?;
+
+        for unsupported in ["rspfile", "pool"] {
+            if rule.bindings.contains_key(unsupported) {
+                self.warnings.push(format!(
+                    "line {}: `{unsupported}` of rule `{}` was ignored",
+                    build.line, build.rule
+                ));
+            }
+        }
+        if expand_rule_binding("deps").is_some_and(|deps| to_text(&deps).trim() == "msvc") {
+            self.warnings.push(format!(
+                "line {}: `deps = msvc` of rule `{}` is not supported",
+                build.line, build.rule
+            ));
+        }
+
+        let depfile = expand_rule_binding("depfile")
+            .map(|pieces| {
+                pieces
+                    .into_iter()
+                    .map(|piece| match piece {
+                        Piece::Text(text) => text,
+                        Piece::In => inputs.join(" "),
+                        Piece::Out => target.to_owned(),
+                    })
+                    .collect::<String>()
+            })
+            .filter(|depfile| !depfile.trim().is_empty());
+
+        // `in` contains all dependencies, so when there are implicit
+        // dependencies, `$in` refers to a separate variable.
+        let in_variable = if implicit.is_empty() { "in" } else { "inputs" };
+        let fragments = command
+            .into_iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => StringFragment::Literal(text.into()),
+                Piece::In => {
+                    StringFragment::Interpolation(path_interpolation(in_variable, true, Vec::new()))
+                }
+                Piece::Out => {
+                    StringFragment::Interpolation(path_interpolation("out", false, Vec::new()))
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut out = String::new();
+        _ = writeln!(
+            out,
+            "build {} {{",
+            pattern_literal(vec![PatternFragment::Literal(target.into())])
+        );
+        let from = inputs
+            .iter()
+            .chain(&implicit)
+            .map(|path| path_literal(path))
+            .collect::<Vec<_>>();
+        if !from.is_empty() {
+            _ = writeln!(out, "    from {}", list_or_single(&from));
+        }
+        if !implicit.is_empty() {
+            let inputs = inputs
+                .iter()
+                .map(|path| path_literal(path))
+                .collect::<Vec<_>>();
+            _ = writeln!(out, "    let inputs = [{}]", inputs.join(", "));
+        }
+        if let Some(depfile) = depfile {
+            _ = writeln!(out, "    depfile {}", path_literal(depfile.trim()));
+        }
+        write_run_statement(&mut out, &[command_literal(fragments)]);
+        out.push_str("}\n");
+        Ok(out)
+    }
+}
+
+/// Resolves references to phony targets and secondary outputs.
+struct Resolver<'a> {
+    phony: &'a HashMap<String, Vec<String>>,
+    aliases: &'a HashMap<String, String>,
+}
+
+impl Resolver<'_> {
+    /// Resolve paths to the files they refer to, replacing phony targets with
+    /// their dependencies.
+    fn files(&self, paths: &[String]) -> Vec<String> {
+        let mut files = Vec::new();
+        let mut visited = HashSet::default();
+        for path in paths {
+            self.resolve_into(path, &mut files, &mut visited);
+        }
+        files
+    }
+
+    fn resolve_into(&self, path: &str, files: &mut Vec<String>, visited: &mut HashSet<String>) {
+        if !visited.insert(path.to_owned()) {
+            return;
+        }
+        if let Some(dependencies) = self.phony.get(path) {
+            for dependency in dependencies {
+                self.resolve_into(dependency, files, visited);
+            }
+        } else {
+            let path = self.aliases.get(path).map_or(path, String::as_str);
+            let path = werk_path(path).unwrap_or(path);
+            if !files.iter().any(|file| file == path) {
+                files.push(path.to_owned());
+            }
+        }
+    }
+
+    /// Dependencies of a phony target that became a task. Dependencies that
+    /// are also tasks are kept by name.
+    fn task_dependencies(&self, name: &str) -> Vec<String> {
+        let mut dependencies = Vec::new();
+        for dependency in self.phony.get(name).into_iter().flatten() {
+            if is_task_name(dependency) && self.phony.contains_key(dependency) {
+                dependencies.push(dependency.clone());
+            } else {
+                for file in self.files(std::slice::from_ref(dependency)) {
+                    if !dependencies.contains(&file) {
+                        dependencies.push(file);
+                    }
+                }
+            }
+        }
+        dependencies
+    }
+
+    fn files_or_tasks(&self, paths: &[String], tasks: &HashSet<String>) -> Vec<String> {
+        let mut result = Vec::new();
+        for path in paths {
+            if tasks.contains(path) {
+                result.push(path.clone());
+            } else {
+                result.extend(self.files(std::slice::from_ref(path)));
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(source: &str) -> Imported {
+        let imported = import_ninja(source);
+        if let Err(err) =
+            werk_parser::parse_werk(std::path::Path::new("Werkfile"), &imported.werkfile)
+        {
+            panic!(
+                "generated Werkfile does not parse: {err:?}\n{}",
+                imported.werkfile
+            );
+        }
+        imported
+    }
+
+    #[test]
+    fn rules_and_builds() {
+        let imported = import(
+            "\
+# Generated
+cflags = -O2
+rule cc
+  command = gcc $cflags -MD -MF $out.d -c $in -o $out
+  depfile = $out.d
+  description = CC $out
+rule link
+  command = gcc -o $out $in && strip $out
+
+build obj/main.o: cc src/main.c
+build obj/util.o: cc src/util.c | gen.h
+  cflags = $cflags -g
+build app: link obj/main.o obj/util.o
+build all: phony app
+default all
+",
+        );
+        assert!(imported.warnings.is_empty(), "{:?}", imported.warnings);
+        assert_eq!(
+            imported.werkfile,
+            r#"config default = "all"
+
+build "obj/main.o" {
+    from "src/main.c"
+    depfile "obj/main.o.d"
+    run "gcc -O2 -MD -MF <out>.d -c <in*> -o <out>"
+}
+
+build "obj/util.o" {
+    from ["src/util.c", "gen.h"]
+    let inputs = ["src/util.c"]
+    depfile "obj/util.o.d"
+    run "gcc -O2 -g -MD -MF <out>.d -c <inputs*> -o <out>"
+}
+
+build "app" {
+    from ["obj/main.o", "obj/util.o"]
+    run "sh -c 'gcc -o <out> <in*> && strip <out>'"
+}
+
+task all {
+    build "app"
+}
+"#
+        );
+    }
+
+    #[test]
+    fn default_without_statement() {
+        let imported = import(
+            "\
+rule touch
+  command = touch $out
+build a.txt: touch
+build b.txt: touch a.txt
+build ../outside.txt: touch
+",
+        );
+        assert_eq!(
+            imported.warnings,
+            ["line 5: build statement for `../outside.txt` was skipped: `../outside.txt` is not a path inside the workspace"]
+        );
+        assert!(imported.werkfile.contains("\nconfig default = \"all\"\n"));
+        assert!(imported
+            .werkfile
+            .ends_with("task all {\n    build \"b.txt\"\n}\n"));
+    }
+}
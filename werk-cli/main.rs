@@ -1,19 +1,33 @@
+mod compdb;
 mod complete;
+mod doctor;
 pub mod dry_run;
+mod eval;
+mod export;
+mod help;
+mod import;
+mod pick;
+mod query;
 mod render;
+mod verify;
+mod which_recipe;
 
 use std::{borrow::Cow, path::Path, sync::Arc};
 
 use ahash::HashSet;
-use clap::{CommandFactory, Parser};
+use clap::{CommandFactory, FromArgMatches as _, Parser};
 use clap_complete::ArgValueCandidates;
 use futures::future::Either;
 use notify_debouncer_full::notify;
 use owo_colors::OwoColorize as _;
 use render::{AutoStream, ColorOutputKind};
 use werk_fs::{Absolute, Normalize as _, PathError};
-use werk_runner::{Runner, Workspace, WorkspaceSettings};
-use werk_util::{Diagnostic, DiagnosticError, DiagnosticFileRepository, DiagnosticSource};
+use werk_runner::{
+    BuildResults, ImportSources, Member, MemberSources, Runner, Workspace, WorkspaceSettings,
+};
+use werk_util::{
+    Diagnostic, DiagnosticError, DiagnosticFileRepository, DiagnosticSource, DiagnosticSummary,
+};
 
 shadow_rs::shadow!(build);
 
@@ -38,9 +52,9 @@ pub struct OutputArgs {
     #[clap(long)]
     pub print_fresh: bool,
 
-    /// Silence informational output from executed commands, only printing to
-    /// the terminal when a recipe fails.
-    #[clap(long)]
+    /// Print nothing but errors and warnings. The output of commands is only
+    /// printed when a recipe fails. Ignored with `--verbose`.
+    #[clap(long, short)]
     pub quiet: bool,
 
     /// Print all informational output from executed commands to the terminal,
@@ -53,16 +67,39 @@ pub struct OutputArgs {
     #[clap(long)]
     pub explain: bool,
 
+    /// Buffer the output of commands and print it as one block when its task
+    /// finishes, instead of interleaving the output of tasks running in
+    /// parallel.
+    #[clap(long)]
+    pub output_group: bool,
+
     /// Shorthand for `--explain --print-commands --print-fresh --no-capture --loud`.
-    #[clap(long, short)]
-    pub verbose: bool,
+    /// Pass it twice (`-vv`) to also print the value of each variable as it
+    /// is evaluated.
+    #[clap(long, short, action = clap::ArgAction::Count)]
+    pub verbose: u8,
 
+    /// Color mode for both stdout and stderr. Each stream is probed
+    /// separately when this is `auto`.
     #[clap(long, default_value = "auto")]
     pub color: ColorChoice,
 
+    /// Color mode for stdout, overriding `--color`.
+    #[clap(long, value_name = "COLOR")]
+    pub color_stdout: Option<ColorChoice>,
+
+    /// Color mode for stderr, overriding `--color`. This applies to progress
+    /// output, and to the output of recipe commands.
+    #[clap(long, value_name = "COLOR")]
+    pub color_stderr: Option<ColorChoice>,
+
     #[clap(long, default_value = "ansi")]
     pub output_format: OutputChoice,
 
+    /// Format of error and warning diagnostics printed to stderr.
+    #[clap(long, default_value = "human")]
+    pub error_format: ErrorFormat,
+
     /// Enable debug logging to stdout.
     ///
     /// This takes a logging directive like `RUST_LOG`.
@@ -71,26 +108,79 @@ pub struct OutputArgs {
 }
 
 #[derive(Debug, clap::Parser)]
-#[command(version = version_string(), bin_name = env!("CARGO_BIN_NAME"))]
+#[command(version = version_string(), bin_name = env!("CARGO_BIN_NAME"), disable_help_subcommand = true, disable_version_flag = true, args_conflicts_with_subcommands = true)]
 pub struct Args {
-    /// The target to build.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// The target to build. A glob like `assets/*.ktx` builds all matching
+    /// source files and outputs of build recipes.
     #[clap(add = ArgValueCandidates::new(complete::targets))]
     pub target: Option<String>,
 
-    /// The path to the Werkfile. Defaults to searching for `Werkfile` in the
-    /// current working directory and its parents.
+    /// The path to the Werkfile. Defaults to searching for `Werkfile`,
+    /// `werkfile`, or `build.werk` in the current working directory and its
+    /// parents. The names and their order can be changed with the
+    /// `WERKFILE_NAMES` environment variable, like
+    /// `WERKFILE_NAMES=build.werk,Werkfile`.
     #[clap(short, long)]
     pub file: Option<std::path::PathBuf>,
 
+    /// Print version. With `--verbose`, also print the Werkfile that would be
+    /// used, its front-end, and the file names searched for.
+    #[clap(short = 'V', long)]
+    pub version: bool,
+
     /// List the available recipes.
     #[clap(short, long)]
     pub list: bool,
 
+    /// List the outputs that each build recipe knows how to build, based on
+    /// the source files in the workspace. This does not execute any recipe
+    /// commands.
+    #[clap(long)]
+    pub list_outputs: bool,
+
+    /// Print the resolved build plan for the target instead of building it.
+    /// This does not execute any recipe commands.
+    #[clap(long)]
+    pub plan: bool,
+
     /// Dry run; do not execute any recipe commands. Note: Shell commands used
-    /// in global variables are still executed!
+    /// in global variables are still executed, unless `--pure` is passed!
     #[clap(long)]
     pub dry_run: bool,
 
+    /// Evaluate the Werkfile without running any programs, so dry runs work
+    /// on machines without the toolchain. `shell` expressions produce a
+    /// placeholder, and programs not found by `which` get a placeholder path.
+    /// Implies `--dry-run`.
+    #[clap(long)]
+    pub pure: bool,
+
+    /// Rebuild targets even if they are up to date. When no target is given,
+    /// all targets are rebuilt. May be passed multiple times.
+    #[clap(long, num_args = 0..=1, require_equals = true, value_name = "TARGET")]
+    pub force: Option<Vec<String>>,
+
+    /// Keep building targets that don't depend on a failed task, and report
+    /// all failures at the end. By default, no new commands are started after
+    /// the first failure.
+    #[clap(long, short)]
+    pub keep_going: bool,
+
+    /// After a successful build, write a Makefile-style depfile to this path,
+    /// listing the Werkfiles and source files that werk consulted. This lets
+    /// other build systems know when to invoke werk again.
+    #[clap(long, value_name = "PATH")]
+    pub emit_depfile: Option<std::path::PathBuf>,
+
+    /// After the build, write the build summary to this path as JSON: the
+    /// number of tasks executed, up-to-date, and served by the cache, the
+    /// total and critical path time, and the slowest commands.
+    #[clap(long, value_name = "PATH")]
+    pub summary_json: Option<std::path::PathBuf>,
+
     /// Build the target, then keep rebuilding it when the workspace changes.
     #[clap(long, short)]
     pub watch: bool,
@@ -117,8 +207,178 @@ pub struct Args {
     #[clap(long, short = 'D', add = ArgValueCandidates::new(complete::defines))]
     pub define: Vec<String>,
 
+    /// Select a profile declared with `profile` in the Werkfile, replacing the
+    /// global variables that it declares. Defaults to `config profile`.
+    #[clap(long, add = ArgValueCandidates::new(complete::profiles))]
+    pub profile: Option<String>,
+
+    /// Report references to undefined variables as errors, even in recipes
+    /// that are not run, and warn about likely mistakes like unused variables.
+    /// Defaults to `config strict`.
+    #[clap(long)]
+    pub strict: bool,
+
+    /// Make `which` resolve a program to a fixed path instead of searching for
+    /// it. This takes the form `name=path`, like `clang=/opt/llvm/bin/clang`.
+    /// Relative paths are relative to the workspace directory.
+    #[clap(long)]
+    pub tool: Vec<String>,
+
+    /// Build the target in a workspace member declared with `member` in the
+    /// Werkfile, instead of the root workspace. This is the same as building
+    /// the target `//<MEMBER>:<TARGET>`.
+    #[clap(long, short = 'p')]
+    pub member: Option<String>,
+
     #[command(flatten)]
     pub output: OutputArgs,
+
+    /// The name of a subcommand given without arguments. If the Werkfile has
+    /// a task with the same name, the task runs instead.
+    #[clap(skip)]
+    pub task_or_subcommand: Option<String>,
+
+    /// The error of parsing a subcommand that is missing its arguments, like
+    /// `werk help`. It is reported unless the Werkfile has a task with the
+    /// same name.
+    #[clap(skip)]
+    pub subcommand_error: Option<clap::Error>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    /// Print a shell completion script. Task names and build targets are
+    /// completed by calling back into `werk`.
+    Completions {
+        /// The shell to generate the completion script for.
+        shell: clap_complete::Shell,
+    },
+    /// List the task names and build targets in the current Werkfile, one per
+    /// line. Used by the completion scripts.
+    #[command(name = "__complete", hide = true)]
+    Complete,
+    /// Convert a build file from another build system to a Werkfile. Anything
+    /// that could not be converted is reported on stderr.
+    Import {
+        #[command(subcommand)]
+        format: ImportFormat,
+    },
+    /// Generate a build file for another build system from the Werkfile in the
+    /// current directory. No recipe commands are run.
+    Export {
+        #[command(subcommand)]
+        format: ExportFormat,
+    },
+    /// Generate a clang compilation database (`compile_commands.json`) from
+    /// the commands that compile C-like source files. No recipe commands are
+    /// run.
+    Compdb {
+        /// The targets to include, along with their dependencies. Defaults to
+        /// `config default`.
+        #[clap(add = ArgValueCandidates::new(complete::targets))]
+        targets: Vec<String>,
+        /// Write the compilation database to this path instead of
+        /// `compile_commands.json` in the workspace directory. Pass `-` to
+        /// write to stdout.
+        #[clap(short, long)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Show which build recipe would be used to build a target, along with
+    /// other matching recipes ordered by specificity. No recipe commands are
+    /// run.
+    WhichRecipe {
+        /// The target path to look up.
+        #[clap(add = ArgValueCandidates::new(complete::targets))]
+        target: String,
+    },
+    /// Show the documentation of a task, the global variables that it uses,
+    /// and the commands that it would run. No recipe commands are run.
+    Help {
+        /// The task to show.
+        #[clap(add = ArgValueCandidates::new(complete::tasks))]
+        task: String,
+    },
+    /// Check the Werkfile, its imports, and workspace members for errors and
+    /// likely mistakes, like undefined or unused variables, unreachable match
+    /// arms, and build recipes that can never be used. Fails if anything is
+    /// found. No recipe commands are run.
+    Check,
+    /// Show the recipe matching a target, its stem and capture groups, the
+    /// evaluated command lines, and its dependencies. No recipe commands are
+    /// run.
+    Query {
+        /// The target to query.
+        #[clap(add = ArgValueCandidates::new(complete::targets))]
+        target: String,
+    },
+    /// Evaluate an expression in the root scope of the Werkfile and print its
+    /// value, like `werk eval 'glob "*.c" | map "{:.c=.o}"'`. Without an
+    /// expression, expressions are read from stdin, one per line. No recipe
+    /// commands are run.
+    Eval {
+        /// The expression to evaluate.
+        expr: Option<String>,
+    },
+    /// Check the environment that `werk` runs in and print a report: terminal
+    /// capabilities, whether the output directory is writable, clock skew
+    /// between the source and output filesystems, and the programs found by
+    /// `which` expressions in the Werkfile. Fails if a check fails. No recipe
+    /// commands are run.
+    Doctor,
+    /// Recompute the hashes of the output files built by earlier runs, and
+    /// compare them to the hashes recorded in the output directory when they
+    /// were built. Reports outputs that were modified or deleted since, and
+    /// fails if any are found. Use `--verbose` to also list unchanged outputs.
+    /// No recipe commands are run, unless `--repro` is given.
+    Verify {
+        /// Instead, build the target twice, rerunning every recipe each time,
+        /// and fail if any output differs between the two builds.
+        #[arg(long)]
+        repro: bool,
+        /// The target built by `--repro`. Defaults to `config default`.
+        #[arg(requires = "repro")]
+        #[clap(add = ArgValueCandidates::new(complete::targets))]
+        target: Option<String>,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum ExportFormat {
+    /// Generate a `build.ninja` file. Commands are run by Ninja through a
+    /// POSIX shell.
+    Ninja {
+        /// The targets to export, including their dependencies. Defaults to
+        /// `config default`.
+        #[clap(add = ArgValueCandidates::new(complete::targets))]
+        targets: Vec<String>,
+        /// Write the Ninja file to this path instead of stdout.
+        #[clap(short, long)]
+        output: Option<std::path::PathBuf>,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum ImportFormat {
+    /// Convert a GNU Makefile. Variables, explicit rules, pattern rules, and
+    /// `.PHONY` targets are supported.
+    Make {
+        /// The Makefile to convert.
+        #[clap(default_value = "Makefile")]
+        file: std::path::PathBuf,
+        /// Write the Werkfile to this path instead of stdout.
+        #[clap(short, long)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Convert a Ninja build file. Rules, build statements, variables, and
+    /// `default` statements are supported.
+    Ninja {
+        /// The Ninja file to convert.
+        #[clap(default_value = "build.ninja")]
+        file: std::path::PathBuf,
+        /// Write the Werkfile to this path instead of stdout.
+        #[clap(short, long)]
+        output: Option<std::path::PathBuf>,
+    },
 }
 
 /// Color mode.
@@ -145,6 +405,22 @@ pub enum OutputChoice {
     Json,
 }
 
+/// Diagnostic output mode.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorFormat {
+    /// Annotated source snippets.
+    #[default]
+    Human,
+    /// One `file:line:column: level[code]: message` line per diagnostic.
+    Short,
+    /// One JSON object per line, with the code, message, and location of each
+    /// diagnostic.
+    Json,
+}
+
+/// Set once at startup from `--error-format`.
+static ERROR_FORMAT: std::sync::OnceLock<ErrorFormat> = std::sync::OnceLock::new();
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Parsing error")]
@@ -159,8 +435,28 @@ pub enum Error {
     OutputDirectory(String, PathError),
     #[error("Werkfile not found in this directory or any parent directory")]
     NoWerkfile,
+    #[error("Found both `{}` and `{}` in {}; remove one of them, or pass `--file`", .1, .2, .0.display())]
+    AmbiguousWerkfile(std::path::PathBuf, String, String),
+    #[error("Invalid target '{0}': {1}")]
+    InvalidTarget(String, PathError),
     #[error("Invalid define (must take the form `key=value`): {0}")]
     InvalidDefineArg(String),
+    #[error("Invalid tool (must take the form `name=path`): {0}")]
+    InvalidToolArg(String),
+    #[error("No workspace member named '{0}'")]
+    NoSuchMember(String),
+    #[error("No task named '{0}'")]
+    NoSuchTask(String),
+    #[error("Found {0} warning(s)")]
+    Warnings(usize),
+    #[error("Found {0} problem(s)")]
+    Problems(usize),
+    #[error("{0} tasks failed")]
+    TasksFailed(usize),
+    #[error("Watch mode is not supported in workspaces with members")]
+    WatchMembers,
+    #[error("`--emit-depfile` cannot be used with a target glob")]
+    DepfileGlob,
     #[error("No target specified. Pass a target name on the command-line, or set the `config.default` variable. Use `--list` to get a list of available targets.")]
     NoTarget,
     #[error(transparent)]
@@ -172,7 +468,37 @@ pub enum Error {
 fn main() -> Result<(), Error> {
     clap_complete::CompleteEnv::with_factory(Args::command).complete();
 
-    let args = Args::parse();
+    let args = parse_args(std::env::args_os().collect());
+
+    if args.version {
+        print_version(&args, &mut std::io::stdout());
+        return Ok(());
+    }
+
+    match args.command {
+        Some(Command::Completions { shell }) => {
+            complete::print_completion_script(shell, &mut std::io::stdout());
+            return Ok(());
+        }
+        Some(Command::Complete) => {
+            complete::print_targets(&args, &mut std::io::stdout());
+            return Ok(());
+        }
+        Some(Command::Import { format }) => return import::run(&format),
+        Some(
+            Command::Export { .. }
+            | Command::Compdb { .. }
+            | Command::WhichRecipe { .. }
+            | Command::Query { .. }
+            | Command::Help { .. }
+            | Command::Check
+            | Command::Eval { .. }
+            | Command::Doctor
+            | Command::Verify { .. },
+        )
+        | None => (),
+    }
+
     match args.output.log {
         Some(Some(ref directive)) => tracing_subscriber::fmt::fmt()
             .with_env_filter(tracing_subscriber::EnvFilter::new(directive))
@@ -188,44 +514,174 @@ fn main() -> Result<(), Error> {
     smol::block_on(try_main(args))
 }
 
-async fn try_main(args: Args) -> Result<(), Error> {
+/// Parse the command line. A subcommand given without arguments, like `werk
+/// check`, may also be the name of a task, which is decided by
+/// `resolve_task_subcommand` once the Werkfile is read. With `--` in front,
+/// like `werk -- check`, it is always the subcommand.
+fn parse_args(mut argv: Vec<std::ffi::OsString>) -> Args {
+    let command = Args::command();
+    let is_subcommand = |arg: &std::ffi::OsString| {
+        arg.to_str()
+            .and_then(|name| command.find_subcommand(name))
+            .is_some_and(|subcommand| !subcommand.is_hide_set())
+    };
+
+    if let Some(index) = argv.iter().position(|arg| arg == "--") {
+        if argv.get(index + 1).is_some_and(is_subcommand) {
+            argv.remove(index);
+            return Args::parse_from(argv);
+        }
+    }
+
+    match command.clone().try_get_matches_from(&argv) {
+        Ok(matches) => {
+            let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+            if let Some((name, sub_matches)) = matches.subcommand() {
+                let has_arguments = sub_matches.ids().any(|id| {
+                    sub_matches.value_source(id.as_str())
+                        == Some(clap::parser::ValueSource::CommandLine)
+                });
+                if !has_arguments && is_subcommand(&name.into()) {
+                    args.task_or_subcommand = Some(name.to_owned());
+                }
+            }
+            args
+        }
+        Err(err)
+            if matches!(
+                err.kind(),
+                clap::error::ErrorKind::MissingRequiredArgument
+                    | clap::error::ErrorKind::MissingSubcommand
+                    | clap::error::ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+            ) =>
+        {
+            // Parse the subcommand as a target, in case it is a task.
+            let Some(index) = argv.iter().skip(1).position(is_subcommand) else {
+                err.exit()
+            };
+            argv.insert(index + 1, "--".into());
+            let Ok(mut args) = Args::try_parse_from(&argv) else {
+                err.exit()
+            };
+            args.task_or_subcommand.clone_from(&args.target);
+            args.subcommand_error = Some(err);
+            args
+        }
+        Err(err) => err.exit(),
+    }
+}
+
+/// Run the task instead of the subcommand given without arguments, when the
+/// Werkfile has a task with the same name. Otherwise, report the error of
+/// parsing the subcommand, if any.
+fn resolve_task_subcommand(args: &mut Args, ast: &werk_parser::ast::Root) {
+    let Some(name) = args.task_or_subcommand.take() else {
+        return;
+    };
+    if ast.find_command(&name).is_some() {
+        tracing::info!("Running task `{name}` instead of the `werk {name}` subcommand");
+        args.command = None;
+        args.target = Some(name);
+    } else if let Some(err) = args.subcommand_error.take() {
+        err.exit();
+    }
+}
+
+async fn try_main(mut args: Args) -> Result<(), Error> {
     anstyle_query::windows::enable_ansi_colors();
+    _ = ERROR_FORMAT.set(args.output.error_format);
 
-    let color_stdout = render::ColorOutputKind::initialize(&std::io::stdout(), args.output.color);
-    let color_stderr = render::ColorOutputKind::initialize(&std::io::stderr(), args.output.color);
+    let color_stdout = render::ColorOutputKind::initialize(
+        &std::io::stdout(),
+        args.output.color_stdout.unwrap_or(args.output.color),
+    );
+    let color_stderr = render::ColorOutputKind::initialize(
+        &std::io::stderr(),
+        args.output.color_stderr.unwrap_or(args.output.color),
+    );
 
     let werkfile = match &args.file {
-        Some(file) => file.clone().normalize()?,
-        _ => find_werkfile()?,
+        Some(file) => file.clone().normalize().map_err(Error::from),
+        _ => find_werkfile(),
+    };
+    let werkfile = match werkfile {
+        Ok(werkfile) => werkfile,
+        // Without a Werkfile, a subcommand missing its arguments can't be a
+        // task.
+        Err(err) => match args.subcommand_error.take() {
+            Some(subcommand_error) => subcommand_error.exit(),
+            None => return Err(err),
+        },
     };
     tracing::info!("Using werkfile: {}", werkfile.display());
 
-    // Determine the workspace directory.
-    let workspace_dir = get_workspace_dir(&args, &werkfile)?;
-
     // Parse the werk manifest!
     let source_code = std::fs::read_to_string(&werkfile)?;
 
-    let ast = werk_parser::parse_werk(&werkfile, &source_code).map_err(|err| {
-        print_parse_error(err.into_diagnostic_error(DiagnosticSource::new(&werkfile, &source_code)))
-    })?;
+    // Report all syntax errors in the Werkfile at once.
+    let (mut ast, parse_errors) = werk_parser::parse_werk_recovering(&werkfile, &source_code);
+    if !parse_errors.is_empty() {
+        for err in parse_errors {
+            print_diagnostic(
+                err.into_diagnostic_error(DiagnosticSource::new(&werkfile, &source_code)),
+            );
+        }
+        return Err(Error::Parse);
+    }
+
+    // A task with the name of a subcommand takes precedence over it.
+    resolve_task_subcommand(&mut args, &ast.root);
+
+    // Determine the workspace directory.
+    let workspace_dir = get_workspace_dir(&args, &werkfile)?;
 
     // Read the configuration statements from the AST.
     let config = werk_runner::ir::Config::new(&ast).map_err(|err| {
         print_eval_error(err.into_diagnostic_error(DiagnosticSource::new(&werkfile, &source_code)))
     })?;
 
-    let settings = get_workspace_settings(&config, &args, &workspace_dir, color_stdout)?;
+    // The output of recipe commands is forwarded to stderr, so child processes
+    // get color when stderr supports it.
+    let mut settings = get_workspace_settings(&config, &args, &workspace_dir, color_stderr)?;
+    // `werk check` reports the warnings of strict mode.
+    let checking = matches!(args.command, Some(Command::Check));
+    settings.strict |= checking;
+    // `werk verify --repro` reruns every recipe, so both builds are complete.
+    if matches!(args.command, Some(Command::Verify { repro: true, .. })) {
+        settings.force_all();
+    }
 
     tracing::info!("Project directory: {}", workspace_dir.display());
     tracing::info!("Output directory: {}", settings.output_directory.display());
 
-    let io: Arc<dyn werk_runner::Io> = if args.dry_run || args.list {
+    let exporting = matches!(
+        args.command,
+        Some(
+            Command::Export { .. }
+                | Command::Compdb { .. }
+                | Command::WhichRecipe { .. }
+                | Command::Query { .. }
+                | Command::Help { .. }
+                | Command::Eval { .. }
+                | Command::Verify { repro: false, .. }
+        )
+    );
+    // `werk doctor` reports programs that are not found, instead of failing
+    // to evaluate the Werkfile.
+    let doctoring = matches!(args.command, Some(Command::Doctor));
+    let io: Arc<dyn werk_runner::Io> = if args.pure || checking || doctoring {
+        Arc::new(dry_run::DryRun::pure())
+    } else if args.dry_run || args.list || args.list_outputs || args.plan || exporting {
         Arc::new(dry_run::DryRun::new())
     } else {
         Arc::new(werk_runner::RealSystem::new())
     };
 
+    // Read and parse imported Werkfiles.
+    let werkfile_dir = werkfile.parent().unwrap_or(&workspace_dir);
+    let import_sources = ImportSources::load(&*io, &ast, werkfile_dir).map_err(print_eval_error)?;
+    ast.imports = import_sources.parse().map_err(print_parse_error)?;
+
     let renderer = render::make_renderer(render::OutputSettings {
         logging_enabled: args.output.log.is_some() || args.list,
         color: color_stderr,
@@ -234,39 +690,225 @@ async fn try_main(args: Args) -> Result<(), Error> {
         } else {
             args.output.output_format
         },
-        print_recipe_commands: args.output.print_commands | args.output.verbose,
-        print_fresh: args.output.print_fresh | args.output.verbose,
-        dry_run: args.dry_run,
-        quiet: args.output.quiet && !args.output.verbose && !args.output.loud,
-        loud: args.output.loud | args.output.verbose,
-        explain: args.output.explain | args.output.verbose,
+        verbosity: render::Verbosity::new(args.output.verbose, args.output.quiet),
+        flags: render::OutputFlags {
+            print_recipe_commands: args.output.print_commands
+                || config.print_commands.unwrap_or(false),
+            print_fresh: args.output.print_fresh,
+            loud: args.output.loud,
+            explain: args.output.explain,
+        },
+        echo_template: config.echo_template.clone(),
+        dry_run: args.dry_run || args.pure,
+        output_group: args.output.output_group,
+        terminal_progress: config.terminal_progress.unwrap_or(true),
     });
 
+    // Read and parse the Werkfiles of workspace members, and their imports.
+    let member_sources = MemberSources::load(&*io, &ast, werkfile_dir).map_err(print_eval_error)?;
+    if args.watch && !member_sources.is_empty() {
+        return Err(Error::WatchMembers);
+    }
+    let mut member_asts = member_sources.parse().map_err(print_parse_error)?;
+    let member_imports = member_sources
+        .iter()
+        .zip(&member_asts)
+        .map(|((_, dir, _), member_ast)| ImportSources::load(&*io, member_ast, dir))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(print_eval_error)?;
+    for (member_ast, imports) in member_asts.iter_mut().zip(&member_imports) {
+        member_ast.imports = imports.parse().map_err(print_parse_error)?;
+    }
+
+    let mut members = Vec::with_capacity(member_asts.len());
+    let mut num_warnings = 0;
+    for ((name, dir, _), member_ast) in member_sources.iter().zip(&member_asts) {
+        let member_config = werk_runner::ir::Config::new(member_ast)
+            .map_err(|err| print_eval_error(err.into_diagnostic_error(member_ast)))?;
+        let mut member_settings = get_member_settings(&member_config, &settings, dir)?;
+        member_settings.strict |= checking;
+        tracing::info!("Member `{name}` directory: {}", dir.display());
+        let workspace = Workspace::new_with_diagnostics(
+            member_ast,
+            &*io,
+            &*renderer,
+            dir.to_owned(),
+            &member_settings,
+        );
+        renderer.flush();
+        let mut workspace = workspace.map_err(print_error)?;
+        num_warnings += print_warnings(&mut workspace, member_ast);
+        members.push(Member {
+            name,
+            workspace,
+            default_target: member_config.default_target,
+        });
+    }
+
     let workspace = Workspace::new_with_diagnostics(
         &ast,
         &*io,
         &*renderer,
         workspace_dir.into_owned(),
         &settings,
-    )
-    .map_err(print_error)?;
+    );
+    renderer.flush();
+    let mut workspace = workspace.map_err(print_error)?;
+    num_warnings += print_warnings(&mut workspace, &ast);
+    workspace.members = &members;
+    let workspace = workspace;
+
+    // The workspace selected with `-p`, used by commands that inspect a
+    // single workspace.
+    let (selected, selected_ast, default_target) = match args.member {
+        Some(ref name) => {
+            let index = members
+                .iter()
+                .position(|member| member.name == name.as_str())
+                .ok_or_else(|| Error::NoSuchMember(name.clone()))?;
+            let member = &members[index];
+            (
+                &member.workspace,
+                &member_asts[index],
+                member.default_target.as_deref(),
+            )
+        }
+        None => (&workspace, &ast, config.default_target.as_deref()),
+    };
 
     if args.list {
         let mut output = AutoStream::new(std::io::stdout(), color_stdout);
-        print_list(&workspace.manifest, &mut output);
+        print_list(selected, &mut output);
+        if args.member.is_none() && !members.is_empty() {
+            print_members(&members, &mut output);
+        }
+        return Ok(());
+    }
+
+    if args.list_outputs {
+        let runner = Runner::new(selected);
+        let outputs = runner.list_outputs();
+        let mut output = AutoStream::new(std::io::stdout(), color_stdout);
+        print_outputs(&outputs, &mut output);
         return Ok(());
     }
 
+    match args.command {
+        Some(Command::Export { ref format }) => {
+            let ExportFormat::Ninja { ref targets, .. } = *format;
+            let plans = plan_targets(selected, targets, default_target)?;
+            return export::run(selected, &plans, format);
+        }
+        Some(Command::Compdb {
+            ref targets,
+            ref output,
+        }) => {
+            let plans = plan_targets(selected, targets, default_target)?;
+            return compdb::run(selected, &plans, output.as_deref());
+        }
+        Some(Command::WhichRecipe { ref target }) => {
+            let mut output = AutoStream::new(std::io::stdout(), color_stdout);
+            return which_recipe::run(selected, target, &mut output);
+        }
+        Some(Command::Help { ref task }) => {
+            if selected.manifest.match_task_recipe(task).is_none() {
+                return Err(Error::NoSuchTask(task.clone()));
+            }
+            let plans = plan_targets(selected, std::slice::from_ref(task), None)?;
+            let mut output = AutoStream::new(std::io::stdout(), color_stdout);
+            return help::run(selected, selected_ast, &plans[0], &mut output);
+        }
+        Some(Command::Query { ref target }) => {
+            let plans = plan_targets(selected, std::slice::from_ref(target), None)?;
+            let mut output = AutoStream::new(std::io::stdout(), color_stdout);
+            return query::run(selected, &plans[0], &mut output);
+        }
+        Some(Command::Eval { ref expr }) => {
+            let mut output = AutoStream::new(std::io::stdout(), color_stdout);
+            return eval::run(selected, expr.as_deref(), &mut output);
+        }
+        Some(Command::Doctor) => {
+            let mut output = AutoStream::new(std::io::stdout(), color_stdout);
+            return doctor::run(selected, color_stderr, &mut output);
+        }
+        Some(Command::Verify { repro: false, .. }) => {
+            let mut output = AutoStream::new(std::io::stdout(), color_stdout);
+            return verify::run(selected, args.output.verbose > 0, &mut output);
+        }
+        Some(Command::Verify {
+            repro: true,
+            ref target,
+        }) => {
+            let target = target
+                .as_deref()
+                .or(default_target)
+                .ok_or(Error::NoTarget)?;
+            let target = match args.member {
+                Some(ref member) => format!("//{member}:{target}"),
+                None => target.to_owned(),
+            };
+            let mut output = AutoStream::new(std::io::stdout(), color_stdout);
+            return verify::run_repro(&workspace, &target, &*renderer, &mut output).await;
+        }
+        Some(Command::Check) => {
+            if num_warnings > 0 {
+                return Err(Error::Warnings(num_warnings));
+            }
+            return Ok(());
+        }
+        _ => (),
+    }
+
     let target = args
         .target
         .clone()
-        .or_else(|| config.default_target.clone());
-    let Some(target) = target else {
-        return Err(Error::NoTarget);
+        .or_else(|| default_target.map(String::from));
+    let target = match target {
+        Some(target) => target,
+        None => {
+            let Some(target) = pick_target(selected, color_stdout)? else {
+                return Err(Error::NoTarget);
+            };
+            target
+        }
     };
+    // Targets in members are built by the root runner, so dependencies
+    // between members are resolved.
+    let target = match args.member {
+        Some(ref member) => format!("//{member}:{target}"),
+        None => target,
+    };
+
+    if args.emit_depfile.is_some() && werk_runner::is_target_glob(&target) {
+        return Err(Error::DepfileGlob);
+    }
 
     let runner = Runner::new(&workspace);
-    let result = runner.build_or_run(&target).await;
+
+    if args.plan {
+        let plans = plan_target_or_glob(&runner, &target);
+        renderer.flush();
+        let plans = plans.map_err(print_error)?;
+        let mut output = AutoStream::new(std::io::stdout(), color_stdout);
+        for plan in &plans {
+            print_plan(&workspace, plan, &mut output);
+        }
+        return Ok(());
+    }
+
+    // Don't leave commands running when werk is interrupted or terminated. In
+    // `--watch`, Ctrl-C is handled by `autowatch_loop()` after this build.
+    if !args.watch {
+        _ = ctrlc::set_handler(|| {
+            werk_runner::kill_child_processes();
+            std::process::exit(130);
+        });
+    }
+
+    workspace.record_recent_target(&target);
+    let result = build_target_or_glob(&runner, &target).await;
+    let summary = report_summary(&*renderer, &runner, args.summary_json.as_deref());
+    renderer.flush();
 
     let write_cache = match result {
         Ok(_) => true,
@@ -274,49 +916,94 @@ async fn try_main(args: Args) -> Result<(), Error> {
     };
 
     if write_cache {
-        if let Err(err) = workspace.finalize().await {
-            eprintln!("Error writing `.werk-cache`: {err}")
+        let workspaces =
+            std::iter::once(&workspace).chain(members.iter().map(|member| &member.workspace));
+        for workspace in workspaces {
+            if let Err(err) = workspace.finalize().await {
+                eprintln!("Error writing `.werk-cache`: {err}")
+            }
         }
     }
 
+    let results = runner.results();
     std::mem::drop(runner);
 
+    if let (Some(depfile_path), Ok(())) = (&args.emit_depfile, &result) {
+        let werkfiles = std::iter::once(&*werkfile)
+            .chain(import_sources.paths())
+            .chain(member_sources.iter().map(|(_, _, werkfile)| werkfile))
+            .chain(member_imports.iter().flat_map(ImportSources::paths));
+        let workspaces =
+            std::iter::once(&workspace).chain(members.iter().map(|member| &member.workspace));
+        let mut deps = werkfiles
+            .map(|path| path.to_path_buf().into_inner())
+            .collect::<Vec<_>>();
+        for workspace in workspaces {
+            deps.extend(
+                workspace
+                    .consulted_files()
+                    .into_iter()
+                    .map(Absolute::into_inner),
+            );
+        }
+        let depfile = werk_runner::depfile::Depfile {
+            target: depfile_target(&workspace, &target),
+            deps,
+        };
+        std::fs::write(depfile_path, depfile.to_string())?;
+    }
+
+    summary?;
+
     if args.watch {
         autowatch_loop(
             std::time::Duration::from_millis(args.watch_delay),
             workspace,
             werkfile.clone(),
+            import_sources
+                .paths()
+                .map(|path| path.to_path_buf())
+                .collect(),
             args.target,
             args.output_dir.as_deref(),
+            args.summary_json.as_deref(),
             &settings,
         )
         .await?;
         Ok(())
     } else {
-        result.map(|_| ()).map_err(print_error)
+        result.map_err(|err| print_build_error(err, &results))
     }
 }
 
+#[expect(clippy::too_many_arguments)]
 async fn autowatch_loop(
     timeout: std::time::Duration,
     // The initial workspace built by main(). Must be finalize()d.
     workspace: Workspace<'_>,
     werkfile: Absolute<std::path::PathBuf>,
+    // Werkfiles imported by the manifest, which are also watched.
+    imports: Vec<Absolute<std::path::PathBuf>>,
     // Target to keep building
     target_from_args: Option<String>,
     output_directory_from_args: Option<&std::path::Path>,
+    summary_json: Option<&std::path::Path>,
     settings: &WorkspaceSettings,
 ) -> Result<(), notify::Error> {
     let (notification_sender, notification_receiver) = smol::channel::bounded(1);
 
     let (ctrlc_sender, ctrlc_receiver) = smol::channel::bounded(1);
     _ = ctrlc::set_handler(move || {
+        // Recipe commands don't receive Ctrl-C from the terminal, so stop the
+        // build that is running, if any, before the loop stops.
+        werk_runner::kill_child_processes();
         _ = ctrlc_sender.try_send(());
     });
 
     let (io, render) = (workspace.io, workspace.render);
 
-    let watch_manifest = HashSet::from_iter([werkfile.clone()]);
+    let mut watch_manifest = HashSet::from_iter([werkfile.clone()]);
+    watch_manifest.extend(imports);
     let mut watch_set = watch_manifest.clone();
     watch_set.extend(workspace.workspace_files().filter_map(|(_, entry)| {
         if entry.metadata.is_file {
@@ -375,7 +1062,7 @@ async fn autowatch_loop(
 
         let ast = werk_parser::parse_werk_with_diagnostics(&werkfile, &source_code);
 
-        let ast = match ast {
+        let mut ast = match ast {
             Ok(ast) => ast,
             Err(err) => {
                 print_parse_error(err);
@@ -384,6 +1071,27 @@ async fn autowatch_loop(
             }
         };
 
+        // Reload imported Werkfiles.
+        let werkfile_dir = werkfile.parent().unwrap_or(&workspace_dir);
+        let import_sources = match ImportSources::load(io, &ast, werkfile_dir) {
+            Ok(import_sources) => import_sources,
+            Err(err) => {
+                print_eval_error(err);
+                watch_set = watch_manifest.clone();
+                continue;
+            }
+        };
+        watch_manifest = HashSet::from_iter([werkfile.clone()]);
+        watch_manifest.extend(import_sources.paths().map(|path| path.to_path_buf()));
+        ast.imports = match import_sources.parse() {
+            Ok(imports) => imports,
+            Err(err) => {
+                print_parse_error(err);
+                watch_set = watch_manifest.clone();
+                continue;
+            }
+        };
+
         // Reload config.
         let config = match werk_runner::ir::Config::new_with_diagnostics(&ast) {
             Ok(config) => config,
@@ -418,6 +1126,7 @@ async fn autowatch_loop(
             );
             settings.output_directory = out_dir;
         }
+        settings.apply_config(&config);
 
         let target = target_from_args
             .clone()
@@ -428,7 +1137,7 @@ async fn autowatch_loop(
             continue;
         };
 
-        let workspace = match Workspace::new_with_diagnostics(
+        let mut workspace = match Workspace::new_with_diagnostics(
             &ast,
             io,
             render,
@@ -437,12 +1146,14 @@ async fn autowatch_loop(
         ) {
             Ok(workspace) => workspace,
             Err(err) => {
+                render.flush();
                 print_error(err);
                 // Workspace evaluation may depend on other files, so just keep
                 // the current watchset.
                 continue;
             }
         };
+        print_warnings(&mut workspace, &ast);
 
         // Update the watchset.
         watch_set.clear();
@@ -457,11 +1168,16 @@ async fn autowatch_loop(
 
         // Finally, rebuild the target!
         let runner = Runner::new(&workspace);
-        let write_cache = match runner.build_or_run(&target).await {
+        let result = build_target_or_glob(&runner, &target).await;
+        if let Err(err) = report_summary(render, &runner, summary_json) {
+            eprintln!("Error writing build summary: {err}");
+        }
+        let write_cache = match result {
             Ok(_) => true,
             Err(err) => {
                 let write_cache = err.error.should_still_write_werk_cache();
-                print_error(err);
+                render.flush();
+                print_build_error(err, &runner.results());
                 write_cache
             }
         };
@@ -498,11 +1214,62 @@ fn make_notifier_for_files(
     Ok(notifier)
 }
 
-pub fn print_list(doc: &werk_runner::ir::Manifest, out: &mut dyn std::io::Write) {
+pub fn print_plan(
+    workspace: &Workspace<'_>,
+    plan: &werk_runner::Plan,
+    out: &mut dyn std::io::Write,
+) {
+    for node in plan {
+        let kind = match node.kind {
+            werk_runner::PlanNodeKind::Task => "task",
+            werk_runner::PlanNodeKind::Build => "build",
+            werk_runner::PlanNodeKind::Exists(_) => "source",
+            werk_runner::PlanNodeKind::Missing => "missing",
+        };
+        if let Some(fingerprint) = node.fingerprint {
+            _ = writeln!(
+                out,
+                "{} {}",
+                node.task_id.bright_yellow(),
+                format_args!("({kind}, {:032x})", fingerprint.0).dimmed(),
+            );
+        } else {
+            _ = writeln!(
+                out,
+                "{} {}",
+                node.task_id.bright_yellow(),
+                format_args!("({kind})").dimmed()
+            );
+        }
+
+        for dependency in &node.dependencies {
+            _ = writeln!(out, "  {} {}", "<-".bright_purple(), dependency);
+        }
+        if let Some(other) = node.same_commands_as {
+            _ = writeln!(
+                out,
+                "  {} {}",
+                "=".bright_purple(),
+                format_args!("same commands as {other}").dimmed()
+            );
+        } else {
+            for command in &node.commands {
+                let command = workspace.redact_run_command(command);
+                _ = writeln!(out, "  {} {}", "$".bright_purple(), command);
+            }
+        }
+    }
+}
+
+pub fn print_list(workspace: &Workspace<'_>, out: &mut dyn std::io::Write) {
+    let doc = &workspace.manifest;
     let globals = doc
         .globals
         .iter()
-        .map(|(k, v)| (k, format!("{}", v.value.display_friendly(80)), &v.comment))
+        .map(|(k, v)| {
+            let value = workspace.redact_value(&v.value);
+            (k, format!("{}", value.display_friendly(80)), &v.comment)
+        })
         .collect::<Vec<_>>();
     let max_global_name_len = globals
         .iter()
@@ -550,6 +1317,13 @@ pub fn print_list(doc: &werk_runner::ir::Manifest, out: &mut dyn std::io::Write)
             }
         }
 
+        if !doc.profiles.is_empty() || max_command_len != 0 || max_pattern_len != 0 {
+            _ = writeln!(out);
+        }
+    }
+
+    if !doc.profiles.is_empty() {
+        print_profiles(doc, out);
         if max_command_len != 0 || max_pattern_len != 0 {
             _ = writeln!(out);
         }
@@ -595,17 +1369,123 @@ pub fn print_list(doc: &werk_runner::ir::Manifest, out: &mut dyn std::io::Write)
     }
 }
 
-pub fn find_werkfile() -> Result<Absolute<std::path::PathBuf>, Error> {
-    const WERKFILE_NAMES: &[&str] = &["Werkfile", "werkfile", "build.werk"];
+fn print_profiles(doc: &werk_runner::ir::Manifest, out: &mut dyn std::io::Write) {
+    let max_profile_len = doc
+        .profiles
+        .keys()
+        .map(|name| name.as_str().len())
+        .max()
+        .unwrap_or(0);
 
+    _ = writeln!(out, "{}", "Profiles:".bright_purple());
+    for (name, profile) in &doc.profiles {
+        let selected = if doc.profile == Some(*name) {
+            " (selected)"
+        } else {
+            ""
+        };
+        if profile.doc_comment.is_empty() {
+            _ = writeln!(out, "  {}{}", name.bright_cyan(), selected.bright_green());
+        } else {
+            _ = writeln!(
+                out,
+                "  {} {}{}",
+                format_args!("{: <w$}", name, w = max_profile_len).bright_cyan(),
+                profile.doc_comment.dimmed(),
+                selected.bright_green(),
+            );
+        }
+    }
+}
+
+/// List the outputs of each build recipe, as found by
+/// [`Runner::list_outputs()`].
+pub fn print_outputs(
+    outputs: &[(
+        &werk_runner::ir::BuildRecipe,
+        Vec<Absolute<werk_fs::PathBuf>>,
+    )],
+    out: &mut dyn std::io::Write,
+) {
+    for (recipe, recipe_outputs) in outputs {
+        _ = writeln!(out, "{}", recipe.pattern.string.bright_yellow());
+        if recipe_outputs.is_empty() {
+            _ = writeln!(out, "  {}", "(no outputs)".dimmed());
+        }
+        for output in recipe_outputs {
+            _ = writeln!(out, "  {output}");
+        }
+    }
+}
+
+/// List the workspace members, along with their default targets.
+pub fn print_members(members: &[Member], out: &mut dyn std::io::Write) {
+    _ = writeln!(out);
+    _ = writeln!(out, "{}", "Workspace members:".bright_purple());
+    for member in members {
+        match member.default_target {
+            Some(ref target) => {
+                _ = writeln!(
+                    out,
+                    "  {} {}",
+                    format_args!("//{}", member.name).bright_cyan(),
+                    format_args!("(default: {target})").dimmed()
+                )
+            }
+            None => _ = writeln!(out, "  {}", format_args!("//{}", member.name).bright_cyan()),
+        }
+    }
+}
+
+fn print_version(args: &Args, out: &mut dyn std::io::Write) {
+    _ = writeln!(out, "werk {}", version_string());
+    if args.output.verbose == 0 {
+        return;
+    }
+
+    let werkfile = match args.file {
+        Some(ref file) => file.clone().normalize().map_err(Error::from),
+        None => find_werkfile(),
+    };
+    match werkfile {
+        Ok(werkfile) => _ = writeln!(out, "werkfile: {}", werkfile.display()),
+        Err(err) => _ = writeln!(out, "werkfile: {err}"),
+    }
+    _ = writeln!(out, "front-end: werk");
+    _ = writeln!(
+        out,
+        "werkfile names: {}",
+        werk_runner::werkfile_names(&werk_runner::RealSystem::new()).join(", ")
+    );
+}
+
+pub fn find_werkfile() -> Result<Absolute<std::path::PathBuf>, Error> {
     let mut current = Absolute::current_dir()?;
+    let io = werk_runner::RealSystem::new();
+    let names = werk_runner::werkfile_names(&io);
 
     loop {
-        for name in WERKFILE_NAMES {
-            let candidate = current.join(name).unwrap();
-            if candidate.is_file() {
-                return Ok(candidate);
-            }
+        let found = werk_runner::select_werkfile(
+            &names,
+            |name| {
+                current
+                    .join(name)
+                    .is_ok_and(|candidate| candidate.is_file())
+            },
+            |a, b| match (current.join(a), current.join(b)) {
+                (Ok(a), Ok(b)) => werk_runner::Io::is_same_file(&io, &a, &b).unwrap_or(false),
+                _ => false,
+            },
+        )
+        .map_err(|[first, second]| {
+            Error::AmbiguousWerkfile(
+                current.to_path_buf().into_inner(),
+                first.to_owned(),
+                second.to_owned(),
+            )
+        })?;
+        if let Some(name) = found {
+            return Ok(current.join(name).unwrap());
         }
 
         if let Some(parent) = current.parent() {
@@ -641,7 +1521,7 @@ pub fn get_workspace_settings(
     config: &werk_runner::ir::Config,
     args: &Args,
     workspace_dir: &Absolute<std::path::Path>,
-    color_stdout: ColorOutputKind,
+    color_stderr: ColorOutputKind,
 ) -> Result<WorkspaceSettings, Error> {
     let out_dir = find_output_directory(
         workspace_dir,
@@ -651,6 +1531,7 @@ pub fn get_workspace_settings(
 
     let mut settings = WorkspaceSettings::new(workspace_dir.to_owned());
     settings.jobs = args.jobs.unwrap_or_else(num_cpus::get);
+    settings.apply_config(config);
     settings.output_directory = out_dir;
     for def in &args.define {
         let Some((key, value)) = def.split_once('=') else {
@@ -658,7 +1539,35 @@ pub fn get_workspace_settings(
         };
         settings.define(key, value);
     }
-    settings.force_color = color_stdout.supports_color();
+    if let Some(ref profile) = args.profile {
+        settings.profile(profile);
+    }
+    settings.strict |= args.strict;
+    for tool in &args.tool {
+        let Some((name, path)) = tool.split_once('=') else {
+            return Err(Error::InvalidToolArg(tool.clone()));
+        };
+        let Ok(path) = std::path::Path::join(workspace_dir, path).normalize() else {
+            return Err(Error::InvalidToolArg(tool.clone()));
+        };
+        settings.tool(name, path);
+    }
+    settings.force_color = color_stderr.supports_color();
+    match args.force {
+        Some(ref targets) if targets.is_empty() => {
+            settings.force_all();
+        }
+        Some(ref targets) => {
+            for target in targets {
+                let path = werk_fs::Path::new(target)
+                    .and_then(|path| path.absolutize(werk_fs::Path::ROOT))
+                    .map_err(|err| Error::InvalidTarget(target.clone(), err))?;
+                settings.force_target(path.into_owned());
+            }
+        }
+        None => (),
+    }
+    settings.keep_going = args.keep_going;
 
     settings.artificial_delay = std::env::var("_WERK_ARTIFICIAL_DELAY")
         .ok()
@@ -668,6 +1577,33 @@ pub fn get_workspace_settings(
     Ok(settings)
 }
 
+/// Settings for the workspace of a member. Command-line overrides like
+/// `--output-dir`, `--define`, `--profile`, and `--strict` only apply to the
+/// root workspace, but `--tool` overrides and the results of searching `PATH`
+/// are shared.
+fn get_member_settings(
+    config: &werk_runner::ir::Config,
+    root: &WorkspaceSettings,
+    member_dir: &Absolute<std::path::Path>,
+) -> Result<WorkspaceSettings, Error> {
+    let mut settings = WorkspaceSettings::new(find_output_directory(
+        member_dir,
+        None,
+        config.output_directory.as_deref(),
+    )?);
+    settings.jobs = root.jobs;
+    settings.apply_config(config);
+    settings.tools.clone_from(&root.tools);
+    settings.which_cache = root.which_cache.clone();
+    settings.force_color = root.force_color;
+    if root.force == werk_runner::ForceRebuild::All {
+        settings.force_all();
+    }
+    settings.keep_going = root.keep_going;
+    settings.artificial_delay = root.artificial_delay;
+    Ok(settings)
+}
+
 fn find_output_directory(
     workspace_dir: &Absolute<std::path::Path>,
     from_args: Option<&std::path::Path>,
@@ -686,11 +1622,159 @@ fn find_output_directory(
     }
 }
 
+/// Build or run `target`, or build all files matching it if it is a glob.
+async fn build_target_or_glob<'a>(
+    runner: &Runner<'a>,
+    target: &str,
+) -> Result<(), DiagnosticError<'a, werk_runner::Error, &'a Workspace<'a>>> {
+    if werk_runner::is_target_glob(target) {
+        runner.build_glob(target).await.map(drop)
+    } else {
+        runner.build_or_run(target).await.map(drop)
+    }
+}
+
+/// Render the summary of the run, and write it to `summary_json` if given.
+fn report_summary(
+    render: &dyn werk_runner::Render,
+    runner: &Runner<'_>,
+    summary_json: Option<&std::path::Path>,
+) -> std::io::Result<()> {
+    let summary = runner.summary();
+    render.build_summary(&summary);
+    if let Some(path) = summary_json {
+        let json = serde_json::to_string_pretty(&render::JsonBuildSummary::new(&summary))?;
+        std::fs::write(path, json + "\n")?;
+    }
+    Ok(())
+}
+
+/// The rule name of a depfile written by `--emit-depfile`. Tasks and member
+/// labels are named as given, while files are named by their path on disk.
+fn depfile_target(workspace: &Workspace<'_>, target: &str) -> std::path::PathBuf {
+    if workspace.manifest.match_task_recipe(target).is_some()
+        || werk_runner::parse_member_label(target).is_some()
+    {
+        return target.into();
+    }
+    let Ok(path) = werk_fs::Path::new(target).and_then(|path| path.absolutize(werk_fs::Path::ROOT))
+    else {
+        return target.into();
+    };
+    match workspace.get_project_file(&path) {
+        Some(entry) => entry.path.to_path_buf().into_inner(),
+        None => workspace
+            .get_output_file_path(&path)
+            .map_or_else(|_| target.into(), Absolute::into_inner),
+    }
+}
+
+/// Resolve the build plan for `target`, or the build plans of all files
+/// matching it if it is a glob.
+fn plan_target_or_glob<'a>(
+    runner: &Runner<'a>,
+    target: &str,
+) -> Result<Vec<werk_runner::Plan>, DiagnosticError<'a, werk_runner::Error, &'a Workspace<'a>>> {
+    if werk_runner::is_target_glob(target) {
+        runner
+            .expand_target_glob(target)?
+            .iter()
+            .map(|path| runner.plan(path.as_str()))
+            .collect()
+    } else {
+        runner.plan(target).map(|plan| vec![plan])
+    }
+}
+
+/// Resolve the build plans for `targets`, or the default target if there are
+/// none, without running any recipe commands.
+fn plan_targets(
+    workspace: &Workspace<'_>,
+    targets: &[String],
+    default_target: Option<&str>,
+) -> Result<Vec<werk_runner::Plan>, Error> {
+    let runner = Runner::new(workspace);
+    let plan = |target: &str| {
+        let plan = runner.plan(target);
+        workspace.render.flush();
+        plan.map_err(print_error)
+    };
+    if targets.is_empty() {
+        let target = default_target.ok_or(Error::NoTarget)?;
+        return Ok(vec![plan(target)?]);
+    }
+    targets.iter().map(|target| plan(target)).collect()
+}
+
 fn print_error<E: Diagnostic, R: DiagnosticFileRepository>(err: DiagnosticError<E, R>) -> Error {
     print_diagnostic(err);
     Error::Runner
 }
 
+/// Print the error of a failed build. When more than one task failed, like
+/// with `--keep-going`, the errors of all failed tasks are printed instead,
+/// each followed by the output of its commands, and then a table of the failed
+/// tasks. The log file of each failed task is printed along with its error.
+fn print_build_error<'a>(
+    err: DiagnosticError<'a, werk_runner::Error, &'a Workspace<'a>>,
+    results: &BuildResults,
+) -> Error {
+    let human = ERROR_FORMAT.get().copied().unwrap_or_default() == ErrorFormat::Human;
+    let print_log_file = |task: &werk_runner::TaskResult| {
+        if let (true, Some(log_file)) = (human, &task.log_file) {
+            anstream::eprintln!("{} {}", "Full output:".bright_purple(), log_file.display());
+        }
+    };
+
+    let failures = results.failures().collect::<Vec<_>>();
+    if failures.len() <= 1 {
+        let err = print_error(err);
+        if let [(task, _)] = failures[..] {
+            print_log_file(task);
+        }
+        return err;
+    }
+    let workspace = err.repository;
+    let mut rows = Vec::with_capacity(failures.len());
+    for (task, failure) in &failures {
+        let err = failure.error.clone().into_diagnostic_error(workspace);
+        rows.push((
+            &task.task,
+            DiagnosticSummary::new(&err.error, &err.repository),
+        ));
+        print_diagnostic(err);
+        if human && !failure.output.is_empty() {
+            anstream::eprintln!(
+                "{} {}",
+                "Output of".bright_purple(),
+                task.task.bright_yellow()
+            );
+            let mut stderr = anstream::stderr().lock();
+            _ = std::io::Write::write_all(&mut stderr, &failure.output);
+        }
+        print_log_file(task);
+    }
+
+    if human {
+        let max_task_len = rows.iter().map(|(task, _)| task.len()).max().unwrap_or(0);
+        anstream::eprintln!("{}", "Failed tasks:".bright_red());
+        for (task, summary) in rows {
+            let location = match (summary.file, summary.line, summary.column) {
+                (Some(file), Some(line), Some(column)) => format!("{file}:{line}:{column}: "),
+                (Some(file), ..) => format!("{file}: "),
+                _ => String::new(),
+            };
+            anstream::eprintln!(
+                "  {}  {}{}",
+                format_args!("{task: <max_task_len$}").bright_yellow(),
+                location.dimmed(),
+                summary.message,
+            );
+        }
+    }
+    Error::TasksFailed(failures.len())
+}
+
 fn print_eval_error<E: Diagnostic, R: DiagnosticFileRepository>(
     err: DiagnosticError<E, R>,
 ) -> Error {
@@ -705,8 +1789,37 @@ fn print_parse_error<E: Diagnostic, R: DiagnosticFileRepository>(
     Error::Parse
 }
 
+/// Print the warnings found by static analysis in strict mode, returning how
+/// many there were.
+fn print_warnings<R: DiagnosticFileRepository + Copy>(
+    workspace: &mut Workspace<'_>,
+    repository: R,
+) -> usize {
+    let warnings = std::mem::take(&mut workspace.warnings);
+    let num_warnings = warnings.len();
+    for warning in warnings {
+        print_diagnostic(warning.into_diagnostic_error(repository));
+    }
+    num_warnings
+}
+
 fn print_diagnostic<E: Diagnostic, R: DiagnosticFileRepository>(err: DiagnosticError<E, R>) {
     use annotate_snippets::renderer::DEFAULT_TERM_WIDTH;
+    match ERROR_FORMAT.get().copied().unwrap_or_default() {
+        ErrorFormat::Human => (),
+        ErrorFormat::Short => {
+            let summary = DiagnosticSummary::new(&err.error, &err.repository);
+            anstream::eprintln!("{summary}");
+            return;
+        }
+        ErrorFormat::Json => {
+            let summary = DiagnosticSummary::new(&err.error, &err.repository);
+            if let Ok(json) = serde_json::to_string(&summary) {
+                anstream::eprintln!("{json}");
+            }
+            return;
+        }
+    }
     let renderer = annotate_snippets::Renderer::styled().term_width(
         render::stderr_width()
             .diagnostic_terminal_width()
@@ -714,3 +1827,104 @@ fn print_diagnostic<E: Diagnostic, R: DiagnosticFileRepository>(err: DiagnosticE
     );
     anstream::eprintln!("{}", err.with_renderer(&renderer));
 }
+
+/// Let the user pick a target interactively, when running in a terminal.
+/// Otherwise, list the available targets and return `None`.
+fn pick_target(
+    workspace: &Workspace,
+    color_stdout: ColorOutputKind,
+) -> Result<Option<String>, Error> {
+    if !pick::is_available() {
+        let mut output = AutoStream::new(std::io::stdout(), color_stdout);
+        print_list(workspace, &mut output);
+        return Ok(None);
+    }
+
+    let recent = workspace.recent_targets();
+    let mut candidates = recent
+        .iter()
+        .map(|target| pick::Candidate {
+            name: target.clone(),
+            detail: String::from("(recent)"),
+        })
+        .collect::<Vec<_>>();
+    candidates.extend(
+        workspace
+            .manifest
+            .task_recipes
+            .iter()
+            .filter(|(name, _)| !recent.iter().any(|target| target == *name))
+            .map(|(name, recipe)| pick::Candidate {
+                name: (*name).to_owned(),
+                detail: recipe.doc_comment.clone(),
+            }),
+    );
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(pick::pick(&candidates)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tests::mock_io::{empty_program_output, program_path, Test};
+
+    use super::*;
+
+    fn parse(argv: &[&str]) -> Args {
+        parse_args(argv.iter().map(Into::into).collect())
+    }
+
+    #[test]
+    fn subcommand_or_task() {
+        let args = parse(&["werk", "check"]);
+        assert!(matches!(args.command, Some(Command::Check)));
+        assert_eq!(args.task_or_subcommand.as_deref(), Some("check"));
+
+        let args = parse(&["werk", "--", "check"]);
+        assert!(matches!(args.command, Some(Command::Check)));
+        assert_eq!(args.task_or_subcommand, None);
+
+        let args = parse(&["werk", "help", "build"]);
+        assert!(matches!(args.command, Some(Command::Help { .. })));
+        assert_eq!(args.task_or_subcommand, None);
+
+        let args = parse(&["werk", "help"]);
+        assert!(args.command.is_none());
+        assert_eq!(args.target.as_deref(), Some("help"));
+        assert_eq!(args.task_or_subcommand.as_deref(), Some("help"));
+        assert!(args.subcommand_error.is_some());
+
+        let args = parse(&["werk", "build"]);
+        assert!(args.command.is_none());
+        assert_eq!(args.task_or_subcommand, None);
+    }
+
+    #[test]
+    fn list_and_plan_redact_secrets() -> anyhow::Result<()> {
+        let test = Test::new(
+            r#"
+let token = "hunter2SECRET" | secret
+
+task deploy {
+    run "deploy --token={token}"
+}
+"#,
+        )?;
+        test.io
+            .set_program("deploy", program_path("deploy"), |_, _, _| {
+                Ok(empty_program_output())
+            });
+        let workspace = test.create_workspace(&[]).unwrap();
+        let mut out = Vec::new();
+        print_list(&workspace, &mut out);
+        let plan = Runner::new(&workspace).plan("deploy").unwrap();
+        print_plan(&workspace, &plan, &mut out);
+        let out = String::from_utf8(out)?;
+        assert!(!out.contains("hunter2SECRET"), "{out}");
+        assert!(out.contains("= \"***\""), "{out}");
+        assert!(out.contains("--token=***"), "{out}");
+        Ok(())
+    }
+}
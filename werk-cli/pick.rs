@@ -0,0 +1,323 @@
+//! Interactive target picker, shown when `werk` runs in a terminal without a
+//! target and without a `default` config.
+
+use std::io::{IsTerminal as _, Write};
+
+use owo_colors::OwoColorize as _;
+
+use crate::render::stderr_width;
+
+/// Maximum number of candidates shown at once.
+const MAX_VISIBLE: usize = 10;
+
+pub struct Candidate {
+    pub name: String,
+    /// Shown next to the name, like the doc comment of a task.
+    pub detail: String,
+}
+
+/// Whether the picker can be shown. It needs a terminal for both input and
+/// output, and is only supported on Unix-like systems.
+pub fn is_available() -> bool {
+    cfg!(unix) && std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+}
+
+/// Fuzzy match `query` against `candidate`, ignoring case. Every character of
+/// the query must appear in the candidate in order. Lower scores are better
+/// matches: the score is the position of the first matched character plus
+/// the number of characters skipped between matched characters.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut start = None;
+    let mut chars = candidate.chars().enumerate();
+    let mut last = 0;
+    for q in query.chars() {
+        let (index, _) = chars
+            .by_ref()
+            .find(|(_, c)| c.to_lowercase().eq(q.to_lowercase()))?;
+        match start {
+            None => {
+                start = Some(index);
+                score += index;
+            }
+            Some(_) => score += index - last - 1,
+        }
+        last = index;
+    }
+    Some(score)
+}
+
+/// The indices of the candidates matching `query`, best matches first.
+/// Candidates with the same score keep their order.
+pub fn filter(candidates: &[Candidate], query: &str) -> Vec<usize> {
+    let mut matches = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(index, candidate)| Some((fuzzy_score(query, &candidate.name)?, index)))
+        .collect::<Vec<_>>();
+    matches.sort_by_key(|&(score, _)| score);
+    matches.into_iter().map(|(_, index)| index).collect()
+}
+
+enum Key {
+    Char(char),
+    Backspace,
+    Up,
+    Down,
+    Enter,
+    Cancel,
+    Other,
+}
+
+/// Show the picker on stderr, and return the name of the chosen candidate, or
+/// `None` if the user cancelled.
+pub fn pick(candidates: &[Candidate]) -> std::io::Result<Option<String>> {
+    let _raw = imp::RawMode::enable()?;
+    let mut stderr = std::io::stderr().lock();
+    let width = stderr_width().progress_max_width().unwrap_or(80);
+
+    let mut query = String::new();
+    let mut selected = 0;
+    let result = loop {
+        let matches = filter(candidates, &query);
+        selected = selected.min(matches.len().saturating_sub(1));
+        render(&mut stderr, candidates, &matches, &query, selected, width)?;
+
+        match read_key()? {
+            Key::Char(c) => {
+                query.push(c);
+                selected = 0;
+            }
+            Key::Backspace => {
+                query.pop();
+                selected = 0;
+            }
+            Key::Up => selected = selected.saturating_sub(1),
+            Key::Down => selected += 1,
+            Key::Enter => {
+                if let Some(&index) = matches.get(selected) {
+                    break Some(candidates[index].name.clone());
+                }
+            }
+            Key::Cancel => break None,
+            Key::Other => (),
+        }
+    };
+
+    // Erase the picker.
+    write!(stderr, "\r\x1b[J")?;
+    stderr.flush()?;
+    Ok(result)
+}
+
+fn render(
+    out: &mut dyn Write,
+    candidates: &[Candidate],
+    matches: &[usize],
+    query: &str,
+    selected: usize,
+    width: usize,
+) -> std::io::Result<()> {
+    let max_name_len = matches
+        .iter()
+        .map(|&index| candidates[index].name.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    // Scroll so the selected candidate is visible.
+    let first = selected.saturating_sub(MAX_VISIBLE - 1);
+    let visible = &matches[first..matches.len().min(first + MAX_VISIBLE)];
+
+    write!(out, "\r\x1b[J")?;
+    for (offset, &index) in visible.iter().enumerate() {
+        let candidate = &candidates[index];
+        let line = if candidate.detail.is_empty() {
+            candidate.name.clone()
+        } else {
+            format!(
+                "{: <w$}  {}",
+                candidate.name,
+                candidate.detail,
+                w = max_name_len
+            )
+        };
+        let line = line
+            .chars()
+            .take(width.saturating_sub(3))
+            .collect::<String>();
+        if first + offset == selected {
+            write!(out, "\n\r{} {}", ">".bright_green(), line.bold())?;
+        } else {
+            write!(out, "\n\r  {line}")?;
+        }
+    }
+    if matches.is_empty() {
+        write!(out, "\n\r  {}", "(no matching targets)".dimmed())?;
+    }
+
+    // Move back up to the prompt, and put the cursor after the query.
+    let lines = visible.len().max(1);
+    let prompt = "Build target (Esc to cancel): ";
+    write!(out, "\x1b[{lines}A\r{}{query}", prompt.bright_purple())?;
+    out.flush()
+}
+
+fn read_key() -> std::io::Result<Key> {
+    Ok(match imp::read_byte()? {
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        // Ctrl-C, Ctrl-D, Ctrl-G
+        0x03 | 0x04 | 0x07 => Key::Cancel,
+        0x1b => {
+            // A lone Escape cancels, while arrow keys are escape sequences
+            // that arrive all at once.
+            if !imp::input_pending()? {
+                return Ok(Key::Cancel);
+            }
+            match [imp::read_byte()?, imp::read_byte()?] {
+                [b'[' | b'O', b'A'] => Key::Up,
+                [b'[' | b'O', b'B'] => Key::Down,
+                _ => Key::Other,
+            }
+        }
+        // Ctrl-P, Ctrl-N
+        0x10 => Key::Up,
+        0x0e => Key::Down,
+        byte if byte.is_ascii_graphic() || byte == b' ' => Key::Char(byte as char),
+        byte if byte >= 0x80 => {
+            // Read the rest of a UTF-8 encoded character.
+            let len = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                _ => 4,
+            };
+            let mut buf = [byte, 0, 0, 0];
+            for b in &mut buf[1..len] {
+                *b = imp::read_byte()?;
+            }
+            std::str::from_utf8(&buf[..len])
+                .ok()
+                .and_then(|s| s.chars().next())
+                .map_or(Key::Other, Key::Char)
+        }
+        _ => Key::Other,
+    })
+}
+
+#[cfg(unix)]
+mod imp {
+    /// Puts the terminal in non-canonical mode without echo, restoring the
+    /// previous mode when dropped.
+    pub struct RawMode(libc::termios);
+
+    impl RawMode {
+        pub fn enable() -> std::io::Result<Self> {
+            unsafe {
+                let mut termios: libc::termios = std::mem::zeroed();
+                if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                let original = termios;
+                termios.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+                termios.c_iflag &= !(libc::IXON | libc::ICRNL);
+                termios.c_cc[libc::VMIN] = 1;
+                termios.c_cc[libc::VTIME] = 0;
+                if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(Self(original))
+            }
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.0);
+            }
+        }
+    }
+
+    /// Read a single byte from stdin, bypassing the buffering of
+    /// [`std::io::Stdin`], so [`input_pending()`] sees all unread input.
+    pub fn read_byte() -> std::io::Result<u8> {
+        let mut byte = 0u8;
+        loop {
+            match unsafe { libc::read(libc::STDIN_FILENO, (&raw mut byte).cast(), 1) } {
+                1 => return Ok(byte),
+                0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                _ => {
+                    let err = std::io::Error::last_os_error();
+                    if err.kind() != std::io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether more input arrives within a short time.
+    pub fn input_pending() -> std::io::Result<bool> {
+        let mut fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut fd, 1, 50) } {
+            -1 => Err(std::io::Error::last_os_error()),
+            n => Ok(n > 0),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    pub struct RawMode;
+
+    impl RawMode {
+        pub fn enable() -> std::io::Result<Self> {
+            Err(std::io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    pub fn read_byte() -> std::io::Result<u8> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn input_pending() -> std::io::Result<bool> {
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(names: &[&str]) -> Vec<Candidate> {
+        names
+            .iter()
+            .map(|name| Candidate {
+                name: (*name).to_owned(),
+                detail: String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(fuzzy_score("", "build"), Some(0));
+        assert_eq!(fuzzy_score("bld", "build"), Some(2));
+        assert_eq!(fuzzy_score("BUI", "build"), Some(0));
+        assert_eq!(fuzzy_score("ild", "build"), Some(2));
+        assert_eq!(fuzzy_score("dl", "build"), None);
+    }
+
+    #[test]
+    fn filter_order() {
+        let candidates = candidates(&["test", "build", "build-release", "clean"]);
+        assert_eq!(filter(&candidates, ""), [0, 1, 2, 3]);
+        assert_eq!(filter(&candidates, "bu"), [1, 2]);
+        assert_eq!(filter(&candidates, "le"), [3, 2]);
+        assert_eq!(filter(&candidates, "xyz"), Vec::<usize>::new());
+    }
+}
@@ -0,0 +1,152 @@
+//! Show everything `werk` knows about how a target would be built, without
+//! running any recipe commands.
+
+use std::io::Write;
+
+use owo_colors::OwoColorize as _;
+use werk_runner::{Plan, PlanNode, PlanNodeKind, Workspace};
+
+use crate::{which_recipe, Error};
+
+/// Run the `werk query` command for a target that has already been planned.
+pub fn run(workspace: &Workspace<'_>, plan: &Plan, out: &mut dyn Write) -> Result<(), Error> {
+    let Some(node) = plan.get(plan.root) else {
+        return Ok(());
+    };
+
+    writeln!(out, "{}", node.task_id.bright_yellow())?;
+
+    let task_recipe = match node.kind {
+        PlanNodeKind::Task => workspace.manifest.match_task_recipe(node.task_id.as_str()),
+        _ => None,
+    };
+    // Planning succeeded, so the recipe match is not ambiguous.
+    let build_recipe_match = match node.task_id.as_path() {
+        Some(path) if node.kind == PlanNodeKind::Build => {
+            workspace.manifest.match_build_recipe(path).ok().flatten()
+        }
+        _ => None,
+    };
+
+    if let Some(recipe) = task_recipe {
+        writeln!(
+            out,
+            "  {} task {} {}",
+            "recipe:".bright_purple(),
+            recipe.name.bright_green(),
+            format_args!(
+                "({})",
+                which_recipe::location(workspace, recipe.file, recipe.span)
+            )
+            .dimmed(),
+        )?;
+    } else if let Some(ref recipe_match) = build_recipe_match {
+        writeln!(
+            out,
+            "  {} build {} {}",
+            "recipe:".bright_purple(),
+            format_args!("\"{}\"", recipe_match.recipe.pattern).bright_green(),
+            format_args!(
+                "({})",
+                which_recipe::location(
+                    workspace,
+                    recipe_match.recipe.file,
+                    recipe_match.recipe.span
+                )
+            )
+            .dimmed(),
+        )?;
+        which_recipe::write_match(out, recipe_match)?;
+    } else {
+        let kind = match node.kind {
+            PlanNodeKind::Exists(_) => "file in the workspace",
+            _ => "missing file",
+        };
+        writeln!(out, "  {} none ({kind})", "recipe:".bright_purple())?;
+    }
+
+    if node.kind == PlanNodeKind::Build {
+        if let Some(path) = node.task_id.as_path() {
+            if let Ok(os_path) = workspace.get_output_file_path(path) {
+                writeln!(out, "  {} {}", "output:".bright_purple(), os_path.display())?;
+            }
+        }
+    }
+    if let Some(ref depfile) = node.depfile {
+        writeln!(out, "  {} {depfile}", "depfile:".bright_purple())?;
+    }
+
+    write_dependencies_and_commands(workspace, out, plan, node)?;
+
+    Ok(())
+}
+
+/// Write the direct dependencies of a planned node, and the commands it would
+/// run with secrets redacted.
+pub fn write_dependencies_and_commands(
+    workspace: &Workspace<'_>,
+    out: &mut dyn Write,
+    plan: &Plan,
+    node: &PlanNode,
+) -> std::io::Result<()> {
+    if !node.dependencies.is_empty() {
+        writeln!(out, "  {}", "dependencies:".bright_purple())?;
+        for dependency in &node.dependencies {
+            let kind = match plan.get(*dependency).map(|node| node.kind) {
+                Some(PlanNodeKind::Task) => "task",
+                Some(PlanNodeKind::Build) => "build",
+                Some(PlanNodeKind::Exists(_)) => "source",
+                Some(PlanNodeKind::Missing) | None => "missing",
+            };
+            writeln!(
+                out,
+                "    {dependency} {}",
+                format_args!("({kind})").dimmed()
+            )?;
+        }
+    }
+
+    if !node.commands.is_empty() {
+        writeln!(out, "  {}", "commands:".bright_purple())?;
+        for command in &node.commands {
+            let command = workspace.redact_run_command(command);
+            writeln!(out, "    {} {command}", "$".bright_purple())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tests::mock_io::{empty_program_output, program_path, Test};
+    use werk_runner::Runner;
+
+    #[test]
+    fn redacts_secrets() -> anyhow::Result<()> {
+        let test = Test::new(
+            r#"
+let token = "hunter2SECRET" | secret
+
+build "out.txt" {
+    env "TOKEN" = "{token}"
+    run "fetch --token={token}"
+    info "fetching with {token}"
+}
+"#,
+        )?;
+        test.io
+            .set_program("fetch", program_path("fetch"), |_, _, _| {
+                Ok(empty_program_output())
+            });
+        let workspace = test.create_workspace(&[]).unwrap();
+        let plan = Runner::new(&workspace).plan("out.txt").unwrap();
+        let mut out = Vec::new();
+        super::run(&workspace, &plan, &mut out)?;
+        let out = String::from_utf8(out)?;
+        assert!(!out.contains("hunter2SECRET"), "{out}");
+        assert!(out.contains("--token=***"), "{out}");
+        assert!(out.contains("fetching with ***"), "{out}");
+        Ok(())
+    }
+}
@@ -6,25 +6,110 @@ mod log;
 pub(crate) mod null;
 mod stream;
 
-pub use ansi::term_width::*;
+pub use ansi::{taskbar::Taskbar, term_width::*};
+pub use json::JsonBuildSummary;
 pub use stream::*;
 
 use crate::OutputChoice;
 
-#[derive(Clone, Copy, Debug)]
+/// How much is printed while building, from `-q`, `-v`, and `-vv`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Print nothing but errors and warnings. The output of commands is only
+    /// printed when they fail.
+    Quiet,
+    #[default]
+    Normal,
+    /// Print all commands, explain why targets are outdated, print
+    /// up-to-date targets, and print the output of all commands.
+    Verbose,
+    /// Also print the value of each variable as it is evaluated.
+    VeryVerbose,
+}
+
+impl Verbosity {
+    /// The verbosity from the number of `-v` flags and the `-q` flag, which is
+    /// ignored when `-v` is given.
+    #[must_use]
+    pub fn new(verbose: u8, quiet: bool) -> Self {
+        match verbose {
+            0 if quiet => Verbosity::Quiet,
+            0 => Verbosity::Normal,
+            1 => Verbosity::Verbose,
+            _ => Verbosity::VeryVerbose,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct OutputSettings {
     /// Logging is enabled, so don't try to modify terminal contents in-place.
     pub logging_enabled: bool,
     pub color: ColorOutputKind,
     pub output: OutputChoice,
+    pub verbosity: Verbosity,
+    /// The parts of `--verbose` that were enabled with their own flags. Use
+    /// the methods of the same name, which take the verbosity into account.
+    pub flags: OutputFlags,
+    /// The line printed before each command, from `config echo-template`.
+    pub echo_template: Option<werk_runner::ir::EchoTemplate>,
+    pub dry_run: bool,
+    /// Print the output of each task as one block when it finishes.
+    pub output_group: bool,
+    /// Report progress to the terminal emulator with `OSC 9;4` sequences, when
+    /// the terminal supports them.
+    pub terminal_progress: bool,
+}
+
+/// `--print-commands`, `--print-fresh`, `--loud`, and `--explain`.
+#[derive(Clone, Copy, Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct OutputFlags {
     pub print_recipe_commands: bool,
     pub print_fresh: bool,
-    pub dry_run: bool,
-    pub quiet: bool,
     pub loud: bool,
     pub explain: bool,
 }
 
+impl OutputSettings {
+    /// Print recipe commands as they are executed.
+    #[must_use]
+    pub fn print_recipe_commands(&self) -> bool {
+        self.flags.print_recipe_commands || self.verbosity >= Verbosity::Verbose
+    }
+
+    /// Print recipes that were up-to-date.
+    #[must_use]
+    pub fn print_fresh(&self) -> bool {
+        self.flags.print_fresh || self.verbosity >= Verbosity::Verbose
+    }
+
+    /// Print the output of all commands, even for quiet recipes.
+    #[must_use]
+    pub fn loud(&self) -> bool {
+        self.flags.loud || self.verbosity >= Verbosity::Verbose
+    }
+
+    /// Explain why each outdated target was outdated.
+    #[must_use]
+    pub fn explain(&self) -> bool {
+        self.flags.explain || self.verbosity >= Verbosity::Verbose
+    }
+
+    /// Print nothing but errors and warnings. `--loud` still prints the output
+    /// of commands.
+    #[must_use]
+    pub fn quiet(&self) -> bool {
+        self.verbosity == Verbosity::Quiet
+    }
+
+    /// Print the value of each variable as it is evaluated.
+    #[must_use]
+    pub fn print_evaluation(&self) -> bool {
+        self.verbosity >= Verbosity::VeryVerbose
+    }
+}
+
 pub(crate) struct Bracketed<T>(pub T);
 impl<T: Display> Display for Bracketed<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -41,7 +126,7 @@ impl Display for Step {
 
 pub fn make_renderer(settings: OutputSettings) -> Arc<dyn werk_runner::Render> {
     match settings.output {
-        OutputChoice::Json => Arc::new(json::JsonWatcher::new()),
+        OutputChoice::Json => Arc::new(json::JsonWatcher::new(settings)),
         OutputChoice::Log => Arc::new(log::LogWatcher::new(settings)),
         OutputChoice::Ansi => {
             let stderr = AutoStream::new(std::io::stderr(), settings.color);
@@ -1,76 +1,208 @@
 mod progress;
+pub mod taskbar;
 pub mod term_width;
 
 use anstream::stream::IsTerminal;
 use indexmap::IndexMap;
 use owo_colors::OwoColorize as _;
-use parking_lot::Mutex;
-use werk_runner::{BuildStatus, Error, Outdatedness, ShellCommandLine, TaskId};
+use werk_runner::{BuildStatus, BuildSummary, Echo, Error, Outdatedness, ShellCommandLine, TaskId};
 
-use std::{io::Write, sync::Arc};
+use std::{
+    io::Write,
+    sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, TryRecvError, TrySendError},
+    time::Duration,
+};
 
 use crate::render::Bracketed;
 
 use super::{AutoStream, OutputSettings, Step};
 
+/// Maximum number of events waiting to be rendered.
+const EVENT_QUEUE_SIZE: usize = 4096;
+
+/// How often the status is re-rendered while tasks are running.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
 /// A watcher that outputs to the terminal, emitting "destructive" ANSI escape
 /// codes that modify the existing terminal (i.e. overwriting the bottom line(s)
 /// with current status).
+///
+/// All terminal I/O happens on a dedicated render thread. Watcher methods only
+/// send an event to that thread, so the runner never waits for the terminal
+/// while other tasks are producing output.
+///
+/// The queue of events is bounded. When the terminal falls behind and the
+/// queue is full, events that only update the status, like a command starting
+/// without being echoed, are dropped instead of waiting, because the status is
+/// redrawn from the next event anyway. Output lines and events that start or
+/// finish tasks are never dropped. The render thread drains all waiting events
+/// before redrawing the status once, so it catches up quickly.
 pub struct TerminalRenderer<const LINEAR: bool> {
-    inner: Arc<Mutex<Renderer<LINEAR>>>,
-    _render_task: Option<smol::Task<()>>,
+    sender: SyncSender<RenderEvent>,
+    render_thread: Option<std::thread::JoinHandle<()>>,
+    /// Print evaluated variables (`-vv`). Checked before sending the event, to
+    /// avoid formatting values that aren't printed.
+    print_evaluation: bool,
+    /// Whether commands are echoed, to tell if a `WillExecute` event prints
+    /// anything.
+    print_recipe_commands: bool,
+    dry_run: bool,
 }
 
 impl<const LINEAR: bool> TerminalRenderer<LINEAR> {
     pub fn new(settings: OutputSettings, stderr: AutoStream<std::io::Stderr>) -> Self {
-        let inner = Arc::new(Mutex::new(Renderer {
+        let use_taskbar = !LINEAR && settings.terminal_progress && taskbar::Taskbar::is_supported();
+        let print_evaluation = settings.print_evaluation();
+        let print_recipe_commands = settings.print_recipe_commands();
+        let dry_run = settings.dry_run;
+        let renderer = Renderer::<LINEAR> {
             stderr,
+            defer_status: false,
+            status_deferred: false,
             state: RenderState {
                 current_tasks: IndexMap::new(),
                 num_tasks: 0,
                 num_completed_tasks: 0,
-                progress: if LINEAR {
+                any_failed: false,
+                progress: if LINEAR || settings.quiet() {
                     None
                 } else {
-                    Some(progress::Progress::default())
+                    Some(progress::Progress::new(progress::ProgressStyle::detect()))
                 },
                 settings,
             },
             needs_clear: false,
-        }));
-
-        let render_task = if !LINEAR {
-            // Spawn a task that automatically updates the terminal with the current
-            // status when a long-running task is present.
-            //
-            // TODO: This task polls continuously when a `--watch` loop is
-            // waiting for changes. Consider pausing it when a run is not in
-            // progress.
-            let renderer = Arc::downgrade(&inner);
-            Some(smol::spawn(async move {
-                loop {
-                    smol::Timer::after(std::time::Duration::from_millis(100)).await;
-                    let Some(renderer) = renderer.upgrade() else {
-                        return;
-                    };
-                    Renderer::render_now(&*renderer);
-                }
-            }))
-        } else {
-            None
+            suspended: None,
+            taskbar: use_taskbar.then(taskbar::Taskbar::new),
         };
 
+        let (sender, receiver) = std::sync::mpsc::sync_channel(EVENT_QUEUE_SIZE);
+        let render_thread = std::thread::Builder::new()
+            .name(String::from("werk-render"))
+            .spawn(move || renderer.run(&receiver))
+            .expect("failed to spawn render thread");
+
         Self {
-            inner,
-            _render_task: render_task,
+            sender,
+            render_thread: Some(render_thread),
+            print_evaluation,
+            print_recipe_commands,
+            dry_run,
+        }
+    }
+
+    fn send(&self, event: RenderEvent) {
+        // The render thread only exits when the renderer is dropped.
+        match self.sender.try_send(event) {
+            Ok(()) | Err(TrySendError::Disconnected(_)) => (),
+            Err(TrySendError::Full(event)) => {
+                if !event.is_status_only() {
+                    _ = self.sender.send(event);
+                }
+            }
+        }
+    }
+}
+
+impl<const LINEAR: bool> Drop for TerminalRenderer<LINEAR> {
+    fn drop(&mut self) {
+        self.send(RenderEvent::Shutdown);
+        if let Some(render_thread) = self.render_thread.take() {
+            _ = render_thread.join();
+        }
+    }
+}
+
+/// Owned copy of a watcher call, sent to the render thread.
+enum RenderEvent {
+    WillBuild {
+        task_id: TaskId,
+        num_steps: usize,
+        outdatedness: Outdatedness,
+    },
+    DidBuild {
+        task_id: TaskId,
+        result: Result<BuildStatus, Error>,
+    },
+    WillExecute {
+        task_id: TaskId,
+        command: ShellCommandLine,
+        step: usize,
+        num_steps: usize,
+        /// Whether the command is printed.
+        print: bool,
+    },
+    DidExecute {
+        task_id: TaskId,
+        command: ShellCommandLine,
+        result: std::io::Result<std::process::ExitStatus>,
+        step: usize,
+        num_steps: usize,
+    },
+    WillRetry {
+        task_id: TaskId,
+        command: ShellCommandLine,
+        status: std::process::ExitStatus,
+        attempt: usize,
+        max_attempts: usize,
+        delay: std::time::Duration,
+    },
+    StderrLine {
+        task_id: TaskId,
+        line: Vec<u8>,
+        quiet: bool,
+    },
+    StdoutLine {
+        task_id: TaskId,
+        line: Vec<u8>,
+    },
+    Message(String),
+    Warning(String),
+    RunnerMessage(String),
+    EvaluateVariable {
+        task_id: Option<TaskId>,
+        name: String,
+        value: String,
+    },
+    BuildSummary(BuildSummary),
+    Reset,
+    /// Signal the sender when all previous events have been rendered.
+    Flush(std::sync::mpsc::Sender<()>),
+    /// Clear the status, signal the sender, and hold back all events until
+    /// `Resume`, while an interactive command uses the terminal.
+    Suspend(std::sync::mpsc::Sender<()>),
+    Resume,
+    Shutdown,
+}
+
+impl RenderEvent {
+    /// Whether the event only updates the status, without printing anything
+    /// or changing which tasks are running.
+    fn is_status_only(&self) -> bool {
+        match self {
+            RenderEvent::WillExecute { print, .. } => !print,
+            RenderEvent::DidExecute { result, .. } => {
+                result.as_ref().is_ok_and(std::process::ExitStatus::success)
+            }
+            _ => false,
         }
     }
 }
 
 struct Renderer<const LINEAR: bool> {
     stderr: AutoStream<std::io::Stderr>,
+    /// Don't redraw the status after rendering lines, because more events are
+    /// waiting to be rendered.
+    defer_status: bool,
+    /// Lines were rendered while `defer_status` was set, so the status must be
+    /// redrawn.
+    status_deferred: bool,
     state: RenderState,
     needs_clear: bool,
+    /// Events received while suspended, rendered when resumed.
+    suspended: Option<Vec<RenderEvent>>,
+    /// Progress reported to the terminal emulator, if it supports it.
+    taskbar: Option<taskbar::Taskbar>,
 }
 
 impl<const LINEAR: bool> Renderer<LINEAR> {
@@ -83,10 +215,14 @@ impl<const LINEAR: bool> Renderer<LINEAR> {
             render(&mut self.stderr, &mut self.state)
         } else {
             if self.needs_clear {
-                self.stderr.write_all(b"\x1B[K")?;
+                self.stderr.write_all(b"\x1B[J")?;
                 self.needs_clear = false;
             }
             render(&mut self.stderr, &mut self.state)?;
+            if self.defer_status {
+                self.status_deferred = true;
+                return Ok(());
+            }
 
             if let Some(progress) = self.state.progress.as_mut() {
                 let term_width = term_width::stderr_width();
@@ -96,7 +232,31 @@ impl<const LINEAR: bool> Renderer<LINEAR> {
                 self.needs_clear = true;
             }
 
-            Ok(())
+            self.update_taskbar()
+        }
+    }
+
+    fn update_taskbar(&mut self) -> std::io::Result<()> {
+        let Some(taskbar) = self.taskbar.as_mut() else {
+            return Ok(());
+        };
+        // The progress is hidden again when the build finishes, which is when
+        // the renderer is flushed or reset.
+        let state = if self.state.num_tasks == 0 {
+            taskbar::TaskbarState::Hidden
+        } else {
+            taskbar::TaskbarState::new(
+                self.state.num_completed_tasks,
+                self.state.num_tasks,
+                self.state.any_failed,
+            )
+        };
+        taskbar.set(&mut self.stderr, state)
+    }
+
+    fn hide_taskbar(&mut self) {
+        if let Some(taskbar) = self.taskbar.as_mut() {
+            _ = taskbar.set(&mut self.stderr, taskbar::TaskbarState::Hidden);
         }
     }
 
@@ -110,10 +270,14 @@ impl<const LINEAR: bool> Renderer<LINEAR> {
             render(&mut stdout, &mut self.state)
         } else {
             if self.needs_clear {
-                self.stderr.write_all(b"\x1B[K")?;
+                self.stderr.write_all(b"\x1B[J")?;
                 self.needs_clear = false;
             }
             render(&mut stdout, &mut self.state)?;
+            if self.defer_status {
+                self.status_deferred = true;
+                return Ok(());
+            }
 
             if let Some(progress) = self.state.progress.as_mut() {
                 let term_width = term_width::stderr_width();
@@ -123,7 +287,7 @@ impl<const LINEAR: bool> Renderer<LINEAR> {
                 self.needs_clear = true;
             }
 
-            Ok(())
+            self.update_taskbar()
         }
     }
 }
@@ -132,6 +296,8 @@ struct RenderState {
     current_tasks: IndexMap<TaskId, TaskStatus>,
     num_tasks: usize,
     num_completed_tasks: usize,
+    /// A task failed since the last reset.
+    any_failed: bool,
     progress: Option<progress::Progress>,
     settings: OutputSettings,
 }
@@ -140,6 +306,14 @@ struct TaskStatus {
     pub progress: usize,
     pub num_steps: usize,
     pub captured: Option<Vec<u8>>,
+    /// Output lines buffered until the task finishes, with `--output-group`.
+    pub grouped: Vec<GroupedLine>,
+    pub started: std::time::Instant,
+}
+
+enum GroupedLine {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
 }
 
 impl TaskStatus {
@@ -148,28 +322,168 @@ impl TaskStatus {
             progress: 0,
             num_steps,
             captured: None,
+            grouped: Vec::new(),
+            started: std::time::Instant::now(),
         }
     }
 }
 
 impl<const LINEAR: bool> Renderer<LINEAR> {
-    pub fn render_now(this: &Mutex<Self>) {
-        if !LINEAR {
-            let mut this = this.lock();
-            _ = this.render_lines(|_, _| Ok(()));
+    /// Render events until the renderer is dropped. While tasks are running,
+    /// the status is re-rendered periodically to animate the spinner.
+    fn run(mut self, receiver: &Receiver<RenderEvent>) {
+        loop {
+            let idle = self.state.current_tasks.is_empty() || self.suspended.is_some();
+            let event = if LINEAR || idle {
+                match receiver.recv() {
+                    Ok(event) => event,
+                    Err(_) => break,
+                }
+            } else {
+                match receiver.recv_timeout(REFRESH_INTERVAL) {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => {
+                        _ = self.render_lines(|_, _| Ok(()));
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            };
+
+            if !self.handle_waiting(event, receiver) {
+                break;
+            }
         }
+
+        self.hide_taskbar();
+        _ = self.stderr.flush();
+        _ = std::io::stdout().flush();
+    }
+
+    /// Render an event and all events waiting after it, redrawing the status
+    /// once at the end. Returns false when the renderer should shut down.
+    fn handle_waiting(&mut self, event: RenderEvent, receiver: &Receiver<RenderEvent>) -> bool {
+        let mut event = event;
+        self.defer_status = true;
+        loop {
+            if !self.handle(event) {
+                return false;
+            }
+            event = match receiver.try_recv() {
+                Ok(event) => event,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return false,
+            };
+        }
+        self.defer_status = false;
+        self.render_deferred_status();
+        true
+    }
+
+    fn render_deferred_status(&mut self) {
+        if std::mem::take(&mut self.status_deferred) && self.suspended.is_none() {
+            let defer_status = std::mem::replace(&mut self.defer_status, false);
+            _ = self.render_lines(|_, _| Ok(()));
+            self.defer_status = defer_status;
+        }
+    }
+
+    /// Render an event. Returns false when the renderer should shut down.
+    fn handle(&mut self, event: RenderEvent) -> bool {
+        if let Some(ref mut held) = self.suspended {
+            match event {
+                RenderEvent::Resume => {
+                    for event in self.suspended.take().expect("suspended") {
+                        self.handle(event);
+                    }
+                }
+                RenderEvent::Flush(done) => _ = done.send(()),
+                RenderEvent::Shutdown => return false,
+                event => held.push(event),
+            }
+            return true;
+        }
+
+        match event {
+            RenderEvent::WillBuild {
+                task_id,
+                num_steps,
+                outdatedness,
+            } => self.will_build(task_id, num_steps, &outdatedness),
+            RenderEvent::DidBuild { task_id, result } => self.did_build(task_id, &result),
+            RenderEvent::WillExecute {
+                task_id,
+                command,
+                step,
+                num_steps,
+                print,
+            } => self.will_execute(task_id, &command, step, num_steps, print),
+            RenderEvent::DidExecute {
+                task_id,
+                command,
+                result,
+                step,
+                num_steps,
+            } => self.did_execute(task_id, &command, &result, step, num_steps),
+            RenderEvent::WillRetry {
+                task_id,
+                command,
+                status,
+                attempt,
+                max_attempts,
+                delay,
+            } => self.will_retry(task_id, &command, status, attempt, max_attempts, delay),
+            RenderEvent::StderrLine {
+                task_id,
+                line,
+                quiet,
+            } => self.on_child_process_stderr_line(task_id, &line, quiet),
+            RenderEvent::StdoutLine { task_id, line } => {
+                self.on_child_process_stdout_line(task_id, &line);
+            }
+            RenderEvent::Message(message) => self.message(&message),
+            RenderEvent::Warning(message) => self.warning(&message),
+            RenderEvent::RunnerMessage(message) => self.runner_message(&message),
+            RenderEvent::EvaluateVariable {
+                task_id,
+                name,
+                value,
+            } => self.evaluate_variable(task_id, &name, &value),
+            RenderEvent::BuildSummary(summary) => self.build_summary(&summary),
+            RenderEvent::Reset => self.reset(),
+            RenderEvent::Flush(done) => {
+                self.render_deferred_status();
+                self.hide_taskbar();
+                _ = self.stderr.flush();
+                _ = std::io::stdout().flush();
+                _ = done.send(());
+            }
+            RenderEvent::Suspend(done) => {
+                if self.needs_clear {
+                    _ = self.stderr.write_all(b"\x1B[J");
+                    self.needs_clear = false;
+                }
+                _ = self.stderr.flush();
+                _ = std::io::stdout().flush();
+                self.suspended = Some(Vec::new());
+                _ = done.send(());
+            }
+            RenderEvent::Resume => (),
+            RenderEvent::Shutdown => return false,
+        }
+        true
     }
 }
 
 impl<const LINEAR: bool> Renderer<LINEAR> {
-    pub fn will_build(&mut self, task_id: TaskId, num_steps: usize, outdatedness: &Outdatedness) {
+    fn will_build(&mut self, task_id: TaskId, num_steps: usize, outdatedness: &Outdatedness) {
         self.state
             .current_tasks
             .insert(task_id, TaskStatus::new(num_steps));
         self.state.num_tasks += 1;
 
         _ = self.render_lines(|out, state| {
-            if state.settings.explain && outdatedness.is_outdated() {
+            if state.settings.explain() && outdatedness.is_outdated() {
                 if let Some(path) = task_id.as_path() {
                     writeln!(
                         out,
@@ -190,6 +504,19 @@ impl<const LINEAR: bool> Renderer<LINEAR> {
                     // (so no overwrite needed).
                     _ = writeln!(out, "  {} {reason}", "Cause:".bright_yellow());
                 }
+            } else if state.settings.explain() && !outdatedness.ignored.is_empty() {
+                writeln!(
+                    out,
+                    "{} up to date `{}`",
+                    Bracketed(Step(0, num_steps)).bright_green().bold(),
+                    task_id.as_str(),
+                )?;
+            }
+
+            if state.settings.explain() {
+                for reason in &outdatedness.ignored {
+                    _ = writeln!(out, "  {} {reason} (volatile)", "Ignored:".bright_black());
+                }
             }
 
             Ok(())
@@ -202,9 +529,11 @@ impl<const LINEAR: bool> Renderer<LINEAR> {
         };
 
         self.state.num_completed_tasks += 1;
+        self.state.any_failed |= result.is_err();
 
         _ = self.render_lines(|out, state| {
             match result {
+                Ok(_) if state.settings.quiet() => {}
                 Ok(BuildStatus::Complete(_task_id, outdatedness)) => {
                     if outdatedness.is_outdated() {
                         writeln!(
@@ -217,10 +546,15 @@ impl<const LINEAR: bool> Renderer<LINEAR> {
                                 ""
                             }
                         )?
-                    } else if state.settings.print_fresh {
+                    } else if state.settings.print_fresh() {
                         writeln!(out, "{} {task_id}", Bracketed(" -- ").bright_blue())?
                     }
                 }
+                Ok(BuildStatus::Unchanged(..)) => writeln!(
+                    out,
+                    "{} {task_id} (unchanged)",
+                    Bracketed(" ok ").bright_green().bold(),
+                )?,
                 Ok(BuildStatus::Exists(..)) => {
                     // Print nothing for file existence checks.
                 }
@@ -235,7 +569,7 @@ impl<const LINEAR: bool> Renderer<LINEAR> {
                     }
                 }
             }
-            Ok(())
+            write_grouped_lines(out, &finished.grouped)
         });
     }
 
@@ -245,6 +579,7 @@ impl<const LINEAR: bool> Renderer<LINEAR> {
         command: &ShellCommandLine,
         step: usize,
         num_steps: usize,
+        print: bool,
     ) {
         let Some(status) = self.state.current_tasks.get_mut(&task_id) else {
             return;
@@ -252,18 +587,19 @@ impl<const LINEAR: bool> Renderer<LINEAR> {
         status.progress = step + 1;
         status.num_steps = num_steps;
 
-        // Avoid taking the stdout lock if we aren't actually going to render anything.
-        let print_something =
-            self.state.settings.dry_run || self.state.settings.print_recipe_commands;
-
-        if print_something {
-            _ = self.render_lines(|out, _status| {
-                writeln!(
+        if print {
+            let line =
+                self.state.settings.echo_template.as_ref().map(|template| {
+                    template.render(step + 1, num_steps, task_id.as_str(), command)
+                });
+            _ = self.render_lines(|out, _status| match line {
+                Some(ref line) => writeln!(out, "{line}"),
+                None => writeln!(
                     out,
                     "{} {task_id}: {}",
                     Bracketed(Step(step + 1, num_steps)).dimmed(),
                     command
-                )
+                ),
             });
         } else if !LINEAR {
             _ = self.render_lines(|_, _| Ok(()));
@@ -273,11 +609,10 @@ impl<const LINEAR: bool> Renderer<LINEAR> {
     fn on_child_process_stderr_line(
         &mut self,
         task_id: TaskId,
-        _command: &ShellCommandLine,
         line_without_eol: &[u8],
         quiet: bool,
     ) {
-        if (quiet | self.state.settings.quiet) && !self.state.settings.loud {
+        if (quiet | self.state.settings.quiet()) && !self.state.settings.loud() {
             // Capture the output for later in case the task fails.
             let Some(status) = self.state.current_tasks.get_mut(&task_id) else {
                 return;
@@ -285,6 +620,14 @@ impl<const LINEAR: bool> Renderer<LINEAR> {
             let captured = status.captured.get_or_insert_default();
             captured.extend_from_slice(line_without_eol);
             captured.push(b'\n');
+        } else if self.state.settings.output_group {
+            // Print the line when the task finishes.
+            let Some(status) = self.state.current_tasks.get_mut(&task_id) else {
+                return;
+            };
+            status
+                .grouped
+                .push(GroupedLine::Stderr(line_without_eol.to_vec()));
         } else {
             // Print the line immediately.
             _ = self.render_lines(|out, _| {
@@ -295,12 +638,17 @@ impl<const LINEAR: bool> Renderer<LINEAR> {
         }
     }
 
-    fn on_child_process_stdout_line(
-        &mut self,
-        _task_id: TaskId,
-        _command: &ShellCommandLine,
-        line_without_eol: &[u8],
-    ) {
+    fn on_child_process_stdout_line(&mut self, task_id: TaskId, line_without_eol: &[u8]) {
+        if self.state.settings.output_group {
+            // Print the line when the task finishes.
+            if let Some(status) = self.state.current_tasks.get_mut(&task_id) {
+                status
+                    .grouped
+                    .push(GroupedLine::Stdout(line_without_eol.to_vec()));
+                return;
+            }
+        }
+
         // Print the line immediately.
         _ = self.render_lines_stdout(|out, _| {
             out.write_all(line_without_eol)?;
@@ -343,38 +691,153 @@ impl<const LINEAR: bool> Renderer<LINEAR> {
         }
     }
 
-    fn message(&mut self, _task_id: Option<TaskId>, message: &str) {
+    fn will_retry(
+        &mut self,
+        task_id: TaskId,
+        command: &ShellCommandLine,
+        status: std::process::ExitStatus,
+        attempt: usize,
+        max_attempts: usize,
+        delay: std::time::Duration,
+    ) {
+        // Separate the output of each attempt, so the output shown when the
+        // last attempt fails tells them apart.
+        let output_group = self.state.settings.output_group;
+        if let Some(task) = self.state.current_tasks.get_mut(&task_id) {
+            let separator = format!("(attempt {attempt}/{max_attempts} failed: {status})");
+            if let Some(ref mut captured) = task.captured {
+                captured.extend_from_slice(separator.as_bytes());
+                captured.push(b'\n');
+            }
+            if output_group {
+                task.grouped
+                    .push(GroupedLine::Stderr(separator.into_bytes()));
+            }
+        }
+
+        if self.state.settings.quiet() {
+            return;
+        }
+        _ = self.render_lines(|out, _status| {
+            writeln!(
+                out,
+                "{} {task_id}: {command} failed ({status}), retrying in {delay:?} (attempt {}/{max_attempts})",
+                Bracketed("retry").bright_yellow().bold(),
+                attempt + 1,
+            )
+        });
+    }
+
+    fn message(&mut self, message: &str) {
+        if self.state.settings.quiet() {
+            return;
+        }
         _ = self
             .render_lines(|out, _status| writeln!(out, "{} {}", "[info]".bright_green(), message));
     }
 
-    fn warning(&mut self, _task_id: Option<TaskId>, message: &str) {
+    fn warning(&mut self, message: &str) {
         _ = self
             .render_lines(|out, _status| writeln!(out, "{} {}", "[warn]".bright_yellow(), message));
     }
 
     fn runner_message(&mut self, message: &str) {
+        if self.state.settings.quiet() {
+            return;
+        }
         _ = self.render_lines(|out, _status| {
             writeln!(out, "{} {}", "[werk]".bright_purple().bold(), message)
         });
     }
 
+    fn evaluate_variable(&mut self, task_id: Option<TaskId>, name: &str, value: &str) {
+        _ = self.render_lines(|out, _status| match task_id {
+            Some(task_id) => writeln!(
+                out,
+                "{} {task_id}: {name} = {}",
+                "[eval]".dimmed(),
+                value.dimmed()
+            ),
+            None => writeln!(out, "{} {name} = {}", "[eval]".dimmed(), value.dimmed()),
+        });
+    }
+
+    fn build_summary(&mut self, summary: &BuildSummary) {
+        if self.state.settings.quiet() {
+            return;
+        }
+        _ = self.render_lines(|out, _status| {
+            write!(
+                out,
+                "{} Finished in {} (critical path {}): {} executed, {} up-to-date",
+                "[werk]".bright_purple().bold(),
+                progress::Elapsed(summary.total_time),
+                progress::Elapsed(summary.critical_path_time),
+                summary.executed,
+                summary.up_to_date,
+            )?;
+            match summary.cache_hits {
+                0 => writeln!(out)?,
+                1 => writeln!(out, ", 1 cache hit")?,
+                n => writeln!(out, ", {n} cache hits")?,
+            }
+            for command in &summary.slowest_commands {
+                writeln!(
+                    out,
+                    "{: >6} {} {}",
+                    progress::Elapsed(command.duration).to_string(),
+                    command.task.bright_yellow(),
+                    command.command.dimmed(),
+                )?;
+            }
+            Ok(())
+        });
+    }
+
     fn reset(&mut self) {
         self.state.current_tasks.clear();
         self.state.num_tasks = 0;
         self.state.num_completed_tasks = 0;
+        self.state.any_failed = false;
+        self.hide_taskbar();
+    }
+}
+
+/// Write the buffered output of a finished task as one contiguous block. Lines
+/// from the standard output of commands still go to stdout.
+fn write_grouped_lines(stderr: &mut dyn Write, lines: &[GroupedLine]) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    for line in lines {
+        match line {
+            GroupedLine::Stdout(line) => {
+                stderr.flush()?;
+                stdout.write_all(line)?;
+                stdout.write_all(b"\n")?;
+            }
+            GroupedLine::Stderr(line) => {
+                stdout.flush()?;
+                stderr.write_all(line)?;
+                stderr.write_all(b"\n")?;
+            }
+        }
     }
+    stdout.flush()
 }
 
 impl<const LINEAR: bool> werk_runner::Render for TerminalRenderer<LINEAR> {
     fn will_build(&self, task_id: TaskId, num_steps: usize, outdatedness: &Outdatedness) {
-        self.inner
-            .lock()
-            .will_build(task_id, num_steps, outdatedness);
+        self.send(RenderEvent::WillBuild {
+            task_id,
+            num_steps,
+            outdatedness: outdatedness.clone(),
+        });
     }
 
     fn did_build(&self, task_id: TaskId, result: &Result<BuildStatus, Error>) {
-        self.inner.lock().did_build(task_id, result);
+        self.send(RenderEvent::DidBuild {
+            task_id,
+            result: result.clone(),
+        });
     }
 
     fn will_execute(
@@ -383,10 +846,17 @@ impl<const LINEAR: bool> werk_runner::Render for TerminalRenderer<LINEAR> {
         command: &ShellCommandLine,
         step: usize,
         num_steps: usize,
+        echo: Echo,
     ) {
-        self.inner
-            .lock()
-            .will_execute(task_id, command, step, num_steps);
+        // Silent commands are still shown in dry runs.
+        let print = self.dry_run || echo.should_print(self.print_recipe_commands);
+        self.send(RenderEvent::WillExecute {
+            task_id,
+            command: command.clone(),
+            step,
+            num_steps,
+            print,
+        });
     }
 
     fn did_execute(
@@ -397,47 +867,154 @@ impl<const LINEAR: bool> werk_runner::Render for TerminalRenderer<LINEAR> {
         step: usize,
         num_steps: usize,
     ) {
-        self.inner
-            .lock()
-            .did_execute(task_id, command, status, step, num_steps);
+        let result = match status {
+            Ok(status) => Ok(*status),
+            Err(err) => Err(std::io::Error::new(err.kind(), err.to_string())),
+        };
+        self.send(RenderEvent::DidExecute {
+            task_id,
+            command: command.clone(),
+            result,
+            step,
+            num_steps,
+        });
     }
 
-    fn message(&self, task_id: Option<TaskId>, message: &str) {
-        self.inner.lock().message(task_id, message)
+    fn will_retry(
+        &self,
+        task_id: TaskId,
+        command: &ShellCommandLine,
+        status: std::process::ExitStatus,
+        attempt: usize,
+        max_attempts: usize,
+        delay: std::time::Duration,
+    ) {
+        self.send(RenderEvent::WillRetry {
+            task_id,
+            command: command.clone(),
+            status,
+            attempt,
+            max_attempts,
+            delay,
+        });
+    }
+
+    fn message(&self, _task_id: Option<TaskId>, message: &str) {
+        self.send(RenderEvent::Message(message.to_owned()));
     }
 
-    fn warning(&self, task_id: Option<TaskId>, message: &str) {
-        self.inner.lock().warning(task_id, message)
+    fn warning(&self, _task_id: Option<TaskId>, message: &str) {
+        self.send(RenderEvent::Warning(message.to_owned()));
     }
 
     fn runner_message(&self, message: &str) {
-        self.inner.lock().runner_message(message);
+        self.send(RenderEvent::RunnerMessage(message.to_owned()));
+    }
+
+    fn did_evaluate_variable(
+        &self,
+        task_id: Option<TaskId>,
+        name: &str,
+        value: &werk_runner::Value,
+    ) {
+        if self.print_evaluation {
+            self.send(RenderEvent::EvaluateVariable {
+                task_id,
+                name: name.to_owned(),
+                value: value.to_string(),
+            });
+        }
     }
 
     fn on_child_process_stderr_line(
         &self,
         task_id: TaskId,
-        command: &ShellCommandLine,
+        _command: &ShellCommandLine,
         line_without_eol: &[u8],
         quiet: bool,
     ) {
-        self.inner
-            .lock()
-            .on_child_process_stderr_line(task_id, command, line_without_eol, quiet);
+        self.send(RenderEvent::StderrLine {
+            task_id,
+            line: line_without_eol.to_vec(),
+            quiet,
+        });
     }
 
     fn on_child_process_stdout_line(
         &self,
         task_id: TaskId,
-        command: &ShellCommandLine,
+        _command: &ShellCommandLine,
         line_without_eol: &[u8],
     ) {
-        self.inner
-            .lock()
-            .on_child_process_stdout_line(task_id, command, line_without_eol);
+        self.send(RenderEvent::StdoutLine {
+            task_id,
+            line: line_without_eol.to_vec(),
+        });
+    }
+
+    fn build_summary(&self, summary: &BuildSummary) {
+        self.send(RenderEvent::BuildSummary(summary.clone()));
     }
 
     fn reset(&self) {
-        self.inner.lock().reset();
+        self.send(RenderEvent::Reset);
+    }
+
+    fn flush(&self) {
+        let (done, wait) = std::sync::mpsc::channel();
+        self.send(RenderEvent::Flush(done));
+        _ = wait.recv();
+    }
+
+    fn suspend(&self, _task_id: TaskId) {
+        let (done, wait) = std::sync::mpsc::channel();
+        self.send(RenderEvent::Suspend(done));
+        _ = wait.recv();
+    }
+
+    fn resume(&self, _task_id: TaskId) {
+        self.send(RenderEvent::Resume);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_only_events() {
+        let task_id = TaskId::command("build");
+        let command = ShellCommandLine {
+            program: werk_fs::Absolute::new(std::path::PathBuf::from("/bin/cc")).unwrap(),
+            arguments: Vec::new(),
+        };
+        let will_execute = |print| RenderEvent::WillExecute {
+            task_id,
+            command: command.clone(),
+            step: 0,
+            num_steps: 1,
+            print,
+        };
+        let did_execute = |result| RenderEvent::DidExecute {
+            task_id,
+            command: command.clone(),
+            result,
+            step: 0,
+            num_steps: 1,
+        };
+
+        // These may be dropped when the terminal falls behind.
+        assert!(will_execute(false).is_status_only());
+        assert!(did_execute(Ok(std::process::ExitStatus::default())).is_status_only());
+
+        // These print something or change the running tasks.
+        assert!(!will_execute(true).is_status_only());
+        assert!(!did_execute(Err(std::io::Error::other("failed"))).is_status_only());
+        assert!(!RenderEvent::StdoutLine {
+            task_id,
+            line: b"output".to_vec(),
+        }
+        .is_status_only());
+        assert!(!RenderEvent::Message(String::from("message")).is_status_only());
     }
 }
@@ -1,4 +1,8 @@
-use std::{fmt::Write as _, io::Write, time::Instant};
+use std::{
+    fmt::Write as _,
+    io::Write,
+    time::{Duration, Instant},
+};
 
 use owo_colors::OwoColorize;
 use werk_runner::TaskId;
@@ -17,12 +21,32 @@ pub struct Progress {
     buffer: String,
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum ProgressStyle {
+    /// A single line listing the running tasks.
     #[default]
     Spinner,
+    /// One line per running task, with its current step and elapsed time.
+    Rows,
 }
 
+impl ProgressStyle {
+    /// Use one row per task when the terminal is known to support moving the
+    /// cursor, and fall back to a single line otherwise.
+    #[must_use]
+    pub fn detect() -> Self {
+        let is_dumb = std::env::var_os("TERM").is_some_and(|term| term == "dumb");
+        if !is_dumb && matches!(super::term_width::stderr_width(), TtyWidth::Known(_)) {
+            ProgressStyle::Rows
+        } else {
+            ProgressStyle::Spinner
+        }
+    }
+}
+
+/// Maximum number of task rows rendered by [`ProgressStyle::Rows`].
+const MAX_ROWS: usize = 8;
+
 const SPINNER_CHARS: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
 
 impl Default for Progress {
@@ -40,6 +64,13 @@ impl Default for Progress {
 }
 
 impl Progress {
+    pub fn new(style: ProgressStyle) -> Self {
+        Self {
+            style,
+            ..Self::default()
+        }
+    }
+
     pub fn set_width(&mut self, term_width: TtyWidth) {
         self.term_width = term_width;
     }
@@ -56,7 +87,76 @@ impl Progress {
     {
         match self.style {
             ProgressStyle::Spinner => self.render_spinner(out, tasks),
+            ProgressStyle::Rows => self.render_rows(out, tasks),
+        }
+    }
+
+    fn tick(&mut self) {
+        let now = std::time::Instant::now();
+        if now.duration_since(self.last_frame_tick) > std::time::Duration::from_millis(100) {
+            self.frame += 1;
+            self.last_frame_tick = now;
+        }
+    }
+
+    /// Render a summary line followed by one row per task, leaving the cursor
+    /// at the start of the first line.
+    fn render_rows<'a, W, I>(&mut self, out: &mut W, tasks: I) -> std::io::Result<()>
+    where
+        W: Write,
+        I: IntoIterator<IntoIter: ExactSizeIterator, Item = (&'a TaskId, &'a TaskStatus)>,
+    {
+        let Some(term_width) = self.term_width.progress_max_width() else {
+            return Ok(());
+        };
+
+        let tasks = tasks.into_iter();
+        let num_running = tasks.len();
+        if num_running == 0 || term_width < 3 {
+            return Ok(());
+        }
+        self.tick();
+
+        let spinner = SPINNER_CHARS[(self.frame % 10) as usize];
+        self.buffer.clear();
+        _ = write!(
+            self.buffer,
+            " [{}/{}] {num_running} running",
+            self.progress, self.num_tasks
+        );
+        write!(out, "  {spinner}")?;
+        write!(
+            out,
+            "{}",
+            truncate(&self.buffer, term_width - 3).bright_cyan()
+        )?;
+
+        let now = Instant::now();
+        let mut lines = 1;
+        for (task_id, status) in tasks.take(MAX_ROWS) {
+            self.buffer.clear();
+            _ = write!(
+                self.buffer,
+                "    {} [{}/{}] {}",
+                task_id.short_name(),
+                status.progress,
+                status.num_steps,
+                Elapsed(now.duration_since(status.started)),
+            );
+            out.write_all(b"\n")?;
+            write!(out, "{}", truncate(&self.buffer, term_width).dimmed())?;
+            lines += 1;
         }
+        if num_running > MAX_ROWS {
+            self.buffer.clear();
+            _ = write!(self.buffer, "    … and {} more", num_running - MAX_ROWS);
+            out.write_all(b"\n")?;
+            write!(out, "{}", truncate(&self.buffer, term_width).dimmed())?;
+            lines += 1;
+        }
+
+        // Move back to the first line, so the next output overwrites the rows.
+        write!(out, "\r\x1B[{}A", lines - 1)
     }
 
     fn render_spinner<'a, W, I>(&mut self, out: &mut W, tasks: I) -> std::io::Result<()>
@@ -74,11 +174,7 @@ impl Progress {
             return Ok(());
         }
 
-        let now = std::time::Instant::now();
-        if now.duration_since(self.last_frame_tick) > std::time::Duration::from_millis(100) {
-            self.frame += 1;
-            self.last_frame_tick = now;
-        }
+        self.tick();
 
         // Render the spinner "responsively", adapting to the terminal width.
 
@@ -104,6 +200,37 @@ impl Progress {
         self.render_spinner(&mut stream, tasks)?;
         Ok(String::from_utf8(buffer).unwrap())
     }
+
+    #[cfg(test)]
+    fn render_rows_to_string<'a, I>(&mut self, tasks: I) -> std::io::Result<String>
+    where
+        I: IntoIterator<IntoIter: ExactSizeIterator, Item = (&'a TaskId, &'a TaskStatus)>,
+    {
+        let mut buffer = Vec::new();
+        let mut stream = crate::render::strip::StripStream::new(&mut buffer);
+        self.render_rows(&mut stream, tasks)?;
+        Ok(String::from_utf8(buffer).unwrap())
+    }
+}
+
+/// The first `width` characters of `text`.
+fn truncate(text: &str, width: usize) -> &str {
+    match text.char_indices().nth(width) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+pub(super) struct Elapsed(pub Duration);
+impl std::fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let secs = self.0.as_secs();
+        if secs < 60 {
+            write!(f, "{:.1}s", self.0.as_secs_f64())
+        } else {
+            write!(f, "{}m{:02}s", secs / 60, secs % 60)
+        }
+    }
 }
 
 fn draw_spinner<'a, W, I>(
@@ -200,6 +327,8 @@ mod tests {
                 progress: 0,
                 num_steps: 1,
                 captured: None,
+                grouped: Vec::new(),
+                started: Instant::now(),
             },
         )];
 
@@ -259,6 +388,8 @@ mod tests {
                     progress: 0,
                     num_steps: 1,
                     captured: None,
+                    grouped: Vec::new(),
+                    started: Instant::now(),
                 },
             ),
             (
@@ -267,6 +398,8 @@ mod tests {
                     progress: 0,
                     num_steps: 1,
                     captured: None,
+                    grouped: Vec::new(),
+                    started: Instant::now(),
                 },
             ),
         ];
@@ -323,4 +456,44 @@ mod tests {
             format!("  ⠋ [0/2] target1.o, target2.o\r")
         );
     }
+
+    #[test]
+    fn rows() {
+        let mut progress = Progress::new(ProgressStyle::Rows);
+        progress.set_progress(1, 12);
+
+        let statuses = (0..10)
+            .map(|i| {
+                (
+                    TaskId::try_build(format!("/target{i}.o").as_str()).unwrap(),
+                    TaskStatus {
+                        progress: 1,
+                        num_steps: 2,
+                        captured: None,
+                        grouped: Vec::new(),
+                        started: Instant::now(),
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        let tasks = || statuses.iter().map(|(task_id, status)| (task_id, status));
+
+        // No TTY
+        assert_eq!(progress.render_rows_to_string(tasks()).unwrap(), "");
+
+        progress.set_width(TtyWidth::Known(80));
+        let rendered = progress.render_rows_to_string(tasks()).unwrap();
+        let lines = rendered.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], "  ⠋ [1/12] 10 running");
+        assert!(lines[1].starts_with("    target0.o [1/2] 0."));
+        assert_eq!(lines[9], "    … and 2 more\r");
+
+        // Rows are truncated to the terminal width.
+        progress.set_width(TtyWidth::Known(12));
+        let rendered = progress.render_rows_to_string(tasks()).unwrap();
+        let lines = rendered.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "  ⠋ [1/12] 1");
+        assert_eq!(lines[1], "    target0.");
+    }
 }
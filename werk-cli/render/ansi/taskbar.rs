@@ -0,0 +1,99 @@
+//! Build progress reported to the terminal emulator through `OSC 9;4` escape
+//! sequences. Terminals that understand them (Windows Terminal, ConEmu, iTerm2,
+//! WezTerm, Ghostty) show the progress in the taskbar or tab.
+
+use std::io::Write;
+
+/// The progress state shown by the terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskbarState {
+    /// No progress is shown.
+    Hidden,
+    /// Normal progress, in percent.
+    Progress(u8),
+    /// Progress after a task failed, in percent.
+    Error(u8),
+}
+
+impl TaskbarState {
+    pub fn new(completed: usize, total: usize, failed: bool) -> Self {
+        let percent = (completed.min(total) * 100)
+            .checked_div(total)
+            .map_or(0, |percent| u8::try_from(percent).unwrap_or(100));
+        if failed {
+            Self::Error(percent)
+        } else {
+            Self::Progress(percent)
+        }
+    }
+}
+
+/// Emits progress sequences, only writing to the terminal when the progress
+/// actually changed.
+pub struct Taskbar {
+    current: TaskbarState,
+}
+
+impl Taskbar {
+    pub fn new() -> Self {
+        Self {
+            current: TaskbarState::Hidden,
+        }
+    }
+
+    /// Detect whether the terminal connected to stderr is known to understand
+    /// `OSC 9;4`. Other terminals may print the sequence as garbage, so this
+    /// errs on the side of caution.
+    pub fn is_supported() -> bool {
+        fn env_is(name: &str, value: &str) -> bool {
+            std::env::var_os(name).is_some_and(|var| var == value)
+        }
+
+        if env_is("TERM", "dumb") {
+            return false;
+        }
+
+        std::env::var_os("WT_SESSION").is_some()
+            || env_is("ConEmuANSI", "ON")
+            || ["iTerm.app", "WezTerm", "ghostty"]
+                .iter()
+                .any(|program| env_is("TERM_PROGRAM", program))
+    }
+
+    pub fn set(&mut self, out: &mut dyn Write, state: TaskbarState) -> std::io::Result<()> {
+        if state == self.current {
+            return Ok(());
+        }
+        self.current = state;
+        match state {
+            TaskbarState::Hidden => out.write_all(b"\x1B]9;4;0;0\x07"),
+            TaskbarState::Progress(percent) => write!(out, "\x1B]9;4;1;{percent}\x07"),
+            TaskbarState::Error(percent) => write!(out, "\x1B]9;4;2;{percent}\x07"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequences() {
+        let mut taskbar = Taskbar::new();
+        let mut out = Vec::new();
+        taskbar
+            .set(&mut out, TaskbarState::new(1, 4, false))
+            .unwrap();
+        taskbar
+            .set(&mut out, TaskbarState::new(1, 4, false))
+            .unwrap();
+        taskbar
+            .set(&mut out, TaskbarState::new(3, 4, true))
+            .unwrap();
+        taskbar.set(&mut out, TaskbarState::Hidden).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\x1B]9;4;1;25\x07\x1B]9;4;2;75\x07\x1B]9;4;0;0\x07"
+        );
+    }
+}
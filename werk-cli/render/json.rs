@@ -1,8 +1,172 @@
-pub struct JsonWatcher;
+use werk_runner::{BuildSummary, Reason, ReasonValue};
+
+use super::OutputSettings;
+
+pub struct JsonWatcher {
+    /// Include the reasons for rebuilding each target (`--explain`).
+    explain: bool,
+    /// Emit an event for each evaluated variable (`-vv`).
+    print_evaluation: bool,
+}
 
 impl JsonWatcher {
-    pub fn new() -> Self {
-        JsonWatcher
+    pub fn new(settings: OutputSettings) -> Self {
+        JsonWatcher {
+            explain: settings.explain(),
+            print_evaluation: settings.print_evaluation(),
+        }
+    }
+}
+
+/// A reason why a target is outdated, for tools that explain rebuilds.
+#[derive(serde::Serialize)]
+struct JsonReason<'a> {
+    kind: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    task: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old: Option<JsonReasonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new: Option<JsonReasonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recipe: Option<JsonRecipeLocation<'a>>,
+}
+
+/// Where the recipe that was being evaluated is declared.
+#[derive(serde::Serialize)]
+struct JsonRecipeLocation<'a> {
+    file: &'a str,
+    /// Byte offsets of the recipe in `file`.
+    span: [u32; 2],
+    start: JsonLineColumn,
+    end: JsonLineColumn,
+}
+
+/// 1-based line and column. Columns are counted in chars.
+#[derive(serde::Serialize)]
+struct JsonLineColumn {
+    line: usize,
+    column: usize,
+}
+
+impl From<werk_util::LineColumn> for JsonLineColumn {
+    fn from(value: werk_util::LineColumn) -> Self {
+        Self {
+            line: value.line,
+            column: value.column,
+        }
+    }
+}
+
+impl<'a> From<&'a werk_runner::ReasonLocation> for JsonRecipeLocation<'a> {
+    fn from(location: &'a werk_runner::ReasonLocation) -> Self {
+        Self {
+            file: &location.file,
+            span: [location.span.start.0, location.span.end.0],
+            start: location.range.start.into(),
+            end: location.range.end.into(),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum JsonReasonValue {
+    Hash(werk_runner::Hash128),
+    /// Nanoseconds since the Unix epoch.
+    Mtime(u64),
+}
+
+impl From<ReasonValue> for JsonReasonValue {
+    fn from(value: ReasonValue) -> Self {
+        match value {
+            ReasonValue::Hash(hash) => JsonReasonValue::Hash(hash),
+            ReasonValue::Mtime(mtime) => JsonReasonValue::Mtime(
+                mtime
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |since| {
+                        u64::try_from(since.as_nanos()).unwrap_or(u64::MAX)
+                    }),
+            ),
+        }
+    }
+}
+
+impl<'a> JsonReason<'a> {
+    fn new(reason: &'a Reason, outdatedness: &'a werk_runner::Outdatedness) -> Self {
+        let (path, name, task) = match reason {
+            Reason::Missing(path) | Reason::Modified(path, _) => {
+                (Some(path.to_string()), None, None)
+            }
+            Reason::Glob(name)
+            | Reason::Env(name)
+            | Reason::Which(name)
+            | Reason::GlobalChanged(name)
+            | Reason::Define(name) => (None, Some(name.as_str()), None),
+            Reason::Rebuilt(task_id) => (None, None, Some(task_id.as_str())),
+            Reason::RecipeChanged | Reason::Forced | Reason::AlwaysRun | Reason::Uncached => {
+                (None, None, None)
+            }
+        };
+        let detail = outdatedness.detail(reason);
+        Self {
+            kind: reason.kind(),
+            message: reason.to_string(),
+            path,
+            name,
+            task,
+            old: detail.and_then(|detail| detail.old).map(Into::into),
+            new: detail.and_then(|detail| detail.new).map(Into::into),
+            recipe: detail
+                .and_then(|detail| detail.location.as_ref())
+                .map(Into::into),
+        }
+    }
+}
+
+/// The statistics of a run, as emitted by the JSON renderer and written by
+/// `--summary-json`.
+#[derive(serde::Serialize)]
+#[serde(tag = "type", rename = "BuildSummary")]
+pub struct JsonBuildSummary<'a> {
+    executed: usize,
+    up_to_date: usize,
+    cache_hits: usize,
+    total_time_ms: u128,
+    critical_path_ms: u128,
+    slowest_commands: Vec<JsonCommandTiming<'a>>,
+}
+
+#[derive(serde::Serialize)]
+struct JsonCommandTiming<'a> {
+    task: &'a str,
+    command: &'a str,
+    duration_ms: u128,
+}
+
+impl<'a> JsonBuildSummary<'a> {
+    pub fn new(summary: &'a BuildSummary) -> Self {
+        Self {
+            executed: summary.executed,
+            up_to_date: summary.up_to_date,
+            cache_hits: summary.cache_hits,
+            total_time_ms: summary.total_time.as_millis(),
+            critical_path_ms: summary.critical_path_time.as_millis(),
+            slowest_commands: summary
+                .slowest_commands
+                .iter()
+                .map(|command| JsonCommandTiming {
+                    task: &command.task,
+                    command: &command.command,
+                    duration_ms: command.duration.as_millis(),
+                })
+                .collect(),
+        }
     }
 }
 
@@ -11,19 +175,38 @@ impl werk_runner::Render for JsonWatcher {
         &self,
         task_id: werk_runner::TaskId,
         num_steps: usize,
-        _outdatedness: &werk_runner::Outdatedness,
+        outdatedness: &werk_runner::Outdatedness,
     ) {
         #[derive(serde::Serialize)]
         #[serde(tag = "type")]
         struct WillBuild<'a> {
             task: &'a str,
             num_steps: usize,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            reasons: Option<Vec<JsonReason<'a>>>,
+            /// Reasons from volatile dependencies, which don't cause a rebuild.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            ignored: Option<Vec<JsonReason<'a>>>,
         }
         serde_json::to_writer(
             std::io::stdout(),
             &WillBuild {
                 task: task_id.as_str(),
                 num_steps,
+                reasons: self.explain.then(|| {
+                    outdatedness
+                        .reasons
+                        .iter()
+                        .map(|reason| JsonReason::new(reason, outdatedness))
+                        .collect()
+                }),
+                ignored: (self.explain && !outdatedness.ignored.is_empty()).then(|| {
+                    outdatedness
+                        .ignored
+                        .iter()
+                        .map(|reason| JsonReason::new(reason, outdatedness))
+                        .collect()
+                }),
             },
         )
         .unwrap();
@@ -47,6 +230,7 @@ impl werk_runner::Render for JsonWatcher {
                 task: task_id.as_str(),
                 result: match result {
                     Ok(werk_runner::BuildStatus::Complete(..)) => Ok("rebuilt"),
+                    Ok(werk_runner::BuildStatus::Unchanged(..)) => Ok("unchanged"),
                     Ok(werk_runner::BuildStatus::Exists(..)) => Ok("exists"),
                     Err(err) => Err(err.to_string()),
                 },
@@ -62,6 +246,7 @@ impl werk_runner::Render for JsonWatcher {
         command: &werk_runner::ShellCommandLine,
         step: usize,
         num_steps: usize,
+        echo: werk_runner::Echo,
     ) {
         #[derive(serde::Serialize)]
         #[serde(tag = "type")]
@@ -70,6 +255,7 @@ impl werk_runner::Render for JsonWatcher {
             command: String,
             step: usize,
             num_steps: usize,
+            silent: bool,
         }
         serde_json::to_writer(
             std::io::stdout(),
@@ -78,6 +264,7 @@ impl werk_runner::Render for JsonWatcher {
                 command: command.to_string(),
                 step,
                 num_steps,
+                silent: echo == werk_runner::Echo::Never,
             },
         )
         .unwrap();
@@ -124,6 +311,40 @@ impl werk_runner::Render for JsonWatcher {
         println!();
     }
 
+    fn will_retry(
+        &self,
+        task_id: werk_runner::TaskId,
+        command: &werk_runner::ShellCommandLine,
+        status: std::process::ExitStatus,
+        attempt: usize,
+        max_attempts: usize,
+        delay: std::time::Duration,
+    ) {
+        #[derive(serde::Serialize)]
+        #[serde(tag = "type")]
+        struct WillRetry<'a> {
+            task: &'a str,
+            command: String,
+            status: String,
+            attempt: usize,
+            max_attempts: usize,
+            delay_ms: u128,
+        }
+        serde_json::to_writer(
+            std::io::stdout(),
+            &WillRetry {
+                task: task_id.as_str(),
+                command: command.to_string(),
+                status: format!("exited with status: {}", status),
+                attempt,
+                max_attempts,
+                delay_ms: delay.as_millis(),
+            },
+        )
+        .unwrap();
+        println!();
+    }
+
     fn message(&self, task_id: Option<werk_runner::TaskId>, message: &str) {
         #[derive(serde::Serialize)]
         #[serde(tag = "type")]
@@ -142,6 +363,34 @@ impl werk_runner::Render for JsonWatcher {
         println!();
     }
 
+    fn did_evaluate_variable(
+        &self,
+        task_id: Option<werk_runner::TaskId>,
+        name: &str,
+        value: &werk_runner::Value,
+    ) {
+        if !self.print_evaluation {
+            return;
+        }
+        #[derive(serde::Serialize)]
+        #[serde(tag = "type")]
+        struct Evaluate<'a> {
+            task: Option<&'a str>,
+            name: &'a str,
+            value: String,
+        }
+        serde_json::to_writer(
+            std::io::stdout(),
+            &Evaluate {
+                task: task_id.map(|id| id.as_str()),
+                name,
+                value: value.to_string(),
+            },
+        )
+        .unwrap();
+        println!();
+    }
+
     fn warning(&self, task_id: Option<werk_runner::TaskId>, message: &str) {
         #[derive(serde::Serialize)]
         #[serde(tag = "type")]
@@ -159,4 +408,9 @@ impl werk_runner::Render for JsonWatcher {
         .unwrap();
         println!();
     }
+
+    fn build_summary(&self, summary: &BuildSummary) {
+        serde_json::to_writer(std::io::stdout(), &JsonBuildSummary::new(summary)).unwrap();
+        println!();
+    }
 }
@@ -25,10 +25,13 @@ impl werk_runner::Render for LogWatcher {
             num_steps = num_steps,
             "Will build",
         );
-        if self.settings.explain {
+        if self.settings.explain() {
             for reason in &outdatedness.reasons {
                 tracing::info!(task_id = %task_id, "Reason: {reason}");
             }
+            for reason in &outdatedness.ignored {
+                tracing::info!(task_id = %task_id, "Ignored: {reason} (volatile)");
+            }
         }
     }
 
@@ -39,7 +42,9 @@ impl werk_runner::Render for LogWatcher {
     ) {
         match result {
             Ok(ref status) => {
-                if let werk_runner::BuildStatus::Complete(task_id, _) = status {
+                if let werk_runner::BuildStatus::Complete(task_id, _)
+                | werk_runner::BuildStatus::Unchanged(task_id, _) = status
+                {
                     tracing::info!(task_id = %task_id, "Success");
                 }
             }
@@ -55,8 +60,9 @@ impl werk_runner::Render for LogWatcher {
         command: &werk_runner::ShellCommandLine,
         step: usize,
         _num_steps: usize,
+        echo: werk_runner::Echo,
     ) {
-        if self.settings.print_recipe_commands {
+        if echo.should_print(self.settings.print_recipe_commands()) {
             tracing::info!(task_id = %task_id, step = step, "Run: {command}");
         }
     }
@@ -83,11 +89,53 @@ impl werk_runner::Render for LogWatcher {
         }
     }
 
+    fn will_retry(
+        &self,
+        task_id: werk_runner::TaskId,
+        command: &werk_runner::ShellCommandLine,
+        status: std::process::ExitStatus,
+        attempt: usize,
+        max_attempts: usize,
+        delay: std::time::Duration,
+    ) {
+        tracing::warn!(
+            task_id = %task_id,
+            attempt = attempt,
+            max_attempts = max_attempts,
+            "Failed ({status}), retrying in {delay:?}: {command}"
+        );
+    }
+
     fn message(&self, task_id: Option<werk_runner::TaskId>, message: &str) {
         tracing::info!(task_id = ?task_id, "Message: {message}");
     }
 
+    fn did_evaluate_variable(
+        &self,
+        task_id: Option<werk_runner::TaskId>,
+        name: &str,
+        value: &werk_runner::Value,
+    ) {
+        if self.settings.print_evaluation() {
+            tracing::info!(task_id = ?task_id, "Let: {name} = {value}");
+        }
+    }
+
     fn warning(&self, task_id: Option<werk_runner::TaskId>, message: &str) {
         tracing::warn!(task_id = ?task_id, "Warning: {message}");
     }
+
+    fn build_summary(&self, summary: &werk_runner::BuildSummary) {
+        tracing::info!(
+            executed = summary.executed,
+            up_to_date = summary.up_to_date,
+            cache_hits = summary.cache_hits,
+            critical_path = ?summary.critical_path_time,
+            "Finished in {:?}",
+            summary.total_time
+        );
+        for command in &summary.slowest_commands {
+            tracing::info!(task_id = %command.task, duration = ?command.duration, "Slow command: {}", command.command);
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use werk_runner::{BuildStatus, Outdatedness, Render, ShellCommandLine, TaskId};
+use werk_runner::{BuildStatus, Echo, Outdatedness, Render, ShellCommandLine, TaskId};
 
 pub struct NullRender;
 impl Render for NullRender {
@@ -6,7 +6,7 @@ impl Render for NullRender {
 
     fn did_build(&self, _: TaskId, _: &Result<BuildStatus, werk_runner::Error>) {}
 
-    fn will_execute(&self, _: TaskId, _: &ShellCommandLine, _: usize, _: usize) {}
+    fn will_execute(&self, _: TaskId, _: &ShellCommandLine, _: usize, _: usize, _: Echo) {}
 
     fn did_execute(
         &self,
@@ -0,0 +1,127 @@
+//! Check that the output files built by earlier runs were not changed since.
+//!
+//! The hash of each output file is recorded in `.werk-cache` when it is
+//! built. Files that were modified or deleted afterwards, by hand or by a tool
+//! outside of the build, are reported.
+//!
+//! With `--repro`, the target is built twice instead, and the hashes of the
+//! outputs of both builds are compared to find recipes that are not
+//! deterministic.
+
+use std::{collections::BTreeMap, io::Write};
+
+use owo_colors::OwoColorize as _;
+use werk_runner::{Hash128, OutputStatus, Runner, Workspace};
+
+use crate::Error;
+
+/// Run the `werk verify` command. Fails if any output was modified or deleted.
+/// Unchanged outputs are only listed with `verbose`.
+pub fn run(workspace: &Workspace<'_>, verbose: bool, out: &mut dyn Write) -> Result<(), Error> {
+    let outputs = workspace.verify_outputs();
+    let mut num_modified = 0;
+    let mut num_missing = 0;
+    for (path, status) in &outputs {
+        match status {
+            OutputStatus::Unchanged => {
+                if verbose {
+                    writeln!(out, "  {}  {path}", "ok      ".bright_green())?;
+                }
+            }
+            OutputStatus::Modified => {
+                num_modified += 1;
+                writeln!(out, "  {}  {path}", "modified".bright_red().bold())?;
+            }
+            OutputStatus::Missing => {
+                num_missing += 1;
+                writeln!(out, "  {}  {path}", "missing ".bright_red().bold())?;
+            }
+        }
+    }
+
+    writeln!(
+        out,
+        "Verified {} output(s): {num_modified} modified, {num_missing} missing",
+        outputs.len()
+    )?;
+    match num_modified + num_missing {
+        0 => Ok(()),
+        problems => Err(Error::Problems(problems)),
+    }
+}
+
+/// Run `werk verify --repro`. Every recipe is forced to rerun by the caller.
+/// Fails if the build fails, or if any output of the second build differs
+/// from the first.
+pub async fn run_repro<'a>(
+    workspace: &'a Workspace<'a>,
+    target: &str,
+    render: &dyn werk_runner::Render,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    build(workspace, target, render).await?;
+    let first = output_hashes(workspace);
+    workspace.forget_builds();
+    build(workspace, target, render).await?;
+    let second = output_hashes(workspace);
+
+    let workspaces =
+        std::iter::once(workspace).chain(workspace.members.iter().map(|member| &member.workspace));
+    for workspace in workspaces {
+        if let Err(err) = workspace.finalize().await {
+            eprintln!("Error writing `.werk-cache`: {err}");
+        }
+    }
+
+    let mut num_differing = 0;
+    for (path, hash) in &second {
+        if first.get(path).is_some_and(|first| first != hash) {
+            num_differing += 1;
+            writeln!(out, "  {}  {path}", "differs".bright_red().bold())?;
+        }
+    }
+    writeln!(
+        out,
+        "Built {} output(s) twice: {num_differing} not reproducible",
+        second.len()
+    )?;
+    match num_differing {
+        0 => Ok(()),
+        problems => Err(Error::Problems(problems)),
+    }
+}
+
+async fn build<'a>(
+    workspace: &'a Workspace<'a>,
+    target: &str,
+    render: &dyn werk_runner::Render,
+) -> Result<(), Error> {
+    let runner = Runner::new(workspace);
+    let result = crate::build_target_or_glob(&runner, target).await;
+    let summary = crate::report_summary(render, &runner, None);
+    render.flush();
+    let results = runner.results();
+    std::mem::drop(runner);
+    summary?;
+    result.map_err(|err| crate::print_build_error(err, &results))
+}
+
+/// The recorded output hashes of the workspace and its members. Outputs of
+/// members are prefixed by their label, like `//member:/out.txt`.
+fn output_hashes(workspace: &Workspace<'_>) -> BTreeMap<String, Hash128> {
+    let mut hashes = workspace
+        .output_hashes()
+        .into_iter()
+        .map(|(path, hash)| (path.to_string(), hash))
+        .collect::<BTreeMap<_, _>>();
+    for member in workspace.members {
+        hashes.extend(
+            member
+                .workspace
+                .output_hashes()
+                .into_iter()
+                .map(|(path, hash)| (format!("//{}:{path}", member.name), hash)),
+        );
+    }
+    hashes
+}
@@ -0,0 +1,154 @@
+//! Show which build recipe would be used to build a target.
+//!
+//! All build recipes matching the target are listed from most to least
+//! specific, so it is possible to see why a recipe was (or wasn't) chosen.
+//! Recipes whose pattern matches the target, but which exclude it with an
+//! `exclude` statement, are listed last.
+
+use std::io::Write;
+
+use owo_colors::OwoColorize as _;
+use werk_parser::parser::Span;
+use werk_runner::{
+    ir::{BuildRecipe, BuildRecipeMatch},
+    Pattern, Workspace,
+};
+use werk_util::{DiagnosticFileId, DiagnosticFileRepository as _};
+
+use crate::Error;
+
+/// Run the `werk which-recipe` command.
+pub fn run(workspace: &Workspace<'_>, target: &str, out: &mut dyn Write) -> Result<(), Error> {
+    let path = werk_fs::Path::new(target)
+        .and_then(|path| path.absolutize(werk_fs::Path::ROOT))
+        .map_err(|err| Error::InvalidTarget(target.to_owned(), err))?;
+
+    let is_task = workspace.manifest.match_task_recipe(target).is_some();
+    if is_task {
+        writeln!(out, "{} is a task recipe", target.bright_yellow())?;
+    }
+
+    let candidates = workspace.manifest.build_recipe_candidates(&path);
+    let excluded = workspace.manifest.excluded_build_recipes(&path);
+    let Some((best, others)) = candidates.split_first() else {
+        if is_task {
+            // Nothing more to say.
+        } else if workspace.get_project_file(&path).is_some() {
+            writeln!(
+                out,
+                "{} is a file in the workspace, and no build recipe matches it",
+                path.bright_yellow()
+            )?;
+        } else {
+            writeln!(out, "No build recipe matches {}", path.bright_yellow())?;
+        }
+        write_excluded(workspace, out, &excluded)?;
+        return Ok(());
+    };
+
+    let location = |candidate: &BuildRecipeMatch| {
+        location(workspace, candidate.recipe.file, candidate.recipe.span)
+    };
+
+    if others
+        .first()
+        .is_some_and(|runner_up| runner_up.specificity() == best.specificity())
+    {
+        writeln!(
+            out,
+            "{} {} matches multiple build recipes equally well",
+            "Ambiguous:".bright_red().bold(),
+            path.bright_yellow()
+        )?;
+        writeln!(
+            out,
+            "  {} {}",
+            format_args!("\"{}\"", best.recipe.pattern).bright_green(),
+            format_args!("({})", location(best)).dimmed(),
+        )?;
+    } else {
+        writeln!(
+            out,
+            "{} is built by {} {}",
+            path.bright_yellow(),
+            format_args!("\"{}\"", best.recipe.pattern).bright_green(),
+            format_args!("({})", location(best)).dimmed(),
+        )?;
+    }
+    write_match(out, best)?;
+
+    if !others.is_empty() {
+        writeln!(
+            out,
+            "{}",
+            "Other matching recipes, from most to least specific:".bright_purple()
+        )?;
+        for candidate in others {
+            writeln!(
+                out,
+                "  {} {}",
+                format_args!("\"{}\"", candidate.recipe.pattern).bright_green(),
+                format_args!("({})", location(candidate)).dimmed(),
+            )?;
+            write_match(out, candidate)?;
+        }
+    }
+
+    write_excluded(workspace, out, &excluded)
+}
+
+fn write_excluded(
+    workspace: &Workspace<'_>,
+    out: &mut dyn Write,
+    excluded: &[(&BuildRecipe<'_>, &Pattern<'_>)],
+) -> Result<(), Error> {
+    if excluded.is_empty() {
+        return Ok(());
+    }
+    writeln!(
+        out,
+        "{}",
+        "Matching recipes that exclude the target:".bright_purple()
+    )?;
+    for (recipe, exclude) in excluded {
+        writeln!(
+            out,
+            "  {} {} {}",
+            format_args!("\"{}\"", recipe.pattern).bright_green(),
+            format_args!("excludes \"{exclude}\"").bright_red(),
+            format_args!("({})", location(workspace, recipe.file, exclude.span)).dimmed(),
+        )?;
+    }
+    Ok(())
+}
+
+/// `<werkfile>:<line>` of the start of a span in the main Werkfile or an
+/// imported Werkfile.
+pub fn location(workspace: &Workspace<'_>, file: DiagnosticFileId, span: Span) -> String {
+    let Some(source) = workspace.get_source(file) else {
+        return String::from("<unknown>");
+    };
+    let offset = (span.start.0 as usize).min(source.source.len());
+    // Count the line breaks before the offset, so an offset at the start of a
+    // line is on that line.
+    let line = source.source[..offset].matches('\n').count() + 1;
+    format!("{}:{line}", source.file)
+}
+
+pub fn write_match(out: &mut dyn Write, candidate: &BuildRecipeMatch) -> std::io::Result<()> {
+    let specificity = candidate.specificity();
+    if specificity.priority != 0 {
+        writeln!(out, "    priority: {}", specificity.priority)?;
+    }
+    match candidate.match_data.stem() {
+        Some(stem) => writeln!(out, "    stem: {stem:?}")?,
+        None => writeln!(out, "    exact match")?,
+    }
+    for (index, capture) in candidate.match_data.captures().iter().enumerate() {
+        writeln!(out, "    capture {index}: {capture:?}")?;
+    }
+    for (name, capture) in &candidate.match_data.named {
+        writeln!(out, "    capture {name}: {capture:?}")?;
+    }
+    writeln!(out, "    literal characters: {}", specificity.literal_chars)
+}
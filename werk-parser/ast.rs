@@ -6,6 +6,73 @@ use crate::{
     SemanticHash,
 };
 
+/// Implements `Serialize` and `Deserialize` for a node that serializes as its
+/// only semantic field, like an identifier as a plain string. When any of its
+/// locations are known, all fields are serialized as a map instead, so spans
+/// survive a round-trip.
+#[cfg(feature = "serde")]
+macro_rules! serde_transparent {
+    (
+        impl[$($gen:tt)*] $ty:ty {
+            $value_field:tt => $value:ident: $value_ty:ty,
+            $($field:tt => $name:ident: $field_ty:ty),+ $(,)?
+        }
+    ) => {
+        const _: () = {
+            #[derive(serde::Deserialize)]
+            #[serde(deny_unknown_fields)]
+            struct Full<$($gen)*> {
+                $value: $value_ty,
+                $($name: $field_ty),+
+            }
+
+            #[derive(serde::Deserialize)]
+            #[serde(untagged)]
+            enum Repr<$($gen)*> {
+                Full(Full<$($gen)*>),
+                Compact($value_ty),
+            }
+
+            impl<$($gen)*> serde::Serialize for $ty
+            where
+                $value_ty: serde::Serialize,
+                $($field_ty: serde::Serialize + crate::parser::IsIgnored),+
+            {
+                fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+                    use serde::ser::SerializeStruct as _;
+                    if true $(&& crate::parser::IsIgnored::is_ignored(&self.$field))+ {
+                        return serde::Serialize::serialize(&self.$value_field, ser);
+                    }
+                    let len = [$(stringify!($name)),+].len() + 1;
+                    let mut state = ser.serialize_struct(stringify!($ty), len)?;
+                    state.serialize_field(stringify!($value), &self.$value_field)?;
+                    $(state.serialize_field(stringify!($name), &self.$field)?;)+
+                    state.end()
+                }
+            }
+
+            impl<'de, $($gen)*> serde::Deserialize<'de> for $ty
+            where
+                Repr<$($gen)*>: serde::Deserialize<'de>,
+                $($field_ty: Default),+
+            {
+                fn deserialize<D: serde::Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+                    Ok(match Repr::deserialize(de)? {
+                        Repr::Full(full) => Self {
+                            $value_field: full.$value,
+                            $($field: full.$name),+
+                        },
+                        Repr::Compact(value) => Self {
+                            $value_field: value,
+                            $($field: Default::default()),+
+                        },
+                    })
+                }
+            }
+        };
+    };
+}
+
 mod expr;
 pub mod keyword;
 mod string;
@@ -15,12 +82,25 @@ pub use expr::*;
 pub use string::*;
 use werk_util::Symbol;
 
+#[cfg(feature = "serde")]
+use crate::parser::is_ignored;
+
 /// Whitespace and comments within statements and expressions (not doc
 /// comments).
 #[derive(Default, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 #[must_use]
 pub struct Whitespace(pub Span);
 
+#[cfg(feature = "serde")]
+impl crate::parser::IsIgnored for Whitespace {
+    #[inline]
+    fn is_ignored(&self) -> bool {
+        self.0.is_ignored()
+    }
+}
+
 impl std::fmt::Debug for Whitespace {
     #[inline]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -30,14 +110,31 @@ impl std::fmt::Debug for Whitespace {
 
 /// Trailing whitespace and comments in a block after each statement.
 #[derive(Default, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[must_use]
 pub struct Trailing<T> {
     /// Whitespace before comma or semicolon.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws: Whitespace,
     /// Comma or semicolon at the end of the item.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
     pub token: Option<T>,
 }
 
+#[cfg(feature = "serde")]
+impl<T: crate::parser::IsIgnored> crate::parser::IsIgnored for Trailing<T> {
+    fn is_ignored(&self) -> bool {
+        self.ws.0.is_ignored()
+            && self
+                .token
+                .as_ref()
+                .is_none_or(crate::parser::IsIgnored::is_ignored)
+    }
+}
+
 impl<const CHAR: char> PartialEq for Trailing<token::Token<CHAR>> {
     fn eq(&self, other: &Self) -> bool {
         self.ws == other.ws
@@ -83,15 +180,21 @@ pub const fn trailing_ignore<const CHAR: char>() -> Trailing<token::Token<CHAR>>
     }
 }
 
-#[derive(Debug, PartialEq, Default, serde::Serialize, serde::Deserialize)]
-#[serde(transparent)]
+#[derive(Debug, PartialEq, Default)]
 pub struct Root<'a> {
     pub statements: Vec<BodyStmt<RootStmt<'a>>>,
     /// Comment at the end of the document, not associated with any item.
-    #[serde(skip, default)]
     pub ws_trailing: Whitespace,
 }
 
+#[cfg(feature = "serde")]
+serde_transparent! {
+    impl['a] Root<'a> {
+        statements => statements: Vec<BodyStmt<RootStmt<'a>>>,
+        ws_trailing => ws_trailing: Whitespace,
+    }
+}
+
 impl Root<'_> {
     #[must_use]
     pub fn find_global(&self, name: &str) -> Option<&LetStmt> {
@@ -116,37 +219,112 @@ impl Root<'_> {
     }
 }
 
-#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RootStmt<'a> {
     Config(ConfigStmt<'a>),
+    Import(ImportStmt<'a>),
+    Member(MemberStmt<'a>),
     Let(LetStmt<'a>),
     Task(CommandRecipe<'a>),
     Build(BuildRecipe<'a>),
+    Profile(ProfileStmt<'a>),
 }
 
-#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConfigStmt<'a> {
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub span: Span,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub token_config: keyword::Config,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_1: Whitespace,
     pub ident: Ident,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_2: Whitespace,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub token_eq: token::Eq,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_3: Whitespace,
     pub value: ConfigValue<'a>,
 }
 
-#[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
-#[serde(untagged)]
+/// `import "path/to/lib.werk" as lib`
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImportStmt<'a> {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub span: Span,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub token_import: keyword::Import,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_1: Whitespace,
+    /// Path to the imported Werkfile, relative to the directory of the
+    /// importing Werkfile.
+    pub path: ConfigString<'a>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_2: Whitespace,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub token_as: keyword::As,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_3: Whitespace,
+    /// The namespace of the imported items.
+    pub ident: Ident,
+}
+
+/// `member "path/to/member"`
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemberStmt<'a> {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub span: Span,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub token_member: keyword::Member,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_1: Whitespace,
+    /// Directory of the workspace member, relative to the directory of the
+    /// root Werkfile. This is also the name of the member.
+    pub path: ConfigString<'a>,
+}
+
+/// `profile release { let cflags = "-O2" }`
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProfileStmt<'a> {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub span: Span,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub token_profile: keyword::Profile,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_1: Whitespace,
+    pub name: Ident,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_2: Whitespace,
+    /// Global variables that are replaced when the profile is selected.
+    pub body: Body<LetStmt<'a>>,
+}
+
+impl<'a> ProfileStmt<'a> {
+    #[must_use]
+    pub fn find_global(&self, name: &str) -> Option<&LetStmt<'a>> {
+        self.body
+            .statements
+            .iter()
+            .map(|stmt| &stmt.statement)
+            .find(|stmt| stmt.ident.ident == name)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
 pub enum ConfigValue<'a> {
     String(ConfigString<'a>),
     Bool(ConfigBool),
+    Int(ConfigInt),
+    /// `["a", "b"]`
+    List(ListExpr<ConfigString<'a>>),
 }
 
 impl Spanned for ConfigValue<'_> {
@@ -154,26 +332,79 @@ impl Spanned for ConfigValue<'_> {
         match self {
             ConfigValue::String(s) => s.0,
             ConfigValue::Bool(b) => b.0,
+            ConfigValue::Int(i) => i.0,
+            ConfigValue::List(l) => l.span,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
-#[serde(transparent)]
-pub struct ConfigString<'a>(#[serde(skip, default)] pub Span, pub Cow<'a, str>);
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConfigString<'a>(pub Span, pub Cow<'a, str>);
+
+#[cfg(feature = "serde")]
+serde_transparent! {
+    impl['a] ConfigString<'a> {
+        1 => value: Cow<'a, str>,
+        0 => span: Span,
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ConfigBool(pub Span, pub bool);
 
-#[derive(Debug, PartialEq, Clone, Copy, serde::Serialize, serde::Deserialize)]
-#[serde(transparent)]
-pub struct ConfigBool(#[serde(skip, default)] pub Span, pub bool);
+#[cfg(feature = "serde")]
+serde_transparent! {
+    impl[] ConfigBool {
+        1 => value: bool,
+        0 => span: Span,
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ConfigInt(pub Span, pub i32);
+
+#[cfg(feature = "serde")]
+serde_transparent! {
+    impl[] ConfigInt {
+        1 => value: i32,
+        0 => span: Span,
+    }
+}
+
+/// `#[name]` or `#[name = value]`, attached to the recipe or `run` statement
+/// that follows it. The grammar accepts any name and literal value; the
+/// evaluator decides which attributes are valid where.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Attribute<'a> {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub span: Span,
+    pub name: Ident,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub value: Option<ConfigValue<'a>>,
+    /// Whitespace between the closing bracket and the next attribute or the
+    /// item that the attribute is attached to.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_trailing: Whitespace,
+}
 
-#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
-#[serde(transparent)]
+#[derive(Clone, PartialEq)]
 pub struct Ident {
-    #[serde(skip, default)]
     pub span: Span,
     pub ident: Symbol,
 }
 
+#[cfg(feature = "serde")]
+serde_transparent! {
+    impl[] Ident {
+        ident => ident: Symbol,
+        span => span: Span,
+    }
+}
+
 impl Ident {
     pub fn new(span: impl Into<Span>, ident: impl Into<Symbol>) -> Self {
         Self {
@@ -231,16 +462,22 @@ pub enum MessageType {
 
 hash_is_semantic!(MessageType);
 
-#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CommandRecipe<'a> {
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub span: Span,
-    #[serde(skip, default)]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub attributes: Vec<Attribute<'a>>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub token_task: keyword::Task,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_1: Whitespace,
     pub name: Ident,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_2: Whitespace,
     pub body: Body<TaskRecipeStmt<'a>>,
 }
@@ -252,17 +489,23 @@ impl SemanticHash for CommandRecipe<'_> {
     }
 }
 
-#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BuildRecipe<'a> {
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub span: Span,
-    #[serde(skip, default)]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub attributes: Vec<Attribute<'a>>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub token_build: keyword::Build,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_1: Whitespace,
     pub pattern: PatternExpr<'a>,
     /// Comment between the pattern and the opening brace.
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_2: Whitespace,
     pub body: Body<BuildRecipeStmt<'a>>,
 }
@@ -275,19 +518,25 @@ impl SemanticHash for BuildRecipe<'_> {
 }
 
 /// A `{...}` block.
-#[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
-#[serde(transparent)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Body<T> {
-    #[serde(skip, default)]
     pub token_open: token::BraceOpen,
     pub statements: Vec<BodyStmt<T>>,
     /// After the last statement.
-    #[serde(skip, default)]
     pub ws_trailing: Whitespace,
-    #[serde(skip, default)]
     pub token_close: token::BraceClose,
 }
 
+#[cfg(feature = "serde")]
+serde_transparent! {
+    impl[T] Body<T> {
+        statements => statements: Vec<BodyStmt<T>>,
+        token_open => token_open: token::BraceOpen,
+        ws_trailing => ws_trailing: Whitespace,
+        token_close => token_close: token::BraceClose,
+    }
+}
+
 impl<T> Spanned for Body<T> {
     fn span(&self) -> Span {
         self.token_open.span().merge(self.token_close.span())
@@ -300,34 +549,57 @@ impl<T: SemanticHash> SemanticHash for Body<T> {
     }
 }
 
-#[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
-#[serde(transparent)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct BodyStmt<T> {
-    #[serde(skip, default)]
     pub ws_pre: Whitespace,
     pub statement: T,
-    #[serde(skip, default)]
     pub trailing: Trailing<token::Semicolon>,
 }
 
+#[cfg(feature = "serde")]
+serde_transparent! {
+    impl[T] BodyStmt<T> {
+        statement => statement: T,
+        ws_pre => ws_pre: Whitespace,
+        trailing => trailing: Trailing<token::Semicolon>,
+    }
+}
+
 impl<T: SemanticHash> SemanticHash for BodyStmt<T> {
     fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.statement.semantic_hash(state);
     }
 }
 
-#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BuildRecipeStmt<'a> {
     Let(LetStmt<'a>),
     From(FromStmt<'a>),
     Depfile(DepfileStmt<'a>),
     Run(RunStmt<'a>),
+    RunShell(RunShellStmt<'a>),
     Info(InfoExpr<'a>),
     Warn(WarnExpr<'a>),
     SetCapture(KwExpr<keyword::SetCapture, ConfigBool>),
     SetNoCapture(KwExpr<keyword::SetNoCapture, ConfigBool>),
+    /// Priority of the recipe when its pattern overlaps with other recipes.
+    Priority(KwExpr<keyword::Priority, ConfigInt>),
     Env(EnvStmt<'a>),
     EnvRemove(EnvRemoveStmt<'a>),
+    Timeout(TimeoutStmt<'a>),
+    /// Pool limiting the number of concurrent commands of the recipe.
+    Pool(PoolStmt<'a>),
+    /// Remote host running the commands of the recipe.
+    Host(HostStmt<'a>),
+    /// Container image running the commands of the recipe.
+    Container(ContainerStmt<'a>),
+    /// Location of the output file within the output directory.
+    Out(OutStmt<'a>),
+    /// Paths matching the pattern are not built by the recipe.
+    Exclude(ExcludeStmt<'a>),
+    /// Working directory of the commands of the recipe.
+    InDir(InDirStmt<'a>),
 }
 
 impl SemanticHash for BuildRecipeStmt<'_> {
@@ -338,28 +610,70 @@ impl SemanticHash for BuildRecipeStmt<'_> {
             BuildRecipeStmt::From(stmt) => stmt.semantic_hash(state),
             BuildRecipeStmt::Depfile(stmt) => stmt.semantic_hash(state),
             BuildRecipeStmt::Run(stmt) => stmt.semantic_hash(state),
+            BuildRecipeStmt::RunShell(stmt) => stmt.semantic_hash(state),
             BuildRecipeStmt::Env(stmt) => stmt.semantic_hash(state),
             BuildRecipeStmt::EnvRemove(stmt) => stmt.semantic_hash(state),
+            BuildRecipeStmt::Out(stmt) => stmt.semantic_hash(state),
+            BuildRecipeStmt::InDir(stmt) => stmt.semantic_hash(state),
+            BuildRecipeStmt::Container(stmt) => stmt.semantic_hash(state),
             // Information statements do not contribute to outdatedness.
             BuildRecipeStmt::SetCapture(_)
             | BuildRecipeStmt::SetNoCapture(_)
+            | BuildRecipeStmt::Priority(_)
+            | BuildRecipeStmt::Exclude(_)
+            | BuildRecipeStmt::Timeout(_)
+            | BuildRecipeStmt::Pool(_)
+            | BuildRecipeStmt::Host(_)
             | BuildRecipeStmt::Info(_)
             | BuildRecipeStmt::Warn(_) => {}
         }
     }
 }
 
-#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+impl Spanned for BuildRecipeStmt<'_> {
+    fn span(&self) -> Span {
+        match self {
+            BuildRecipeStmt::Let(stmt) => stmt.span,
+            BuildRecipeStmt::From(stmt) => stmt.span,
+            BuildRecipeStmt::Depfile(stmt) => stmt.span,
+            BuildRecipeStmt::Run(stmt) => stmt.span,
+            BuildRecipeStmt::RunShell(stmt) => stmt.span,
+            BuildRecipeStmt::Info(stmt) => stmt.span,
+            BuildRecipeStmt::Warn(stmt) => stmt.span,
+            BuildRecipeStmt::SetCapture(stmt) => stmt.span,
+            BuildRecipeStmt::SetNoCapture(stmt) => stmt.span,
+            BuildRecipeStmt::Priority(stmt) => stmt.span,
+            BuildRecipeStmt::Env(stmt) => stmt.span,
+            BuildRecipeStmt::EnvRemove(stmt) => stmt.span,
+            BuildRecipeStmt::Timeout(stmt) => stmt.span,
+            BuildRecipeStmt::Pool(stmt) => stmt.span,
+            BuildRecipeStmt::Host(stmt) => stmt.span,
+            BuildRecipeStmt::Container(stmt) => stmt.span,
+            BuildRecipeStmt::Out(stmt) => stmt.span,
+            BuildRecipeStmt::Exclude(stmt) => stmt.span,
+            BuildRecipeStmt::InDir(stmt) => stmt.span,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TaskRecipeStmt<'a> {
     Let(LetStmt<'a>),
     Build(BuildStmt<'a>),
     Run(RunStmt<'a>),
+    RunShell(RunShellStmt<'a>),
     Info(InfoExpr<'a>),
     Warn(WarnExpr<'a>),
     SetCapture(KwExpr<keyword::SetCapture, ConfigBool>),
     SetNoCapture(KwExpr<keyword::SetNoCapture, ConfigBool>),
     Env(EnvStmt<'a>),
     EnvRemove(EnvRemoveStmt<'a>),
+    Timeout(TimeoutStmt<'a>),
+    /// Pool limiting the number of concurrent commands of the recipe.
+    Pool(PoolStmt<'a>),
+    /// Working directory of the commands of the recipe.
+    InDir(InDirStmt<'a>),
 }
 
 impl SemanticHash for TaskRecipeStmt<'_> {
@@ -369,57 +683,94 @@ impl SemanticHash for TaskRecipeStmt<'_> {
             TaskRecipeStmt::Let(stmt) => stmt.semantic_hash(state),
             TaskRecipeStmt::Build(stmt) => stmt.semantic_hash(state),
             TaskRecipeStmt::Run(stmt) => stmt.semantic_hash(state),
+            TaskRecipeStmt::RunShell(stmt) => stmt.semantic_hash(state),
             TaskRecipeStmt::Env(stmt) => stmt.semantic_hash(state),
             TaskRecipeStmt::EnvRemove(stmt) => stmt.semantic_hash(state),
+            TaskRecipeStmt::InDir(stmt) => stmt.semantic_hash(state),
             // Information statements do not contribute to outdatedness.
             TaskRecipeStmt::SetCapture(_)
             | TaskRecipeStmt::SetNoCapture(_)
+            | TaskRecipeStmt::Timeout(_)
+            | TaskRecipeStmt::Pool(_)
             | TaskRecipeStmt::Info(_)
             | TaskRecipeStmt::Warn(_) => {}
         }
     }
 }
 
-#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+impl Spanned for TaskRecipeStmt<'_> {
+    fn span(&self) -> Span {
+        match self {
+            TaskRecipeStmt::Let(stmt) => stmt.span,
+            TaskRecipeStmt::Build(stmt) => stmt.span,
+            TaskRecipeStmt::Run(stmt) => stmt.span,
+            TaskRecipeStmt::RunShell(stmt) => stmt.span,
+            TaskRecipeStmt::Info(stmt) => stmt.span,
+            TaskRecipeStmt::Warn(stmt) => stmt.span,
+            TaskRecipeStmt::SetCapture(stmt) => stmt.span,
+            TaskRecipeStmt::SetNoCapture(stmt) => stmt.span,
+            TaskRecipeStmt::Env(stmt) => stmt.span,
+            TaskRecipeStmt::EnvRemove(stmt) => stmt.span,
+            TaskRecipeStmt::Timeout(stmt) => stmt.span,
+            TaskRecipeStmt::Pool(stmt) => stmt.span,
+            TaskRecipeStmt::InDir(stmt) => stmt.span,
+        }
+    }
+}
+
+/// `let <ident> = <expr>`, optionally preceded by attributes.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LetStmt<'a> {
-    #[serde(skip, default)]
+    // Renamed to avoid clashing with the span of the flattened expression.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "stmt_span", default, skip_serializing_if = "is_ignored")
+    )]
     pub span: Span,
-    #[serde(skip, default)]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub attributes: Vec<Attribute<'a>>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub token_let: keyword::Let,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_1: Whitespace,
     pub ident: Ident,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_2: Whitespace,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub token_eq: token::Eq,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_3: Whitespace,
-    #[serde(flatten)]
+    #[cfg_attr(feature = "serde", serde(flatten))]
     pub value: ExprChain<'a>,
 }
 
 impl SemanticHash for LetStmt<'_> {
     fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // Attributes don't contribute to outdatedness.
         self.ident.semantic_hash(state);
         self.value.semantic_hash(state);
     }
 }
 
-#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnvStmt<'a> {
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub span: Span,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub token: keyword::Env,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_1: Whitespace,
     pub key: StringExpr<'a>,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_2: Whitespace,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub token_eq: token::Eq,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_3: Whitespace,
     pub value: StringExpr<'a>,
 }
@@ -434,27 +785,116 @@ impl SemanticHash for EnvStmt<'_> {
 pub type FromStmt<'a> = KwExpr<keyword::From, ExprChain<'a>>;
 pub type BuildStmt<'a> = KwExpr<keyword::Build, ExprChain<'a>>;
 pub type DepfileStmt<'a> = KwExpr<keyword::Depfile, ExprChain<'a>>;
-pub type RunStmt<'a> = KwExpr<keyword::Run, RunExpr<'a>>;
 pub type ErrorStmt<'a> = KwExpr<keyword::Error, StringExpr<'a>>;
 pub type DeleteExpr<'a> = KwExpr<keyword::Delete, Expr<'a>>;
+pub type MkdirExpr<'a> = KwExpr<keyword::Mkdir, Expr<'a>>;
+pub type TouchExpr<'a> = KwExpr<keyword::Touch, Expr<'a>>;
+/// `args ["cc", "-c", flags, "-o", "<out>"]`
+pub type ArgsExpr<'a> = KwExpr<keyword::Args, Expr<'a>>;
 pub type EnvRemoveStmt<'a> = KwExpr<keyword::RemoveEnv, StringExpr<'a>>;
+/// `timeout "30s"`
+pub type TimeoutStmt<'a> = KwExpr<keyword::Timeout, StringExpr<'a>>;
+pub type PoolStmt<'a> = KwExpr<keyword::Pool, ConfigString<'a>>;
+/// `host "builder1"`
+pub type HostStmt<'a> = KwExpr<keyword::Host, ConfigString<'a>>;
+/// `container "alpine:3.21"`
+pub type ContainerStmt<'a> = KwExpr<keyword::Container, StringExpr<'a>>;
+/// `out "objs/{%}.o"`
+pub type OutStmt<'a> = KwExpr<keyword::Out, StringExpr<'a>>;
+/// `exclude "vendor/%"`
+pub type ExcludeStmt<'a> = KwExpr<keyword::Exclude, PatternExpr<'a>>;
+/// `in-dir "tools"`
+pub type InDirStmt<'a> = KwExpr<keyword::InDir, StringExpr<'a>>;
+
+/// `run <expr>`, optionally preceded by attributes.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunStmt<'a> {
+    // Renamed to avoid clashing with the span of the flattened expression.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "stmt_span", default, skip_serializing_if = "is_ignored")
+    )]
+    pub span: Span,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub attributes: Vec<Attribute<'a>>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub token: keyword::Run,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_1: Whitespace,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub param: RunExpr<'a>,
+}
+
+impl SemanticHash for RunStmt<'_> {
+    fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // Attributes don't contribute to outdatedness.
+        self.param.semantic_hash(state);
+    }
+}
+
+/// `run-shell "<script>"`, optionally preceded by attributes. The script is
+/// passed to the configured shell interpreter.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunShellStmt<'a> {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub span: Span,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub attributes: Vec<Attribute<'a>>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub token: keyword::RunShell,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_1: Whitespace,
+    pub script: StringExpr<'a>,
+}
+
+impl SemanticHash for RunShellStmt<'_> {
+    fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // Attributes don't contribute to outdatedness.
+        self.script.semantic_hash(state);
+    }
+}
 
 /// Things that can appear in the `command` part of recipes.
-#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-#[serde(tag = "type", content = "value")]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 pub enum RunExpr<'a> {
     /// Run shell command.
     Shell(ShellExpr<'a>),
+    /// Run a program with a list of arguments, where each string is exactly
+    /// one argument.
+    Args(ArgsExpr<'a>),
+    /// Run shell commands connected by pipes, optionally with the standard
+    /// input and/or output redirected to files.
+    Pipeline(PipelineExpr<'a>),
     /// Write the result of the expression to the path. The string is an OS path.
     Write(WriteExpr<'a>),
     /// Copy one file to another.
     Copy(CopyExpr<'a>),
+    /// Download a file and verify its digest.
+    Fetch(FetchExpr<'a>),
+    /// Extract an archive to a directory.
+    Extract(ExtractExpr<'a>),
     /// Delete a file.
     Delete(DeleteExpr<'a>),
+    /// Create a directory and its parents.
+    Mkdir(MkdirExpr<'a>),
+    /// Create an empty file or update its modification time.
+    Touch(TouchExpr<'a>),
     /// Set an environment variable.
     Env(EnvStmt<'a>),
     /// Remove an environment variable.
     EnvRemove(EnvRemoveStmt<'a>),
+    /// Set the timeout of subsequent commands.
+    Timeout(TimeoutStmt<'a>),
     /// Print a message while running the command.
     Info(InfoExpr<'a>),
     /// Print a warning while running the command.
@@ -469,11 +909,18 @@ impl Spanned for RunExpr<'_> {
     fn span(&self) -> Span {
         match self {
             RunExpr::Shell(expr) => expr.span,
+            RunExpr::Args(expr) => expr.span,
+            RunExpr::Pipeline(expr) => expr.span,
             RunExpr::Write(expr) => expr.span,
             RunExpr::Copy(expr) => expr.span,
+            RunExpr::Fetch(expr) => expr.span,
+            RunExpr::Extract(expr) => expr.span,
             RunExpr::Delete(expr) => expr.span,
+            RunExpr::Mkdir(expr) => expr.span,
+            RunExpr::Touch(expr) => expr.span,
             RunExpr::Env(expr) => expr.span,
             RunExpr::EnvRemove(expr) => expr.span,
+            RunExpr::Timeout(expr) => expr.span,
             RunExpr::Info(expr) => expr.span,
             RunExpr::Warn(expr) => expr.span,
             RunExpr::List(list) => list.span,
@@ -487,33 +934,128 @@ impl SemanticHash for RunExpr<'_> {
         std::mem::discriminant(self).hash(state);
         match self {
             RunExpr::Shell(expr) => expr.semantic_hash(state),
+            RunExpr::Args(expr) => expr.semantic_hash(state),
+            RunExpr::Pipeline(expr) => expr.semantic_hash(state),
             RunExpr::Write(expr) => expr.semantic_hash(state),
             RunExpr::Copy(expr) => expr.semantic_hash(state),
+            RunExpr::Fetch(expr) => expr.semantic_hash(state),
+            RunExpr::Extract(expr) => expr.semantic_hash(state),
             RunExpr::Delete(expr) => expr.semantic_hash(state),
+            RunExpr::Mkdir(expr) => expr.semantic_hash(state),
+            RunExpr::Touch(expr) => expr.semantic_hash(state),
             RunExpr::Env(expr) => expr.semantic_hash(state),
             RunExpr::EnvRemove(expr) => expr.semantic_hash(state),
-            // Messages don't contribute to outdatedness.
-            RunExpr::Info(_) | RunExpr::Warn(_) => (),
+            // Messages and timeouts don't contribute to outdatedness.
+            RunExpr::Info(_) | RunExpr::Warn(_) | RunExpr::Timeout(_) => (),
             RunExpr::List(expr) => expr.semantic_hash(state),
             RunExpr::Block(block) => block.semantic_hash(state),
         }
     }
 }
 
-#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+/// `<command> < <stdin> | <command> > <stdout>`, where at least one pipe or
+/// redirection is present.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PipelineExpr<'a> {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub span: Span,
+    pub command: ShellExpr<'a>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub stdin: Option<Redirect<'a, token::LessThan>>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub pipes: Vec<PipeCommand<'a>>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub stdout: Option<Redirect<'a, token::GreaterThan>>,
+}
+
+impl SemanticHash for PipelineExpr<'_> {
+    fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.command.semantic_hash(state);
+        self.stdin.semantic_hash(state);
+        self.pipes.semantic_hash(state);
+        self.stdout.semantic_hash(state);
+    }
+}
+
+/// `| <command>`, including the preceding whitespace.
+#[derive(Debug, PartialEq)]
+pub struct PipeCommand<'a> {
+    pub span: Span,
+    pub ws_1: Whitespace,
+    pub token: token::Pipe,
+    pub ws_2: Whitespace,
+    pub command: ShellExpr<'a>,
+}
+
+#[cfg(feature = "serde")]
+serde_transparent! {
+    impl['a] PipeCommand<'a> {
+        command => command: ShellExpr<'a>,
+        span => span: Span,
+        ws_1 => ws_1: Whitespace,
+        token => token: token::Pipe,
+        ws_2 => ws_2: Whitespace,
+    }
+}
+
+impl SemanticHash for PipeCommand<'_> {
+    fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.command.semantic_hash(state);
+    }
+}
+
+/// `< <path>` or `> <path>`, including the preceding whitespace.
+#[derive(Debug, PartialEq)]
+pub struct Redirect<'a, T> {
+    pub span: Span,
+    pub ws_1: Whitespace,
+    pub token: T,
+    pub ws_2: Whitespace,
+    pub path: StringExpr<'a>,
+}
+
+#[cfg(feature = "serde")]
+serde_transparent! {
+    impl['a, T] Redirect<'a, T> {
+        path => path: StringExpr<'a>,
+        span => span: Span,
+        ws_1 => ws_1: Whitespace,
+        token => token: T,
+        ws_2 => ws_2: Whitespace,
+    }
+}
+
+impl<T> SemanticHash for Redirect<'_, T> {
+    fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.path.semantic_hash(state);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CopyExpr<'a> {
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub span: Span,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub token_copy: keyword::Copy,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_1: Whitespace,
     pub src: StringExpr<'a>,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_2: Whitespace,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub token_to: keyword::To,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_3: Whitespace,
     pub dest: StringExpr<'a>,
 }
@@ -525,20 +1067,83 @@ impl SemanticHash for CopyExpr<'_> {
     }
 }
 
-#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+/// `extract "<archive>" to "<dir>"`
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtractExpr<'a> {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub span: Span,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub token_extract: keyword::Extract,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_1: Whitespace,
+    pub archive: StringExpr<'a>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_2: Whitespace,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub token_to: keyword::To,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_3: Whitespace,
+    pub dest: StringExpr<'a>,
+}
+
+impl SemanticHash for ExtractExpr<'_> {
+    fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.archive.semantic_hash(state);
+        self.dest.semantic_hash(state);
+    }
+}
+
+/// `fetch "<url>" sha256 "<digest>" to "<path>"`
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FetchExpr<'a> {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub span: Span,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub token_fetch: keyword::Fetch,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_1: Whitespace,
+    pub url: StringExpr<'a>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_2: Whitespace,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub token_sha256: keyword::Sha256,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_3: Whitespace,
+    pub sha256: StringExpr<'a>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_4: Whitespace,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub token_to: keyword::To,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_5: Whitespace,
+    pub dest: StringExpr<'a>,
+}
+
+impl SemanticHash for FetchExpr<'_> {
+    fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.url.semantic_hash(state);
+        self.sha256.semantic_hash(state);
+        self.dest.semantic_hash(state);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WriteExpr<'a> {
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub span: Span,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub token_write: keyword::Write,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_1: Whitespace,
     pub value: Expr<'a>,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_2: Whitespace,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub token_to: keyword::To,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_3: Whitespace,
     pub path: Expr<'a>,
 }
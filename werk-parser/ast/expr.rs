@@ -7,6 +7,9 @@ use crate::{
 
 use super::{keyword, token, Body, BodyStmt, Ident, PatternExpr, StringExpr, Trailing, Whitespace};
 
+#[cfg(feature = "serde")]
+use crate::parser::is_ignored;
+
 /// "Atomic" expression (no pipe chaining).
 ///
 /// Pipe chains always start with an atomic expression, optionally followed by a
@@ -15,8 +18,9 @@ use super::{keyword, token, Body, BodyStmt, Ident, PatternExpr, StringExpr, Trai
 ///
 /// Most operations take an atomic expression as a parameter - in other words,
 /// passing the output of a pipe expression as a parameter to an operation requires that it is parenthesized.
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-#[serde(tag = "type", content = "value")]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 pub enum Expr<'a> {
     // Look up variable in scope.
     Ident(Ident),
@@ -30,6 +34,22 @@ pub enum Expr<'a> {
     /// `(<expr>)`
     SubExpr(SubExpr<'a>),
     Error(ErrorExpr<'a>),
+    /// `glob-out "<pattern>"`: Like `glob`, but also includes the targets of
+    /// build recipes that match the pattern.
+    GlobOut(GlobOutExpr<'a>),
+    /// `is-overridden <ident>`: `"1"` if the global variable was overridden
+    /// from the command line with `-D`, and `"0"` otherwise.
+    IsOverridden(IsOverriddenExpr),
+    /// `hash <expr>`: Digest of the contents of a file, or of a list of files.
+    Hash(HashExpr<'a>),
+    /// Integer literal, like `2`. Evaluates to the decimal string.
+    Int(IntLiteral),
+    /// `cpu-count`: The number of CPUs available to the build.
+    CpuCount(keyword::CpuCount),
+    /// `<expr> <op> <expr>`: Integer arithmetic.
+    Arith(Box<ArithExpr<'a>>),
+    /// `if <condition> then <expr> else <expr>`
+    If(Box<IfExpr<'a>>),
 }
 
 impl<'a> Expr<'a> {
@@ -52,6 +72,13 @@ impl Spanned for Expr<'_> {
             Expr::List(list) => list.span,
             Expr::SubExpr(expr) => expr.span,
             Expr::Error(expr) => expr.span,
+            Expr::GlobOut(expr) => expr.span,
+            Expr::IsOverridden(expr) => expr.span,
+            Expr::Hash(expr) => expr.span,
+            Expr::Int(expr) => expr.span,
+            Expr::CpuCount(kw) => kw.span(),
+            Expr::Arith(expr) => expr.span,
+            Expr::If(expr) => expr.span,
         }
     }
 }
@@ -69,48 +96,279 @@ impl SemanticHash for Expr<'_> {
             Expr::Env(s) => s.semantic_hash(state),
             Expr::List(list) => list.semantic_hash(state),
             Expr::SubExpr(expr) => expr.expr.semantic_hash(state),
-            // The error message does not contribute to outdatedness.
-            Expr::Error(_) => (),
+            // The error message does not contribute to outdatedness, and
+            // `cpu-count` is covered by the discriminant.
+            Expr::Error(_) | Expr::CpuCount(_) => (),
+            Expr::GlobOut(s) => s.semantic_hash(state),
+            Expr::IsOverridden(s) => s.semantic_hash(state),
+            Expr::Hash(s) => s.semantic_hash(state),
+            Expr::Int(int) => int.value.hash(state),
+            Expr::Arith(expr) => expr.semantic_hash(state),
+            Expr::If(expr) => expr.semantic_hash(state),
+        }
+    }
+}
+
+/// Integer literal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntLiteral {
+    pub span: Span,
+    pub value: i64,
+}
+
+#[cfg(feature = "serde")]
+serde_transparent! {
+    impl[] IntLiteral {
+        value => value: i64,
+        span => span: Span,
+    }
+}
+
+/// Binary arithmetic expression, like `cpu-count / 2`. Operands must evaluate
+/// to strings containing integers.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ArithExpr<'a> {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub span: Span,
+    pub lhs: Expr<'a>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_1: Whitespace,
+    pub op: ArithOp,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_2: Whitespace,
+    pub rhs: Expr<'a>,
+}
+
+impl SemanticHash for ArithExpr<'_> {
+    fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.lhs.semantic_hash(state);
+        std::mem::discriminant(&self.op).hash(state);
+        self.rhs.semantic_hash(state);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ArithOp {
+    /// `+`
+    Add(keyword::Plus),
+    /// `-`
+    Sub(keyword::Minus),
+    /// `*`
+    Mul(keyword::Star),
+    /// `/`
+    Div(keyword::Slash),
+    /// `%`
+    Rem(keyword::Percent),
+}
+
+impl ArithOp {
+    /// Multiplicative operators bind tighter than additive operators.
+    #[inline]
+    #[must_use]
+    pub fn precedence(self) -> u8 {
+        match self {
+            ArithOp::Add(_) | ArithOp::Sub(_) => 1,
+            ArithOp::Mul(_) | ArithOp::Div(_) | ArithOp::Rem(_) => 2,
+        }
+    }
+}
+
+impl Spanned for ArithOp {
+    #[inline]
+    fn span(&self) -> Span {
+        match self {
+            ArithOp::Add(kw) => kw.span(),
+            ArithOp::Sub(kw) => kw.span(),
+            ArithOp::Mul(kw) => kw.span(),
+            ArithOp::Div(kw) => kw.span(),
+            ArithOp::Rem(kw) => kw.span(),
+        }
+    }
+}
+
+/// `if <condition> then <expr> else <expr>`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IfExpr<'a> {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub span: Span,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub token_if: keyword::If,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_1: Whitespace,
+    pub condition: Condition<'a>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_2: Whitespace,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub token_then: keyword::Then,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_3: Whitespace,
+    pub then_expr: Expr<'a>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_4: Whitespace,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub token_else: keyword::Else,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_5: Whitespace,
+    pub else_expr: Expr<'a>,
+}
+
+impl SemanticHash for IfExpr<'_> {
+    fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.condition.semantic_hash(state);
+        self.then_expr.semantic_hash(state);
+        self.else_expr.semantic_hash(state);
+    }
+}
+
+/// Boolean condition in `if` expressions and match guards.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Condition<'a> {
+    /// `<expr>`: True unless the value is `""`, `"0"`, `"false"`, or an empty
+    /// list.
+    Value(Expr<'a>),
+    /// `<expr> <op> <expr>`
+    Compare(Box<Comparison<'a>>),
+    /// `not <condition>`
+    Not(Box<NotCondition<'a>>),
+    /// `<condition> and <condition>` or `<condition> or <condition>`
+    Logical(Box<LogicalCondition<'a>>),
+}
+
+impl Spanned for Condition<'_> {
+    #[inline]
+    fn span(&self) -> Span {
+        match self {
+            Condition::Value(expr) => expr.span(),
+            Condition::Compare(cmp) => cmp.span,
+            Condition::Not(not) => not.span,
+            Condition::Logical(logical) => logical.span,
+        }
+    }
+}
+
+impl SemanticHash for Condition<'_> {
+    fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Condition::Value(expr) => expr.semantic_hash(state),
+            Condition::Compare(cmp) => cmp.semantic_hash(state),
+            Condition::Not(not) => not.semantic_hash(state),
+            Condition::Logical(logical) => logical.semantic_hash(state),
         }
     }
 }
 
+pub type NotCondition<'a> = KwExpr<keyword::Not, Condition<'a>>;
+
+/// Comparison of two values, like `os == "windows"`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Comparison<'a> {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub span: Span,
+    pub lhs: Expr<'a>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_1: Whitespace,
+    pub op: CompareOp,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_2: Whitespace,
+    pub rhs: Expr<'a>,
+}
+
+impl SemanticHash for Comparison<'_> {
+    fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.lhs.semantic_hash(state);
+        std::mem::discriminant(&self.op).hash(state);
+        self.rhs.semantic_hash(state);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LogicalCondition<'a> {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub span: Span,
+    pub lhs: Condition<'a>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_1: Whitespace,
+    pub op: LogicalOp,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_2: Whitespace,
+    pub rhs: Condition<'a>,
+}
+
+impl SemanticHash for LogicalCondition<'_> {
+    fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.lhs.semantic_hash(state);
+        std::mem::discriminant(&self.op).hash(state);
+        self.rhs.semantic_hash(state);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LogicalOp {
+    /// `and`
+    And(keyword::And),
+    /// `or`
+    Or(keyword::Or),
+}
+
 /// Parenthesized sub-expression.
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-#[serde(transparent)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SubExpr<'a> {
-    #[serde(skip, default)]
     pub span: Span,
-    #[serde(skip, default)]
     pub token_open: token::ParenOpen,
-    #[serde(skip, default)]
     pub ws_1: Whitespace,
     pub expr: Box<ExprChain<'a>>,
-    #[serde(skip, default)]
     pub ws_2: Whitespace,
-    #[serde(skip, default)]
     pub token_close: token::ParenClose,
 }
 
+#[cfg(feature = "serde")]
+serde_transparent! {
+    impl['a] SubExpr<'a> {
+        expr => expr: Box<ExprChain<'a>>,
+        span => span: Span,
+        token_open => token_open: token::ParenOpen,
+        ws_1 => ws_1: Whitespace,
+        ws_2 => ws_2: Whitespace,
+        token_close => token_close: token::ParenClose,
+    }
+}
+
 /// An operation within an expression chain (`... | <op>`).
 ///
 /// These are expressions that take an input (left-hand side of the pipe symbol)
 /// and produce an output, which will be passed to any subsequent operations, or
 /// returned as the value.
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ExprOp<'a> {
     SubExpr(SubExpr<'a>),
     StringExpr(StringExpr<'a>),
     Match(MatchExpr<'a>),
+    MatchRegex(MatchRegexExpr<'a>),
     Map(MapExpr<'a>),
     Flatten(FlattenExpr<'a>),
     Filter(FilterExpr<'a>),
     FilterMatch(FilterMatchExpr<'a>),
     Discard(DiscardExpr<'a>),
     Join(JoinExpr<'a>),
+    PathJoin(PathJoinExpr<'a>),
     Split(SplitExpr<'a>),
     Lines(LinesExpr<'a>),
     Dedup(DedupExpr<'a>),
+    /// Mark the strings of the value as secret, so they are redacted in
+    /// everything shown to the user.
+    Secret(SecretExpr<'a>),
+    /// Mark the dependencies of a `from` statement as volatile, so they never
+    /// make the recipe outdated.
+    Volatile(VolatileExpr<'a>),
     Info(InfoExpr<'a>),
     Warn(WarnExpr<'a>),
     Error(ErrorExpr<'a>),
@@ -125,14 +383,18 @@ impl Spanned for ExprOp<'_> {
             ExprOp::SubExpr(expr) => expr.span,
             ExprOp::StringExpr(expr) => expr.span,
             ExprOp::Match(expr) => expr.span,
+            ExprOp::MatchRegex(expr) => expr.span,
             ExprOp::Map(expr) => expr.span,
             ExprOp::Flatten(expr) => expr.span(),
             ExprOp::Filter(expr) => expr.span,
             ExprOp::FilterMatch(expr) => expr.span,
             ExprOp::Discard(expr) => expr.span,
             ExprOp::Join(expr) => expr.span,
+            ExprOp::PathJoin(expr) => expr.span(),
             ExprOp::Split(expr) => expr.span,
             ExprOp::Dedup(expr) => expr.span(),
+            ExprOp::Secret(expr) => expr.span(),
+            ExprOp::Volatile(expr) => expr.span(),
             ExprOp::Lines(expr) => expr.span(),
             ExprOp::Info(expr) => expr.span,
             ExprOp::Warn(expr) => expr.span,
@@ -150,6 +412,7 @@ impl SemanticHash for ExprOp<'_> {
             ExprOp::SubExpr(expr) => expr.expr.semantic_hash(state),
             ExprOp::StringExpr(expr) => expr.semantic_hash(state),
             ExprOp::Match(expr) => expr.semantic_hash(state),
+            ExprOp::MatchRegex(expr) => expr.semantic_hash(state),
             ExprOp::Map(expr) => expr.semantic_hash(state),
             ExprOp::Filter(expr) => expr.semantic_hash(state),
             ExprOp::FilterMatch(expr) => expr.semantic_hash(state),
@@ -163,22 +426,24 @@ impl SemanticHash for ExprOp<'_> {
             | ExprOp::AssertEq(_)
             | ExprOp::AssertMatch(_)
             // Covered by the discriminant:
-            | ExprOp::Dedup(_) | ExprOp::Flatten(_) | ExprOp::Lines(_)
+            | ExprOp::Dedup(_) | ExprOp::Flatten(_) | ExprOp::Lines(_) | ExprOp::PathJoin(_)
+            | ExprOp::Secret(_) | ExprOp::Volatile(_)
             => (),
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ListExpr<E> {
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub span: Span,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub token_open: token::BracketOpen,
     pub items: Vec<ListItem<E>>,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_trailing: Whitespace,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub token_close: token::BracketClose,
 }
 
@@ -188,23 +453,30 @@ impl<E: SemanticHash> SemanticHash for ListExpr<E> {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-#[serde(transparent)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ListItem<E> {
-    #[serde(skip, default)]
     pub ws_pre: Whitespace,
     pub item: E,
-    #[serde(skip, default)]
     pub trailing: Trailing<token::Comma>,
 }
 
+#[cfg(feature = "serde")]
+serde_transparent! {
+    impl[E] ListItem<E> {
+        item => item: E,
+        ws_pre => ws_pre: Whitespace,
+        trailing => trailing: Trailing<token::Comma>,
+    }
+}
+
 impl<E: SemanticHash> SemanticHash for ListItem<E> {
     fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.item.semantic_hash(state);
     }
 }
 
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MatchBody<'a> {
     Single(Box<MatchArm<'a>>),
     Braced(Body<MatchArm<'a>>),
@@ -280,34 +552,184 @@ impl SemanticHash for MatchBody<'_> {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MatchArm<'a> {
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub span: Span,
-    pub pattern: PatternExpr<'a>,
-    /// Whitespace between the pattern and the fat arrow.
-    #[serde(skip, default)]
+    pub pattern: MatchPattern<'a>,
+    /// `if <expr> == <expr>`, including preceding whitespace.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub guard: Option<MatchGuard<'a>>,
+    /// Whitespace between the pattern (or guard) and the fat arrow.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_1: Whitespace,
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub token_fat_arrow: keyword::FatArrow,
     /// Whitespace between the fat arrow and the expression.
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub ws_2: Whitespace,
     pub expr: ExprChain<'a>,
 }
 
+impl MatchArm<'_> {
+    /// True if the arm matches any string, i.e. it is `_` or `"%"` without a
+    /// guard.
+    #[must_use]
+    pub fn is_catch_all(&self) -> bool {
+        self.guard.is_none()
+            && match self.pattern {
+                MatchPattern::Pattern(ref pattern) => {
+                    matches!(
+                        pattern.fragments.as_slice(),
+                        [super::PatternFragment::PatternStem]
+                    )
+                }
+                MatchPattern::Special(SpecialPattern::Wildcard(_)) => true,
+                MatchPattern::Special(_) | MatchPattern::Regex(_) => false,
+            }
+    }
+}
+
 impl SemanticHash for MatchArm<'_> {
     fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.pattern.semantic_hash(state);
+        self.guard.semantic_hash(state);
         self.expr.semantic_hash(state);
     }
 }
 
+/// The left-hand side of a match arm.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum MatchPattern<'a> {
+    /// String pattern, like `"%.c"`.
+    Pattern(PatternExpr<'a>),
+    /// Regular expression in `match-regex`, like `"^v(\d+)"`.
+    Regex(RegexPattern),
+    Special(SpecialPattern),
+}
+
+impl Spanned for MatchPattern<'_> {
+    #[inline]
+    fn span(&self) -> Span {
+        match self {
+            MatchPattern::Pattern(pattern) => pattern.span,
+            MatchPattern::Regex(regex) => regex.span,
+            MatchPattern::Special(SpecialPattern::Wildcard(kw)) => kw.span(),
+            MatchPattern::Special(SpecialPattern::EmptyList(kw)) => kw.span(),
+            MatchPattern::Special(SpecialPattern::NonEmptyList(kw)) => kw.span(),
+        }
+    }
+}
+
+impl SemanticHash for MatchPattern<'_> {
+    fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            MatchPattern::Pattern(pattern) => pattern.semantic_hash(state),
+            MatchPattern::Regex(regex) => regex.regex.as_str().hash(state),
+            MatchPattern::Special(special) => std::mem::discriminant(special).hash(state),
+        }
+    }
+}
+
+/// Regular expression pattern in a `match-regex` arm. The string literal is
+/// passed verbatim to the `regex` crate, and is compiled while parsing.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegexPattern {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub span: Span,
+    #[cfg_attr(feature = "serde", serde(with = "serde_regex"))]
+    pub regex: regex::Regex,
+}
+
+impl PartialEq for RegexPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.regex.as_str() == other.regex.as_str()
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_regex {
+    use serde::Deserialize as _;
+
+    pub fn serialize<S: serde::Serializer>(
+        regex: &regex::Regex,
+        ser: S,
+    ) -> Result<S::Ok, S::Error> {
+        ser.serialize_str(regex.as_str())
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(de: D) -> Result<regex::Regex, D::Error> {
+        let s = String::deserialize(de)?;
+        regex::Regex::new(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Match patterns that are not strings.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpecialPattern {
+    /// `_`, matching any string.
+    Wildcard(keyword::Underscore),
+    /// `[]`, matching an empty list.
+    EmptyList(keyword::EmptyList),
+    /// `[..]`, matching a non-empty list.
+    NonEmptyList(keyword::NonEmptyList),
+}
+
+/// `if <condition>` after the pattern of a match arm. The arm is only chosen if
+/// the condition holds.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchGuard<'a> {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub span: Span,
+    /// Whitespace between the pattern and `if`.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_pre: Whitespace,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub token_if: keyword::If,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
+    pub ws_1: Whitespace,
+    pub condition: Condition<'a>,
+}
+
+impl SemanticHash for MatchGuard<'_> {
+    fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.condition.semantic_hash(state);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CompareOp {
+    /// `==`
+    Eq(keyword::EqEq),
+    /// `!=`
+    NotEq(keyword::NotEq),
+    /// `<`, comparing integers
+    Less(keyword::Less),
+    /// `<=`, comparing integers
+    LessEq(keyword::LessEq),
+    /// `>`, comparing integers
+    Greater(keyword::Greater),
+    /// `>=`, comparing integers
+    GreaterEq(keyword::GreaterEq),
+}
+
 /// Expression with optional chain of operations. This is valid after `let =`,
 /// inside parentheses, as list elements, or the right-hand side of braced match arms.
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExprChain<'a> {
-    #[serde(skip, default)]
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_ignored"))]
     pub span: Span,
     /// The initial expression of the chain.
     pub expr: Expr<'a>,
@@ -343,20 +765,26 @@ impl SemanticHash for ExprChain<'_> {
 }
 
 /// Entry in an expression chain `| expr`.
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-#[serde(transparent)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ChainSubExpr<'a> {
-    #[serde(skip, default)]
     pub span: Span,
-    #[serde(skip, default)]
     pub ws_1: Whitespace,
-    #[serde(skip, default)]
     pub token_pipe: token::Pipe,
-    #[serde(skip, default)]
     pub ws_2: Whitespace,
     pub expr: ExprOp<'a>,
 }
 
+#[cfg(feature = "serde")]
+serde_transparent! {
+    impl['a] ChainSubExpr<'a> {
+        expr => expr: ExprOp<'a>,
+        span => span: Span,
+        ws_1 => ws_1: Whitespace,
+        token_pipe => token_pipe: token::Pipe,
+        ws_2 => ws_2: Whitespace,
+    }
+}
+
 impl SemanticHash for ChainSubExpr<'_> {
     fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.expr.semantic_hash(state);
@@ -365,11 +793,14 @@ impl SemanticHash for ChainSubExpr<'_> {
 
 pub type JoinExpr<'a> = KwExpr<keyword::Join, StringExpr<'a>>;
 pub type MapExpr<'a> = KwExpr<keyword::Map, Expr<'a>>;
-pub type GlobExpr<'a> = KwExpr<keyword::Glob, StringExpr<'a>>;
+pub type GlobExpr<'a> = KwExpr<keyword::Glob, Box<Expr<'a>>>;
+pub type GlobOutExpr<'a> = KwExpr<keyword::GlobOut, Box<Expr<'a>>>;
 pub type WhichExpr<'a> = KwExpr<keyword::Which, StringExpr<'a>>;
 pub type EnvExpr<'a> = KwExpr<keyword::Env, StringExpr<'a>>;
+pub type IsOverriddenExpr = KwExpr<keyword::IsOverridden, Ident>;
 pub type ShellExpr<'a> = KwExpr<keyword::Shell, StringExpr<'a>>;
 pub type ReadExpr<'a> = KwExpr<keyword::Read, StringExpr<'a>>;
+pub type HashExpr<'a> = KwExpr<keyword::Hash, Box<Expr<'a>>>;
 pub type InfoExpr<'a> = KwExpr<keyword::Info, StringExpr<'a>>;
 pub type WarnExpr<'a> = KwExpr<keyword::Warn, StringExpr<'a>>;
 pub type ErrorExpr<'a> = KwExpr<keyword::Error, StringExpr<'a>>;
@@ -378,26 +809,36 @@ pub type AssertMatchExpr<'a> = KwExpr<keyword::AssertEq, Box<PatternExpr<'a>>>;
 pub type FlattenExpr<'a> = keyword::Flatten;
 pub type SplitExpr<'a> = KwExpr<keyword::Split, PatternExpr<'a>>;
 pub type DedupExpr<'a> = keyword::Dedup;
+pub type SecretExpr<'a> = keyword::Secret;
+pub type VolatileExpr<'a> = keyword::Volatile;
 pub type LinesExpr<'a> = keyword::Lines;
+pub type PathJoinExpr<'a> = keyword::PathJoin;
 pub type FilterExpr<'a> = KwExpr<keyword::Filter, PatternExpr<'a>>;
 pub type FilterMatchExpr<'a> = KwExpr<keyword::FilterMatch, MatchBody<'a>>;
 pub type MatchExpr<'a> = KwExpr<keyword::Match, MatchBody<'a>>;
+pub type MatchRegexExpr<'a> = KwExpr<keyword::MatchRegex, MatchBody<'a>>;
 pub type DiscardExpr<'a> = KwExpr<keyword::Discard, PatternExpr<'a>>;
 
 /// Expression that is a pair of a token and a parameter, such as `<keyword>
 /// <expr>`. Example: `join ","`
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-#[serde(transparent)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct KwExpr<Token, Param> {
-    #[serde(skip, default)]
     pub span: Span,
-    #[serde(skip, default)]
     pub token: Token,
-    #[serde(skip, default)]
     pub ws_1: Whitespace,
     pub param: Param,
 }
 
+#[cfg(feature = "serde")]
+serde_transparent! {
+    impl[Token, Param] KwExpr<Token, Param> {
+        param => param: Param,
+        span => span: Span,
+        token => token: Token,
+        ws_1 => ws_1: Whitespace,
+    }
+}
+
 impl<T, P: SemanticHash> SemanticHash for KwExpr<T, P> {
     fn semantic_hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.param.semantic_hash(state);
@@ -67,32 +67,29 @@ macro_rules! def_keyword {
                     .parse_next(input)
             }
         }
+        impl crate::parser::Shift for $t {
+            #[inline]
+            fn shift(&mut self, delta: i64) {
+                crate::parser::Shift::shift(&mut self.0, delta);
+            }
+        }
+        #[cfg(feature = "serde")]
+        impl crate::parser::IsIgnored for $t {
+            #[inline]
+            fn is_ignored(&self) -> bool {
+                self.0.is_ignored()
+            }
+        }
+        #[cfg(feature = "serde")]
         impl serde::Serialize for $t {
             fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
-                ser.serialize_unit()
+                serde::Serialize::serialize(&self.0, ser)
             }
         }
+        #[cfg(feature = "serde")]
         impl<'de> serde::Deserialize<'de> for $t {
-            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-            where
-                D: serde::Deserializer<'de>,
-            {
-                struct Visitor;
-                impl<'de> serde::de::Visitor<'de> for Visitor {
-                    type Value = $t;
-
-                    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                        write!(formatter, "unit")
-                    }
-
-                    fn visit_unit<E>(self) -> Result<Self::Value, E>
-                    where
-                        E: serde::de::Error,
-                    {
-                        Ok($t::default())
-                    }
-                }
-                deserializer.deserialize_unit(Visitor)
+            fn deserialize<D: serde::Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+                <Offset as serde::Deserialize>::deserialize(de).map(Self)
             }
         }
     };
@@ -100,24 +97,60 @@ macro_rules! def_keyword {
 
 def_keyword!(Let, "let");
 def_keyword!(Config, "config");
+def_keyword!(Import, "import");
+def_keyword!(As, "as");
+def_keyword!(Member, "member");
+def_keyword!(Profile, "profile");
 def_keyword!(Build, "build");
 def_keyword!(Task, "task");
 def_keyword!(Shell, "shell");
 def_keyword!(Glob, "glob");
+def_keyword!(GlobOut, "glob-out");
 def_keyword!(Which, "which");
 def_keyword!(Env, "env");
+def_keyword!(IsOverridden, "is-overridden");
 def_keyword!(Join, "join");
+def_keyword!(PathJoin, "path-join");
 def_keyword!(Then, "then");
+def_keyword!(Else, "else");
+def_keyword!(And, "and");
+def_keyword!(Or, "or");
+def_keyword!(Not, "not");
 def_keyword!(Info, "info");
 def_keyword!(Warn, "warn");
 def_keyword!(Error, "error");
 def_keyword!(Match, "match");
+def_keyword!(MatchRegex, "match-regex");
 def_keyword!(Write, "write");
 def_keyword!(Read, "read");
+def_keyword!(Hash, "hash");
 def_keyword!(Run, "run");
+def_keyword!(RunShell, "run-shell");
 def_keyword!(Copy, "copy");
+def_keyword!(Fetch, "fetch");
+def_keyword!(Sha256, "sha256");
+def_keyword!(Extract, "extract");
 def_keyword!(Delete, "delete");
+def_keyword!(Mkdir, "mkdir");
+def_keyword!(Touch, "touch");
+def_keyword!(Args, "args");
 def_keyword!(FatArrow, "=>");
+def_keyword!(EqEq, "==");
+def_keyword!(NotEq, "!=");
+def_keyword!(LessEq, "<=");
+def_keyword!(Less, "<");
+def_keyword!(GreaterEq, ">=");
+def_keyword!(Greater, ">");
+def_keyword!(Plus, "+");
+def_keyword!(Minus, "-");
+def_keyword!(Star, "*");
+def_keyword!(Slash, "/");
+def_keyword!(Percent, "%");
+def_keyword!(CpuCount, "cpu-count");
+def_keyword!(If, "if");
+def_keyword!(Underscore, "_");
+def_keyword!(EmptyList, "[]");
+def_keyword!(NonEmptyList, "[..]");
 def_keyword!(From, "from");
 def_keyword!(Depfile, "depfile");
 def_keyword!(False, "false");
@@ -131,9 +164,19 @@ def_keyword!(Discard, "discard");
 def_keyword!(Split, "split");
 def_keyword!(Dedup, "dedup");
 def_keyword!(Lines, "lines");
+def_keyword!(Secret, "secret");
+def_keyword!(Volatile, "volatile");
 
 def_keyword!(AssertEq, "assert-eq");
 def_keyword!(SetCapture, "capture");
 def_keyword!(SetNoCapture, "no-capture");
+def_keyword!(Priority, "priority");
+def_keyword!(Timeout, "timeout");
+def_keyword!(Pool, "pool");
+def_keyword!(Host, "host");
+def_keyword!(Container, "container");
+def_keyword!(Out, "out");
+def_keyword!(Exclude, "exclude");
+def_keyword!(InDir, "in-dir");
 def_keyword!(SetEnv, "env");
 def_keyword!(RemoveEnv, "env-remove");
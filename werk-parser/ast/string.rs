@@ -2,11 +2,22 @@ use std::{borrow::Cow, fmt::Write, hash::Hash as _};
 
 use werk_util::Symbol;
 
+#[cfg(feature = "serde")]
+use crate::parser::{parse_pattern_expr_unquoted, parse_string_expr_unquoted};
 use crate::{
-    parser::{parse_pattern_expr_unquoted, parse_string_expr_unquoted, Escape, Span},
+    parser::{escape_special_char, Escape, Span},
     SemanticHash,
 };
 
+/// The serialized form of [`StringExpr`] and [`PatternExpr`].
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum SpannedString {
+    Full { span: Span, string: String },
+    Compact(String),
+}
+
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct StringExpr<'a> {
     pub span: Span,
@@ -40,21 +51,35 @@ impl std::fmt::Display for StringExpr<'_> {
     }
 }
 
+/// Serialized as the string without quotes, or as `{"span": [start, end],
+/// "string": "..."}` when the span is known.
+#[cfg(feature = "serde")]
 impl serde::Serialize for StringExpr<'_> {
     fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
-        ser.serialize_str(&self.to_string())
+        if self.span.is_ignored() {
+            return ser.serialize_str(&self.to_string());
+        }
+        let mut state = serde::Serializer::serialize_struct(ser, "StringExpr", 2)?;
+        serde::ser::SerializeStruct::serialize_field(&mut state, "span", &self.span)?;
+        serde::ser::SerializeStruct::serialize_field(&mut state, "string", &self.to_string())?;
+        serde::ser::SerializeStruct::end(state)
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> serde::Deserialize<'de> for StringExpr<'_> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        parse_string_expr_unquoted(&s)
+        let (span, string) = match <SpannedString as serde::Deserialize>::deserialize(deserializer)?
+        {
+            SpannedString::Full { span, string } => (span, string),
+            SpannedString::Compact(string) => (Span::ignore(), string),
+        };
+        parse_string_expr_unquoted(&string)
             .map(|mut expr| {
-                expr.span = Span::ignore();
+                expr.span = span;
                 expr.into_static()
             })
             .map_err(serde::de::Error::custom)
@@ -142,6 +167,23 @@ impl std::fmt::Display for PatternExpr<'_> {
                 PatternFragment::Literal(s) => Escape::<true>(s).fmt(f)?,
                 PatternFragment::Interpolation(interp) => interp.fmt(f)?,
                 PatternFragment::PatternStem => f.write_char('%')?,
+                PatternFragment::NamedCapture(name) => write!(f, "(%{name})")?,
+                PatternFragment::CharClass(ranges) => {
+                    f.write_char('[')?;
+                    // A `-` is only literal outside of a range, so write it
+                    // first.
+                    if ranges.contains(&('-'..='-')) {
+                        f.write_char('-')?;
+                    }
+                    for range in ranges.iter().filter(|range| **range != ('-'..='-')) {
+                        write_class_char(f, *range.start())?;
+                        if range.start() != range.end() {
+                            f.write_char('-')?;
+                            write_class_char(f, *range.end())?;
+                        }
+                    }
+                    f.write_char(']')?;
+                }
                 PatternFragment::OneOf(vec) => {
                     f.write_char('(')?;
                     for (index, pattern) in vec.iter().enumerate() {
@@ -158,21 +200,47 @@ impl std::fmt::Display for PatternExpr<'_> {
     }
 }
 
+fn write_class_char(f: &mut std::fmt::Formatter<'_>, ch: char) -> std::fmt::Result {
+    if matches!(ch, ']' | '\\' | '"') {
+        f.write_char('\\')?;
+        f.write_char(ch)
+    } else if let Some(escape_char) = escape_special_char(ch) {
+        f.write_char('\\')?;
+        f.write_char(escape_char)
+    } else {
+        f.write_char(ch)
+    }
+}
+
+/// Serialized as the string without quotes, or as `{"span": [start, end],
+/// "string": "..."}` when the span is known.
+#[cfg(feature = "serde")]
 impl serde::Serialize for PatternExpr<'_> {
     fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
-        ser.serialize_str(&self.to_string())
+        if self.span.is_ignored() {
+            return ser.serialize_str(&self.to_string());
+        }
+        let mut state = serde::Serializer::serialize_struct(ser, "PatternExpr", 2)?;
+        serde::ser::SerializeStruct::serialize_field(&mut state, "span", &self.span)?;
+        serde::ser::SerializeStruct::serialize_field(&mut state, "string", &self.to_string())?;
+        serde::ser::SerializeStruct::end(state)
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> serde::Deserialize<'de> for PatternExpr<'_> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        parse_pattern_expr_unquoted(&s)
+        let (span, string) = match <SpannedString as serde::Deserialize>::deserialize(deserializer)?
+        {
+            SpannedString::Full { span, string } => (span, string),
+            SpannedString::Compact(string) => (Span::ignore(), string),
+        };
+        parse_pattern_expr_unquoted(&string)
             .map(|mut expr| {
-                expr.span = Span::ignore();
+                expr.span = span;
                 expr.into_static()
             })
             .map_err(serde::de::Error::custom)
@@ -191,8 +259,13 @@ pub enum PatternFragment<'a> {
     Literal(Cow<'a, str>),
     /// `%`
     PatternStem,
+    /// `(%name)`, matching any string like the stem.
+    NamedCapture(Symbol),
     /// `(a|b|c)`
     OneOf(Vec<Cow<'a, str>>),
+    /// `[abc]` or `[a-z]`, matching a single character. Captured like a
+    /// one-of group.
+    CharClass(Vec<std::ops::RangeInclusive<char>>),
     /// `{...}`
     Interpolation(Interpolation<'a>),
 }
@@ -203,6 +276,8 @@ impl PatternFragment<'_> {
         match self {
             PatternFragment::Literal(s) => PatternFragment::Literal(s.into_owned().into()),
             PatternFragment::PatternStem => PatternFragment::PatternStem,
+            PatternFragment::NamedCapture(name) => PatternFragment::NamedCapture(name),
+            PatternFragment::CharClass(ranges) => PatternFragment::CharClass(ranges),
             PatternFragment::OneOf(v) => {
                 PatternFragment::OneOf(v.into_iter().map(Cow::into_owned).map(Cow::Owned).collect())
             }
@@ -219,7 +294,9 @@ impl SemanticHash for PatternFragment<'_> {
         match self {
             PatternFragment::Literal(s) => s.hash(state),
             PatternFragment::PatternStem => (),
+            PatternFragment::NamedCapture(name) => name.as_str().hash(state),
             PatternFragment::OneOf(v) => v.hash(state),
+            PatternFragment::CharClass(ranges) => ranges.hash(state),
             PatternFragment::Interpolation(i) => i.semantic_hash(state),
         }
     }
@@ -274,6 +351,11 @@ impl SemanticHash for Interpolation<'_> {
     }
 }
 
+/// Characters that can be used as the separator in a join interpolation
+/// without quoting, like `{list,*}`. Other separators must be given with the
+/// `join="..."` operation.
+pub const VALID_JOIN_SEPARATORS: &[char] = &['+', ',', '.', '|', '/', '\\', ':', ';', ' '];
+
 impl std::fmt::Display for Interpolation<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let is_path = self.is_path_interpolation();
@@ -283,16 +365,23 @@ impl std::fmt::Display for Interpolation<'_> {
         match &self.stem {
             InterpolationStem::Implied => (),
             InterpolationStem::PatternCapture => f.write_char('%')?,
+            InterpolationStem::NamedCapture(name) => write!(f, "%{name}")?,
             InterpolationStem::CaptureGroup(i) => write!(f, "{i}")?,
             InterpolationStem::Ident(ident) => write!(f, "{ident}")?,
         }
 
+        // Separators that can't be written as `{...sep*}` are written as
+        // `join="sep"` after the other operations.
+        let mut join_op = None;
         if let Some(join) = self.join() {
             if join == " " {
                 // Elide the separator when it is a single space.
                 f.write_char('*')?;
-            } else {
+            } else if !join.is_empty() && join.chars().all(|ch| VALID_JOIN_SEPARATORS.contains(&ch))
+            {
                 write!(f, "{join}*")?;
+            } else {
+                join_op = Some(join);
             }
         }
 
@@ -331,8 +420,14 @@ impl std::fmt::Display for Interpolation<'_> {
                     InterpolationOp::Ext => f.write_str("ext")?,
                     InterpolationOp::ResolveOutDir => f.write_str("out-dir")?,
                     InterpolationOp::ResolveWorkspace => f.write_str("workspace")?,
+                    InterpolationOp::Quote(style) => f.write_str(style.name())?,
                 }
             }
+
+            if let Some(join) = join_op {
+                f.write_char(if has_colon { ',' } else { ':' })?;
+                write!(f, "join=\"{}\"", Escape::<false>(join))?;
+            }
         }
 
         f.write_char(if is_path { '>' } else { '}' })
@@ -343,8 +438,8 @@ impl std::fmt::Display for Interpolation<'_> {
 pub struct InterpolationOptions<'a> {
     /// `{stem:operation}`
     pub ops: Vec<InterpolationOp<'a>>,
-    /// `{...*}` - This is not a normal operation because we need to treat it
-    /// specially when building shell commands.
+    /// `{...*}` or `{...:join="sep"}` - This is not a normal operation because
+    /// we need to treat it specially when building shell commands.
     pub join: Option<Cow<'a, str>>,
 }
 
@@ -374,6 +469,8 @@ pub enum InterpolationStem {
     Implied,
     /// `{%}` - output is string.
     PatternCapture,
+    /// `{%name}` - output is string.
+    NamedCapture(Symbol),
     /// `{1}` - output is string.
     CaptureGroup(u32),
     /// `{ident}` - output is string.
@@ -386,7 +483,9 @@ impl SemanticHash for InterpolationStem {
         match self {
             InterpolationStem::PatternCapture | InterpolationStem::Implied => (),
             InterpolationStem::CaptureGroup(i) => i.hash(state),
-            InterpolationStem::Ident(s) => s.as_str().hash(state),
+            InterpolationStem::NamedCapture(s) | InterpolationStem::Ident(s) => {
+                s.as_str().hash(state);
+            }
         }
     }
 }
@@ -413,6 +512,46 @@ pub enum InterpolationOp<'a> {
     ResolveOsPath,
     ResolveOutDir,
     ResolveWorkspace,
+    /// Escape the string for use in another language, like `quote-sh`.
+    /// Quoting is applied after all other operations, including path
+    /// resolution.
+    Quote(QuoteStyle),
+}
+
+/// The target of a quoting interpolation operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum QuoteStyle {
+    /// `quote-sh`: A single word in a POSIX shell.
+    Sh,
+    /// `quote-cmd`: A single argument in a `cmd.exe` command line.
+    Cmd,
+    /// `quote-regex`: A regex matching the string literally.
+    Regex,
+    /// `quote-c`: A C string literal, including the quotes.
+    C,
+}
+
+impl QuoteStyle {
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "quote-sh" => Some(Self::Sh),
+            "quote-cmd" => Some(Self::Cmd),
+            "quote-regex" => Some(Self::Regex),
+            "quote-c" => Some(Self::C),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sh => "quote-sh",
+            Self::Cmd => "quote-cmd",
+            Self::Regex => "quote-regex",
+            Self::C => "quote-c",
+        }
+    }
 }
 
 impl InterpolationOp<'_> {
@@ -434,6 +573,7 @@ impl InterpolationOp<'_> {
             InterpolationOp::ResolveOsPath => InterpolationOp::ResolveOsPath,
             InterpolationOp::ResolveOutDir => InterpolationOp::ResolveOutDir,
             InterpolationOp::ResolveWorkspace => InterpolationOp::ResolveWorkspace,
+            InterpolationOp::Quote(style) => InterpolationOp::Quote(style),
         }
     }
 }
@@ -448,6 +588,7 @@ impl SemanticHash for InterpolationOp<'_> {
             }
             InterpolationOp::PrependEach(s) | InterpolationOp::AppendEach(s) => s.hash(state),
             InterpolationOp::RegexReplace(r) => r.hash(state),
+            InterpolationOp::Quote(style) => style.hash(state),
             // Covered by discriminant.
             InterpolationOp::Dedup
             | InterpolationOp::Filename
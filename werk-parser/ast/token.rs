@@ -45,6 +45,35 @@ impl<'a, const CHAR: char> Parse<'a> for Token<CHAR> {
     }
 }
 
+impl<const CHAR: char> crate::parser::Shift for Token<CHAR> {
+    #[inline]
+    fn shift(&mut self, delta: i64) {
+        crate::parser::Shift::shift(&mut self.0, delta);
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<const CHAR: char> crate::parser::IsIgnored for Token<CHAR> {
+    #[inline]
+    fn is_ignored(&self) -> bool {
+        self.0.is_ignored()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<const CHAR: char> serde::Serialize for Token<CHAR> {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&self.0, ser)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const CHAR: char> serde::Deserialize<'de> for Token<CHAR> {
+    fn deserialize<D: serde::Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        <Offset as serde::Deserialize>::deserialize(de).map(Self)
+    }
+}
+
 macro_rules! def_token {
     ($t:ident, $s:literal) => {
         #[doc = concat!("`", $s, "`")]
@@ -1,3 +1,5 @@
+use werk_util::Symbol;
+
 use crate::ast;
 
 pub struct Document<'a> {
@@ -6,6 +8,21 @@ pub struct Document<'a> {
     pub source: &'a str,
     /// "Whitespace" smuggled from TOML decorations.
     pub smuggled_whitespace: Option<String>,
+    /// Werkfiles imported by this document, including nested imports. Their
+    /// diagnostic file IDs are their index in this list plus one, since the
+    /// importing document itself is file 0.
+    pub imports: Vec<Import<'a>>,
+    /// False if the source code had syntax errors, so some of it is missing
+    /// from `root`. Only complete documents can be reparsed incrementally.
+    pub(crate) complete: bool,
+}
+
+/// A Werkfile imported into a namespace with `import "..." as name`.
+pub struct Import<'a> {
+    /// The namespace of the imported items, like `lib`, or `lib.util` when
+    /// `lib` itself imports a Werkfile as `util`.
+    pub namespace: Symbol,
+    pub document: Document<'a>,
 }
 
 impl<'a> Document<'a> {
@@ -20,9 +37,18 @@ impl<'a> Document<'a> {
             origin,
             source,
             smuggled_whitespace,
+            imports: Vec::new(),
+            complete: true,
         }
     }
 
+    /// Line and column lookups for the spans of this document, like
+    /// `document.line_index().range(span)`.
+    #[must_use]
+    pub fn line_index(&self) -> werk_util::LineIndex<'a> {
+        werk_util::LineIndex::new(self.source)
+    }
+
     #[must_use]
     pub fn get_whitespace(&self, whitespace: ast::Whitespace) -> &str {
         let range = whitespace.0.start.0 as usize..whitespace.0.end.0 as usize;
@@ -33,6 +59,46 @@ impl<'a> Document<'a> {
         }
     }
 
+    /// The doc comment of a statement, given the whitespace preceding it: the
+    /// comment lines immediately before the statement, without the leading
+    /// `#`. Empty if the statement is not preceded by a comment.
+    #[must_use]
+    pub fn doc_comment(&self, ws_pre: ast::Whitespace) -> String {
+        let whitespace = self.get_whitespace(ws_pre);
+        let mut lines = whitespace
+            .lines()
+            .map(str::trim)
+            .rev()
+            .skip_while(|line| line.is_empty())
+            .take_while(|line| line.starts_with('#') && !line.starts_with("#!"))
+            .map(|line| {
+                let line = &line[1..];
+                line.strip_prefix(' ').unwrap_or(line)
+            })
+            .collect::<Vec<_>>();
+        lines.reverse();
+        lines.join("\n")
+    }
+
+    /// The doc comment of the task recipe named `name`. Tasks in imported
+    /// Werkfiles are named by their qualified name, like `lib.test`.
+    #[must_use]
+    pub fn task_doc_comment(&self, name: &str) -> Option<String> {
+        let local = self.root.statements.iter().find(
+            |stmt| matches!(stmt.statement, ast::RootStmt::Task(ref task) if task.name == name),
+        );
+        if let Some(stmt) = local {
+            return Some(self.doc_comment(stmt.ws_pre));
+        }
+
+        self.imports.iter().find_map(|import| {
+            let name = name
+                .strip_prefix(import.namespace.as_str())?
+                .strip_prefix('.')?;
+            import.document.task_doc_comment(name)
+        })
+    }
+
     pub fn task_recipes(&self) -> impl Iterator<Item = &ast::CommandRecipe<'_>> + '_ {
         self.root
             .statements
@@ -63,6 +129,36 @@ impl<'a> Document<'a> {
             })
     }
 
+    pub fn import_stmts(&self) -> impl Iterator<Item = &ast::ImportStmt<'_>> + '_ {
+        self.root
+            .statements
+            .iter()
+            .filter_map(|stmt| match &stmt.statement {
+                ast::RootStmt::Import(import) => Some(import),
+                _ => None,
+            })
+    }
+
+    pub fn member_stmts(&self) -> impl Iterator<Item = &ast::MemberStmt<'_>> + '_ {
+        self.root
+            .statements
+            .iter()
+            .filter_map(|stmt| match &stmt.statement {
+                ast::RootStmt::Member(member) => Some(member),
+                _ => None,
+            })
+    }
+
+    pub fn profile_stmts(&self) -> impl Iterator<Item = &ast::ProfileStmt<'_>> + '_ {
+        self.root
+            .statements
+            .iter()
+            .filter_map(|stmt| match &stmt.statement {
+                ast::RootStmt::Profile(profile) => Some(profile),
+                _ => None,
+            })
+    }
+
     pub fn globals(&self) -> impl Iterator<Item = &ast::LetStmt<'_>> + '_ {
         self.root
             .statements
@@ -118,7 +214,11 @@ impl<'a> werk_util::DiagnosticFileRepository for &'a Document<'a> {
         if id.0 == 0 {
             Some(werk_util::DiagnosticSource::new(self.origin, self.source))
         } else {
-            None
+            let import = self.imports.get(id.0 as usize - 1)?;
+            Some(werk_util::DiagnosticSource::new(
+                import.document.origin,
+                import.document.source,
+            ))
         }
     }
 }
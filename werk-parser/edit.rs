@@ -0,0 +1,627 @@
+//! Programmatic edits of the source code of a Werkfile, like renaming a
+//! variable. Edits only touch the parts of the source code that actually
+//! change, so comments and formatting are preserved.
+
+use werk_util::Symbol;
+
+use crate::{
+    ast::{
+        self, BuildRecipeStmt, Condition, Expr, ExprOp, MatchPattern, RootStmt, RunExpr,
+        TaskRecipeStmt,
+    },
+    parser::{Offset, Span},
+    Document,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum EditError {
+    #[error("`{0}` is not a valid identifier")]
+    InvalidIdent(String),
+    #[error("no variable named `{0}`")]
+    UnknownVariable(String),
+    #[error("`{0}` is already used in this Werkfile")]
+    IdentInUse(String),
+    #[error("invalid statement: {0}")]
+    InvalidStatement(#[source] crate::Error),
+    #[error("expected a single statement, found {0}")]
+    NotOneStatement(usize),
+    #[error("edit at {0:?} is outside of the source code")]
+    OutOfBounds(Span),
+    #[error("edits at {0:?} and {1:?} overlap")]
+    Overlapping(Span, Span),
+}
+
+/// Replacement of a range of the source code.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextEdit {
+    /// The replaced range. Empty for insertions.
+    pub span: Span,
+    pub text: String,
+}
+
+/// A set of edits to the source code of a document, applied all at once. Spans
+/// refer to the original source code.
+#[derive(Clone, Debug)]
+pub struct Edits<'a> {
+    source: &'a str,
+    edits: Vec<TextEdit>,
+}
+
+impl<'a> Edits<'a> {
+    #[must_use]
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            edits: Vec::new(),
+        }
+    }
+
+    /// Replace the source code in `span` with `text`. The parts of `text` that
+    /// are already equal to the source code at the start and end of the span
+    /// are not included in the edit.
+    pub fn replace(&mut self, span: Span, text: &str) {
+        let Some(old) = self.get(span) else {
+            // Reported by `apply()`.
+            self.edits.push(TextEdit {
+                span,
+                text: text.to_owned(),
+            });
+            return;
+        };
+
+        let prefix = old
+            .char_indices()
+            .zip(text.chars())
+            .find(|&((_, a), b)| a != b)
+            .map_or_else(|| old.len().min(text.len()), |((index, _), _)| index);
+        let (old, text) = (&old[prefix..], &text[prefix..]);
+        let suffix = old
+            .char_indices()
+            .rev()
+            .zip(text.chars().rev())
+            .find(|&((_, a), b)| a != b)
+            .map_or_else(
+                || old.len().min(text.len()),
+                |((index, a), _)| old.len() - index - a.len_utf8(),
+            );
+        let (old, text) = (&old[..old.len() - suffix], &text[..text.len() - suffix]);
+        if old.is_empty() && text.is_empty() {
+            return;
+        }
+
+        let start = span.start.0 + prefix as u32;
+        self.edits.push(TextEdit {
+            span: Span::from(start..start + old.len() as u32),
+            text: text.to_owned(),
+        });
+    }
+
+    /// Insert `text` at `offset`. Insertions at the same offset are applied in
+    /// the order they were made.
+    pub fn insert(&mut self, offset: Offset, text: &str) {
+        self.edits.push(TextEdit {
+            span: Span::from(offset..offset),
+            text: text.to_owned(),
+        });
+    }
+
+    /// Replace a string literal, including its quotes.
+    pub fn replace_string(&mut self, string: &ast::StringExpr, new: &ast::StringExpr) {
+        self.replace(string.span, &format!("\"{new}\""));
+    }
+
+    /// Replace a pattern literal, including its quotes.
+    pub fn replace_pattern(&mut self, pattern: &ast::PatternExpr, new: &ast::PatternExpr) {
+        self.replace(pattern.span, &format!("\"{new}\""));
+    }
+
+    /// Add all edits of `other`, which must refer to the same source code.
+    pub fn extend(&mut self, other: Edits<'_>) {
+        self.edits.extend(other.edits);
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// The edits in source order, for example to send them to an editor.
+    #[must_use]
+    pub fn edits(&self) -> Vec<TextEdit> {
+        let mut edits = self.edits.clone();
+        edits.sort_by_key(|edit| (edit.span.start.0, edit.span.end.0));
+        edits
+    }
+
+    /// The source code with all edits applied.
+    pub fn apply(&self) -> Result<String, EditError> {
+        let edits = self.edits();
+        let mut result = String::with_capacity(self.source.len());
+        let mut pos = 0;
+        let mut prev: Option<Span> = None;
+        for edit in &edits {
+            if self.get(edit.span).is_none() {
+                return Err(EditError::OutOfBounds(edit.span));
+            }
+            if let Some(prev) = prev {
+                if prev.end.0 > edit.span.start.0 {
+                    return Err(EditError::Overlapping(prev, edit.span));
+                }
+            }
+            result.push_str(&self.source[pos..edit.span.start.0 as usize]);
+            result.push_str(&edit.text);
+            pos = edit.span.end.0 as usize;
+            prev = Some(edit.span);
+        }
+        result.push_str(&self.source[pos..]);
+        Ok(result)
+    }
+
+    fn get(&self, span: Span) -> Option<&'a str> {
+        if span.is_ignored() {
+            return None;
+        }
+        self.source.get(span.start.0 as usize..span.end.0 as usize)
+    }
+}
+
+impl<'a> Document<'a> {
+    /// Rename the variable `from` to `to`: all `let` statements declaring it,
+    /// including local variables and profiles, and all uses of it, including
+    /// interpolations in strings. Fails if `to` is already used, since the
+    /// renamed variable could then shadow it or be shadowed by it.
+    ///
+    /// Imported Werkfiles are not changed.
+    pub fn rename_variable(&self, from: &str, to: &str) -> Result<Edits<'a>, EditError> {
+        if !crate::parser::is_ident(to) {
+            return Err(EditError::InvalidIdent(to.to_owned()));
+        }
+
+        let mut rename = Rename {
+            from: Symbol::new(from),
+            to: Symbol::new(to),
+            edits: Edits::new(self.source),
+            found: false,
+            in_use: false,
+        };
+        rename.root(&self.root);
+
+        if rename.in_use {
+            Err(EditError::IdentInUse(to.to_owned()))
+        } else if rename.found {
+            Ok(rename.edits)
+        } else {
+            Err(EditError::UnknownVariable(from.to_owned()))
+        }
+    }
+
+    /// Add a statement, like `let cflags = ["-O2"]`, to the end of the
+    /// Werkfile, separated from the preceding statement by an empty line.
+    pub fn append_statement(&self, statement: &str) -> Result<Edits<'a>, EditError> {
+        let statement = statement.trim();
+        let parsed =
+            crate::parse_werk(self.origin, statement).map_err(EditError::InvalidStatement)?;
+        if parsed.root.statements.len() != 1 {
+            return Err(EditError::NotOneStatement(parsed.root.statements.len()));
+        }
+
+        let mut text = String::new();
+        if !self.source.is_empty() {
+            if !self.source.ends_with('\n') {
+                text.push('\n');
+            }
+            if !self.source.ends_with("\n\n") {
+                text.push('\n');
+            }
+        }
+        text.push_str(statement);
+        text.push('\n');
+
+        let mut edits = Edits::new(self.source);
+        edits.insert(Offset(self.source.len() as u32), &text);
+        Ok(edits)
+    }
+}
+
+/// Walks the syntax tree to find declarations and uses of a variable.
+struct Rename<'a> {
+    from: Symbol,
+    to: Symbol,
+    edits: Edits<'a>,
+    found: bool,
+    in_use: bool,
+}
+
+impl Rename<'_> {
+    fn ident(&mut self, ident: &ast::Ident) {
+        if ident.ident == self.from {
+            self.found = true;
+            self.edits.replace(ident.span, self.to.as_str());
+        } else if ident.ident == self.to {
+            self.in_use = true;
+        }
+    }
+
+    /// Rename the stem of the interpolation, returning true if it changed.
+    fn interpolation(&mut self, interp: &mut ast::Interpolation) -> bool {
+        match interp.stem {
+            ast::InterpolationStem::Ident(ref mut stem) if *stem == self.from => {
+                self.found = true;
+                *stem = self.to;
+                true
+            }
+            ast::InterpolationStem::Ident(stem) if stem == self.to => {
+                self.in_use = true;
+                false
+            }
+            _ => false,
+        }
+    }
+
+    fn string(&mut self, string: &ast::StringExpr) {
+        let mut new = string.clone();
+        let mut changed = false;
+        for fragment in &mut new.fragments {
+            if let ast::StringFragment::Interpolation(interp) = fragment {
+                changed |= self.interpolation(interp);
+            }
+        }
+        if changed {
+            self.edits.replace_string(string, &new);
+        }
+    }
+
+    fn pattern(&mut self, pattern: &ast::PatternExpr) {
+        let mut new = pattern.clone();
+        let mut changed = false;
+        for fragment in &mut new.fragments {
+            if let ast::PatternFragment::Interpolation(interp) = fragment {
+                changed |= self.interpolation(interp);
+            }
+        }
+        if changed {
+            self.edits.replace_pattern(pattern, &new);
+        }
+    }
+
+    fn root(&mut self, root: &ast::Root) {
+        for stmt in &root.statements {
+            match stmt.statement {
+                RootStmt::Let(ref stmt) => self.let_stmt(stmt),
+                RootStmt::Task(ref recipe) => {
+                    for stmt in &recipe.body.statements {
+                        self.task_stmt(&stmt.statement);
+                    }
+                }
+                RootStmt::Build(ref recipe) => {
+                    self.pattern(&recipe.pattern);
+                    for stmt in &recipe.body.statements {
+                        self.build_stmt(&stmt.statement);
+                    }
+                }
+                RootStmt::Profile(ref profile) => {
+                    for stmt in &profile.body.statements {
+                        self.let_stmt(&stmt.statement);
+                    }
+                }
+                RootStmt::Config(_) | RootStmt::Import(_) | RootStmt::Member(_) => (),
+            }
+        }
+    }
+
+    fn let_stmt(&mut self, stmt: &ast::LetStmt) {
+        self.ident(&stmt.ident);
+        self.chain(&stmt.value);
+    }
+
+    fn task_stmt(&mut self, stmt: &TaskRecipeStmt) {
+        match stmt {
+            TaskRecipeStmt::Let(stmt) => self.let_stmt(stmt),
+            TaskRecipeStmt::Build(stmt) => self.chain(&stmt.param),
+            TaskRecipeStmt::Run(stmt) => self.run(&stmt.param),
+            TaskRecipeStmt::RunShell(stmt) => self.string(&stmt.script),
+            TaskRecipeStmt::Env(stmt) => self.env(stmt),
+            TaskRecipeStmt::Info(stmt) => self.string(&stmt.param),
+            TaskRecipeStmt::Warn(stmt) => self.string(&stmt.param),
+            TaskRecipeStmt::EnvRemove(stmt) => self.string(&stmt.param),
+            TaskRecipeStmt::Timeout(stmt) => self.string(&stmt.param),
+            TaskRecipeStmt::InDir(stmt) => self.string(&stmt.param),
+            TaskRecipeStmt::SetCapture(_)
+            | TaskRecipeStmt::SetNoCapture(_)
+            | TaskRecipeStmt::Pool(_) => (),
+        }
+    }
+
+    fn build_stmt(&mut self, stmt: &BuildRecipeStmt) {
+        match stmt {
+            BuildRecipeStmt::Let(stmt) => self.let_stmt(stmt),
+            BuildRecipeStmt::From(stmt) => self.chain(&stmt.param),
+            BuildRecipeStmt::Depfile(stmt) => self.chain(&stmt.param),
+            BuildRecipeStmt::Run(stmt) => self.run(&stmt.param),
+            BuildRecipeStmt::RunShell(stmt) => self.string(&stmt.script),
+            BuildRecipeStmt::Env(stmt) => self.env(stmt),
+            BuildRecipeStmt::Info(stmt) => self.string(&stmt.param),
+            BuildRecipeStmt::Warn(stmt) => self.string(&stmt.param),
+            BuildRecipeStmt::EnvRemove(stmt) => self.string(&stmt.param),
+            BuildRecipeStmt::Timeout(stmt) => self.string(&stmt.param),
+            BuildRecipeStmt::InDir(stmt) => self.string(&stmt.param),
+            BuildRecipeStmt::Out(stmt) => self.string(&stmt.param),
+            BuildRecipeStmt::Container(stmt) => self.string(&stmt.param),
+            BuildRecipeStmt::Exclude(stmt) => self.pattern(&stmt.param),
+            BuildRecipeStmt::SetCapture(_)
+            | BuildRecipeStmt::SetNoCapture(_)
+            | BuildRecipeStmt::Priority(_)
+            | BuildRecipeStmt::Pool(_)
+            | BuildRecipeStmt::Host(_) => (),
+        }
+    }
+
+    fn env(&mut self, stmt: &ast::EnvStmt) {
+        self.string(&stmt.key);
+        self.string(&stmt.value);
+    }
+
+    fn run(&mut self, expr: &RunExpr) {
+        match expr {
+            RunExpr::Shell(expr) => self.string(&expr.param),
+            RunExpr::Pipeline(pipeline) => {
+                self.string(&pipeline.command.param);
+                if let Some(ref stdin) = pipeline.stdin {
+                    self.string(&stdin.path);
+                }
+                for pipe in &pipeline.pipes {
+                    self.string(&pipe.command.param);
+                }
+                if let Some(ref stdout) = pipeline.stdout {
+                    self.string(&stdout.path);
+                }
+            }
+            RunExpr::Write(expr) => {
+                self.expr(&expr.value);
+                self.expr(&expr.path);
+            }
+            RunExpr::Copy(expr) => {
+                self.string(&expr.src);
+                self.string(&expr.dest);
+            }
+            RunExpr::Extract(expr) => {
+                self.string(&expr.archive);
+                self.string(&expr.dest);
+            }
+            RunExpr::Fetch(expr) => {
+                self.string(&expr.url);
+                self.string(&expr.sha256);
+                self.string(&expr.dest);
+            }
+            RunExpr::Delete(expr) => self.expr(&expr.param),
+            RunExpr::Mkdir(expr) => self.expr(&expr.param),
+            RunExpr::Touch(expr) => self.expr(&expr.param),
+            RunExpr::Args(expr) => self.expr(&expr.param),
+            RunExpr::Env(stmt) => self.env(stmt),
+            RunExpr::EnvRemove(expr) => self.string(&expr.param),
+            RunExpr::Timeout(expr) => self.string(&expr.param),
+            RunExpr::Info(expr) => self.string(&expr.param),
+            RunExpr::Warn(expr) => self.string(&expr.param),
+            RunExpr::List(list) => {
+                for item in &list.items {
+                    self.run(&item.item);
+                }
+            }
+            RunExpr::Block(body) => {
+                for stmt in &body.statements {
+                    self.run(&stmt.statement);
+                }
+            }
+        }
+    }
+
+    fn chain(&mut self, chain: &ast::ExprChain) {
+        self.expr(&chain.expr);
+        for op in &chain.ops {
+            self.op(&op.expr);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Ident(ident) => self.ident(ident),
+            Expr::IsOverridden(expr) => self.ident(&expr.param),
+            Expr::StringExpr(string) => self.string(string),
+            Expr::Shell(expr) => self.string(&expr.param),
+            Expr::Read(expr) => self.string(&expr.param),
+            Expr::Which(expr) => self.string(&expr.param),
+            Expr::Env(expr) => self.string(&expr.param),
+            Expr::Error(expr) => self.string(&expr.param),
+            Expr::Glob(expr) => self.expr(&expr.param),
+            Expr::GlobOut(expr) => self.expr(&expr.param),
+            Expr::Hash(expr) => self.expr(&expr.param),
+            Expr::List(list) => {
+                for item in &list.items {
+                    self.chain(&item.item);
+                }
+            }
+            Expr::SubExpr(expr) => self.chain(&expr.expr),
+            Expr::Arith(expr) => {
+                self.expr(&expr.lhs);
+                self.expr(&expr.rhs);
+            }
+            Expr::If(expr) => {
+                self.condition(&expr.condition);
+                self.expr(&expr.then_expr);
+                self.expr(&expr.else_expr);
+            }
+            Expr::Int(_) | Expr::CpuCount(_) => (),
+        }
+    }
+
+    fn op(&mut self, op: &ExprOp) {
+        match op {
+            ExprOp::SubExpr(expr) => self.chain(&expr.expr),
+            ExprOp::StringExpr(string) => self.string(string),
+            ExprOp::Match(expr) => self.match_body(&expr.param),
+            ExprOp::MatchRegex(expr) => self.match_body(&expr.param),
+            ExprOp::FilterMatch(expr) => self.match_body(&expr.param),
+            ExprOp::Map(expr) => self.expr(&expr.param),
+            ExprOp::Filter(expr) => self.pattern(&expr.param),
+            ExprOp::Discard(expr) => self.pattern(&expr.param),
+            ExprOp::Split(expr) => self.pattern(&expr.param),
+            ExprOp::Join(expr) => self.string(&expr.param),
+            ExprOp::Info(expr) => self.string(&expr.param),
+            ExprOp::Warn(expr) => self.string(&expr.param),
+            ExprOp::Error(expr) => self.string(&expr.param),
+            ExprOp::AssertEq(expr) => self.expr(&expr.param),
+            ExprOp::AssertMatch(expr) => self.pattern(&expr.param),
+            ExprOp::Flatten(_)
+            | ExprOp::PathJoin(_)
+            | ExprOp::Lines(_)
+            | ExprOp::Dedup(_)
+            | ExprOp::Secret(_)
+            | ExprOp::Volatile(_) => (),
+        }
+    }
+
+    fn match_body(&mut self, body: &ast::MatchBody) {
+        for arm in body {
+            match arm.pattern {
+                MatchPattern::Pattern(ref pattern) => self.pattern(pattern),
+                MatchPattern::Regex(_) | MatchPattern::Special(_) => (),
+            }
+            if let Some(ref guard) = arm.guard {
+                self.condition(&guard.condition);
+            }
+            self.chain(&arm.expr);
+        }
+    }
+
+    fn condition(&mut self, condition: &Condition) {
+        match condition {
+            Condition::Value(expr) => self.expr(expr),
+            Condition::Compare(cmp) => {
+                self.expr(&cmp.lhs);
+                self.expr(&cmp.rhs);
+            }
+            Condition::Not(not) => self.condition(&not.param),
+            Condition::Logical(logical) => {
+                self.condition(&logical.lhs);
+                self.condition(&logical.rhs);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn rename(source: &str, from: &str, to: &str) -> String {
+        let doc = crate::parse_werk(Path::new("Werkfile"), source).unwrap();
+        doc.rename_variable(from, to).unwrap().apply().unwrap()
+    }
+
+    #[test]
+    fn rename_variable() {
+        let source = r#"# The C compiler.
+let cc = which "clang"   # trailing comment
+let ld = cc
+
+task build {
+    let cmd = "{cc} -c"
+    run "{cc:.c=.o} {ld}"
+    info "using <cc>"
+}
+
+build "%.o" {
+    from "{%}.c"
+    run [
+        "{cc*}",
+        write cc to "cc.txt",
+    ]
+}
+"#;
+        let expected = r#"# The C compiler.
+let compiler = which "clang"   # trailing comment
+let ld = compiler
+
+task build {
+    let cmd = "{compiler} -c"
+    run "{compiler:.c=.o} {ld}"
+    info "using <compiler>"
+}
+
+build "%.o" {
+    from "{%}.c"
+    run [
+        "{compiler*}",
+        write compiler to "cc.txt",
+    ]
+}
+"#;
+        assert_eq!(rename(source, "cc", "compiler"), expected);
+    }
+
+    #[test]
+    fn rename_variable_errors() {
+        let doc = crate::parse_werk(Path::new("Werkfile"), "let a = \"x\"\nlet b = a\n").unwrap();
+        assert!(matches!(
+            doc.rename_variable("a", "b"),
+            Err(EditError::IdentInUse(_))
+        ));
+        assert!(matches!(
+            doc.rename_variable("c", "d"),
+            Err(EditError::UnknownVariable(_))
+        ));
+        assert!(matches!(
+            doc.rename_variable("a", "not valid"),
+            Err(EditError::InvalidIdent(_))
+        ));
+    }
+
+    #[test]
+    fn append_statement() {
+        let doc = crate::parse_werk(Path::new("Werkfile"), "let a = \"x\"").unwrap();
+        let edits = doc.append_statement("task b { info \"{a}\" }").unwrap();
+        assert_eq!(
+            edits.apply().unwrap(),
+            "let a = \"x\"\n\ntask b { info \"{a}\" }\n"
+        );
+        assert!(matches!(
+            doc.append_statement("let a = \"x\"\nlet b = a"),
+            Err(EditError::NotOneStatement(2))
+        ));
+        assert!(matches!(
+            doc.append_statement("let = "),
+            Err(EditError::InvalidStatement(_))
+        ));
+    }
+
+    #[test]
+    fn minimal_edits() {
+        let source = "let a = \"hello\\tworld\"";
+        let doc = crate::parse_werk(Path::new("Werkfile"), source).unwrap();
+        let ast::RootStmt::Let(ref stmt) = doc.root.statements[0].statement else {
+            panic!("expected let statement");
+        };
+        let ast::Expr::StringExpr(ref string) = stmt.value.expr else {
+            panic!("expected string");
+        };
+
+        let mut edits = Edits::new(source);
+        edits.replace_string(
+            string,
+            &ast::StringExpr::literal(Span::ignore(), "hello\tthere"),
+        );
+        assert_eq!(
+            edits.edits(),
+            [TextEdit {
+                span: Span::from(16u32..21),
+                text: String::from("there"),
+            }]
+        );
+        assert_eq!(edits.apply().unwrap(), "let a = \"hello\\tthere\"");
+
+        edits.replace(Span::from(17u32..19), "x");
+        assert!(matches!(edits.apply(), Err(EditError::Overlapping(..))));
+    }
+}
@@ -69,6 +69,7 @@ impl werk_util::Diagnostic for Error {
             Failure::InvalidEscapeChar(_) => 1003,
             Failure::InvalidInterpolationOp => 1004,
             Failure::ExpectedChar(_) => 1005,
+            Failure::InvalidCharRange(..) => 1006,
             Failure::ValidRegex(_) => 100,
             Failure::ParseInt(_) => 101,
         }
@@ -119,12 +120,10 @@ impl werk_util::Diagnostic for Error {
         if let Some(ref context) = self.context {
             context
                 .iter()
-                .filter_map(|c| {
-                    if let ErrContext::Hint(h) = c.1 {
-                        Some(h.to_string())
-                    } else {
-                        None
-                    }
+                .filter_map(|c| match c.1 {
+                    ErrContext::Hint(h) => Some(h.to_string()),
+                    ErrContext::DidYouMean(_) => Some(c.1.to_string()),
+                    _ => None,
                 })
                 .collect()
         } else {
@@ -156,6 +155,8 @@ pub enum Failure {
     InvalidInterpolationOp,
     #[error("expected character {0}")]
     ExpectedChar(char),
+    #[error("invalid character range {0}-{1}")]
+    InvalidCharRange(char, char),
     #[error(transparent)]
     ValidRegex(Arc<regex::Error>),
     #[error(transparent)]
@@ -179,6 +180,8 @@ pub enum ErrContext {
     Hint(&'static str),
     #[error("{0}")]
     Note(&'static str),
+    #[error("did you mean `{0}`?")]
+    DidYouMean(&'static str),
 }
 
 pub enum ModalErr {
@@ -189,6 +192,17 @@ pub enum ModalErr {
     Error(Error),
 }
 
+impl ModalErr {
+    /// The error, whether or not the parser could backtrack.
+    #[must_use]
+    pub fn into_error(self) -> Error {
+        match self {
+            ModalErr::Backtrack(offset, fail) => Error::new(offset, fail),
+            ModalErr::Error(error) => error,
+        }
+    }
+}
+
 impl winnow::error::ParserError<Input<'_>> for ModalErr {
     type Inner = Error;
 
@@ -200,10 +214,7 @@ impl winnow::error::ParserError<Input<'_>> for ModalErr {
 
     #[inline]
     fn into_inner(self) -> Result<Self::Inner, Self> {
-        match self {
-            ModalErr::Backtrack(offset, fail) => Ok(Error::new(offset, fail)),
-            ModalErr::Error(error) => Ok(error),
-        }
+        Ok(self.into_error())
     }
 
     #[inline]
@@ -0,0 +1,343 @@
+//! A tokenizer for Werkfiles, separate from the parser. It never fails, so
+//! tools like syntax highlighters can work on source code that does not parse.
+
+use crate::{
+    ast::keyword::{self, Keyword as _},
+    parser::Span,
+};
+
+/// Words that are keywords somewhere in the language. The lexer has no
+/// context, so these are always `TokenKind::Keyword`, even where the parser
+/// accepts them as identifiers, like the name of a task.
+const KEYWORDS: &[&str] = &[
+    keyword::Let::TOKEN,
+    keyword::Config::TOKEN,
+    keyword::Import::TOKEN,
+    keyword::As::TOKEN,
+    keyword::Member::TOKEN,
+    keyword::Profile::TOKEN,
+    keyword::Build::TOKEN,
+    keyword::Task::TOKEN,
+    keyword::Shell::TOKEN,
+    keyword::Glob::TOKEN,
+    keyword::GlobOut::TOKEN,
+    keyword::Which::TOKEN,
+    keyword::Env::TOKEN,
+    keyword::IsOverridden::TOKEN,
+    keyword::Join::TOKEN,
+    keyword::PathJoin::TOKEN,
+    keyword::Then::TOKEN,
+    keyword::Else::TOKEN,
+    keyword::And::TOKEN,
+    keyword::Or::TOKEN,
+    keyword::Not::TOKEN,
+    keyword::Info::TOKEN,
+    keyword::Warn::TOKEN,
+    keyword::Error::TOKEN,
+    keyword::Match::TOKEN,
+    keyword::MatchRegex::TOKEN,
+    keyword::Write::TOKEN,
+    keyword::Read::TOKEN,
+    keyword::Hash::TOKEN,
+    keyword::Run::TOKEN,
+    keyword::RunShell::TOKEN,
+    keyword::Copy::TOKEN,
+    keyword::Fetch::TOKEN,
+    keyword::Sha256::TOKEN,
+    keyword::Extract::TOKEN,
+    keyword::Delete::TOKEN,
+    keyword::Mkdir::TOKEN,
+    keyword::Touch::TOKEN,
+    keyword::Args::TOKEN,
+    keyword::CpuCount::TOKEN,
+    keyword::If::TOKEN,
+    keyword::Underscore::TOKEN,
+    keyword::From::TOKEN,
+    keyword::Depfile::TOKEN,
+    keyword::False::TOKEN,
+    keyword::True::TOKEN,
+    keyword::To::TOKEN,
+    keyword::Map::TOKEN,
+    keyword::Flatten::TOKEN,
+    keyword::Filter::TOKEN,
+    keyword::FilterMatch::TOKEN,
+    keyword::Discard::TOKEN,
+    keyword::Split::TOKEN,
+    keyword::Dedup::TOKEN,
+    keyword::Lines::TOKEN,
+    keyword::Secret::TOKEN,
+    keyword::Volatile::TOKEN,
+    keyword::AssertEq::TOKEN,
+    keyword::SetCapture::TOKEN,
+    keyword::SetNoCapture::TOKEN,
+    keyword::Priority::TOKEN,
+    keyword::Timeout::TOKEN,
+    keyword::Pool::TOKEN,
+    keyword::Host::TOKEN,
+    keyword::Container::TOKEN,
+    keyword::Out::TOKEN,
+    keyword::Exclude::TOKEN,
+    keyword::InDir::TOKEN,
+    keyword::RemoveEnv::TOKEN,
+];
+
+/// Operators of more than one character.
+const OPERATORS: &[&str] = &["=>", "==", "!=", "<=", ">=", ".."];
+
+const PUNCTUATION: &[char] = &[
+    ':', '=', ',', ';', '{', '}', '(', ')', '[', ']', '<', '>', '%', '|', '+', '-', '*', '/', '#',
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// Spaces, tabs, and newlines.
+    Whitespace,
+    /// `# ...`, until the end of the line.
+    Comment,
+    Keyword,
+    Ident,
+    Int,
+    /// A string, pattern, or regex literal, including the quotes and any
+    /// interpolations.
+    String,
+    /// An operator or delimiter, like `=`, `=>`, `{`, or the `#` of an
+    /// attribute.
+    Punct,
+    /// A string without a closing quote, until the end of the line, or a
+    /// character that can't appear outside of a string.
+    Error,
+}
+
+/// A token and its location in the source code.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lexeme {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Iterator over the tokens of a Werkfile. The tokens cover the whole source
+/// code without gaps, including whitespace and comments.
+#[derive(Clone, Debug)]
+pub struct Lexer<'a> {
+    source: &'a str,
+    offset: usize,
+}
+
+impl<'a> Lexer<'a> {
+    #[must_use]
+    pub fn new(source: &'a str) -> Self {
+        Self { source, offset: 0 }
+    }
+
+    #[must_use]
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// The text of a token produced by this lexer.
+    #[must_use]
+    pub fn text(&self, lexeme: Lexeme) -> &'a str {
+        &self.source[std::ops::Range::<usize>::from(lexeme.span)]
+    }
+
+    /// The kind and length of the token at the start of `rest`.
+    fn next_token(rest: &str) -> (TokenKind, usize) {
+        let Some(first) = rest.chars().next() else {
+            unreachable!("no token at the end of the source");
+        };
+
+        match first {
+            ' ' | '\t' | '\r' | '\n' => (
+                TokenKind::Whitespace,
+                rest.find(|c| !matches!(c, ' ' | '\t' | '\r' | '\n'))
+                    .unwrap_or(rest.len()),
+            ),
+            // `#[` starts an attribute, not a comment.
+            '#' if !rest.starts_with("#[") => {
+                (TokenKind::Comment, rest.find('\n').unwrap_or(rest.len()))
+            }
+            '"' => string_literal(rest),
+            '0'..='9' => {
+                let len = word_len(rest);
+                if rest[..len].bytes().all(|b| b.is_ascii_digit()) {
+                    (TokenKind::Int, len)
+                } else {
+                    (TokenKind::Error, len)
+                }
+            }
+            c if c == '_' || unicode_ident::is_xid_start(c) => {
+                let len = word_len(rest);
+                if KEYWORDS.contains(&&rest[..len]) {
+                    (TokenKind::Keyword, len)
+                } else {
+                    (TokenKind::Ident, len)
+                }
+            }
+            c => {
+                if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+                    (TokenKind::Punct, op.len())
+                } else if PUNCTUATION.contains(&c) {
+                    (TokenKind::Punct, c.len_utf8())
+                } else {
+                    (TokenKind::Error, c.len_utf8())
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Lexeme;
+
+    fn next(&mut self) -> Option<Lexeme> {
+        let rest = &self.source[self.offset..];
+        if rest.is_empty() {
+            return None;
+        }
+        let (kind, len) = Self::next_token(rest);
+        let start = self.offset;
+        self.offset += len;
+        Some(Lexeme {
+            kind,
+            span: Span::from(start..self.offset),
+        })
+    }
+}
+
+impl std::iter::FusedIterator for Lexer<'_> {}
+
+/// The length of an identifier, keyword, or integer, which may contain `-`.
+fn word_len(rest: &str) -> usize {
+    rest.find(|c: char| c != '-' && c != '_' && !unicode_ident::is_xid_continue(c))
+        .unwrap_or(rest.len())
+}
+
+/// The length of a quoted literal. Interpolations may contain quoted strings
+/// of their own, like `{files:join=", "}`.
+fn string_literal(rest: &str) -> (TokenKind, usize) {
+    let mut depth = 0usize;
+    let mut in_interpolation_string = false;
+    let mut chars = rest.char_indices().skip(1);
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '"' if in_interpolation_string => in_interpolation_string = false,
+            '"' if depth > 0 => in_interpolation_string = true,
+            '"' => return (TokenKind::String, index + 1),
+            _ if in_interpolation_string => (),
+            '{' | '<' => depth += 1,
+            '}' | '>' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+    (TokenKind::Error, rest.find('\n').unwrap_or(rest.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(source: &str) -> Vec<(TokenKind, &str)> {
+        let lexer = Lexer::new(source);
+        lexer
+            .clone()
+            .map(|lexeme| (lexeme.kind, lexer.text(lexeme)))
+            .collect()
+    }
+
+    #[test]
+    fn lex() {
+        use TokenKind::*;
+        assert_eq!(
+            tokens("#[pool = \"x\"]\nlet cc-flags = [\"{in:join=\\\", \\\"}\", 12] # flags\n"),
+            [
+                (Punct, "#"),
+                (Punct, "["),
+                (Keyword, "pool"),
+                (Whitespace, " "),
+                (Punct, "="),
+                (Whitespace, " "),
+                (String, "\"x\""),
+                (Punct, "]"),
+                (Whitespace, "\n"),
+                (Keyword, "let"),
+                (Whitespace, " "),
+                (Ident, "cc-flags"),
+                (Whitespace, " "),
+                (Punct, "="),
+                (Whitespace, " "),
+                (Punct, "["),
+                (String, "\"{in:join=\\\", \\\"}\""),
+                (Punct, ","),
+                (Whitespace, " "),
+                (Int, "12"),
+                (Punct, "]"),
+                (Whitespace, " "),
+                (Comment, "# flags"),
+                (Whitespace, "\n"),
+            ]
+        );
+        assert_eq!(
+            tokens("\"{x:join=\", \"}\" => [..]"),
+            [
+                (String, "\"{x:join=\", \"}\""),
+                (Whitespace, " "),
+                (Punct, "=>"),
+                (Whitespace, " "),
+                (Punct, "["),
+                (Punct, ".."),
+                (Punct, "]"),
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_examples() {
+        let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/../examples");
+        for entry in std::fs::read_dir(examples).unwrap() {
+            let path = entry.unwrap().path().join("Werkfile");
+            let Ok(source) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let lexer = Lexer::new(&source);
+            for lexeme in lexer.clone() {
+                assert_ne!(
+                    lexeme.kind,
+                    TokenKind::Error,
+                    "{}: {:?}",
+                    path.display(),
+                    lexer.text(lexeme)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lex_errors() {
+        use TokenKind::*;
+        assert_eq!(
+            tokens("let x = \"abc\nlet y = 1a @"),
+            [
+                (Keyword, "let"),
+                (Whitespace, " "),
+                (Ident, "x"),
+                (Whitespace, " "),
+                (Punct, "="),
+                (Whitespace, " "),
+                (Error, "\"abc"),
+                (Whitespace, "\n"),
+                (Keyword, "let"),
+                (Whitespace, " "),
+                (Ident, "y"),
+                (Whitespace, " "),
+                (Punct, "="),
+                (Whitespace, " "),
+                (Error, "1a"),
+                (Whitespace, " "),
+                (Error, "@"),
+            ]
+        );
+    }
+}
@@ -2,13 +2,20 @@
 
 pub mod ast;
 mod document;
+mod edit;
 mod error;
+mod lexer;
 pub mod parser;
 mod pattern;
 mod semantic_hash;
 
 pub use document::*;
+pub use edit::*;
 pub use error::*;
-pub use parser::{parse_werk, parse_werk_with_diagnostics};
+pub use lexer::*;
+pub use parser::{
+    parse_expr, parse_werk, parse_werk_recovering, parse_werk_with_diagnostics, reparse_werk,
+};
 pub use pattern::*;
 pub use semantic_hash::*;
+pub use werk_util::{LineColumn, LineIndex};
@@ -3,7 +3,9 @@ use std::marker::PhantomData;
 use werk_util::Diagnostic as _;
 use winnow::{
     ascii::{line_ending, till_line_ending},
-    combinator::{alt, cut_err, delimited, empty, eof, opt, peek, preceded, repeat, seq},
+    combinator::{
+        alt, cut_err, delimited, empty, eof, not, opt, peek, preceded, repeat, seq, terminated,
+    },
     error::AddContext as _,
     stream::{Location, Stream as _},
     token::{any, none_of, one_of, take_while},
@@ -15,9 +17,11 @@ use crate::{
     fatal, ErrContext, Error, Failure, ModalErr,
 };
 
+mod reparse;
 mod span;
 mod string;
 
+pub use reparse::*;
 pub use span::*;
 pub use string::*;
 
@@ -99,10 +103,48 @@ pub fn parse_werk<'a>(
     origin: &'a std::path::Path,
     source_code: &'a str,
 ) -> Result<crate::Document<'a>, crate::Error> {
-    let root = root
+    let (document, errors) = parse_werk_recovering(origin, source_code);
+    match errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(document),
+    }
+}
+
+/// Parse a Werkfile, recovering from syntax errors by skipping to the next
+/// line that starts a root statement. Returns the statements that could be
+/// parsed, along with all syntax errors in source order.
+#[must_use]
+pub fn parse_werk_recovering<'a>(
+    origin: &'a std::path::Path,
+    source_code: &'a str,
+) -> (crate::Document<'a>, Vec<crate::Error>) {
+    let mut errors = Vec::new();
+    let root = (|input: &mut Input<'a>| root(input, &mut errors))
         .parse(Input::new(source_code))
-        .map_err(winnow::error::ParseError::into_inner)?;
-    Ok(crate::Document::new(root, origin, source_code, None))
+        .unwrap_or_else(|err| {
+            errors.push(err.into_inner());
+            ast::Root::default()
+        });
+    let mut document = crate::Document::new(root, origin, source_code, None);
+    document.complete = errors.is_empty();
+    (document, errors)
+}
+
+/// Parse a single expression, like the value of a `let` statement, surrounded
+/// by optional whitespace. This is used by `werk eval`.
+pub fn parse_expr(source: &str) -> Result<ast::ExprChain<'_>, Error> {
+    delimited(
+        whitespace,
+        parse,
+        (whitespace, cut_err(eof).expect(&"end of expression")),
+    )
+    .parse(Input::new(source))
+    .map_err(winnow::error::ParseError::into_inner)
+}
+
+/// True if the string can be used as the name of a variable.
+pub(crate) fn is_ident(source: &str) -> bool {
+    (parse::<ast::Ident>, eof).parse(Input::new(source)).is_ok()
 }
 
 pub fn parse_werk_with_diagnostics<'a>(
@@ -117,15 +159,49 @@ pub fn parse_werk_with_diagnostics<'a>(
     })
 }
 
-fn root<'a>(input: &mut Input<'a>) -> PResult<ast::Root<'a>> {
+fn root<'a>(input: &mut Input<'a>, errors: &mut Vec<Error>) -> PResult<ast::Root<'a>> {
+    let recover = |input: &mut Input<'a>, err: ModalErr| {
+        errors.push(err.into_error());
+        skip_to_next_root_stmt(input);
+        Ok(())
+    };
     let ((), statements, decor_trailing, _) =
-        statements_delimited(empty, parse, peek(eof)).parse_next(input)?;
+        statements_delimited(empty, parse, peek(eof), recover).parse_next(input)?;
     Ok(ast::Root {
         statements,
         ws_trailing: decor_trailing,
     })
 }
 
+/// Skip the rest of the current line, and all following lines until one that
+/// looks like the start of a root statement: a keyword or an attribute at the
+/// start of the line. The input is left before the line ending, so the
+/// statement separator is still there.
+fn skip_to_next_root_stmt(input: &mut Input) {
+    let rest = input.peek_finish();
+    let mut end = rest.len();
+    let mut line_end = 0;
+    while let Some(newline) = rest[line_end..].find('\n') {
+        let next_line = &rest[line_end + newline + 1..];
+        line_end += newline;
+        if next_line.starts_with(|c: char| c.is_ascii_alphabetic()) || next_line.starts_with("#[") {
+            end = rest[..line_end]
+                .strip_suffix('\r')
+                .map_or(line_end, str::len);
+            break;
+        }
+        line_end += 1;
+    }
+    input.next_slice(end);
+}
+
+/// Recovery function for [`statements_delimited`] in bodies, where errors are
+/// not recovered from.
+#[allow(clippy::needless_pass_by_value)]
+fn no_recovery(_input: &mut Input, err: ModalErr) -> PResult<()> {
+    Err(err)
+}
+
 #[allow(clippy::unnecessary_wraps)]
 fn default<T: Default>(_input: &mut Input) -> PResult<T> {
     Ok(Default::default())
@@ -145,15 +221,22 @@ fn default<T: Default>(_input: &mut Input) -> PResult<T> {
 ///
 /// Each statement is separated by a statement separator: one or more newlines
 /// (including comments), or semicolons.
-fn statements_delimited<'a, Open, Item, Close, OpenParser, ParseNextItem, CloseParser>(
+///
+/// When a statement or separator fails to parse, `recover` is called with the
+/// error. It can either return the error, or skip past the statement and
+/// return `Ok`, in which case the statement is left out and parsing continues
+/// after the skipped input.
+fn statements_delimited<'a, Open, Item, Close, OpenParser, ParseNextItem, CloseParser, Recover>(
     mut initial: OpenParser,
     parse_next: ParseNextItem,
     mut terminal: CloseParser,
+    mut recover: Recover,
 ) -> impl Parser<'a, (Open, Vec<ast::BodyStmt<Item>>, ast::Whitespace, Close)>
 where
     OpenParser: Parser<'a, Open>,
     CloseParser: Parser<'a, Close>,
     ParseNextItem: Parser<'a, Item>,
+    Recover: FnMut(&mut Input<'a>, ModalErr) -> PResult<()>,
 {
     let mut parse_next = parse_next;
 
@@ -165,7 +248,7 @@ where
         let open = initial.parse_next(input)?;
         // Ignore all whitespace (including comments) until the first statement
         // or the terminator.
-        let mut last_decor = whitespace_parsed.parse_next(input)?;
+        let mut last_decor = statement_whitespace_parsed.parse_next(input)?;
 
         loop {
             if let Ok(close) = terminal.parse_next(input) {
@@ -173,16 +256,28 @@ where
             }
 
             if !has_separator {
-                return Err(ModalErr::Error(Error::new(Offset(input.previous_token_end() as u32),
-                    Failure::Expected(&"semicolon or newline before next statement"))));
+                recover(input, ModalErr::Error(Error::new(Offset(input.previous_token_end() as u32),
+                    Failure::Expected(&"semicolon or newline before next statement"))))?;
+                last_decor = statement_whitespace_parsed.parse_next(input)?;
+                has_separator = true;
+                continue;
             }
 
-            let item = parse_next.parse_next(input)?;
+            let start = input.checkpoint();
+            let item = match parse_next.parse_next(input) {
+                Ok(item) => item,
+                Err(err) => {
+                    input.reset(&start);
+                    recover(input, err)?;
+                    last_decor = statement_whitespace_parsed.parse_next(input)?;
+                    continue;
+                }
+            };
             let preceding_whitespace = last_decor;
             let trailing;
 
-            let whitespace_before_semicolon = whitespace_parsed.parse_next(input)?;
-            let semicolon_and_whitespace = opt((parse, whitespace_parsed)).parse_next(input)?;
+            let whitespace_before_semicolon = statement_whitespace_parsed.parse_next(input)?;
+            let semicolon_and_whitespace = opt((parse, statement_whitespace_parsed)).parse_next(input)?;
 
             if let Some((semicolon, whitespace_after_semicolon)) = semicolon_and_whitespace {
                 // All whitespace before the semicolon is trailing for the item we just found.
@@ -215,7 +310,7 @@ where
 impl<'a, T: Parse<'a>> Parse<'a> for ast::Body<T> {
     fn parse(input: &mut Input<'a>) -> PResult<Self> {
         let (token_open, statements, decor_trailing, token_close) =
-            statements_delimited(parse, parse, parse).parse_next(input)?;
+            statements_delimited(parse, parse, parse, no_recovery).parse_next(input)?;
 
         Ok(ast::Body {
             token_open,
@@ -230,11 +325,15 @@ impl<'a> Parse<'a> for ast::RootStmt<'a> {
     fn parse(input: &mut Input<'a>) -> PResult<Self> {
         alt((
             parse.map(ast::RootStmt::Config),
+            parse.map(ast::RootStmt::Import),
+            parse.map(ast::RootStmt::Member),
+            parse.map(ast::RootStmt::Profile),
             parse.map(ast::RootStmt::Let),
             parse.map(ast::RootStmt::Task),
             parse.map(ast::RootStmt::Build),
-            fatal(Failure::Expected(&"statement"))
-                .help("one of `config`, `let`, `task`, or `build`"),
+            misplaced_attributes(&"`let`, `task`, or `build` after attributes"),
+            unknown_keyword(&"statement", ROOT_KEYWORDS)
+                .help("one of `config`, `import`, `member`, `profile`, `let`, `task`, or `build`"),
         ))
         .parse_next(input)
     }
@@ -246,7 +345,7 @@ impl<'a> Parse<'a> for ast::ConfigStmt<'a> {
             span: default,
             token_config: parse,
             ws_1: whitespace,
-            ident: cut_err(parse).help("`config` must be followed by an identifier"),
+            ident: cut_err(ident_path).help("`config` must be followed by an identifier"),
             ws_2: whitespace,
             token_eq: cut_err(parse).help("`config` statements look like this: config ident = ..."),
             ws_3: whitespace,
@@ -256,56 +355,290 @@ impl<'a> Parse<'a> for ast::ConfigStmt<'a> {
         .while_parsing("`config` statement")
         .parse_next(input)?;
         config.span = span;
+        check_config_value(&config)?;
+        Ok(config)
+    }
+}
 
-        let value_start = config.value.span().start;
-
-        match config.ident.ident.as_str() {
-            "print-commands" => {
-                if !matches!(config.value, ast::ConfigValue::Bool(_)) {
-                    return Err(ModalErr::Error(Error::new(
-                        value_start,
-                        Failure::Expected(&"boolean value for `print-commands`"),
-                    )));
-                }
+/// Config keys, for suggestions when an unknown key is encountered.
+const CONFIG_KEYS: &[&str] = &[
+    "out-dir",
+    "edition",
+    "print-commands",
+    "echo-template",
+    "terminal-progress",
+    "default",
+    "profile",
+    "strict",
+    "shell",
+    "source-dirs",
+    "tool-paths",
+    "create-dirs",
+    "reproducible",
+    "hash-algorithm",
+    "glob-ignore",
+    "container-runtime",
+    "container-pull",
+];
+
+/// Check that the value of a `config` statement has the type expected by its
+/// key.
+#[allow(clippy::too_many_lines)]
+fn check_config_value(config: &ast::ConfigStmt) -> PResult<()> {
+    let value_start = config.value.span().start;
+
+    match config.ident.ident.as_str() {
+        "print-commands" => {
+            if !matches!(config.value, ast::ConfigValue::Bool(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"boolean value for `print-commands`"),
+                )));
             }
-            "edition" => {
-                if !matches!(config.value, ast::ConfigValue::String(_)) {
-                    return Err(ModalErr::Error(Error::new(
-                        value_start,
-                        Failure::Expected(&"string literal for `edition`"),
-                    )));
-                }
+        }
+        "echo-template" => {
+            if !matches!(config.value, ast::ConfigValue::String(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"string literal for `echo-template`"),
+                )));
             }
-            "out-dir" | "output-directory" => {
-                if !matches!(config.value, ast::ConfigValue::String(_)) {
-                    return Err(ModalErr::Error(Error::new(
-                        value_start,
-                        Failure::Expected(&"string literal for `out-dir`"),
-                    )));
-                }
+        }
+        "terminal-progress" => {
+            if !matches!(config.value, ast::ConfigValue::Bool(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"boolean value for `terminal-progress`"),
+                )));
             }
-            "default" | "default-target" => {
-                if !matches!(config.value, ast::ConfigValue::String(_)) {
-                    return Err(ModalErr::Error(Error::new(
-                        value_start,
-                        Failure::Expected(&"string literal for `default`"),
-                    )));
-                }
+        }
+        "edition" => {
+            if !matches!(config.value, ast::ConfigValue::String(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"string literal for `edition`"),
+                )));
+            }
+        }
+        "out-dir" | "output-directory" => {
+            if !matches!(config.value, ast::ConfigValue::String(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"string literal for `out-dir`"),
+                )));
+            }
+        }
+        "default" | "default-target" => {
+            if !matches!(config.value, ast::ConfigValue::String(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"string literal for `default`"),
+                )));
+            }
+        }
+        "shell" => {
+            if !matches!(config.value, ast::ConfigValue::String(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"string literal for `shell`"),
+                )));
+            }
+        }
+        "source-dirs" => {
+            if !matches!(config.value, ast::ConfigValue::List(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"list of string literals for `source-dirs`"),
+                )));
+            }
+        }
+        "profile" => {
+            if !matches!(config.value, ast::ConfigValue::String(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"string literal for `profile`"),
+                )));
+            }
+        }
+        "strict" => {
+            if !matches!(config.value, ast::ConfigValue::Bool(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"boolean value for `strict`"),
+                )));
+            }
+        }
+        "tool-paths" => {
+            if !matches!(config.value, ast::ConfigValue::List(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"list of string literals for `tool-paths`"),
+                )));
+            }
+        }
+        "create-dirs" => {
+            if !matches!(config.value, ast::ConfigValue::Bool(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"boolean value for `create-dirs`"),
+                )));
+            }
+        }
+        "reproducible" => {
+            if !matches!(config.value, ast::ConfigValue::Bool(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"boolean value for `reproducible`"),
+                )));
+            }
+        }
+        "hash-algorithm" => {
+            if !matches!(config.value, ast::ConfigValue::String(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"string literal for `hash-algorithm`"),
+                )));
             }
-            _ => {
+        }
+        "glob-ignore" => {
+            if !matches!(config.value, ast::ConfigValue::String(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"string literal for `glob-ignore`"),
+                )));
+            }
+        }
+        "container-runtime" => {
+            if !matches!(config.value, ast::ConfigValue::String(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"string literal for `container-runtime`"),
+                )));
+            }
+        }
+        "container-pull" => {
+            if !matches!(config.value, ast::ConfigValue::String(_)) {
                 return Err(ModalErr::Error(Error::new(
-                    config.ident.span.start,
-                    Failure::Expected(
-                        &"config key, one of `out-dir`, `edition`, `print-commands`, or `default`",
-                    ),
-                )))
+                    value_start,
+                    Failure::Expected(&"string literal for `container-pull`"),
+                )));
+            }
+        }
+        key if key.starts_with("host.") => {
+            if !matches!(config.value, ast::ConfigValue::String(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"string literal for `host.<name>`"),
+                )));
             }
         }
+        key if key.starts_with("pool.") => {
+            if !matches!(config.value, ast::ConfigValue::Int(_)) {
+                return Err(ModalErr::Error(Error::new(
+                    value_start,
+                    Failure::Expected(&"integer value for `pool.<name>`"),
+                )));
+            }
+        }
+        key => return Err(ModalErr::Error(unknown_config_key(config, key))),
+    }
 
-        Ok(config)
+    Ok(())
+}
+
+fn unknown_config_key(config: &ast::ConfigStmt, key: &str) -> Error {
+    let mut error = Error::new(
+        config.ident.span.start,
+        Failure::Expected(
+            &"config key, one of `out-dir`, `edition`, `print-commands`, `echo-template`, `terminal-progress`, `default`, `profile`, `strict`, `shell`, `source-dirs`, `tool-paths`, `create-dirs`, `reproducible`, `hash-algorithm`, `glob-ignore`, `container-runtime`, `container-pull`, `pool.<name>`, or `host.<name>`",
+        ),
+    );
+    if let Some(suggestion) = werk_util::did_you_mean(key, CONFIG_KEYS).copied() {
+        error.push(config.ident.span.start, ErrContext::DidYouMean(suggestion));
+    }
+    error
+}
+
+impl<'a> Parse<'a> for ast::ImportStmt<'a> {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        let (mut import, span) = seq! {ast::ImportStmt {
+            span: default,
+            token_import: parse,
+            ws_1: whitespace,
+            path: cut_err(escaped_string.with_token_span())
+                .map(|(path, span)| ast::ConfigString(span, path.into()))
+                .expect(&"string literal")
+                .help("`import` must be followed by the path to a Werkfile"),
+            ws_2: whitespace,
+            token_as: cut_err(parse).help("imports look like this: import \"lib.werk\" as lib"),
+            ws_3: whitespace,
+            ident: cut_err(parse).help("`as` must be followed by an identifier"),
+        }}
+        .with_token_span()
+        .while_parsing("`import` statement")
+        .parse_next(input)?;
+        import.span = span;
+        Ok(import)
     }
 }
 
+impl<'a> Parse<'a> for ast::MemberStmt<'a> {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        let (mut member, span) = seq! {ast::MemberStmt {
+            span: default,
+            token_member: parse,
+            ws_1: whitespace,
+            path: cut_err(escaped_string.with_token_span())
+                .map(|(path, span)| ast::ConfigString(span, path.into()))
+                .expect(&"string literal")
+                .help("`member` must be followed by the directory of a workspace member"),
+        }}
+        .with_token_span()
+        .while_parsing("`member` statement")
+        .parse_next(input)?;
+        member.span = span;
+        Ok(member)
+    }
+}
+
+impl<'a> Parse<'a> for ast::ProfileStmt<'a> {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        let (mut profile, span) = seq! {ast::ProfileStmt {
+            span: default,
+            token_profile: parse,
+            ws_1: whitespace,
+            name: cut_err(parse).help("`profile` must be followed by an identifier"),
+            ws_2: whitespace,
+            body: cut_err(profile_body),
+        }}
+        .with_token_span()
+        .while_parsing("`profile` statement")
+        .parse_next(input)?;
+        profile.span = span;
+        Ok(profile)
+    }
+}
+
+fn profile_body<'a>(input: &mut Input<'a>) -> PResult<ast::Body<ast::LetStmt<'a>>> {
+    let (token_open, statements, ws_trailing, token_close) = statements_delimited(
+        parse,
+        alt((
+            parse,
+            fatal(Failure::Expected(&"`let` statement"))
+                .help("profiles can only contain `let` statements"),
+        )),
+        parse,
+        no_recovery,
+    )
+    .parse_next(input)?;
+    Ok(ast::Body {
+        token_open,
+        statements,
+        ws_trailing,
+        token_close,
+    })
+}
+
 impl<'a> Parse<'a> for ast::ConfigBool {
     fn parse(input: &mut Input<'a>) -> PResult<Self> {
         let (value, span) = alt((
@@ -318,32 +651,156 @@ impl<'a> Parse<'a> for ast::ConfigBool {
     }
 }
 
+impl<'a> Parse<'a> for ast::ConfigString<'a> {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        let (value, span) = escaped_string.with_token_span().parse_next(input)?;
+        Ok(ast::ConfigString(span, value.into()))
+    }
+}
+
+impl<'a> Parse<'a> for ast::ConfigInt {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        let (value, span) = (opt('-'), take_while(1.., '0'..='9'))
+            .take()
+            .try_map(str::parse)
+            .with_token_span()
+            .expect(&"integer")
+            .parse_next(input)?;
+        Ok(ast::ConfigInt(span, value))
+    }
+}
+
 impl<'a> Parse<'a> for ast::ConfigValue<'a> {
     fn parse(input: &mut Input<'a>) -> PResult<Self> {
         alt((
             parse.map(ast::ConfigValue::Bool),
+            parse.map(ast::ConfigValue::Int),
             escaped_string.with_token_span().map(|(string, span)| {
                 ast::ConfigValue::String(ast::ConfigString(span, string.into()))
             }),
+            parse.map(ast::ConfigValue::List),
         ))
-        .expect(&"string literal or boolean value")
+        .expect(&"string literal, integer, boolean value, or list of string literals")
         .parse_next(input)
     }
 }
 
+impl<'a> Parse<'a> for ast::Attribute<'a> {
+    /// `#[name]` or `#[name = value]`
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        let (mut attribute, span) = seq! { ast::Attribute {
+            _: ("#", parse::<token::BracketOpen>, whitespace),
+            span: default,
+            name: cut_err(parse).help("attributes look like this: #[name] or #[name = value]"),
+            _: whitespace,
+            value: opt(preceded((parse::<token::Eq>, whitespace), cut_err(parse))),
+            _: (whitespace, cut_err(parse::<token::BracketClose>)),
+            ws_trailing: default,
+        }}
+        .with_token_span()
+        .while_parsing("attribute")
+        .parse_next(input)?;
+        attribute.span = span;
+        attribute.ws_trailing = attribute_whitespace_parsed
+            .map(ParsedWhitespace::into_whitespace)
+            .parse_next(input)?;
+        Ok(attribute)
+    }
+}
+
+/// Attributes preceding a recipe, `let` statement, or `run` statement.
+fn attributes<'a>(input: &mut Input<'a>) -> PResult<Vec<ast::Attribute<'a>>> {
+    repeat(0.., parse::<ast::Attribute>).parse_next(input)
+}
+
+/// Attributes that are not followed by an item that accepts attributes.
+fn misplaced_attributes<'a, O>(expected: &'static &'static str) -> impl Parser<'a, O> {
+    preceded(
+        (parse::<ast::Attribute>, attributes),
+        fatal(Failure::Expected(expected)),
+    )
+}
+
+const ROOT_KEYWORDS: &[&str] = &[
+    "config", "import", "member", "profile", "let", "task", "build",
+];
+
+const TASK_RECIPE_KEYWORDS: &[&str] = &[
+    "let",
+    "build",
+    "run",
+    "run-shell",
+    "env",
+    "env-remove",
+    "info",
+    "warn",
+    "capture",
+    "no-capture",
+    "timeout",
+    "pool",
+    "in-dir",
+];
+
+const BUILD_RECIPE_KEYWORDS: &[&str] = &[
+    "from",
+    "let",
+    "depfile",
+    "run",
+    "run-shell",
+    "env",
+    "env-remove",
+    "info",
+    "warn",
+    "capture",
+    "no-capture",
+    "priority",
+    "timeout",
+    "pool",
+    "host",
+    "container",
+    "out",
+    "exclude",
+    "in-dir",
+];
+
+/// Fail with `expected`, suggesting one of `keywords` if the input starts with
+/// a word that looks like a misspelling of one of them.
+fn unknown_keyword<'a, O>(
+    expected: &'static &'static str,
+    keywords: &'static [&'static str],
+) -> impl Parser<'a, O> {
+    move |input: &mut Input<'a>| {
+        let offset = Offset(input.current_token_start() as u32);
+        let rest = input.peek_finish();
+        let word_len = rest
+            .find(|c: char| !(c == '-' || c.is_alphanumeric()))
+            .unwrap_or(rest.len());
+        let mut error = Error::new(offset, Failure::Expected(expected));
+        if let Some(suggestion) = werk_util::did_you_mean(&rest[..word_len], keywords).copied() {
+            error.push(offset, ErrContext::DidYouMean(suggestion));
+        }
+        Err(ModalErr::Error(error))
+    }
+}
+
 impl<'a> Parse<'a> for ast::TaskRecipeStmt<'a> {
     fn parse(input: &mut Input<'a>) -> PResult<Self> {
         alt((
             parse.map(ast::TaskRecipeStmt::Let),
             parse.map(ast::TaskRecipeStmt::Build),
             parse.map(ast::TaskRecipeStmt::Run),
+            parse.map(ast::TaskRecipeStmt::RunShell),
             parse.map(ast::TaskRecipeStmt::EnvRemove),
             parse.map(ast::TaskRecipeStmt::Env),
             parse.map(ast::TaskRecipeStmt::Info),
             parse.map(ast::TaskRecipeStmt::Warn),
             parse.map(ast::TaskRecipeStmt::SetCapture),
             parse.map(ast::TaskRecipeStmt::SetNoCapture),
-            fatal(Failure::Expected(&"task recipe statement")).help(
+            parse.map(ast::TaskRecipeStmt::Timeout),
+            parse.map(ast::TaskRecipeStmt::Pool),
+            parse.map(ast::TaskRecipeStmt::InDir),
+            misplaced_attributes(&"`run` or `run-shell` statement after attributes"),
+            unknown_keyword(&"task recipe statement", TASK_RECIPE_KEYWORDS).help(
                 "could be one of `let`, `from`, `build`, `depfile`, `run`, or `echo` statement",
             ),
         ))
@@ -355,6 +812,7 @@ impl<'a> Parse<'a> for ast::CommandRecipe<'a> {
     fn parse(input: &mut Input<'a>) -> PResult<Self> {
         let (mut recipe, span) = seq! { ast::CommandRecipe {
             span: default,
+            attributes: attributes,
             token_task: parse,
             ws_1: whitespace,
             name: cut_err(parse).help(
@@ -378,13 +836,23 @@ impl<'a> Parse<'a> for ast::BuildRecipeStmt<'a> {
             parse.map(ast::BuildRecipeStmt::Let),
             parse.map(ast::BuildRecipeStmt::Depfile),
             parse.map(ast::BuildRecipeStmt::Run),
+            parse.map(ast::BuildRecipeStmt::RunShell),
             parse.map(ast::BuildRecipeStmt::EnvRemove),
             parse.map(ast::BuildRecipeStmt::Env),
             parse.map(ast::BuildRecipeStmt::Info),
             parse.map(ast::BuildRecipeStmt::Warn),
             parse.map(ast::BuildRecipeStmt::SetCapture),
             parse.map(ast::BuildRecipeStmt::SetNoCapture),
-            fatal(Failure::Expected(&"build recipe statement")).help(
+            parse.map(ast::BuildRecipeStmt::Priority),
+            parse.map(ast::BuildRecipeStmt::Timeout),
+            parse.map(ast::BuildRecipeStmt::Pool),
+            parse.map(ast::BuildRecipeStmt::Host),
+            parse.map(ast::BuildRecipeStmt::Container),
+            parse.map(ast::BuildRecipeStmt::Out),
+            parse.map(ast::BuildRecipeStmt::Exclude),
+            parse.map(ast::BuildRecipeStmt::InDir),
+            misplaced_attributes(&"`run` or `run-shell` statement after attributes"),
+            unknown_keyword(&"build recipe statement", BUILD_RECIPE_KEYWORDS).help(
                 "could be one of `let`, `from`, `build`, `depfile`, `run`, or `echo` statement",
             ),
         ))
@@ -396,6 +864,7 @@ impl<'a> Parse<'a> for ast::BuildRecipe<'a> {
     fn parse(input: &mut Input<'a>) -> PResult<Self> {
         let (mut recipe, span) = seq! { ast::BuildRecipe {
             span: default,
+            attributes: attributes,
             token_build: parse,
             ws_1: whitespace,
             pattern: cut_err(parse).help(
@@ -415,7 +884,8 @@ impl<'a> Parse<'a> for ast::BuildRecipe<'a> {
 impl<'a> Parse<'a> for ast::LetStmt<'a> {
     fn parse(input: &mut Input<'a>) -> PResult<Self> {
         fn let_stmt_inner<'a>(input: &mut Input<'a>) -> PResult<ast::LetStmt<'a>> {
-            let (token_let, ws_1, ident, ws_2, token_eq, ws_3, value) = seq! {(
+            let (attributes, token_let, ws_1, ident, ws_2, token_eq, ws_3, value) = seq! {(
+                attributes,
                 parse,
                 cut_err(whitespace_nonempty).expect(&"whitespace after `let`"),
                 cut_err(parse).help("`let` must be followed by an identifier"),
@@ -429,6 +899,7 @@ impl<'a> Parse<'a> for ast::LetStmt<'a> {
 
             Ok(ast::LetStmt {
                 span: Span::default(),
+                attributes,
                 token_let,
                 ws_1,
                 ident,
@@ -510,24 +981,113 @@ where
 
 impl<'a> Parse<'a> for ast::Expr<'a> {
     fn parse(input: &mut Input<'a>) -> PResult<Self> {
-        alt((
+        arith_expr(input, 0)
+    }
+}
+
+/// Expression with any number of binary arithmetic operators, parsed by
+/// precedence climbing. Only operators with at least `min_precedence` are
+/// consumed.
+fn arith_expr<'a>(input: &mut Input<'a>, min_precedence: u8) -> PResult<ast::Expr<'a>> {
+    let mut lhs = atom_expr(input)?;
+
+    loop {
+        let before_op = input.checkpoint();
+        let Some((ws_1, op)) = opt((whitespace, parse::<ast::ArithOp>)).parse_next(input)? else {
+            break;
+        };
+        if op.precedence() < min_precedence {
+            input.reset(&before_op);
+            break;
+        }
+
+        let ws_2 = whitespace.parse_next(input)?;
+        let rhs = cut_err(|input: &mut Input<'a>| arith_expr(input, op.precedence() + 1))
+            .help("arithmetic operators must be followed by an expression")
+            .parse_next(input)?;
+        lhs = ast::Expr::Arith(Box::new(ast::ArithExpr {
+            span: lhs.span().merge(rhs.span()),
+            lhs,
+            ws_1,
+            op,
+            ws_2,
+            rhs,
+        }));
+    }
+
+    Ok(lhs)
+}
+
+/// Expression without binary operators.
+fn atom_expr<'a>(input: &mut Input<'a>) -> PResult<ast::Expr<'a>> {
+    alt((
             parse.map(ast::Expr::StringExpr),
             parse.map(ast::Expr::List),
             parse.map(ast::Expr::Shell),
             parse.map(ast::Expr::Read),
+            parse.map(ast::Expr::Hash),
             parse.map(ast::Expr::Glob),
+            parse.map(ast::Expr::GlobOut),
             parse.map(ast::Expr::Which),
             parse.map(ast::Expr::Env),
+            is_overridden_expr.map(ast::Expr::IsOverridden),
             parse.map(ast::Expr::Error),
-            parse.map(ast::Expr::Ident),
+            parse.map(ast::Expr::CpuCount),
+            parse.map(|expr| ast::Expr::If(Box::new(expr))),
+            parse.map(ast::Expr::Int),
+            ident_path.map(ast::Expr::Ident),
             parse.map(ast::Expr::SubExpr),
             fatal(Failure::Expected(&"expression"))
                 .help("expressions must start with a value, or an `env`, `glob`, `which`, or `shell` operation")
         ))
         .parse_next(input)
+}
+
+impl<'a> Parse<'a> for ast::IntLiteral {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        let (value, span) = terminated(
+            take_while(1.., '0'..='9'),
+            peek(not(one_of(|c: char| {
+                c.is_alphanumeric() || c == '-' || c == '_'
+            }))),
+        )
+        .try_map(str::parse)
+        .with_token_span()
+        .expect(&"integer")
+        .parse_next(input)?;
+        Ok(ast::IntLiteral { span, value })
+    }
+}
+
+impl<'a> Parse<'a> for ast::ArithOp {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        alt((
+            parse.map(ast::ArithOp::Add),
+            parse.map(ast::ArithOp::Sub),
+            parse.map(ast::ArithOp::Mul),
+            parse.map(ast::ArithOp::Div),
+            parse.map(ast::ArithOp::Rem),
+        ))
+        .parse_next(input)
     }
 }
 
+/// `is-overridden <ident>`, where the identifier may refer to the global
+/// variable of an imported Werkfile.
+fn is_overridden_expr(input: &mut Input<'_>) -> PResult<ast::IsOverriddenExpr> {
+    let (mut expr, span) = seq! { ast::KwExpr {
+        span: default,
+        token: parse,
+        ws_1: whitespace_nonempty,
+        param: cut_err(ident_path).help("`is-overridden` must be followed by the name of a global variable"),
+    }}
+    .with_token_span()
+    .while_parsing("is-overridden")
+    .parse_next(input)?;
+    expr.span = span;
+    Ok(expr)
+}
+
 impl<'a> Parse<'a> for ast::ChainSubExpr<'a> {
     // "|" expression_tail
     fn parse(input: &mut Input<'a>) -> PResult<Self> {
@@ -584,14 +1144,21 @@ fn expression_chain_op<'a>(input: &mut Input<'a>) -> PResult<ast::ExprOp<'a>> {
         parse.map(ast::ExprOp::SubExpr),
         parse.map(ast::ExprOp::StringExpr),
         parse.map(ast::ExprOp::Match),
+        match_regex_expr.map(ast::ExprOp::MatchRegex),
         parse.map(ast::ExprOp::Map),
         parse.map(ast::ExprOp::Flatten),
         parse.map(ast::ExprOp::Filter),
         parse.map(ast::ExprOp::FilterMatch),
         parse.map(ast::ExprOp::Discard),
         parse.map(ast::ExprOp::Join),
+        parse.map(ast::ExprOp::PathJoin),
         parse.map(ast::ExprOp::Split),
         parse.map(ast::ExprOp::Dedup),
+        // Nested to stay within the maximum number of alternatives.
+        alt((
+            parse.map(ast::ExprOp::Secret),
+            parse.map(ast::ExprOp::Volatile),
+        )),
         parse.map(ast::ExprOp::Lines),
         parse.map(ast::ExprOp::Info),
         parse.map(ast::ExprOp::Warn),
@@ -604,34 +1171,142 @@ fn expression_chain_op<'a>(input: &mut Input<'a>) -> PResult<ast::ExprOp<'a>> {
     .parse_next(input)
 }
 
+impl<'a> Parse<'a> for ast::RunStmt<'a> {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        let (mut stmt, span) = seq! { ast::RunStmt {
+            span: default,
+            attributes: attributes,
+            token: parse,
+            ws_1: whitespace_nonempty,
+            param: cut_err(parse),
+        }}
+        .with_token_span()
+        .while_parsing("run")
+        .parse_next(input)?;
+        stmt.span = span;
+        Ok(stmt)
+    }
+}
+
+impl<'a> Parse<'a> for ast::RunShellStmt<'a> {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        let (mut stmt, span) = seq! { ast::RunShellStmt {
+            span: default,
+            attributes: attributes,
+            token: parse,
+            ws_1: whitespace_nonempty,
+            script: cut_err(parse)
+                .help("`run-shell` must be followed by a string containing the shell script"),
+        }}
+        .with_token_span()
+        .while_parsing("run-shell")
+        .parse_next(input)?;
+        stmt.span = span;
+        Ok(stmt)
+    }
+}
+
 impl<'a> Parse<'a> for ast::RunExpr<'a> {
     fn parse(input: &mut Input<'a>) -> PResult<Self> {
         alt((
-            parse.map(|string: ast::StringExpr<'_>| {
-                ast::RunExpr::Shell(ast::ShellExpr {
-                    span: string.span,
-                    token: keyword::Keyword::with_span(string.span),
-                    ws_1: ws_ignore(),
-                    param: string,
-                })
-            }),
+            shell_run_expr,
             parse.map(ast::RunExpr::List),
-            parse.map(ast::RunExpr::Shell),
+            parse.map(ast::RunExpr::Args),
             parse.map(ast::RunExpr::Info),
             parse.map(ast::RunExpr::Warn),
             parse.map(ast::RunExpr::Write),
             parse.map(ast::RunExpr::Copy),
+            parse.map(ast::RunExpr::Fetch),
+            parse.map(ast::RunExpr::Extract),
             parse.map(ast::RunExpr::Delete),
+            parse.map(ast::RunExpr::Mkdir),
+            parse.map(ast::RunExpr::Touch),
             parse.map(ast::RunExpr::EnvRemove),
             parse.map(ast::RunExpr::Env),
+            parse.map(ast::RunExpr::Timeout),
             parse.map(ast::RunExpr::Block),
             fatal(Failure::Expected(&"a run expression"))
-                .help("one of `shell`, `info`, `warn`, `write`, `copy`, `delete`, `env`, `env-remove`, a string literal, a list, or a block")
+                .help("one of `shell`, `args`, `info`, `warn`, `write`, `copy`, `fetch`, `extract`, `delete`, `mkdir`, `touch`, `env`, `env-remove`, `timeout`, a string literal, a list, or a block")
         ))
         .parse_next(input)
     }
 }
 
+/// Shell command in a run expression, either as a string literal or as a
+/// `shell` expression, optionally followed by pipes and redirections.
+fn shell_run_expr<'a>(input: &mut Input<'a>) -> PResult<ast::RunExpr<'a>> {
+    let ((command, stdin, pipes, stdout), span): ((_, _, Vec<_>, _), _) =
+        (shell_command, opt(parse), repeat(0.., parse), opt(parse))
+            .with_token_span()
+            .parse_next(input)?;
+
+    if stdin.is_none() && pipes.is_empty() && stdout.is_none() {
+        return Ok(ast::RunExpr::Shell(command));
+    }
+    Ok(ast::RunExpr::Pipeline(ast::PipelineExpr {
+        span,
+        command,
+        stdin,
+        pipes,
+        stdout,
+    }))
+}
+
+/// Either a string literal or a `shell` expression.
+fn shell_command<'a>(input: &mut Input<'a>) -> PResult<ast::ShellExpr<'a>> {
+    alt((
+        parse.map(|string: ast::StringExpr<'_>| ast::ShellExpr {
+            span: string.span,
+            token: keyword::Keyword::with_span(string.span),
+            ws_1: ws_ignore(),
+            param: string,
+        }),
+        parse,
+    ))
+    .parse_next(input)
+}
+
+impl<'a> Parse<'a> for ast::PipeCommand<'a> {
+    /// `| <command>`
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        let (mut pipe, span) = seq! { ast::PipeCommand {
+            span: default,
+            ws_1: whitespace,
+            token: parse,
+            ws_2: whitespace,
+            command: cut_err(shell_command)
+                .help("pipelines look like this: run \"...\" | \"...\" > \"output-file\""),
+        }}
+        .with_token_span()
+        .while_parsing("pipeline")
+        .parse_next(input)?;
+        pipe.span = span;
+        Ok(pipe)
+    }
+}
+
+impl<'a, T> Parse<'a> for ast::Redirect<'a, T>
+where
+    T: Parse<'a>,
+{
+    /// `< <path>` or `> <path>`
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        let (mut redirect, span) = seq! { ast::Redirect {
+            span: default,
+            ws_1: whitespace,
+            token: parse,
+            ws_2: whitespace,
+            path: cut_err(parse)
+                .help("redirections look like this: run \"...\" < \"input-file\" > \"output-file\""),
+        }}
+        .with_token_span()
+        .while_parsing("redirection")
+        .parse_next(input)?;
+        redirect.span = span;
+        Ok(redirect)
+    }
+}
+
 impl<'a> Parse<'a> for ast::WriteExpr<'a> {
     fn parse(input: &mut Input<'a>) -> PResult<Self> {
         let (mut expr, span) = seq! {ast::WriteExpr {
@@ -670,70 +1345,313 @@ impl<'a> Parse<'a> for ast::CopyExpr<'a> {
     }
 }
 
-impl<'a> Parse<'a> for ast::MatchBody<'a> {
+impl<'a> Parse<'a> for ast::ExtractExpr<'a> {
     fn parse(input: &mut Input<'a>) -> PResult<Self> {
-        struct MatchArmBraced<'a>(ast::MatchArm<'a>);
-        impl<'a> Parse<'a> for MatchArmBraced<'a> {
-            fn parse(input: &mut Input<'a>) -> PResult<Self> {
-                let (mut arm, span) = seq! {ast::MatchArm {
+        let (mut expr, span) = seq! {ast::ExtractExpr {
             span: default,
-            pattern: cut_err(parse).help("`match` arm must start with a pattern"),
+            token_extract: parse,
             ws_1: whitespace,
-            token_fat_arrow: cut_err(parse).help("pattern must be followed by `=>` in `match`"),
+            archive: cut_err(parse),
             ws_2: whitespace,
-            expr: cut_err(parse).help("`=>` must be followed by an expression in `match`"),
+            token_to: cut_err(parse),
+            ws_3: whitespace,
+            dest: cut_err(parse),
         }}
         .with_token_span()
+        .while_parsing("`extract` expression")
         .parse_next(input)?;
-                arm.span = span;
-                Ok(MatchArmBraced(arm))
-            }
+        expr.span = span;
+        Ok(expr)
+    }
+}
+
+impl<'a> Parse<'a> for ast::FetchExpr<'a> {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        let (mut expr, span) = seq! {ast::FetchExpr {
+            span: default,
+            token_fetch: parse,
+            ws_1: whitespace,
+            url: cut_err(parse),
+            ws_2: whitespace,
+            token_sha256: cut_err(parse),
+            ws_3: whitespace,
+            sha256: cut_err(parse),
+            ws_4: whitespace,
+            token_to: cut_err(parse),
+            ws_5: whitespace,
+            dest: cut_err(parse),
+        }}
+        .with_token_span()
+        .while_parsing("`fetch` expression")
+        .parse_next(input)?;
+        expr.span = span;
+        Ok(expr)
+    }
+}
+
+impl<'a> Parse<'a> for ast::MatchBody<'a> {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        match_body::<false>(input)
+    }
+}
+
+/// Body of `match` (or `match-regex`, when `REGEX` is true).
+fn match_body<'a, const REGEX: bool>(input: &mut Input<'a>) -> PResult<ast::MatchBody<'a>> {
+    struct MatchArmBraced<'a, const REGEX: bool>(ast::MatchArm<'a>);
+    impl<'a, const REGEX: bool> Parse<'a> for MatchArmBraced<'a, REGEX> {
+        fn parse(input: &mut Input<'a>) -> PResult<Self> {
+            let (mut arm, span) = seq! {ast::MatchArm {
+        span: default,
+        pattern: cut_err(match_pattern::<REGEX>).help(if REGEX { "`match-regex` arm must start with a regular expression" } else { "`match` arm must start with a pattern" }),
+        guard: opt(parse),
+        ws_1: whitespace,
+        token_fat_arrow: cut_err(parse).help("pattern must be followed by `=>` in `match`"),
+        ws_2: whitespace,
+        expr: cut_err(parse).help("`=>` must be followed by an expression in `match`"),
+    }}
+    .with_token_span()
+    .parse_next(input)?;
+            arm.span = span;
+            Ok(MatchArmBraced(arm))
+        }
+    }
+
+    #[allow(dead_code)] // False positive!
+    struct MatchArmSingle<'a, const REGEX: bool>(ast::MatchArm<'a>);
+    impl<'a, const REGEX: bool> Parse<'a> for MatchArmSingle<'a, REGEX> {
+        fn parse(input: &mut Input<'a>) -> PResult<MatchArmSingle<'a, REGEX>> {
+            let (mut arm, span) = seq! {ast::MatchArm {
+        span: default,
+        pattern: cut_err(match_pattern::<REGEX>).help("`match` must be followed by a `{...}` block, or a single pattern"),
+        guard: opt(parse),
+        ws_1: whitespace,
+        token_fat_arrow: cut_err(parse).help("pattern must be followed by `=>` in `match`"),
+        ws_2: whitespace,
+        expr: cut_err(parse)
+            .help("`=>` must be followed by a string literal in inline `match`")
+            .map(ast::Expr::StringExpr)
+            .map(Into::into),
+    }}
+    .with_token_span()
+    .parse_next(input)?;
+            arm.span = span;
+            Ok(MatchArmSingle(arm))
         }
+    }
+
+    alt((
+        preceded(
+            peek(parse::<token::BraceOpen>),
+            cut_err(parse::<ast::Body<MatchArmBraced<REGEX>>>).map(|body| {
+                ast::MatchBody::Braced(ast::Body {
+                    token_open: body.token_open,
+                    statements: body
+                        .statements
+                        .into_iter()
+                        .map(|stmt| ast::BodyStmt {
+                            ws_pre: stmt.ws_pre,
+                            statement: stmt.statement.0,
+                            trailing: stmt.trailing,
+                        })
+                        // Note: Guaranteed in-place.
+                        .collect(),
+                    ws_trailing: body.ws_trailing,
+                    token_close: body.token_close,
+                })
+            }),
+        ),
+        parse.map(|MatchArmSingle::<REGEX>(arm)| ast::MatchBody::Single(Box::new(arm))),
+    ))
+    .parse_next(input)
+}
+
+/// The pattern of a match arm, which is a regular expression in `match-regex`.
+fn match_pattern<'a, const REGEX: bool>(input: &mut Input<'a>) -> PResult<ast::MatchPattern<'a>> {
+    if REGEX {
+        alt((
+            regex_pattern.map(ast::MatchPattern::Regex),
+            parse.map(|kw| ast::MatchPattern::Special(ast::SpecialPattern::Wildcard(kw))),
+            parse.map(|kw| ast::MatchPattern::Special(ast::SpecialPattern::EmptyList(kw))),
+            parse.map(|kw| ast::MatchPattern::Special(ast::SpecialPattern::NonEmptyList(kw))),
+        ))
+        .parse_next(input)
+    } else {
+        parse.parse_next(input)
+    }
+}
+
+/// String literal containing a regular expression, compiled while parsing so
+/// errors point at the pattern.
+fn regex_pattern(input: &mut Input<'_>) -> PResult<ast::RegexPattern> {
+    let (pattern, span) = escaped_string.with_token_span().parse_next(input)?;
+    match regex::Regex::new(pattern) {
+        Ok(regex) => Ok(ast::RegexPattern { span, regex }),
+        Err(err) => Err(ModalErr::Error(Error::new(
+            span.start,
+            Failure::ValidRegex(std::sync::Arc::new(err)),
+        ))),
+    }
+}
+
+/// `match-regex <match-body>`
+fn match_regex_expr<'a>(input: &mut Input<'a>) -> PResult<ast::MatchRegexExpr<'a>> {
+    let (mut expr, span) = seq! { ast::KwExpr {
+        span: default,
+        token: parse,
+        ws_1: whitespace_nonempty,
+        param: cut_err(match_body::<true>),
+    }}
+    .with_token_span()
+    .while_parsing("match-regex")
+    .parse_next(input)?;
+    expr.span = span;
+    Ok(expr)
+}
+
+impl<'a> Parse<'a> for ast::MatchPattern<'a> {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        alt((
+            parse.map(ast::MatchPattern::Pattern),
+            parse.map(|kw| ast::MatchPattern::Special(ast::SpecialPattern::Wildcard(kw))),
+            parse.map(|kw| ast::MatchPattern::Special(ast::SpecialPattern::EmptyList(kw))),
+            parse.map(|kw| ast::MatchPattern::Special(ast::SpecialPattern::NonEmptyList(kw))),
+        ))
+        .parse_next(input)
+    }
+}
+
+impl<'a> Parse<'a> for ast::MatchGuard<'a> {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        let (mut guard, span) = seq! {ast::MatchGuard {
+            span: default,
+            ws_pre: whitespace,
+            token_if: parse,
+            ws_1: whitespace,
+            condition: cut_err(parse).help("`if` must be followed by a condition in `match`"),
+        }}
+        .with_token_span()
+        .parse_next(input)?;
+        guard.span = span;
+        Ok(guard)
+    }
+}
 
-        #[allow(dead_code)] // False positive!
-        struct MatchArmSingle<'a>(ast::MatchArm<'a>);
-        impl<'a> Parse<'a> for MatchArmSingle<'a> {
-            fn parse(input: &mut Input<'a>) -> PResult<MatchArmSingle<'a>> {
-                let (mut arm, span) = seq! {ast::MatchArm {
+impl<'a> Parse<'a> for ast::IfExpr<'a> {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        const HELP: &str =
+            "`if` expressions look like this: if <condition> then <expr> else <expr>";
+        let (mut expr, span) = seq! {ast::IfExpr {
             span: default,
-            pattern: cut_err(parse).help("`match` must be followed by a `{...}` block, or a single pattern"),
+            token_if: parse,
             ws_1: whitespace,
-            token_fat_arrow: cut_err(parse).help("pattern must be followed by `=>` in `match`"),
+            condition: cut_err(parse).help(HELP),
             ws_2: whitespace,
-            expr: cut_err(parse)
-                .help("`=>` must be followed by a string literal in inline `match`")
-                .map(ast::Expr::StringExpr)
-                .map(Into::into),
+            token_then: cut_err(parse).help(HELP),
+            ws_3: whitespace,
+            then_expr: cut_err(parse).help(HELP),
+            ws_4: whitespace,
+            token_else: cut_err(parse).help(HELP),
+            ws_5: whitespace,
+            else_expr: cut_err(parse).help(HELP),
         }}
         .with_token_span()
+        .while_parsing("if expression")
         .parse_next(input)?;
-                arm.span = span;
-                Ok(MatchArmSingle(arm))
-            }
+        expr.span = span;
+        Ok(expr)
+    }
+}
+
+/// Condition, where `not` binds tighter than `and`, which binds tighter than
+/// `or`.
+impl<'a> Parse<'a> for ast::Condition<'a> {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
+        let mut lhs = and_condition(input)?;
+        while let Some((ws_1, op)) =
+            opt((whitespace, parse.map(ast::LogicalOp::Or))).parse_next(input)?
+        {
+            let ws_2 = whitespace.parse_next(input)?;
+            let rhs = cut_err(and_condition)
+                .help("`or` must be followed by a condition")
+                .parse_next(input)?;
+            lhs = logical_condition(lhs, ws_1, op, ws_2, rhs);
         }
+        Ok(lhs)
+    }
+}
+
+fn and_condition<'a>(input: &mut Input<'a>) -> PResult<ast::Condition<'a>> {
+    let mut lhs = not_condition(input)?;
+    while let Some((ws_1, op)) =
+        opt((whitespace, parse.map(ast::LogicalOp::And))).parse_next(input)?
+    {
+        let ws_2 = whitespace.parse_next(input)?;
+        let rhs = cut_err(not_condition)
+            .help("`and` must be followed by a condition")
+            .parse_next(input)?;
+        lhs = logical_condition(lhs, ws_1, op, ws_2, rhs);
+    }
+    Ok(lhs)
+}
+
+fn logical_condition<'a>(
+    lhs: ast::Condition<'a>,
+    ws_1: ast::Whitespace,
+    op: ast::LogicalOp,
+    ws_2: ast::Whitespace,
+    rhs: ast::Condition<'a>,
+) -> ast::Condition<'a> {
+    ast::Condition::Logical(Box::new(ast::LogicalCondition {
+        span: lhs.span().merge(rhs.span()),
+        lhs,
+        ws_1,
+        op,
+        ws_2,
+        rhs,
+    }))
+}
+
+fn not_condition<'a>(input: &mut Input<'a>) -> PResult<ast::Condition<'a>> {
+    if let Some(token) = opt(parse::<keyword::Not>).parse_next(input)? {
+        let ws_1 = whitespace.parse_next(input)?;
+        let param = cut_err(not_condition)
+            .help("`not` must be followed by a condition")
+            .parse_next(input)?;
+        return Ok(ast::Condition::Not(Box::new(ast::KwExpr {
+            span: token.span().merge(param.span()),
+            token,
+            ws_1,
+            param,
+        })));
+    }
 
+    let lhs = parse::<ast::Expr>.parse_next(input)?;
+    let Some((ws_1, op)) = opt((whitespace, parse::<ast::CompareOp>)).parse_next(input)? else {
+        return Ok(ast::Condition::Value(lhs));
+    };
+    let ws_2 = whitespace.parse_next(input)?;
+    let rhs = cut_err(parse::<ast::Expr>)
+        .help("comparisons must have an expression on both sides")
+        .parse_next(input)?;
+    Ok(ast::Condition::Compare(Box::new(ast::Comparison {
+        span: lhs.span().merge(rhs.span()),
+        lhs,
+        ws_1,
+        op,
+        ws_2,
+        rhs,
+    })))
+}
+
+impl<'a> Parse<'a> for ast::CompareOp {
+    fn parse(input: &mut Input<'a>) -> PResult<Self> {
         alt((
-            preceded(
-                peek(parse::<token::BraceOpen>),
-                cut_err(parse::<ast::Body<MatchArmBraced>>).map(|body| {
-                    ast::MatchBody::Braced(ast::Body {
-                        token_open: body.token_open,
-                        statements: body
-                            .statements
-                            .into_iter()
-                            .map(|stmt| ast::BodyStmt {
-                                ws_pre: stmt.ws_pre,
-                                statement: stmt.statement.0,
-                                trailing: stmt.trailing,
-                            })
-                            // Note: Guaranteed in-place.
-                            .collect(),
-                        ws_trailing: body.ws_trailing,
-                        token_close: body.token_close,
-                    })
-                }),
-            ),
-            parse.map(|MatchArmSingle(arm)| ast::MatchBody::Single(Box::new(arm))),
+            parse.map(ast::CompareOp::Eq),
+            parse.map(ast::CompareOp::NotEq),
+            parse.map(ast::CompareOp::LessEq),
+            parse.map(ast::CompareOp::Less),
+            parse.map(ast::CompareOp::GreaterEq),
+            parse.map(ast::CompareOp::Greater),
         ))
         .parse_next(input)
     }
@@ -836,6 +1754,19 @@ impl<'a> Parse<'a> for ast::Ident {
     }
 }
 
+/// Identifier optionally qualified by the namespaces of imported Werkfiles,
+/// like `lib.cflags`.
+fn ident_path(input: &mut Input<'_>) -> PResult<ast::Ident> {
+    let (ident, span) = (
+        parse::<ast::Ident>,
+        repeat::<_, _, (), _, _>(0.., ('.', parse::<ast::Ident>)),
+    )
+        .take()
+        .with_token_span()
+        .parse_next(input)?;
+    Ok(ast::Ident::new(span, ident))
+}
+
 fn escaped_string<'a>(input: &mut Input<'a>) -> PResult<&'a str> {
     fn escaped_string_char(input: &mut Input<'_>) -> PResult<()> {
         alt((none_of(['\\', '\"']).value(()), ('\\', any).value(()))).parse_next(input)
@@ -859,8 +1790,37 @@ fn until_eol_or_eof<'a>(input: &mut Input<'a>) -> PResult<&'a str> {
 
 /// Whitespace (whitespace and comments) preceding a statement. The whitespace
 /// can be used to separate statements. Does not include semicolons.
-#[expect(clippy::unnecessary_wraps)] // This parser can never fail.
 fn whitespace_parsed(input: &mut Input) -> PResult<ParsedWhitespace> {
+    whitespace_parsed_impl(input, false, false)
+}
+
+/// Whitespace between statements, which stops at a line containing attributes
+/// of the next statement.
+fn statement_whitespace_parsed(input: &mut Input) -> PResult<ParsedWhitespace> {
+    let at_line_start = input.current_token_start() == 0;
+    whitespace_parsed_impl(input, true, at_line_start)
+}
+
+/// Whitespace after an attribute, which may be followed by more attributes on
+/// the same line.
+fn attribute_whitespace_parsed(input: &mut Input) -> PResult<ParsedWhitespace> {
+    whitespace_parsed_impl(input, true, true)
+}
+
+/// Whether the rest of the line only contains attributes, like `#[silent]`.
+/// Other lines starting with `#[` are comments, like `#[TODO] ...`.
+fn is_attribute_line(input: &Input) -> bool {
+    let line = input.peek_finish().lines().next().unwrap_or_default();
+    let line = line.trim_end();
+    line.starts_with("#[") && line.ends_with(']')
+}
+
+#[expect(clippy::unnecessary_wraps)] // This parser can never fail.
+fn whitespace_parsed_impl(
+    input: &mut Input,
+    stop_at_attributes: bool,
+    at_line_start: bool,
+) -> PResult<ParsedWhitespace> {
     #[derive(Clone, Copy)]
     enum WsPart {
         Comment,
@@ -871,10 +1831,29 @@ fn whitespace_parsed(input: &mut Input) -> PResult<ParsedWhitespace> {
         Newline,
     }
 
+    // Attributes must be on their own line, so `#[` after a statement on the
+    // same line is a comment.
+    let at_line_start = std::cell::Cell::new(at_line_start);
+    let comment = |input: &mut Input<'_>| {
+        if stop_at_attributes && at_line_start.get() && is_attribute_line(input) {
+            return Err(ModalErr::Backtrack(
+                Offset(input.current_token_start() as u32),
+                Failure::Unknown,
+            ));
+        }
+        ('#', until_eol_or_eof).void().parse_next(input)?;
+        at_line_start.set(true);
+        Ok(WsPart::Comment)
+    };
+    let newline = |input: &mut Input<'_>| {
+        '\n'.parse_next(input)?;
+        at_line_start.set(true);
+        Ok(WsPart::Newline)
+    };
     let ws_part = alt((
-        ('#', until_eol_or_eof).value(WsPart::Comment),
+        comment,
         one_of([' ', '\t', '\r']).value(WsPart::Whitespace),
-        '\n'.value(WsPart::Newline),
+        newline,
     ));
 
     let mut parser = repeat(0.., ws_part)
@@ -1058,7 +2037,11 @@ mod tests {
     fn root_statements() {
         let input =
             Input::new("config out-dir = \"../target\"\n\nlet cc = which \"clang\"\nlet ld = cc\n");
-        let root = super::root.parse(input).unwrap();
+        let mut errors = Vec::new();
+        let root = (|input: &mut Input<'static>| super::root(input, &mut errors))
+            .parse(input)
+            .unwrap();
+        assert!(errors.is_empty());
         assert_eq!(
             root,
             ast::Root {
@@ -1087,6 +2070,7 @@ mod tests {
                         ws_pre: ws_ignore(),
                         statement: ast::RootStmt::Let(ast::LetStmt {
                             span: span(30..52),
+                            attributes: vec![],
                             token_let: keyword::Let(Offset(30)),
                             ws_1: ws_ignore(),
                             ident: ast::Ident {
@@ -1113,6 +2097,7 @@ mod tests {
                         ws_pre: ws_ignore(),
                         statement: ast::RootStmt::Let(ast::LetStmt {
                             span: span(53..64),
+                            attributes: vec![],
                             token_let: keyword::Let(Offset(53)),
                             ws_1: ws_ignore(),
                             ident: ast::Ident {
@@ -1143,6 +2128,7 @@ mod tests {
             parse::<ast::LetStmt>.parse(input).unwrap(),
             ast::LetStmt {
                 span: span(0..22),
+                attributes: vec![],
                 token_let: keyword::Let(Offset(0)),
                 ws_1: ws(3..4),
                 ident: ast::Ident {
@@ -1265,6 +2251,40 @@ mod tests {
                 has_comments: false
             }
         );
+
+        // Lines starting with `#[` are comments, unless they contain only
+        // attributes and appear between statements.
+        assert_eq!(
+            super::whitespace_parsed
+                .parse(Input::new("#[silent]\n"))
+                .unwrap(),
+            ParsedWhitespace {
+                span: span(0..10),
+                has_newlines: false,
+                has_comments: true
+            }
+        );
+        assert_eq!(
+            super::statement_whitespace_parsed
+                .parse(Input::new("#[TODO] fix this\n"))
+                .unwrap(),
+            ParsedWhitespace {
+                span: span(0..17),
+                has_newlines: false,
+                has_comments: true
+            }
+        );
+        assert!(super::statement_whitespace_parsed
+            .parse(Input::new("\n#[silent]\n"))
+            .is_err());
+    }
+
+    #[test]
+    fn bracket_comments() {
+        let source = "#[TODO] use clang\nlet cc = \"gcc\" #[x]\n\n#[silent] #[retry = 1]\ntask build {\n    #[FIXME]: flags\n    run \"{cc}\"\n}\n";
+        let ast = crate::parse_werk(std::path::Path::new("INPUT"), source).unwrap();
+        let task = ast.root.find_command("build").unwrap();
+        assert_eq!(task.attributes.len(), 2);
     }
 
     #[test]
@@ -1279,10 +2299,11 @@ mod tests {
                 ws_1: ws_ignore(),
                 param: ast::MatchBody::Single(Box::new(ast::MatchArm {
                     span: span(13..23),
-                    pattern: ast::PatternExpr {
+                    pattern: ast::MatchPattern::Pattern(ast::PatternExpr {
                         span: span(13..16),
                         fragments: vec![ast::PatternFragment::Literal("a".into())]
-                    },
+                    }),
+                    guard: None,
                     ws_1: ws_ignore(),
                     token_fat_arrow: keyword::FatArrow::with_span(span(17..19)),
                     ws_2: ws_ignore(),
@@ -1295,4 +2316,92 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn match_guard() {
+        assert_eq!(
+            super::expression_chain_op
+                .parse(Input::new("match _ if os != \"linux\" => \"b\""))
+                .unwrap(),
+            ast::ExprOp::Match(ast::KwExpr {
+                span: span(0..31),
+                token: keyword::Match::with_span(span(0..5)),
+                ws_1: ws_ignore(),
+                param: ast::MatchBody::Single(Box::new(ast::MatchArm {
+                    span: span(6..31),
+                    pattern: ast::MatchPattern::Special(ast::SpecialPattern::Wildcard(
+                        keyword::Underscore::with_span(span(6..7))
+                    )),
+                    guard: Some(ast::MatchGuard {
+                        span: span(7..24),
+                        ws_pre: ws_ignore(),
+                        token_if: keyword::If::with_span(span(8..10)),
+                        ws_1: ws_ignore(),
+                        condition: ast::Condition::Compare(Box::new(ast::Comparison {
+                            span: span(11..24),
+                            lhs: ast::Expr::Ident(ast::Ident::new(span(11..13), "os")),
+                            ws_1: ws_ignore(),
+                            op: ast::CompareOp::NotEq(keyword::NotEq::with_span(span(14..16))),
+                            ws_2: ws_ignore(),
+                            rhs: ast::Expr::StringExpr(ast::StringExpr {
+                                span: span(17..24),
+                                fragments: vec![ast::StringFragment::Literal("linux".into())]
+                            }),
+                        })),
+                    }),
+                    ws_1: ws_ignore(),
+                    token_fat_arrow: keyword::FatArrow::with_span(span(25..27)),
+                    ws_2: ws_ignore(),
+                    expr: ast::Expr::StringExpr(ast::StringExpr {
+                        span: span(28..31),
+                        fragments: vec![ast::StringFragment::Literal("b".into())]
+                    })
+                    .into()
+                }))
+            })
+        );
+    }
+
+    #[test]
+    fn doc_comment() {
+        let source = "let x = \"a\"\n\n# Not documentation.\n\n# Build the program.\n#\n#   Indented.\ntask build {}\ntask test {}\n";
+        let doc = crate::parse_werk(std::path::Path::new("INPUT"), source).unwrap();
+        assert_eq!(
+            doc.task_doc_comment("build").as_deref(),
+            Some("Build the program.\n\n  Indented.")
+        );
+        assert_eq!(doc.task_doc_comment("test").as_deref(), Some(""));
+        assert_eq!(doc.task_doc_comment("missing"), None);
+    }
+
+    #[test]
+    fn recover_root_statements() {
+        let source = "let a = \"a\"\ntask build {\n    run \"a\"\n    oops\n}\n# Comment.\nlet c = \"c\" let d = \"d\"\n#[silent]\ntask test {}\nbuild \"%.o\" {\n";
+        let (doc, errors) =
+            crate::parser::parse_werk_recovering(std::path::Path::new("INPUT"), source);
+        let names = doc
+            .root
+            .statements
+            .iter()
+            .map(|stmt| match stmt.statement {
+                ast::RootStmt::Let(ref stmt) => stmt.ident.ident.as_str(),
+                ast::RootStmt::Task(ref task) => task.name.ident.as_str(),
+                _ => "other",
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a", "c", "test"]);
+        let offsets = errors
+            .iter()
+            .map(|err| err.offset.0 as usize)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            offsets,
+            [
+                source.find("oops").unwrap(),
+                source.find("let d").unwrap(),
+                source.len(),
+            ]
+        );
+        assert!(crate::parse_werk(std::path::Path::new("INPUT"), source).is_err());
+    }
 }
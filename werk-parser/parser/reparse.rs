@@ -0,0 +1,344 @@
+use winnow::{
+    combinator::{empty, eof, peek},
+    stream::{Location as _, Stream as _},
+    Parser as _,
+};
+
+use crate::{ast, Document, Error, Failure, ModalErr};
+
+use super::{no_recovery, parse, parse_werk_recovering, statements_delimited, Input, Offset};
+use super::{Shift, Span};
+
+/// Parse a Werkfile after an edit, reusing the statements of the previous
+/// document that the edit did not touch. `edit` is the range of the previous
+/// source code that was replaced, and the rest of `source` must be the same as
+/// the previous source code.
+///
+/// Only the root statements around the edit are parsed again, and the spans of
+/// the statements after them are moved. When that is not possible, for example
+/// because the previous document had syntax errors, the whole source code is
+/// parsed again. Either way, the result is the same as
+/// `parse_werk_recovering()`, except that the imports of the previous document
+/// are kept.
+///
+/// The AST borrows from the source code, so the previous source code must live
+/// as long as the new one.
+#[must_use]
+pub fn reparse_werk<'a>(
+    mut previous: Document<'a>,
+    source: &'a str,
+    edit: Span,
+) -> (Document<'a>, Vec<Error>) {
+    if let Some(region) = reparse_region(&previous, source, edit) {
+        region.splice(&mut previous, source);
+        return (previous, Vec::new());
+    }
+
+    let (mut document, errors) = parse_werk_recovering(previous.origin, source);
+    document.imports = previous.imports;
+    (document, errors)
+}
+
+/// The reparsed root statements `range` of the previous document.
+struct Region<'a> {
+    range: std::ops::Range<usize>,
+    statements: Vec<ast::BodyStmt<ast::RootStmt<'a>>>,
+    /// The whitespace after the reparsed statements, which precedes the next
+    /// statement or ends the file.
+    ws_trailing: ast::Whitespace,
+    delta: i64,
+}
+
+impl<'a> Region<'a> {
+    fn splice(self, document: &mut Document<'a>, source: &'a str) {
+        let root = &mut document.root;
+        let mut after = root.statements.split_off(self.range.end);
+        root.statements.truncate(self.range.start);
+
+        after.shift(self.delta);
+        if let Some(next) = after.first_mut() {
+            next.ws_pre = self.ws_trailing;
+            root.ws_trailing.shift(self.delta);
+        } else {
+            root.ws_trailing = self.ws_trailing;
+        }
+
+        root.statements.extend(self.statements);
+        root.statements.extend(after);
+        document.source = source;
+    }
+}
+
+fn reparse_region<'a>(previous: &Document<'a>, source: &'a str, edit: Span) -> Option<Region<'a>> {
+    let old = previous.source;
+    let statements = &previous.root.statements;
+    if !previous.complete || previous.smuggled_whitespace.is_some() || statements.is_empty() {
+        return None;
+    }
+
+    // The text outside the edit must be unchanged.
+    let edit = edit.start.0 as usize..edit.end.0 as usize;
+    if edit.start > edit.end || edit.end > old.len() {
+        return None;
+    }
+    let delta = i64::try_from(source.len()).ok()? - i64::try_from(old.len()).ok()?;
+    let new_edit_end = source.len().checked_sub(old.len() - edit.end)?;
+    if new_edit_end < edit.start
+        || source.get(..edit.start)? != &old[..edit.start]
+        || source.get(new_edit_end..)? != &old[edit.end..]
+    {
+        return None;
+    }
+
+    // A statement extends from its preceding whitespace to the preceding
+    // whitespace of the next statement. The statements on either side of the
+    // edited ones are parsed again as well, because the edit may have joined
+    // them or split them.
+    let start_of = |index: usize| statements[index].ws_pre.0.start.0 as usize;
+    let end_of = |index: usize| {
+        statements
+            .get(index + 1)
+            .map_or(old.len(), |next| next.ws_pre.0.start.0 as usize)
+    };
+    let first = (0..statements.len()).find(|&index| end_of(index) >= edit.start)?;
+    let last = (0..statements.len()).rfind(|&index| start_of(index) <= edit.end)?;
+    let range = first.saturating_sub(1)..(last + 2).min(statements.len());
+
+    // The parse ends at the first unchanged statement after the edit, or at
+    // the end of the file.
+    let end = statements.get(range.end).map(|next| {
+        let mut end = next.ws_pre.0.end;
+        end.shift(delta);
+        end.0 as usize
+    });
+    let terminal = |input: &mut Input<'a>| -> Result<(), ModalErr> {
+        match end {
+            Some(end) if input.current_token_start() >= end => Ok(()),
+            Some(_) => Err(ModalErr::Backtrack(
+                Offset(input.current_token_start() as u32),
+                Failure::Unknown,
+            )),
+            None => peek(eof).void().parse_next(input),
+        }
+    };
+
+    let mut input = Input::new(source);
+    input.next_slice(start_of(range.start));
+    let ((), new_statements, ws_trailing, ()) =
+        statements_delimited(empty, parse, terminal, no_recovery)
+            .parse_next(&mut input)
+            .ok()?;
+    if end.is_some_and(|end| input.current_token_start() != end) {
+        return None;
+    }
+
+    // The next statement must still be separated from the reparsed ones. The
+    // start needs no check, because the whitespace before the first reparsed
+    // statement is unchanged.
+    if end.is_some() {
+        let previous_semicolon = match new_statements.last() {
+            Some(stmt) => Some(stmt.trailing.token.is_some()),
+            None => range
+                .start
+                .checked_sub(1)
+                .map(|index| statements[index].trailing.token.is_some()),
+        };
+        let whitespace = &source[std::ops::Range::<usize>::from(ws_trailing.0)];
+        let separated = previous_semicolon.is_none_or(|semicolon| semicolon)
+            || whitespace.contains(['\n', '#']);
+        if !separated {
+            return None;
+        }
+    }
+
+    Some(Region {
+        range,
+        statements: new_statements,
+        ws_trailing,
+        delta,
+    })
+}
+
+/// Implement `Shift` for structs, shifting the listed fields.
+macro_rules! shift_fields {
+    ($(impl [$($generics:tt)*] $t:ty { $($field:tt),* $(,)? })*) => {
+        $(
+            impl<$($generics)*> Shift for $t {
+                fn shift(&mut self, delta: i64) {
+                    $(self.$field.shift(delta);)*
+                }
+            }
+        )*
+    };
+}
+
+/// Implement `Shift` for enums where each variant has a single field.
+macro_rules! shift_variants {
+    ($(impl [$($generics:tt)*] $t:ty { $($variant:ident),* $(,)? })*) => {
+        $(
+            impl<$($generics)*> Shift for $t {
+                fn shift(&mut self, delta: i64) {
+                    match self {
+                        $(Self::$variant(value) => value.shift(delta),)*
+                    }
+                }
+            }
+        )*
+    };
+}
+
+shift_fields! {
+    impl [] ast::Whitespace { 0 }
+    impl [T: Shift] ast::Trailing<T> { ws, token }
+    impl ['a] ast::Root<'a> { statements, ws_trailing }
+    impl ['a] ast::ConfigStmt<'a> { span, token_config, ws_1, ident, ws_2, token_eq, ws_3, value }
+    impl ['a] ast::ImportStmt<'a> { span, token_import, ws_1, path, ws_2, token_as, ws_3, ident }
+    impl ['a] ast::MemberStmt<'a> { span, token_member, ws_1, path }
+    impl ['a] ast::ProfileStmt<'a> { span, token_profile, ws_1, name, ws_2, body }
+    impl ['a] ast::ConfigString<'a> { 0 }
+    impl [] ast::ConfigBool { 0 }
+    impl [] ast::ConfigInt { 0 }
+    impl ['a] ast::Attribute<'a> { span, name, value, ws_trailing }
+    impl [] ast::Ident { span }
+    impl ['a] ast::CommandRecipe<'a> { span, attributes, token_task, ws_1, name, ws_2, body }
+    impl ['a] ast::BuildRecipe<'a> { span, attributes, token_build, ws_1, pattern, ws_2, body }
+    impl [T: Shift] ast::Body<T> { token_open, statements, ws_trailing, token_close }
+    impl [T: Shift] ast::BodyStmt<T> { ws_pre, statement, trailing }
+    impl ['a] ast::LetStmt<'a> { span, attributes, token_let, ws_1, ident, ws_2, token_eq, ws_3, value }
+    impl ['a] ast::EnvStmt<'a> { span, token, ws_1, key, ws_2, token_eq, ws_3, value }
+    impl ['a] ast::RunStmt<'a> { span, attributes, token, ws_1, param }
+    impl ['a] ast::RunShellStmt<'a> { span, attributes, token, ws_1, script }
+    impl ['a] ast::PipelineExpr<'a> { span, command, stdin, pipes, stdout }
+    impl ['a] ast::PipeCommand<'a> { span, ws_1, token, ws_2, command }
+    impl ['a, T: Shift] ast::Redirect<'a, T> { span, ws_1, token, ws_2, path }
+    impl ['a] ast::CopyExpr<'a> { span, token_copy, ws_1, src, ws_2, token_to, ws_3, dest }
+    impl ['a] ast::ExtractExpr<'a> {
+        span, token_extract, ws_1, archive, ws_2, token_to, ws_3, dest,
+    }
+    impl ['a] ast::FetchExpr<'a> {
+        span, token_fetch, ws_1, url, ws_2, token_sha256, ws_3, sha256, ws_4, token_to, ws_5, dest,
+    }
+    impl ['a] ast::WriteExpr<'a> { span, token_write, ws_1, value, ws_2, token_to, ws_3, path }
+    impl [] ast::IntLiteral { span }
+    impl ['a] ast::ArithExpr<'a> { span, lhs, ws_1, op, ws_2, rhs }
+    impl ['a] ast::IfExpr<'a> {
+        span, token_if, ws_1, condition, ws_2, token_then, ws_3, then_expr, ws_4, token_else, ws_5,
+        else_expr,
+    }
+    impl ['a] ast::Comparison<'a> { span, lhs, ws_1, op, ws_2, rhs }
+    impl ['a] ast::LogicalCondition<'a> { span, lhs, ws_1, op, ws_2, rhs }
+    impl ['a] ast::SubExpr<'a> { span, token_open, ws_1, expr, ws_2, token_close }
+    impl [E: Shift] ast::ListExpr<E> { span, token_open, items, ws_trailing, token_close }
+    impl [E: Shift] ast::ListItem<E> { ws_pre, item, trailing }
+    impl ['a] ast::MatchArm<'a> { span, pattern, guard, ws_1, token_fat_arrow, ws_2, expr }
+    impl [] ast::RegexPattern { span }
+    impl ['a] ast::MatchGuard<'a> { span, ws_pre, token_if, ws_1, condition }
+    impl ['a] ast::ExprChain<'a> { span, expr, ops }
+    impl ['a] ast::ChainSubExpr<'a> { span, ws_1, token_pipe, ws_2, expr }
+    impl [T: Shift, P: Shift] ast::KwExpr<T, P> { span, token, ws_1, param }
+    impl ['a] ast::StringExpr<'a> { span }
+    impl ['a] ast::PatternExpr<'a> { span }
+}
+
+shift_variants! {
+    impl ['a] ast::RootStmt<'a> { Config, Import, Member, Let, Task, Build, Profile }
+    impl ['a] ast::ConfigValue<'a> { String, Bool, Int, List }
+    impl ['a] ast::BuildRecipeStmt<'a> {
+        Let, From, Depfile, Run, RunShell, Info, Warn, SetCapture, SetNoCapture, Priority, Env,
+        EnvRemove, Timeout, Pool, Host, Container, Out, Exclude, InDir,
+    }
+    impl ['a] ast::TaskRecipeStmt<'a> {
+        Let, Build, Run, RunShell, Info, Warn, SetCapture, SetNoCapture, Env, EnvRemove, Timeout,
+        Pool, InDir,
+    }
+    impl ['a] ast::RunExpr<'a> {
+        Shell, Args, Pipeline, Write, Copy, Fetch, Extract, Delete, Mkdir, Touch, Env, EnvRemove,
+        Timeout, Info, Warn, List, Block,
+    }
+    impl ['a] ast::Expr<'a> {
+        Ident, StringExpr, Shell, Read, Glob, Which, Env, List, SubExpr, Error, GlobOut,
+        IsOverridden, Hash, Int, CpuCount, Arith, If,
+    }
+    impl [] ast::ArithOp { Add, Sub, Mul, Div, Rem }
+    impl ['a] ast::Condition<'a> { Value, Compare, Not, Logical }
+    impl [] ast::LogicalOp { And, Or }
+    impl ['a] ast::ExprOp<'a> {
+        SubExpr, StringExpr, Match, MatchRegex, Map, Flatten, Filter, FilterMatch, Discard, Join,
+        PathJoin, Split, Lines, Dedup, Secret, Volatile, Info, Warn, Error, AssertEq, AssertMatch,
+    }
+    impl ['a] ast::MatchBody<'a> { Single, Braced }
+    impl ['a] ast::MatchPattern<'a> { Pattern, Regex, Special }
+    impl [] ast::SpecialPattern { Wildcard, EmptyList, NonEmptyList }
+    impl [] ast::CompareOp { Eq, NotEq, Less, LessEq, Greater, GreaterEq }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::parse_werk;
+
+    const SOURCE: &str = r#"# The compiler.
+let cc = "clang"
+
+let cflags = ["-O2", "-Wall"]; let debug = "0"
+
+task build {
+    build "main"
+}
+
+# Compile a file.
+build "%.o" {
+    from "{%}.c"
+    run "{cc} -c <in> -o <out>"
+}
+"#;
+
+    /// Reparse `SOURCE` after replacing `old` with `new`, and check that the
+    /// result is the same as a full parse. Returns whether the statements
+    /// were reparsed incrementally.
+    fn check(old: &str, new: &str) -> bool {
+        let start = SOURCE.find(old).unwrap();
+        let edit = Span::from(start..start + old.len());
+        let source = format!("{}{new}{}", &SOURCE[..start], &SOURCE[start + old.len()..]);
+
+        let previous = parse_werk(Path::new("Werkfile"), SOURCE).unwrap();
+        let incremental = reparse_region(&previous, &source, edit).is_some();
+        let (reparsed, errors) = reparse_werk(previous, &source, edit);
+        let (parsed, expected_errors) = parse_werk_recovering(Path::new("Werkfile"), &source);
+
+        assert_eq!(reparsed.source, source);
+        assert_eq!(reparsed.root, parsed.root);
+        assert_eq!(format!("{:?}", reparsed.root), format!("{:?}", parsed.root));
+        assert_eq!(errors.len(), expected_errors.len());
+        incremental
+    }
+
+    #[test]
+    fn reparse_edit() {
+        assert!(check("# The", "#The"));
+        assert!(check("clang", "gcc"));
+        assert!(check("\"-O2\", ", ""));
+        assert!(check("\"main\"", "[\"main\", \"test\"]"));
+        assert!(check("# Compile", "#    Compile"));
+        assert!(check("<out>\"\n}\n", "<out>\"\n}\n\nlet x = 1\n"));
+    }
+
+    #[test]
+    fn reparse_statements() {
+        // Insert, remove, split, and join statements.
+        assert!(check("\n\ntask build", "\nlet y = cc\n\ntask build"));
+        assert!(check("; let debug = \"0\"", ""));
+        assert!(check("; let", "\nlet"));
+        assert!(check("\"0\"\n", "\"0\"; "));
+        assert!(check(SOURCE, ""));
+    }
+
+    #[test]
+    fn reparse_fallback() {
+        // Syntax errors are reported by a full parse.
+        assert!(!check("let cc", "let cc cc"));
+        assert!(!check("; let", " let"));
+    }
+}
@@ -261,3 +261,125 @@ impl<T: Hash> SemanticHash for SpannedValue<T> {
         self.value.hash(state);
     }
 }
+
+/// Moves all locations by `delta` bytes, for nodes after an edit that changed
+/// the length of the source code. Ignored locations stay ignored.
+pub(crate) trait Shift {
+    fn shift(&mut self, delta: i64);
+}
+
+impl Shift for Offset {
+    #[inline]
+    fn shift(&mut self, delta: i64) {
+        if !self.is_ignored() {
+            self.0 = u32::try_from(i64::from(self.0) + delta).expect("offset out of range");
+        }
+    }
+}
+
+impl Shift for Span {
+    #[inline]
+    fn shift(&mut self, delta: i64) {
+        self.start.shift(delta);
+        self.end.shift(delta);
+    }
+}
+
+impl<T: Shift> Shift for Option<T> {
+    #[inline]
+    fn shift(&mut self, delta: i64) {
+        if let Some(value) = self {
+            value.shift(delta);
+        }
+    }
+}
+
+impl<T: Shift> Shift for Box<T> {
+    #[inline]
+    fn shift(&mut self, delta: i64) {
+        (**self).shift(delta);
+    }
+}
+
+impl<T: Shift> Shift for Vec<T> {
+    #[inline]
+    fn shift(&mut self, delta: i64) {
+        for value in self {
+            value.shift(delta);
+        }
+    }
+}
+
+/// Location information that may be ignored, like the spans of AST nodes that
+/// were constructed rather than parsed. Ignored locations are left out when
+/// serializing.
+#[cfg(feature = "serde")]
+pub trait IsIgnored {
+    fn is_ignored(&self) -> bool;
+}
+
+#[cfg(feature = "serde")]
+impl IsIgnored for Span {
+    #[inline]
+    fn is_ignored(&self) -> bool {
+        Span::is_ignored(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl IsIgnored for Offset {
+    #[inline]
+    fn is_ignored(&self) -> bool {
+        Offset::is_ignored(*self)
+    }
+}
+
+/// Used with `#[serde(skip_serializing_if = "is_ignored")]`.
+#[cfg(feature = "serde")]
+#[inline]
+pub fn is_ignored<T: IsIgnored>(value: &T) -> bool {
+    value.is_ignored()
+}
+
+/// Serialized as `[start, end]`, or `null` when ignored.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Span {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        if self.is_ignored() {
+            ser.serialize_none()
+        } else {
+            ser.serialize_some(&(self.start.0, self.end.0))
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Span {
+    fn deserialize<D: serde::Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        Ok(
+            match <Option<(u32, u32)> as serde::Deserialize>::deserialize(de)? {
+                Some((start, end)) => Span::from(start..end),
+                None => Span::ignore(),
+            },
+        )
+    }
+}
+
+/// Serialized as a number, or `null` when ignored.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Offset {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        if self.is_ignored() {
+            ser.serialize_none()
+        } else {
+            ser.serialize_some(&self.0)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Offset {
+    fn deserialize<D: serde::Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        Ok(<Option<u32> as serde::Deserialize>::deserialize(de)?.map_or(Offset::ignore(), Offset))
+    }
+}
@@ -1,4 +1,4 @@
-use std::{borrow::Cow, fmt::Write, sync::Arc};
+use std::{borrow::Cow, fmt::Write, ops::RangeInclusive, sync::Arc};
 
 use crate::{
     ast::{self, token},
@@ -14,7 +14,7 @@ use winnow::{
         terminated,
     },
     stream::Location,
-    token::{any, one_of, take_till, take_while},
+    token::{any, none_of, one_of, take_till, take_while},
     Parser,
 };
 
@@ -106,7 +106,9 @@ fn pattern_fragment<'a>(input: &mut Input<'a>) -> PResult<StringFragment<'a>> {
     // TODO: Consider escape sequences etc.
     alt((
         '%'.value(StringFragment::PatternStem),
+        pattern_named_capture.map(StringFragment::NamedCapture),
         pattern_one_of.map(StringFragment::OneOf),
+        pattern_char_class.map(StringFragment::CharClass),
         string_literal_fragment::<true>.map(StringFragment::Literal),
         escaped_char.map(StringFragment::EscapedChar),
         escaped_whitespace.value(StringFragment::EscapedWhitespace),
@@ -126,7 +128,10 @@ pub const fn needs_string_escape(ch: char) -> bool {
 #[inline]
 #[must_use]
 pub const fn needs_pattern_escape(ch: char) -> bool {
-    matches!(ch, '\\' | '{' | '}' | '<' | '>' | '%' | '(' | ')' | '"')
+    matches!(
+        ch,
+        '\\' | '{' | '}' | '<' | '>' | '%' | '(' | ')' | '[' | ']' | '"'
+    )
 }
 
 #[inline]
@@ -200,6 +205,8 @@ fn escaped_char(input: &mut Input) -> Result<char, ModalErr> {
         '%' => empty.value('%'),
         '(' => empty.value('('),
         ')' => empty.value(')'),
+        '[' => empty.value('['),
+        ']' => empty.value(']'),
         '"' => empty.value('"'),
         'n' => empty.value('\n'),
         'r' => empty.value('\r'),
@@ -238,6 +245,15 @@ fn ident(input: &mut Input<'_>) -> PResult<Symbol> {
     ident_str.map(Symbol::new).parse_next(input)
 }
 
+/// Identifier optionally qualified by the namespaces of imported Werkfiles,
+/// like `lib.cflags`.
+fn ident_path(input: &mut Input<'_>) -> PResult<Symbol> {
+    (ident_str, repeat::<_, _, (), _, _>(0.., ('.', ident_str)))
+        .take()
+        .map(Symbol::new)
+        .parse_next(input)
+}
+
 #[derive(Debug, Clone)]
 enum StringFragment<'a> {
     Literal(&'a str),
@@ -245,7 +261,9 @@ enum StringFragment<'a> {
     EscapedWhitespace,
     Interpolation(ast::Interpolation<'a>),
     PatternStem,
+    NamedCapture(Symbol),
     OneOf(Vec<Cow<'a, str>>),
+    CharClass(Vec<RangeInclusive<char>>),
 }
 
 fn push_string_fragment<'a>(expr: &mut ast::StringExpr<'a>, frag: StringFragment<'a>) {
@@ -272,7 +290,11 @@ fn push_string_fragment<'a>(expr: &mut ast::StringExpr<'a>, frag: StringFragment
                 .push(ast::StringFragment::Interpolation(string_interpolation));
         }
         StringFragment::PatternStem => panic!("pattern stem in string expr must be escaped"),
-        StringFragment::OneOf(_) => panic!("captue group in string expr must be escaped"),
+        StringFragment::NamedCapture(_)
+        | StringFragment::OneOf(_)
+        | StringFragment::CharClass(_) => {
+            panic!("captue group in string expr must be escaped")
+        }
     }
 }
 
@@ -300,10 +322,56 @@ fn push_pattern_fragment<'a>(expr: &mut ast::PatternExpr<'a>, frag: StringFragme
                 .push(ast::PatternFragment::Interpolation(string_interpolation));
         }
         StringFragment::PatternStem => expr.fragments.push(ast::PatternFragment::PatternStem),
+        StringFragment::NamedCapture(name) => {
+            expr.fragments
+                .push(ast::PatternFragment::NamedCapture(name));
+        }
         StringFragment::OneOf(one_of) => expr.fragments.push(ast::PatternFragment::OneOf(one_of)),
+        StringFragment::CharClass(ranges) => {
+            expr.fragments.push(ast::PatternFragment::CharClass(ranges));
+        }
     }
 }
 
+/// `(%name)`
+fn pattern_named_capture(input: &mut Input<'_>) -> PResult<Symbol> {
+    delimited(
+        ("(%").expect(&"start of named pattern capture"),
+        cut_err(ident),
+        cut_err(parse::<token::ParenClose>),
+    )
+    .while_parsing("named pattern capture")
+    .parse_next(input)
+}
+
+/// `[abc]` or `[a-z]`
+fn pattern_char_class(input: &mut Input<'_>) -> PResult<Vec<RangeInclusive<char>>> {
+    fn class_char(input: &mut Input<'_>) -> PResult<char> {
+        alt((none_of([']', '\\', '"']), escaped_char)).parse_next(input)
+    }
+
+    fn class_range(input: &mut Input<'_>) -> PResult<RangeInclusive<char>> {
+        let location = input.current_token_start();
+        let (start, end) = (class_char, opt(preceded('-', class_char))).parse_next(input)?;
+        let end = end.unwrap_or(start);
+        if start > end {
+            return Err(ModalErr::Error(Error::new(
+                Offset(location as u32),
+                Failure::InvalidCharRange(start, end),
+            )));
+        }
+        Ok(start..=end)
+    }
+
+    delimited(
+        parse::<token::BracketOpen>.expect(&"start of character class"),
+        repeat(1.., class_range).expect(&"characters in character class"),
+        cut_err(parse::<token::BracketClose>),
+    )
+    .while_parsing("character class")
+    .parse_next(input)
+}
+
 fn pattern_one_of<'a>(input: &mut Input<'a>) -> PResult<Vec<Cow<'a, str>>> {
     delimited(
         parse::<token::ParenOpen>.expect(&"start of pattern one-of group"),
@@ -383,11 +451,12 @@ fn interpolation_inner_with_stem<'a, const TERMINATE: char>(
 
 fn interpolation_stem(input: &mut Input) -> PResult<ast::InterpolationStem> {
     alt((
+        preceded('%', ident).map(ast::InterpolationStem::NamedCapture),
         '%'.value(ast::InterpolationStem::PatternCapture),
         digit1
             .try_map(str::parse)
             .map(ast::InterpolationStem::CaptureGroup),
-        ident.map(ast::InterpolationStem::Ident),
+        ident_path.map(ast::InterpolationStem::Ident),
     ))
     .expect(&"one of %, a capture group number, or an identifier")
     .parse_next(input)
@@ -397,6 +466,7 @@ fn interpolation_options<'a>(
     input: &mut Input<'a>,
 ) -> PResult<Option<ast::InterpolationOptions<'a>>> {
     let join = opt(interpolation_join).parse_next(input)?;
+    let location = input.current_token_start();
     let ops = if join.is_some() {
         opt(interpolation_ops).parse_next(input)?
     } else {
@@ -404,6 +474,20 @@ fn interpolation_options<'a>(
         // succeed if there are other options.
         Some(interpolation_ops.parse_next(input)?)
     };
+    let (join, ops) = match ops {
+        Some((ops, Some(join_op))) => {
+            if join.is_some() {
+                // Both `{...*}` and `{...:join="..."}`.
+                return Err(ModalErr::Error(Error::new(
+                    Offset(location as u32),
+                    Failure::InvalidInterpolationOp,
+                )));
+            }
+            (Some(join_op), Some(ops))
+        }
+        Some((ops, None)) => (join, Some(ops)),
+        None => (join, None),
+    };
     Ok(match (join, ops) {
         (None, None) => None,
         (Some(join), None) => Some(ast::InterpolationOptions {
@@ -416,8 +500,7 @@ fn interpolation_options<'a>(
 }
 
 fn interpolation_join<'a>(input: &mut Input<'a>) -> PResult<Cow<'a, str>> {
-    const VALID_JOIN_SEPARATORS: &[char] = &['+', ',', '.', '|', '/', '\\', ':', ';', ' '];
-    let sep: String = terminated(repeat(0.., one_of(VALID_JOIN_SEPARATORS)), '*')
+    let sep: String = terminated(repeat(0.., one_of(ast::VALID_JOIN_SEPARATORS)), '*')
         .expect(&"join separator")
         .parse_next(input)?;
     if sep.is_empty() {
@@ -426,27 +509,88 @@ fn interpolation_join<'a>(input: &mut Input<'a>) -> PResult<Cow<'a, str>> {
     Ok(Cow::Owned(sep))
 }
 
+/// Either an interpolation operation, or the `join="..."` option, which is
+/// stored separately from the other operations.
+enum InterpolationOpOrJoin<'a> {
+    Op(ast::InterpolationOp<'a>),
+    Join(Cow<'a, str>),
+}
+
 // At least one interpolation option
-fn interpolation_ops<'a>(input: &mut Input<'a>) -> PResult<Vec<ast::InterpolationOp<'a>>> {
-    preceded(
+fn interpolation_ops<'a>(
+    input: &mut Input<'a>,
+) -> PResult<(Vec<ast::InterpolationOp<'a>>, Option<Cow<'a, str>>)> {
+    let location = input.current_token_start();
+    let ops: Vec<_> = preceded(
         ':'.expect(&"interpolation options"),
         separated(0.., interpolation_op, ','),
     )
-    .parse_next(input)
+    .parse_next(input)?;
+
+    let mut join = None;
+    let mut result = Vec::with_capacity(ops.len());
+    for op in ops {
+        match op {
+            InterpolationOpOrJoin::Op(op) => result.push(op),
+            InterpolationOpOrJoin::Join(sep) => {
+                if join.replace(sep).is_some() {
+                    return Err(ModalErr::Error(Error::new(
+                        Offset(location as u32),
+                        Failure::InvalidInterpolationOp,
+                    )));
+                }
+            }
+        }
+    }
+    Ok((result, join))
 }
 
-fn interpolation_op<'a>(input: &mut Input<'a>) -> PResult<ast::InterpolationOp<'a>> {
+fn interpolation_op<'a>(input: &mut Input<'a>) -> PResult<InterpolationOpOrJoin<'a>> {
     alt((
-        interpolation_op_replace_ext.map(|(from, to)| ast::InterpolationOp::ReplaceExtension {
-            from: Cow::from(from),
-            to: Cow::from(to),
+        interpolation_op_replace_ext.map(|(from, to)| {
+            InterpolationOpOrJoin::Op(ast::InterpolationOp::ReplaceExtension {
+                from: Cow::from(from),
+                to: Cow::from(to),
+            })
         }),
-        interpolation_op_regex_replace.map(ast::InterpolationOp::RegexReplace),
-        interpolation_op_kw,
+        interpolation_op_regex_replace
+            .map(|op| InterpolationOpOrJoin::Op(ast::InterpolationOp::RegexReplace(op))),
+        interpolation_op_join.map(InterpolationOpOrJoin::Join),
+        interpolation_op_kw.map(InterpolationOpOrJoin::Op),
     ))
     .parse_next(input)
 }
 
+/// `join="sep"`, where the separator may contain escape sequences.
+fn interpolation_op_join<'a>(input: &mut Input<'a>) -> PResult<Cow<'a, str>> {
+    preceded(
+        "join=",
+        delimited(
+            '"'.expect(&"quoted join separator"),
+            repeat(
+                0..,
+                alt((
+                    take_till(1.., ['"', '\\']).map(Cow::Borrowed),
+                    escaped_char.map(|ch| Cow::Owned(ch.to_string())),
+                )),
+            )
+            .fold(
+                || Cow::Borrowed(""),
+                |mut acc: Cow<'a, str>, part: Cow<'a, str>| {
+                    if acc.is_empty() {
+                        acc = part;
+                    } else {
+                        acc.to_mut().push_str(&part);
+                    }
+                    acc
+                },
+            ),
+            cut_err('"'),
+        ),
+    )
+    .parse_next(input)
+}
+
 fn interpolation_op_kw<'a>(input: &mut Input<'a>) -> PResult<ast::InterpolationOp<'a>> {
     let location = input.current_token_start();
     let ident = ident_str.parse_next(input)?;
@@ -457,10 +601,12 @@ fn interpolation_op_kw<'a>(input: &mut Input<'a>) -> PResult<ast::InterpolationO
         "ext" => Ok(ast::InterpolationOp::Ext),
         "out-dir" => Ok(ast::InterpolationOp::ResolveOutDir),
         "workspace" => Ok(ast::InterpolationOp::ResolveWorkspace),
-        _ => Err(ModalErr::Error(Error::new(
-            Offset(location as u32),
-            Failure::InvalidInterpolationOp,
-        ))),
+        _ => ast::QuoteStyle::from_name(ident)
+            .map(ast::InterpolationOp::Quote)
+            .ok_or(ModalErr::Error(Error::new(
+                Offset(location as u32),
+                Failure::InvalidInterpolationOp,
+            ))),
     }
 }
 
@@ -879,5 +1025,78 @@ mod tests {
                 })),
             }
         );
+
+        let join_op = r#"{name:.c=.o,join=" and "}"#;
+        let expected = ast::Interpolation {
+            stem: ast::InterpolationStem::Ident("name".into()),
+            options: Some(Box::new(ast::InterpolationOptions {
+                ops: vec![ast::InterpolationOp::ReplaceExtension {
+                    from: Cow::from(".c"),
+                    to: Cow::from(".o"),
+                }],
+                join: Some(Cow::from(" and ")),
+            })),
+        };
+        assert_eq!(
+            string_interpolation.parse(Input::new(join_op)).unwrap(),
+            expected
+        );
+        assert_eq!(expected.to_string(), join_op);
+
+        let join_op_escaped = r#"{name:join="\"\t"}"#;
+        assert_eq!(
+            string_interpolation
+                .parse(Input::new(join_op_escaped))
+                .unwrap()
+                .join(),
+            Some("\"\t")
+        );
+
+        // Only one join operator is allowed.
+        assert!(string_interpolation
+            .parse(Input::new(r#"{name,*:join=";"}"#))
+            .is_err());
+    }
+
+    #[test]
+    fn pattern_captures() {
+        let pattern = parse_pattern_expr_unquoted(r"(%dir)/%.[ch\]-]").unwrap();
+        assert_eq!(
+            pattern.fragments,
+            [
+                ast::PatternFragment::NamedCapture(Symbol::new("dir")),
+                ast::PatternFragment::Literal("/".into()),
+                ast::PatternFragment::PatternStem,
+                ast::PatternFragment::Literal(".".into()),
+                ast::PatternFragment::CharClass(vec!['c'..='c', 'h'..='h', ']'..=']', '-'..='-']),
+            ]
+        );
+        assert_eq!(pattern.to_string(), r"(%dir)/%.[-ch\]]");
+
+        let pattern = parse_pattern_expr_unquoted("[a-z0-9_]").unwrap();
+        assert_eq!(
+            pattern.fragments,
+            [ast::PatternFragment::CharClass(vec![
+                'a'..='z',
+                '0'..='9',
+                '_'..='_'
+            ])]
+        );
+        assert_eq!(
+            parse_pattern_expr_unquoted(r"\[a\]").unwrap().fragments,
+            [ast::PatternFragment::Literal("[a]".into())]
+        );
+
+        assert!(parse_pattern_expr_unquoted("[]").is_err());
+        assert!(parse_pattern_expr_unquoted("[z-a]").is_err());
+        assert!(parse_pattern_expr_unquoted("(%)").is_err());
+
+        assert_eq!(
+            string_interpolation.parse(Input::new("{%dir}")).unwrap(),
+            ast::Interpolation {
+                stem: ast::InterpolationStem::NamedCapture(Symbol::new("dir")),
+                options: None,
+            }
+        );
     }
 }
@@ -98,6 +98,17 @@ macro_rules! success_case {
                 eprintln!("Expected:\n{}", expected_json);
                 panic!("AST mismatch");
             }
+
+            // The parsed AST has spans, which must survive a round-trip.
+            let input_json = serde_json::to_string(&input.root).unwrap();
+            let roundtrip = serde_json::from_str::<ast::Root>(&input_json).unwrap();
+            let roundtrip_json = serde_json::to_string(&roundtrip).unwrap();
+            if roundtrip_json != input_json {
+                eprintln!("Round-trip mismatch!");
+                eprintln!("Got:\n{}\n", roundtrip_json);
+                eprintln!("Expected:\n{}", input_json);
+                panic!("round-trip mismatch");
+            }
         }
     };
 }
@@ -112,6 +123,11 @@ error_case!(task_string_name);
 error_case!(build_ident_name);
 error_case!(match_unterminated);
 error_case!(match_no_arrow);
+error_case!(misplaced_attribute);
+error_case!(profile_not_let);
+error_case!(misspelled_keyword);
+error_case!(misspelled_config_key);
+error_case!(match_regex_invalid);
 
 success_case!(c);
 success_case!(config);
@@ -122,3 +138,16 @@ success_case!(let_match_inline);
 success_case!(let_map);
 success_case!(let_list);
 success_case!(expr_parens);
+success_case!(attributes);
+success_case!(pool);
+success_case!(out);
+success_case!(redirect);
+success_case!(pipeline);
+success_case!(run_shell);
+success_case!(source_dirs);
+success_case!(tool_paths);
+success_case!(is_overridden);
+success_case!(profile);
+success_case!(glob_out);
+success_case!(arith);
+success_case!(if_expr);
@@ -9,6 +9,36 @@ pub struct WerkCache {
     /// Per-build-target caches.
     #[serde(default)]
     pub build: BTreeMap<Absolute<werk_fs::PathBuf>, TargetOutdatednessCache>,
+    /// Recorded output of `shell` expressions in global variables with a
+    /// `#[cache]` attribute, keyed by the command line.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub shell: BTreeMap<String, RecordedShellOutput>,
+    /// Targets recently built from the command line, most recent first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recent: Vec<String>,
+}
+
+/// The output of a `shell` expression recorded in `.werk-cache`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecordedShellOutput {
+    pub stdout: String,
+    /// When the command ran, in seconds since the Unix epoch.
+    pub recorded_at: u64,
+    /// The `#[cache-key]` of the variable when the command ran.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+impl RecordedShellOutput {
+    /// Whether the recorded output can be used instead of running the command
+    /// again at time `now` (in seconds since the Unix epoch).
+    #[must_use]
+    pub fn is_fresh(&self, policy: &crate::ir::ShellCache, now: u64) -> bool {
+        self.key == policy.key
+            && policy
+                .ttl
+                .is_none_or(|ttl| now.saturating_sub(self.recorded_at) < ttl.as_secs())
+    }
 }
 
 /// Per-target cache of used outdatedness information.
@@ -31,6 +61,26 @@ pub struct TargetOutdatednessCache {
     /// Hash of `define` variables.
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub define: BTreeMap<Symbol, Hash128>,
+    /// Hash of the evaluated commands and environment of the recipe.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commands_hash: Option<Hash128>,
+    /// Hash of the contents of the output file when it was last built.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_hash: Option<Hash128>,
+    /// How long the commands of the recipe took when they last ran, in
+    /// milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// The estimated time from the start of the recipe to the end of the
+    /// build in the last run, in milliseconds. Recipes with longer critical
+    /// paths are scheduled first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub critical_path_ms: Option<u64>,
+}
+
+/// Convert a duration to whole milliseconds, as stored in the cache.
+pub(crate) fn duration_to_millis(duration: std::time::Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
 }
 
 impl TargetOutdatednessCache {
@@ -39,6 +89,12 @@ impl TargetOutdatednessCache {
         self.recipe_hash != new_hash
     }
 
+    #[inline]
+    pub fn is_commands_outdated(&self, new_hash: Hash128) -> bool {
+        self.commands_hash
+            .is_some_and(|old_hash| old_hash != new_hash)
+    }
+
     #[inline]
     pub fn is_glob_outdated(&self, glob: Symbol, new_hash: Hash128) -> bool {
         self.glob
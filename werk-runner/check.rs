@@ -0,0 +1,772 @@
+//! Static analysis of Werkfiles, used by `werk check` and by strict mode
+//! (`config strict = true` or `--strict`).
+//!
+//! [`check_document()`] runs on the AST before anything is evaluated:
+//! references to undefined identifiers are errors, even in recipes that are
+//! never run, and likely mistakes are reported as [`Warning`]s.
+//! [`check_build_recipes()`] runs on the evaluated patterns of build recipes.
+
+use ahash::HashMap;
+use werk_parser::{
+    ast,
+    parser::{Span, Spanned as _},
+    Document,
+};
+use werk_util::{DiagnosticFileId, DiagnosticSnippet, Symbol};
+
+use crate::{
+    compute_stable_semantic_hash, default_global_constants, import_file_id, ir, qualify, EvalError,
+    Pattern, SymCache,
+};
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Warning {
+    #[error("unused variable `{1}`")]
+    UnusedVariable(Span, Symbol),
+    #[error("unreachable match arm")]
+    UnreachableMatchArm(Span, Span),
+    #[error("list `{1}` is interpolated without a separator; only its first item is used")]
+    ListInterpolation(Span, Symbol),
+    #[error("build recipe pattern can never match a target path")]
+    UnmatchablePattern(Span),
+    #[error(
+        "build recipe is never used; a recipe with a higher priority matches all of its targets"
+    )]
+    ShadowedRecipe(Span, DiagnosticFileId, Span),
+    #[error("build recipe is never used; its `exclude` pattern matches all of its targets")]
+    ExcludedRecipe(Span, Span),
+    #[error(
+        "`match` has no catch-all arm; values that no arm matches are passed through unchanged"
+    )]
+    NonExhaustiveMatch(Span),
+    #[error("{1}")]
+    Imported(DiagnosticFileId, Box<Warning>),
+}
+
+impl Warning {
+    /// Attribute the warning to an imported Werkfile. See
+    /// [`EvalError::in_file()`].
+    #[must_use]
+    pub fn in_file(self, file: DiagnosticFileId) -> Self {
+        if file == DiagnosticFileId::default() || matches!(self, Warning::Imported(..)) {
+            self
+        } else {
+            Warning::Imported(file, Box::new(self))
+        }
+    }
+
+    #[must_use]
+    pub fn file(&self) -> DiagnosticFileId {
+        match self {
+            Warning::Imported(file, _) => *file,
+            _ => DiagnosticFileId::default(),
+        }
+    }
+
+    fn context_snippets_in(&self, file: DiagnosticFileId) -> Vec<DiagnosticSnippet> {
+        match self {
+            Warning::Imported(file, warning) => warning.context_snippets_in(*file),
+            Warning::UnreachableMatchArm(_, earlier) => vec![DiagnosticSnippet {
+                file_id: file,
+                span: (*earlier).into(),
+                message: String::from("this arm matches the same values"),
+                info: vec![],
+            }],
+            Warning::ShadowedRecipe(_, other_file, other) => vec![DiagnosticSnippet {
+                file_id: *other_file,
+                span: (*other).into(),
+                message: String::from("this recipe is chosen instead"),
+                info: vec![],
+            }],
+            Warning::ExcludedRecipe(_, exclude) => vec![DiagnosticSnippet {
+                file_id: file,
+                span: (*exclude).into(),
+                message: String::from("this pattern excludes every target"),
+                info: vec![],
+            }],
+            _ => vec![],
+        }
+    }
+}
+
+impl werk_parser::parser::Spanned for Warning {
+    fn span(&self) -> Span {
+        match self {
+            Warning::UnusedVariable(span, _)
+            | Warning::UnreachableMatchArm(span, _)
+            | Warning::ListInterpolation(span, _)
+            | Warning::UnmatchablePattern(span)
+            | Warning::ShadowedRecipe(span, ..)
+            | Warning::ExcludedRecipe(span, _)
+            | Warning::NonExhaustiveMatch(span) => *span,
+            Warning::Imported(_, warning) => warning.span(),
+        }
+    }
+}
+
+impl werk_util::Diagnostic for Warning {
+    fn id_prefix(&self) -> &'static str {
+        "W"
+    }
+
+    fn level(&self) -> annotate_snippets::Level {
+        annotate_snippets::Level::Warning
+    }
+
+    fn id(&self) -> u32 {
+        match self {
+            Warning::UnusedVariable(..) => 1,
+            Warning::UnreachableMatchArm(..) => 2,
+            Warning::ListInterpolation(..) => 3,
+            Warning::UnmatchablePattern(..) => 4,
+            Warning::ShadowedRecipe(..) => 5,
+            Warning::ExcludedRecipe(..) => 6,
+            Warning::NonExhaustiveMatch(..) => 7,
+            Warning::Imported(_, warning) => warning.id(),
+        }
+    }
+
+    fn title(&self) -> String {
+        self.to_string()
+    }
+
+    fn snippet(&self) -> Option<DiagnosticSnippet> {
+        use werk_parser::parser::Spanned;
+        Some(DiagnosticSnippet {
+            file_id: self.file(),
+            span: self.span().into(),
+            message: self.to_string(),
+            info: vec![],
+        })
+    }
+
+    fn context_snippets(&self) -> Vec<DiagnosticSnippet> {
+        self.context_snippets_in(DiagnosticFileId::default())
+    }
+
+    fn help(&self) -> Vec<String> {
+        match self {
+            Warning::Imported(_, warning) => warning.help(),
+            Warning::UnreachableMatchArm(..) => vec![String::from(
+                "match arms are tried in order; move this arm before the arm that matches the same values",
+            )],
+            Warning::ListInterpolation(_, name) => vec![format!(
+                "use `{{{name}*}}` to join the items with spaces, or `{{{name},*}}` to join them with commas"
+            )],
+            Warning::UnmatchablePattern(..) => vec![String::from(
+                "targets are normalized paths, without `.` or `..` components, empty components, or a trailing separator",
+            )],
+            Warning::ShadowedRecipe(..) => vec![String::from(
+                "use `priority` to choose between recipes whose patterns match the same targets",
+            )],
+            Warning::NonExhaustiveMatch(..) => vec![String::from(
+                "add a `_` arm to handle other values, like `_ => \"{}\"` to keep them, or `_ => error \"...\"`",
+            )],
+            Warning::UnusedVariable(..) | Warning::ExcludedRecipe(..) => vec![],
+        }
+    }
+}
+
+/// Check all identifiers referenced in the main Werkfile and its imports, and
+/// look for likely mistakes.
+///
+/// Unused global variables of imported Werkfiles are not reported, since they
+/// may be intended for other Werkfiles importing them.
+pub fn check_document(ast: &Document<'_>) -> Result<Vec<Warning>, EvalError> {
+    let mut checker = Checker::default();
+
+    // Global variables are evaluated in order, imported Werkfiles first.
+    for (index, import) in ast.imports.iter().enumerate() {
+        let file = import_file_id(index);
+        checker
+            .check_globals(&import.document, Some(import.namespace), file)
+            .map_err(|err| err.in_file(file))?;
+    }
+    checker.check_globals(ast, None, DiagnosticFileId::default())?;
+
+    // Recipes are evaluated when they are run, when all global variables
+    // exist.
+    for (index, import) in ast.imports.iter().enumerate() {
+        let file = import_file_id(index);
+        checker
+            .check_recipes(&import.document, Some(import.namespace), file)
+            .map_err(|err| err.in_file(file))?;
+    }
+    checker.check_recipes(ast, None, DiagnosticFileId::default())?;
+
+    let mut warnings = checker.warnings;
+    warnings.extend(
+        checker
+            .bindings
+            .into_iter()
+            .filter(|binding| binding.report && !binding.used)
+            .map(|binding| {
+                Warning::UnusedVariable(binding.span, binding.name).in_file(binding.file)
+            }),
+    );
+    Ok(warnings)
+}
+
+/// Check the evaluated patterns of build recipes, looking for recipes that can
+/// never be chosen to build a target.
+#[must_use]
+pub fn check_build_recipes(manifest: &ir::Manifest<'_>) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    for recipe in &manifest.build_recipes {
+        let warning = if !can_match_target(&recipe.pattern) {
+            Warning::UnmatchablePattern(recipe.pattern.span)
+        } else if let Some(exclude) = recipe
+            .excludes
+            .iter()
+            .find(|exclude| exclude.contains(&recipe.pattern))
+        {
+            Warning::ExcludedRecipe(recipe.pattern.span, exclude.span)
+        } else if let Some(other) = manifest.build_recipes.iter().find(|other| {
+            // A recipe with exclusions may leave some of the paths to others.
+            other.priority > recipe.priority
+                && other.excludes.is_empty()
+                && other.pattern.contains(&recipe.pattern)
+        }) {
+            Warning::ShadowedRecipe(recipe.pattern.span, other.file, other.pattern.span)
+        } else {
+            continue;
+        };
+        warnings.push(warning.in_file(recipe.file));
+    }
+    warnings
+}
+
+/// True if the pattern matches at least one normalized target path. Patterns
+/// with a stem are tried with a couple of ordinary stems.
+fn can_match_target(pattern: &Pattern) -> bool {
+    let candidates = pattern.expand().unwrap_or_else(|| {
+        ["x", "stem"]
+            .iter()
+            .flat_map(|stem| pattern.expand_with_stem(stem))
+            .collect()
+    });
+    candidates
+        .iter()
+        .any(|candidate| werk_fs::Path::new(candidate).is_ok_and(werk_fs::Path::is_normalized))
+}
+
+/// What an expression evaluates to, as far as it can be known without
+/// evaluating it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    String,
+    List,
+    Unknown,
+}
+
+struct Binding {
+    name: Symbol,
+    file: DiagnosticFileId,
+    span: Span,
+    shape: Shape,
+    used: bool,
+    /// Report the binding if it is unused.
+    report: bool,
+}
+
+#[derive(Default)]
+struct Checker {
+    /// All variables seen so far.
+    bindings: Vec<Binding>,
+    /// Global variables declared so far, by qualified name.
+    globals: HashMap<Symbol, usize>,
+    /// Variables in the recipe being checked, in declaration order.
+    locals: Vec<(Symbol, usize)>,
+    /// Whether a recipe is being checked, where `tmp` is available.
+    in_recipe: bool,
+    namespace: Option<Symbol>,
+    file: DiagnosticFileId,
+    warnings: Vec<Warning>,
+}
+
+impl Checker {
+    fn check_globals(
+        &mut self,
+        ast: &Document<'_>,
+        namespace: Option<Symbol>,
+        file: DiagnosticFileId,
+    ) -> Result<(), EvalError> {
+        self.namespace = namespace;
+        self.file = file;
+
+        for stmt in &ast.root.statements {
+            match stmt.statement {
+                ast::RootStmt::Let(ref let_stmt) => {
+                    let mut shape = self.expr_chain(&let_stmt.value)?;
+                    // Profiles replace global variables in the main Werkfile,
+                    // so their values are evaluated in the same scope.
+                    if namespace.is_none() {
+                        for profile in ast.profile_stmts() {
+                            if let Some(replaced) =
+                                profile.find_global(let_stmt.ident.ident.as_str())
+                            {
+                                if self.expr_chain(&replaced.value)? != shape {
+                                    shape = Shape::Unknown;
+                                }
+                            }
+                        }
+                    }
+                    let index = self.bind(
+                        let_stmt.ident.ident,
+                        let_stmt.ident.span,
+                        shape,
+                        namespace.is_none(),
+                    );
+                    self.globals
+                        .insert(qualify(namespace, let_stmt.ident.ident), index);
+                }
+                // Patterns are evaluated when the recipe is declared.
+                ast::RootStmt::Build(ref recipe) => {
+                    self.pattern_expr(&recipe.pattern)?;
+                    for exclude in ir::BuildRecipe::excludes_of(recipe) {
+                        self.pattern_expr(exclude)?;
+                    }
+                }
+                ast::RootStmt::Config(_)
+                | ast::RootStmt::Import(_)
+                | ast::RootStmt::Member(_)
+                | ast::RootStmt::Profile(_)
+                | ast::RootStmt::Task(_) => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_recipes(
+        &mut self,
+        ast: &Document<'_>,
+        namespace: Option<Symbol>,
+        file: DiagnosticFileId,
+    ) -> Result<(), EvalError> {
+        self.namespace = namespace;
+        self.file = file;
+        self.in_recipe = true;
+
+        for stmt in &ast.root.statements {
+            self.locals.clear();
+            match stmt.statement {
+                ast::RootStmt::Task(ref recipe) => {
+                    for stmt in &recipe.body.statements {
+                        self.task_recipe_stmt(&stmt.statement)?;
+                    }
+                }
+                ast::RootStmt::Build(ref recipe) => {
+                    let sym = SymCache::get();
+                    // `in` is a list, but usually holds a single file.
+                    self.bind_builtin(sym.symbol_in, Shape::Unknown);
+                    self.bind_builtin(sym.symbol_out, Shape::String);
+                    for stmt in &recipe.body.statements {
+                        self.build_recipe_stmt(&stmt.statement)?;
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        self.in_recipe = false;
+        Ok(())
+    }
+
+    fn bind(&mut self, name: Symbol, span: Span, shape: Shape, report: bool) -> usize {
+        self.bindings.push(Binding {
+            name,
+            file: self.file,
+            span,
+            shape,
+            used: false,
+            report,
+        });
+        self.bindings.len() - 1
+    }
+
+    fn bind_local(&mut self, ident: &ast::Ident, shape: Shape) {
+        let index = self.bind(ident.ident, ident.span, shape, true);
+        self.locals.push((ident.ident, index));
+    }
+
+    /// Built-in recipe variables, like `in` and `out`.
+    fn bind_builtin(&mut self, name: Symbol, shape: Shape) {
+        let index = self.bind(name, Span::default(), shape, false);
+        self.locals.push((name, index));
+    }
+
+    fn warn(&mut self, warning: Warning) {
+        self.warnings.push(warning.in_file(self.file));
+    }
+
+    /// Look up an identifier in the same way as the evaluator: recipe
+    /// variables, then global variables, then built-in constants.
+    fn reference(&mut self, name: Symbol, span: Span) -> Result<Shape, EvalError> {
+        let local = self.locals.iter().rev().find(|(local, _)| *local == name);
+        let global = || self.globals.get(&qualify(self.namespace, name));
+        if let Some(&index) = local.map(|(_, index)| index).or_else(global) {
+            let binding = &mut self.bindings[index];
+            binding.used = true;
+            return Ok(binding.shape);
+        }
+
+        let sym = SymCache::get();
+        if default_global_constants().contains_key(&name)
+            || name == sym.symbol_color
+            || (self.in_recipe && name == sym.symbol_tmp)
+        {
+            return Ok(Shape::String);
+        }
+
+        Err(EvalError::NoSuchIdentifier(
+            span,
+            name.to_string(),
+            self.suggest(name),
+        ))
+    }
+
+    /// Suggest a visible identifier similar to `name`, like
+    /// [`Scope::visible_names()`](crate::Scope::visible_names).
+    fn suggest(&self, name: Symbol) -> Option<Symbol> {
+        let prefix = self.namespace.map(|namespace| format!("{namespace}."));
+        let mut globals = self
+            .globals
+            .iter()
+            .filter_map(|(global, &index)| {
+                let global = match prefix {
+                    Some(ref prefix) => global.as_str().strip_prefix(prefix.as_str())?,
+                    None => global.as_str(),
+                };
+                (!global.contains('.')).then(|| (index, Symbol::new(global)))
+            })
+            .collect::<Vec<_>>();
+        globals.sort_unstable_by_key(|(index, _)| *index);
+
+        let mut constants = default_global_constants()
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        constants.sort_unstable_by_key(|name| name.as_str());
+
+        let names = self
+            .locals
+            .iter()
+            .rev()
+            .map(|(local, _)| *local)
+            .chain(globals.into_iter().map(|(_, global)| global))
+            .chain(self.in_recipe.then_some(SymCache::get().symbol_tmp))
+            .chain(constants)
+            .chain([SymCache::get().symbol_color])
+            .collect::<Vec<_>>();
+        werk_util::did_you_mean(name.as_str(), &names).copied()
+    }
+
+    fn task_recipe_stmt(&mut self, stmt: &ast::TaskRecipeStmt<'_>) -> Result<(), EvalError> {
+        match stmt {
+            ast::TaskRecipeStmt::Let(let_stmt) => {
+                let shape = self.expr_chain(&let_stmt.value)?;
+                self.bind_local(&let_stmt.ident, shape);
+                Ok(())
+            }
+            ast::TaskRecipeStmt::Build(expr) => self.expr_chain(&expr.param).map(drop),
+            ast::TaskRecipeStmt::Run(stmt) => self.run_expr(&stmt.param),
+            ast::TaskRecipeStmt::RunShell(stmt) => self.string_expr(&stmt.script),
+            ast::TaskRecipeStmt::Info(ast::KwExpr { param, .. })
+            | ast::TaskRecipeStmt::Warn(ast::KwExpr { param, .. })
+            | ast::TaskRecipeStmt::EnvRemove(ast::KwExpr { param, .. })
+            | ast::TaskRecipeStmt::Timeout(ast::KwExpr { param, .. })
+            | ast::TaskRecipeStmt::InDir(ast::KwExpr { param, .. }) => self.string_expr(param),
+            ast::TaskRecipeStmt::Env(stmt) => self.env_stmt(stmt),
+            ast::TaskRecipeStmt::SetCapture(_)
+            | ast::TaskRecipeStmt::SetNoCapture(_)
+            | ast::TaskRecipeStmt::Pool(_) => Ok(()),
+        }
+    }
+
+    fn build_recipe_stmt(&mut self, stmt: &ast::BuildRecipeStmt<'_>) -> Result<(), EvalError> {
+        match stmt {
+            ast::BuildRecipeStmt::Let(let_stmt) => {
+                let shape = self.expr_chain(&let_stmt.value)?;
+                self.bind_local(&let_stmt.ident, shape);
+                Ok(())
+            }
+            ast::BuildRecipeStmt::From(expr) => self.expr_chain(&expr.param).map(drop),
+            ast::BuildRecipeStmt::Depfile(expr) => {
+                self.expr_chain(&expr.param)?;
+                self.bind_builtin(Symbol::from("depfile"), Shape::String);
+                Ok(())
+            }
+            ast::BuildRecipeStmt::Run(stmt) => self.run_expr(&stmt.param),
+            ast::BuildRecipeStmt::RunShell(stmt) => self.string_expr(&stmt.script),
+            ast::BuildRecipeStmt::Info(ast::KwExpr { param, .. })
+            | ast::BuildRecipeStmt::Warn(ast::KwExpr { param, .. })
+            | ast::BuildRecipeStmt::EnvRemove(ast::KwExpr { param, .. })
+            | ast::BuildRecipeStmt::Timeout(ast::KwExpr { param, .. })
+            | ast::BuildRecipeStmt::InDir(ast::KwExpr { param, .. })
+            | ast::BuildRecipeStmt::Out(ast::KwExpr { param, .. })
+            | ast::BuildRecipeStmt::Container(ast::KwExpr { param, .. }) => self.string_expr(param),
+            ast::BuildRecipeStmt::Env(stmt) => self.env_stmt(stmt),
+            ast::BuildRecipeStmt::SetCapture(_)
+            | ast::BuildRecipeStmt::SetNoCapture(_)
+            | ast::BuildRecipeStmt::Priority(_)
+            | ast::BuildRecipeStmt::Exclude(_)
+            | ast::BuildRecipeStmt::Pool(_)
+            | ast::BuildRecipeStmt::Host(_) => Ok(()),
+        }
+    }
+
+    fn env_stmt(&mut self, stmt: &ast::EnvStmt<'_>) -> Result<(), EvalError> {
+        self.string_expr(&stmt.key)?;
+        self.string_expr(&stmt.value)
+    }
+
+    fn run_expr(&mut self, expr: &ast::RunExpr<'_>) -> Result<(), EvalError> {
+        match expr {
+            ast::RunExpr::Shell(expr) => self.string_expr(&expr.param),
+            ast::RunExpr::Pipeline(pipeline) => {
+                self.string_expr(&pipeline.command.param)?;
+                if let Some(ref stdin) = pipeline.stdin {
+                    self.string_expr(&stdin.path)?;
+                }
+                for pipe in &pipeline.pipes {
+                    self.string_expr(&pipe.command.param)?;
+                }
+                if let Some(ref stdout) = pipeline.stdout {
+                    self.string_expr(&stdout.path)?;
+                }
+                Ok(())
+            }
+            ast::RunExpr::Write(expr) => {
+                self.expr(&expr.value)?;
+                self.expr(&expr.path).map(drop)
+            }
+            ast::RunExpr::Copy(expr) => {
+                self.string_expr(&expr.src)?;
+                self.string_expr(&expr.dest)
+            }
+            ast::RunExpr::Extract(expr) => {
+                self.string_expr(&expr.archive)?;
+                self.string_expr(&expr.dest)
+            }
+            ast::RunExpr::Fetch(expr) => {
+                self.string_expr(&expr.url)?;
+                self.string_expr(&expr.sha256)?;
+                self.string_expr(&expr.dest)
+            }
+            ast::RunExpr::Delete(expr) => self.expr(&expr.param).map(drop),
+            ast::RunExpr::Mkdir(expr) => self.expr(&expr.param).map(drop),
+            ast::RunExpr::Touch(expr) => self.expr(&expr.param).map(drop),
+            ast::RunExpr::Args(expr) => self.expr(&expr.param).map(drop),
+            ast::RunExpr::Env(stmt) => self.env_stmt(stmt),
+            ast::RunExpr::EnvRemove(ast::KwExpr { param, .. })
+            | ast::RunExpr::Timeout(ast::KwExpr { param, .. })
+            | ast::RunExpr::Info(ast::KwExpr { param, .. })
+            | ast::RunExpr::Warn(ast::KwExpr { param, .. }) => self.string_expr(param),
+            ast::RunExpr::List(list) => list
+                .items
+                .iter()
+                .try_for_each(|item| self.run_expr(&item.item)),
+            ast::RunExpr::Block(block) => block
+                .statements
+                .iter()
+                .try_for_each(|stmt| self.run_expr(&stmt.statement)),
+        }
+    }
+
+    fn expr_chain(&mut self, chain: &ast::ExprChain<'_>) -> Result<Shape, EvalError> {
+        let mut shape = self.expr(&chain.expr)?;
+        for op in &chain.ops {
+            shape = self.expr_op(&op.expr, shape)?;
+        }
+        Ok(shape)
+    }
+
+    fn expr(&mut self, expr: &ast::Expr<'_>) -> Result<Shape, EvalError> {
+        match expr {
+            ast::Expr::Ident(ident) => self.reference(ident.ident, ident.span),
+            ast::Expr::IsOverridden(expr) => self
+                .reference(expr.param.ident, expr.param.span)
+                .map(|_| Shape::String),
+            ast::Expr::StringExpr(expr) => self.string_expr(expr).map(|()| Shape::String),
+            ast::Expr::Shell(ast::KwExpr { param, .. })
+            | ast::Expr::Read(ast::KwExpr { param, .. })
+            | ast::Expr::Which(ast::KwExpr { param, .. })
+            | ast::Expr::Env(ast::KwExpr { param, .. }) => {
+                self.string_expr(param).map(|()| Shape::String)
+            }
+            ast::Expr::Glob(ast::KwExpr { param, .. })
+            | ast::Expr::GlobOut(ast::KwExpr { param, .. }) => {
+                self.expr(param).map(|_| Shape::List)
+            }
+            ast::Expr::Error(ast::KwExpr { param, .. }) => {
+                self.string_expr(param).map(|()| Shape::Unknown)
+            }
+            ast::Expr::List(list) => {
+                for item in &list.items {
+                    self.expr_chain(&item.item)?;
+                }
+                Ok(Shape::List)
+            }
+            ast::Expr::SubExpr(expr) => self.expr_chain(&expr.expr),
+            ast::Expr::Hash(expr) => self.expr(&expr.param).map(|_| Shape::String),
+            ast::Expr::Int(_) | ast::Expr::CpuCount(_) => Ok(Shape::String),
+            ast::Expr::Arith(expr) => {
+                self.expr(&expr.lhs)?;
+                self.expr(&expr.rhs)?;
+                Ok(Shape::String)
+            }
+            ast::Expr::If(expr) => {
+                self.condition(&expr.condition)?;
+                let then_shape = self.expr(&expr.then_expr)?;
+                let else_shape = self.expr(&expr.else_expr)?;
+                Ok(if then_shape == else_shape {
+                    then_shape
+                } else {
+                    Shape::Unknown
+                })
+            }
+        }
+    }
+
+    fn condition(&mut self, condition: &ast::Condition<'_>) -> Result<(), EvalError> {
+        match condition {
+            ast::Condition::Value(expr) => self.expr(expr).map(|_| ()),
+            ast::Condition::Compare(cmp) => {
+                self.expr(&cmp.lhs)?;
+                self.expr(&cmp.rhs).map(|_| ())
+            }
+            ast::Condition::Not(not) => self.condition(&not.param),
+            ast::Condition::Logical(logical) => {
+                self.condition(&logical.lhs)?;
+                self.condition(&logical.rhs)
+            }
+        }
+    }
+
+    /// Check an operation in an expression chain, returning the shape of its
+    /// result given the shape of its input.
+    fn expr_op(&mut self, op: &ast::ExprOp<'_>, input: Shape) -> Result<Shape, EvalError> {
+        match op {
+            ast::ExprOp::SubExpr(expr) => self.expr_chain(&expr.expr).map(|_| Shape::Unknown),
+            ast::ExprOp::StringExpr(expr) => self.string_expr(expr).map(|()| Shape::Unknown),
+            ast::ExprOp::Match(ast::KwExpr { token, param, .. }) => self
+                .match_body(param, Some(token.span()))
+                .map(|()| Shape::Unknown),
+            ast::ExprOp::MatchRegex(ast::KwExpr { token, param, .. }) => self
+                .match_body(param, Some(token.span()))
+                .map(|()| Shape::Unknown),
+            // Values that no arm matches are filtered out, which is the point.
+            ast::ExprOp::FilterMatch(ast::KwExpr { param, .. }) => {
+                self.match_body(param, None).map(|()| Shape::List)
+            }
+            ast::ExprOp::Map(expr) => self.expr(&expr.param).map(|_| input),
+            ast::ExprOp::Filter(ast::KwExpr { param, .. })
+            | ast::ExprOp::Discard(ast::KwExpr { param, .. })
+            | ast::ExprOp::Split(ast::KwExpr { param, .. }) => {
+                self.pattern_expr(param).map(|()| Shape::List)
+            }
+            ast::ExprOp::Join(ast::KwExpr { param, .. }) => {
+                self.string_expr(param).map(|()| Shape::String)
+            }
+            ast::ExprOp::Info(ast::KwExpr { param, .. })
+            | ast::ExprOp::Warn(ast::KwExpr { param, .. })
+            | ast::ExprOp::Error(ast::KwExpr { param, .. }) => {
+                self.string_expr(param).map(|()| input)
+            }
+            ast::ExprOp::AssertEq(expr) => self.expr(&expr.param).map(|_| input),
+            ast::ExprOp::AssertMatch(expr) => self.pattern_expr(&expr.param).map(|()| input),
+            ast::ExprOp::Flatten(_) | ast::ExprOp::Lines(_) => Ok(Shape::List),
+            ast::ExprOp::Dedup(_) | ast::ExprOp::Secret(_) | ast::ExprOp::Volatile(_) => Ok(input),
+            ast::ExprOp::PathJoin(_) => Ok(Shape::String),
+        }
+    }
+
+    /// Check the arms of a match. With the span of the `match` keyword, also
+    /// warn when no arm catches the values that the others don't match.
+    fn match_body(
+        &mut self,
+        body: &ast::MatchBody<'_>,
+        keyword: Option<Span>,
+    ) -> Result<(), EvalError> {
+        // The first arm matching any string, and the unguarded arms so far by
+        // the hash of their pattern. Arms matching lists are still reachable
+        // after a catch-all arm.
+        let mut catch_all = None;
+        let mut unguarded = HashMap::default();
+
+        for arm in body {
+            let hash = compute_stable_semantic_hash(&arm.pattern);
+            let matches_strings = !matches!(
+                arm.pattern,
+                ast::MatchPattern::Special(
+                    ast::SpecialPattern::EmptyList(_) | ast::SpecialPattern::NonEmptyList(_)
+                )
+            );
+            let earlier = catch_all
+                .filter(|_| matches_strings)
+                .or_else(|| unguarded.get(&hash).copied());
+            if let Some(earlier) = earlier {
+                self.warn(Warning::UnreachableMatchArm(arm.span, earlier));
+            } else if arm.is_catch_all() {
+                catch_all = Some(arm.span);
+            } else if arm.guard.is_none() {
+                unguarded.insert(hash, arm.span);
+            }
+
+            if let ast::MatchPattern::Pattern(ref pattern) = arm.pattern {
+                self.pattern_expr(pattern)?;
+            }
+            if let Some(ref guard) = arm.guard {
+                self.condition(&guard.condition)?;
+            }
+            self.expr_chain(&arm.expr)?;
+        }
+
+        if let (Some(keyword), None) = (keyword, catch_all) {
+            self.warn(Warning::NonExhaustiveMatch(keyword));
+        }
+        Ok(())
+    }
+
+    fn string_expr(&mut self, expr: &ast::StringExpr<'_>) -> Result<(), EvalError> {
+        for fragment in &expr.fragments {
+            if let ast::StringFragment::Interpolation(ref interp) = fragment {
+                let shape = self.interpolation(expr.span, interp)?;
+                let joined = interp
+                    .options
+                    .as_ref()
+                    .is_some_and(|options| options.join.is_some());
+                if let (ast::InterpolationStem::Ident(name), Shape::List, false) =
+                    (interp.stem, shape, joined)
+                {
+                    self.warn(Warning::ListInterpolation(expr.span, name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn pattern_expr(&mut self, expr: &ast::PatternExpr<'_>) -> Result<(), EvalError> {
+        for fragment in &expr.fragments {
+            if let ast::PatternFragment::Interpolation(ref interp) = fragment {
+                self.interpolation(expr.span, interp)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn interpolation(
+        &mut self,
+        span: Span,
+        interp: &ast::Interpolation<'_>,
+    ) -> Result<Shape, EvalError> {
+        match interp.stem {
+            ast::InterpolationStem::Ident(name) => self.reference(name, span),
+            ast::InterpolationStem::Implied
+            | ast::InterpolationStem::PatternCapture
+            | ast::InterpolationStem::NamedCapture(_)
+            | ast::InterpolationStem::CaptureGroup(_) => Ok(Shape::Unknown),
+        }
+    }
+}
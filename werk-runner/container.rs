@@ -0,0 +1,110 @@
+//! Running the commands of build recipes with a `container` statement in a
+//! container, using `docker run` or a compatible runtime like Podman.
+//!
+//! The workspace and output directories are mounted at the same paths in the
+//! container, so paths in arguments refer to the same files without being
+//! rewritten. Each `run` command runs in its own container, which is removed
+//! when the command exits.
+
+use std::path::Path;
+
+use werk_fs::Absolute;
+
+use crate::{ir::PullPolicy, Env, ShellCommandLine};
+
+pub(crate) struct Container<'a> {
+    runtime: Absolute<std::path::PathBuf>,
+    image: &'a str,
+    pull: PullPolicy,
+    mounts: Vec<&'a Absolute<Path>>,
+}
+
+impl<'a> Container<'a> {
+    pub fn new(
+        runtime: Absolute<std::path::PathBuf>,
+        image: &'a str,
+        pull: PullPolicy,
+        workspace_dir: &'a Absolute<Path>,
+        output_dir: &'a Absolute<Path>,
+    ) -> Self {
+        let mut mounts = vec![workspace_dir];
+        if !output_dir.starts_with(workspace_dir) {
+            mounts.push(output_dir);
+        }
+        Self {
+            runtime,
+            image,
+            pull,
+            mounts,
+        }
+    }
+
+    /// A command running the command line in a new container, in the working
+    /// directory and with the variables of `env`. With `interactive`, the
+    /// standard input of the command is forwarded to the container, which is
+    /// needed in pipelines.
+    pub fn run(
+        &self,
+        command_line: &ShellCommandLine,
+        env: &Env,
+        working_dir: &Absolute<Path>,
+        interactive: bool,
+    ) -> ShellCommandLine {
+        let mut arguments = vec![String::from("run"), String::from("--rm")];
+        if interactive {
+            arguments.push(String::from("-i"));
+        }
+        arguments.push(format!("--pull={}", self.pull.as_str()));
+        arguments.extend(self.user_mapping());
+        for mount in &self.mounts {
+            arguments.push(String::from("-v"));
+            arguments.push(format!("{0}:{0}", mount.display()));
+        }
+        arguments.push(String::from("-w"));
+        arguments.push(working_dir.display().to_string());
+        // The container starts with the environment of the image, so only
+        // the variables set by the recipe are passed.
+        for (key, value) in &env.env {
+            arguments.push(String::from("-e"));
+            arguments.push(format!(
+                "{}={}",
+                key.to_string_lossy(),
+                value.to_string_lossy()
+            ));
+        }
+        arguments.push(self.image.to_owned());
+        arguments.push(command_line.program.display().to_string());
+        arguments.extend(command_line.arguments.iter().cloned());
+
+        ShellCommandLine {
+            program: self.runtime.clone(),
+            arguments,
+        }
+    }
+
+    /// Run the command as the current user, so the files it creates in the
+    /// mounted directories are owned by the user instead of root. Rootless
+    /// Podman maps the user into the container itself.
+    // There is no user to map on other platforms.
+    #[cfg_attr(unix, expect(clippy::unnecessary_wraps))]
+    fn user_mapping(&self) -> Option<String> {
+        let is_podman = self
+            .runtime
+            .file_stem()
+            .is_some_and(|name| name.to_string_lossy().starts_with("podman"));
+        if is_podman {
+            return Some(String::from("--userns=keep-id"));
+        }
+
+        #[cfg(unix)]
+        {
+            // SAFETY: `getuid` and `getgid` are always successful.
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            Some(format!("--user={uid}:{gid}"))
+        }
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    }
+}
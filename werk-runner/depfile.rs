@@ -74,6 +74,35 @@ impl Depfile {
     }
 }
 
+/// Render the depfile in the Clang/GCC flavor, with one prerequisite per line.
+/// Spaces and `#` are escaped with backslashes, and `$` is doubled.
+impl std::fmt::Display for Depfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_escaped_path(f, &self.target)?;
+        f.write_str(":")?;
+        for dep in &self.deps {
+            f.write_str(" \\\n  ")?;
+            write_escaped_path(f, dep)?;
+        }
+        f.write_str("\n")
+    }
+}
+
+fn write_escaped_path(f: &mut std::fmt::Formatter<'_>, path: &std::path::Path) -> std::fmt::Result {
+    use std::fmt::Write as _;
+    for ch in path.to_string_lossy().chars() {
+        match ch {
+            ' ' | '\t' | '#' => {
+                f.write_char('\\')?;
+                f.write_char(ch)?;
+            }
+            '$' => f.write_str("$$")?,
+            _ => f.write_char(ch)?,
+        }
+    }
+    Ok(())
+}
+
 fn parse_depfile(input: &mut &str) -> PResult<(String, Vec<String>)> {
     delimited(
         // Ignore initial whitespace.
@@ -228,6 +257,25 @@ mod tests {
         assert_eq!(deps, vec!["dep1", "dep2", "dep3"]);
     }
 
+    #[test]
+    fn write() {
+        let depfile = Depfile {
+            target: PathBuf::from("/out/main.o"),
+            deps: vec![
+                PathBuf::from("/src/main.c"),
+                PathBuf::from("/my project/test.h"),
+            ],
+        };
+        let written = depfile.to_string();
+        assert_eq!(
+            written,
+            "/out/main.o: \\\n  /src/main.c \\\n  /my\\ project/test.h\n"
+        );
+        let (target, deps) = parse_depfile.parse(&written).unwrap();
+        assert_eq!(target, "/out/main.o");
+        assert_eq!(deps, vec!["/src/main.c", "/my project/test.h"]);
+    }
+
     #[test]
     fn test_prerequisite_separator() {
         let one_space = " ";
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use werk_fs::Absolute;
 use werk_parser::parser::Span;
-use werk_util::{DiagnosticFileId, DiagnosticSnippet};
+use werk_util::{DiagnosticAnnotationInfo, DiagnosticFileId, DiagnosticSnippet, Symbol};
 
 use crate::{depfile::DepfileError, OwnedDependencyChain, ShellCommandLine, TaskId, Value};
 
@@ -36,7 +36,7 @@ pub enum Error {
     /// stdout/stderr is a UI concern and only available through the
     /// `TrackRunner` interface.
     #[error("command failed: {0}")]
-    CommandFailed(std::process::ExitStatus),
+    CommandFailed(std::process::ExitStatus, Option<Box<RecipeLocation>>),
     #[error("cannot convert abstract paths to native OS paths yet; output directory has not been set in the [global] scope")]
     OutputDirectoryNotAvailable,
     #[error("depfile was not found: '{0}'; perhaps the rule to generate it writes to the wrong location?")]
@@ -49,6 +49,37 @@ pub enum Error {
     InvalidTargetPath(String, werk_fs::PathError),
     #[error("invalid path in depfile `{0}`: {1}")]
     InvalidPathInDepfile(String, werk_fs::PathError),
+    #[error("no workspace member named `{0}`")]
+    NoSuchMember(String),
+    #[error("circular dependency between workspace members: {0}")]
+    CircularMemberDependency(String),
+    /// A command was killed because it ran for longer than its timeout. The
+    /// duration is the time elapsed when it was killed.
+    #[error("command timed out after {0:.1?}")]
+    CommandTimedOut(std::time::Duration, Option<Box<RecipeLocation>>),
+    #[error("source directory `{0}` does not exist or is not a directory")]
+    InvalidSourceDir(String),
+    #[error("no profile named `{0}`")]
+    NoSuchProfile(String),
+    /// An input file of a recipe does not exist, and there is no recipe to
+    /// build it.
+    #[error("missing input: {0}")]
+    MissingInput(String, Box<RecipeLocation>),
+    /// A command line would exceed the limits of the OS, so it was not
+    /// spawned.
+    #[error("{0}")]
+    CommandLineTooLong(
+        crate::shell::CommandLineTooLong,
+        Option<Box<RecipeLocation>>,
+    ),
+    /// A file downloaded by a `fetch` command does not have the expected
+    /// digest.
+    #[error("{0}")]
+    ChecksumMismatch(ChecksumMismatch, Option<Box<RecipeLocation>>),
+    #[error("failed to download `{0}`: {1}")]
+    FetchFailed(String, Arc<std::io::Error>, Option<Box<RecipeLocation>>),
+    #[error("failed to extract `{0}`: {1}")]
+    ExtractFailed(String, Arc<std::io::Error>, Option<Box<RecipeLocation>>),
     #[error(transparent)]
     Custom(Arc<anyhow::Error>),
 }
@@ -68,7 +99,13 @@ impl Error {
             | Error::NoRuleToBuildTarget(_)
             | Error::CircularDependency(_)
             | Error::DependencyFailed(..)
-            | Error::CommandFailed(_)
+            | Error::CommandFailed(..)
+            | Error::CommandTimedOut(..)
+            | Error::CommandLineTooLong(..)
+            | Error::ChecksumMismatch(..)
+            | Error::FetchFailed(..)
+            | Error::ExtractFailed(..)
+            | Error::MissingInput(..)
             | Error::DepfileNotFound(_)
             | Error::DepfileError(_)
             | Error::Cancelled(_) => true,
@@ -82,6 +119,10 @@ impl Error {
             | Error::ClobberedWorkspace(_)
             | Error::InvalidTargetPath(..)
             | Error::InvalidPathInDepfile(..)
+            | Error::NoSuchMember(_)
+            | Error::CircularMemberDependency(_)
+            | Error::InvalidSourceDir(_)
+            | Error::NoSuchProfile(_)
             | Error::Custom(_) => false,
         }
     }
@@ -101,10 +142,15 @@ impl PartialEq for Error {
             (Self::Walk(l0), Self::Walk(r0)) => l0.to_string() == r0.to_string(),
             (Self::Glob(l0), Self::Glob(r0)) => l0 == r0,
             (Self::NoRuleToBuildTarget(l0), Self::NoRuleToBuildTarget(r0))
+            | (Self::NoSuchMember(l0), Self::NoSuchMember(r0))
+            | (Self::CircularMemberDependency(l0), Self::CircularMemberDependency(r0))
+            | (Self::InvalidSourceDir(l0), Self::InvalidSourceDir(r0))
+            | (Self::NoSuchProfile(l0), Self::NoSuchProfile(r0))
             | (Self::DuplicateCommand(l0), Self::DuplicateCommand(r0))
             | (Self::DuplicateTarget(l0), Self::DuplicateTarget(r0)) => l0 == r0,
             (Self::AmbiguousPattern(l0), Self::AmbiguousPattern(r0)) => l0 == r0,
-            (Self::CommandFailed(l0), Self::CommandFailed(r0)) => l0 == r0,
+            (Self::CommandFailed(l0, l1), Self::CommandFailed(r0, r1)) => l0 == r0 && l1 == r1,
+            (Self::MissingInput(l0, l1), Self::MissingInput(r0, r1)) => l0 == r0 && l1 == r1,
             (Self::ClobberedWorkspace(l0), Self::ClobberedWorkspace(r0)) => l0 == r0,
             (Self::Custom(l0), Self::Custom(r0)) => l0.to_string() == r0.to_string(),
             _ => core::mem::discriminant(self) == core::mem::discriminant(other),
@@ -112,6 +158,38 @@ impl PartialEq for Error {
     }
 }
 
+impl Error {
+    /// Attribute evaluation errors to an imported Werkfile. See
+    /// [`EvalError::in_file()`].
+    #[must_use]
+    pub fn in_file(self, file: DiagnosticFileId) -> Self {
+        match self {
+            Error::Eval(err) => Error::Eval(err.in_file(file)),
+            _ => self,
+        }
+    }
+
+    /// Attribute evaluation errors to the Werkfiles of a workspace member. See
+    /// [`EvalError::in_member()`].
+    #[must_use]
+    pub fn in_member(mut self, index: usize) -> Self {
+        match self {
+            Error::Eval(err) => Error::Eval(err.in_member(index)),
+            Error::CommandFailed(_, Some(ref mut location))
+            | Error::CommandTimedOut(_, Some(ref mut location))
+            | Error::CommandLineTooLong(_, Some(ref mut location))
+            | Error::ChecksumMismatch(_, Some(ref mut location))
+            | Error::FetchFailed(_, _, Some(ref mut location))
+            | Error::ExtractFailed(_, _, Some(ref mut location))
+            | Error::MissingInput(_, ref mut location) => {
+                location.in_member(index);
+                self
+            }
+            _ => self,
+        }
+    }
+}
+
 impl From<anyhow::Error> for Error {
     #[inline]
     fn from(err: anyhow::Error) -> Self {
@@ -185,6 +263,16 @@ impl werk_util::Diagnostic for Error {
             Error::ClobberedWorkspace(..) => 16,
             Error::InvalidTargetPath(..) => 17,
             Error::InvalidPathInDepfile(..) => 18,
+            Error::NoSuchMember(..) => 19,
+            Error::CircularMemberDependency(..) => 20,
+            Error::CommandTimedOut(..) => 21,
+            Error::InvalidSourceDir(..) => 22,
+            Error::NoSuchProfile(..) => 23,
+            Error::MissingInput(..) => 24,
+            Error::CommandLineTooLong(..) => 25,
+            Error::ChecksumMismatch(..) => 26,
+            Error::FetchFailed(..) => 27,
+            Error::ExtractFailed(..) => 28,
             Error::Custom(..) => 9999,
         }
     }
@@ -197,10 +285,30 @@ impl werk_util::Diagnostic for Error {
     }
 
     fn snippet(&self) -> Option<DiagnosticSnippet> {
-        if let Error::Eval(ref err) = self {
-            err.snippet()
-        } else {
-            None
+        match self {
+            Error::Eval(ref err) => err.snippet(),
+            Error::CommandFailed(_, Some(ref location)) => {
+                Some(location.snippet(format!("this command failed: {}", location.evaluated)))
+            }
+            Error::CommandTimedOut(_, Some(ref location)) => {
+                Some(location.snippet(format!("this command timed out: {}", location.evaluated)))
+            }
+            Error::MissingInput(ref input, ref location) => {
+                Some(location.snippet(format!("`{input}` does not exist")))
+            }
+            Error::CommandLineTooLong(ref err, Some(ref location)) => {
+                Some(location.snippet(err.to_string()))
+            }
+            Error::ChecksumMismatch(ref err, Some(ref location)) => {
+                Some(location.snippet(format!("the downloaded file has sha256 {}", err.actual)))
+            }
+            Error::FetchFailed(_, ref err, Some(ref location)) => {
+                Some(location.snippet(format!("this download failed: {err}")))
+            }
+            Error::ExtractFailed(_, ref err, Some(ref location)) => {
+                Some(location.snippet(format!("this extraction failed: {err}")))
+            }
+            _ => None,
         }
     }
 
@@ -228,14 +336,69 @@ impl werk_util::Diagnostic for Error {
     }
 
     fn help(&self) -> Vec<String> {
-        if let Error::Eval(ref err) = self {
-            err.help()
-        } else {
-            vec![]
+        match self {
+            Error::Eval(ref err) => err.help(),
+            Error::AmbiguousPattern(_) => vec![String::from(
+                "add a `priority` statement to one of the build recipes to choose between them",
+            )],
+            Error::NoSuchProfile(_) => vec![String::from(
+                "use `--list` to see the profiles declared in the Werkfile",
+            )],
+            Error::CommandLineTooLong(..) => vec![String::from(
+                "add `#[response-file = \"gnu\"]` or `#[response-file = \"msvc\"]` to the `run` statement to pass the arguments in a file, if the program supports it",
+            )],
+            Error::ChecksumMismatch(..) => vec![String::from(
+                "if the file was changed on purpose, update the digest in the `fetch` command",
+            )],
+            _ => vec![],
+        }
+    }
+}
+
+/// The statement of a recipe that caused a runtime error, like the `run`
+/// statement of a failing command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecipeLocation {
+    /// The Werkfile containing the recipe.
+    pub file: DiagnosticFileId,
+    /// The name or pattern of the recipe.
+    pub recipe: Span,
+    /// The statement within the recipe.
+    pub stmt: Span,
+    /// What the statement evaluated to, like the command line of a `run`
+    /// statement, with string interpolations applied.
+    pub evaluated: String,
+}
+
+impl RecipeLocation {
+    fn in_member(&mut self, index: usize) {
+        if crate::split_member_file_id(self.file).0.is_none() {
+            self.file = crate::member_file_id(index, self.file);
+        }
+    }
+
+    fn snippet(&self, message: String) -> DiagnosticSnippet {
+        DiagnosticSnippet {
+            file_id: self.file,
+            span: self.stmt.into(),
+            message,
+            info: vec![DiagnosticAnnotationInfo {
+                span: self.recipe.into(),
+                message: String::from("in this recipe"),
+            }],
         }
     }
 }
 
+/// The digests of a file downloaded by `fetch`, as lowercase hex.
+#[derive(Debug, Clone, thiserror::Error, PartialEq)]
+#[error("checksum mismatch for `{url}`: expected sha256 {expected}, got {actual}")]
+pub struct ChecksumMismatch {
+    pub url: String,
+    pub expected: String,
+    pub actual: String,
+}
+
 #[derive(Debug, thiserror::Error, PartialEq)]
 #[error("ambiguous pattern match: {path}")]
 pub struct AmbiguousPatternError {
@@ -309,7 +472,7 @@ pub enum EvalError {
     #[error("capture group with index {1} is out of bounds in the current scope")]
     NoSuchCaptureGroup(Span, u32),
     #[error("no identifier with name {1}")]
-    NoSuchIdentifier(Span, String),
+    NoSuchIdentifier(Span, String, Option<Symbol>),
     #[error("unexpected list; perhaps a join operation `{{var*}}` is missing?")]
     UnexpectedList(Span),
     #[error("pattern stems `{{%}}` cannot be interpolated in patterns")]
@@ -357,6 +520,114 @@ pub enum EvalError {
     AssertCustomFailed(Span, String),
     #[error("{1}")]
     AmbiguousPathResolution(Span, Arc<AmbiguousPathError>),
+    #[error("could not read imported Werkfile {}: {}", .1.display(), .2)]
+    ImportFailed(Span, std::path::PathBuf, IoError),
+    #[error("circular import of {}", .1.display())]
+    CircularImport(Span, std::path::PathBuf),
+    #[error("duplicate import namespace `{1}`")]
+    DuplicateImport(Span, String),
+    #[error("could not find a Werkfile for workspace member {}: {}", .1.display(), .2)]
+    MemberFailed(Span, std::path::PathBuf, IoError),
+    #[error("duplicate workspace member `{1}`")]
+    DuplicateMember(Span, String),
+    #[error("unknown attribute `{1}`")]
+    UnknownAttribute(Span, String),
+    #[error("attribute `{1}` cannot be applied to {2}")]
+    AttributeNotAllowed(Span, &'static str, &'static str),
+    #[error("expected {2} for attribute `{1}`")]
+    InvalidAttributeValue(Span, &'static str, &'static str),
+    #[error("duplicate attribute `{1}`")]
+    DuplicateAttribute(Span, &'static str),
+    #[error("invalid duration `{1}`")]
+    InvalidDuration(Span, String),
+    #[error("no pool with name `{1}`")]
+    UnknownPool(Span, String),
+    #[error("pool depth must be a positive integer")]
+    InvalidPoolDepth(Span),
+    #[error("expected a list of strings")]
+    ExpectedConfigList(Span),
+    #[error("duplicate profile `{1}`")]
+    DuplicateProfile(Span, String),
+    #[error("profile overrides `{1}`, which is not a global variable")]
+    NoSuchProfileVariable(Span, String),
+    #[error("unknown hash algorithm `{1}`")]
+    InvalidHashAlgorithm(Span, String),
+    #[error("expected an integer, found `{1}`")]
+    ExpectedInteger(Span, String),
+    #[error("division by zero")]
+    DivisionByZero(Span),
+    #[error("integer overflow")]
+    IntegerOverflow(Span),
+    #[error("unknown glob ignore mode `{1}`")]
+    InvalidGlobIgnore(Span, String),
+    #[error("ambiguous Werkfile for workspace member: both `{1}` and `{2}` exist")]
+    AmbiguousWerkfile(Span, String, String),
+    #[error("unknown placeholder `{1}` in echo template")]
+    InvalidEchoTemplate(Span, String),
+    #[error("no named capture `(%{1})` in the current pattern")]
+    NoSuchNamedCapture(Span, Symbol),
+    #[error("invalid SHA-256 digest `{1}`")]
+    InvalidSha256(Span, String),
+    #[error("no host with name `{1}`")]
+    UnknownHost(Span, String),
+    #[error("invalid remote host `{1}`")]
+    InvalidHost(Span, String),
+    #[error("running commands on remote hosts is not supported by this build of werk")]
+    RemoteUnsupported(Span),
+    #[error("invalid container pull policy `{1}`")]
+    InvalidPullPolicy(Span, String),
+    #[error("a recipe cannot have both a `container` and a `host` statement")]
+    ContainerOnHost(Span),
+    #[error("running commands in containers is not supported on this platform")]
+    ContainerUnsupported(Span),
+    #[error("`volatile` can only be used at the end of a `from` statement in a build recipe")]
+    UnexpectedVolatile(Span),
+    #[error(
+        "built-in commands that change files cannot be used in a recipe with a `host` statement"
+    )]
+    BuiltinOnHost(Span),
+    /// An error in an imported Werkfile, which is not the file that spans
+    /// refer to by default.
+    #[error("{1}")]
+    Imported(DiagnosticFileId, Box<EvalError>),
+}
+
+impl EvalError {
+    /// Attribute the error to an imported Werkfile. Errors that are already
+    /// attributed to a file are unchanged, as are errors in the main Werkfile
+    /// (file 0).
+    #[must_use]
+    pub fn in_file(self, file: DiagnosticFileId) -> Self {
+        if file == DiagnosticFileId::default() || matches!(self, EvalError::Imported(..)) {
+            self
+        } else {
+            EvalError::Imported(file, Box::new(self))
+        }
+    }
+
+    /// Attribute the error to the Werkfiles of the workspace member at
+    /// `index`. See [`member_file_id()`](crate::member_file_id).
+    #[must_use]
+    pub fn in_member(self, index: usize) -> Self {
+        if crate::split_member_file_id(self.file()).0.is_some() {
+            // Already attributed to a (nested) member.
+            return self;
+        }
+        let file = crate::member_file_id(index, self.file());
+        match self {
+            EvalError::Imported(_, err) => EvalError::Imported(file, err),
+            err => EvalError::Imported(file, Box::new(err)),
+        }
+    }
+
+    /// The file that the spans of this error refer to.
+    #[must_use]
+    pub fn file(&self) -> DiagnosticFileId {
+        match self {
+            EvalError::Imported(file, _) => *file,
+            _ => DiagnosticFileId::default(),
+        }
+    }
 }
 
 impl werk_parser::parser::Spanned for EvalError {
@@ -372,7 +643,7 @@ impl werk_parser::parser::Spanned for EvalError {
             | EvalError::DuplicatePattern(span, _)
             | EvalError::NoImpliedValue(span)
             | EvalError::NoSuchCaptureGroup(span, _)
-            | EvalError::NoSuchIdentifier(span, _)
+            | EvalError::NoSuchIdentifier(span, ..)
             | EvalError::UnexpectedList(span)
             | EvalError::PatternStemInterpolationInPattern(span)
             | EvalError::ResolvePathInPattern(span)
@@ -394,7 +665,40 @@ impl werk_parser::parser::Spanned for EvalError {
             | EvalError::AssertEqFailed(span, _)
             | EvalError::AssertMatchFailed(span, _)
             | EvalError::AssertCustomFailed(span, _)
-            | EvalError::AmbiguousPathResolution(span, _) => *span,
+            | EvalError::AmbiguousPathResolution(span, _)
+            | EvalError::ImportFailed(span, _, _)
+            | EvalError::CircularImport(span, _)
+            | EvalError::DuplicateImport(span, _)
+            | EvalError::MemberFailed(span, _, _)
+            | EvalError::DuplicateMember(span, _)
+            | EvalError::UnknownAttribute(span, _)
+            | EvalError::AttributeNotAllowed(span, _, _)
+            | EvalError::InvalidAttributeValue(span, _, _)
+            | EvalError::DuplicateAttribute(span, _)
+            | EvalError::InvalidDuration(span, _)
+            | EvalError::UnknownPool(span, _)
+            | EvalError::InvalidPoolDepth(span)
+            | EvalError::ExpectedConfigList(span)
+            | EvalError::DuplicateProfile(span, _)
+            | EvalError::NoSuchProfileVariable(span, _)
+            | EvalError::InvalidHashAlgorithm(span, _)
+            | EvalError::ExpectedInteger(span, _)
+            | EvalError::DivisionByZero(span)
+            | EvalError::IntegerOverflow(span)
+            | EvalError::InvalidGlobIgnore(span, _)
+            | EvalError::AmbiguousWerkfile(span, ..)
+            | EvalError::InvalidEchoTemplate(span, _)
+            | EvalError::NoSuchNamedCapture(span, _)
+            | EvalError::InvalidSha256(span, _)
+            | EvalError::UnknownHost(span, _)
+            | EvalError::InvalidHost(span, _)
+            | EvalError::RemoteUnsupported(span)
+            | EvalError::InvalidPullPolicy(span, _)
+            | EvalError::ContainerOnHost(span)
+            | EvalError::ContainerUnsupported(span)
+            | EvalError::UnexpectedVolatile(span)
+            | EvalError::BuiltinOnHost(span) => *span,
+            EvalError::Imported(_, err) => err.span(),
         }
     }
 }
@@ -442,6 +746,39 @@ impl werk_util::Diagnostic for EvalError {
             EvalError::AssertMatchFailed(..) => 30,
             EvalError::AssertCustomFailed(..) => 31,
             EvalError::AmbiguousPathResolution(..) => 32,
+            EvalError::ImportFailed(..) => 33,
+            EvalError::CircularImport(..) => 34,
+            EvalError::DuplicateImport(..) => 35,
+            EvalError::MemberFailed(..) => 36,
+            EvalError::DuplicateMember(..) => 37,
+            EvalError::UnknownAttribute(..) => 38,
+            EvalError::AttributeNotAllowed(..) => 39,
+            EvalError::InvalidAttributeValue(..) => 40,
+            EvalError::DuplicateAttribute(..) => 41,
+            EvalError::InvalidDuration(..) => 42,
+            EvalError::UnknownPool(..) => 43,
+            EvalError::InvalidPoolDepth(..) => 44,
+            EvalError::ExpectedConfigList(..) => 45,
+            EvalError::DuplicateProfile(..) => 46,
+            EvalError::NoSuchProfileVariable(..) => 47,
+            EvalError::InvalidHashAlgorithm(..) => 48,
+            EvalError::ExpectedInteger(..) => 49,
+            EvalError::DivisionByZero(..) => 50,
+            EvalError::IntegerOverflow(..) => 51,
+            EvalError::InvalidGlobIgnore(..) => 52,
+            EvalError::AmbiguousWerkfile(..) => 53,
+            EvalError::InvalidEchoTemplate(..) => 54,
+            EvalError::NoSuchNamedCapture(..) => 55,
+            EvalError::InvalidSha256(..) => 56,
+            EvalError::UnknownHost(..) => 57,
+            EvalError::InvalidHost(..) => 58,
+            EvalError::RemoteUnsupported(..) => 59,
+            EvalError::InvalidPullPolicy(..) => 60,
+            EvalError::ContainerOnHost(..) => 61,
+            EvalError::ContainerUnsupported(..) => 62,
+            EvalError::UnexpectedVolatile(..) => 63,
+            EvalError::BuiltinOnHost(..) => 64,
+            EvalError::Imported(_, err) => err.id(),
         }
     }
 
@@ -452,7 +789,7 @@ impl werk_util::Diagnostic for EvalError {
     fn snippet(&self) -> Option<DiagnosticSnippet> {
         use werk_parser::parser::Spanned;
         Some(DiagnosticSnippet {
-            file_id: werk_util::DiagnosticFileId::default(), // TODO
+            file_id: self.file(),
             span: self.span().into(),
             message: self.to_string(),
             info: vec![],
@@ -461,6 +798,7 @@ impl werk_util::Diagnostic for EvalError {
 
     fn context_snippets(&self) -> Vec<DiagnosticSnippet> {
         match self {
+            EvalError::Imported(_, err) => err.context_snippets(),
             EvalError::AmbiguousPathResolution(_, err) => {
                 vec![DiagnosticSnippet {
                     file_id: DiagnosticFileId::default(), // TODO: might come from another file
@@ -475,12 +813,69 @@ impl werk_util::Diagnostic for EvalError {
 
     fn help(&self) -> Vec<String> {
         match self {
+            EvalError::Imported(_, err) => err.help(),
+            EvalError::NoSuchIdentifier(_, _, Some(suggestion)) => {
+                vec![format!("did you mean `{suggestion}`?")]
+            }
             EvalError::NoSuchCaptureGroup(..) => vec![String::from(
                 "pattern capture groups are zero-indexed, starting from 0",
             )],
             EvalError::AmbiguousPathResolution(..) => vec![String::from(
                 "use `<...:out-dir>` or `<...:workspace>` to disambiguate between paths in the workspace and the output directory",
             )],
+            EvalError::InvalidDuration(..) => vec![String::from(
+                "durations are a number followed by a unit, like `500ms`, `30s`, `5m`, or `1h30m`",
+            )],
+            EvalError::UnknownPool(..) => vec![String::from(
+                "pools are declared with `config pool.<name> = <depth>`",
+            )],
+            EvalError::NoSuchProfileVariable(..) => vec![String::from(
+                "profiles can only replace variables declared with `let` in the root scope of the Werkfile",
+            )],
+            EvalError::InvalidHashAlgorithm(..) => vec![String::from(
+                "supported hash algorithms are `sip128` and `sha256`",
+            )],
+            EvalError::InvalidGlobIgnore(..) => vec![String::from(
+                "supported glob ignore modes are `git` and `werk`",
+            )],
+            EvalError::AmbiguousWerkfile(..) => {
+                vec![String::from("rename or remove one of the Werkfiles")]
+            }
+            EvalError::InvalidSha256(..) => vec![String::from(
+                "a SHA-256 digest is 64 hexadecimal digits, like the output of `sha256sum`",
+            )],
+            EvalError::UnknownHost(..) => vec![String::from(
+                "hosts are declared with `config host.<name> = \"<destination>:<directory>\"`",
+            )],
+            EvalError::InvalidHost(..) => vec![String::from(
+                "a host is an SSH destination and an absolute directory, like `\"user@builder1:/srv/werk\"`",
+            )],
+            EvalError::RemoteUnsupported(..) => vec![String::from(
+                "build werk with the `ssh` feature to run commands over SSH",
+            )],
+            EvalError::InvalidPullPolicy(..) => vec![String::from(
+                "supported pull policies are `missing`, `always`, and `never`",
+            )],
+            EvalError::ContainerOnHost(..) => vec![String::from(
+                "to use a container on a remote host, run the container runtime in a `run` statement",
+            )],
+            EvalError::ContainerUnsupported(..) => vec![String::from(
+                "containers are only supported on Unix-like systems",
+            )],
+            EvalError::UnexpectedVolatile(..) => vec![String::from(
+                "write `from \"<dependency>\" | volatile` to mark dependencies as volatile",
+            )],
+            EvalError::BuiltinOnHost(..) => vec![String::from(
+                "built-in commands run locally, so their files are not seen by the host; \
+                 move them to a recipe without `host`, or use a shell command",
+            )],
+            EvalError::InvalidEchoTemplate(..) => vec![String::from(
+                "placeholders are `{step}`, `{steps}`, `{task}`, and `{command}`; write `{{` for a literal `{`",
+            )],
+            EvalError::UnknownAttribute(..) => vec![String::from(
+                "valid attributes are `no-capture`, `silent`, `print-commands`, `interactive`, \
+                 `always-run`, `uncached`, `stamp`, `restat`, and `retry` on recipes, and `cache` and `cache-key` on global variables",
+            )],
             _ => vec![],
         }
     }
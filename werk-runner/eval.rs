@@ -12,9 +12,10 @@ use werk_parser::{
 };
 
 use crate::{
-    AmbiguousPatternError, BuildRecipeScope, Env, EvalError, Lookup, LookupValue, MatchScope,
-    Pattern, PatternBuilder, RunCommand, Scope, ShellCommandLine, ShellCommandLineBuilder,
-    ShellError, SubexprScope, TaskRecipeScope, Value, Workspace,
+    ir, AmbiguousPatternError, BuildRecipeScope, Env, EvalError, Lookup, LookupValue, MatchScope,
+    Pattern, PatternBuilder, PatternMatchData, Redirection, RootScope, RunCommand, Scope,
+    ShellCommandLine, ShellCommandLineBuilder, ShellError, SubexprScope, TaskId, TaskRecipeScope,
+    Value, Workspace,
 };
 
 /// Evaluated value, which keeps track of "outdatedness" with respect to cached
@@ -132,7 +133,17 @@ pub fn eval(scope: &dyn Scope, expr: &ast::Expr<'_>) -> Result<Eval<Value>, Eval
         ast::Expr::StringExpr(expr) => Ok(eval_string_expr(scope, expr)?.map(Value::String)),
         ast::Expr::Shell(expr) => Ok(eval_shell(scope, &expr.param)?.map(Value::String)),
         ast::Expr::Read(expr) => Ok(eval_read(scope, &expr.param)?.map(Value::String)),
+        ast::Expr::Hash(expr) => Ok(eval_hash(scope, expr)?.map(Value::String)),
         ast::Expr::Glob(expr) => Ok(eval_glob(scope, expr)?.map(Value::List)),
+        ast::Expr::GlobOut(expr) => Ok(eval_glob_out(scope, expr)?.map(Value::List)),
+        ast::Expr::IsOverridden(expr) => eval_is_overridden(scope, &expr.param),
+        ast::Expr::Int(int) => Ok(Eval::inherent(Value::String(int.value.to_string()))),
+        ast::Expr::CpuCount(_) => {
+            let cpu_count = std::thread::available_parallelism().map_or(1, usize::from);
+            Ok(Eval::inherent(Value::String(cpu_count.to_string())))
+        }
+        ast::Expr::Arith(expr) => eval_arith(scope, expr),
+        ast::Expr::If(expr) => eval_if(scope, expr),
         ast::Expr::Which(expr) => {
             let Eval {
                 value: string,
@@ -197,7 +208,7 @@ pub fn eval(scope: &dyn Scope, expr: &ast::Expr<'_>) -> Result<Eval<Value>, Eval
         }
         ast::Expr::Ident(ident) => scope
             .get(Lookup::Ident(ident.ident))
-            .ok_or_else(|| EvalError::NoSuchIdentifier(ident.span, ident.ident.to_string()))
+            .ok_or_else(|| no_such_identifier(scope, ident.span, ident.ident))
             .map(LookupValue::into_owned),
         ast::Expr::Error(expr) => {
             let message = eval_string_expr(scope, &expr.param)?;
@@ -228,101 +239,192 @@ pub fn eval_op(
             let subscope = SubexprScope::new(scope, &param);
             eval_string_expr(&subscope, expr).map(|eval| eval.map(Value::String))
         }
-        ast::ExprOp::Match(match_expr) => eval_match_expr(scope, match_expr, param),
+        ast::ExprOp::Match(match_expr) => eval_match_expr(scope, &match_expr.param, param),
+        ast::ExprOp::MatchRegex(match_expr) => eval_match_expr(scope, &match_expr.param, param),
         ast::ExprOp::Map(expr) => eval_map(scope, expr, param),
         ast::ExprOp::Flatten(_) => Ok(eval_flatten(scope, param)),
         ast::ExprOp::Filter(expr) => eval_filter(scope, expr, param),
         ast::ExprOp::FilterMatch(expr) => eval_filter_match(scope, expr, param),
         ast::ExprOp::Discard(expr) => eval_discard(scope, expr, param),
         ast::ExprOp::Join(expr) => eval_join(scope, expr, param),
+        ast::ExprOp::PathJoin(expr) => eval_path_join(expr.span(), param),
         ast::ExprOp::Split(expr) => eval_split(scope, expr, param),
         ast::ExprOp::Dedup(_) => Ok(eval_dedup(param)),
+        ast::ExprOp::Secret(_) => {
+            scope.workspace().add_secret(&param.value);
+            Ok(param)
+        }
+        // Handled by `eval_from_stmt`.
+        ast::ExprOp::Volatile(expr) => Err(EvalError::UnexpectedVolatile(expr.span())),
         ast::ExprOp::Lines(_) => Ok(eval_split_lines(scope, param)),
         ast::ExprOp::Info(expr) => {
             let scope = SubexprScope::new(scope, &param);
             let message = eval_string_expr(&scope, &expr.param)?;
-            scope.render().message(scope.task_id(), &message.value);
+            let message = scope.workspace().redact(&message.value);
+            scope.render().message(scope.task_id(), &message);
             Ok(param)
         }
         ast::ExprOp::Warn(expr) => {
             let scope = SubexprScope::new(scope, &param);
             let message = eval_string_expr(&scope, &expr.param)?;
-            scope.render().warning(scope.task_id(), &message.value);
+            let message = scope.workspace().redact(&message.value);
+            scope.render().warning(scope.task_id(), &message);
             Ok(param)
         }
         ast::ExprOp::Error(error_expr) => {
             let string = eval_string_expr(scope, &error_expr.param)?;
-            Err(EvalError::ErrorExpression(error_expr.span, string.value))
+            let message = scope.workspace().redact(&string.value).into_owned();
+            Err(EvalError::ErrorExpression(error_expr.span, message))
         }
         ast::ExprOp::AssertEq(expr) => eval_assert_eq(scope, expr, param),
         ast::ExprOp::AssertMatch(expr) => eval_assert_match(scope, expr, param),
     }
 }
 
+/// The pattern of a match arm, after evaluating string interpolations.
+enum MatchArmPattern<'a> {
+    String(Pattern<'a>),
+    Regex(regex::Regex),
+    Wildcard,
+    EmptyList,
+    NonEmptyList,
+}
+
+struct EvaluatedMatchArm<'a, 'b> {
+    pattern: MatchArmPattern<'a>,
+    arm: &'b ast::MatchArm<'a>,
+}
+
+fn eval_match_arms<'a, 'b>(
+    scope: &dyn Scope,
+    body: &'b ast::MatchBody<'a>,
+    used: &mut Used,
+) -> Result<Vec<EvaluatedMatchArm<'a, 'b>>, EvalError> {
+    let mut arms = Vec::with_capacity(body.len());
+    for arm in body {
+        let pattern = match arm.pattern {
+            ast::MatchPattern::Pattern(ref pattern_expr) => {
+                let pattern = eval_pattern(scope, pattern_expr)?;
+                *used |= pattern.used;
+                MatchArmPattern::String(pattern.value)
+            }
+            ast::MatchPattern::Regex(ref regex) => MatchArmPattern::Regex(regex.regex.clone()),
+            ast::MatchPattern::Special(ast::SpecialPattern::Wildcard(_)) => {
+                MatchArmPattern::Wildcard
+            }
+            ast::MatchPattern::Special(ast::SpecialPattern::EmptyList(_)) => {
+                MatchArmPattern::EmptyList
+            }
+            ast::MatchPattern::Special(ast::SpecialPattern::NonEmptyList(_)) => {
+                MatchArmPattern::NonEmptyList
+            }
+        };
+        arms.push(EvaluatedMatchArm { pattern, arm });
+    }
+    Ok(arms)
+}
+
+/// Try each arm in order, and evaluate the right-hand side of the first arm
+/// where both the pattern and the guard (if any) match. Returns `None` if no
+/// arm matched the input.
+fn apply_match_arms(
+    scope: &dyn Scope,
+    arms: &[EvaluatedMatchArm<'_, '_>],
+    input: &Eval<Value>,
+    used: &mut Used,
+) -> Result<Option<Value>, EvalError> {
+    for EvaluatedMatchArm { pattern, arm } in arms {
+        let pattern_match = match (pattern, &input.value) {
+            (MatchArmPattern::String(pattern), Value::String(_) | Value::Path(_)) => {
+                let input_string = input.value.as_str().expect("not a list");
+                tracing::trace!("trying match '{:?}' against '{}'", pattern, input_string);
+                let Some(pattern_match) = pattern.match_whole_string(input_string) else {
+                    continue;
+                };
+                pattern_match
+            }
+            // The whole match is the stem, and capture groups are numbered
+            // from zero like the capture groups of string patterns.
+            (MatchArmPattern::Regex(regex), Value::String(_) | Value::Path(_)) => {
+                let input_string = input.value.as_str().expect("not a list");
+                let Some(captures) = regex.captures(input_string) else {
+                    continue;
+                };
+                PatternMatchData::new(
+                    captures.get(0).map(|m| m.as_str()),
+                    captures
+                        .iter()
+                        .skip(1)
+                        .map(|group| group.map_or("", |m| m.as_str())),
+                )
+            }
+            (MatchArmPattern::Wildcard, Value::String(_) | Value::Path(_)) => {
+                PatternMatchData::default()
+            }
+            (MatchArmPattern::EmptyList, Value::List(list)) if list.is_empty() => {
+                PatternMatchData::default()
+            }
+            (MatchArmPattern::NonEmptyList, Value::List(list)) if !list.is_empty() => {
+                PatternMatchData::default()
+            }
+            _ => continue,
+        };
+
+        // Don't need to forward used variables here, because
+        // we are manually collecting used variables
+        let scope = MatchScope::new(scope, &pattern_match, input);
+
+        if let Some(ref guard) = arm.guard {
+            if !eval_condition(&scope, &guard.condition, used)? {
+                continue;
+            }
+        }
+
+        let new_value = eval_chain(&scope, &arm.expr)?;
+        *used |= new_value.used;
+        return Ok(Some(new_value.value));
+    }
+
+    Ok(None)
+}
+
+/// Evaluate the body of `match` or `match-regex`.
 pub fn eval_match_expr(
     scope: &dyn Scope,
-    expr: &ast::MatchExpr<'_>,
+    body: &ast::MatchBody<'_>,
     param: Eval<Value>,
 ) -> Result<Eval<Value>, EvalError> {
     // Apply the match recursively to the input.
     fn apply_match_recursively(
         scope: &dyn Scope,
-        patterns: &[(Pattern<'_>, &ast::ExprChain<'_>)],
+        arms: &[EvaluatedMatchArm<'_, '_>],
         value: Value,
         used: &mut Used,
     ) -> Result<Value, EvalError> {
-        match value {
-            Value::String(s) => apply_match(scope, patterns, s.clone(), used),
-            Value::List(list) => {
-                if list.is_empty() {
-                    return Ok(Value::List(Vec::new()));
-                }
+        let input = Eval::inherent(value);
+        if let Some(new_value) = apply_match_arms(scope, arms, &input, used)? {
+            return Ok(new_value);
+        }
 
+        match input.value {
+            // Passed through unchanged. `werk check` warns about matches
+            // without a catch-all arm.
+            value @ (Value::String(_) | Value::Path(_)) => Ok(value),
+            // No list pattern matched the list as a whole; match each element
+            // instead.
+            Value::List(list) => {
                 let mut new_list = Vec::with_capacity(list.len());
                 for item in list {
-                    new_list.push(apply_match_recursively(scope, patterns, item, used)?);
+                    new_list.push(apply_match_recursively(scope, arms, item, used)?);
                 }
                 Ok(Value::List(new_list))
             }
         }
     }
 
-    fn apply_match(
-        scope: &dyn Scope,
-        patterns: &[(Pattern<'_>, &ast::ExprChain<'_>)],
-        input_string: String,
-        used: &mut Used,
-    ) -> Result<Value, EvalError> {
-        for (pattern, replacement_expr) in patterns {
-            tracing::trace!("trying match '{:?}' against '{}'", pattern, input_string);
-            let Some(pattern_match) = pattern.match_whole_string(&input_string) else {
-                continue;
-            };
-
-            // Don't need to forward used variables here, because
-            // we are manually collecting used variables
-            let matched_string = Eval::inherent(Value::String(input_string));
-            let scope = MatchScope::new(scope, &pattern_match, &matched_string);
-            let new_value = eval_chain(&scope, replacement_expr)?;
-            *used |= new_value.used;
-            return Ok(new_value.value);
-        }
-
-        // Unmodified.
-        Ok(Value::String(input_string))
-    }
-
     let mut used = param.used;
-
-    // Evaluate patterns.
-    let mut patterns = Vec::with_capacity(expr.param.len());
-    for stmt in &expr.param {
-        let pattern = eval_pattern(scope, &stmt.pattern)?;
-        used |= pattern.used;
-        patterns.push((pattern.value, &stmt.expr));
-    }
-
-    let value = apply_match_recursively(scope, &patterns, param.value, &mut used)?;
+    let arms = eval_match_arms(scope, body, &mut used)?;
+    let value = apply_match_recursively(scope, &arms, param.value, &mut used)?;
 
     Ok(Eval { value, used })
 }
@@ -335,61 +437,33 @@ pub fn eval_filter_match(
     // Apply the match recursively to the input.
     fn apply_filter_match_recursively(
         scope: &dyn Scope,
-        patterns: &[(Pattern<'_>, &ast::ExprChain<'_>)],
+        arms: &[EvaluatedMatchArm<'_, '_>],
         value: Value,
         used: &mut Used,
         result: &mut Vec<Value>,
     ) -> Result<(), EvalError> {
         match value {
-            Value::String(s) => apply_filter_match(scope, patterns, s, used, result),
+            value @ (Value::String(_) | Value::Path(_)) => {
+                let input = Eval::inherent(value);
+                if let Some(new_value) = apply_match_arms(scope, arms, &input, used)? {
+                    result.push(new_value);
+                }
+                Ok(())
+            }
             Value::List(list) => {
                 for item in list {
-                    apply_filter_match_recursively(scope, patterns, item, used, result)?;
+                    apply_filter_match_recursively(scope, arms, item, used, result)?;
                 }
                 Ok(())
             }
         }
     }
 
-    fn apply_filter_match(
-        scope: &dyn Scope,
-        patterns: &[(Pattern<'_>, &ast::ExprChain<'_>)],
-        input_string: String,
-        used: &mut Used,
-        result: &mut Vec<Value>,
-    ) -> Result<(), EvalError> {
-        for (pattern, replacement_expr) in patterns {
-            tracing::trace!("trying match '{:?}' against '{}'", pattern, input_string);
-            let Some(pattern_match) = pattern.match_whole_string(&input_string) else {
-                continue;
-            };
-
-            // Don't need to forward used variables here, because
-            // we are manually collecting used variables
-            let matched_string = Eval::inherent(Value::String(input_string));
-            let scope = MatchScope::new(scope, &pattern_match, &matched_string);
-            let new_value = eval_chain(&scope, replacement_expr)?;
-            *used |= new_value.used;
-            result.push(new_value.value);
-            break;
-        }
-
-        // No match.
-        Ok(())
-    }
-
     let mut used = param.used;
-
-    // Evaluate patterns.
-    let mut patterns = Vec::with_capacity(expr.param.len());
-    for stmt in &expr.param {
-        let pattern = eval_pattern(scope, &stmt.pattern)?;
-        used |= pattern.used;
-        patterns.push((pattern.value, &stmt.expr));
-    }
+    let arms = eval_match_arms(scope, &expr.param, &mut used)?;
 
     let mut result = Vec::new();
-    apply_filter_match_recursively(scope, &patterns, param.value, &mut used, &mut result)?;
+    apply_filter_match_recursively(scope, &arms, param.value, &mut used, &mut result)?;
 
     Ok(Eval {
         value: Value::List(result),
@@ -410,6 +484,39 @@ fn eval_join(
     })
 }
 
+fn eval_path_join(span: Span, param: Eval<Value>) -> Result<Eval<Value>, EvalError> {
+    fn path_join_recursive(
+        span: Span,
+        value: &Value,
+        path: &mut Absolute<werk_fs::PathBuf>,
+    ) -> Result<(), EvalError> {
+        match value {
+            Value::List(vec) => {
+                for item in vec {
+                    path_join_recursive(span, item, path)?;
+                }
+            }
+            // Like `std::path::Path::join()`, an absolute component replaces
+            // the path so far.
+            Value::Path(component) => path.clone_from(component),
+            Value::String(component) => {
+                let joined = werk_fs::Path::new(component)
+                    .and_then(|component| component.absolutize(path))
+                    .map_err(|err| EvalError::Path(span, err))?;
+                *path = joined.into_owned();
+            }
+        }
+        Ok(())
+    }
+
+    let mut path = werk_fs::Path::ROOT.to_owned();
+    path_join_recursive(span, &param.value, &mut path)?;
+    Ok(Eval {
+        value: Value::Path(path),
+        used: param.used,
+    })
+}
+
 fn eval_map(
     scope: &dyn Scope,
     expr: &ast::MapExpr<'_>,
@@ -430,7 +537,7 @@ fn eval_map(
                 }
                 Ok(Value::List(result))
             }
-            value @ Value::String(_) => {
+            value @ (Value::String(_) | Value::Path(_)) => {
                 let input = Eval::inherent(value);
                 let subscope = SubexprScope::new(scope, &input);
                 let new_value = eval(&subscope, map)?;
@@ -453,7 +560,7 @@ fn eval_flatten(_scope: &dyn Scope, param: Eval<Value>) -> Eval<Value> {
                     apply_flatten_recursive(item, flattened);
                 }
             }
-            Value::String(_) => flattened.push(value),
+            Value::String(_) | Value::Path(_) => flattened.push(value),
         }
     }
 
@@ -478,8 +585,11 @@ fn eval_filter(
                     eval_filter_recursive(pattern, item, result);
                 }
             }
-            Value::String(ref s) => {
-                if pattern.match_whole_string(s).is_some() {
+            Value::String(_) | Value::Path(_) => {
+                if pattern
+                    .match_whole_string(value.as_str().expect("not a list"))
+                    .is_some()
+                {
                     result.push(value);
                 }
             }
@@ -508,8 +618,11 @@ fn eval_discard(
                     eval_discard_recursive(pattern, item, result);
                 }
             }
-            Value::String(ref s) => {
-                if pattern.match_whole_string(s).is_none() {
+            Value::String(_) | Value::Path(_) => {
+                if pattern
+                    .match_whole_string(value.as_str().expect("not a list"))
+                    .is_none()
+                {
                     result.push(value);
                 }
             }
@@ -538,7 +651,8 @@ fn eval_split(
                     split_recursive(item, regex, result);
                 }
             }
-            Value::String(s) => {
+            Value::String(_) | Value::Path(_) => {
+                let s = value.as_str().expect("not a list");
                 for split in regex.split(s) {
                     result.push(Value::String(split.to_owned()));
                 }
@@ -582,7 +696,8 @@ fn eval_split_lines(_scope: &dyn Scope, param: Eval<Value>) -> Eval<Value> {
                     split_lines_recursive(item, result);
                 }
             }
-            Value::String(s) => {
+            Value::String(_) | Value::Path(_) => {
+                let s = value.as_str().expect("not a list");
                 for line in s.lines() {
                     result.push(Value::String(line.to_owned()));
                 }
@@ -612,9 +727,15 @@ pub fn eval_pattern_builder<'a, P: Scope + ?Sized>(
         match fragment {
             ast::PatternFragment::Literal(lit) => pattern_builder.push_str(lit),
             ast::PatternFragment::PatternStem => pattern_builder.push_pattern_stem(),
+            ast::PatternFragment::NamedCapture(name) => pattern_builder.push_named_capture(*name),
             ast::PatternFragment::OneOf(one_of) => pattern_builder.push_one_of(one_of.clone()),
+            ast::PatternFragment::CharClass(ranges) => {
+                pattern_builder.push_char_class(ranges.clone());
+            }
             ast::PatternFragment::Interpolation(interp) => {
-                if let ast::InterpolationStem::PatternCapture = interp.stem {
+                if let ast::InterpolationStem::PatternCapture
+                | ast::InterpolationStem::NamedCapture(_) = interp.stem
+                {
                     return Err(EvalError::PatternStemInterpolationInPattern(expr.span));
                 }
 
@@ -644,14 +765,10 @@ pub fn eval_pattern_builder<'a, P: Scope + ?Sized>(
                 // Note: Ignoring the build-status of the interpolation
                 // stem, because we are building a pattern - it can't itself
                 // be outdated.
-                match value {
-                    Value::String(string) => {
-                        pattern_builder.push_str(string);
-                    }
-                    Value::List(_) => {
-                        return Err(EvalError::ListInPattern(expr.span));
-                    }
-                }
+                let Some(string) = value.as_str() else {
+                    return Err(EvalError::ListInPattern(expr.span));
+                };
+                pattern_builder.push_str(string);
             }
         }
     }
@@ -710,8 +827,8 @@ pub fn eval_string_expr<P: Scope + ?Sized>(
                             s.push_str(first);
                         }
                     }
-                    Value::String(value) => {
-                        s.push_str(value);
+                    Value::String(_) | Value::Path(_) => {
+                        s.push_str(value.as_str().expect("not a list"));
                     }
                 }
             }
@@ -721,118 +838,356 @@ pub fn eval_string_expr<P: Scope + ?Sized>(
     Ok(Eval { value: s, used })
 }
 
-pub(crate) fn eval_run_exprs<S: Scope>(
+/// Evaluate a `run` statement. The attributes of the statement apply to the
+/// commands it produces, and nothing else.
+pub(crate) fn eval_run_stmt<S: Scope>(
     scope: &S,
-    expr: &ast::RunExpr<'_>,
+    stmt: &ast::RunStmt<'_>,
     commands: &mut Vec<RunCommand>,
 ) -> Result<Used, EvalError> {
-    fn eval_run_exprs_recursively<S: Scope>(
-        scope: &S,
-        expr: &ast::RunExpr<'_>,
-        commands: &mut Vec<RunCommand>,
-        used: &mut Used,
-    ) -> Result<(), EvalError> {
-        match expr {
-            ast::RunExpr::Shell(expr) => {
-                let shell = eval_shell_command(scope, &expr.param)?;
-                *used |= shell.used;
-                commands.push(RunCommand::Shell(shell.value));
-            }
-            ast::RunExpr::Write(expr) => {
-                let destination = eval(scope, &expr.path)?;
-                let Value::String(dest_path) = destination.value else {
-                    return Err(EvalError::UnexpectedList(expr.path.span()));
-                };
-                let dest_path = werk_fs::Path::new(&dest_path)
-                    .and_then(|path| scope.workspace().get_output_file_path(path))
-                    .map_err(|err| EvalError::Path(expr.span, err))?;
-                let data = eval(scope, &expr.value)?;
-                let write_used = destination.used | data.used;
-                let Value::String(data) = data.value else {
-                    return Err(EvalError::UnexpectedList(expr.value.span()));
-                };
+    let attributes = eval_run_attributes(&stmt.attributes)?;
 
-                *used |= write_used;
-                commands.push(RunCommand::Write(dest_path, data.into()));
-            }
-            ast::RunExpr::Copy(expr) => {
-                let from = eval_string_expr(scope, &expr.src)?;
-                let to = eval_string_expr(scope, &expr.dest)?;
-                let from_path = werk_fs::PathBuf::new(from.value)
-                    .and_then(|path| {
-                        path.absolutize(werk_fs::Path::ROOT)
-                            .map(std::borrow::Cow::into_owned)
-                    })
-                    .map_err(|err| EvalError::Path(expr.src.span, err))?;
-                let to_path = werk_fs::Path::new(&to)
-                    .and_then(|path| scope.workspace().get_output_file_path(path))
-                    .map_err(|err| EvalError::Path(expr.dest.span, err))?;
-                let copy_used = from.used | to.used;
-                *used |= copy_used;
-                commands.push(RunCommand::Copy(from_path, to_path));
-            }
-            ast::RunExpr::Delete(expr) => {
-                let evaluated_paths = eval(scope, &expr.param)?;
-                let mut paths = Vec::new();
-                evaluated_paths
-                    .value
-                    .try_collect_strings_recursive(|path| {
-                        let path = werk_fs::PathBuf::new(path)?;
-                        let path = path.absolutize(werk_fs::Path::ROOT)?;
-                        let path = scope.workspace().get_output_file_path(&path)?;
-                        paths.push(path);
-                        Ok(())
-                    })
-                    .map_err(|err| EvalError::Path(expr.param.span(), err))?;
-                *used |= evaluated_paths.used;
-                commands.push(RunCommand::Delete(paths));
-            }
-            ast::RunExpr::Env(expr) => {
-                let key = eval_string_expr(scope, &expr.key)?;
-                let value = eval_string_expr(scope, &expr.value)?;
-                *used |= key.used;
-                *used |= value.used;
-                commands.push(RunCommand::SetEnv(key.value, value.value));
-            }
-            ast::RunExpr::EnvRemove(expr) => {
-                let key = eval_string_expr(scope, &expr.param)?;
-                *used |= key.used;
-                commands.push(RunCommand::RemoveEnv(key.value));
-            }
-            ast::RunExpr::Info(expr) => {
-                let message = eval_string_expr(scope, &expr.param)?;
-                *used |= message.used;
-                commands.push(RunCommand::Info(message.value));
-            }
-            ast::RunExpr::Warn(expr) => {
-                let message = eval_string_expr(scope, &expr.param)?;
-                *used |= message.used;
-                // TODO: Specific warn command.
-                commands.push(RunCommand::Info(message.value));
-            }
-            ast::RunExpr::List(exprs) => {
-                for expr in &exprs.items {
-                    eval_run_exprs_recursively(scope, &expr.item, commands, used)?;
-                }
+    let mut used = Used::none();
+    if let Some(attributes) = attributes {
+        commands.push(RunCommand::PushAttributes(attributes));
+    }
+    eval_run_expr(scope, &stmt.param, commands, &mut used)?;
+    if attributes.is_some() {
+        commands.push(RunCommand::PopAttributes);
+    }
+    Ok(used)
+}
+
+fn eval_run_attributes(
+    attributes: &[ast::Attribute<'_>],
+) -> Result<Option<ir::Attributes>, EvalError> {
+    if attributes.is_empty() {
+        Ok(None)
+    } else {
+        ir::Attributes::new(attributes, ir::AttributeTarget::RunStmt).map(Some)
+    }
+}
+
+/// Evaluate a `run-shell` statement to a command line running the script with
+/// the configured shell interpreter.
+pub(crate) fn eval_run_shell_stmt<S: Scope>(
+    scope: &S,
+    stmt: &ast::RunShellStmt<'_>,
+    commands: &mut Vec<RunCommand>,
+) -> Result<Used, EvalError> {
+    let attributes = eval_run_attributes(&stmt.attributes)?;
+
+    let script = eval_string_expr(scope, &stmt.script)?;
+    if script.value.trim().is_empty() {
+        return Err(EvalError::EmptyCommand(stmt.script.span));
+    }
+    let mut used = script.used;
+
+    let workspace = scope.workspace();
+    let mut builder = ShellCommandLineBuilder::default();
+    builder.push_lit(&workspace.shell).push_arg(&script.value);
+    let (command_line, used_which) = if scope.in_container() {
+        builder.build_in_container(stmt.span, workspace)?
+    } else {
+        builder.build(stmt.span, workspace)?
+    };
+    if let Some(used_which) = used_which {
+        used.insert(used_which);
+    }
+
+    if let Some(attributes) = attributes {
+        commands.push(RunCommand::PushAttributes(attributes));
+    }
+    commands.push(RunCommand::Shell(command_line));
+    if attributes.is_some() {
+        commands.push(RunCommand::PopAttributes);
+    }
+    Ok(used)
+}
+
+#[expect(clippy::too_many_lines)]
+fn eval_run_expr<S: Scope>(
+    scope: &S,
+    expr: &ast::RunExpr<'_>,
+    commands: &mut Vec<RunCommand>,
+    used: &mut Used,
+) -> Result<(), EvalError> {
+    match expr {
+        ast::RunExpr::Shell(expr) => {
+            let shell = eval_command_line(scope, &expr.param, scope.in_container())?;
+            *used |= shell.used;
+            commands.push(RunCommand::Shell(shell.value));
+        }
+        ast::RunExpr::Args(expr) => {
+            let command_line = eval_args_command(scope, expr)?;
+            *used |= command_line.used;
+            commands.push(RunCommand::Shell(command_line.value));
+        }
+        ast::RunExpr::Pipeline(expr) => {
+            let pipeline = eval_pipeline(scope, expr)?;
+            *used |= pipeline.used;
+            commands.push(pipeline.value);
+        }
+        ast::RunExpr::Write(expr) => {
+            let destination = eval(scope, &expr.path)?;
+            let Some(dest_path) = destination.value.as_str() else {
+                return Err(EvalError::UnexpectedList(expr.path.span()));
+            };
+            let dest_path = werk_fs::Path::new(dest_path)
+                .and_then(|path| scope.workspace().get_output_file_path(path))
+                .map_err(|err| EvalError::Path(expr.span, err))?;
+            let data = eval(scope, &expr.value)?;
+            let write_used = destination.used | data.used;
+            let Some(data) = data.value.as_str() else {
+                return Err(EvalError::UnexpectedList(expr.value.span()));
+            };
+
+            *used |= write_used;
+            commands.push(RunCommand::Write(dest_path, data.as_bytes().into()));
+        }
+        ast::RunExpr::Copy(expr) => {
+            let from = eval_string_expr(scope, &expr.src)?;
+            let to = eval_string_expr(scope, &expr.dest)?;
+            let from_path = werk_fs::PathBuf::new(from.value)
+                .and_then(|path| {
+                    path.absolutize(werk_fs::Path::ROOT)
+                        .map(std::borrow::Cow::into_owned)
+                })
+                .map_err(|err| EvalError::Path(expr.src.span, err))?;
+            let to_path = werk_fs::Path::new(&to)
+                .and_then(|path| scope.workspace().get_output_file_path(path))
+                .map_err(|err| EvalError::Path(expr.dest.span, err))?;
+            let copy_used = from.used | to.used;
+            *used |= copy_used;
+            commands.push(RunCommand::Copy(from_path, to_path));
+        }
+        ast::RunExpr::Extract(expr) => {
+            let archive = eval_string_expr(scope, &expr.archive)?;
+            let dest = eval_string_expr(scope, &expr.dest)?;
+            let archive_path = werk_fs::PathBuf::new(archive.value)
+                .and_then(|path| {
+                    path.absolutize(werk_fs::Path::ROOT)
+                        .map(std::borrow::Cow::into_owned)
+                })
+                .map_err(|err| EvalError::Path(expr.archive.span, err))?;
+            let dest_path = werk_fs::Path::new(&dest)
+                .and_then(|path| scope.workspace().get_output_file_path(path))
+                .map_err(|err| EvalError::Path(expr.dest.span, err))?;
+            *used |= archive.used | dest.used;
+            commands.push(RunCommand::Extract(archive_path, dest_path));
+        }
+        ast::RunExpr::Fetch(expr) => {
+            let fetch = eval_fetch_command(scope, expr)?;
+            *used |= fetch.used;
+            commands.push(fetch.value);
+        }
+        ast::RunExpr::Delete(expr) => {
+            let (paths, paths_used) = eval_output_paths(scope, &expr.param)?;
+            *used |= paths_used;
+            commands.push(RunCommand::Delete(paths));
+        }
+        ast::RunExpr::Mkdir(expr) => {
+            let (paths, paths_used) = eval_output_paths(scope, &expr.param)?;
+            *used |= paths_used;
+            commands.push(RunCommand::CreateDir(paths));
+        }
+        ast::RunExpr::Touch(expr) => {
+            let (paths, paths_used) = eval_output_paths(scope, &expr.param)?;
+            *used |= paths_used;
+            commands.push(RunCommand::Touch(paths));
+        }
+        ast::RunExpr::Env(expr) => {
+            let key = eval_string_expr(scope, &expr.key)?;
+            let value = eval_string_expr(scope, &expr.value)?;
+            *used |= key.used;
+            *used |= value.used;
+            commands.push(RunCommand::SetEnv(key.value, value.value));
+        }
+        ast::RunExpr::EnvRemove(expr) => {
+            let key = eval_string_expr(scope, &expr.param)?;
+            *used |= key.used;
+            commands.push(RunCommand::RemoveEnv(key.value));
+        }
+        ast::RunExpr::Timeout(expr) => {
+            commands.push(RunCommand::SetTimeout(eval_timeout(scope, expr)?));
+        }
+        ast::RunExpr::Info(expr) => {
+            let message = eval_string_expr(scope, &expr.param)?;
+            *used |= message.used;
+            commands.push(RunCommand::Info(message.value));
+        }
+        ast::RunExpr::Warn(expr) => {
+            let message = eval_string_expr(scope, &expr.param)?;
+            *used |= message.used;
+            // TODO: Specific warn command.
+            commands.push(RunCommand::Info(message.value));
+        }
+        ast::RunExpr::List(exprs) => {
+            for expr in &exprs.items {
+                eval_run_expr(scope, &expr.item, commands, used)?;
             }
-            ast::RunExpr::Block(block) => {
-                for stmt in &block.statements {
-                    eval_run_exprs_recursively(scope, &stmt.statement, commands, used)?;
-                }
+        }
+        ast::RunExpr::Block(block) => {
+            for stmt in &block.statements {
+                eval_run_expr(scope, &stmt.statement, commands, used)?;
             }
         }
+    }
 
-        Ok(())
+    Ok(())
+}
+
+/// Evaluate the paths of a `delete`, `mkdir`, or `touch` expression, which are always in
+/// the output directory.
+fn eval_output_paths<S: Scope>(
+    scope: &S,
+    expr: &ast::Expr<'_>,
+) -> Result<(Vec<Absolute<std::path::PathBuf>>, Used), EvalError> {
+    let evaluated_paths = eval(scope, expr)?;
+    let mut paths = Vec::new();
+    evaluated_paths
+        .value
+        .try_collect_strings_recursive(|path| {
+            let path = werk_fs::PathBuf::new(path)?;
+            let path = path.absolutize(werk_fs::Path::ROOT)?;
+            let path = scope.workspace().get_output_file_path(&path)?;
+            paths.push(path);
+            Ok(())
+        })
+        .map_err(|err| EvalError::Path(expr.span(), err))?;
+    Ok((paths, evaluated_paths.used))
+}
+
+/// Evaluate a `timeout` statement. A timeout of zero disables the timeout.
+/// Evaluate the directory of an `in-dir` statement, a path in the workspace.
+fn eval_in_dir<S: Scope + ?Sized>(
+    scope: &S,
+    stmt: &ast::InDirStmt<'_>,
+) -> Result<Absolute<std::path::PathBuf>, EvalError> {
+    let dir = eval_string_expr(scope, &stmt.param)?;
+    let path = werk_fs::Path::new(&dir.value)
+        .and_then(|path| path.absolutize(werk_fs::Path::ROOT))
+        .map_err(|err| EvalError::Path(stmt.param.span, err))?;
+    Ok(scope.workspace().resolve_source_path(&path))
+}
+
+fn eval_timeout<S: Scope + ?Sized>(
+    scope: &S,
+    stmt: &ast::TimeoutStmt<'_>,
+) -> Result<Option<std::time::Duration>, EvalError> {
+    let string = eval_string_expr(scope, &stmt.param)?;
+    let Some(duration) = parse_duration(&string.value) else {
+        return Err(EvalError::InvalidDuration(stmt.param.span, string.value));
+    };
+    Ok((!duration.is_zero()).then_some(duration))
+}
+
+/// Parse a duration like `500ms`, `30s`, `1.5m`, or `1h30m`.
+pub(crate) fn parse_duration(s: &str) -> Option<std::time::Duration> {
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return None;
     }
 
+    let mut total = std::time::Duration::ZERO;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(number_len);
+        let unit_len = tail
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        rest = tail;
+
+        let number: f64 = number.parse().ok()?;
+        let seconds = match unit {
+            "ms" => number / 1000.0,
+            "s" => number,
+            "m" => number * 60.0,
+            "h" => number * 3600.0,
+            _ => return None,
+        };
+        total += std::time::Duration::try_from_secs_f64(seconds).ok()?;
+    }
+
+    Some(total)
+}
+
+fn eval_pipeline<S: Scope>(
+    scope: &S,
+    expr: &ast::PipelineExpr<'_>,
+) -> Result<Eval<RunCommand>, EvalError> {
     let mut used = Used::none();
-    eval_run_exprs_recursively(scope, expr, commands, &mut used)?;
-    Ok(used)
+    let mut command_lines = Vec::with_capacity(expr.pipes.len() + 1);
+    let pipes = expr.pipes.iter().map(|pipe| &pipe.command);
+    for command in std::iter::once(&expr.command).chain(pipes) {
+        let shell = eval_command_line(scope, &command.param, scope.in_container())?;
+        used |= shell.used;
+        command_lines.push(shell.value);
+    }
+    let redirection = eval_redirection(scope, expr)?;
+    used |= redirection.used;
+
+    Ok(Eval {
+        value: RunCommand::Pipeline(command_lines, redirection.value),
+        used,
+    })
+}
+
+/// Resolve the files of `< "..."` and `> "..."` redirections. Standard input
+/// is resolved like `<...>` interpolations, and standard output is always
+/// written to the output directory, like `write`.
+fn eval_redirection<S: Scope>(
+    scope: &S,
+    expr: &ast::PipelineExpr<'_>,
+) -> Result<Eval<Redirection>, EvalError> {
+    let mut redirection = Redirection::default();
+    let mut used = Used::none();
+
+    if let Some(ref stdin) = expr.stdin {
+        let path = eval_string_expr(scope, &stdin.path)?;
+        used |= path.used;
+        let path = werk_fs::Path::new(&path.value)
+            .and_then(|path| path.absolutize(werk_fs::Path::ROOT))
+            .map_err(|err| EvalError::Path(stdin.path.span, err))?;
+        redirection.stdin = Some(resolve_path_infer(
+            stdin.path.span,
+            &path,
+            scope.workspace(),
+        )?);
+    }
+
+    if let Some(ref stdout) = expr.stdout {
+        let path = eval_string_expr(scope, &stdout.path)?;
+        used |= path.used;
+        let path = werk_fs::Path::new(&path.value)
+            .and_then(|path| scope.workspace().get_output_file_path(path))
+            .map_err(|err| EvalError::Path(stdout.path.span, err))?;
+        redirection.stdout = Some(path);
+    }
+
+    Ok(Eval {
+        value: redirection,
+        used,
+    })
 }
 
 pub fn eval_shell_command<P: Scope + ?Sized>(
     scope: &P,
     expr: &ast::StringExpr,
+) -> Result<Eval<ShellCommandLine>, EvalError> {
+    eval_command_line(scope, expr, false)
+}
+
+/// Evaluate a command line, which runs in the container of the build recipe
+/// when `in_container` is true. See
+/// [`ShellCommandLineBuilder::build_in_container()`].
+fn eval_command_line<P: Scope + ?Sized>(
+    scope: &P,
+    expr: &ast::StringExpr,
+    in_container: bool,
 ) -> Result<Eval<ShellCommandLine>, EvalError> {
     let mut builder = ShellCommandLineBuilder::default();
 
@@ -874,10 +1229,12 @@ pub fn eval_shell_command<P: Scope + ?Sized>(
                         Some(" ") => {
                             builder.push_all(value);
                         }
-                        // Otherwise, we join the list into a single argument.
+                        // Otherwise, we join the list into a single string,
+                        // which is pasted into the current argument like a
+                        // string value (e.g., `-Wl,{libs:join=","}`).
                         Some(sep) => {
                             let s = flat_join(value, sep);
-                            builder.push_arg(&s);
+                            builder.push_str(&s);
                         }
                         // When no join operator is present take the first element of the list.
                         None => {
@@ -887,15 +1244,19 @@ pub fn eval_shell_command<P: Scope + ?Sized>(
                             builder.push_arg(s);
                         }
                     },
-                    Value::String(s) => {
-                        builder.push_str(s);
+                    Value::String(_) | Value::Path(_) => {
+                        builder.push_str(value.as_str().expect("not a list"));
                     }
                 }
             }
         }
     }
 
-    let (command_line, used_which) = builder.build(expr.span, scope.workspace())?;
+    let (command_line, used_which) = if in_container {
+        builder.build_in_container(expr.span, scope.workspace())?
+    } else {
+        builder.build(expr.span, scope.workspace())?
+    };
 
     if let Some(used_which) = used_which {
         used.insert(used_which);
@@ -907,6 +1268,55 @@ pub fn eval_shell_command<P: Scope + ?Sized>(
     })
 }
 
+/// Evaluate a `fetch` expression. The digest must be 64 hex digits, and is
+/// normalized to lowercase.
+fn eval_fetch_command<P: Scope>(
+    scope: &P,
+    expr: &ast::FetchExpr,
+) -> Result<Eval<RunCommand>, EvalError> {
+    let url = eval_string_expr(scope, &expr.url)?;
+    let sha256 = eval_string_expr(scope, &expr.sha256)?;
+    let dest = eval_string_expr(scope, &expr.dest)?;
+    if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(EvalError::InvalidSha256(expr.sha256.span, sha256.value));
+    }
+    let dest_path = werk_fs::Path::new(&dest)
+        .and_then(|path| scope.workspace().get_output_file_path(path))
+        .map_err(|err| EvalError::Path(expr.dest.span, err))?;
+    let digest = sha256.to_ascii_lowercase();
+    Ok(Eval {
+        value: RunCommand::Fetch(url.value, digest, dest_path),
+        used: url.used | sha256.used | dest.used,
+    })
+}
+
+/// Evaluate an `args` expression. Unlike a command string, each string in the
+/// (flattened) list is passed as exactly one argument, without splitting on
+/// whitespace or trimming. The first string is the program.
+fn eval_args_command<P: Scope>(
+    scope: &P,
+    expr: &ast::ArgsExpr,
+) -> Result<Eval<ShellCommandLine>, EvalError> {
+    let Eval { value, mut used } = eval(scope, &expr.param)?;
+    let mut args = Vec::new();
+    value.for_each_string_recursive(|arg| args.push(arg.to_owned()));
+
+    let mut builder = ShellCommandLineBuilder::from_args(args);
+    let (command_line, used_which) = if scope.in_container() {
+        builder.build_in_container(expr.span, scope.workspace())?
+    } else {
+        builder.build(expr.span, scope.workspace())?
+    };
+    if let Some(used_which) = used_which {
+        used.insert(used_which);
+    }
+
+    Ok(Eval {
+        value: command_line,
+        used,
+    })
+}
+
 fn eval_string_interpolation_stem<P: Scope + ?Sized>(
     scope: &P,
     span: Span,
@@ -919,15 +1329,26 @@ fn eval_string_interpolation_stem<P: Scope + ?Sized>(
         ast::InterpolationStem::PatternCapture => scope
             .get(Lookup::PatternStem)
             .ok_or(EvalError::NoPatternStem(span))?,
+        ast::InterpolationStem::NamedCapture(name) => scope
+            .get(Lookup::NamedCapture(name))
+            .ok_or(EvalError::NoSuchNamedCapture(span, name))?,
         ast::InterpolationStem::CaptureGroup(group) => scope
             .get(Lookup::CaptureGroup(group))
             .ok_or(EvalError::NoSuchCaptureGroup(span, group))?,
         ast::InterpolationStem::Ident(ident) => scope
             .get(Lookup::Ident(ident))
-            .ok_or_else(|| EvalError::NoSuchIdentifier(span, ident.as_ref().to_owned()))?,
+            .ok_or_else(|| no_such_identifier(scope, span, ident))?,
     })
 }
 
+/// Identifier lookup failed, suggesting a similar name visible in the scope.
+fn no_such_identifier<P: Scope + ?Sized>(scope: &P, span: Span, name: Symbol) -> EvalError {
+    let mut names = Vec::new();
+    scope.visible_names(&mut names);
+    let suggestion = werk_util::did_you_mean(name.as_str(), &names).copied();
+    EvalError::NoSuchIdentifier(span, name.to_string(), suggestion)
+}
+
 #[derive(Clone, Copy)]
 enum ResolvePathMode {
     /// Infer from whether or not the path exists in the workspace.
@@ -949,6 +1370,7 @@ fn eval_string_interpolation_ops(
     default_resolve_mode: ResolvePathMode,
 ) -> Result<(), EvalError> {
     let mut resolve_mode = default_resolve_mode;
+    let mut quotes = Vec::new();
 
     for op in ops {
         match op {
@@ -991,18 +1413,160 @@ fn eval_string_interpolation_ops(
             ast::InterpolationOp::ResolveWorkspace => {
                 resolve_mode = ResolvePathMode::Workspace;
             }
+            // Quoting must see the final string, so `<out:quote-sh>` quotes
+            // the native path after `ResolveOsPath`, which is always last.
+            ast::InterpolationOp::Quote(style) => quotes.push(*style),
         }
     }
 
+    for style in quotes {
+        value.recursive_modify(|s| *s = crate::shell::quote(style, s));
+    }
+
     Ok(())
 }
 
+/// Evaluate `is-overridden <ident>` to `"1"` if the global variable was
+/// overridden with `-D`, or `"0"` otherwise.
+/// Evaluate the condition of an `if` expression or a match guard, collecting
+/// the variables used by the parts that were evaluated.
+fn eval_condition(
+    scope: &dyn Scope,
+    condition: &ast::Condition<'_>,
+    used: &mut Used,
+) -> Result<bool, EvalError> {
+    match condition {
+        ast::Condition::Value(expr) => {
+            let value = eval(scope, expr)?;
+            *used |= value.used;
+            Ok(is_truthy(&value.value))
+        }
+        ast::Condition::Compare(cmp) => {
+            let lhs = eval(scope, &cmp.lhs)?;
+            let rhs = eval(scope, &cmp.rhs)?;
+            *used |= lhs.used;
+            *used |= rhs.used;
+            Ok(match cmp.op {
+                ast::CompareOp::Eq(_) => lhs.value == rhs.value,
+                ast::CompareOp::NotEq(_) => lhs.value != rhs.value,
+                ast::CompareOp::Less(_)
+                | ast::CompareOp::LessEq(_)
+                | ast::CompareOp::Greater(_)
+                | ast::CompareOp::GreaterEq(_) => {
+                    let lhs = expect_integer(cmp.lhs.span(), &lhs.value)?;
+                    let rhs = expect_integer(cmp.rhs.span(), &rhs.value)?;
+                    match cmp.op {
+                        ast::CompareOp::Less(_) => lhs < rhs,
+                        ast::CompareOp::LessEq(_) => lhs <= rhs,
+                        ast::CompareOp::Greater(_) => lhs > rhs,
+                        _ => lhs >= rhs,
+                    }
+                }
+            })
+        }
+        ast::Condition::Not(not) => Ok(!eval_condition(scope, &not.param, used)?),
+        ast::Condition::Logical(logical) => {
+            let lhs = eval_condition(scope, &logical.lhs, used)?;
+            match logical.op {
+                ast::LogicalOp::And(_) if !lhs => Ok(false),
+                ast::LogicalOp::Or(_) if lhs => Ok(true),
+                _ => eval_condition(scope, &logical.rhs, used),
+            }
+        }
+    }
+}
+
+/// Values that are false in conditions: `""`, `"0"`, `"false"`, and empty
+/// lists. Everything else is true.
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::List(list) => !list.is_empty(),
+        Value::String(_) | Value::Path(_) => !matches!(value.as_str(), Some("" | "0" | "false")),
+    }
+}
+
+fn eval_if(scope: &dyn Scope, expr: &ast::IfExpr<'_>) -> Result<Eval<Value>, EvalError> {
+    let mut used = Used::none();
+    let branch = if eval_condition(scope, &expr.condition, &mut used)? {
+        &expr.then_expr
+    } else {
+        &expr.else_expr
+    };
+    let mut value = eval(scope, branch)?;
+    value.used |= used;
+    Ok(value)
+}
+
+fn eval_arith(scope: &dyn Scope, expr: &ast::ArithExpr<'_>) -> Result<Eval<Value>, EvalError> {
+    let lhs = eval(scope, &expr.lhs)?;
+    let rhs = eval(scope, &expr.rhs)?;
+    let a = expect_integer(expr.lhs.span(), &lhs.value)?;
+    let b = expect_integer(expr.rhs.span(), &rhs.value)?;
+
+    if b == 0 && matches!(expr.op, ast::ArithOp::Div(_) | ast::ArithOp::Rem(_)) {
+        return Err(EvalError::DivisionByZero(expr.span));
+    }
+    let result = match expr.op {
+        ast::ArithOp::Add(_) => a.checked_add(b),
+        ast::ArithOp::Sub(_) => a.checked_sub(b),
+        ast::ArithOp::Mul(_) => a.checked_mul(b),
+        ast::ArithOp::Div(_) => a.checked_div(b),
+        ast::ArithOp::Rem(_) => a.checked_rem(b),
+    }
+    .ok_or(EvalError::IntegerOverflow(expr.span))?;
+
+    Ok(Eval {
+        value: Value::String(result.to_string()),
+        used: lhs.used | rhs.used,
+    })
+}
+
+/// Interpret a value as an integer, for arithmetic and integer comparisons.
+fn expect_integer(span: Span, value: &Value) -> Result<i64, EvalError> {
+    match value {
+        Value::List(_) => Err(EvalError::UnexpectedList(span)),
+        Value::String(_) | Value::Path(_) => {
+            let s = value.as_str().expect("not a list");
+            s.trim()
+                .parse()
+                .map_err(|_| EvalError::ExpectedInteger(span, s.to_owned()))
+        }
+    }
+}
+
+fn eval_is_overridden(scope: &dyn Scope, ident: &ast::Ident) -> Result<Eval<Value>, EvalError> {
+    let Some(variable) = scope.get(Lookup::Ident(ident.ident)) else {
+        return Err(no_such_identifier(scope, ident.span, ident.ident));
+    };
+    let name = crate::qualify(scope.namespace(), ident.ident);
+    let overridden = scope.workspace().defines.contains_key(&name);
+    Ok(Eval {
+        value: Value::String(if overridden { "1" } else { "0" }.to_owned()),
+        used: variable.used().clone(),
+    })
+}
+
 pub fn eval_shell<P: Scope + ?Sized>(
     scope: &P,
     expr: &ast::StringExpr<'_>,
 ) -> Result<Eval<String>, EvalError> {
     let command = eval_shell_command(scope, expr)?;
 
+    // Global variables with a `#[cache]` attribute reuse output recorded in
+    // `.werk-cache` while it is fresh.
+    let recorded = scope
+        .shell_cache()
+        .map(|policy| (policy, command.value.to_string()));
+    if let Some((policy, ref key)) = recorded {
+        if let Some(stdout) = scope.workspace().recorded_shell_output(key, policy) {
+            tracing::debug!("using recorded output of `{key}`");
+            return Ok(Eval {
+                value: stdout,
+                used: command.used,
+            });
+        }
+    }
+
     // Unconditionally disable color output when executing shell command during eval.
     let mut env = Env::default();
     env.set_no_color();
@@ -1035,9 +1599,19 @@ pub fn eval_shell<P: Scope + ?Sized>(
         ));
     }
 
-    let stdout = String::from_utf8_lossy(output.stdout.trim_ascii());
+    let stdout = String::from_utf8_lossy(output.stdout.trim_ascii()).into_owned();
+
+    // Output from dry-runs may be fake, so never record it.
+    if let Some((policy, key)) = recorded {
+        if !scope.io().is_dry_run() {
+            scope
+                .workspace()
+                .record_shell_output(key, stdout.clone(), policy);
+        }
+    }
+
     Ok(Eval {
-        value: stdout.into_owned(),
+        value: stdout,
         used: command.used,
     })
 }
@@ -1063,6 +1637,7 @@ pub fn eval_read<P: Scope + ?Sized>(
         ));
     };
 
+    scope.workspace().register_consulted_file(&fs_entry.path);
     let contents = scope
         .io()
         .read_file(&fs_entry.path)
@@ -1083,27 +1658,113 @@ pub fn eval_read<P: Scope + ?Sized>(
     })
 }
 
+pub fn eval_hash(scope: &dyn Scope, expr: &ast::HashExpr<'_>) -> Result<Eval<String>, EvalError> {
+    let span = expr.param.span();
+    let Eval {
+        value: paths,
+        mut used,
+    } = eval(scope, &expr.param)?;
+
+    let path_err = |err| EvalError::Path(span, err);
+    let mut contents = Vec::new();
+    paths.try_collect_strings_recursive(|path| {
+        let path = werk_fs::Path::new(&path).map_err(path_err)?;
+        let path = path.absolutize(werk_fs::Path::ROOT).map_err(path_err)?;
+        // Files produced by other recipes are hashed from the output
+        // directory, like the inputs of recipes.
+        let fs_entry = scope
+            .workspace()
+            .get_existing_project_or_output_file(&path)
+            .map_err(|err| match err {
+                crate::Error::Io(error) => EvalError::Io(span, crate::IoError { error }),
+                err => EvalError::Io(span, std::io::Error::other(err.to_string()).into()),
+            })?;
+        let Some(fs_entry) = fs_entry else {
+            return Err(EvalError::Io(
+                span,
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("file not found during `hash`: {path}"),
+                )
+                .into(),
+            ));
+        };
+
+        if scope.workspace().get_project_file(&path).is_some() {
+            scope.workspace().register_consulted_file(&fs_entry.path);
+        }
+        let data = scope
+            .io()
+            .read_file(&fs_entry.path)
+            .map_err(|err| EvalError::Io(span, err.into()))?;
+        contents.push(data);
+        used.insert(UsedVariable::WorkspaceFile(
+            Absolute::symbolicate(&path),
+            fs_entry.metadata.mtime,
+        ));
+        Ok(())
+    })?;
+
+    if contents.is_empty() {
+        return Err(EvalError::EmptyList(span));
+    }
+
+    Ok(Eval {
+        value: scope.workspace().hash_algorithm.digest(&contents),
+        used,
+    })
+}
+
 pub fn eval_glob(
     scope: &dyn Scope,
     expr: &ast::GlobExpr<'_>,
 ) -> Result<Eval<Vec<Value>>, EvalError> {
-    let Eval {
-        value: mut glob_pattern_string,
-        mut used,
-    } = eval_string_expr(scope, &expr.param)?;
+    eval_glob_pattern(scope, &expr.param, expr.span, false)
+}
+
+pub fn eval_glob_out(
+    scope: &dyn Scope,
+    expr: &ast::GlobOutExpr<'_>,
+) -> Result<Eval<Vec<Value>>, EvalError> {
+    eval_glob_pattern(scope, &expr.param, expr.span, true)
+}
+
+fn eval_glob_pattern(
+    scope: &dyn Scope,
+    param: &ast::Expr<'_>,
+    span: Span,
+    include_outputs: bool,
+) -> Result<Eval<Vec<Value>>, EvalError> {
+    let Eval { value, mut used } = eval(scope, param)?;
+
+    // Patterns are relative to the workspace root, also when they don't start
+    // with a slash. Negated patterns keep their `!` in front.
+    let mut patterns = Vec::new();
+    value.try_collect_strings_recursive(|pattern| {
+        let (negate, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => ("!", pattern),
+            None => ("", pattern.as_str()),
+        };
+        let slash = if pattern.starts_with('/') { "" } else { "/" };
+        patterns.push(format!("{negate}{slash}{pattern}"));
+        Ok::<_, EvalError>(())
+    })?;
 
-    if !glob_pattern_string.starts_with('/') {
-        glob_pattern_string.insert(0, '/');
+    let workspace = scope.workspace();
+    let (matches, hash) = if include_outputs {
+        workspace.glob_workspace_and_output_files(&patterns)
+    } else {
+        workspace.glob_workspace_files(&patterns)
     }
-    let (matches, hash) = scope
-        .workspace()
-        .glob_workspace_files(&glob_pattern_string)
-        .map_err(|err| EvalError::Glob(expr.span, Arc::new(err)))?;
-    used.insert(UsedVariable::Glob(Symbol::new(&glob_pattern_string), hash));
-    let matches = matches
-        .into_iter()
-        .map(|p| Value::String(p.into_inner().into()))
-        .collect();
+    .map_err(|err| EvalError::Glob(span, Arc::new(err)))?;
+    let patterns = patterns.join(", ");
+    let key = if include_outputs {
+        Symbol::new(&format!("glob-out {patterns}"))
+    } else {
+        Symbol::new(&patterns)
+    };
+    used.insert(UsedVariable::Glob(key, hash));
+    let matches = matches.into_iter().map(Value::Path).collect();
 
     Ok(Eval {
         value: matches,
@@ -1113,20 +1774,37 @@ pub fn eval_glob(
 
 pub(crate) struct EvaluatedBuildRecipe {
     pub explicit_dependencies: Vec<String>,
+    /// The `from` statement of each explicit dependency.
+    pub dependency_spans: Vec<Span>,
+    /// Whether each explicit dependency is marked `volatile`, so it never
+    /// makes the recipe outdated.
+    pub volatile_dependencies: Vec<bool>,
     pub depfile: Option<String>,
     pub commands: Vec<RunCommand>,
+    /// The statement that produced each command.
+    pub command_spans: Vec<Span>,
     pub env: Env,
+    /// The temporary directory of the recipe, if the recipe refers to it.
+    pub tmp_dir: Option<Absolute<std::path::PathBuf>>,
+    /// The image of the `container` statement running the commands.
+    pub container: Option<String>,
 }
 
+#[expect(clippy::too_many_lines)]
 pub(crate) fn eval_build_recipe_statements(
     scope: &mut BuildRecipeScope<'_>,
     body: &[ast::BodyStmt<ast::BuildRecipeStmt<'_>>],
 ) -> Result<Eval<EvaluatedBuildRecipe>, EvalError> {
     let mut evaluated = EvaluatedBuildRecipe {
         explicit_dependencies: Vec::new(),
+        dependency_spans: Vec::new(),
+        volatile_dependencies: Vec::new(),
         depfile: None,
         commands: Vec::new(),
+        command_spans: Vec::new(),
         env: Env::default(),
+        tmp_dir: None,
+        container: None,
     };
     let mut used = Used::none();
 
@@ -1134,15 +1812,26 @@ pub(crate) fn eval_build_recipe_statements(
         match stmt.statement {
             ast::BuildRecipeStmt::Let(ref let_stmt) => {
                 let value = eval_chain(scope, &let_stmt.value)?;
+                scope.render().did_evaluate_variable(
+                    scope.task_id(),
+                    let_stmt.ident.ident.as_str(),
+                    &scope.workspace().redact_value(&value),
+                );
                 scope.set(let_stmt.ident.ident, value);
             }
             ast::BuildRecipeStmt::From(ref expr) => {
-                let value = eval_chain(scope, &expr.param)?;
+                let (value, volatile) = eval_from_stmt(scope, &expr.param)?;
                 used |= value.used;
                 let offset = evaluated.explicit_dependencies.len();
                 value
                     .value
                     .collect_strings_into(&mut evaluated.explicit_dependencies);
+                evaluated
+                    .dependency_spans
+                    .resize(evaluated.explicit_dependencies.len(), expr.span);
+                evaluated
+                    .volatile_dependencies
+                    .resize(evaluated.explicit_dependencies.len(), volatile);
 
                 // Populate the `in` variable.
                 scope.push_input_files(&evaluated.explicit_dependencies[offset..]);
@@ -1150,15 +1839,11 @@ pub(crate) fn eval_build_recipe_statements(
             ast::BuildRecipeStmt::Depfile(ref expr) => {
                 let value = eval_chain(scope, &expr.param)?;
                 used |= &value.used;
-                match value.value {
-                    Value::String(ref depfile) => {
-                        evaluated.depfile = Some(depfile.clone());
-                        scope.set(Symbol::from("depfile"), value);
-                    }
-                    Value::List(_) => {
-                        return Err(EvalError::UnexpectedList(expr.span));
-                    }
-                }
+                let Some(depfile) = value.value.as_str() else {
+                    return Err(EvalError::UnexpectedList(expr.span));
+                };
+                evaluated.depfile = Some(depfile.to_owned());
+                scope.set(Symbol::from("depfile"), value);
             }
             ast::BuildRecipeStmt::Env(ref expr) => {
                 let key = eval_string_expr(scope, &expr.key)?;
@@ -1173,7 +1858,10 @@ pub(crate) fn eval_build_recipe_statements(
                 evaluated.env.env_remove(key.value);
             }
             ast::BuildRecipeStmt::Run(ref expr) => {
-                used |= eval_run_exprs(scope, &expr.param, &mut evaluated.commands)?;
+                used |= eval_run_stmt(scope, expr, &mut evaluated.commands)?;
+            }
+            ast::BuildRecipeStmt::RunShell(ref stmt) => {
+                used |= eval_run_shell_stmt(scope, stmt, &mut evaluated.commands)?;
             }
             ast::BuildRecipeStmt::Info(ref expr) => {
                 let message = eval_string_expr(scope, &expr.param)?;
@@ -1193,6 +1881,58 @@ pub(crate) fn eval_build_recipe_statements(
                     .commands
                     .push(RunCommand::SetCapture(!kw_expr.param.1));
             }
+            ast::BuildRecipeStmt::Priority(_)
+            | ast::BuildRecipeStmt::Exclude(_)
+            | ast::BuildRecipeStmt::Pool(_)
+            | ast::BuildRecipeStmt::Host(_)
+            | ast::BuildRecipeStmt::Out(_) => {
+                // Used when matching recipes, scheduling and running
+                // commands, and resolving the output file, see
+                // `BuildRecipe::priority`, `BuildRecipe::excludes`,
+                // `BuildRecipe::pool`, `BuildRecipe::host`, and
+                // `eval_out_stmt`.
+            }
+            ast::BuildRecipeStmt::Timeout(ref expr) => {
+                evaluated
+                    .commands
+                    .push(RunCommand::SetTimeout(eval_timeout(scope, expr)?));
+            }
+            ast::BuildRecipeStmt::InDir(ref stmt) => {
+                evaluated
+                    .commands
+                    .push(RunCommand::SetWorkingDir(eval_in_dir(scope, stmt)?));
+            }
+            ast::BuildRecipeStmt::Container(ref expr) => {
+                let image = eval_string_expr(scope, &expr.param)?;
+                used |= image.used;
+                evaluated.container = Some(image.value);
+            }
+        }
+
+        evaluated
+            .command_spans
+            .resize(evaluated.commands.len(), stmt.statement.span());
+    }
+    evaluated.tmp_dir = scope.used_tmp_dir();
+
+    // Only the inputs and outputs of a recipe are copied to and from its host,
+    // so files changed locally by built-in commands would be missed or
+    // overwritten.
+    if scope.recipe().host.is_some() {
+        let builtin = evaluated.commands.iter().position(|command| {
+            matches!(
+                command,
+                RunCommand::Write(..)
+                    | RunCommand::Copy(..)
+                    | RunCommand::Extract(..)
+                    | RunCommand::Fetch(..)
+                    | RunCommand::Delete(..)
+                    | RunCommand::CreateDir(..)
+                    | RunCommand::Touch(..)
+            )
+        });
+        if let Some(index) = builtin {
+            return Err(EvalError::BuiltinOnHost(evaluated.command_spans[index]));
         }
     }
 
@@ -1202,43 +1942,110 @@ pub(crate) fn eval_build_recipe_statements(
     })
 }
 
+/// Evaluate the dependencies of a `from` statement in a build recipe, and
+/// whether they are marked `volatile` by the last operation of the chain.
+fn eval_from_stmt(
+    scope: &dyn Scope,
+    expr: &ast::ExprChain<'_>,
+) -> Result<(Eval<Value>, bool), EvalError> {
+    let (ops, volatile) = match expr.ops.split_last() {
+        Some((last, ops)) if matches!(last.expr, ast::ExprOp::Volatile(_)) => (ops, true),
+        _ => (&*expr.ops, false),
+    };
+    let mut value = eval(scope, &expr.expr)?;
+    for entry in ops {
+        value = eval_op(scope, &entry.expr, value)?;
+    }
+    Ok((value, volatile))
+}
+
+/// Evaluate the `out` statement of a build recipe, giving the location of the
+/// output file in the output directory. The statement is evaluated with only
+/// the pattern stem and captures in scope, not the variables of the recipe
+/// body.
+pub fn eval_out_stmt(
+    workspace: &Workspace,
+    recipe_match: &ir::BuildRecipeMatch<'_>,
+) -> Result<Option<Absolute<werk_fs::PathBuf>>, EvalError> {
+    let Some(stmt) = recipe_match.recipe.out else {
+        return Ok(None);
+    };
+    let root = RootScope::with_namespace(workspace, recipe_match.recipe.namespace);
+    let scope = BuildRecipeScope::new(
+        &root,
+        TaskId::build(&recipe_match.target_file),
+        recipe_match,
+    );
+    let location = eval_string_expr(&scope, &stmt.param)?;
+    let location = werk_fs::Path::new(&location.value)
+        .and_then(|path| path.absolutize(werk_fs::Path::ROOT))
+        .map(Cow::into_owned)
+        .map_err(|err| EvalError::Path(stmt.param.span, err))?;
+    Ok(Some(location))
+}
+
 pub(crate) struct EvaluatedTaskRecipe {
     pub build: Vec<String>,
+    /// The `build` statement of each dependency.
+    pub build_spans: Vec<Span>,
     pub commands: Vec<RunCommand>,
+    /// The statement that produced each command.
+    pub command_spans: Vec<Span>,
     pub env: Env,
+    /// The temporary directory of the recipe, if the recipe refers to it.
+    pub tmp_dir: Option<Absolute<std::path::PathBuf>>,
 }
 
 pub(crate) fn eval_task_recipe_statements(
     scope: &mut TaskRecipeScope<'_>,
     body: &[ast::BodyStmt<ast::TaskRecipeStmt<'_>>],
-) -> Result<EvaluatedTaskRecipe, EvalError> {
+) -> Result<Eval<EvaluatedTaskRecipe>, EvalError> {
     let mut evaluated = EvaluatedTaskRecipe {
         build: Vec::new(),
+        build_spans: Vec::new(),
         commands: Vec::new(),
+        command_spans: Vec::new(),
         env: Env::default(),
+        tmp_dir: None,
     };
+    let mut used = Used::none();
 
     for stmt in body {
         match stmt.statement {
             ast::TaskRecipeStmt::Let(ref let_stmt) => {
                 let value = eval_chain(scope, &let_stmt.value)?;
-                scope.set(let_stmt.ident.ident, Eval::inherent(value.value));
+                scope.render().did_evaluate_variable(
+                    scope.task_id(),
+                    let_stmt.ident.ident.as_str(),
+                    &scope.workspace().redact_value(&value),
+                );
+                scope.set(let_stmt.ident.ident, value);
             }
             ast::TaskRecipeStmt::Build(ref expr) => {
                 let value = eval_chain(scope, &expr.param)?;
+                used |= &value.used;
                 value.value.collect_strings_into(&mut evaluated.build);
+                evaluated
+                    .build_spans
+                    .resize(evaluated.build.len(), expr.span);
             }
             ast::TaskRecipeStmt::Env(ref expr) => {
                 let key = eval_string_expr(scope, &expr.key)?;
                 let value = eval_string_expr(scope, &expr.value)?;
+                used |= key.used;
+                used |= value.used;
                 evaluated.env.env(key.value, value.value);
             }
             ast::TaskRecipeStmt::EnvRemove(ref expr) => {
                 let key = eval_string_expr(scope, &expr.param)?;
+                used |= key.used;
                 evaluated.env.env_remove(key.value);
             }
             ast::TaskRecipeStmt::Run(ref expr) => {
-                eval_run_exprs(scope, &expr.param, &mut evaluated.commands)?;
+                used |= eval_run_stmt(scope, expr, &mut evaluated.commands)?;
+            }
+            ast::TaskRecipeStmt::RunShell(ref stmt) => {
+                used |= eval_run_shell_stmt(scope, stmt, &mut evaluated.commands)?;
             }
             ast::TaskRecipeStmt::Info(ref expr) => {
                 let message = eval_string_expr(scope, &expr.param)?;
@@ -1254,10 +2061,27 @@ pub(crate) fn eval_task_recipe_statements(
             ast::TaskRecipeStmt::SetNoCapture(ref kw_expr) => evaluated
                 .commands
                 .push(RunCommand::SetCapture(!kw_expr.param.1)),
+            ast::TaskRecipeStmt::Timeout(ref expr) => evaluated
+                .commands
+                .push(RunCommand::SetTimeout(eval_timeout(scope, expr)?)),
+            ast::TaskRecipeStmt::InDir(ref stmt) => evaluated
+                .commands
+                .push(RunCommand::SetWorkingDir(eval_in_dir(scope, stmt)?)),
+            ast::TaskRecipeStmt::Pool(_) => {
+                // Used when scheduling commands, see `TaskRecipe::pool`.
+            }
         }
+
+        evaluated
+            .command_spans
+            .resize(evaluated.commands.len(), stmt.statement.span());
     }
+    evaluated.tmp_dir = scope.used_tmp_dir();
 
-    Ok(evaluated)
+    Ok(Eval {
+        value: evaluated,
+        used,
+    })
 }
 
 fn eval_assert_eq(
@@ -1285,10 +2109,11 @@ fn eval_assert_match(
     expr: &ast::AssertMatchExpr<'_>,
     param: Eval<Value>,
 ) -> Result<Eval<Value>, EvalError> {
-    fn get_mismatch<'a>(pattern: &Pattern, value: &'a Value) -> Option<&'a String> {
+    fn get_mismatch<'a>(pattern: &Pattern, value: &'a Value) -> Option<&'a str> {
         match value {
             Value::List(vec) => vec.iter().find_map(|item| get_mismatch(pattern, item)),
-            Value::String(s) => {
+            Value::String(_) | Value::Path(_) => {
+                let s = value.as_str().expect("not a list");
                 if pattern.match_whole_string(s).is_none() {
                     Some(s)
                 } else {
@@ -1305,7 +2130,7 @@ fn eval_assert_match(
     if let Some(mismatch) = get_mismatch(&pattern.value, &param.value) {
         return Err(EvalError::AssertMatchFailed(
             expr.span,
-            Box::new((mismatch.clone(), pattern.value.string)),
+            Box::new((mismatch.to_owned(), pattern.value.string)),
         ));
     }
 
@@ -1319,11 +2144,11 @@ fn flat_join(values: &Value, sep: &str) -> String {
     fn flat_join(values: &[Value], string: &mut String, sep: &str, mut first: bool) {
         for value in values {
             match value {
-                Value::String(s) => {
+                Value::String(_) | Value::Path(_) => {
                     if !first {
                         string.push_str(sep);
                     }
-                    string.push_str(s);
+                    string.push_str(value.as_str().expect("not a list"));
                 }
                 Value::List(values) => flat_join(values, string, sep, first),
             }
@@ -1334,6 +2159,7 @@ fn flat_join(values: &Value, sep: &str) -> String {
 
     match values {
         Value::String(s) => s.clone(),
+        Value::Path(path) => path.as_str().to_owned(),
         Value::List(l) => {
             let mut s = String::new();
             flat_join(l, &mut s, sep, true);
@@ -1345,6 +2171,7 @@ fn flat_join(values: &Value, sep: &str) -> String {
 fn recursive_join(value: Value, sep: &str) -> String {
     match value {
         Value::String(s) => s,
+        Value::Path(path) => path.into_inner().into(),
         ref value @ Value::List(_) => flat_join(value, sep),
     }
 }
@@ -1356,21 +2183,35 @@ fn recursive_resolve_path(
     workspace: &Workspace,
     resolve_mode: ResolvePathMode,
 ) -> Result<(), EvalError> {
-    value.try_recursive_modify(|string| {
-        let path = werk_fs::Path::new(string).map_err(|err| EvalError::Path(span, err))?;
-        let path = path.absolutize(working_dir).map_err(|err| EvalError::Path(span, err))?;
+    let resolve = |path: &Absolute<werk_fs::Path>| {
         let path = match resolve_mode {
-            ResolvePathMode::Infer => resolve_path_infer(span, &path, workspace)?,
-            ResolvePathMode::OutDir => path.resolve(workspace.output_directory()),
-            ResolvePathMode::Workspace => path.resolve(workspace.project_root()),
+            ResolvePathMode::Infer => resolve_path_infer(span, path, workspace)?,
+            ResolvePathMode::OutDir => workspace.output_location(path),
+            ResolvePathMode::Workspace => workspace.resolve_source_path(path),
             ResolvePathMode::Illegal => return Err(EvalError::ResolvePathInPattern(span)),
         };
-        match path.to_str() {
-            Some(path) => path.clone_into(string),
-            None => panic!("Path resolution produced a non-UTF8 path; probably the project root path is non-UTF8"),
+        Ok(path.into_inner().into_os_string().into_string().expect(
+            "Path resolution produced a non-UTF8 path; probably the project root path is non-UTF8",
+        ))
+    };
+
+    match value {
+        Value::List(values) => {
+            for value in values {
+                recursive_resolve_path(span, value, working_dir, workspace, resolve_mode)?;
+            }
         }
-        Ok::<_, EvalError>(())
-    })
+        // Abstract paths are already absolute and normalized.
+        Value::Path(path) => *value = Value::String(resolve(path)?),
+        Value::String(string) => {
+            let path = werk_fs::Path::new(string).map_err(|err| EvalError::Path(span, err))?;
+            let path = path
+                .absolutize(working_dir)
+                .map_err(|err| EvalError::Path(span, err))?;
+            *string = resolve(&path)?;
+        }
+    }
+    Ok(())
 }
 
 fn resolve_path_infer(
@@ -1398,17 +2239,35 @@ fn resolve_path_infer(
             Ok(None) => Ok(workspace_file.path.clone()),
         }
     } else {
-        Ok(path.resolve(workspace.output_directory()))
+        Ok(workspace.output_location(path))
     }
 }
 
 fn recursive_replace_extension(value: &mut Value, from: &str, to: &str) {
-    value.recursive_modify(|s| {
-        if s.ends_with(from) {
-            s.truncate(s.len() - from.len());
-            s.push_str(to);
+    match value {
+        Value::List(values) => {
+            for value in values {
+                recursive_replace_extension(value, from, to);
+            }
         }
-    });
+        // Replacing the extension of a path produces a path, unless the
+        // result is not a valid path.
+        Value::Path(path) => {
+            if let Some(stem) = path.as_str().strip_suffix(from) {
+                let replaced = format!("{stem}{to}");
+                *value = match Absolute::try_from(replaced.as_str()) {
+                    Ok(path) => Value::Path(path),
+                    Err(_) => Value::String(replaced),
+                };
+            }
+        }
+        Value::String(s) => {
+            if s.ends_with(from) {
+                s.truncate(s.len() - from.len());
+                s.push_str(to);
+            }
+        }
+    }
 }
 
 fn recursive_into_filename(value: &mut Value) {
@@ -1426,18 +2285,27 @@ fn recursive_into_filename(value: &mut Value) {
 }
 
 fn recursive_into_dirname(value: &mut Value) {
-    value.recursive_modify(|s| {
-        if let Ok(path) = werk_fs::Path::new(s) {
+    match value {
+        Value::List(values) => values.iter_mut().for_each(recursive_into_dirname),
+        // The parent directory of a path is also a path.
+        Value::Path(path) => {
             if let Some(dirname) = path.parent() {
-                *s = dirname.to_string();
+                *path = dirname.to_path_buf();
             }
-        } else {
-            let path = std::path::Path::new(s);
-            if let Some(dirname) = path.parent() {
-                *s = dirname.to_string_lossy().into_owned();
+        }
+        Value::String(s) => {
+            if let Ok(path) = werk_fs::Path::new(s) {
+                if let Some(dirname) = path.parent() {
+                    *s = dirname.to_string();
+                }
+            } else {
+                let path = std::path::Path::new(s);
+                if let Some(dirname) = path.parent() {
+                    *s = dirname.to_string_lossy().into_owned();
+                }
             }
         }
-    });
+    }
 }
 
 fn recursive_into_ext(value: &mut Value) {
@@ -1478,23 +2346,26 @@ fn recursive_regex_replace(value: &mut Value, regex: &regex::Regex, replacer: &s
 }
 
 fn dedup_recursive(value: Value) -> Value {
-    fn dedup_recursive(set: &mut IndexSet<String>, values: Vec<Value>) {
+    fn dedup_recursive(seen: &mut IndexSet<String>, result: &mut Vec<Value>, values: Vec<Value>) {
         for value in values {
             match value {
-                Value::List(values) => dedup_recursive(set, values),
-                Value::String(s) => {
-                    set.insert(s);
+                Value::List(values) => dedup_recursive(seen, result, values),
+                Value::String(_) | Value::Path(_) => {
+                    if seen.insert(value.as_str().expect("not a list").to_owned()) {
+                        result.push(value);
+                    }
                 }
             }
         }
     }
 
     match value {
-        Value::String(_) => value,
+        Value::String(_) | Value::Path(_) => value,
         Value::List(values) => {
-            let mut set = IndexSet::default();
-            dedup_recursive(&mut set, values);
-            Value::List(set.into_iter().map(Value::String).collect())
+            let mut seen = IndexSet::default();
+            let mut result = Vec::new();
+            dedup_recursive(&mut seen, &mut result, values);
+            Value::List(result)
         }
     }
 }
@@ -1502,6 +2373,27 @@ fn dedup_recursive(value: Value) -> Value {
 fn find_first_string(list: &[Value]) -> Option<&str> {
     list.iter().find_map(|value| match value {
         Value::String(s) => Some(&**s),
+        Value::Path(path) => Some(path.as_str()),
         Value::List(list) => find_first_string(list),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration("1.5m"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("0s"), Some(Duration::ZERO));
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("30"), None);
+        assert_eq!(parse_duration("s"), None);
+        assert_eq!(parse_duration("10 s"), None);
+        assert_eq!(parse_duration("1d"), None);
+    }
+}
@@ -0,0 +1,167 @@
+use werk_fs::Absolute;
+use werk_parser::{Document, Import};
+use werk_util::{Diagnostic as _, DiagnosticError, DiagnosticFileId, DiagnosticSource, Symbol};
+
+use crate::{EvalError, Io};
+
+/// The source code of all Werkfiles imported by a document, directly or
+/// through other imports.
+///
+/// Imported documents borrow their source code, so the sources are read
+/// before the documents are parsed with [`ImportSources::parse()`].
+pub struct ImportSources<'a> {
+    root_origin: &'a std::path::Path,
+    root_source: &'a str,
+    files: Vec<ImportSource>,
+}
+
+struct ImportSource {
+    namespace: Symbol,
+    path: Absolute<std::path::PathBuf>,
+    source: String,
+}
+
+impl<'a> ImportSources<'a> {
+    /// Read all Werkfiles imported by `document`, which lives in `dir`. Import
+    /// paths are relative to the directory of the importing Werkfile.
+    ///
+    /// Imported files come before the files that import them, so they can be
+    /// evaluated in order.
+    pub fn load(
+        io: &dyn Io,
+        document: &Document<'a>,
+        dir: &Absolute<std::path::Path>,
+    ) -> Result<Self, DiagnosticError<'a, EvalError, Self>> {
+        let mut sources = Self {
+            root_origin: document.origin,
+            root_source: document.source,
+            files: Vec::new(),
+        };
+
+        let mut stack = Vec::new();
+        if let Some(path) = document.origin.file_name().map(|name| dir.join(name)) {
+            stack.extend(path.ok());
+        }
+
+        match sources.load_imports(io, document, None, dir, &mut stack) {
+            Ok(()) => Ok(sources),
+            Err(err) => Err(err.into_diagnostic_error(sources)),
+        }
+    }
+
+    fn load_imports(
+        &mut self,
+        io: &dyn Io,
+        document: &Document<'_>,
+        namespace: Option<Symbol>,
+        dir: &Absolute<std::path::Path>,
+        stack: &mut Vec<Absolute<std::path::PathBuf>>,
+    ) -> Result<(), EvalError> {
+        let mut seen_namespaces = Vec::new();
+
+        for import in document.import_stmts() {
+            let span = import.path.0;
+            if seen_namespaces.contains(&import.ident.ident) {
+                return Err(EvalError::DuplicateImport(
+                    import.ident.span,
+                    import.ident.ident.to_string(),
+                ));
+            }
+            seen_namespaces.push(import.ident.ident);
+
+            let path = dir.join(&*import.path.1).map_err(|_| {
+                EvalError::ImportFailed(
+                    span,
+                    import.path.1.to_string().into(),
+                    std::io::Error::from(std::io::ErrorKind::NotFound).into(),
+                )
+            })?;
+            if stack.contains(&path) {
+                return Err(EvalError::CircularImport(span, path.into_inner()));
+            }
+
+            let source = io
+                .read_file(&path)
+                .and_then(|data| {
+                    String::from_utf8(data)
+                        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+                })
+                .map_err(|err| {
+                    EvalError::ImportFailed(span, path.to_path_buf().into_inner(), err.into())
+                })?;
+
+            let namespace = qualify(namespace, import.ident.ident);
+
+            // Find the nested imports. Parse errors are reported when the
+            // imported document is parsed for real.
+            let result = if let Ok(nested) = werk_parser::parse_werk(&path, &source) {
+                let nested_dir = path.parent().unwrap_or(dir).to_path_buf();
+                stack.push(path.clone());
+                let result = self.load_imports(io, &nested, Some(namespace), &nested_dir, stack);
+                stack.pop();
+                result
+            } else {
+                Ok(())
+            };
+
+            self.files.push(ImportSource {
+                namespace,
+                path,
+                source,
+            });
+            result.map_err(|err| err.in_file(import_file_id(self.files.len() - 1)))?;
+        }
+
+        Ok(())
+    }
+
+    /// The paths of all imported Werkfiles.
+    pub fn paths(&self) -> impl Iterator<Item = &Absolute<std::path::Path>> {
+        self.files.iter().map(|file| &*file.path)
+    }
+
+    /// Parse the imported Werkfiles, in the same order as they were loaded.
+    /// The result can be assigned to [`Document::imports`].
+    pub fn parse(
+        &self,
+    ) -> Result<Vec<Import<'_>>, DiagnosticError<'_, werk_parser::Error, DiagnosticSource<'_>>>
+    {
+        self.files
+            .iter()
+            .map(|file| {
+                let document = werk_parser::parse_werk_with_diagnostics(&file.path, &file.source)?;
+                Ok(Import {
+                    namespace: file.namespace,
+                    document,
+                })
+            })
+            .collect()
+    }
+}
+
+impl werk_util::DiagnosticFileRepository for ImportSources<'_> {
+    fn get_source(&self, id: DiagnosticFileId) -> Option<DiagnosticSource<'_>> {
+        if id.0 == 0 {
+            Some(DiagnosticSource::new(self.root_origin, self.root_source))
+        } else {
+            let file = self.files.get(id.0 as usize - 1)?;
+            Some(DiagnosticSource::new(&file.path, &file.source))
+        }
+    }
+}
+
+/// The diagnostic file ID of the import at `index` in [`Document::imports`].
+/// The importing document itself is file 0.
+#[must_use]
+pub fn import_file_id(index: usize) -> DiagnosticFileId {
+    DiagnosticFileId(u32::try_from(index + 1).expect("too many imported Werkfiles"))
+}
+
+/// The name of an item in a namespace, like `lib.cflags`.
+#[must_use]
+pub fn qualify(namespace: Option<Symbol>, name: Symbol) -> Symbol {
+    match namespace {
+        Some(namespace) => Symbol::new(&format!("{namespace}.{name}")),
+        None => name,
+    }
+}
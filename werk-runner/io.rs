@@ -7,10 +7,14 @@ pub use ignore::WalkState;
 use parking_lot::Mutex;
 use werk_fs::{Absolute, Normalize as _};
 
-use crate::{Env, Error, GlobSettings, ShellCommandLine};
+use crate::{Env, Error, GlobSettings, Redirection, ShellCommandLine};
 
+#[cfg(feature = "extract")]
+mod archive;
 mod child;
+mod cleanup;
 pub use child::*;
+pub use cleanup::kill_child_processes;
 
 /// Abstract interface to the file system and OS.
 ///
@@ -20,17 +24,69 @@ pub use child::*;
 /// or `metadata()` do access the filesystem, and should be avoided.
 ///
 /// This abstraction exists to allow for testing the runner in a controlled
-/// environment.
+/// environment, like the in-memory filesystem of `MockIo` in the `tests`
+/// crate, which runs full builds hermetically. Tools embedding werk through a
+/// [`Session`](crate::Session) may also provide their own implementation, for
+/// example to build on a remote filesystem.
+///
+/// [`RealSystem`] is the implementation used by the `werk` binary.
 pub trait Io: Send + Sync + 'static {
     /// Run a command as part of a recipe. This will do nothing in dry-run mode.
+    ///
+    /// When `process_group` is true, the command is started in its own process
+    /// group, so [`Child::kill()`] also terminates any processes it starts.
+    /// Such commands do not receive Ctrl-C from the terminal, so werk kills
+    /// them with [`kill_child_processes()`] when it is interrupted. The runner
+    /// starts all non-interactive recipe commands in their own process group.
+    ///
+    /// Redirected standard output is written to the file instead of being
+    /// forwarded, regardless of `forward_stdout`.
     fn run_recipe_command(
         &self,
         command_line: &ShellCommandLine,
         working_dir: &Absolute<Path>,
         env: &Env,
+        redirection: &Redirection,
         forward_stdout: bool,
+        process_group: bool,
     ) -> Result<Box<dyn Child>, std::io::Error>;
 
+    /// Run a pipeline of commands as part of a recipe, connecting the standard
+    /// output of each command to the standard input of the next. This will do
+    /// nothing in dry-run mode.
+    ///
+    /// The redirection applies to the standard input of the first command and
+    /// the standard output of the last command. Only the last command forwards
+    /// its standard output. Children are returned in the same order as the
+    /// command lines.
+    fn run_recipe_pipeline(
+        &self,
+        command_lines: &[ShellCommandLine],
+        working_dir: &Absolute<Path>,
+        env: &Env,
+        redirection: &Redirection,
+        forward_stdout: bool,
+        process_group: bool,
+    ) -> Result<Vec<Box<dyn Child>>, std::io::Error>;
+
+    /// Run a command of a recipe with the `#[interactive]` attribute. Its
+    /// standard input and output are connected to the terminal unless they
+    /// are redirected, and its standard error is always connected to the
+    /// terminal. This will do nothing in dry-run mode.
+    ///
+    /// The default implementation runs the command like
+    /// [`run_recipe_command()`](Self::run_recipe_command), forwarding its
+    /// standard output.
+    fn run_interactive_command(
+        &self,
+        command_line: &ShellCommandLine,
+        working_dir: &Absolute<Path>,
+        env: &Env,
+        redirection: &Redirection,
+    ) -> Result<Box<dyn Child>, std::io::Error> {
+        self.run_recipe_command(command_line, working_dir, env, redirection, true, false)
+    }
+
     /// Run a command as part of evaluating the contents of a Werkfile. This
     /// might still do something in dry-run mode.
     fn run_during_eval(
@@ -59,6 +115,19 @@ pub trait Io: Send + Sync + 'static {
     /// Read a file from the filesystem.
     fn read_file(&self, path: &Absolute<Path>) -> Result<Vec<u8>, std::io::Error>;
 
+    /// Open a file for reading, along with its length in bytes, so large files
+    /// like build outputs can be hashed without reading them into memory.
+    ///
+    /// The default implementation reads the whole file with
+    /// [`read_file()`](Self::read_file).
+    fn open_file(
+        &self,
+        path: &Absolute<Path>,
+    ) -> Result<(u64, Box<dyn std::io::Read + Send>), std::io::Error> {
+        let data = self.read_file(path)?;
+        Ok((data.len() as u64, Box::new(std::io::Cursor::new(data))))
+    }
+
     /// Write a file to the filesystem.
     fn write_file(&self, path: &Absolute<Path>, data: &[u8]) -> Result<(), std::io::Error>;
 
@@ -72,6 +141,41 @@ pub trait Io: Send + Sync + 'static {
     /// Create the parent directories of `path`, recursively.
     fn create_parent_dirs(&self, path: &Absolute<Path>) -> Result<(), std::io::Error>;
 
+    /// Create a directory and its parents, recursively. Must do nothing in
+    /// dry-run.
+    fn create_dir(&self, path: &Absolute<Path>) -> Result<(), std::io::Error>;
+
+    /// Delete a directory and everything in it. Must do nothing in dry-run.
+    fn delete_dir(&self, path: &Absolute<Path>) -> Result<(), std::io::Error>;
+
+    /// Create an empty file, or set the modification time of an existing file
+    /// to now. Must do nothing in dry-run.
+    fn touch(&self, path: &Absolute<Path>) -> Result<(), std::io::Error>;
+
+    /// Download a file over HTTP(S). With `resume`, an existing file is treated
+    /// as the start of the download, and only the rest is requested, if the
+    /// server supports it. Otherwise the file is replaced. Must do nothing in
+    /// dry-run.
+    fn download(
+        &self,
+        url: &str,
+        path: &Absolute<Path>,
+        resume: bool,
+    ) -> Result<(), std::io::Error>;
+
+    /// Extract a `.tar`, `.tar.gz`, or `.zip` archive into an existing
+    /// directory. Entries must not be written outside of the directory. Must do
+    /// nothing in dry-run.
+    fn extract_archive(
+        &self,
+        archive: &Absolute<Path>,
+        dir: &Absolute<Path>,
+    ) -> Result<(), std::io::Error>;
+
+    /// Whether two paths refer to the same file, like names that only differ
+    /// by case on a case-insensitive file system.
+    fn is_same_file(&self, a: &Absolute<Path>, b: &Absolute<Path>) -> Result<bool, std::io::Error>;
+
     /// Read environment variable.
     fn read_env(&self, name: &str) -> Option<String>;
 
@@ -156,15 +260,24 @@ impl RealSystem {
     }
 }
 
-impl Io for RealSystem {
-    fn run_recipe_command(
-        &self,
+impl RealSystem {
+    fn spawn_recipe_command(
         command_line: &ShellCommandLine,
         working_dir: &Absolute<Path>,
         env: &Env,
-        forward_stdout: bool,
-    ) -> Result<Box<dyn Child>, std::io::Error> {
-        let mut command = smol::process::Command::new(&command_line.program);
+        stdin: std::process::Stdio,
+        stdout: std::process::Stdio,
+        stderr: std::process::Stdio,
+        process_group: bool,
+    ) -> Result<smol::process::Child, std::io::Error> {
+        let mut command = if process_group {
+            let mut command = std::process::Command::new(&command_line.program);
+            #[cfg(unix)]
+            std::os::unix::process::CommandExt::process_group(&mut command, 0);
+            smol::process::Command::from(command)
+        } else {
+            smol::process::Command::new(&command_line.program)
+        };
         command
             .args(
                 command_line
@@ -172,26 +285,159 @@ impl Io for RealSystem {
                     .iter()
                     .filter(|s| !s.trim().is_empty()),
             )
-            .stdin(std::process::Stdio::piped())
-            // Never capture stdout in recipe commands. By convention, all
-            // informational output goes to stderr.
-            .stdout(if forward_stdout {
-                std::process::Stdio::piped()
-            } else {
-                std::process::Stdio::null()
-            })
-            .stderr(std::process::Stdio::piped())
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(stderr)
             // All spawned commands always run in the project root.
             .current_dir(working_dir);
 
+        if env.clear {
+            command.env_clear();
+        }
         for k in &env.env_remove {
             command.env_remove(k);
         }
         command.envs(&env.env);
 
         tracing::trace!("spawning {command:?}");
-        let child = command.spawn()?;
-        Ok(Box::new(child))
+        command.spawn()
+    }
+
+    fn boxed_child(child: smol::process::Child, process_group: bool) -> Box<dyn Child> {
+        Box::new(RecipeChild::new(child, process_group))
+    }
+
+    fn recipe_stdout(
+        redirection: &Redirection,
+        forward_stdout: bool,
+    ) -> Result<std::process::Stdio, std::io::Error> {
+        Ok(match redirection.stdout {
+            Some(ref path) => std::fs::File::create(path)?.into(),
+            // Never capture stdout in recipe commands. By convention, all
+            // informational output goes to stderr.
+            None if forward_stdout => std::process::Stdio::piped(),
+            None => std::process::Stdio::null(),
+        })
+    }
+}
+
+impl Io for RealSystem {
+    fn run_recipe_command(
+        &self,
+        command_line: &ShellCommandLine,
+        working_dir: &Absolute<Path>,
+        env: &Env,
+        redirection: &Redirection,
+        forward_stdout: bool,
+        process_group: bool,
+    ) -> Result<Box<dyn Child>, std::io::Error> {
+        let stdin = match redirection.stdin {
+            Some(ref path) => std::fs::File::open(path)?.into(),
+            None => std::process::Stdio::piped(),
+        };
+        let stdout = Self::recipe_stdout(redirection, forward_stdout)?;
+        let child = Self::spawn_recipe_command(
+            command_line,
+            working_dir,
+            env,
+            stdin,
+            stdout,
+            std::process::Stdio::piped(),
+            process_group,
+        )?;
+        Ok(Self::boxed_child(child, process_group))
+    }
+
+    fn run_interactive_command(
+        &self,
+        command_line: &ShellCommandLine,
+        working_dir: &Absolute<Path>,
+        env: &Env,
+        redirection: &Redirection,
+    ) -> Result<Box<dyn Child>, std::io::Error> {
+        let stdin = match redirection.stdin {
+            Some(ref path) => std::fs::File::open(path)?.into(),
+            None => std::process::Stdio::inherit(),
+        };
+        let stdout = match redirection.stdout {
+            Some(ref path) => std::fs::File::create(path)?.into(),
+            None => std::process::Stdio::inherit(),
+        };
+        // Stay in the process group of werk, so the command can read from the
+        // terminal and receives Ctrl-C.
+        let child = Self::spawn_recipe_command(
+            command_line,
+            working_dir,
+            env,
+            stdin,
+            stdout,
+            std::process::Stdio::inherit(),
+            false,
+        )?;
+        Ok(Self::boxed_child(child, false))
+    }
+
+    fn run_recipe_pipeline(
+        &self,
+        command_lines: &[ShellCommandLine],
+        working_dir: &Absolute<Path>,
+        env: &Env,
+        redirection: &Redirection,
+        forward_stdout: bool,
+        process_group: bool,
+    ) -> Result<Vec<Box<dyn Child>>, std::io::Error> {
+        let mut stdin = match redirection.stdin {
+            Some(ref path) => std::fs::File::open(path)?.into(),
+            None => std::process::Stdio::piped(),
+        };
+
+        let mut children: Vec<Box<dyn Child>> = Vec::with_capacity(command_lines.len());
+        for (index, command_line) in command_lines.iter().enumerate() {
+            let is_last = index + 1 == command_lines.len();
+            let spawned = if is_last {
+                Self::recipe_stdout(redirection, forward_stdout)
+            } else {
+                Ok(std::process::Stdio::piped())
+            }
+            .and_then(|stdout| {
+                Self::spawn_recipe_command(
+                    command_line,
+                    working_dir,
+                    env,
+                    stdin,
+                    stdout,
+                    std::process::Stdio::piped(),
+                    process_group,
+                )
+            })
+            .and_then(|mut child| {
+                let next_stdin = if is_last {
+                    std::process::Stdio::null()
+                } else {
+                    let stdout = child.stdout.take().expect("stdout is piped");
+                    // Nothing has been read from the pipe yet, so this
+                    // completes immediately.
+                    smol::block_on(stdout.into_stdio())?
+                };
+                Ok((child, next_stdin))
+            });
+
+            match spawned {
+                Ok((child, next_stdin)) => {
+                    children.push(Self::boxed_child(child, process_group));
+                    stdin = next_stdin;
+                }
+                Err(err) => {
+                    // Don't leave the beginning of the pipeline running.
+                    for child in &mut children {
+                        _ = child.kill();
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(children)
     }
 
     fn run_during_eval(
@@ -214,6 +460,9 @@ impl Io for RealSystem {
             // All spawned commands always run in the project root.
             .current_dir(working_dir);
 
+        if env.clear {
+            command.env_clear();
+        }
         for k in &env.env_remove {
             command.env_remove(k);
         }
@@ -287,6 +536,7 @@ impl Io for RealSystem {
             git_ignore_exclude,
             git_ignore_from_parents,
             dot_ignore,
+            werk_ignore,
             ignore_explicitly,
         } = settings.clone();
 
@@ -297,6 +547,9 @@ impl Io for RealSystem {
             .git_exclude(git_ignore_exclude)
             .ignore(dot_ignore)
             .parents(git_ignore_from_parents);
+        if werk_ignore {
+            walker.add_custom_ignore_filename(".werkignore");
+        }
 
         walker.filter_entry(move |entry| !ignore_explicitly.is_match(entry.path()));
 
@@ -315,6 +568,15 @@ impl Io for RealSystem {
         std::fs::read(path)
     }
 
+    fn open_file(
+        &self,
+        path: &Absolute<Path>,
+    ) -> Result<(u64, Box<dyn std::io::Read + Send>), std::io::Error> {
+        let file = std::fs::File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok((len, Box::new(file)))
+    }
+
     fn write_file(&self, path: &Absolute<Path>, data: &[u8]) -> Result<(), std::io::Error> {
         std::fs::write(path, data)
     }
@@ -337,6 +599,95 @@ impl Io for RealSystem {
         Ok(())
     }
 
+    fn create_dir(&self, path: &Absolute<Path>) -> Result<(), std::io::Error> {
+        std::fs::create_dir_all(path)
+    }
+
+    fn delete_dir(&self, path: &Absolute<Path>) -> Result<(), std::io::Error> {
+        std::fs::remove_dir_all(path)
+    }
+
+    fn touch(&self, path: &Absolute<Path>) -> Result<(), std::io::Error> {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .set_modified(SystemTime::now())
+    }
+
+    #[cfg(feature = "fetch")]
+    fn download(
+        &self,
+        url: &str,
+        path: &Absolute<Path>,
+        resume: bool,
+    ) -> Result<(), std::io::Error> {
+        let offset = if resume {
+            std::fs::metadata(path).map_or(0, |metadata| metadata.len())
+        } else {
+            0
+        };
+
+        let mut request = ureq::get(url);
+        if offset != 0 {
+            request = request.header("Range", format!("bytes={offset}-"));
+        }
+        let response = match request.call() {
+            // The partial file is already complete, or longer than the file on
+            // the server. Start over.
+            Err(ureq::Error::StatusCode(416)) if offset != 0 => {
+                return self.download(url, path, false)
+            }
+            result => result.map_err(std::io::Error::other)?,
+        };
+
+        let mut file = if response.status() == 206 {
+            std::fs::OpenOptions::new().append(true).open(path)?
+        } else {
+            std::fs::File::create(path)?
+        };
+        std::io::copy(&mut response.into_body().into_reader(), &mut file)?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "fetch"))]
+    fn download(
+        &self,
+        _url: &str,
+        _path: &Absolute<Path>,
+        _resume: bool,
+    ) -> Result<(), std::io::Error> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "werk was built without the `fetch` feature",
+        ))
+    }
+
+    #[cfg(feature = "extract")]
+    fn extract_archive(
+        &self,
+        archive: &Absolute<Path>,
+        dir: &Absolute<Path>,
+    ) -> Result<(), std::io::Error> {
+        archive::extract(archive, dir)
+    }
+
+    #[cfg(not(feature = "extract"))]
+    fn extract_archive(
+        &self,
+        _archive: &Absolute<Path>,
+        _dir: &Absolute<Path>,
+    ) -> Result<(), std::io::Error> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "werk was built without the `extract` feature",
+        ))
+    }
+
+    fn is_same_file(&self, a: &Absolute<Path>, b: &Absolute<Path>) -> Result<bool, std::io::Error> {
+        same_file::is_same_file(a, b)
+    }
+
     fn read_env(&self, name: &str) -> Option<String> {
         std::env::var(name).ok()
     }
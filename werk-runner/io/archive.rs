@@ -0,0 +1,68 @@
+use std::{
+    io::{BufReader, Read as _, Seek as _},
+    path::Path,
+};
+
+use werk_fs::Absolute;
+
+/// The formats understood by `extract`, detected from the first bytes of the
+/// file rather than its extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ArchiveFormat {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    fn detect(magic: &[u8]) -> Self {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            ArchiveFormat::TarGz
+        } else if magic.starts_with(b"PK\x03\x04") || magic.starts_with(b"PK\x05\x06") {
+            ArchiveFormat::Zip
+        } else {
+            ArchiveFormat::Tar
+        }
+    }
+}
+
+/// Unpack an archive into an existing directory. Entries that would be written
+/// outside of the directory are skipped or rejected. The files get the current
+/// time as their modification time, so they are newer than the archive.
+pub(super) fn extract(archive: &Absolute<Path>, dir: &Absolute<Path>) -> std::io::Result<()> {
+    let mut file = std::fs::File::open(archive)?;
+    let mut magic = Vec::with_capacity(4);
+    (&mut file).take(4).read_to_end(&mut magic)?;
+    file.rewind()?;
+    let file = BufReader::new(file);
+
+    match ArchiveFormat::detect(&magic) {
+        ArchiveFormat::Tar => unpack_tar(file, dir),
+        ArchiveFormat::TarGz => unpack_tar(flate2::read::GzDecoder::new(file), dir),
+        ArchiveFormat::Zip => zip::ZipArchive::new(file)
+            .and_then(|mut zip| zip.extract(dir))
+            .map_err(std::io::Error::other),
+    }
+}
+
+fn unpack_tar(reader: impl std::io::Read, dir: &Absolute<Path>) -> std::io::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_mtime(false);
+    archive.unpack(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect() {
+        assert_eq!(
+            ArchiveFormat::detect(&[0x1f, 0x8b, 8, 0]),
+            ArchiveFormat::TarGz
+        );
+        assert_eq!(ArchiveFormat::detect(b"PK\x03\x04"), ArchiveFormat::Zip);
+        assert_eq!(ArchiveFormat::detect(b"src/"), ArchiveFormat::Tar);
+        assert_eq!(ArchiveFormat::detect(b""), ArchiveFormat::Tar);
+    }
+}
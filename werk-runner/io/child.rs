@@ -21,6 +21,10 @@ pub trait Child: Send + Sync + Unpin {
     fn status(
         &mut self,
     ) -> Pin<Box<dyn Future<Output = Result<std::process::ExitStatus, std::io::Error>> + Send>>;
+
+    /// Forcefully terminate the process. If the process was spawned in its own
+    /// process group, any processes it started are terminated as well.
+    fn kill(&mut self) -> io::Result<()>;
 }
 
 impl Child for smol::process::Child {
@@ -52,6 +56,104 @@ impl Child for smol::process::Child {
     {
         Box::pin(self.status())
     }
+
+    fn kill(&mut self) -> io::Result<()> {
+        smol::process::Child::kill(self)
+    }
+}
+
+/// A child process running a recipe command. The process is killed if it is
+/// still running when dropped, for example when werk panics, and it can be
+/// killed by [`kill_child_processes()`](super::kill_child_processes) until
+/// then.
+pub(crate) struct RecipeChild {
+    child: smol::process::Child,
+    /// The child was spawned in its own process group, so it can be
+    /// terminated along with all of its descendants.
+    process_group: bool,
+}
+
+impl RecipeChild {
+    pub fn new(child: smol::process::Child, process_group: bool) -> Self {
+        super::cleanup::did_spawn(&child, process_group);
+        Self {
+            child,
+            process_group,
+        }
+    }
+}
+
+impl Drop for RecipeChild {
+    fn drop(&mut self) {
+        if matches!(self.child.try_status(), Ok(None)) {
+            if let Err(err) = Child::kill(self) {
+                tracing::error!("failed to kill command: {err}");
+            }
+        }
+        super::cleanup::did_drop(&self.child);
+    }
+}
+
+impl Child for RecipeChild {
+    fn stdin(self: Pin<&mut Self>) -> Option<Pin<&mut dyn AsyncWrite>> {
+        Pin::new(&mut self.get_mut().child).stdin()
+    }
+
+    fn stderr(self: Pin<&mut Self>) -> Option<Pin<&mut dyn AsyncRead>> {
+        Pin::new(&mut self.get_mut().child).stderr()
+    }
+
+    fn take_stdin(&mut self) -> Option<Pin<Box<dyn AsyncWrite + Send>>> {
+        self.child.take_stdin()
+    }
+
+    fn take_stdout(&mut self) -> Option<Pin<Box<dyn AsyncRead + Send>>> {
+        self.child.take_stdout()
+    }
+
+    fn take_stderr(&mut self) -> Option<Pin<Box<dyn AsyncRead + Send>>> {
+        self.child.take_stderr()
+    }
+
+    fn status(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<std::process::ExitStatus, std::io::Error>> + Send>>
+    {
+        Child::status(&mut self.child)
+    }
+
+    fn kill(&mut self) -> io::Result<()> {
+        if !self.process_group {
+            return smol::process::Child::kill(&mut self.child);
+        }
+
+        #[cfg(unix)]
+        {
+            // The process group ID is the PID of the process that created it.
+            #[expect(clippy::cast_possible_wrap)]
+            let pgid = self.child.id() as libc::pid_t;
+            // SAFETY: Sending a signal has no memory safety implications.
+            if unsafe { libc::kill(-pgid, libc::SIGKILL) } == 0 {
+                return Ok(());
+            }
+        }
+
+        #[cfg(windows)]
+        {
+            // Windows has no process groups in the Unix sense, but `taskkill`
+            // can terminate the whole process tree.
+            let status = std::process::Command::new("taskkill")
+                .args(["/T", "/F", "/PID", &self.child.id().to_string()])
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .status();
+            if status.is_ok_and(|status| status.success()) {
+                return Ok(());
+            }
+        }
+
+        smol::process::Child::kill(&mut self.child)
+    }
 }
 
 pub enum ChildCaptureOutput {
@@ -67,12 +169,12 @@ pub enum ChildCaptureOutput {
 /// (informational output).
 ///
 /// If both stdout and stderr are simultaneously available, both are emitted
-/// simultaneously.
+/// simultaneously. The stream ends after the exit status.
 #[expect(clippy::type_complexity)]
 pub struct ChildLinesStream {
     stdout: Option<ByteLines<BufReader<Pin<Box<dyn AsyncRead + Send>>>>>,
     stderr: Option<ByteLines<BufReader<Pin<Box<dyn AsyncRead + Send>>>>>,
-    status: Pin<Box<dyn Future<Output = std::io::Result<std::process::ExitStatus>> + Send>>,
+    status: Option<Pin<Box<dyn Future<Output = std::io::Result<std::process::ExitStatus>> + Send>>>,
 }
 
 impl ChildLinesStream {
@@ -101,7 +203,7 @@ impl ChildLinesStream {
             }
         });
 
-        let status = child.status();
+        let status = Some(child.status());
 
         ChildLinesStream {
             stdout,
@@ -151,9 +253,12 @@ impl Stream for ChildLinesStream {
         }
 
         if this.stdout.is_none() && this.stderr.is_none() {
-            this.status
-                .poll_unpin(cx)?
-                .map(|status| Some(Ok(ChildCaptureOutput::Exit(status))))
+            let Some(status) = this.status.as_mut() else {
+                return Poll::Ready(None);
+            };
+            let status = ready!(status.poll_unpin(cx));
+            this.status = None;
+            Poll::Ready(Some(status.map(ChildCaptureOutput::Exit)))
         } else {
             Poll::Pending
         }
@@ -0,0 +1,138 @@
+//! Terminate the commands of recipes when werk exits abnormally, so they don't
+//! keep running in the background.
+//!
+//! On Unix, the running children are recorded, so they can be killed by
+//! [`kill_child_processes()`] when werk receives a termination signal. On
+//! Windows, children are also assigned to a Job Object that terminates them
+//! when werk exits for any reason, including being killed.
+
+use parking_lot::Mutex;
+
+/// The PIDs of running children, and whether they were spawned in their own
+/// process group.
+static RUNNING: Mutex<Vec<(u32, bool)>> = Mutex::new(Vec::new());
+
+pub(crate) fn did_spawn(child: &smol::process::Child, process_group: bool) {
+    #[cfg(windows)]
+    job::assign(child.id());
+    RUNNING.lock().push((child.id(), process_group));
+}
+
+pub(crate) fn did_drop(child: &smol::process::Child) {
+    let mut running = RUNNING.lock();
+    if let Some(index) = running.iter().position(|(pid, _)| *pid == child.id()) {
+        running.swap_remove(index);
+    }
+}
+
+/// Forcefully terminate the commands of all recipes that are still running,
+/// along with the processes they started in their process group. This is
+/// meant to be called right before werk exits because of a signal, and may be
+/// called from any thread.
+pub fn kill_child_processes() {
+    #[cfg(unix)]
+    for (pid, process_group) in RUNNING.lock().iter().copied() {
+        #[expect(clippy::cast_possible_wrap)]
+        let pid = pid as libc::pid_t;
+        // SAFETY: Sending a signal has no memory safety implications.
+        unsafe {
+            libc::kill(if process_group { -pid } else { pid }, libc::SIGKILL);
+        }
+    }
+
+    #[cfg(windows)]
+    job::terminate();
+}
+
+#[cfg(windows)]
+mod job {
+    use std::sync::OnceLock;
+
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, HANDLE},
+        System::{
+            JobObjects::{
+                AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+                SetInformationJobObject, TerminateJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+                JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+            },
+            Threading::{OpenProcess, PROCESS_SET_QUOTA, PROCESS_TERMINATE},
+        },
+    };
+
+    /// A Job Object that terminates all of its processes when its last handle
+    /// is closed, which happens when werk exits.
+    struct Job(HANDLE);
+
+    // SAFETY: Job Object handles can be used from any thread.
+    unsafe impl Send for Job {}
+    unsafe impl Sync for Job {}
+
+    fn job() -> Option<&'static Job> {
+        static JOB: OnceLock<Option<Job>> = OnceLock::new();
+        JOB.get_or_init(|| {
+            // SAFETY: The limit information is a plain struct of the size
+            // that is passed, and the handle is closed if it is not kept.
+            unsafe {
+                let handle = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+                if handle.is_null() {
+                    tracing::error!(
+                        "failed to create Job Object: {}",
+                        std::io::Error::last_os_error()
+                    );
+                    return None;
+                }
+                let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+                info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+                #[expect(clippy::cast_possible_truncation)]
+                let size = size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32;
+                if SetInformationJobObject(
+                    handle,
+                    JobObjectExtendedLimitInformation,
+                    (&raw const info).cast(),
+                    size,
+                ) == 0
+                {
+                    tracing::error!(
+                        "failed to configure Job Object: {}",
+                        std::io::Error::last_os_error()
+                    );
+                    CloseHandle(handle);
+                    return None;
+                }
+                Some(Job(handle))
+            }
+        })
+        .as_ref()
+    }
+
+    pub fn assign(pid: u32) {
+        let Some(job) = job() else {
+            return;
+        };
+        // SAFETY: The process handle is closed after use. Assigning a process
+        // that already exited fails harmlessly.
+        unsafe {
+            let process = OpenProcess(PROCESS_SET_QUOTA | PROCESS_TERMINATE, 0, pid);
+            if process.is_null() {
+                return;
+            }
+            if AssignProcessToJobObject(job.0, process) == 0 {
+                tracing::warn!(
+                    "failed to assign command to Job Object: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+            CloseHandle(process);
+        }
+    }
+
+    pub fn terminate() {
+        if let Some(job) = job() {
+            // SAFETY: The handle is valid for the lifetime of the process.
+            unsafe {
+                TerminateJobObject(job.0, 1);
+            }
+        }
+    }
+}
@@ -1,10 +1,14 @@
 use indexmap::IndexMap;
 use werk_fs::Absolute;
-use werk_parser::{ast, parser::Span};
-use werk_util::{Diagnostic, DiagnosticError, Symbol};
+use werk_parser::{
+    ast,
+    parser::{Span, Spanned as _},
+};
+use werk_util::{Diagnostic, DiagnosticError, DiagnosticFileId, Symbol};
 
 use crate::{
     cache::Hash128, AmbiguousPatternError, EvalError, GlobalVariables, Pattern, PatternMatchData,
+    ResponseFile,
 };
 
 type Result<T, E = EvalError> = std::result::Result<T, E>;
@@ -18,6 +22,11 @@ type Result<T, E = EvalError> = std::result::Result<T, E>;
 #[derive(Default)]
 pub struct Manifest<'a> {
     pub globals: GlobalVariables,
+    /// Profiles declared in the main Werkfile, which may replace global
+    /// variables.
+    pub profiles: IndexMap<Symbol, Profile<'a>>,
+    /// The selected profile, if any.
+    pub profile: Option<Symbol>,
     pub task_recipes: IndexMap<&'static str, TaskRecipe<'a>>,
     pub build_recipes: Vec<BuildRecipe<'a>>,
 }
@@ -33,58 +42,64 @@ impl<'a> Manifest<'a> {
         &'b self,
         path: &Absolute<werk_fs::Path>,
     ) -> Result<Option<BuildRecipeMatch<'b>>, AmbiguousPatternError> {
-        let matches = self.build_recipes.iter().filter_map(|recipe| {
-            recipe
-                .pattern
-                .match_whole_path(path)
-                .map(|match_data| (recipe, match_data))
-        });
-
-        let mut best_match = None;
-
-        for (candidate_recipe, candidate_pattern_match) in matches {
-            match best_match {
-                None => {
-                    // No match yet, pick this candidate.
-                    best_match = Some((candidate_recipe, candidate_pattern_match));
-                }
-                Some((best_recipe, ref best_data)) => {
-                    match (best_data.stem(), candidate_pattern_match.stem()) {
-                        (None, Some(_)) => {
-                            // Best match is exact, do nothing.
-                        }
-                        (Some(_), None) => {
-                            // Candidate is exact, do nothing.
-                            best_match = Some((candidate_recipe, candidate_pattern_match));
-                        }
-                        (Some(best_stem), Some(candidate_stem))
-                            if candidate_stem.len() < best_stem.len() =>
-                        {
-                            // Candidate has a shorter stem, so it's better.
-                            best_match = Some((candidate_recipe, candidate_pattern_match));
-                        }
-                        (Some(best_stem), Some(candidate_stem))
-                            if candidate_stem.len() > best_stem.len() =>
-                        {
-                            // Candidate has a longer stem, so it's worse; do nothing.
-                        }
-                        _ => {
-                            return Err(AmbiguousPatternError {
-                                pattern1: best_recipe.pattern.span,
-                                pattern2: candidate_recipe.pattern.span,
-                                path: path.to_string(),
-                            });
-                        }
-                    }
-                }
+        let mut candidates = self.build_recipe_candidates(path).into_iter();
+        let Some(best) = candidates.next() else {
+            return Ok(None);
+        };
+
+        if let Some(runner_up) = candidates.next() {
+            if runner_up.specificity() == best.specificity() {
+                return Err(AmbiguousPatternError {
+                    pattern1: best.recipe.pattern.span,
+                    pattern2: runner_up.recipe.pattern.span,
+                    path: path.to_string(),
+                });
             }
         }
 
-        Ok(best_match.map(|(recipe, match_data)| BuildRecipeMatch {
-            recipe,
-            match_data,
-            target_file: path.to_owned().into_boxed_path(),
-        }))
+        Ok(Some(best))
+    }
+
+    /// All build recipes whose pattern matches the path, from most to least
+    /// specific. Recipes that are equally specific are in the order that they
+    /// appear in the Werkfile.
+    #[must_use]
+    pub fn build_recipe_candidates<'b>(
+        &'b self,
+        path: &Absolute<werk_fs::Path>,
+    ) -> Vec<BuildRecipeMatch<'b>> {
+        let mut candidates = self
+            .build_recipes
+            .iter()
+            .filter(|recipe| recipe.excluded_by(path).is_none())
+            .filter_map(|recipe| {
+                recipe
+                    .pattern
+                    .match_whole_path(path)
+                    .map(|match_data| BuildRecipeMatch {
+                        recipe,
+                        match_data,
+                        target_file: path.to_owned().into_boxed_path(),
+                    })
+            })
+            .collect::<Vec<_>>();
+        // Stable sort, so ties keep their order.
+        candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.specificity()));
+        candidates
+    }
+
+    /// Build recipes whose pattern matches the path, but which don't build it
+    /// because of an `exclude` statement, along with the matching exclusion.
+    #[must_use]
+    pub fn excluded_build_recipes<'b>(
+        &'b self,
+        path: &Absolute<werk_fs::Path>,
+    ) -> Vec<(&'b BuildRecipe<'a>, &'b Pattern<'a>)> {
+        self.build_recipes
+            .iter()
+            .filter(|recipe| recipe.pattern.match_whole_path(path).is_some())
+            .filter_map(|recipe| Some((recipe, recipe.excluded_by(path)?)))
+            .collect()
     }
 
     pub fn match_recipe_by_name<'b>(
@@ -119,25 +134,288 @@ pub enum RecipeMatch<'a> {
     Build(BuildRecipeMatch<'a>),
 }
 
+impl RecipeMatch<'_> {
+    /// The Werkfile declaring the recipe.
+    #[must_use]
+    pub fn file(&self) -> DiagnosticFileId {
+        match self {
+            RecipeMatch::Task(recipe) => recipe.file,
+            RecipeMatch::Build(recipe_match) => recipe_match.recipe.file,
+        }
+    }
+}
+
 pub struct BuildRecipeMatch<'a> {
     pub recipe: &'a BuildRecipe<'a>,
     pub match_data: PatternMatchData,
     pub target_file: Box<Absolute<werk_fs::Path>>,
 }
 
+impl BuildRecipeMatch<'_> {
+    #[must_use]
+    pub fn specificity(&self) -> Specificity {
+        Specificity {
+            priority: self.recipe.priority,
+            exact: self.match_data.is_verbatim(),
+            stem_len: std::cmp::Reverse(self.match_data.stem_len()),
+            literal_chars: self.recipe.pattern.num_literal_chars(),
+        }
+    }
+}
+
+/// How well a build recipe matches a path. When multiple recipes match, the
+/// greatest specificity wins. The fields are compared in order:
+///
+/// 1. The recipe with the highest `priority`.
+/// 2. A pattern without a stem or named captures (exact match).
+/// 3. The shortest stem, including the named captures.
+/// 4. The pattern with the most literal characters, so `%.c` wins over
+///    `%.(c|cpp)`.
+///
+/// Matches that are equally specific are ambiguous.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Specificity {
+    pub priority: i32,
+    pub exact: bool,
+    pub stem_len: std::cmp::Reverse<usize>,
+    pub literal_chars: usize,
+}
+
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum Edition {
     #[default]
     V1,
 }
 
+/// The digest computed by `hash` expressions, selected with `config
+/// hash-algorithm = "..."`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// `"sip128"`: The 128-bit hash that werk uses for outdatedness, as 32
+    /// hex digits.
+    #[default]
+    Sip128,
+    /// `"sha256"`: SHA-256, as 64 hex digits.
+    Sha256,
+}
+
+/// The ignore files respected when globbing the workspace, selected with
+/// `config glob-ignore = "..."`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GlobIgnore {
+    /// `"git"`: `.gitignore` files (including the global gitignore and
+    /// `.git/info/exclude`), `.ignore` files, and `.werkignore` files.
+    #[default]
+    Git,
+    /// `"werk"`: Only `.werkignore` files.
+    Werk,
+}
+
+impl GlobIgnore {
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "git" => Some(GlobIgnore::Git),
+            "werk" => Some(GlobIgnore::Werk),
+            _ => None,
+        }
+    }
+}
+
+/// When the image of a `container` statement is pulled, selected with `config
+/// container-pull = "..."`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PullPolicy {
+    /// `"missing"`: Pull the image when it is not present locally.
+    #[default]
+    Missing,
+    /// `"always"`: Pull the image every time a command runs in it.
+    Always,
+    /// `"never"`: Never pull the image. Commands fail if it is not present.
+    Never,
+}
+
+impl PullPolicy {
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "missing" => Some(PullPolicy::Missing),
+            "always" => Some(PullPolicy::Always),
+            "never" => Some(PullPolicy::Never),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PullPolicy::Missing => "missing",
+            PullPolicy::Always => "always",
+            PullPolicy::Never => "never",
+        }
+    }
+}
+
+/// The line printed before each command of a recipe, selected with `config
+/// echo-template = "..."`. The placeholders `{step}`, `{steps}`, `{task}`, and
+/// `{command}` are replaced with the step of the recipe, the number of steps,
+/// the name of the task, and the command line. `{{` is a literal `{`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EchoTemplate {
+    parts: Vec<EchoTemplatePart>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum EchoTemplatePart {
+    Literal(String),
+    Step,
+    Steps,
+    Task,
+    Command,
+}
+
+impl EchoTemplate {
+    /// Parse a template. On error, returns the unknown placeholder.
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            literal.push_str(&rest[..start]);
+            rest = &rest[start + 1..];
+            if let Some(after) = rest.strip_prefix('{') {
+                literal.push('{');
+                rest = after;
+                continue;
+            }
+            let Some(end) = rest.find('}') else {
+                return Err(format!("{{{rest}"));
+            };
+            let part = match &rest[..end] {
+                "step" => EchoTemplatePart::Step,
+                "steps" => EchoTemplatePart::Steps,
+                "task" => EchoTemplatePart::Task,
+                "command" => EchoTemplatePart::Command,
+                other => return Err(format!("{{{other}}}")),
+            };
+            if !literal.is_empty() {
+                parts.push(EchoTemplatePart::Literal(std::mem::take(&mut literal)));
+            }
+            parts.push(part);
+            rest = &rest[end + 1..];
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            parts.push(EchoTemplatePart::Literal(literal));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Fill in the placeholders. `step` counts from 1.
+    #[must_use]
+    pub fn render(
+        &self,
+        step: usize,
+        num_steps: usize,
+        task: &str,
+        command: &dyn std::fmt::Display,
+    ) -> String {
+        use std::fmt::Write as _;
+        let mut line = String::new();
+        for part in &self.parts {
+            _ = match part {
+                EchoTemplatePart::Literal(literal) => line.write_str(literal),
+                EchoTemplatePart::Step => write!(line, "{step}"),
+                EchoTemplatePart::Steps => write!(line, "{num_steps}"),
+                EchoTemplatePart::Task => line.write_str(task),
+                EchoTemplatePart::Command => write!(line, "{command}"),
+            };
+        }
+        line
+    }
+}
+
+impl HashAlgorithm {
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sip128" => Some(HashAlgorithm::Sip128),
+            "sha256" => Some(HashAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    /// Compute the digest of `contents` as a hex string. When there is more
+    /// than one item, the digest is computed over the digests of each item.
+    #[must_use]
+    pub fn digest(self, contents: &[Vec<u8>]) -> String {
+        let digest_one = |data: &[u8]| -> Vec<u8> {
+            match self {
+                HashAlgorithm::Sip128 => crate::compute_stable_hash(data).0.to_be_bytes().to_vec(),
+                HashAlgorithm::Sha256 => {
+                    let mut sha = werk_util::Sha256::new();
+                    sha.update(data);
+                    sha.finalize().to_vec()
+                }
+            }
+        };
+
+        let digest = match contents {
+            [single] => digest_one(single),
+            many => digest_one(
+                &many
+                    .iter()
+                    .flat_map(|data| digest_one(data))
+                    .collect::<Vec<_>>(),
+            ),
+        };
+        digest.iter().fold(String::new(), |mut s, byte| {
+            _ = std::fmt::Write::write_fmt(&mut s, format_args!("{byte:02x}"));
+            s
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Profile<'a> {
+    pub span: Span,
+    pub name: Symbol,
+    pub doc_comment: String,
+    pub ast: &'a ast::ProfileStmt<'a>,
+}
+
 #[derive(Debug)]
 pub struct TaskRecipe<'a> {
     pub span: Span,
+    /// The name of the task, including the namespace of the import that it
+    /// came from.
     pub name: Symbol,
     pub doc_comment: String,
     pub ast: &'a ast::CommandRecipe<'a>,
     pub hash: Hash128,
+    /// The namespace of the imported Werkfile declaring the recipe, or `None`
+    /// for the main Werkfile.
+    pub namespace: Option<Symbol>,
+    /// The Werkfile declaring the recipe.
+    pub file: DiagnosticFileId,
+    pub attributes: Attributes,
+    /// The `pool` limiting the number of concurrent commands of the recipe.
+    pub pool: Option<Symbol>,
+}
+
+impl TaskRecipe<'_> {
+    /// Get the pool declared by the last `pool` statement in the recipe body.
+    #[must_use]
+    pub fn pool_of<'b>(ast: &'b ast::CommandRecipe<'_>) -> Option<&'b ast::ConfigString<'b>> {
+        ast.body
+            .statements
+            .iter()
+            .rev()
+            .find_map(|stmt| match stmt.statement {
+                ast::TaskRecipeStmt::Pool(ref kw_expr) => Some(&kw_expr.param),
+                _ => None,
+            })
+    }
 }
 
 #[derive(Debug)]
@@ -147,6 +425,423 @@ pub struct BuildRecipe<'a> {
     pub doc_comment: String,
     pub ast: &'a ast::BuildRecipe<'a>,
     pub hash: Hash128,
+    /// The namespace of the imported Werkfile declaring the recipe, or `None`
+    /// for the main Werkfile.
+    pub namespace: Option<Symbol>,
+    /// The Werkfile declaring the recipe.
+    pub file: DiagnosticFileId,
+    /// The `priority` of the recipe. When the patterns of multiple recipes
+    /// match a path, the recipe with the highest priority is chosen before
+    /// considering how specific the patterns are. Default is 0.
+    pub priority: i32,
+    pub attributes: Attributes,
+    /// The `pool` limiting the number of concurrent commands of the recipe.
+    pub pool: Option<Symbol>,
+    /// The `host` running the commands of the recipe over SSH, instead of the
+    /// local machine.
+    pub host: Option<Symbol>,
+    /// Whether the recipe has a `container` statement, running its commands
+    /// in a container instead of directly on the local machine.
+    pub container: bool,
+    /// The `out` statement placing the output file somewhere else in the
+    /// output directory than the path matched by the pattern.
+    pub out: Option<&'a ast::OutStmt<'a>>,
+    /// The patterns of `exclude` statements. Paths matching any of them are
+    /// not built by the recipe, even if they match its pattern.
+    pub excludes: Vec<Pattern<'a>>,
+}
+
+impl<'a> BuildRecipe<'a> {
+    /// Get the priority declared by the last `priority` statement in the
+    /// recipe body.
+    #[must_use]
+    pub fn priority_of(ast: &ast::BuildRecipe) -> i32 {
+        ast.body
+            .statements
+            .iter()
+            .rev()
+            .find_map(|stmt| match stmt.statement {
+                ast::BuildRecipeStmt::Priority(ref kw_expr) => Some(kw_expr.param.1),
+                _ => None,
+            })
+            .unwrap_or(0)
+    }
+
+    /// Get the pool declared by the last `pool` statement in the recipe body.
+    #[must_use]
+    pub fn pool_of<'b>(ast: &'b ast::BuildRecipe<'_>) -> Option<&'b ast::ConfigString<'b>> {
+        ast.body
+            .statements
+            .iter()
+            .rev()
+            .find_map(|stmt| match stmt.statement {
+                ast::BuildRecipeStmt::Pool(ref kw_expr) => Some(&kw_expr.param),
+                _ => None,
+            })
+    }
+
+    /// Get the host declared by the last `host` statement in the recipe body.
+    #[must_use]
+    pub fn host_of<'b>(ast: &'b ast::BuildRecipe<'_>) -> Option<&'b ast::ConfigString<'b>> {
+        ast.body
+            .statements
+            .iter()
+            .rev()
+            .find_map(|stmt| match stmt.statement {
+                ast::BuildRecipeStmt::Host(ref kw_expr) => Some(&kw_expr.param),
+                _ => None,
+            })
+    }
+
+    /// Get the last `container` statement in the recipe body.
+    #[must_use]
+    pub fn container_of<'b>(ast: &'b ast::BuildRecipe<'_>) -> Option<&'b ast::ContainerStmt<'b>> {
+        ast.body
+            .statements
+            .iter()
+            .rev()
+            .find_map(|stmt| match stmt.statement {
+                ast::BuildRecipeStmt::Container(ref kw_expr) => Some(kw_expr),
+                _ => None,
+            })
+    }
+
+    /// The first `exclude` pattern matching the path, if any.
+    #[must_use]
+    pub fn excluded_by(&self, path: &Absolute<werk_fs::Path>) -> Option<&Pattern<'a>> {
+        self.excludes
+            .iter()
+            .find(|exclude| exclude.match_whole_path(path).is_some())
+    }
+
+    /// Get the patterns of all `exclude` statements in the recipe body.
+    pub fn excludes_of<'b>(
+        ast: &'b ast::BuildRecipe<'a>,
+    ) -> impl Iterator<Item = &'b ast::PatternExpr<'a>> {
+        ast.body
+            .statements
+            .iter()
+            .filter_map(|stmt| match stmt.statement {
+                ast::BuildRecipeStmt::Exclude(ref kw_expr) => Some(&kw_expr.param),
+                _ => None,
+            })
+    }
+
+    /// Get the last `out` statement in the recipe body.
+    #[must_use]
+    pub fn out_of(ast: &'a ast::BuildRecipe<'a>) -> Option<&'a ast::OutStmt<'a>> {
+        ast.body
+            .statements
+            .iter()
+            .rev()
+            .find_map(|stmt| match stmt.statement {
+                ast::BuildRecipeStmt::Out(ref kw_expr) => Some(kw_expr),
+                _ => None,
+            })
+    }
+}
+
+/// The kind of item that attributes are attached to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttributeTarget {
+    TaskRecipe,
+    BuildRecipe,
+    RunStmt,
+}
+
+impl AttributeTarget {
+    fn description(self) -> &'static str {
+        match self {
+            AttributeTarget::TaskRecipe => "task recipes",
+            AttributeTarget::BuildRecipe => "build recipes",
+            AttributeTarget::RunStmt => "`run` statements",
+        }
+    }
+}
+
+/// Attributes like `#[silent]` attached to a recipe or `run` statement.
+/// Boolean attributes may be given a value, like `#[silent = false]`.
+/// Attributes on a `run` statement override those of the recipe.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Attributes {
+    /// `#[no-capture]`: Forward the output of commands to the terminal while
+    /// they are running, instead of only showing it when the recipe fails.
+    pub no_capture: bool,
+    /// `#[silent]`: Don't print commands before executing them, even with
+    /// `--print-commands`.
+    pub silent: bool,
+    /// `#[print-commands]`: Print commands before executing them, even without
+    /// `--print-commands`. `#[print-commands = false]` is the same as
+    /// `#[silent]`. `None` when the attribute is not given.
+    pub print_commands: Option<bool>,
+    /// `#[interactive]`: Connect the standard input, output, and error of
+    /// commands directly to the terminal, pausing the progress display while
+    /// they run. Only one interactive command runs at a time.
+    pub interactive: bool,
+    /// `#[always-run]`: The build recipe is outdated in every run, like a task
+    /// recipe.
+    pub always_run: bool,
+    /// `#[uncached]`: The build recipe is outdated in every run, and the hash
+    /// of its output is not recorded, so its dependents are always rebuilt
+    /// too.
+    pub uncached: bool,
+    /// `#[stamp]`: The target file of the build recipe is created or touched
+    /// when its commands succeed, so recipes without a natural output file
+    /// can still be up to date.
+    pub stamp: bool,
+    /// `#[restat]`: When the output of the build recipe is identical to the
+    /// output of its previous run, recipes depending on it are not rebuilt
+    /// because of it, like `restat` in Ninja.
+    pub restat: bool,
+    /// `#[retry = n]`: Retry commands that fail up to `n` times, waiting
+    /// longer between each attempt.
+    pub retry: u32,
+    /// `#[response-file = "gnu"]`: Pass the arguments of command lines that
+    /// are too long for the platform in a response file, as `@<file>`. `None`
+    /// when the attribute is not given.
+    pub response_file: Option<ResponseFile>,
+}
+
+impl Attributes {
+    #[expect(clippy::too_many_lines)]
+    pub fn new(attributes: &[ast::Attribute], target: AttributeTarget) -> Result<Self> {
+        let mut result = Self::default();
+        let mut seen = Vec::<&'static str>::new();
+
+        for attribute in attributes {
+            if attribute.name.ident == "retry" {
+                if seen.contains(&"retry") {
+                    return Err(EvalError::DuplicateAttribute(attribute.span, "retry"));
+                }
+                seen.push("retry");
+
+                let retry = match attribute.value {
+                    Some(ast::ConfigValue::Int(ast::ConfigInt(_, value))) => {
+                        u32::try_from(value).ok()
+                    }
+                    _ => None,
+                };
+                let Some(retry) = retry else {
+                    return Err(EvalError::InvalidAttributeValue(
+                        attribute.span,
+                        "retry",
+                        "a non-negative integer",
+                    ));
+                };
+                result.retry = retry;
+                continue;
+            }
+
+            if attribute.name.ident == "response-file" {
+                if seen.contains(&"response-file") {
+                    return Err(EvalError::DuplicateAttribute(
+                        attribute.span,
+                        "response-file",
+                    ));
+                }
+                seen.push("response-file");
+                result.response_file = Some(response_file_attribute(attribute)?);
+                continue;
+            }
+
+            if attribute.name.ident == "print-commands" {
+                if seen.contains(&"print-commands") {
+                    return Err(EvalError::DuplicateAttribute(
+                        attribute.span,
+                        "print-commands",
+                    ));
+                }
+                seen.push("print-commands");
+
+                result.print_commands = Some(match attribute.value {
+                    None => true,
+                    Some(ast::ConfigValue::Bool(ast::ConfigBool(_, value))) => value,
+                    Some(ref value) => {
+                        return Err(EvalError::InvalidAttributeValue(
+                            value.span(),
+                            "print-commands",
+                            "a boolean value",
+                        ));
+                    }
+                });
+                continue;
+            }
+
+            let (name, field) = match attribute.name.ident.as_str() {
+                "no-capture" => ("no-capture", &mut result.no_capture),
+                "silent" => ("silent", &mut result.silent),
+                "interactive" => ("interactive", &mut result.interactive),
+                "always-run" if target == AttributeTarget::BuildRecipe => {
+                    ("always-run", &mut result.always_run)
+                }
+                "always-run" => {
+                    return Err(EvalError::AttributeNotAllowed(
+                        attribute.span,
+                        "always-run",
+                        target.description(),
+                    ))
+                }
+                "uncached" if target == AttributeTarget::BuildRecipe => {
+                    ("uncached", &mut result.uncached)
+                }
+                "uncached" => {
+                    return Err(EvalError::AttributeNotAllowed(
+                        attribute.span,
+                        "uncached",
+                        target.description(),
+                    ))
+                }
+                "stamp" if target == AttributeTarget::BuildRecipe => ("stamp", &mut result.stamp),
+                "stamp" => {
+                    return Err(EvalError::AttributeNotAllowed(
+                        attribute.span,
+                        "stamp",
+                        target.description(),
+                    ))
+                }
+                "restat" if target == AttributeTarget::BuildRecipe => {
+                    ("restat", &mut result.restat)
+                }
+                "restat" => {
+                    return Err(EvalError::AttributeNotAllowed(
+                        attribute.span,
+                        "restat",
+                        target.description(),
+                    ))
+                }
+                other => {
+                    return Err(EvalError::UnknownAttribute(
+                        attribute.name.span,
+                        other.to_owned(),
+                    ))
+                }
+            };
+
+            if seen.contains(&name) {
+                return Err(EvalError::DuplicateAttribute(attribute.span, name));
+            }
+            seen.push(name);
+
+            *field = match attribute.value {
+                None => true,
+                Some(ast::ConfigValue::Bool(ast::ConfigBool(_, value))) => value,
+                Some(ref value) => {
+                    return Err(EvalError::InvalidAttributeValue(
+                        value.span(),
+                        name,
+                        "a boolean value",
+                    ));
+                }
+            };
+        }
+
+        Ok(result)
+    }
+}
+
+/// The value of a `#[response-file = "..."]` attribute.
+fn response_file_attribute(attribute: &ast::Attribute) -> Result<ResponseFile> {
+    let response_file = match attribute.value {
+        Some(ast::ConfigValue::String(ast::ConfigString(_, ref name))) => {
+            ResponseFile::from_name(name)
+        }
+        _ => None,
+    };
+    response_file.ok_or(EvalError::InvalidAttributeValue(
+        attribute.span,
+        "response-file",
+        "one of \"gnu\", \"msvc\", or \"none\"",
+    ))
+}
+
+/// Attributes like `#[cache = "1h"]` attached to a global `let` statement,
+/// recording the output of `shell` expressions in the variable's definition in
+/// `.werk-cache`, so expensive probes don't run in every invocation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShellCache {
+    /// `#[cache = "<duration>"]`: Recorded output older than this is run
+    /// again. `#[cache]` without a duration never expires.
+    pub ttl: Option<std::time::Duration>,
+    /// `#[cache-key = "<key>"]`: Recorded output is discarded when the key
+    /// changes, so changing the key forces the commands to run again.
+    pub key: Option<String>,
+}
+
+impl ShellCache {
+    /// Returns `None` when there are no attributes.
+    pub fn new(attributes: &[ast::Attribute]) -> Result<Option<Self>> {
+        let mut result = Self::default();
+        let mut seen = Vec::<&'static str>::new();
+
+        for attribute in attributes {
+            let name = match attribute.name.ident.as_str() {
+                "cache" => "cache",
+                "cache-key" => "cache-key",
+                other => {
+                    let recipe_attribute = [
+                        "no-capture",
+                        "silent",
+                        "print-commands",
+                        "interactive",
+                        "always-run",
+                        "uncached",
+                        "stamp",
+                        "restat",
+                        "retry",
+                        "response-file",
+                    ]
+                    .into_iter()
+                    .find(|name| *name == other);
+                    return Err(match recipe_attribute {
+                        Some(name) => {
+                            EvalError::AttributeNotAllowed(attribute.span, name, "`let` statements")
+                        }
+                        None => EvalError::UnknownAttribute(attribute.name.span, other.to_owned()),
+                    });
+                }
+            };
+
+            if seen.contains(&name) {
+                return Err(EvalError::DuplicateAttribute(attribute.span, name));
+            }
+            seen.push(name);
+
+            match (name, &attribute.value) {
+                ("cache", None) => {}
+                ("cache", Some(value)) => {
+                    let ttl = match value {
+                        ast::ConfigValue::String(ast::ConfigString(_, ttl)) => {
+                            crate::eval::parse_duration(ttl)
+                        }
+                        _ => None,
+                    };
+                    let Some(ttl) = ttl else {
+                        return Err(EvalError::InvalidAttributeValue(
+                            value.span(),
+                            "cache",
+                            "a duration like \"30m\" or \"1h\"",
+                        ));
+                    };
+                    result.ttl = Some(ttl);
+                }
+                (_, Some(ast::ConfigValue::String(ast::ConfigString(_, key)))) => {
+                    result.key = Some(key.clone().into_owned());
+                }
+                (_, value) => {
+                    return Err(EvalError::InvalidAttributeValue(
+                        value
+                            .as_ref()
+                            .map_or(attribute.span, ast::ConfigValue::span),
+                        "cache-key",
+                        "a string",
+                    ));
+                }
+            }
+        }
+
+        Ok((!seen.is_empty()).then_some(result))
+    }
 }
 
 #[derive(Debug, Default, PartialEq)]
@@ -154,7 +849,89 @@ pub struct Config {
     pub edition: Edition,
     pub output_directory: Option<String>,
     pub print_commands: Option<bool>,
+    /// The line printed before each command, instead of the default
+    /// `[step/steps] task: command`.
+    pub echo_template: Option<EchoTemplate>,
     pub default_target: Option<String>,
+    /// Report build progress to the terminal emulator (`OSC 9;4`).
+    pub terminal_progress: Option<bool>,
+    /// The depth of each pool declared with `config pool.<name> = <depth>`.
+    pub pools: IndexMap<Symbol, usize>,
+    /// The hosts declared with `config host.<name> = "<destination>:<dir>"`.
+    pub hosts: IndexMap<Symbol, RemoteHost>,
+    /// The interpreter of `run-shell` statements, like `"bash -c"`.
+    pub shell: Option<String>,
+    /// Directories searched for source files, in priority order, relative to
+    /// the directory of the Werkfile. Empty means the Werkfile directory.
+    pub source_dirs: Vec<String>,
+    /// Directories searched for programs by `which` before `PATH`, in
+    /// priority order, relative to the directory of the Werkfile.
+    pub tool_paths: Vec<String>,
+    /// The profile selected when none is given on the command line.
+    pub profile: Option<String>,
+    /// Check for undefined identifiers and unused variables before evaluating
+    /// the Werkfile.
+    pub strict: bool,
+    /// Create the parent directories of output files before running the
+    /// commands of build recipes. Default is true.
+    pub create_dirs: Option<bool>,
+    /// Run recipe commands in a scrubbed environment with a fixed
+    /// `SOURCE_DATE_EPOCH`. The modification times of outputs are not
+    /// normalized, because outdatedness is decided by comparing them.
+    pub reproducible: bool,
+    /// The digest computed by `hash` expressions.
+    pub hash_algorithm: HashAlgorithm,
+    /// The ignore files respected when globbing the workspace.
+    pub glob_ignore: GlobIgnore,
+    /// The program running the containers of `container` statements, like
+    /// `"podman"`.
+    pub container_runtime: Option<String>,
+    /// When the images of `container` statements are pulled.
+    pub container_pull: PullPolicy,
+}
+
+/// A host declared with `config host.<name> = "<destination>:<dir>"`, which
+/// runs the commands of build recipes with a `host` statement over SSH.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteHost {
+    /// The SSH destination, like `user@builder1`.
+    pub destination: String,
+    /// The absolute directory on the host where inputs are copied and commands
+    /// are run, without a trailing slash.
+    pub dir: String,
+}
+
+impl RemoteHost {
+    /// Parse `"<destination>:<dir>"`, where the directory is absolute.
+    #[must_use]
+    pub fn parse(s: &str) -> Option<Self> {
+        let (destination, dir) = s.split_once(':')?;
+        if destination.is_empty() || !dir.starts_with('/') {
+            return None;
+        }
+        Some(RemoteHost {
+            destination: destination.to_owned(),
+            dir: dir.trim_end_matches('/').to_owned(),
+        })
+    }
+}
+
+fn config_string(config_stmt: &ast::ConfigStmt) -> Result<String> {
+    match config_stmt.value {
+        ast::ConfigValue::String(ast::ConfigString(_, ref value)) => Ok(value.to_string()),
+        ast::ConfigValue::Bool(_) | ast::ConfigValue::Int(_) | ast::ConfigValue::List(_) => {
+            Err(EvalError::ExpectedConfigString(config_stmt.span))
+        }
+    }
+}
+
+fn config_bool(config_stmt: &ast::ConfigStmt) -> Result<bool> {
+    match config_stmt.value {
+        ast::ConfigValue::Bool(ast::ConfigBool(_, value)) => Ok(value),
+        ast::ConfigValue::String(_) | ast::ConfigValue::Int(_) | ast::ConfigValue::List(_) => {
+            Err(EvalError::ExpectedConfigBool(config_stmt.span))
+        }
+    }
 }
 
 impl Config {
@@ -164,6 +941,7 @@ impl Config {
         Self::new(doc).map_err(|err| err.into_diagnostic_error(doc))
     }
 
+    #[allow(clippy::too_many_lines)]
     pub fn new(doc: &werk_parser::Document) -> Result<Self> {
         let mut config = Self::default();
         for stmt in &doc.root.statements {
@@ -184,35 +962,107 @@ impl Config {
                     config.edition = edition;
                 }
                 "out-dir" | "output-directory" => {
-                    let value = match config_stmt.value {
-                        ast::ConfigValue::String(ast::ConfigString(_, ref value)) => {
-                            value.to_string()
-                        }
-                        ast::ConfigValue::Bool(_) => {
-                            return Err(EvalError::ExpectedConfigString(config_stmt.span))
-                        }
-                    };
-                    config.output_directory = Some(value);
+                    config.output_directory = Some(config_string(config_stmt)?);
                 }
                 "print-commands" => {
-                    let value = match config_stmt.value {
-                        ast::ConfigValue::Bool(ast::ConfigBool(_, ref value)) => *value,
-                        ast::ConfigValue::String(_) => {
-                            return Err(EvalError::ExpectedConfigBool(config_stmt.span))
-                        }
-                    };
-                    config.print_commands = Some(value);
+                    config.print_commands = Some(config_bool(config_stmt)?);
+                }
+                "echo-template" => {
+                    let template = config_string(config_stmt)?;
+                    config.echo_template =
+                        Some(EchoTemplate::parse(&template).map_err(|placeholder| {
+                            EvalError::InvalidEchoTemplate(config_stmt.value.span(), placeholder)
+                        })?);
+                }
+                "terminal-progress" => {
+                    config.terminal_progress = Some(config_bool(config_stmt)?);
                 }
                 "default" | "default-target" => {
-                    let value = match config_stmt.value {
-                        ast::ConfigValue::String(ast::ConfigString(_, ref value)) => {
-                            value.to_string()
+                    config.default_target = Some(config_string(config_stmt)?);
+                }
+                "shell" => {
+                    config.shell = Some(config_string(config_stmt)?);
+                }
+                "profile" => {
+                    config.profile = Some(config_string(config_stmt)?);
+                }
+                "strict" => {
+                    config.strict = config_bool(config_stmt)?;
+                }
+                "create-dirs" => {
+                    config.create_dirs = Some(config_bool(config_stmt)?);
+                }
+                "reproducible" => {
+                    config.reproducible = config_bool(config_stmt)?;
+                }
+                "hash-algorithm" => {
+                    let name = config_string(config_stmt)?;
+                    config.hash_algorithm = HashAlgorithm::from_name(&name).ok_or(
+                        EvalError::InvalidHashAlgorithm(config_stmt.value.span(), name),
+                    )?;
+                }
+                "glob-ignore" => {
+                    let name = config_string(config_stmt)?;
+                    config.glob_ignore = GlobIgnore::from_name(&name)
+                        .ok_or(EvalError::InvalidGlobIgnore(config_stmt.value.span(), name))?;
+                }
+                "container-runtime" => {
+                    config.container_runtime = Some(config_string(config_stmt)?);
+                }
+                "container-pull" => {
+                    let name = config_string(config_stmt)?;
+                    config.container_pull = PullPolicy::from_name(&name)
+                        .ok_or(EvalError::InvalidPullPolicy(config_stmt.value.span(), name))?;
+                }
+                "source-dirs" => {
+                    let ast::ConfigValue::List(ref list) = config_stmt.value else {
+                        return Err(EvalError::ExpectedConfigList(config_stmt.span));
+                    };
+                    config.source_dirs = list
+                        .items
+                        .iter()
+                        .map(|item| item.item.1.to_string())
+                        .collect();
+                }
+                "tool-paths" => {
+                    let ast::ConfigValue::List(ref list) = config_stmt.value else {
+                        return Err(EvalError::ExpectedConfigList(config_stmt.span));
+                    };
+                    config.tool_paths = list
+                        .items
+                        .iter()
+                        .map(|item| item.item.1.to_string())
+                        .collect();
+                }
+                key if key.starts_with("pool.") => {
+                    let name = &key["pool.".len()..];
+                    if name.contains('.') {
+                        return Err(EvalError::UnknownConfigKey(config_stmt.ident.span));
+                    }
+                    let depth = match config_stmt.value {
+                        ast::ConfigValue::Int(ast::ConfigInt(span, value)) => {
+                            usize::try_from(value)
+                                .ok()
+                                .filter(|depth| *depth > 0)
+                                .ok_or(EvalError::InvalidPoolDepth(span))?
                         }
-                        ast::ConfigValue::Bool(_) => {
-                            return Err(EvalError::ExpectedConfigString(config_stmt.span))
+                        ast::ConfigValue::String(_)
+                        | ast::ConfigValue::Bool(_)
+                        | ast::ConfigValue::List(_) => {
+                            return Err(EvalError::InvalidPoolDepth(config_stmt.value.span()))
                         }
                     };
-                    config.default_target = Some(value);
+                    config.pools.insert(Symbol::new(name), depth);
+                }
+                key if key.starts_with("host.") => {
+                    let name = &key["host.".len()..];
+                    if name.contains('.') {
+                        return Err(EvalError::UnknownConfigKey(config_stmt.ident.span));
+                    }
+                    let value = config_string(config_stmt)?;
+                    let host = RemoteHost::parse(&value)
+                        .ok_or(EvalError::InvalidHost(config_stmt.value.span(), value))?;
+                    config.hosts.insert(Symbol::new(name), host);
                 }
                 _ => return Err(EvalError::UnknownConfigKey(config_stmt.ident.span)),
             }
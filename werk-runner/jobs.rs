@@ -0,0 +1,129 @@
+use std::{collections::BinaryHeap, time::Duration};
+
+use futures::channel::oneshot;
+use parking_lot::Mutex;
+
+/// Limits the number of recipe commands running at the same time. Unlike a
+/// plain semaphore, waiting tasks get a job slot in priority order rather
+/// than first come first served: tasks with the longest estimated critical
+/// path to the end of the build go first, so long chains of dependencies
+/// start as early as possible.
+pub(crate) struct JobSlots {
+    state: Mutex<State>,
+}
+
+struct State {
+    available: usize,
+    waiting: BinaryHeap<Waiter>,
+    /// Incremented for each waiter, so waiters with the same priority are
+    /// served in the order they arrived.
+    next_seq: u64,
+}
+
+struct Waiter {
+    priority: Duration,
+    seq: u64,
+    send: oneshot::Sender<()>,
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // Higher priority first, then lower sequence number first.
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// A job slot, released when dropped.
+pub(crate) struct JobSlot<'a>(&'a JobSlots);
+
+impl Drop for JobSlot<'_> {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+/// Releases the job slot if the task stops waiting after being handed one.
+struct Waiting<'a> {
+    slots: &'a JobSlots,
+    recv: Option<oneshot::Receiver<()>>,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if let Some(mut recv) = self.recv.take() {
+            recv.close();
+            if let Ok(Some(())) = recv.try_recv() {
+                self.slots.release();
+            }
+        }
+    }
+}
+
+impl JobSlots {
+    pub fn new(jobs: usize) -> Self {
+        Self {
+            state: Mutex::new(State {
+                available: jobs.max(1),
+                waiting: BinaryHeap::new(),
+                next_seq: 0,
+            }),
+        }
+    }
+
+    /// Wait for a free job slot. When several tasks are waiting, the one with
+    /// the highest `priority` gets the next free slot.
+    pub async fn acquire(&self, priority: Duration) -> JobSlot<'_> {
+        let recv = {
+            let mut state = self.state.lock();
+            if state.available > 0 {
+                state.available -= 1;
+                return JobSlot(self);
+            }
+            let (send, recv) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiting.push(Waiter {
+                priority,
+                seq,
+                send,
+            });
+            recv
+        };
+
+        let mut waiting = Waiting {
+            slots: self,
+            recv: Some(recv),
+        };
+        // The sender is only dropped without sending when the waiter is
+        // cancelled, which drops the receiver first.
+        _ = waiting.recv.as_mut().expect("not received yet").await;
+        waiting.recv = None;
+        JobSlot(self)
+    }
+
+    fn release(&self) {
+        let mut state = self.state.lock();
+        while let Some(waiter) = state.waiting.pop() {
+            if waiter.send.send(()).is_ok() {
+                return;
+            }
+        }
+        state.available += 1;
+    }
+}
@@ -1,25 +1,42 @@
 mod cache;
+pub mod check;
+mod container;
 pub mod depfile;
 mod error;
 pub mod eval;
+mod import;
 mod io;
 pub mod ir;
+mod jobs;
+mod member;
+mod metrics;
 mod outdatedness;
 mod pattern;
+mod plan;
+mod remote;
 mod render;
+mod results;
 mod runner;
 mod scope;
+mod session;
 mod shell;
 mod value;
 mod workspace;
 
+pub use cache::Hash128;
 pub use error::*;
+pub use import::*;
 pub use io::*;
+pub use member::*;
+pub use metrics::*;
 pub use outdatedness::*;
 pub use pattern::*;
+pub use plan::*;
 pub use render::*;
+pub use results::*;
 pub use runner::*;
 pub use scope::*;
+pub use session::*;
 pub use shell::*;
 pub use value::*;
 pub use workspace::*;
@@ -0,0 +1,226 @@
+use werk_fs::Absolute;
+use werk_parser::Document;
+use werk_util::{Diagnostic as _, DiagnosticError, DiagnosticFileId, DiagnosticSource, Symbol};
+
+use crate::{EvalError, Io, Workspace};
+
+/// File names that are recognized as Werkfiles, in order of preference.
+pub const WERKFILE_NAMES: &[&str] = &["Werkfile", "werkfile", "build.werk"];
+
+/// The file names recognized as Werkfiles, in order of preference. This is
+/// [`WERKFILE_NAMES`], unless the `WERKFILE_NAMES` environment variable is set
+/// to a comma-separated list of file names.
+#[must_use]
+pub fn werkfile_names(io: &dyn Io) -> Vec<String> {
+    match io.read_env("WERKFILE_NAMES") {
+        Some(names) => names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(ToOwned::to_owned)
+            .collect(),
+        None => WERKFILE_NAMES.iter().map(|&name| name.to_owned()).collect(),
+    }
+}
+
+/// Pick the Werkfile among `names`, where `exists` checks whether a file with
+/// the name exists in the directory being searched, and `same_file` whether
+/// two names refer to the same file. When more than one of the names exist,
+/// the first two are returned as an error. Names that refer to the same file,
+/// like names that only differ by case on case-insensitive file systems, are
+/// only counted once.
+pub fn select_werkfile(
+    names: &[String],
+    mut exists: impl FnMut(&str) -> bool,
+    mut same_file: impl FnMut(&str, &str) -> bool,
+) -> Result<Option<&str>, [&str; 2]> {
+    let mut found: Option<&str> = None;
+    for name in names {
+        if !exists(name) || found.is_some_and(|found| same_file(found, name)) {
+            continue;
+        }
+        match found {
+            Some(found) => return Err([found, name]),
+            None => found = Some(name),
+        }
+    }
+    Ok(found)
+}
+
+/// A member of a workspace, declared in the root Werkfile with
+/// `member "path/to/member"`. Members have their own Werkfile and output
+/// directory, and are built by the runner of the root workspace.
+pub struct Member<'a> {
+    /// The name of the member, which is the path of its directory relative to
+    /// the root Werkfile, like `crates/app`.
+    pub name: Symbol,
+    pub workspace: Workspace<'a>,
+    /// The `default` config of the member's Werkfile, built for the label
+    /// `//name`.
+    pub default_target: Option<String>,
+}
+
+/// The Werkfiles of all workspace members declared by a document.
+pub struct MemberSources {
+    members: Vec<MemberSource>,
+}
+
+struct MemberSource {
+    name: Symbol,
+    dir: Absolute<std::path::PathBuf>,
+    werkfile: Absolute<std::path::PathBuf>,
+    source: String,
+}
+
+impl MemberSources {
+    /// Find and read the Werkfiles of all members declared by `document`,
+    /// which lives in `dir`. Member directories are relative to `dir`.
+    pub fn load<'a>(
+        io: &dyn Io,
+        document: &'a Document<'a>,
+        dir: &Absolute<std::path::Path>,
+    ) -> Result<Self, DiagnosticError<'a, EvalError, &'a Document<'a>>> {
+        Self::load_members(io, document, dir).map_err(|err| err.into_diagnostic_error(document))
+    }
+
+    fn load_members(
+        io: &dyn Io,
+        document: &Document<'_>,
+        dir: &Absolute<std::path::Path>,
+    ) -> Result<Self, EvalError> {
+        let mut members = Vec::<MemberSource>::new();
+        let werkfile_names = werkfile_names(io);
+
+        for member in document.member_stmts() {
+            let span = member.path.0;
+            let name = member.path.1.trim_end_matches('/');
+            if members.iter().any(|existing| existing.name == name) {
+                return Err(EvalError::DuplicateMember(span, name.to_owned()));
+            }
+
+            let member_dir = dir.join(name).map_err(|_| {
+                EvalError::MemberFailed(
+                    span,
+                    name.into(),
+                    std::io::Error::from(std::io::ErrorKind::NotFound).into(),
+                )
+            })?;
+
+            let found = select_werkfile(
+                &werkfile_names,
+                |werkfile_name| {
+                    member_dir.join(werkfile_name).is_ok_and(|werkfile| {
+                        io.metadata(&werkfile)
+                            .is_ok_and(|metadata| metadata.is_file)
+                    })
+                },
+                |a, b| match (member_dir.join(a), member_dir.join(b)) {
+                    (Ok(a), Ok(b)) => io.is_same_file(&a, &b).unwrap_or(false),
+                    _ => false,
+                },
+            )
+            .map_err(|[first, second]| {
+                EvalError::AmbiguousWerkfile(span, first.to_owned(), second.to_owned())
+            })?;
+            let Some(werkfile_name) = found else {
+                return Err(EvalError::MemberFailed(
+                    span,
+                    member_dir.into_inner(),
+                    std::io::Error::from(std::io::ErrorKind::NotFound).into(),
+                ));
+            };
+            let werkfile = member_dir.join(werkfile_name).unwrap();
+            let data = io.read_file(&werkfile).map_err(|err| {
+                EvalError::MemberFailed(span, werkfile.to_path_buf().into_inner(), err.into())
+            })?;
+            let source = String::from_utf8(data).map_err(|err| {
+                EvalError::MemberFailed(
+                    span,
+                    werkfile.to_path_buf().into_inner(),
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, err).into(),
+                )
+            })?;
+
+            members.push(MemberSource {
+                name: Symbol::new(name),
+                dir: member_dir,
+                werkfile,
+                source,
+            });
+        }
+
+        Ok(Self { members })
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// The names, directories, and Werkfile paths of the members, in the order
+    /// they were declared.
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<
+        Item = (
+            Symbol,
+            &Absolute<std::path::Path>,
+            &Absolute<std::path::Path>,
+        ),
+    > {
+        self.members
+            .iter()
+            .map(|member| (member.name, &*member.dir, &*member.werkfile))
+    }
+
+    /// Parse the Werkfiles of the members, in the order they were declared.
+    /// Imports of the parsed documents must be loaded separately.
+    pub fn parse(
+        &self,
+    ) -> Result<Vec<Document<'_>>, DiagnosticError<'_, werk_parser::Error, DiagnosticSource<'_>>>
+    {
+        self.members
+            .iter()
+            .map(|member| {
+                werk_parser::parse_werk_with_diagnostics(&member.werkfile, &member.source)
+            })
+            .collect()
+    }
+}
+
+/// Split a label like `//member:target` into the member name and the target.
+/// The target is `None` for labels like `//member`, which refer to the default
+/// target of the member.
+#[must_use]
+pub fn parse_member_label(label: &str) -> Option<(&str, Option<&str>)> {
+    let label = label.strip_prefix("//")?;
+    Some(match label.split_once(':') {
+        Some((member, target)) => (member, Some(target)),
+        None => (label, None),
+    })
+}
+
+const MEMBER_FILE_SHIFT: u32 = 20;
+
+/// The diagnostic file ID of `file` in the Werkfiles of the workspace member
+/// at `index`. The lower bits are the file ID within the member's workspace
+/// (the member's Werkfile or one of its imports), and the upper bits identify
+/// the member.
+#[must_use]
+pub fn member_file_id(index: usize, file: DiagnosticFileId) -> DiagnosticFileId {
+    let member = u32::try_from(index + 1).expect("too many workspace members");
+    DiagnosticFileId(member << MEMBER_FILE_SHIFT | file.0)
+}
+
+/// The inverse of [`member_file_id()`]. Returns `None` as the member index for
+/// files in the root workspace.
+#[must_use]
+pub fn split_member_file_id(id: DiagnosticFileId) -> (Option<usize>, DiagnosticFileId) {
+    let member = id.0 >> MEMBER_FILE_SHIFT;
+    let file = DiagnosticFileId(id.0 & ((1 << MEMBER_FILE_SHIFT) - 1));
+    if member == 0 {
+        (None, file)
+    } else {
+        (Some(member as usize - 1), file)
+    }
+}
@@ -0,0 +1,192 @@
+use std::time::{Duration, Instant};
+
+use ahash::{HashMap, HashSet};
+use parking_lot::Mutex;
+
+use crate::TaskId;
+
+/// Number of commands reported in [`BuildSummary::slowest_commands`].
+pub const NUM_SLOWEST_COMMANDS: usize = 3;
+
+/// Statistics about a run, from [`Runner::summary()`](crate::Runner::summary).
+#[derive(Debug, Clone, Default)]
+pub struct BuildSummary {
+    /// Tasks that ran their commands: outdated build recipes and all task
+    /// recipes.
+    pub executed: usize,
+    /// Build recipes that were up-to-date.
+    pub up_to_date: usize,
+    /// Outdated build recipes that did not run their commands, because
+    /// another recipe ran identical commands in the same run.
+    pub cache_hits: usize,
+    /// Wall time since the runner was created.
+    pub total_time: Duration,
+    /// The longest time spent running commands along a chain of dependencies.
+    /// This is the shortest the run could have taken with unlimited jobs.
+    pub critical_path_time: Duration,
+    /// The commands that took the longest to run, slowest first.
+    pub slowest_commands: Vec<CommandTiming>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandTiming {
+    /// The task that ran the command, with a `//member:` prefix if the task
+    /// belongs to a workspace member.
+    pub task: String,
+    /// The command line, or the command lines of a pipeline separated by `|`.
+    pub command: String,
+    /// How long the command ran, including any retries.
+    pub duration: Duration,
+}
+
+/// Identifies a task across workspace members. The member index is `None` for
+/// the root workspace.
+pub(crate) type TaskKey = (Option<usize>, TaskId);
+
+/// Collects the statistics of a run. Shared by the runners of all workspace
+/// members.
+pub(crate) struct Metrics {
+    started: Instant,
+    state: Mutex<MetricsState>,
+}
+
+#[derive(Default)]
+struct MetricsState {
+    executed: usize,
+    up_to_date: usize,
+    cache_hits: usize,
+    /// Time spent running the commands of each task.
+    command_time: HashMap<TaskKey, Duration>,
+    /// The critical path time of each finished task, which is its own command
+    /// time plus the longest critical path time of its dependencies.
+    critical_path: HashMap<TaskKey, Duration>,
+    /// Tasks that reused the result of identical commands of another task.
+    shared: HashSet<TaskKey>,
+    /// Finished tasks in the order they finished, with their dependencies
+    /// and the estimated duration of their commands.
+    finished: Vec<(TaskKey, Vec<TaskKey>, Duration)>,
+    slowest_commands: Vec<CommandTiming>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            state: Mutex::new(MetricsState::default()),
+        }
+    }
+
+    /// A command (or pipeline) of the task finished running.
+    pub fn did_run_command(&self, key: TaskKey, timing: CommandTiming) {
+        let mut state = self.state.lock();
+        *state.command_time.entry(key).or_default() += timing.duration;
+        let index = state
+            .slowest_commands
+            .partition_point(|other| other.duration >= timing.duration);
+        if index < NUM_SLOWEST_COMMANDS {
+            state.slowest_commands.insert(index, timing);
+            state.slowest_commands.truncate(NUM_SLOWEST_COMMANDS);
+        }
+    }
+
+    /// The task reused the result of the commands of `other` instead of
+    /// running its own. The time spent running them still counts towards the
+    /// critical path of the task.
+    pub fn did_share_commands(&self, key: TaskKey, other: TaskKey) {
+        let mut state = self.state.lock();
+        let time = state.command_time.get(&other).copied().unwrap_or_default();
+        state.command_time.insert(key, time);
+        state.shared.insert(key);
+    }
+
+    /// The time spent running the commands of the task so far.
+    pub fn command_time(&self, key: TaskKey) -> Duration {
+        let state = self.state.lock();
+        state.command_time.get(&key).copied().unwrap_or_default()
+    }
+
+    /// The task finished. `executed` is false if the task was up-to-date, in
+    /// which case `previous_duration` is how long its commands took when they
+    /// last ran, if known.
+    pub fn did_finish(
+        &self,
+        key: TaskKey,
+        dependencies: &[TaskKey],
+        executed: bool,
+        previous_duration: Option<Duration>,
+    ) {
+        let mut state = self.state.lock();
+        if !executed {
+            state.up_to_date += 1;
+        } else if state.shared.contains(&key) {
+            state.cache_hits += 1;
+        } else {
+            state.executed += 1;
+        }
+        let dependencies_time = dependencies
+            .iter()
+            .filter_map(|dep| state.critical_path.get(dep))
+            .max()
+            .copied()
+            .unwrap_or_default();
+        let own_time = state.command_time.get(&key).copied().unwrap_or_default();
+        state
+            .critical_path
+            .insert(key, dependencies_time + own_time);
+        let duration = if executed {
+            own_time
+        } else {
+            previous_duration.unwrap_or_default()
+        };
+        state.finished.push((key, dependencies.to_vec(), duration));
+    }
+
+    /// The task `key` stands for the task `target` in another workspace, and
+    /// shares its critical path.
+    pub fn did_finish_alias(&self, key: TaskKey, target: TaskKey) {
+        let mut state = self.state.lock();
+        if let Some(time) = state.critical_path.get(&target).copied() {
+            state.critical_path.insert(key, time);
+        }
+        state.finished.push((key, vec![target], Duration::ZERO));
+    }
+
+    /// The estimated time from the start of each finished task to the end of
+    /// the build, assuming unlimited jobs: the duration of the task plus the
+    /// longest remaining critical path of the tasks depending on it. Tasks
+    /// that were up-to-date count with their duration from the run that last
+    /// executed them.
+    pub fn remaining_critical_paths(&self) -> Vec<(TaskKey, Duration)> {
+        let state = self.state.lock();
+        // Dependents always finish after their dependencies, so visiting the
+        // tasks in reverse order visits all dependents of a task before it.
+        let mut longest_dependent = HashMap::<TaskKey, Duration>::default();
+        let mut remaining = Vec::with_capacity(state.finished.len());
+        for (key, dependencies, duration) in state.finished.iter().rev() {
+            let time = *duration + longest_dependent.get(key).copied().unwrap_or_default();
+            for dependency in dependencies {
+                let longest = longest_dependent.entry(*dependency).or_default();
+                *longest = (*longest).max(time);
+            }
+            remaining.push((*key, time));
+        }
+        remaining
+    }
+
+    pub fn summary(&self) -> BuildSummary {
+        let state = self.state.lock();
+        BuildSummary {
+            executed: state.executed,
+            up_to_date: state.up_to_date,
+            cache_hits: state.cache_hits,
+            total_time: self.started.elapsed(),
+            critical_path_time: state
+                .critical_path
+                .values()
+                .max()
+                .copied()
+                .unwrap_or_default(),
+            slowest_commands: state.slowest_commands.clone(),
+        }
+    }
+}
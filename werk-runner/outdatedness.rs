@@ -4,12 +4,15 @@ use std::{
 };
 
 use werk_fs::{Absolute, SymPath};
-use werk_util::Symbol;
+use werk_parser::parser::Span;
+use werk_util::{DiagnosticFileId, LineColumn, LineIndex, Symbol};
 
 use crate::{
-    cache::TargetOutdatednessCache,
+    cache::{Hash128, TargetOutdatednessCache},
     eval::{Used, UsedVariable},
-    ir, TaskId, Workspace,
+    ir,
+    workspace::compute_stable_hash,
+    Env, RunCommand, TaskId, Workspace,
 };
 
 /// A reason why a variable or recipe is "outdated".
@@ -33,19 +36,107 @@ pub enum Reason {
     Define(Symbol),
     /// The recipe has a dependency that was rebuilt.
     Rebuilt(TaskId),
+    /// The target was explicitly forced to rebuild.
+    Forced,
+    /// The recipe has the `#[always-run]` attribute.
+    AlwaysRun,
+    /// The recipe has the `#[uncached]` attribute.
+    Uncached,
 }
 
 impl Reason {
     pub fn missing(path: impl Into<Absolute<SymPath>>) -> Self {
         Reason::Missing(path.into())
     }
+
+    /// Stable identifier of the kind of reason, for machine-readable output.
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Reason::Missing(_) => "missing",
+            Reason::Modified(..) => "modified",
+            Reason::Glob(_) => "glob",
+            Reason::Env(_) => "env",
+            Reason::Which(_) => "which",
+            Reason::GlobalChanged(_) => "global-changed",
+            Reason::RecipeChanged => "recipe-changed",
+            Reason::Define(_) => "define",
+            Reason::Rebuilt(_) => "rebuilt",
+            Reason::Forced => "forced",
+            Reason::AlwaysRun => "always-run",
+            Reason::Uncached => "uncached",
+        }
+    }
+}
+
+/// Additional information about a [`Reason`], used to explain outdatedness to
+/// tools.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReasonDetail {
+    /// The value recorded by the previous build, if any.
+    pub old: Option<ReasonValue>,
+    /// The value that was observed in this build, if any.
+    pub new: Option<ReasonValue>,
+    /// The recipe that was being evaluated when the reason was found.
+    pub location: Option<ReasonLocation>,
+}
+
+/// Where the recipe that found a reason is declared, for tools that point at
+/// it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReasonLocation {
+    /// The path of the Werkfile declaring the recipe. This is not necessarily
+    /// the main Werkfile, when the recipe is imported or belongs to a
+    /// workspace member.
+    pub file: String,
+    /// The byte range of the recipe in the Werkfile.
+    pub span: Span,
+    /// The line and column of the start and end of the recipe.
+    pub range: std::ops::Range<LineColumn>,
+}
+
+impl ReasonLocation {
+    #[must_use]
+    pub fn new(workspace: &Workspace, file: DiagnosticFileId, span: Span) -> Option<Self> {
+        let source = workspace.get_werkfile_source(file)?;
+        Some(Self {
+            file: source.file.to_owned(),
+            span,
+            range: LineIndex::new(source.source).range(span),
+        })
+    }
+}
+
+/// A value that changed between builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReasonValue {
+    /// Hash of a value recorded in the cache (glob results, environment
+    /// variables, recipe ASTs, etc.).
+    Hash(Hash128),
+    /// Modification time of a file.
+    Mtime(std::time::SystemTime),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub struct Outdatedness {
     pub reasons: BTreeSet<Reason>,
+    /// Details about some of the reasons. These are informational, and are not
+    /// considered when comparing outdatedness.
+    pub details: BTreeMap<Reason, ReasonDetail>,
+    /// Reasons from dependencies marked `volatile`, which did not make the
+    /// target outdated. These are informational, like `details`.
+    pub ignored: BTreeSet<Reason>,
 }
 
+impl PartialEq for Outdatedness {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.reasons == other.reasons
+    }
+}
+
+impl Eq for Outdatedness {}
+
 impl Outdatedness {
     pub fn new<I: IntoIterator<Item = Reason>>(reasons: I) -> Self {
         Self::from_iter(reasons)
@@ -59,6 +150,8 @@ impl Outdatedness {
     pub fn unchanged() -> Self {
         Self {
             reasons: BTreeSet::new(),
+            details: BTreeMap::new(),
+            ignored: BTreeSet::new(),
         }
     }
 
@@ -67,7 +160,11 @@ impl Outdatedness {
     pub fn outdated(reason: Reason) -> Self {
         let mut reasons = BTreeSet::new();
         reasons.insert(reason);
-        Self { reasons }
+        Self {
+            reasons,
+            details: BTreeMap::new(),
+            ignored: BTreeSet::new(),
+        }
     }
 
     #[inline]
@@ -86,12 +183,26 @@ impl Outdatedness {
     pub fn insert(&mut self, reason: Reason) {
         self.reasons.insert(reason);
     }
+
+    #[inline]
+    pub fn insert_with_detail(&mut self, reason: Reason, detail: ReasonDetail) {
+        self.details.insert(reason.clone(), detail);
+        self.reasons.insert(reason);
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn detail(&self, reason: &Reason) -> Option<&ReasonDetail> {
+        self.details.get(reason)
+    }
 }
 
 impl FromIterator<Reason> for Outdatedness {
     fn from_iter<T: IntoIterator<Item = Reason>>(iter: T) -> Self {
         Outdatedness {
             reasons: BTreeSet::from_iter(iter),
+            details: BTreeMap::new(),
+            ignored: BTreeSet::new(),
         }
     }
 }
@@ -99,12 +210,16 @@ impl FromIterator<Reason> for Outdatedness {
 impl BitOrAssign for Outdatedness {
     #[inline]
     fn bitor_assign(&mut self, rhs: Self) {
-        if self.reasons.is_empty() {
+        if self.reasons.is_empty() && self.ignored.is_empty() {
             self.reasons = rhs.reasons;
+            self.details = rhs.details;
+            self.ignored = rhs.ignored;
             return;
         }
 
         self.reasons.extend(rhs.reasons);
+        self.details.extend(rhs.details);
+        self.ignored.extend(rhs.ignored);
     }
 }
 
@@ -112,6 +227,12 @@ impl BitOrAssign<&Outdatedness> for Outdatedness {
     #[inline]
     fn bitor_assign(&mut self, rhs: &Self) {
         self.reasons.extend(rhs.reasons.iter().cloned());
+        self.details.extend(
+            rhs.details
+                .iter()
+                .map(|(reason, detail)| (reason.clone(), detail.clone())),
+        );
+        self.ignored.extend(rhs.ignored.iter().cloned());
     }
 }
 
@@ -147,6 +268,9 @@ impl std::fmt::Display for Reason {
             Reason::RecipeChanged => f.write_str("recipe changed"),
             Reason::GlobalChanged(variable) => write!(f, "global variable `{variable}` changed"),
             Reason::Define(define) => write!(f, "variable `{define}` was manually overridden"),
+            Reason::Forced => f.write_str("rebuild was forced"),
+            Reason::AlwaysRun => f.write_str("recipe is marked `always-run`"),
+            Reason::Uncached => f.write_str("recipe is marked `uncached`"),
             Reason::Rebuilt(task_id) => {
                 if task_id.is_command() {
                     write!(f, "`{task_id}` is a command recipe")
@@ -159,10 +283,17 @@ impl std::fmt::Display for Reason {
 }
 
 pub struct OutdatednessTracker<'a> {
+    workspace: &'a Workspace<'a>,
     outdatedness: Outdatedness,
     cache: Option<&'a TargetOutdatednessCache>,
     new_cache: TargetOutdatednessCache,
     target_mtime: Option<std::time::SystemTime>,
+    commands_changed: bool,
+    file: DiagnosticFileId,
+    span: Span,
+    /// Where the recipe is declared, attached to the details of each reason.
+    /// Computed when the first reason is found.
+    location: std::cell::OnceCell<Option<ReasonLocation>>,
 }
 
 impl<'a> OutdatednessTracker<'a> {
@@ -176,7 +307,14 @@ impl<'a> OutdatednessTracker<'a> {
         let recipe_hash = workspace.register_used_recipe_hash(recipe);
         if let Some(cache) = cache {
             if recipe_hash != cache.recipe_hash {
-                outdatedness.insert(Reason::RecipeChanged);
+                outdatedness.insert_with_detail(
+                    Reason::RecipeChanged,
+                    ReasonDetail {
+                        old: Some(ReasonValue::Hash(cache.recipe_hash)),
+                        new: Some(ReasonValue::Hash(recipe_hash)),
+                        location: ReasonLocation::new(workspace, recipe.file, recipe.span),
+                    },
+                );
             }
         }
         let new_cache = TargetOutdatednessCache {
@@ -186,16 +324,47 @@ impl<'a> OutdatednessTracker<'a> {
             env: BTreeMap::default(),
             define: BTreeMap::default(),
             global: BTreeMap::default(),
+            commands_hash: None,
+            output_hash: None,
+            duration_ms: None,
+            critical_path_ms: None,
         };
 
         Self {
+            workspace,
             outdatedness,
             cache,
             new_cache,
             target_mtime,
+            commands_changed: false,
+            file: recipe.file,
+            span: recipe.span,
+            location: std::cell::OnceCell::new(),
         }
     }
 
+    /// Record a reason, along with the values that caused it.
+    fn insert(&mut self, reason: Reason, old: Option<ReasonValue>, new: Option<ReasonValue>) {
+        let detail = ReasonDetail {
+            old,
+            new,
+            location: self
+                .location
+                .get_or_init(|| ReasonLocation::new(self.workspace, self.file, self.span))
+                .clone(),
+        };
+        self.outdatedness.insert_with_detail(reason, detail);
+    }
+
+    /// Record that a cached hash changed.
+    fn insert_changed_hash(&mut self, reason: Reason, old: Option<&Hash128>, new: Hash128) {
+        self.insert(
+            reason,
+            old.copied().map(ReasonValue::Hash),
+            Some(ReasonValue::Hash(new)),
+        );
+    }
+
     pub fn did_use(&mut self, used: Used) {
         for var in used.vars {
             match var {
@@ -204,7 +373,8 @@ impl<'a> OutdatednessTracker<'a> {
                         .cache
                         .is_some_and(|cache| cache.is_glob_outdated(glob, hash))
                     {
-                        self.outdatedness.insert(Reason::Glob(glob));
+                        let old = self.cache.and_then(|cache| cache.glob.get(&glob));
+                        self.insert_changed_hash(Reason::Glob(glob), old, hash);
                     }
                     self.new_cache.glob.insert(glob, hash);
                 }
@@ -213,7 +383,8 @@ impl<'a> OutdatednessTracker<'a> {
                         .cache
                         .is_some_and(|cache| cache.is_which_outdated(which, hash))
                     {
-                        self.outdatedness.insert(Reason::Which(which));
+                        let old = self.cache.and_then(|cache| cache.which.get(&which));
+                        self.insert_changed_hash(Reason::Which(which), old, hash);
                     }
                     self.new_cache.which.insert(which, hash);
                 }
@@ -222,7 +393,8 @@ impl<'a> OutdatednessTracker<'a> {
                         .cache
                         .is_some_and(|cache| cache.is_env_outdated(env, hash))
                     {
-                        self.outdatedness.insert(Reason::Env(env));
+                        let old = self.cache.and_then(|cache| cache.env.get(&env));
+                        self.insert_changed_hash(Reason::Env(env), old, hash);
                     }
                     self.new_cache.env.insert(env, hash);
                 }
@@ -231,7 +403,8 @@ impl<'a> OutdatednessTracker<'a> {
                         .cache
                         .is_some_and(|cache| cache.is_define_outdated(def, hash))
                     {
-                        self.outdatedness.insert(Reason::Define(def));
+                        let old = self.cache.and_then(|cache| cache.define.get(&def));
+                        self.insert_changed_hash(Reason::Define(def), old, hash);
                     }
                     self.new_cache.define.insert(def, hash);
                 }
@@ -240,14 +413,19 @@ impl<'a> OutdatednessTracker<'a> {
                         .cache
                         .is_some_and(|cache| cache.is_global_outdated(var, hash))
                     {
-                        self.outdatedness.insert(Reason::GlobalChanged(var));
+                        let old = self.cache.and_then(|cache| cache.global.get(&var));
+                        self.insert_changed_hash(Reason::GlobalChanged(var), old, hash);
                     }
                     self.new_cache.global.insert(var, hash);
                 }
                 UsedVariable::WorkspaceFile(path, mtime) => {
                     if let Some(target_mtime) = self.target_mtime {
                         if mtime > target_mtime {
-                            self.outdatedness.insert(Reason::Modified(path, mtime));
+                            self.insert(
+                                Reason::Modified(path, mtime),
+                                Some(ReasonValue::Mtime(target_mtime)),
+                                Some(ReasonValue::Mtime(mtime)),
+                            );
                         }
                     }
                 }
@@ -255,16 +433,46 @@ impl<'a> OutdatednessTracker<'a> {
         }
     }
 
+    /// Record the fully evaluated commands of the recipe, so changes that are
+    /// not caught by any other reason (such as the output of a `shell`
+    /// expression) still cause a rebuild.
+    pub fn did_evaluate_commands(&mut self, commands: &[RunCommand], env: &Env) {
+        // Messages don't affect how the target is built.
+        let commands = commands
+            .iter()
+            .filter(|command| !matches!(command, RunCommand::Info(_) | RunCommand::Warn(_)))
+            .collect::<Vec<_>>();
+        let hash = compute_stable_hash(&(commands, env));
+        self.commands_changed = self
+            .cache
+            .is_some_and(|cache| cache.is_commands_outdated(hash));
+        self.new_cache.commands_hash = Some(hash);
+    }
+
     pub fn missing(&mut self, target: impl Into<Absolute<SymPath>>) {
-        self.outdatedness.insert(Reason::Missing(target.into()));
+        self.add_reason(Reason::Missing(target.into()));
     }
 
     pub fn add_reason(&mut self, reason: Reason) {
-        self.outdatedness.insert(reason);
+        match reason {
+            Reason::Modified(_, mtime) => {
+                let old = self.target_mtime.map(ReasonValue::Mtime);
+                self.insert(reason, old, Some(ReasonValue::Mtime(mtime)));
+            }
+            _ => self.insert(reason, None, None),
+        }
     }
 
     pub fn add_reasons(&mut self, reasons: impl IntoIterator<Item = Reason>) {
-        self.outdatedness.reasons.extend(reasons);
+        for reason in reasons {
+            self.add_reason(reason);
+        }
+    }
+
+    /// Record reasons from `volatile` dependencies, which are shown by
+    /// `--explain` but do not make the target outdated.
+    pub fn ignore_reasons(&mut self, reasons: impl IntoIterator<Item = Reason>) {
+        self.outdatedness.ignored.extend(reasons);
     }
 
     #[must_use]
@@ -274,11 +482,24 @@ impl<'a> OutdatednessTracker<'a> {
         if let Some(cache) = self.cache {
             for key in cache.define.keys() {
                 if !self.new_cache.define.contains_key(key) {
-                    self.outdatedness.insert(Reason::Define(*key));
+                    let old = cache.define.get(key).copied().map(ReasonValue::Hash);
+                    self.insert(Reason::Define(*key), old, None);
                 }
             }
         }
 
+        // Only report changed commands when nothing else explains them, so
+        // the reasons point at the actual cause.
+        if self.commands_changed && self.outdatedness.is_unchanged() {
+            let old = self.cache.and_then(|cache| cache.commands_hash);
+            let new = self.new_cache.commands_hash;
+            self.insert(
+                Reason::RecipeChanged,
+                old.map(ReasonValue::Hash),
+                new.map(ReasonValue::Hash),
+            );
+        }
+
         (self.outdatedness, self.new_cache)
     }
 }
@@ -1,6 +1,7 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, fmt::Write as _, ops::RangeInclusive};
 
 use werk_parser::parser::Span;
+use werk_util::Symbol;
 
 #[derive(Debug, Clone)]
 pub struct Pattern<'a> {
@@ -23,12 +24,20 @@ struct PatternRegex<'a> {
     pub fragments: Box<[PatternFragment<'a>]>,
     /// The regular expression used to match this pattern.
     pub regex: Box<regex::Regex>,
-    /// The index of the regex capture group that represents the pattern stem.
-    pub stem_capture_index: Option<usize>,
-    /// The number of "one-of" capture groups in the pattern.
+    /// What each regex capture group represents, in order.
+    pub captures: Box<[Capture]>,
+    /// The number of "one-of" and character class capture groups in the
+    /// pattern.
     pub num_capture_groups: usize,
 }
 
+#[derive(Debug, Clone, Copy)]
+enum Capture {
+    Stem,
+    Named(Symbol),
+    Group,
+}
+
 impl PartialEq for Pattern<'_> {
     #[inline]
     fn eq(&self, other: &Self) -> bool {
@@ -59,15 +68,20 @@ pub struct PatternBuilder<'a> {
 pub enum PatternFragment<'a> {
     Literal(Cow<'a, str>),
     PatternStem,
+    NamedCapture(Symbol),
     OneOf(Vec<Cow<'a, str>>),
+    CharClass(Vec<RangeInclusive<char>>),
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct PatternMatchData {
     /// The matched stem, if the pattern has a stem.
     pub stem: Option<Box<str>>,
-    /// One entry for each `OneOf` capture group `(a|b|...)` in the pattern.
+    /// One entry for each `OneOf` capture group `(a|b|...)` and character
+    /// class `[abc]` in the pattern.
     pub captures: Box<[Box<str>]>,
+    /// The matches of the named captures `(%name)` in the pattern.
+    pub named: Box<[(Symbol, Box<str>)]>,
 }
 
 impl PatternMatchData {
@@ -78,8 +92,21 @@ impl PatternMatchData {
         Self {
             stem: stem.map(Into::into),
             captures: captures.into_iter().map(Into::into).collect(),
+            named: Box::default(),
         }
     }
+
+    #[must_use]
+    pub fn with_named(
+        mut self,
+        named: impl IntoIterator<Item = (Symbol, impl Into<Box<str>>)>,
+    ) -> Self {
+        self.named = named
+            .into_iter()
+            .map(|(name, value)| (name, value.into()))
+            .collect();
+        self
+    }
 }
 
 impl<'a> PatternBuilder<'a> {
@@ -116,6 +143,26 @@ impl<'a> PatternBuilder<'a> {
         self.fragments.push(PatternFragment::PatternStem);
     }
 
+    pub fn push_named_capture(&mut self, name: Symbol) {
+        self.string.push_str("(%");
+        self.string.push_str(name.as_str());
+        self.string.push(')');
+        self.fragments.push(PatternFragment::NamedCapture(name));
+    }
+
+    pub fn push_char_class(&mut self, ranges: Vec<RangeInclusive<char>>) {
+        self.string.push('[');
+        for range in &ranges {
+            self.string.push(*range.start());
+            if range.start() != range.end() {
+                self.string.push('-');
+                self.string.push(*range.end());
+            }
+        }
+        self.string.push(']');
+        self.fragments.push(PatternFragment::CharClass(ranges));
+    }
+
     /// Ensure that the pattern starts with `/`. This should be used in build
     /// recipe patterns, where output paths are always "absolutized" before
     /// matching.
@@ -159,16 +206,17 @@ impl<'a> PatternBuilder<'a> {
         }
 
         let mut regex_pattern = String::from(if self.match_substrings { "" } else { "^" });
-        let mut capture_count = 0;
-        let mut stem_capture_index = None;
-        let mut num_capture_groups = 0;
+        let mut captures = Vec::new();
         for fragment in &self.fragments {
             match fragment {
                 PatternFragment::Literal(lit) => regex_pattern.push_str(&regex::escape(lit)),
                 PatternFragment::PatternStem => {
                     regex_pattern.push_str(r"(.*)");
-                    stem_capture_index = Some(capture_count);
-                    capture_count += 1;
+                    captures.push(Capture::Stem);
+                }
+                PatternFragment::NamedCapture(name) => {
+                    regex_pattern.push_str(r"(.*)");
+                    captures.push(Capture::Named(*name));
                 }
                 PatternFragment::OneOf(vec) => {
                     regex_pattern.push('(');
@@ -179,8 +227,21 @@ impl<'a> PatternBuilder<'a> {
                         regex_pattern.push_str(&regex::escape(capture));
                     }
                     regex_pattern.push(')');
-                    capture_count += 1;
-                    num_capture_groups += 1;
+                    captures.push(Capture::Group);
+                }
+                PatternFragment::CharClass(ranges) => {
+                    regex_pattern.push_str("([");
+                    for range in ranges {
+                        write!(
+                            regex_pattern,
+                            r"\x{{{:x}}}-\x{{{:x}}}",
+                            u32::from(*range.start()),
+                            u32::from(*range.end())
+                        )
+                        .unwrap();
+                    }
+                    regex_pattern.push_str("])");
+                    captures.push(Capture::Group);
                 }
             }
         }
@@ -199,8 +260,11 @@ impl<'a> PatternBuilder<'a> {
             matcher: PatternMatcher::Regex(PatternRegex {
                 fragments: self.fragments.into(),
                 regex: Box::new(regex),
-                stem_capture_index,
-                num_capture_groups,
+                num_capture_groups: captures
+                    .iter()
+                    .filter(|capture| matches!(capture, Capture::Group))
+                    .count(),
+                captures: captures.into(),
             }),
         }
     }
@@ -215,7 +279,13 @@ impl<'a> Pattern<'a> {
             match fragment {
                 werk_parser::ast::PatternFragment::Literal(lit) => builder.push_str(&lit),
                 werk_parser::ast::PatternFragment::PatternStem => builder.push_pattern_stem(),
+                werk_parser::ast::PatternFragment::NamedCapture(name) => {
+                    builder.push_named_capture(name);
+                }
                 werk_parser::ast::PatternFragment::OneOf(one_of) => builder.push_one_of(one_of),
+                werk_parser::ast::PatternFragment::CharClass(ranges) => {
+                    builder.push_char_class(ranges);
+                }
                 werk_parser::ast::PatternFragment::Interpolation(_) => panic!(
                     "Pattern::parse cannot handle interpolations; use `eval_pattern` instead"
                 ),
@@ -229,10 +299,7 @@ impl<'a> Pattern<'a> {
         match self.matcher {
             PatternMatcher::Literal => {
                 if string == self.string {
-                    Some(PatternMatchData {
-                        stem: None,
-                        captures: Box::default(),
-                    })
+                    Some(PatternMatchData::default())
                 } else {
                     None
                 }
@@ -240,22 +307,23 @@ impl<'a> Pattern<'a> {
             PatternMatcher::Regex(ref regex) => {
                 let m = regex.regex.captures(string)?;
                 let mut capture_groups = Vec::with_capacity(regex.num_capture_groups);
+                let mut named = Vec::new();
                 let mut stem = None;
 
                 let mut group_matches = m.iter();
                 // Skip the implicit whole-string match group.
                 group_matches.next().unwrap();
 
-                for (index, group) in group_matches.enumerate() {
+                for (capture, group) in regex.captures.iter().zip(group_matches) {
                     let group_str = group.unwrap().as_str();
-                    if regex.stem_capture_index == Some(index) {
-                        stem = Some(group_str);
-                    } else {
-                        capture_groups.push(group_str);
+                    match capture {
+                        Capture::Stem => stem = Some(group_str),
+                        Capture::Named(name) => named.push((*name, group_str)),
+                        Capture::Group => capture_groups.push(group_str),
                     }
                 }
 
-                Some(PatternMatchData::new(stem, capture_groups))
+                Some(PatternMatchData::new(stem, capture_groups).with_named(named))
             }
         }
     }
@@ -274,6 +342,152 @@ impl<'a> Pattern<'a> {
         }
     }
 
+    /// The number of literal characters in the pattern, not counting the stem
+    /// and capture groups.
+    #[must_use]
+    pub fn num_literal_chars(&self) -> usize {
+        match self.matcher {
+            PatternMatcher::Literal => self.string.chars().count(),
+            PatternMatcher::Regex(ref regex) => regex
+                .fragments
+                .iter()
+                .map(|fragment| match fragment {
+                    PatternFragment::Literal(literal) => literal.chars().count(),
+                    PatternFragment::PatternStem
+                    | PatternFragment::NamedCapture(_)
+                    | PatternFragment::OneOf(_)
+                    | PatternFragment::CharClass(_) => 0,
+                })
+                .sum(),
+        }
+    }
+
+    /// All strings matched by the pattern, or `None` if the pattern has a stem
+    /// or named captures and so matches infinitely many strings. One-of groups
+    /// and character classes are expanded in order.
+    #[must_use]
+    pub fn expand(&self) -> Option<Vec<String>> {
+        self.expand_fragments(None)
+    }
+
+    /// All strings matched by the pattern when its stem matches `stem`. For
+    /// patterns without a stem, this is the same as [`Pattern::expand()`].
+    /// Patterns with named captures expand to nothing.
+    #[must_use]
+    pub fn expand_with_stem(&self, stem: &str) -> Vec<String> {
+        self.expand_fragments(Some(stem)).unwrap_or_default()
+    }
+
+    fn expand_fragments(&self, stem: Option<&str>) -> Option<Vec<String>> {
+        let PatternMatcher::Regex(ref regex) = self.matcher else {
+            return Some(vec![self.string.clone()]);
+        };
+
+        let mut strings = vec![String::new()];
+        for fragment in &regex.fragments {
+            match fragment {
+                PatternFragment::Literal(lit) => {
+                    for string in &mut strings {
+                        string.push_str(lit);
+                    }
+                }
+                PatternFragment::PatternStem => {
+                    let stem = stem?;
+                    for string in &mut strings {
+                        string.push_str(stem);
+                    }
+                }
+                PatternFragment::NamedCapture(_) => return None,
+                PatternFragment::OneOf(_) | PatternFragment::CharClass(_) => {
+                    let alternatives = fragment.alternatives();
+                    strings = strings
+                        .iter()
+                        .flat_map(|string| {
+                            alternatives.iter().map(move |alt| format!("{string}{alt}"))
+                        })
+                        .collect();
+                }
+            }
+        }
+        Some(strings)
+    }
+
+    /// For patterns with a stem, the literal prefix before the stem and all
+    /// possible suffixes after it, such that the pattern matches
+    /// `prefix + stem + suffix`. Returns `None` if the pattern has no stem, if
+    /// there is a one-of group or character class before the stem, or if it
+    /// has named captures.
+    #[must_use]
+    pub fn stem_affixes(&self) -> Option<(String, Vec<String>)> {
+        let PatternMatcher::Regex(ref regex) = self.matcher else {
+            return None;
+        };
+        let stem_index = regex
+            .fragments
+            .iter()
+            .position(|fragment| matches!(fragment, PatternFragment::PatternStem))?;
+
+        let mut prefix = String::new();
+        for fragment in &regex.fragments[..stem_index] {
+            let PatternFragment::Literal(lit) = fragment else {
+                return None;
+            };
+            prefix.push_str(lit);
+        }
+
+        let mut suffixes = vec![String::new()];
+        for fragment in &regex.fragments[stem_index + 1..] {
+            match fragment {
+                PatternFragment::Literal(lit) => {
+                    for suffix in &mut suffixes {
+                        suffix.push_str(lit);
+                    }
+                }
+                PatternFragment::OneOf(_) | PatternFragment::CharClass(_) => {
+                    let alternatives = fragment.alternatives();
+                    suffixes = suffixes
+                        .iter()
+                        .flat_map(|suffix| {
+                            alternatives.iter().map(move |alt| format!("{suffix}{alt}"))
+                        })
+                        .collect();
+                }
+                // Patterns have at most one stem.
+                PatternFragment::PatternStem | PatternFragment::NamedCapture(_) => return None,
+            }
+        }
+
+        Some((prefix, suffixes))
+    }
+
+    /// True if every string matched by `other` is also matched by this
+    /// pattern. This is conservative: it may return false for some patterns
+    /// that do contain `other`, like when `other` has a one-of group before
+    /// its stem.
+    #[must_use]
+    pub fn contains(&self, other: &Pattern) -> bool {
+        if let Some(strings) = other.expand() {
+            return strings
+                .iter()
+                .all(|string| self.match_whole_string(string).is_some());
+        }
+
+        // Both patterns have a stem, which can match any string. `other`
+        // matches `prefix + stem + suffix`, which this pattern also matches if
+        // its prefix and one of its suffixes surround it.
+        let (Some((prefix, suffixes)), Some((other_prefix, other_suffixes))) =
+            (self.stem_affixes(), other.stem_affixes())
+        else {
+            return false;
+        };
+        other_prefix.starts_with(&prefix)
+            && other_suffixes.iter().all(|other_suffix| {
+                suffixes
+                    .iter()
+                    .any(|suffix| other_suffix.ends_with(suffix.as_str()))
+            })
+    }
+
     #[must_use]
     pub fn fragments(&self) -> Option<&[PatternFragment<'a>]> {
         match self.matcher {
@@ -283,6 +497,24 @@ impl<'a> Pattern<'a> {
     }
 }
 
+impl PatternFragment<'_> {
+    /// The strings matched by a one-of group or a character class, in order.
+    fn alternatives(&self) -> Vec<Cow<'_, str>> {
+        match self {
+            PatternFragment::OneOf(one_of) => {
+                one_of.iter().map(|alt| Cow::Borrowed(&**alt)).collect()
+            }
+            PatternFragment::CharClass(ranges) => ranges
+                .iter()
+                .flat_map(Clone::clone)
+                .map(|ch| Cow::Owned(ch.to_string()))
+                .collect(),
+            PatternFragment::Literal(lit) => vec![Cow::Borrowed(&**lit)],
+            PatternFragment::PatternStem | PatternFragment::NamedCapture(_) => Vec::new(),
+        }
+    }
+}
+
 impl std::fmt::Display for Pattern<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&self.string)
@@ -290,11 +522,34 @@ impl std::fmt::Display for Pattern<'_> {
 }
 
 impl PatternMatchData {
-    /// True if the pattern did not contain a stem.
+    /// True if the pattern did not contain a stem or named captures.
     #[inline]
     #[must_use]
     pub fn is_verbatim(&self) -> bool {
-        self.stem.is_none()
+        self.stem.is_none() && self.named.is_empty()
+    }
+
+    /// The total length of the stem and the named captures, which can all
+    /// match any string.
+    #[inline]
+    #[must_use]
+    pub fn stem_len(&self) -> usize {
+        self.stem.as_ref().map_or(0, |stem| stem.len())
+            + self
+                .named
+                .iter()
+                .map(|(_, value)| value.len())
+                .sum::<usize>()
+    }
+
+    /// The match of the named capture `(%name)`.
+    #[inline]
+    #[must_use]
+    pub fn named_capture(&self, name: Symbol) -> Option<&str> {
+        self.named
+            .iter()
+            .find(|(capture, _)| *capture == name)
+            .map(|(_, value)| &**value)
     }
 
     #[inline]
@@ -329,9 +584,7 @@ mod tests {
                 werk_parser::ast::PatternFragment::Literal(lit) => builder.push_str(&lit),
                 werk_parser::ast::PatternFragment::PatternStem => builder.push_pattern_stem(),
                 werk_parser::ast::PatternFragment::OneOf(vec) => builder.push_one_of(vec),
-                werk_parser::ast::PatternFragment::Interpolation(_) => {
-                    panic!("unexpected interpolation")
-                }
+                _ => panic!("unexpected fragment"),
             }
         }
         let pattern = builder.build();
@@ -351,4 +604,46 @@ mod tests {
         assert_eq!(pattern_match.stem(), Some("/main"));
         assert!(pattern_match.captures.is_empty());
     }
+
+    #[test]
+    fn expand() {
+        let pattern = Pattern::parse("gen/(a|b).(c|h)").unwrap();
+        assert_eq!(
+            pattern.expand().unwrap(),
+            ["gen/a.c", "gen/a.h", "gen/b.c", "gen/b.h"]
+        );
+        assert_eq!(
+            Pattern::parse("foo.c").unwrap().expand().unwrap(),
+            ["foo.c"]
+        );
+        assert_eq!(Pattern::parse("%.c").unwrap().expand(), None);
+    }
+
+    #[test]
+    fn stem_affixes() {
+        let pattern = Pattern::parse("assets/%.(ktx|dds)").unwrap();
+        assert_eq!(
+            pattern.stem_affixes().unwrap(),
+            (
+                String::from("assets/"),
+                vec![String::from(".ktx"), String::from(".dds")]
+            )
+        );
+        assert_eq!(Pattern::parse("foo.c").unwrap().stem_affixes(), None);
+        assert_eq!(Pattern::parse("(a|b)/%.c").unwrap().stem_affixes(), None);
+    }
+
+    #[test]
+    fn contains() {
+        let pattern = |s| Pattern::parse(s).unwrap();
+        assert!(pattern("%").contains(&pattern("%.c")));
+        assert!(pattern("%.c").contains(&pattern("src/%.c")));
+        assert!(pattern("%.(c|h)").contains(&pattern("gen/%.h")));
+        assert!(pattern("%.c").contains(&pattern("(a|b).c")));
+        assert!(pattern("foo.c").contains(&pattern("foo.c")));
+        assert!(!pattern("%.c").contains(&pattern("%")));
+        assert!(!pattern("src/%.c").contains(&pattern("%.c")));
+        assert!(!pattern("%.c").contains(&pattern("%.(c|h)")));
+        assert!(!pattern("foo.c").contains(&pattern("%.c")));
+    }
 }
@@ -0,0 +1,177 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    hash::Hash as _,
+    time::SystemTime,
+};
+
+use indexmap::IndexMap;
+use werk_fs::Absolute;
+
+use crate::{
+    cache::Hash128,
+    eval::{Used, UsedVariable},
+    Env, RunCommand, TaskId,
+};
+
+/// The fully resolved build graph for a target, computed without running any
+/// recipe commands.
+///
+/// Nodes are stored in dependency order: every node appears after all of its
+/// dependencies, so executing the nodes front-to-back is a valid schedule.
+/// Individual nodes can be built by passing their [`TaskId`] to
+/// [`Runner::build_or_run()`](crate::Runner::build_or_run).
+#[derive(Debug, Clone)]
+pub struct Plan {
+    /// The task that was requested.
+    pub root: TaskId,
+    pub nodes: IndexMap<TaskId, PlanNode>,
+}
+
+impl Plan {
+    #[inline]
+    #[must_use]
+    pub fn get(&self, task_id: TaskId) -> Option<&PlanNode> {
+        self.nodes.get(&task_id)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &PlanNode> + '_ {
+        self.nodes.values()
+    }
+
+    /// Find build recipes that run exactly the same commands as a build
+    /// recipe earlier in the plan.
+    pub(crate) fn detect_same_commands(&mut self) {
+        let mut seen = HashMap::<(&[RunCommand], &Env), TaskId>::default();
+        let mut duplicates = Vec::new();
+        for node in self.nodes.values() {
+            if node.kind != PlanNodeKind::Build || node.commands.is_empty() {
+                continue;
+            }
+            match seen.entry((&node.commands, &node.env)) {
+                Entry::Occupied(entry) => duplicates.push((node.task_id, *entry.get())),
+                Entry::Vacant(entry) => {
+                    entry.insert(node.task_id);
+                }
+            }
+        }
+        for (task_id, other) in duplicates {
+            self.nodes[&task_id].same_commands_as = Some(other);
+        }
+    }
+
+    /// All edges in the graph, as `(dependent, dependency)` pairs.
+    pub fn edges(&self) -> impl Iterator<Item = (TaskId, TaskId)> + '_ {
+        self.nodes.values().flat_map(|node| {
+            node.dependencies
+                .iter()
+                .map(move |dependency| (node.task_id, *dependency))
+        })
+    }
+}
+
+impl<'a> IntoIterator for &'a Plan {
+    type Item = &'a PlanNode;
+    type IntoIter = indexmap::map::Values<'a, TaskId, PlanNode>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.nodes.values()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlanNode {
+    pub task_id: TaskId,
+    pub kind: PlanNodeKind,
+    /// Direct dependencies of this node, including dependencies discovered
+    /// through an existing depfile.
+    pub dependencies: Vec<TaskId>,
+    /// The commands that would run if the node is outdated. Empty for files
+    /// without a recipe.
+    pub commands: Vec<RunCommand>,
+    pub env: Env,
+    /// An earlier build recipe in the plan that runs exactly the same
+    /// commands. The commands only run once, and the result is shared.
+    pub same_commands_as: Option<TaskId>,
+    /// The depfile declared by a build recipe, if any.
+    pub depfile: Option<Absolute<werk_fs::PathBuf>>,
+    /// The external variables used while evaluating the recipe.
+    pub used: Used,
+    /// Stable hash of everything that is recorded in `.werk-cache` for this
+    /// target (the recipe itself and the variables it used). `None` for task
+    /// recipes and files without a recipe, which are never cached.
+    pub fingerprint: Option<Hash128>,
+}
+
+impl PlanNode {
+    fn file(task_id: TaskId, kind: PlanNodeKind) -> Self {
+        Self {
+            task_id,
+            kind,
+            dependencies: Vec::new(),
+            commands: Vec::new(),
+            env: Env::default(),
+            same_commands_as: None,
+            depfile: None,
+            used: Used::none(),
+            fingerprint: None,
+        }
+    }
+
+    pub(crate) fn source_file(task_id: TaskId, mtime: SystemTime) -> Self {
+        Self::file(task_id, PlanNodeKind::Exists(mtime))
+    }
+
+    pub(crate) fn missing_file(task_id: TaskId) -> Self {
+        Self::file(task_id, PlanNodeKind::Missing)
+    }
+
+    /// A target in a workspace member. Its recipe and dependencies are part of
+    /// the plan of the member, not this plan.
+    pub(crate) fn member_target(task_id: TaskId) -> Self {
+        Self::file(task_id, PlanNodeKind::Task)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanNodeKind {
+    /// A task recipe, which always runs.
+    Task,
+    /// A build recipe producing the file identified by the task ID.
+    Build,
+    /// A file in the workspace without a recipe, along with its last
+    /// modification time.
+    Exists(SystemTime),
+    /// A file discovered through a depfile that no longer exists. The
+    /// dependent will be considered outdated.
+    Missing,
+}
+
+/// Compute the fingerprint of a build recipe from the recipe hash and the
+/// variables it used.
+pub(crate) fn compute_fingerprint(recipe_hash: Hash128, used: &Used) -> Hash128 {
+    // Symbols hash and order by address, so use the string contents in a
+    // deterministic order instead.
+    let mut vars = used
+        .vars
+        .iter()
+        .filter_map(|var| match var {
+            UsedVariable::Glob(name, hash) => Some((0u8, name.as_str(), hash.0)),
+            UsedVariable::Which(name, hash) => Some((1u8, name.as_str(), hash.0)),
+            UsedVariable::Env(name, hash) => Some((2u8, name.as_str(), hash.0)),
+            UsedVariable::Define(name, hash) => Some((3u8, name.as_str(), hash.0)),
+            UsedVariable::Global(name, hash) => Some((4u8, name.as_str(), hash.0)),
+            // File modification times are tracked through dependency edges,
+            // and are not part of the cache.
+            UsedVariable::WorkspaceFile(..) => None,
+        })
+        .collect::<Vec<_>>();
+    vars.sort_unstable();
+
+    let mut hasher = rustc_stable_hash::StableSipHasher128::new();
+    recipe_hash.0.hash(&mut hasher);
+    vars.hash(&mut hasher);
+    hasher.finish()
+}
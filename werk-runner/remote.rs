@@ -0,0 +1,223 @@
+//! Running the commands of build recipes with a `host` statement on another
+//! machine over SSH.
+//!
+//! The workspace and output directories are mirrored in the directory of the
+//! host, as `<dir>/workspace` and `<dir>/output`. The declared inputs of the
+//! recipe are copied to the host with `rsync` before its commands run, each
+//! `run` command runs in its own `ssh` session with the local directories in
+//! its arguments replaced by the mirrored ones, and the target file is copied
+//! back afterwards.
+
+use std::path::Path;
+
+use werk_fs::Absolute;
+use werk_parser::ast::QuoteStyle;
+
+use crate::{ir::RemoteHost, shell::quote, Env, ShellCommandLine};
+
+pub(crate) struct Remote<'a> {
+    host: &'a RemoteHost,
+    ssh: Absolute<std::path::PathBuf>,
+    rsync: Absolute<std::path::PathBuf>,
+    workspace_dir: &'a Absolute<Path>,
+    output_dir: &'a Absolute<Path>,
+}
+
+impl<'a> Remote<'a> {
+    pub fn new(
+        host: &'a RemoteHost,
+        ssh: Absolute<std::path::PathBuf>,
+        rsync: Absolute<std::path::PathBuf>,
+        workspace_dir: &'a Absolute<Path>,
+        output_dir: &'a Absolute<Path>,
+    ) -> Self {
+        Self {
+            host,
+            ssh,
+            rsync,
+            workspace_dir,
+            output_dir,
+        }
+    }
+
+    /// The mirrored directories, output directory first, since it is often
+    /// inside the workspace directory.
+    fn roots(&self) -> [(&Absolute<Path>, String); 2] {
+        [
+            (self.output_dir, format!("{}/output", self.host.dir)),
+            (self.workspace_dir, format!("{}/workspace", self.host.dir)),
+        ]
+    }
+
+    /// The path on the host of a local file in the workspace or output
+    /// directory.
+    fn remote_path(&self, path: &Path) -> Option<String> {
+        self.roots().into_iter().find_map(|(local, remote)| {
+            let relative = path.strip_prefix(local).ok()?;
+            let mut remote = remote;
+            for component in relative.components() {
+                remote.push('/');
+                remote.push_str(&component.as_os_str().to_string_lossy());
+            }
+            Some(remote)
+        })
+    }
+
+    /// Replace the local workspace and output directories in an argument.
+    fn remote_arg(&self, arg: &str) -> String {
+        let mut arg = arg.to_owned();
+        for (local, remote) in self.roots() {
+            arg = arg.replace(&*local.to_string_lossy(), &remote);
+        }
+        arg
+    }
+
+    fn ssh(&self, script: String) -> ShellCommandLine {
+        ShellCommandLine {
+            program: self.ssh.clone(),
+            arguments: vec![
+                String::from("-o"),
+                String::from("BatchMode=yes"),
+                self.host.destination.clone(),
+                script,
+            ],
+        }
+    }
+
+    fn rsync(&self, arguments: Vec<String>) -> ShellCommandLine {
+        // `-s` keeps the remote shell from splitting paths with spaces.
+        let mut all = vec![String::from("-as")];
+        all.extend(arguments);
+        ShellCommandLine {
+            program: self.rsync.clone(),
+            arguments: all,
+        }
+    }
+
+    /// The commands creating the directories of the host, including the parent
+    /// directories of the outputs, and copying the inputs to the host. Inputs
+    /// outside of the workspace and output directories are not copied.
+    pub fn push(
+        &self,
+        inputs: &[Absolute<std::path::PathBuf>],
+        outputs: &[Absolute<std::path::PathBuf>],
+    ) -> Vec<ShellCommandLine> {
+        let mut dirs = self
+            .roots()
+            .into_iter()
+            .map(|(_, remote)| remote)
+            .collect::<Vec<_>>();
+        dirs.extend(outputs.iter().filter_map(|output| {
+            let remote = self.remote_path(output)?;
+            Some(remote.rsplit_once('/')?.0.to_owned())
+        }));
+        dirs.sort();
+        dirs.dedup();
+        let mkdir = dirs
+            .iter()
+            .map(|dir| quote(QuoteStyle::Sh, dir))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut commands = vec![self.ssh(format!("mkdir -p {mkdir}"))];
+
+        for (local, remote) in self.roots() {
+            // `-R` recreates the part of the source path after `/./` under
+            // the destination.
+            let mut arguments = vec![String::from("-R")];
+            arguments.extend(inputs.iter().filter_map(|input| {
+                let relative = input.strip_prefix(local).ok()?;
+                // Inputs in the output directory are not in the workspace.
+                if local == self.workspace_dir && input.starts_with(self.output_dir) {
+                    return None;
+                }
+                Some(format!(
+                    "{}/./{}",
+                    local.display(),
+                    relative.to_string_lossy()
+                ))
+            }));
+            if arguments.len() > 1 {
+                arguments.push(format!("{}:{remote}/", self.host.destination));
+                commands.push(self.rsync(arguments));
+            }
+        }
+        commands
+    }
+
+    /// A command running the command lines on the host in the mirror of the
+    /// working directory, connected by pipes.
+    pub fn run(
+        &self,
+        command_lines: &[ShellCommandLine],
+        env: &Env,
+        working_dir: &Absolute<Path>,
+    ) -> ShellCommandLine {
+        let working_dir = self
+            .remote_path(working_dir)
+            .unwrap_or_else(|| format!("{}/workspace", self.host.dir));
+        let pipeline = command_lines
+            .iter()
+            .map(|command_line| self.remote_command(command_line, env))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        self.ssh(format!(
+            "cd {} && {pipeline}",
+            quote(QuoteStyle::Sh, &working_dir)
+        ))
+    }
+
+    fn remote_command(&self, command_line: &ShellCommandLine, env: &Env) -> String {
+        let mut words = Vec::new();
+        if env.clear || !env.env.is_empty() || !env.env_remove.is_empty() {
+            words.push(String::from("env"));
+            if env.clear {
+                words.push(String::from("-i"));
+            }
+            for key in &env.env_remove {
+                words.push(String::from("-u"));
+                words.push(quote(QuoteStyle::Sh, &key.to_string_lossy()));
+            }
+            for (key, value) in &env.env {
+                let value = self.remote_arg(&value.to_string_lossy());
+                words.push(quote(
+                    QuoteStyle::Sh,
+                    &format!("{}={value}", key.to_string_lossy()),
+                ));
+            }
+        }
+
+        // Programs outside of the mirrored directories are looked up in the
+        // `PATH` of the host, since they are usually installed elsewhere.
+        let program = self.remote_path(&command_line.program).unwrap_or_else(|| {
+            command_line
+                .program
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+        });
+        words.push(quote(QuoteStyle::Sh, &program));
+        words.extend(
+            command_line
+                .arguments
+                .iter()
+                .map(|arg| quote(QuoteStyle::Sh, &self.remote_arg(arg))),
+        );
+        words.join(" ")
+    }
+
+    /// The commands copying the outputs back from the host. The copies get
+    /// the current time as their modification time, so they are newer than
+    /// the inputs, even if the clock of the host is behind.
+    pub fn pull(&self, outputs: &[Absolute<std::path::PathBuf>]) -> Vec<ShellCommandLine> {
+        outputs
+            .iter()
+            .filter_map(|output| {
+                let remote = self.remote_path(output)?;
+                Some(self.rsync(vec![
+                    String::from("--no-times"),
+                    format!("{}:{remote}", self.host.destination),
+                    output.display().to_string(),
+                ]))
+            })
+            .collect()
+    }
+}
@@ -1,4 +1,44 @@
-use crate::{BuildStatus, Error, Outdatedness, ShellCommandLine, TaskId};
+use crate::{ir, BuildStatus, BuildSummary, Error, Outdatedness, ShellCommandLine, TaskId, Value};
+
+/// Whether a command is printed before it is executed, from the `#[silent]`
+/// and `#[print-commands]` attributes of its recipe and `run` statement.
+/// Commands are always printed in dry runs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Echo {
+    /// Print the command with `--print-commands`.
+    #[default]
+    Default,
+    /// `#[print-commands]`: Print the command even without `--print-commands`.
+    Always,
+    /// `#[silent]`: Don't print the command, even with `--print-commands`.
+    Never,
+}
+
+impl Echo {
+    /// Apply the attributes of a recipe or `run` statement. `#[silent]` takes
+    /// precedence over `#[print-commands]`.
+    #[must_use]
+    pub fn with_attributes(self, attributes: ir::Attributes) -> Self {
+        if attributes.silent {
+            return Echo::Never;
+        }
+        match attributes.print_commands {
+            Some(true) => Echo::Always,
+            Some(false) => Echo::Never,
+            None => self,
+        }
+    }
+
+    /// Whether the command is printed, given the `--print-commands` setting.
+    #[must_use]
+    pub fn should_print(self, print_commands: bool) -> bool {
+        match self {
+            Echo::Default => print_commands,
+            Echo::Always => true,
+            Echo::Never => false,
+        }
+    }
+}
 
 pub trait Render: Send + Sync {
     /// Build task is about to start.
@@ -6,13 +46,15 @@ pub trait Render: Send + Sync {
 
     /// Build task finished (all steps have been completed).
     fn did_build(&self, task_id: TaskId, result: &Result<BuildStatus, Error>);
-    /// Run command is about to be executed.
+    /// Run command is about to be executed. `echo` says whether the command
+    /// should be printed.
     fn will_execute(
         &self,
         task_id: TaskId,
         command: &ShellCommandLine,
         step: usize,
         num_steps: usize,
+        echo: Echo,
     );
 
     fn on_child_process_stderr_line(
@@ -49,6 +91,21 @@ pub trait Render: Send + Sync {
         num_steps: usize,
     );
 
+    /// Run command failed, and will be executed again after `delay` because of
+    /// the `#[retry]` attribute. `attempt` is the attempt that failed,
+    /// starting at 1. The final attempt is reported through `did_execute()`.
+    fn will_retry(
+        &self,
+        task_id: TaskId,
+        command: &ShellCommandLine,
+        status: std::process::ExitStatus,
+        attempt: usize,
+        max_attempts: usize,
+        delay: std::time::Duration,
+    ) {
+        _ = (task_id, command, status, attempt, max_attempts, delay);
+    }
+
     /// Emit a message from the user, typically from the `info` expression in
     /// the manifest.
     fn message(&self, task_id: Option<TaskId>, message: &str);
@@ -57,6 +114,12 @@ pub trait Render: Send + Sync {
     /// the manifest.
     fn warning(&self, task_id: Option<TaskId>, message: &str);
 
+    /// A `let` statement assigned a variable, either a global variable or a
+    /// variable in the recipe of `task_id`.
+    fn did_evaluate_variable(&self, task_id: Option<TaskId>, name: &str, value: &Value) {
+        _ = (task_id, name, value);
+    }
+
     /// Emit an informational message from the runtime, typically the `werk`
     /// binary wants to tell the user about something that happened.
     ///
@@ -66,7 +129,33 @@ pub trait Render: Send + Sync {
         _ = message;
     }
 
+    /// The run finished. This is called by the `werk` binary with the
+    /// statistics from [`Runner::summary()`](crate::Runner::summary).
+    fn build_summary(&self, summary: &BuildSummary) {
+        _ = summary;
+    }
+
+    /// An interactive command of the task is about to take over the terminal.
+    /// Nothing may be written to the terminal until [`resume()`](Self::resume)
+    /// is called, and everything emitted so far must be written before this
+    /// returns.
+    fn suspend(&self, task_id: TaskId) {
+        _ = task_id;
+        self.flush();
+    }
+
+    /// The interactive command of the task finished, and the terminal may be
+    /// used again.
+    fn resume(&self, task_id: TaskId) {
+        _ = task_id;
+    }
+
     /// Reset the renderer. This is called between iterations in `--watch` to
     /// reset the render state between runs.
     fn reset(&self) {}
+
+    /// Wait until everything emitted so far has been written to the output.
+    /// This is called before the `werk` binary writes to the terminal
+    /// directly, for example to print errors.
+    fn flush(&self) {}
 }
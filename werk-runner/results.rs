@@ -0,0 +1,170 @@
+use ahash::{HashMap, HashSet};
+use parking_lot::Mutex;
+
+use werk_fs::Absolute;
+
+use crate::{metrics::TaskKey, Error, TaskId};
+
+/// Maximum number of bytes of standard error kept for each failed task. When a
+/// task writes more, the start of its output is dropped.
+pub const MAX_CAPTURED_OUTPUT: usize = 64 * 1024;
+
+/// The outcome of each task of a run, from
+/// [`Runner::results()`](crate::Runner::results). Tasks that were not reached
+/// before the run ended are not included.
+#[derive(Debug, Clone, Default)]
+pub struct BuildResults {
+    /// The tasks in the order they finished.
+    pub tasks: Vec<TaskResult>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskResult {
+    /// The name of the task, with a `//member:` prefix if the task belongs to
+    /// a workspace member.
+    pub task: String,
+    pub task_id: TaskId,
+    /// The index of the workspace member that the task belongs to, or `None`
+    /// for the root workspace.
+    pub member: Option<usize>,
+    pub outcome: TaskOutcome,
+    /// The file that the output of the commands of the task was written to,
+    /// if they printed anything. See
+    /// [`Workspace::write_task_log()`](crate::Workspace::write_task_log).
+    pub log_file: Option<Absolute<std::path::PathBuf>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TaskOutcome {
+    /// The task ran its commands, or reused the result of identical commands
+    /// run by another task.
+    Executed,
+    /// The task is a build recipe that was up-to-date.
+    UpToDate,
+    /// The task failed on its own.
+    Failed(TaskFailure),
+    /// One of the dependencies of the task failed.
+    DependencyFailed,
+    /// The task was stopped before it finished, because another task failed
+    /// or the build was cancelled.
+    Cancelled,
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskFailure {
+    /// The error, attributed to the Werkfile of the workspace member that the
+    /// task belongs to.
+    pub error: Error,
+    /// The standard error of the commands of the task, one line per `\n`, at
+    /// most [`MAX_CAPTURED_OUTPUT`] bytes.
+    pub output: Vec<u8>,
+}
+
+impl BuildResults {
+    /// The tasks that failed on their own, in the order they failed.
+    pub fn failures(&self) -> impl Iterator<Item = (&TaskResult, &TaskFailure)> {
+        self.tasks.iter().filter_map(|task| match task.outcome {
+            TaskOutcome::Failed(ref failure) => Some((task, failure)),
+            _ => None,
+        })
+    }
+
+    /// True if all finished tasks succeeded.
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.tasks
+            .iter()
+            .all(|task| matches!(task.outcome, TaskOutcome::Executed | TaskOutcome::UpToDate))
+    }
+}
+
+/// Collects the outcome of each task of a run. Shared by the runners of all
+/// workspace members.
+#[derive(Default)]
+pub(crate) struct Results {
+    state: Mutex<ResultsState>,
+}
+
+#[derive(Default)]
+struct ResultsState {
+    tasks: Vec<TaskResult>,
+    num_failed: usize,
+    /// Tasks that failed because a dependency failed.
+    dependency_failed: HashSet<TaskKey>,
+    /// Output of the tasks that are running.
+    output: HashMap<TaskKey, CapturedOutput>,
+}
+
+#[derive(Default)]
+struct CapturedOutput {
+    /// The end of the standard error of the task.
+    stderr: Vec<u8>,
+    /// All output of the task, for its log file.
+    log: Vec<u8>,
+}
+
+impl Results {
+    /// A command of the task wrote a line to stdout or stderr. Lines written
+    /// to stdout are only seen when the output of the command is captured.
+    pub fn did_output_line(&self, key: TaskKey, line_without_eol: &[u8], is_stderr: bool) {
+        let mut state = self.state.lock();
+        let output = state.output.entry(key).or_default();
+        output.log.extend_from_slice(line_without_eol);
+        output.log.push(b'\n');
+        if is_stderr {
+            output.stderr.extend_from_slice(line_without_eol);
+            output.stderr.push(b'\n');
+            if output.stderr.len() > MAX_CAPTURED_OUTPUT {
+                let excess = output.stderr.len() - MAX_CAPTURED_OUTPUT;
+                output.stderr.drain(..excess);
+            }
+        }
+    }
+
+    /// Take all output of the task so far, to write it to its log file.
+    pub fn take_log(&self, key: TaskKey) -> Vec<u8> {
+        let mut state = self.state.lock();
+        state
+            .output
+            .get_mut(&key)
+            .map(|output| std::mem::take(&mut output.log))
+            .unwrap_or_default()
+    }
+
+    /// The task failed because one of its dependencies failed.
+    pub fn did_fail_dependency(&self, key: TaskKey) {
+        self.state.lock().dependency_failed.insert(key);
+    }
+
+    pub fn has_failed_dependency(&self, key: TaskKey) -> bool {
+        self.state.lock().dependency_failed.contains(&key)
+    }
+
+    /// The task finished. The captured output of the task is moved into the
+    /// outcome if the task failed, and discarded otherwise.
+    pub fn did_finish(&self, key: TaskKey, mut result: TaskResult) {
+        let mut state = self.state.lock();
+        let output = state.output.remove(&key);
+        if let TaskOutcome::Failed(ref mut failure) = result.outcome {
+            failure.output = output.map(|output| output.stderr).unwrap_or_default();
+            state.num_failed += 1;
+        }
+        state.tasks.push(result);
+    }
+
+    /// Discard the captured output of a task that isn't reported.
+    pub fn discard(&self, key: TaskKey) {
+        self.state.lock().output.remove(&key);
+    }
+
+    /// True if any task failed on its own.
+    pub fn any_failed(&self) -> bool {
+        self.state.lock().num_failed != 0
+    }
+
+    pub fn results(&self) -> BuildResults {
+        BuildResults {
+            tasks: self.state.lock().tasks.clone(),
+        }
+    }
+}
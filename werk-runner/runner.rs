@@ -1,33 +1,82 @@
-use std::{future::Future, sync::Arc, time::SystemTime};
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
+use ahash::HashMap;
 use futures::{channel::oneshot, StreamExt};
 use indexmap::{map::Entry, IndexMap};
 use parking_lot::Mutex;
 use werk_fs::{Absolute, Normalize as _, Path, SymPath};
-use werk_util::{Diagnostic, DiagnosticError, Symbol};
+use werk_parser::parser::Span;
+use werk_util::{Diagnostic, DiagnosticError, DiagnosticFileId, Symbol};
 
 use crate::{
+    cache::duration_to_millis,
+    container::Container,
     depfile::Depfile,
     eval::{self, Eval},
     ir::{self},
-    AmbiguousPatternError, BuildRecipeScope, ChildCaptureOutput, ChildLinesStream, Env, Error,
-    Outdatedness, OutdatednessTracker, Reason, RootScope, Scope as _, ShellCommandLine,
-    TaskRecipeScope, Value, Workspace, WorkspaceSettings,
+    jobs::JobSlots,
+    metrics::{Metrics, TaskKey},
+    plan::compute_fingerprint,
+    remote::Remote,
+    results::Results,
+    workspace::GlobPatterns,
+    AmbiguousPatternError, BuildRecipeScope, BuildResults, BuildSummary, ChecksumMismatch,
+    ChildCaptureOutput, ChildLinesStream, CommandTiming, Echo, Env, Error, Outdatedness,
+    OutdatednessTracker, Plan, PlanNode, PlanNodeKind, Reason, RecipeLocation, Redirection,
+    ResponseFile, RootScope, ShellCommandLine, TaskFailure, TaskOutcome, TaskRecipeScope,
+    TaskResult, Value, Workspace, WorkspaceSettings, COMMAND_LINE_LIMIT,
+    REPRODUCIBLE_ENV_ALLOWLIST,
 };
 
 /// Workspace-wide runner state.
 pub(crate) struct RunnerState {
-    concurrency_limit: smol::lock::Semaphore,
+    /// Job slots, handed out to the waiting recipes with the longest critical
+    /// path first.
+    concurrency_limit: JobSlots,
+    /// Semaphores of the pools declared in the workspace settings.
+    pools: HashMap<Symbol, smol::lock::Semaphore>,
     tasks: Mutex<IndexMap<TaskId, TaskStatus>>,
+    /// Commands executed by build recipes in this run, so recipes that run
+    /// identical commands only execute them once.
+    commands: Mutex<HashMap<RecipeCommands, CommandStatus>>,
+    /// Held while an interactive command owns the terminal.
+    terminal: smol::lock::Mutex<()>,
 }
 
 impl RunnerState {
-    pub fn new(jobs: usize) -> Self {
+    pub fn new(jobs: usize, pools: &IndexMap<Symbol, usize>) -> Self {
         Self {
-            concurrency_limit: smol::lock::Semaphore::new(jobs.max(1)),
+            concurrency_limit: JobSlots::new(jobs),
+            pools: pools
+                .iter()
+                .map(|(name, depth)| (*name, smol::lock::Semaphore::new((*depth).max(1))))
+                .collect(),
             tasks: Mutex::new(IndexMap::default()),
+            commands: Mutex::new(HashMap::default()),
+            terminal: smol::lock::Mutex::new(()),
         }
     }
+
+    pub fn has_pool(&self, name: Symbol) -> bool {
+        self.pools.contains_key(&name)
+    }
+
+    /// Forget the tasks and commands executed so far.
+    pub fn forget_tasks(&self) {
+        self.tasks.lock().clear();
+        self.commands.lock().clear();
+    }
+}
+
+/// True if the target argument is a glob rather than a path or task name.
+/// Abstract paths and task names can never contain glob metacharacters.
+#[must_use]
+pub fn is_target_glob(target: &str) -> bool {
+    target.contains(['*', '?'])
 }
 
 pub struct Runner<'a> {
@@ -36,7 +85,18 @@ pub struct Runner<'a> {
 
 struct Inner<'a> {
     workspace: &'a Workspace<'a>,
-    executor: smol::Executor<'a>,
+    /// The workspace that the runner was created for. When building targets
+    /// in a workspace member, this is the root workspace, which owns the
+    /// members and limits the number of jobs for all of them.
+    root: &'a Workspace<'a>,
+    /// The members that were entered to get to `workspace`, used to detect
+    /// circular dependencies between members.
+    member_chain: Vec<usize>,
+    executor: Arc<smol::Executor<'a>>,
+    /// Statistics of the run, shared with the runners of members.
+    metrics: Arc<Metrics>,
+    /// The outcome of each task, shared with the runners of members.
+    results: Arc<Results>,
 }
 
 #[derive(Clone)]
@@ -50,6 +110,10 @@ pub enum BuildStatus {
     /// Target was built, along with the outdatedness. If the outdatedness is
     /// empty, the target was determined to be up-to-date.
     Complete(TaskId, Outdatedness),
+    /// Target was outdated and its commands were run, but the output is
+    /// byte-identical to the output of the previous run, so targets depending
+    /// on it don't need to be rebuilt.
+    Unchanged(TaskId, Outdatedness),
     /// Target is a dependency that exists in the filesystem, along with its
     /// last modification time.
     Exists(Absolute<SymPath>, SystemTime),
@@ -69,6 +133,7 @@ impl BuildStatus {
                     None
                 }
             }
+            BuildStatus::Unchanged(..) => None,
             BuildStatus::Exists(path_buf, system_time) => {
                 let output_mtime = output_mtime?;
 
@@ -87,6 +152,117 @@ enum TaskStatus {
     Pending(Vec<oneshot::Sender<Result<BuildStatus, Error>>>),
 }
 
+/// The fully evaluated commands of a recipe, along with its environment.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct RecipeCommands {
+    commands: Vec<RunCommand>,
+    env: Env,
+}
+
+/// A recipe evaluated for a task, see [`Inner::evaluate_recipe()`].
+struct EvaluatedRecipe<'a> {
+    /// The node of the task in a plan. Its `dependencies` are left empty, and
+    /// filled in by planning.
+    node: PlanNode,
+    /// The dependencies declared by the recipe, as written.
+    dependencies: Vec<String>,
+    dependency_specs: Vec<TaskSpec<'a>>,
+    /// The `from` or `build` statement of each dependency.
+    dependency_spans: Vec<Span>,
+    /// Whether each dependency is marked `volatile`, so it never makes the
+    /// recipe outdated.
+    volatile_dependencies: Vec<bool>,
+    /// The depfile of a build recipe, and how it is generated.
+    depfile: Option<(Absolute<werk_fs::PathBuf>, DepfileSpec<'a>)>,
+    /// The statement that produced each command.
+    command_spans: Vec<Span>,
+    /// The temporary directory of the recipe, if the recipe refers to it.
+    tmp_dir: Option<Absolute<std::path::PathBuf>>,
+    /// The image of the `container` statement running the commands.
+    container: Option<String>,
+}
+
+/// Where the commands of a recipe were declared, so errors from running them
+/// can point at the Werkfile.
+#[derive(Clone, Copy)]
+struct RecipeSource<'r> {
+    file: DiagnosticFileId,
+    /// The name or pattern of the recipe.
+    recipe: Span,
+    /// The statement that produced each command.
+    command_spans: &'r [Span],
+}
+
+impl RecipeSource<'_> {
+    fn location(&self, stmt: Span, evaluated: String) -> RecipeLocation {
+        RecipeLocation {
+            file: self.file,
+            recipe: self.recipe,
+            stmt,
+            evaluated,
+        }
+    }
+
+    /// Attribute a failing command to the statement that produced it.
+    fn failed_command(
+        &self,
+        err: Error,
+        index: usize,
+        command: &RunCommand,
+        workspace: &Workspace,
+    ) -> Error {
+        let location = || {
+            let evaluated = workspace.redact(&command.to_string()).into_owned();
+            Box::new(self.location(self.command_spans[index], evaluated))
+        };
+        match err {
+            Error::CommandFailed(status, None) => Error::CommandFailed(status, Some(location())),
+            Error::CommandTimedOut(elapsed, None) => {
+                Error::CommandTimedOut(elapsed, Some(location()))
+            }
+            Error::CommandLineTooLong(err, None) => {
+                Error::CommandLineTooLong(err, Some(location()))
+            }
+            Error::ChecksumMismatch(err, None) => Error::ChecksumMismatch(err, Some(location())),
+            Error::FetchFailed(url, err, None) => Error::FetchFailed(url, err, Some(location())),
+            Error::ExtractFailed(archive, err, None) => {
+                Error::ExtractFailed(archive, err, Some(location()))
+            }
+            err => err,
+        }
+    }
+
+    /// Attribute a dependency that does not exist to the statement that
+    /// declared it, given the dependencies of the recipe and their statements.
+    fn missing_input(&self, err: Error, dependencies: &[String], spans: &[Span]) -> Error {
+        let Error::NoRuleToBuildTarget(ref target) = err else {
+            return err;
+        };
+        let stmt = dependencies
+            .iter()
+            .zip(spans)
+            .find_map(|(dependency, span)| {
+                werk_fs::Path::new(dependency)
+                    .and_then(|path| path.absolutize(werk_fs::Path::ROOT))
+                    .is_ok_and(|path| path.as_str() == target)
+                    .then_some(*span)
+            });
+        match stmt {
+            Some(stmt) => Error::MissingInput(
+                target.clone(),
+                Box::new(self.location(stmt, target.clone())),
+            ),
+            None => err,
+        }
+    }
+}
+
+/// The status of a set of commands executed by the build recipe for a task.
+enum CommandStatus {
+    Executed(TaskId, Result<(), Error>),
+    Pending(TaskId, Vec<oneshot::Sender<Result<(), Error>>>),
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum TaskId {
     Task(Symbol),
@@ -99,7 +275,9 @@ pub enum TaskId {
 impl TaskId {
     pub fn command(s: impl Into<Symbol>) -> Self {
         let name = s.into();
-        debug_assert!(!name.as_str().starts_with('/'));
+        // Labels of targets in workspace members, like `//member:target`, are
+        // also commands.
+        debug_assert!(!name.as_str().starts_with('/') || name.as_str().starts_with("//"));
         TaskId::Task(name)
     }
 
@@ -176,6 +354,31 @@ enum TaskSpec<'a> {
     ///
     /// If the file does not exist, the task will be considered outdated.
     CheckExistsRelaxed(Absolute<werk_fs::PathBuf>),
+    /// Build or run a target in a workspace member, from a label like
+    /// `//member:target`.
+    Member {
+        index: usize,
+        label: Symbol,
+        target: String,
+    },
+}
+
+/// What kind of task a [`TaskSpec`] is, once it has been consumed.
+#[derive(Clone, Copy)]
+enum TaskKind {
+    Recipe,
+    CheckExists,
+    Member,
+}
+
+impl TaskKind {
+    fn of(spec: &TaskSpec) -> Self {
+        match spec {
+            TaskSpec::Recipe(_) => TaskKind::Recipe,
+            TaskSpec::CheckExists(_) | TaskSpec::CheckExistsRelaxed(_) => TaskKind::CheckExists,
+            TaskSpec::Member { .. } => TaskKind::Member,
+        }
+    }
 }
 
 enum DepfileSpec<'a> {
@@ -197,6 +400,7 @@ impl TaskSpec<'_> {
             TaskSpec::CheckExists(path_buf) | TaskSpec::CheckExistsRelaxed(path_buf) => {
                 TaskId::build(path_buf.clone().into_boxed_path())
             }
+            TaskSpec::Member { label, .. } => TaskId::command(*label),
         }
     }
 }
@@ -206,11 +410,46 @@ impl<'a> Runner<'a> {
         Self {
             inner: Arc::new(Inner {
                 workspace,
-                executor: smol::Executor::new(),
+                root: workspace,
+                member_chain: Vec::new(),
+                executor: Arc::new(smol::Executor::new()),
+                metrics: Arc::new(Metrics::new()),
+                results: Arc::default(),
             }),
         }
     }
 
+    /// Store the estimated critical path of each build recipe that ran so far
+    /// in the cache of its workspace, to prioritize it in later runs.
+    fn record_critical_paths(&self) {
+        for ((member, task_id), critical_path) in self.inner.metrics.remaining_critical_paths() {
+            let Some(path) = task_id.as_path() else {
+                continue;
+            };
+            let workspace = match member {
+                Some(index) => &self.inner.root.members[index].workspace,
+                None => self.inner.root,
+            };
+            workspace.set_build_target_critical_path(path, critical_path);
+        }
+    }
+
+    /// The outcome of each task that finished so far, including the tasks of
+    /// workspace members. Without [`WorkspaceSettings::keep_going`], no new
+    /// commands are started after the first failure, but commands that were
+    /// already running may still fail.
+    #[must_use]
+    pub fn results(&self) -> BuildResults {
+        self.inner.results.results()
+    }
+
+    /// Statistics about the tasks run so far, and the time since the runner
+    /// was created.
+    #[must_use]
+    pub fn summary(&self) -> BuildSummary {
+        self.inner.metrics.summary()
+    }
+
     pub async fn build_file(
         &self,
         target: &Path,
@@ -226,11 +465,13 @@ impl<'a> Runner<'a> {
             .map_err(|err| err.into_diagnostic_error(self.inner.workspace))?;
         let inner = self.inner.clone();
         // TODO: Run the executor with multiple threads.
-        self.inner
+        let result = self
+            .inner
             .executor
             .run(async move { inner.run_task(spec, DepChain::Empty).await })
-            .await
-            .map_err(|err| err.into_diagnostic_error(self.inner.workspace))
+            .await;
+        self.record_critical_paths();
+        result.map_err(|err| err.into_diagnostic_error(self.inner.workspace))
     }
 
     pub async fn run_command(
@@ -244,11 +485,13 @@ impl<'a> Runner<'a> {
             .map_err(|err| err.into_diagnostic_error(self.inner.workspace))?;
         let inner = self.inner.clone();
         // TODO: Run the executor with multiple threads.
-        self.inner
+        let result = self
+            .inner
             .executor
             .run(async move { inner.run_task(spec, DepChain::Empty).await })
-            .await
-            .map_err(|err| err.into_diagnostic_error(self.inner.workspace))
+            .await;
+        self.record_critical_paths();
+        result.map_err(|err| err.into_diagnostic_error(self.inner.workspace))
     }
 
     pub async fn build_or_run(
@@ -262,15 +505,243 @@ impl<'a> Runner<'a> {
             .map_err(|err| err.into_diagnostic_error(self.inner.workspace))?;
         let inner = self.inner.clone();
         // TODO: Run the executor with multiple threads.
-        self.inner
+        let result = self
+            .inner
             .executor
             .run(async move { inner.run_task(spec, DepChain::Empty).await })
-            .await
+            .await;
+        self.record_critical_paths();
+        result.map_err(|err| err.into_diagnostic_error(self.inner.workspace))
+    }
+
+    /// Build all files matching a target glob, like `assets/*.ktx`. See
+    /// [`expand_target_glob()`](Self::expand_target_glob).
+    pub async fn build_glob(
+        &self,
+        pattern: &str,
+    ) -> Result<Vec<BuildStatus>, DiagnosticError<'a, Error, &'a Workspace<'a>>> {
+        tracing::debug!("Build glob: {pattern}");
+        let targets = self.expand_target_glob(pattern)?;
+        let mut tasks = Vec::with_capacity(targets.len());
+        for target in targets {
+            let spec = self
+                .inner
+                .get_build_spec(&target)
+                .map_err(|err| err.into_diagnostic_error(self.inner.workspace))?;
+            let inner = self.inner.clone();
+            tasks.push(
+                self.inner
+                    .executor
+                    .spawn(async move { inner.run_task(spec, DepChain::Empty).await }),
+            );
+        }
+
+        let result = self
+            .inner
+            .executor
+            .run(async move {
+                let mut statuses = Vec::with_capacity(tasks.len());
+                let mut first_error = None;
+                for task in tasks {
+                    match task.await {
+                        Ok(status) => statuses.push(status),
+                        // Don't interrupt other targets if one fails.
+                        Err(err) => _ = first_error.get_or_insert(err),
+                    }
+                }
+                match first_error {
+                    Some(err) => Err(err),
+                    None => Ok(statuses),
+                }
+            })
+            .await;
+        self.record_critical_paths();
+        result.map_err(|err| err.into_diagnostic_error(self.inner.workspace))
+    }
+
+    /// Expand a target glob, like `assets/*.ktx`, to the files matching it.
+    /// These are the source files in the workspace, the targets of build
+    /// recipes without a pattern stem, and the outputs that build recipes with
+    /// a pattern stem can produce from the source files in the workspace and
+    /// the targets of recipes without a pattern stem.
+    pub fn expand_target_glob(
+        &self,
+        pattern: &str,
+    ) -> Result<Vec<Absolute<werk_fs::PathBuf>>, DiagnosticError<'a, Error, &'a Workspace<'a>>>
+    {
+        self.inner
+            .expand_target_glob(pattern)
             .map_err(|err| err.into_diagnostic_error(self.inner.workspace))
     }
+
+    /// List the outputs that each build recipe knows how to build, in the
+    /// order the recipes are declared. See
+    /// [`expand_target_glob()`](Self::expand_target_glob) for how outputs of
+    /// recipes with a pattern stem are found.
+    #[must_use]
+    pub fn list_outputs(&self) -> Vec<(&'a ir::BuildRecipe<'a>, Vec<Absolute<werk_fs::PathBuf>>)> {
+        let mut outputs = self.inner.buildable_outputs(|_| true);
+        outputs.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        self.inner
+            .workspace
+            .manifest
+            .build_recipes
+            .iter()
+            .map(|recipe| {
+                let recipe_outputs = outputs
+                    .iter()
+                    .filter(|(_, output_recipe)| std::ptr::eq(*output_recipe, recipe))
+                    .map(|(output, _)| output.clone())
+                    .collect();
+                (recipe, recipe_outputs)
+            })
+            .collect()
+    }
+
+    /// Resolve the full build graph for `target` without running any recipe
+    /// commands. The target is resolved in the same way as
+    /// [`build_or_run()`](Self::build_or_run).
+    ///
+    /// Dependencies discovered through depfiles are only included if the
+    /// depfile exists from a previous build.
+    pub fn plan(
+        &self,
+        target: &str,
+    ) -> Result<Plan, DiagnosticError<'a, Error, &'a Workspace<'a>>> {
+        tracing::debug!("Plan: {target}");
+        let spec = self
+            .inner
+            .get_build_or_command_spec(target)
+            .map_err(|err| err.into_diagnostic_error(self.inner.workspace))?;
+        let root = spec.to_task_id();
+        let mut plan = Plan {
+            root,
+            nodes: IndexMap::new(),
+        };
+        self.inner
+            .plan_task(spec, DepChain::Empty, &mut plan)
+            .map_err(|err| err.into_diagnostic_error(self.inner.workspace))?;
+        plan.detect_same_commands();
+        Ok(plan)
+    }
 }
 
 impl<'a> Inner<'a> {
+    fn expand_target_glob(&self, pattern: &str) -> Result<Vec<Absolute<werk_fs::PathBuf>>, Error> {
+        let pattern = if pattern.starts_with('/') {
+            pattern.to_owned()
+        } else {
+            format!("/{pattern}")
+        };
+        let patterns = [pattern];
+        let (mut targets, _) = self
+            .workspace
+            .glob_workspace_files(&patterns)
+            .map_err(|err| Error::Glob(Arc::new(err)))?;
+        let matcher = GlobPatterns::new(&patterns).map_err(|err| Error::Glob(Arc::new(err)))?;
+        targets.extend(
+            self.buildable_outputs(|candidate| matcher.is_match(candidate))
+                .into_iter()
+                .map(|(target, _)| target),
+        );
+        let [pattern] = patterns;
+
+        if targets.is_empty() {
+            return Err(Error::NoRuleToBuildTarget(pattern));
+        }
+        targets.sort_unstable();
+        targets.dedup();
+        Ok(targets)
+    }
+
+    /// Find the outputs that build recipes know how to build, along with the
+    /// recipe that builds each of them, in no particular order.
+    ///
+    /// The targets of build recipes without a pattern stem are always
+    /// included. The outputs of build recipes with a pattern stem cannot be
+    /// enumerated, so candidates are inferred from the paths of source files
+    /// and the targets of recipes without a stem, with and without their
+    /// extension. Candidates accepted by `filter` are only kept if they can be
+    /// planned, meaning all of their dependencies exist or can be built.
+    fn buildable_outputs(
+        &self,
+        mut filter: impl FnMut(&Absolute<werk_fs::PathBuf>) -> bool,
+    ) -> Vec<(Absolute<werk_fs::PathBuf>, &'a ir::BuildRecipe<'a>)> {
+        let manifest = &self.workspace.manifest;
+        let mut seen = ahash::HashSet::default();
+        let mut outputs = Vec::new();
+
+        let mut stem_sources = self
+            .workspace
+            .workspace_files()
+            .filter(|(_, entry)| entry.metadata.is_file)
+            .map(|(path, _)| path.to_path_buf())
+            .collect::<Vec<_>>();
+        for recipe in &manifest.build_recipes {
+            for target in recipe.pattern.expand().into_iter().flatten() {
+                let Ok(target) = Absolute::try_from(target.as_str()) else {
+                    continue;
+                };
+                stem_sources.push(target.clone());
+                if filter(&target) && seen.insert(target.clone()) {
+                    if let Ok(Some(recipe_match)) = manifest.match_build_recipe(&target) {
+                        outputs.push((target, recipe_match.recipe));
+                    }
+                }
+            }
+        }
+
+        for recipe in &manifest.build_recipes {
+            let Some((prefix, suffixes)) = recipe.pattern.stem_affixes() else {
+                continue;
+            };
+            for path in &stem_sources {
+                let path = path.as_str();
+                let without_extension = path
+                    .rfind('.')
+                    .filter(|dot| !path[*dot..].contains('/'))
+                    .map(|dot| &path[..dot]);
+                for stem_source in std::iter::once(path).chain(without_extension) {
+                    let Some(stem) = stem_source.strip_prefix(&*prefix) else {
+                        continue;
+                    };
+                    if stem.is_empty() {
+                        continue;
+                    }
+                    for suffix in &suffixes {
+                        let Ok(candidate) = Absolute::try_from(&*format!("{prefix}{stem}{suffix}"))
+                        else {
+                            continue;
+                        };
+                        if !filter(&candidate) || !seen.insert(candidate.clone()) {
+                            continue;
+                        }
+                        if let Some(recipe) = self.plannable_recipe(&candidate) {
+                            outputs.push((candidate, recipe));
+                        }
+                    }
+                }
+            }
+        }
+
+        outputs
+    }
+
+    /// Get the build recipe matching `target`, if all of its dependencies
+    /// exist or can be built.
+    fn plannable_recipe(&self, target: &Absolute<Path>) -> Option<&'a ir::BuildRecipe<'a>> {
+        let recipe_match = self.workspace.manifest.match_build_recipe(target).ok()??;
+        let recipe = recipe_match.recipe;
+        let spec = TaskSpec::Recipe(ir::RecipeMatch::Build(recipe_match));
+        let mut plan = Plan {
+            root: spec.to_task_id(),
+            nodes: IndexMap::new(),
+        };
+        self.plan_task(spec, DepChain::Empty, &mut plan)
+            .is_ok()
+            .then_some(recipe)
+    }
+
     fn get_build_spec(&self, target: &Absolute<Path>) -> Result<TaskSpec<'a>, Error> {
         let recipe_match = self.workspace.manifest.match_build_recipe(target)?;
         Ok(if let Some(recipe_match) = recipe_match {
@@ -305,7 +776,28 @@ impl<'a> Inner<'a> {
         Ok(TaskSpec::Recipe(ir::RecipeMatch::Task(recipe_match)))
     }
 
+    /// Get the spec of a dependency named in the body of a recipe. Recipes in
+    /// imported Werkfiles refer to tasks in the same Werkfile without the
+    /// namespace.
+    fn get_dependency_spec(
+        &self,
+        namespace: Option<Symbol>,
+        target: &str,
+    ) -> Result<TaskSpec<'a>, Error> {
+        if let Some(namespace) = namespace {
+            let qualified = format!("{namespace}.{target}");
+            if let Some(recipe) = self.workspace.manifest.match_task_recipe(&qualified) {
+                return Ok(TaskSpec::Recipe(ir::RecipeMatch::Task(recipe)));
+            }
+        }
+        self.get_build_or_command_spec(target)
+    }
+
     fn get_build_or_command_spec(&self, target: &str) -> Result<TaskSpec<'a>, Error> {
+        if let Some((member, member_target)) = crate::parse_member_label(target) {
+            return self.get_member_spec(member, member_target);
+        }
+
         let task_recipe_match = self.workspace.manifest.match_task_recipe(target);
 
         if let Ok(path) = werk_fs::Path::new(target) {
@@ -336,6 +828,69 @@ impl<'a> Inner<'a> {
         }
     }
 
+    /// Get the spec of a target in a workspace member. When `target` is
+    /// `None`, this is the default target of the member.
+    fn get_member_spec(&self, name: &str, target: Option<&str>) -> Result<TaskSpec<'a>, Error> {
+        let Some(index) = self
+            .root
+            .members
+            .iter()
+            .position(|member| member.name == name)
+        else {
+            return Err(Error::NoSuchMember(name.to_owned()));
+        };
+        let member = &self.root.members[index];
+        let target = match target {
+            Some(target) => target.to_owned(),
+            None => member
+                .default_target
+                .clone()
+                .ok_or_else(|| Error::NoRuleToBuildTarget(format!("//{name}")))?,
+        };
+        Ok(TaskSpec::Member {
+            index,
+            label: Symbol::new(&format!("//{}:{target}", member.name)),
+            target,
+        })
+    }
+
+    /// Build or run a target in the workspace of a member. The member keeps
+    /// track of its own tasks, so each target is only built once, no matter
+    /// how many recipes depend on it.
+    async fn run_member_task(
+        self: &Arc<Self>,
+        index: usize,
+        target: &str,
+    ) -> Result<BuildStatus, Error> {
+        if self.member_chain.contains(&index) {
+            let chain = self
+                .member_chain
+                .iter()
+                .chain([&index])
+                .map(|&member| self.root.members[member].name.as_str())
+                .collect::<Vec<_>>();
+            return Err(Error::CircularMemberDependency(chain.join(" -> ")));
+        }
+
+        let mut member_chain = self.member_chain.clone();
+        member_chain.push(index);
+        let inner = Arc::new(Inner {
+            workspace: &self.root.members[index].workspace,
+            root: self.root,
+            member_chain,
+            executor: self.executor.clone(),
+            metrics: self.metrics.clone(),
+            results: self.results.clone(),
+        });
+        let spec = inner
+            .get_build_or_command_spec(target)
+            .map_err(|err| err.in_member(index))?;
+        // Boxing because of recursion.
+        Box::pin(inner.run_task(spec, DepChain::Empty))
+            .await
+            .map_err(|err| err.in_member(index))
+    }
+
     /// Build the task, coordinating dependencies and rebuilds. The `invoker`
     /// is the chain of dependencies that triggered this build, not including
     /// this task.
@@ -401,45 +956,294 @@ impl<'a> Inner<'a> {
                 receiver.await.map_err(|_| Error::Cancelled(task_id))?
             }
             Scheduling::BuildNow(task_spec) => {
+                let kind = TaskKind::of(&task_spec);
                 let result = self
                     .clone()
                     .rebuild_spec(task_id, task_spec, dep_chain)
                     .await;
+                self.did_finish_task(task_id, kind, &result);
                 finish_built(&self.workspace.runner_state, task_id, &result);
                 result
             }
         }
     }
 
+    /// Record the outcome of a task. Tasks that stand for a task of a member
+    /// are reported by the runner of the member, and checking that a file
+    /// exists is only reported when it fails.
+    fn did_finish_task(
+        &self,
+        task_id: TaskId,
+        kind: TaskKind,
+        result: &Result<BuildStatus, Error>,
+    ) {
+        let key = self.metrics_key(task_id);
+        let outcome = match (kind, result) {
+            (TaskKind::Member, _) | (TaskKind::CheckExists, Ok(_)) => {
+                self.results.discard(key);
+                return;
+            }
+            (TaskKind::Recipe, Ok(BuildStatus::Complete(_, outdatedness)))
+                if !outdatedness.is_outdated() =>
+            {
+                TaskOutcome::UpToDate
+            }
+            (TaskKind::Recipe, Ok(_)) => TaskOutcome::Executed,
+            (_, Err(Error::Cancelled(_))) => TaskOutcome::Cancelled,
+            (_, Err(_)) if self.results.has_failed_dependency(key) => TaskOutcome::DependencyFailed,
+            (_, Err(err)) => TaskOutcome::Failed(TaskFailure {
+                // Attribute the error to the member, like `run_member_task()`
+                // does.
+                error: self
+                    .member_chain
+                    .iter()
+                    .rev()
+                    .fold(err.clone(), |err, &index| err.in_member(index)),
+                output: Vec::new(),
+            }),
+        };
+        self.results.did_finish(
+            key,
+            TaskResult {
+                task: self.task_label(task_id),
+                task_id,
+                member: self.member_chain.last().copied(),
+                outcome,
+                log_file: self.write_log(task_id),
+            },
+        );
+    }
+
+    /// Write the output of the commands of a task to its log file, if they
+    /// printed anything.
+    fn write_log(&self, task_id: TaskId) -> Option<Absolute<std::path::PathBuf>> {
+        let log = self.results.take_log(self.metrics_key(task_id));
+        if log.is_empty() {
+            return None;
+        }
+        match self.workspace.write_task_log(task_id, &log) {
+            Ok(path) => Some(path),
+            Err(err) => {
+                tracing::error!("error writing log of task '{task_id}': {err}");
+                None
+            }
+        }
+    }
+
+    /// True if no new commands should be started, because a task failed and
+    /// the build doesn't keep going.
+    fn is_stopped(&self) -> bool {
+        !self.root.keep_going && self.results.any_failed()
+    }
+
+    /// The name of a task of this runner's workspace, with the label prefix of
+    /// the member if the workspace is a member.
+    fn task_label(&self, task_id: TaskId) -> String {
+        match self.member_chain.last() {
+            Some(&index) => format!("//{}:{task_id}", self.root.members[index].name),
+            None => task_id.to_string(),
+        }
+    }
+
+    /// The key of a task of this runner's workspace in the metrics.
+    fn metrics_key(&self, task_id: TaskId) -> TaskKey {
+        (self.member_chain.last().copied(), task_id)
+    }
+
     fn check_exists(&self, path: &Absolute<werk_fs::Path>) -> Result<BuildStatus, Error> {
         let Some(entry) = self.workspace.get_project_file(path) else {
             return Err(Error::NoRuleToBuildTarget(path.to_string()));
         };
+        self.workspace.register_consulted_file(&entry.path);
         let mtime = entry.metadata.mtime;
         tracing::debug!("Check file mtime `{path}`: {mtime:?}");
         Ok(BuildStatus::Exists(Absolute::symbolicate(path), mtime))
     }
 
+    /// Parse the depfile at `depfile_path` in the output directory, if it
+    /// exists, and get the specs for the dependencies that it lists.
+    fn read_depfile_dependency_specs(
+        &self,
+        depfile_path: &Absolute<werk_fs::Path>,
+    ) -> Result<Option<Vec<TaskSpec<'a>>>, Error> {
+        let Some(depfile_entry) = self.workspace.get_existing_output_file(depfile_path)? else {
+            return Ok(None);
+        };
+
+        tracing::debug!("Parsing depfile: {}", depfile_entry.path.display());
+        let depfile_contents = self.workspace.io.read_file(&depfile_entry.path)?;
+        let depfile = Depfile::parse(&depfile_contents)?;
+        let mut specs = Vec::with_capacity(depfile.deps.len());
+        for dep in &depfile.deps {
+            // Translate the filesystem path produced by the compiler into an
+            // abstract path within the workspace. Normally this will be a file
+            // inside the output directory.
+            let dep = dep.as_path().normalize()?;
+            let abstract_path = self
+                .workspace
+                .unresolve_path(&dep)
+                .map_err(|err| Error::InvalidPathInDepfile(dep.display().to_string(), err))?;
+            tracing::debug!("Discovered depfile dependency: {abstract_path}");
+            specs.push(self.get_build_spec_relaxed(&abstract_path)?);
+        }
+        Ok(Some(specs))
+    }
+
+    /// Evaluate the body of a recipe for a task. Planning and execution both
+    /// start from the result, so a plan shows exactly what a build would run.
+    fn evaluate_recipe(
+        &self,
+        task_id: TaskId,
+        recipe: &ir::RecipeMatch<'_>,
+    ) -> Result<EvaluatedRecipe<'a>, Error> {
+        match *recipe {
+            ir::RecipeMatch::Task(recipe) => self.evaluate_task_recipe(task_id, recipe),
+            ir::RecipeMatch::Build(ref recipe_match) => {
+                self.evaluate_build_recipe(task_id, recipe_match)
+            }
+        }
+    }
+
+    fn evaluate_build_recipe(
+        &self,
+        task_id: TaskId,
+        recipe_match: &ir::BuildRecipeMatch<'_>,
+    ) -> Result<EvaluatedRecipe<'a>, Error> {
+        let global_scope = RootScope::with_namespace(self.workspace, recipe_match.recipe.namespace);
+        let mut scope = BuildRecipeScope::new(&global_scope, task_id, recipe_match);
+        scope.set(
+            Symbol::new("out"),
+            Eval::inherent(Value::Path(recipe_match.target_file.to_path_buf())),
+        );
+
+        // Report errors in the `out` statement, which are ignored when looking
+        // up the location of the output file.
+        eval::eval_out_stmt(self.workspace, recipe_match)?;
+
+        // Evaluate recipe body (`out` is available and in scope).
+        let eval::Eval {
+            value: evaluated,
+            used,
+        } = eval::eval_build_recipe_statements(
+            &mut scope,
+            &recipe_match.recipe.ast.body.statements,
+        )?;
+
+        let dependency_specs = evaluated
+            .explicit_dependencies
+            .iter()
+            .map(|s| self.get_dependency_spec(recipe_match.recipe.namespace, s))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let depfile = match evaluated.depfile {
+            Some(ref depfile) => {
+                let path = werk_fs::Path::new(depfile)
+                    .and_then(|p| p.absolutize(werk_fs::Path::ROOT))
+                    .map_err(|err| Error::InvalidTargetPath(depfile.clone(), err))?;
+                let spec = self.get_depfile_build_spec(&path)?;
+                Some((path.into_owned(), spec))
+            }
+            None => None,
+        };
+
+        let fingerprint = compute_fingerprint(recipe_match.recipe.hash, &used);
+
+        Ok(EvaluatedRecipe {
+            node: PlanNode {
+                task_id,
+                kind: PlanNodeKind::Build,
+                dependencies: Vec::new(),
+                commands: evaluated.commands,
+                env: evaluated.env,
+                same_commands_as: None,
+                depfile: depfile.as_ref().map(|(path, _)| path.clone()),
+                used,
+                fingerprint: Some(fingerprint),
+            },
+            dependencies: evaluated.explicit_dependencies,
+            dependency_specs,
+            dependency_spans: evaluated.dependency_spans,
+            volatile_dependencies: evaluated.volatile_dependencies,
+            depfile,
+            command_spans: evaluated.command_spans,
+            tmp_dir: evaluated.tmp_dir,
+            container: evaluated.container,
+        })
+    }
+
+    fn evaluate_task_recipe(
+        &self,
+        task_id: TaskId,
+        recipe: &ir::TaskRecipe<'_>,
+    ) -> Result<EvaluatedRecipe<'a>, Error> {
+        let global_scope = RootScope::with_namespace(self.workspace, recipe.namespace);
+        let mut scope = TaskRecipeScope::new(&global_scope, task_id);
+
+        // Evaluate dependencies (`out` is not available in commands).
+        let eval::Eval {
+            value: evaluated,
+            used,
+        } = eval::eval_task_recipe_statements(&mut scope, &recipe.ast.body.statements)?;
+        let dependency_specs = evaluated
+            .build
+            .iter()
+            .map(|s| self.get_dependency_spec(recipe.namespace, s))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(EvaluatedRecipe {
+            node: PlanNode {
+                task_id,
+                kind: PlanNodeKind::Task,
+                dependencies: Vec::new(),
+                commands: evaluated.commands,
+                env: evaluated.env,
+                same_commands_as: None,
+                depfile: None,
+                used,
+                fingerprint: None,
+            },
+            volatile_dependencies: vec![false; dependency_specs.len()],
+            dependencies: evaluated.build,
+            dependency_specs,
+            dependency_spans: evaluated.build_spans,
+            depfile: None,
+            command_spans: evaluated.command_spans,
+            tmp_dir: evaluated.tmp_dir,
+            container: None,
+        })
+    }
+
     #[tracing::instrument(level = "debug", skip_all, fields(target_file))]
     async fn execute_build_recipe(
         self: &Arc<Self>,
         task_id: TaskId,
         recipe_match: ir::BuildRecipeMatch<'_>,
+        evaluated: EvaluatedRecipe<'a>,
         dep_chain: DepChainEntry<'_>,
     ) -> Result<BuildStatus, Error> {
-        let global_scope = RootScope::new(self.workspace);
-        let mut scope = BuildRecipeScope::new(&global_scope, task_id, &recipe_match);
-        scope.set(
-            Symbol::new("out"),
-            Eval::inherent(Value::String(recipe_match.target_file.to_string())),
-        );
+        let EvaluatedRecipe {
+            node,
+            dependencies,
+            dependency_specs,
+            dependency_spans,
+            volatile_dependencies,
+            depfile,
+            command_spans,
+            tmp_dir,
+            container,
+        } = evaluated;
 
         let cache = self
             .workspace
             .take_build_target_cache(&recipe_match.target_file);
+        let previous_duration_ms = cache.as_ref().and_then(|cache| cache.duration_ms);
+        let previous_critical_path_ms = cache.as_ref().and_then(|cache| cache.critical_path_ms);
+        // Recipes with the longest critical path in the previous run get a job
+        // slot first.
+        let priority = previous_critical_path_ms.map_or(Duration::ZERO, Duration::from_millis);
         // Check the target's mtime.
-        let out_mtime = scope
-            .workspace()
+        let out_mtime = self
+            .workspace
             .get_existing_output_file(&recipe_match.target_file)?
             .map(|entry| entry.metadata.mtime);
 
@@ -450,19 +1254,28 @@ impl<'a> Inner<'a> {
             out_mtime,
         );
 
-        // Evaluate recipe body (`out` is available and in scope).
-        let evaluated = eval::eval_build_recipe_statements(
-            &mut scope,
-            &recipe_match.recipe.ast.body.statements,
-        )?;
-        outdatedness.did_use(evaluated.used);
-        let evaluated = evaluated.value;
+        if self.workspace.force.is_forced(&recipe_match.target_file) {
+            outdatedness.add_reason(Reason::Forced);
+        }
+        if recipe_match.recipe.attributes.always_run {
+            outdatedness.add_reason(Reason::AlwaysRun);
+        }
+        if recipe_match.recipe.attributes.uncached {
+            outdatedness.add_reason(Reason::Uncached);
+        }
 
-        let mut explicit_dependency_specs = evaluated
-            .explicit_dependencies
-            .iter()
-            .map(|s| self.get_build_or_command_spec(s))
-            .collect::<Result<Vec<_>, Error>>()?;
+        outdatedness.did_use(node.used.clone());
+        outdatedness.did_evaluate_commands(&node.commands, &node.env);
+
+        let mut explicit_dependency_specs = Vec::new();
+        let mut volatile_dependency_specs = Vec::new();
+        for (spec, volatile) in dependency_specs.into_iter().zip(volatile_dependencies) {
+            if volatile {
+                volatile_dependency_specs.push(spec);
+            } else {
+                explicit_dependency_specs.push(spec);
+            }
+        }
 
         // Rebuild if the target does not exist.
         if let Some(mtime) = out_mtime {
@@ -472,19 +1285,9 @@ impl<'a> Inner<'a> {
             outdatedness.missing(Absolute::symbolicate(&recipe_match.target_file));
         }
 
+        let mut dependency_keys = Vec::new();
         let mut check_implicit_depfile_was_generated = None;
-        if let Some(depfile) = evaluated.depfile {
-            let depfile_path = werk_fs::Path::new(&depfile)
-                .and_then(|p| p.absolutize(werk_fs::Path::ROOT))
-                .map_err(|err| Error::InvalidTargetPath(depfile.clone(), err))?;
-            let dep = self.get_depfile_build_spec(&depfile_path)?;
-
-            // Make the `depfile` variable available to the recipe body.
-            scope.set(
-                Symbol::from("depfile"),
-                Eval::inherent(Value::String(depfile.clone())),
-            );
-
+        if let Some((depfile_path, dep)) = depfile {
             match dep {
                 DepfileSpec::Recipe(depfile_recipe_match_data) => {
                     tracing::debug!(
@@ -492,15 +1295,11 @@ impl<'a> Inner<'a> {
                         depfile_recipe_match_data.recipe.pattern
                     );
                     // Depfile is explicitly generated by a recipe - build it.
+                    let spec = TaskSpec::Recipe(ir::RecipeMatch::Build(depfile_recipe_match_data));
+                    dependency_keys.push(self.metrics_key(spec.to_task_id()));
                     let dep_reasons = self
                         .clone()
-                        .build_dependencies(
-                            vec![TaskSpec::Recipe(ir::RecipeMatch::Build(
-                                depfile_recipe_match_data,
-                            ))],
-                            dep_chain,
-                            out_mtime,
-                        )
+                        .build_dependencies(vec![spec], dep_chain, out_mtime)
                         .await?;
                     outdatedness.add_reasons(dep_reasons);
                 }
@@ -511,30 +1310,16 @@ impl<'a> Inner<'a> {
             }
             let is_implicit_depfile = check_implicit_depfile_was_generated.is_some();
 
-            if let Some(depfile_entry) = self.workspace.get_existing_output_file(&depfile_path)? {
+            if let Some(depfile_deps) = self.read_depfile_dependency_specs(&depfile_path)? {
                 // The depfile was generated, either by a build recipe or by a
-                // previous run of this recipe. Parse it and add its
-                // dependencies!
-                tracing::debug!("Parsing depfile: {}", depfile_entry.path.display());
-                let depfile_contents = self.workspace.io.read_file(&depfile_entry.path)?;
-                let depfile = Depfile::parse(&depfile_contents)?;
-                for dep in &depfile.deps {
-                    // Translate the filesystem path produced by the compiler into an
-                    // abstract path within the workspace. Normally this will be a file
-                    // inside the output directory.
-                    let dep = dep.as_path().normalize()?;
-                    let abstract_path = self.workspace.unresolve_path(&dep).map_err(|err| {
-                        Error::InvalidPathInDepfile(dep.display().to_string(), err)
-                    })?;
-                    tracing::debug!("Discovered depfile dependency: {abstract_path}");
-                    explicit_dependency_specs.push(self.get_build_spec_relaxed(&abstract_path)?);
-                }
+                // previous run of this recipe. Add its dependencies!
+                explicit_dependency_specs.extend(depfile_deps);
             } else if is_implicit_depfile {
                 // The implicit depfile was not generated yet, in this run
                 // or a previous run. Add that as a reason to rebuild the
                 // main recipe. Note that this causes the main recipe to
                 // always be outdated if it fails to generate the depfile!
-                outdatedness.add_reason(Reason::Missing(Absolute::symbolicate(depfile_path)));
+                outdatedness.add_reason(Reason::Missing(Absolute::symbolicate(&depfile_path)));
             } else {
                 // If the depfile is generated by a rule, it is an error if that
                 // rule did not generate the depfile. If it is implicit, it's
@@ -545,43 +1330,148 @@ impl<'a> Inner<'a> {
                         "Depfile does not exist, and was not generated, because this is a dry run",
                     );
                 } else {
-                    return Err(Error::DepfileNotFound(
-                        depfile_path.into_owned().into_inner(),
-                    ));
+                    return Err(Error::DepfileNotFound(depfile_path.into_inner()));
                 }
             }
         }
 
+        let source = RecipeSource {
+            file: recipe_match.recipe.file,
+            recipe: recipe_match.recipe.ast.pattern.span,
+            command_spans: &command_spans,
+        };
+
         // Build dependencies!
-        let dep_reasons = self
-            .build_dependencies(explicit_dependency_specs, dep_chain, out_mtime)
-            .await?;
-        outdatedness.add_reasons(dep_reasons);
+        dependency_keys.extend(
+            explicit_dependency_specs
+                .iter()
+                .chain(&volatile_dependency_specs)
+                .map(|spec| self.metrics_key(spec.to_task_id())),
+        );
+        // Volatile dependencies are built at the same time as the others, but
+        // never make the recipe outdated.
+        let (dep_reasons, volatile_reasons) = futures::join!(
+            self.build_dependencies(explicit_dependency_specs, dep_chain, out_mtime),
+            self.build_dependencies(volatile_dependency_specs, dep_chain, out_mtime),
+        );
+        let missing_input = |err| source.missing_input(err, &dependencies, &dependency_spans);
+        outdatedness.add_reasons(dep_reasons.map_err(missing_input)?);
+        outdatedness.ignore_reasons(volatile_reasons.map_err(missing_input)?);
 
         // Create the parent directory for the target file if it doesn't exist.
-        scope
-            .workspace()
-            .create_output_parent_dirs(&recipe_match.target_file)?;
+        if self.workspace.create_dirs {
+            self.workspace
+                .create_output_parent_dirs(&recipe_match.target_file)?;
+        }
 
-        let (outdated, new_cache) = outdatedness.finish();
-        self.workspace
-            .store_build_target_cache(recipe_match.target_file.to_path_buf(), new_cache);
+        let (outdated, mut new_cache) = outdatedness.finish();
+        let previous_output_hash = cache.and_then(|cache| cache.output_hash);
 
         self.workspace
             .render
-            .will_build(task_id, evaluated.commands.len(), &outdated);
+            .will_build(task_id, node.commands.len(), &outdated);
 
-        let result = if outdated.is_outdated() {
+        let is_outdated = outdated.is_outdated();
+        let result = if is_outdated {
             tracing::debug!("Rebuilding");
             tracing::trace!("Reasons: {:?}", outdated);
-            self.execute_recipe_commands(task_id, evaluated.commands, evaluated.env, true, false)
-                .await
-                .map(|()| BuildStatus::Complete(task_id, outdated))
+            let tmp_dir = tmp_dir.as_deref();
+            let wrapped = if let Some(host) = recipe_match.recipe.host {
+                Some(self.remote_commands(
+                    host,
+                    &recipe_match,
+                    &node,
+                    &dependencies,
+                    &command_spans,
+                ))
+            } else {
+                container.as_deref().map(|image| {
+                    self.container_commands(image, &node)
+                        .map(|commands| (commands, command_spans.clone()))
+                })
+            };
+            let result = match (self.create_tmp_dir(tmp_dir), wrapped) {
+                (Ok(()), None) => {
+                    self.execute_build_recipe_commands(
+                        task_id,
+                        node.commands,
+                        node.env,
+                        recipe_match.recipe.attributes,
+                        recipe_match.recipe.pool,
+                        priority,
+                        source,
+                    )
+                    .await
+                }
+                (Ok(()), Some(Ok((commands, command_spans)))) => {
+                    self.execute_build_recipe_commands(
+                        task_id,
+                        commands,
+                        node.env,
+                        recipe_match.recipe.attributes,
+                        recipe_match.recipe.pool,
+                        priority,
+                        RecipeSource {
+                            command_spans: &command_spans,
+                            ..source
+                        },
+                    )
+                    .await
+                }
+                (Err(err), _) | (Ok(()), Some(Err(err))) => Err(err),
+            };
+            self.finish_tmp_dir(task_id, tmp_dir, result.is_ok());
+            let stamp = recipe_match.recipe.attributes.stamp;
+            let result = if stamp {
+                result.and_then(|()| self.touch_stamp_file(&recipe_match.target_file))
+            } else {
+                result
+            };
+            if self.workspace.io.is_dry_run() {
+                new_cache.output_hash = previous_output_hash;
+            } else if result.is_ok() && !stamp && !recipe_match.recipe.attributes.uncached {
+                // Stamp files are always empty, so their hash says nothing
+                // about whether dependents must be rebuilt. The outputs of
+                // uncached recipes are not recorded, so their dependents are
+                // always rebuilt.
+                new_cache.output_hash = self.workspace.hash_output_file(&recipe_match.target_file);
+            }
+
+            result.map(|()| {
+                // Like Ninja's `restat`: if the output did not actually change,
+                // there is no need to rebuild anything that depends on it,
+                // unless the rebuild was explicitly forced.
+                let unchanged = recipe_match.recipe.attributes.restat
+                    && new_cache.output_hash.is_some()
+                    && new_cache.output_hash == previous_output_hash
+                    && !outdated.reasons.contains(&Reason::Forced);
+                if unchanged {
+                    tracing::debug!("Output is unchanged");
+                    BuildStatus::Unchanged(task_id, outdated)
+                } else {
+                    BuildStatus::Complete(task_id, outdated)
+                }
+            })
         } else {
             tracing::debug!("Up to date");
+            new_cache.output_hash = previous_output_hash;
             Ok(BuildStatus::Complete(task_id, outdated))
         };
 
+        // Remember how long the commands took, to estimate critical paths in
+        // later runs. The critical path is updated when the run is finished.
+        new_cache.duration_ms = if is_outdated && result.is_ok() && !self.workspace.io.is_dry_run()
+        {
+            Some(duration_to_millis(
+                self.metrics.command_time(self.metrics_key(task_id)),
+            ))
+        } else {
+            previous_duration_ms
+        };
+        new_cache.critical_path_ms = previous_critical_path_ms;
+        self.workspace
+            .store_build_target_cache(recipe_match.target_file.to_path_buf(), new_cache);
+
         // Check if the implicit depfile was actually generated, and emit a warning if not.
         if let Some(ref implicit_depfile_path) = check_implicit_depfile_was_generated {
             if !self.workspace.io.is_dry_run() {
@@ -599,6 +1489,16 @@ impl<'a> Inner<'a> {
             }
         }
 
+        // Recipes that were stopped before running their commands are not
+        // counted.
+        if !matches!(result, Err(Error::Cancelled(_))) {
+            self.metrics.did_finish(
+                self.metrics_key(task_id),
+                &dependency_keys,
+                is_outdated,
+                previous_duration_ms.map(Duration::from_millis),
+            );
+        }
         self.workspace.render.did_build(task_id, &result);
         result
     }
@@ -607,58 +1507,412 @@ impl<'a> Inner<'a> {
         self: &Arc<Self>,
         task_id: TaskId,
         recipe: &ir::TaskRecipe<'a>,
+        evaluated: EvaluatedRecipe<'a>,
         dep_chain: DepChainEntry<'_>,
     ) -> Result<BuildStatus, Error> {
-        let global_scope = RootScope::new(self.workspace);
-        let mut scope = TaskRecipeScope::new(&global_scope, task_id);
-
-        // Evaluate dependencies (`out` is not available in commands).
+        let EvaluatedRecipe {
+            node,
+            dependencies,
+            dependency_specs,
+            dependency_spans,
+            command_spans,
+            tmp_dir,
+            ..
+        } = evaluated;
+
+        let source = RecipeSource {
+            file: recipe.file,
+            recipe: recipe.ast.name.span,
+            command_spans: &command_spans,
+        };
 
-        let evaluated = eval::eval_task_recipe_statements(&mut scope, &recipe.ast.body.statements)?;
-        let dependency_specs = evaluated
-            .build
+        let dependency_keys = dependency_specs
             .iter()
-            .map(|s| self.get_build_or_command_spec(s))
-            .collect::<Result<Vec<_>, _>>()?;
+            .map(|spec| self.metrics_key(spec.to_task_id()))
+            .collect::<Vec<_>>();
 
         // Note: We don't care about the status of dependencies.
         self.build_dependencies(dependency_specs, dep_chain, None)
-            .await?;
+            .await
+            .map_err(|err| source.missing_input(err, &dependencies, &dependency_spans))?;
 
         let outdated = Outdatedness::outdated(Reason::Rebuilt(task_id));
         self.workspace
             .render
-            .will_build(task_id, evaluated.commands.len(), &outdated);
-
-        let result = self
-            .execute_recipe_commands(task_id, evaluated.commands, evaluated.env, false, true)
-            .await
-            .map(|()| BuildStatus::Complete(task_id, outdated));
+            .will_build(task_id, node.commands.len(), &outdated);
+
+        let tmp_dir = tmp_dir.as_deref();
+        let result = match self.create_tmp_dir(tmp_dir) {
+            Ok(()) => {
+                // Task recipes have no cache, so their critical path is not
+                // known from previous runs.
+                self.execute_recipe_commands(
+                    task_id,
+                    node.commands,
+                    node.env,
+                    recipe.attributes,
+                    recipe.pool,
+                    Duration::ZERO,
+                    source,
+                    false,
+                    true,
+                )
+                .await
+            }
+            Err(err) => Err(err),
+        };
+        self.finish_tmp_dir(task_id, tmp_dir, result.is_ok());
+        let result = result.map(|()| BuildStatus::Complete(task_id, outdated));
 
+        if !matches!(result, Err(Error::Cancelled(_))) {
+            self.metrics
+                .did_finish(self.metrics_key(task_id), &dependency_keys, true, None);
+        }
         self.workspace.render.did_build(task_id, &result);
         result
     }
 
+    /// Create an empty temporary directory for a recipe that uses one,
+    /// clearing out anything left behind by a previous failed run.
+    fn create_tmp_dir(&self, tmp_dir: Option<&Absolute<std::path::Path>>) -> Result<(), Error> {
+        let Some(tmp_dir) = tmp_dir else {
+            return Ok(());
+        };
+        match self.workspace.io.delete_dir(tmp_dir) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+            _ => (),
+        }
+        self.workspace.io.create_dir(tmp_dir)?;
+        Ok(())
+    }
+
+    /// Delete the temporary directory of a recipe when its commands
+    /// succeeded, and keep it for debugging when they failed.
+    fn finish_tmp_dir(
+        &self,
+        task_id: TaskId,
+        tmp_dir: Option<&Absolute<std::path::Path>>,
+        succeeded: bool,
+    ) {
+        let Some(tmp_dir) = tmp_dir else {
+            return;
+        };
+        if !succeeded {
+            self.workspace.render.warning(
+                Some(task_id),
+                &format!(
+                    "Keeping temporary directory for debugging: {}",
+                    tmp_dir.display()
+                ),
+            );
+        } else if let Err(err) = self.workspace.io.delete_dir(tmp_dir) {
+            self.workspace.render.warning(
+                Some(task_id),
+                &format!(
+                    "Could not delete temporary directory `{}`: {err}",
+                    tmp_dir.display()
+                ),
+            );
+        }
+    }
+
+    /// The commands of a build recipe with a `host` statement, and the
+    /// statement that produced each command. The inputs are copied to the
+    /// host first, `run` commands run on the host, and the target file and
+    /// depfile are copied back last. Built-in commands that change files are
+    /// rejected when evaluating the recipe, and the others, like `info`, still
+    /// run locally.
+    fn remote_commands(
+        &self,
+        host: Symbol,
+        recipe_match: &ir::BuildRecipeMatch<'_>,
+        node: &PlanNode,
+        dependencies: &[String],
+        spans: &[Span],
+    ) -> Result<(Vec<RunCommand>, Vec<Span>), Error> {
+        let workspace = self.workspace;
+        let which = |program: &str| {
+            workspace
+                .io
+                .which(program)
+                .map_err(|err| Error::CommandNotFound(program.to_owned(), err))
+        };
+        let remote = Remote::new(
+            &workspace.hosts[&host],
+            which("ssh")?,
+            which("rsync")?,
+            workspace.project_root(),
+            workspace.output_directory(),
+        );
+
+        let mut inputs = Vec::new();
+        for dependency in dependencies {
+            let Ok(path) = werk_fs::Path::new(dependency)
+                .and_then(|path| path.absolutize(werk_fs::Path::ROOT))
+            else {
+                continue;
+            };
+            if let Some(entry) = workspace.get_existing_project_or_output_file(&path)? {
+                inputs.push(entry.path);
+            }
+        }
+        let mut outputs = Vec::new();
+        // Stamp files are touched locally.
+        if !recipe_match.recipe.attributes.stamp {
+            outputs.push(workspace.output_location(&recipe_match.target_file));
+        }
+        if let Some(ref depfile) = node.depfile {
+            outputs.push(workspace.output_location(depfile));
+        }
+
+        let host_span = ir::BuildRecipe::host_of(recipe_match.recipe.ast)
+            .map_or(recipe_match.recipe.span, |host| host.0);
+        let mut commands = Vec::new();
+        let mut command_spans = Vec::new();
+        for command_line in remote.push(&inputs, &outputs) {
+            commands.push(RunCommand::Shell(command_line));
+            command_spans.push(host_span);
+        }
+
+        let mut env = node.env.clone();
+        let mut working_dir = workspace.project_root().to_owned();
+        for (command, span) in node.commands.iter().zip(spans) {
+            let command = match *command {
+                RunCommand::Shell(ref command_line) => RunCommand::Shell(remote.run(
+                    std::slice::from_ref(command_line),
+                    &env,
+                    &working_dir,
+                )),
+                RunCommand::Pipeline(ref command_lines, ref redirection) => RunCommand::Pipeline(
+                    vec![remote.run(command_lines, &env, &working_dir)],
+                    redirection.clone(),
+                ),
+                RunCommand::SetEnv(ref key, ref value) => {
+                    env.env(key, value);
+                    command.clone()
+                }
+                RunCommand::RemoveEnv(ref key) => {
+                    env.env_remove(key);
+                    command.clone()
+                }
+                RunCommand::SetWorkingDir(ref dir) => {
+                    working_dir.clone_from(dir);
+                    command.clone()
+                }
+                _ => command.clone(),
+            };
+            commands.push(command);
+            command_spans.push(*span);
+        }
+
+        for command_line in remote.pull(&outputs) {
+            commands.push(RunCommand::Shell(command_line));
+            command_spans.push(host_span);
+        }
+        Ok((commands, command_spans))
+    }
+
+    /// The commands of a build recipe with a `container` statement, where
+    /// `run` commands run in a container of the image. Other commands, like
+    /// `write` and `copy`, still run locally.
+    fn container_commands(&self, image: &str, node: &PlanNode) -> Result<Vec<RunCommand>, Error> {
+        let workspace = self.workspace;
+        let runtime = workspace
+            .io
+            .which(&workspace.container_runtime)
+            .map_err(|err| Error::CommandNotFound(workspace.container_runtime.clone(), err))?;
+        let container = Container::new(
+            runtime,
+            image,
+            workspace.container_pull,
+            workspace.project_root(),
+            workspace.output_directory(),
+        );
+
+        let mut env = node.env.clone();
+        if workspace.reproducible {
+            // The variables inherited from the host, like `PATH`, would
+            // replace the ones of the image.
+            let reproducible = Env::reproducible(workspace.io);
+            for (key, value) in reproducible.env {
+                let inherited = key
+                    .to_str()
+                    .is_some_and(|key| REPRODUCIBLE_ENV_ALLOWLIST.contains(&key));
+                if !inherited && !env.env.contains_key(&key) {
+                    env.env(key, value);
+                }
+            }
+        }
+        let mut working_dir = workspace.project_root().to_owned();
+        let mut commands = Vec::with_capacity(node.commands.len());
+        for command in &node.commands {
+            let command = match *command {
+                RunCommand::Shell(ref command_line) => {
+                    RunCommand::Shell(container.run(command_line, &env, &working_dir, false))
+                }
+                RunCommand::Pipeline(ref command_lines, ref redirection) => RunCommand::Pipeline(
+                    command_lines
+                        .iter()
+                        .map(|command_line| container.run(command_line, &env, &working_dir, true))
+                        .collect(),
+                    redirection.clone(),
+                ),
+                RunCommand::SetEnv(ref key, ref value) => {
+                    env.env(key, value);
+                    command.clone()
+                }
+                RunCommand::RemoveEnv(ref key) => {
+                    env.env_remove(key);
+                    command.clone()
+                }
+                RunCommand::SetWorkingDir(ref dir) => {
+                    working_dir.clone_from(dir);
+                    command.clone()
+                }
+                _ => command.clone(),
+            };
+            commands.push(command);
+        }
+        Ok(commands)
+    }
+
+    /// Execute the commands of an outdated build recipe. If another build
+    /// recipe executes the exact same commands in this run (e.g., a code
+    /// generator producing multiple files), the commands are only executed
+    /// once, and the result is shared by both recipes.
+    #[expect(clippy::too_many_arguments)]
+    async fn execute_build_recipe_commands(
+        &self,
+        task_id: TaskId,
+        commands: Vec<RunCommand>,
+        env: Env,
+        attributes: ir::Attributes,
+        pool: Option<Symbol>,
+        priority: Duration,
+        source: RecipeSource<'_>,
+    ) -> Result<(), Error> {
+        enum Scheduling {
+            Done(Result<(), Error>),
+            Pending(oneshot::Receiver<Result<(), Error>>),
+            ExecuteNow,
+        }
+
+        if commands.is_empty() {
+            return Ok(());
+        }
+
+        let key = RecipeCommands { commands, env };
+        let (other_task_id, scheduling) = match self
+            .workspace
+            .runner_state
+            .commands
+            .lock()
+            .entry(key.clone())
+        {
+            std::collections::hash_map::Entry::Occupied(mut entry) => match entry.get_mut() {
+                CommandStatus::Executed(other, result) => {
+                    (Some(*other), Scheduling::Done(result.clone()))
+                }
+                CommandStatus::Pending(other, waiters) => {
+                    let (send, recv) = oneshot::channel();
+                    waiters.push(send);
+                    (Some(*other), Scheduling::Pending(recv))
+                }
+            },
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(CommandStatus::Pending(task_id, Vec::new()));
+                (None, Scheduling::ExecuteNow)
+            }
+        };
+
+        if let Some(other_task_id) = other_task_id {
+            tracing::debug!("Sharing the result of identical commands from `{other_task_id}`");
+        }
+
+        let result = match scheduling {
+            Scheduling::Done(result) => result,
+            Scheduling::Pending(receiver) => receiver
+                .await
+                .map_err(|_| Error::Cancelled(other_task_id.unwrap_or(task_id)))?,
+            Scheduling::ExecuteNow => {
+                let result = self
+                    .execute_recipe_commands(
+                        task_id,
+                        key.commands.clone(),
+                        key.env.clone(),
+                        attributes,
+                        pool,
+                        priority,
+                        source,
+                        true,
+                        false,
+                    )
+                    .await;
+
+                let mut commands = self.workspace.runner_state.commands.lock();
+                let status = commands.get_mut(&key).expect("commands not registered");
+                let CommandStatus::Pending(_, waiters) =
+                    std::mem::replace(status, CommandStatus::Executed(task_id, result.clone()))
+                else {
+                    panic!("Commands executed multiple times: {task_id}");
+                };
+                std::mem::drop(commands);
+
+                for waiter in waiters {
+                    _ = waiter.send(result.clone());
+                }
+                result
+            }
+        };
+
+        if let Some(other_task_id) = other_task_id {
+            self.metrics
+                .did_share_commands(self.metrics_key(task_id), self.metrics_key(other_task_id));
+        }
+        result
+    }
+
+    #[expect(clippy::too_many_arguments, clippy::too_many_lines)]
     async fn execute_recipe_commands(
         &self,
         task_id: TaskId,
         run_commands: Vec<RunCommand>,
         mut env: Env,
-        silent_by_default: bool,
+        attributes: ir::Attributes,
+        pool: Option<Symbol>,
+        priority: Duration,
+        source: RecipeSource<'_>,
+        capture_by_default: bool,
         forward_stdout: bool,
     ) -> Result<(), Error> {
-        let num_steps = run_commands.len();
+        let num_steps = run_commands.iter().filter(|cmd| cmd.is_step()).count();
         if num_steps == 0 {
             return Ok(());
         }
 
+        if self.workspace.reproducible {
+            let mut reproducible = Env::reproducible(self.workspace.io);
+            reproducible.merge_from(&env);
+            env = reproducible;
+        }
+
+        // Wait for the recipe's pool before taking a job slot, so recipes
+        // waiting for a busy pool do not prevent other recipes from running.
+        let _limit_pool = match pool.and_then(|pool| self.workspace.runner_state.pools.get(&pool)) {
+            Some(semaphore) => Some(semaphore.acquire().await),
+            None => None,
+        };
+
         // Ensure that only the desired number of jobs are running.
         let _limit_concurrency = self
-            .workspace
+            .root
             .runner_state
             .concurrency_limit
-            .acquire()
+            .acquire(priority)
             .await;
+        if self.is_stopped() {
+            return Err(Error::Cancelled(task_id));
+        }
 
         if self.workspace.force_color {
             env.set_force_color();
@@ -666,52 +1920,107 @@ impl<'a> Inner<'a> {
             env.set_no_color();
         }
 
-        let mut silent = silent_by_default;
+        let mut settings = CommandSettings::new(capture_by_default, attributes);
+        let mut working_dir = self.workspace.project_root().to_path_buf();
+        // Settings to restore at the end of `run` statements with attributes.
+        let mut attribute_stack = Vec::new();
 
         if let Some(delay) = self.workspace.artificial_delay {
             smol::Timer::after(delay).await;
         }
 
-        for (step, run_command) in run_commands.into_iter().enumerate() {
+        let mut step = 0;
+        for (index, run_command) in run_commands.into_iter().enumerate() {
             match run_command {
-                RunCommand::Shell(command_line) => {
+                RunCommand::Shell(ref command_line) => {
                     self.execute_recipe_run_command(
                         task_id,
-                        &command_line,
+                        std::slice::from_ref(command_line),
+                        &Redirection::default(),
                         &env,
-                        silent,
+                        &working_dir,
+                        settings,
                         step,
                         num_steps,
                         forward_stdout,
                     )
-                    .await?;
+                    .await
+                    .map_err(|err| {
+                        source.failed_command(err, index, &run_command, self.workspace)
+                    })?;
+                }
+                RunCommand::Pipeline(ref command_lines, ref redirection) => {
+                    self.execute_recipe_run_command(
+                        task_id,
+                        command_lines,
+                        redirection,
+                        &env,
+                        &working_dir,
+                        settings,
+                        step,
+                        num_steps,
+                        forward_stdout,
+                    )
+                    .await
+                    .map_err(|err| {
+                        source.failed_command(err, index, &run_command, self.workspace)
+                    })?;
                 }
                 RunCommand::Write(path_buf, vec) => {
+                    if self.workspace.create_dirs {
+                        self.workspace.io.create_parent_dirs(&path_buf)?;
+                    }
                     self.workspace.io.write_file(&path_buf, &vec)?;
                 }
                 RunCommand::Copy(from, to) => {
-                    let Some(src_entry) =
-                        self.workspace.get_existing_project_or_output_file(&from)?
-                    else {
-                        return Err(std::io::Error::new(
-                            std::io::ErrorKind::NotFound,
-                            "`copy` source file not found in workspace or output directory",
-                        )
-                        .into());
-                    };
-                    self.workspace.io.copy_file(&src_entry.path, &to)?;
+                    self.execute_recipe_copy_command(&from, &to)?;
+                }
+                RunCommand::Extract(ref archive, ref dir) => {
+                    self.execute_recipe_extract_command(archive, dir)
+                        .map_err(|err| {
+                            source.failed_command(err, index, &run_command, self.workspace)
+                        })?;
+                }
+                RunCommand::Fetch(ref url, ref sha256, ref dest) => {
+                    self.execute_recipe_fetch_command(url, sha256, dest)
+                        .map_err(|err| {
+                            source.failed_command(err, index, &run_command, self.workspace)
+                        })?;
                 }
                 RunCommand::Delete(paths) => {
-                    self.execute_recipe_delete_command(task_id, &paths, silent)?;
+                    self.execute_recipe_delete_command(
+                        task_id,
+                        &paths,
+                        settings.capture || settings.echo == Echo::Never,
+                    )?;
+                }
+                RunCommand::CreateDir(paths) => {
+                    self.execute_recipe_mkdir_command(
+                        task_id,
+                        &paths,
+                        settings.capture || settings.echo == Echo::Never,
+                    )?;
+                }
+                RunCommand::Touch(paths) => {
+                    self.execute_recipe_touch_command(
+                        task_id,
+                        &paths,
+                        settings.capture || settings.echo == Echo::Never,
+                    )?;
                 }
                 RunCommand::Info(message) => {
+                    let message = self.workspace.redact(&message);
                     self.workspace.render.message(Some(task_id), &message);
                 }
                 RunCommand::Warn(message) => {
+                    let message = self.workspace.redact(&message);
                     self.workspace.render.warning(Some(task_id), &message);
                 }
                 RunCommand::SetCapture(value) => {
-                    silent = value;
+                    settings.capture = value;
+                }
+                RunCommand::SetTimeout(value) => {
+                    settings.timeout = value;
                 }
                 RunCommand::SetEnv(key, value) => {
                     env.env(key, value);
@@ -719,7 +2028,23 @@ impl<'a> Inner<'a> {
                 RunCommand::RemoveEnv(key) => {
                     env.env_remove(key);
                 }
+                RunCommand::SetWorkingDir(dir) => {
+                    working_dir = dir;
+                }
+                RunCommand::PushAttributes(attributes) => {
+                    attribute_stack.push(settings);
+                    settings.apply_attributes(attributes);
+                    continue;
+                }
+                RunCommand::PopAttributes => {
+                    // Timeouts are not affected by attributes.
+                    let timeout = settings.timeout;
+                    settings = attribute_stack.pop().expect("unbalanced attributes");
+                    settings.timeout = timeout;
+                    continue;
+                }
             }
+            step += 1;
 
             if let Some(delay) = self.workspace.artificial_delay {
                 smol::Timer::after(delay).await;
@@ -729,62 +2054,455 @@ impl<'a> Inner<'a> {
         Ok(())
     }
 
+    /// Run a single command, or a pipeline of commands. The pipeline counts as
+    /// a single step, and fails if any of its commands fail.
     #[expect(clippy::too_many_arguments)]
     async fn execute_recipe_run_command(
         &self,
         task_id: TaskId,
-        command_line: &ShellCommandLine,
+        command_lines: &[ShellCommandLine],
+        redirection: &Redirection,
         env: &Env,
-        capture: bool,
+        working_dir: &Absolute<std::path::Path>,
+        settings: CommandSettings,
         step: usize,
         num_steps: usize,
         forward_stdout: bool,
     ) -> Result<(), Error> {
-        self.workspace
-            .render
-            .will_execute(task_id, command_line, step, num_steps);
-        let mut child = self.workspace.io.run_recipe_command(
-            command_line,
-            self.workspace.project_root(),
+        // Everything shown to the user has secrets redacted.
+        let shown_lines = command_lines
+            .iter()
+            .map(|command_line| self.workspace.redact_command_line(command_line))
+            .collect::<Vec<_>>();
+        for command_line in &shown_lines {
+            self.workspace.render.will_execute(
+                task_id,
+                command_line,
+                step,
+                num_steps,
+                settings.echo,
+            );
+        }
+        let mut response_files = Vec::new();
+        let spawned_lines = self.write_response_files(
+            task_id,
+            step,
+            command_lines,
+            settings.response_file,
+            &mut response_files,
+        )?;
+        for command_line in spawned_lines.iter() {
+            command_line
+                .check_os_limits()
+                .map_err(|err| Error::CommandLineTooLong(err, None))?;
+        }
+
+        let started = std::time::Instant::now();
+        let max_attempts = settings.retry as usize + 1;
+        let mut attempt = 1;
+        let results = loop {
+            let results = self
+                .spawn_recipe_run_command(
+                    task_id,
+                    &spawned_lines,
+                    redirection,
+                    env,
+                    working_dir,
+                    settings,
+                    forward_stdout,
+                )
+                .await?;
+            let failed = results
+                .iter()
+                .zip(&shown_lines)
+                .find(|(result, _)| !matches!(result, Ok(status) if status.success()));
+            match failed {
+                Some((Ok(status), command_line)) if attempt < max_attempts => {
+                    let delay = retry_delay(attempt);
+                    self.workspace.render.will_retry(
+                        task_id,
+                        command_line,
+                        *status,
+                        attempt,
+                        max_attempts,
+                        delay,
+                    );
+                    smol::Timer::after(delay).await;
+                    attempt += 1;
+                }
+                _ => break results,
+            }
+        };
+        self.metrics.did_run_command(
+            self.metrics_key(task_id),
+            CommandTiming {
+                task: self.task_label(task_id),
+                command: shown_lines
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" | "),
+                duration: started.elapsed(),
+            },
+        );
+
+        for (command_line, result) in shown_lines.iter().zip(&results) {
+            self.workspace
+                .render
+                .did_execute(task_id, command_line, result, step, num_steps);
+        }
+        for result in results {
+            let status = result?;
+            if !status.success() {
+                // Response files are kept for debugging.
+                return Err(Error::CommandFailed(status, None));
+            }
+        }
+        for path in response_files {
+            if let Err(err) = self.workspace.io.delete_file(&path) {
+                tracing::warn!("could not delete response file {}: {err}", path.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// Pass the arguments of command lines that are longer than
+    /// [`COMMAND_LINE_LIMIT`] in response files, unless `response_file` is
+    /// `None`. Returns the command lines to run, and adds the response files
+    /// that were written to `response_files`.
+    fn write_response_files<'b>(
+        &self,
+        task_id: TaskId,
+        step: usize,
+        command_lines: &'b [ShellCommandLine],
+        response_file: ResponseFile,
+        response_files: &mut Vec<Absolute<std::path::PathBuf>>,
+    ) -> Result<std::borrow::Cow<'b, [ShellCommandLine]>, Error> {
+        let is_too_long =
+            |command_line: &ShellCommandLine| command_line.command_line_len() > COMMAND_LINE_LIMIT;
+        if response_file == ResponseFile::None || !command_lines.iter().any(is_too_long) {
+            return Ok(command_lines.into());
+        }
+
+        let mut spawned_lines = command_lines.to_vec();
+        for (index, command_line) in spawned_lines.iter_mut().enumerate() {
+            if !is_too_long(command_line) {
+                continue;
+            }
+            let path = self.workspace.response_file_path(task_id, step, index);
+            self.workspace.io.create_parent_dirs(&path)?;
+            let contents = response_file.contents(&command_line.arguments);
+            self.workspace.io.write_file(&path, contents.as_bytes())?;
+            command_line.arguments = vec![format!("@{}", path.display())];
+            response_files.push(path);
+        }
+        Ok(spawned_lines.into())
+    }
+
+    /// Run the commands once, forwarding their output to the renderer, and
+    /// return the result of each command. The outer error means that the
+    /// commands could not be started, or that they timed out or were
+    /// cancelled.
+    #[expect(clippy::too_many_arguments)]
+    async fn spawn_recipe_run_command(
+        &self,
+        task_id: TaskId,
+        command_lines: &[ShellCommandLine],
+        redirection: &Redirection,
+        env: &Env,
+        working_dir: &Absolute<std::path::Path>,
+        settings: CommandSettings,
+        forward_stdout: bool,
+    ) -> Result<Vec<std::io::Result<std::process::ExitStatus>>, Error> {
+        let CommandSettings {
+            capture,
+            timeout,
+            interactive,
+            ..
+        } = settings;
+        if self.workspace.cancellation.is_cancelled() {
+            return Err(Error::Cancelled(task_id));
+        }
+
+        // Interactive commands run one at a time, while the renderer is
+        // suspended. Pipelines are never interactive.
+        let _terminal = match command_lines {
+            [_] if interactive => Some(self.take_terminal(task_id).await),
+            _ => None,
+        };
+
+        let mut children = self.spawn_children(
+            command_lines,
+            redirection,
             env,
+            working_dir,
+            settings,
             forward_stdout,
         )?;
 
+        let mut interrupt = std::pin::pin!(self.interrupt_recipe_command(task_id, timeout));
+
         // TODO: Avoid this heavy machinery when the renderer isn't
         // interested in the output.
-        let mut reader = ChildLinesStream::new(&mut *child, true);
-        let result = loop {
-            match reader.next().await {
-                Some(Err(err)) => break Err(err),
-                Some(Ok(output)) => match output {
-                    ChildCaptureOutput::Stdout(line) => {
-                        self.workspace.render.on_child_process_stdout_line(
-                            task_id,
-                            command_line,
-                            &line,
-                        );
-                    }
-                    ChildCaptureOutput::Stderr(line) => {
-                        self.workspace.render.on_child_process_stderr_line(
-                            task_id,
-                            command_line,
-                            &line,
-                            capture,
-                        );
-                    }
-                    ChildCaptureOutput::Exit(status) => break Ok(status),
-                },
-                None => panic!("child process stream ended without an exit status"),
+        let shown_lines = command_lines
+            .iter()
+            .map(|command_line| self.workspace.redact_command_line(command_line))
+            .collect::<Vec<_>>();
+        let mut reader =
+            futures::stream::select_all(children.iter_mut().enumerate().map(|(index, child)| {
+                ChildLinesStream::new(&mut **child, true).map(move |next| (index, next))
+            }));
+        let mut results: Vec<Option<std::io::Result<std::process::ExitStatus>>> =
+            command_lines.iter().map(|_| None).collect();
+        let mut remaining = results.len();
+        let interrupted = loop {
+            if remaining == 0 {
+                break None;
+            }
+
+            let next = match futures::future::select(reader.next(), interrupt.as_mut()).await {
+                futures::future::Either::Left((next, _)) => next,
+                futures::future::Either::Right((err, _)) => break Some(err),
+            };
+
+            let Some((index, next)) = next else {
+                panic!("child process stream ended without an exit status");
+            };
+            if results[index].is_some() {
+                // Reading the output failed earlier.
+                continue;
+            }
+            let command_line = &shown_lines[index];
+            match next {
+                Err(err) => {
+                    results[index] = Some(Err(err));
+                    remaining -= 1;
+                }
+                Ok(ChildCaptureOutput::Stdout(line)) => {
+                    let line = self.workspace.redact_bytes(&line);
+                    self.results
+                        .did_output_line(self.metrics_key(task_id), &line, false);
+                    self.workspace.render.on_child_process_stdout_line(
+                        task_id,
+                        command_line,
+                        &line,
+                    );
+                }
+                Ok(ChildCaptureOutput::Stderr(line)) => {
+                    let line = self.workspace.redact_bytes(&line);
+                    self.results
+                        .did_output_line(self.metrics_key(task_id), &line, true);
+                    self.workspace.render.on_child_process_stderr_line(
+                        task_id,
+                        command_line,
+                        &line,
+                        capture,
+                    );
+                }
+                Ok(ChildCaptureOutput::Exit(status)) => {
+                    results[index] = Some(Ok(status));
+                    remaining -= 1;
+                }
             }
         };
+        std::mem::drop(reader);
 
-        self.workspace
-            .render
-            .did_execute(task_id, command_line, &result, step, num_steps);
-        let status = result?;
-        if !status.success() {
-            return Err(Error::CommandFailed(status));
+        if let Some(err) = interrupted {
+            for child in &mut children {
+                if let Err(err) = child.kill() {
+                    tracing::error!("failed to kill interrupted command: {err}");
+                }
+            }
+            return Err(err);
+        }
+        Ok(results
+            .into_iter()
+            .map(|result| result.expect("all commands finished"))
+            .collect())
+    }
+
+    /// Start the commands, connected as a pipeline if there is more than one.
+    fn spawn_children(
+        &self,
+        command_lines: &[ShellCommandLine],
+        redirection: &Redirection,
+        env: &Env,
+        working_dir: &Absolute<std::path::Path>,
+        settings: CommandSettings,
+        forward_stdout: bool,
+    ) -> Result<Vec<Box<dyn crate::Child>>, std::io::Error> {
+        // Recipe commands get their own process group, so anything they spawn
+        // is killed along with them, by a timeout or when werk is interrupted.
+        // Interactive commands stay in the process group of werk, because
+        // they use the terminal.
+        let process_group = true;
+        Ok(match command_lines {
+            [command_line] if settings.interactive => {
+                vec![self.workspace.io.run_interactive_command(
+                    command_line,
+                    working_dir,
+                    env,
+                    redirection,
+                )?]
+            }
+            [command_line] => vec![self.workspace.io.run_recipe_command(
+                command_line,
+                working_dir,
+                env,
+                redirection,
+                forward_stdout,
+                process_group,
+            )?],
+            _ => self.workspace.io.run_recipe_pipeline(
+                command_lines,
+                working_dir,
+                env,
+                redirection,
+                forward_stdout,
+                process_group,
+            )?,
+        })
+    }
+
+    /// Wait until no other interactive command is running, and suspend the
+    /// renderer until the returned guard is dropped.
+    async fn take_terminal(&self, task_id: TaskId) -> TerminalGuard<'a> {
+        let lock = self.workspace.runner_state.terminal.lock().await;
+        self.workspace.render.suspend(task_id);
+        TerminalGuard {
+            render: self.workspace.render,
+            task_id,
+            _lock: lock,
         }
+    }
+
+    /// Resolves with the error to return when running commands must be killed,
+    /// because they timed out or the build was cancelled.
+    async fn interrupt_recipe_command(
+        &self,
+        task_id: TaskId,
+        timeout: Option<std::time::Duration>,
+    ) -> Error {
+        let started = std::time::Instant::now();
+        let deadline = timeout.map(smol::Timer::after);
+        let timed_out = async move {
+            match deadline {
+                Some(deadline) => {
+                    deadline.await;
+                    Error::CommandTimedOut(started.elapsed(), None)
+                }
+                None => std::future::pending().await,
+            }
+        };
+        let cancelled = async move {
+            self.workspace.cancellation.cancelled().await;
+            Error::Cancelled(task_id)
+        };
+        smol::future::or(timed_out, cancelled).await
+    }
+
+    fn execute_recipe_copy_command(
+        &self,
+        from: &Absolute<werk_fs::Path>,
+        to: &Absolute<std::path::Path>,
+    ) -> Result<(), Error> {
+        let Some(src_entry) = self.workspace.get_existing_project_or_output_file(from)? else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "`copy` source file not found in workspace or output directory",
+            )
+            .into());
+        };
+        if self.workspace.create_dirs {
+            self.workspace.io.create_parent_dirs(to)?;
+        }
+        self.workspace.io.copy_file(&src_entry.path, to)?;
+        Ok(())
+    }
+
+    /// Extract an archive, replacing the destination directory, so it contains
+    /// exactly the files in the archive, and is newer than the archive.
+    fn execute_recipe_extract_command(
+        &self,
+        archive: &Absolute<werk_fs::Path>,
+        dir: &Absolute<std::path::Path>,
+    ) -> Result<(), Error> {
+        let Some(archive_entry) = self
+            .workspace
+            .get_existing_project_or_output_file(archive)?
+        else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "`extract` archive not found in workspace or output directory",
+            )
+            .into());
+        };
+        let io = self.workspace.io;
+        match io.metadata(dir) {
+            Ok(metadata) if metadata.is_dir() => io.delete_dir(dir)?,
+            Ok(_) => io.delete_file(dir)?,
+            Err(_) => (),
+        }
+        io.create_dir(dir)?;
+        io.extract_archive(&archive_entry.path, dir)
+            .map_err(|err| Error::ExtractFailed(archive.to_string(), Arc::new(err), None))
+    }
+
+    /// Download a file, unless it already exists with the expected digest. The
+    /// download goes to a `.part` file next to the destination, so the next
+    /// build can resume an interrupted download, and the destination only ever
+    /// has verified contents.
+    fn execute_recipe_fetch_command(
+        &self,
+        url: &str,
+        sha256: &str,
+        dest: &Absolute<std::path::Path>,
+    ) -> Result<(), Error> {
+        let io = self.workspace.io;
+        let digest = |path: &Absolute<std::path::Path>| {
+            io.read_file(path)
+                .map(|data| ir::HashAlgorithm::Sha256.digest(std::slice::from_ref(&data)))
+        };
+        if digest(dest).is_ok_and(|actual| actual == sha256) {
+            return Ok(());
+        }
+
+        let mut file_name = dest.file_name().unwrap_or_default().to_owned();
+        file_name.push(".part");
+        let part = dest
+            .parent()
+            .expect("output file has no parent directory")
+            .join(file_name)
+            .expect("file name is a single path component");
+        if self.workspace.create_dirs {
+            io.create_parent_dirs(&part)?;
+        }
+
+        let download = |resume| {
+            io.download(url, &part, resume)
+                .and_then(|()| digest(&part))
+                .map_err(|err| Error::FetchFailed(url.to_owned(), Arc::new(err), None))
+        };
+        let resumed = io.metadata(&part).is_ok();
+        let mut actual = download(true)?;
+        if actual != sha256 && resumed {
+            // The partial file may be from a different version of the file.
+            actual = download(false)?;
+        }
+        if actual != sha256 {
+            _ = io.delete_file(&part);
+            return Err(Error::ChecksumMismatch(
+                ChecksumMismatch {
+                    url: url.to_owned(),
+                    expected: sha256.to_owned(),
+                    actual,
+                },
+                None,
+            ));
+        }
+
+        io.copy_file(&part, dest)?;
+        io.delete_file(&part)?;
         Ok(())
     }
 
@@ -796,7 +2514,13 @@ impl<'a> Inner<'a> {
     ) -> Result<(), Error> {
         for path in paths {
             if self.workspace.is_in_output_directory(path) {
-                match self.workspace.io.delete_file(path) {
+                let result = match self.workspace.io.metadata(path) {
+                    Ok(metadata) if !metadata.is_file && !metadata.is_symlink => {
+                        self.workspace.io.delete_dir(path)
+                    }
+                    _ => self.workspace.io.delete_file(path),
+                };
+                match result {
                     Ok(()) => (),
                     Err(err) => match err.kind() {
                         std::io::ErrorKind::NotFound => {
@@ -827,6 +2551,62 @@ impl<'a> Inner<'a> {
         Ok(())
     }
 
+    fn execute_recipe_mkdir_command(
+        &self,
+        task_id: TaskId,
+        paths: &[Absolute<std::path::PathBuf>],
+        silent: bool,
+    ) -> Result<(), Error> {
+        for path in paths {
+            if self.workspace.is_in_output_directory(path) {
+                self.workspace.io.create_dir(path)?;
+            } else if !silent {
+                self.workspace.render.warning(
+                    Some(task_id),
+                    &format!(
+                        "cannot `mkdir` path outside of output directory: {}",
+                        path.display()
+                    ),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create or touch the target file of a `#[stamp]` build recipe after its
+    /// commands succeeded.
+    fn touch_stamp_file(&self, target: &Absolute<werk_fs::Path>) -> Result<(), Error> {
+        let path = self.workspace.output_location(target);
+        self.workspace.io.touch(&path).map_err(Into::into)
+    }
+
+    fn execute_recipe_touch_command(
+        &self,
+        task_id: TaskId,
+        paths: &[Absolute<std::path::PathBuf>],
+        silent: bool,
+    ) -> Result<(), Error> {
+        for path in paths {
+            if self.workspace.is_in_output_directory(path) {
+                if self.workspace.create_dirs {
+                    self.workspace.io.create_parent_dirs(path)?;
+                }
+                self.workspace.io.touch(path)?;
+            } else if !silent {
+                self.workspace.render.warning(
+                    Some(task_id),
+                    &format!(
+                        "cannot `touch` path outside of output directory: {}",
+                        path.display()
+                    ),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
     async fn build_dependencies(
         self: &Arc<Self>,
         mut dependencies: Vec<TaskSpec<'a>>,
@@ -875,7 +2655,7 @@ impl<'a> Inner<'a> {
             }
         }
 
-        if dependencies.len() == 1 {
+        let result = if dependencies.len() == 1 {
             let dependency = dependencies.pop().unwrap();
             let this = self.clone();
             // Boxing because of recursion.
@@ -904,7 +2684,13 @@ impl<'a> Inner<'a> {
             .await
         } else {
             Ok(vec![])
+        };
+
+        if result.is_err() {
+            self.results
+                .did_fail_dependency(self.metrics_key(dependent.this));
         }
+        result
     }
 
     /// Unconditionally run the task if it is outdated.
@@ -917,16 +2703,30 @@ impl<'a> Inner<'a> {
         let dep_chain_entry = dep_chain.push(task_id);
 
         match spec {
-            TaskSpec::Recipe(recipe) => match recipe {
-                ir::RecipeMatch::Task(recipe) => {
-                    self.execute_command_recipe(task_id, recipe, dep_chain_entry)
-                        .await
-                }
-                ir::RecipeMatch::Build(recipe_match) => {
-                    self.execute_build_recipe(task_id, recipe_match, dep_chain_entry)
+            TaskSpec::Recipe(recipe) => {
+                let file = recipe.file();
+                let evaluated = self
+                    .evaluate_recipe(task_id, &recipe)
+                    .map_err(|err| err.in_file(file))?;
+                match recipe {
+                    ir::RecipeMatch::Task(recipe) => {
+                        self.execute_command_recipe(task_id, recipe, evaluated, dep_chain_entry)
+                            .await
+                    }
+                    ir::RecipeMatch::Build(recipe_match) => {
+                        // Boxed to keep the size of the futures of all tasks
+                        // down.
+                        Box::pin(self.execute_build_recipe(
+                            task_id,
+                            recipe_match,
+                            evaluated,
+                            dep_chain_entry,
+                        ))
                         .await
+                    }
                 }
-            },
+                .map_err(|err| err.in_file(file))
+            }
             TaskSpec::CheckExists(path) => self.check_exists(&path),
             TaskSpec::CheckExistsRelaxed(path) => match self.check_exists(&path) {
                 Err(Error::NoRuleToBuildTarget(_)) => Ok(BuildStatus::Complete(
@@ -935,36 +2735,262 @@ impl<'a> Inner<'a> {
                 )),
                 otherwise => otherwise,
             },
+            TaskSpec::Member { index, target, .. } => {
+                let result = self.run_member_task(index, &target).await;
+                if result.is_err() {
+                    // The task in the member reports the failure.
+                    self.results.did_fail_dependency(self.metrics_key(task_id));
+                }
+                if let Ok(
+                    BuildStatus::Complete(member_task_id, _)
+                    | BuildStatus::Unchanged(member_task_id, _),
+                ) = result
+                {
+                    self.metrics
+                        .did_finish_alias(self.metrics_key(task_id), (Some(index), member_task_id));
+                }
+                result
+            }
+        }
+    }
+
+    /// Add the task and all of its dependencies to the plan, unless it is
+    /// already there.
+    fn plan_task(
+        &self,
+        spec: TaskSpec<'a>,
+        dep_chain: DepChain<'_>,
+        plan: &mut Plan,
+    ) -> Result<TaskId, Error> {
+        let task_id = spec.to_task_id();
+
+        if dep_chain.contains(task_id) {
+            let dep_chain = dep_chain.push(task_id);
+            return Err(Error::CircularDependency(dep_chain.collect()));
+        }
+
+        if plan.nodes.contains_key(&task_id) {
+            return Ok(task_id);
+        }
+
+        let dep_chain_entry = dep_chain.push(task_id);
+        let node = match spec {
+            TaskSpec::Recipe(recipe) => self
+                .plan_recipe(task_id, &recipe, dep_chain_entry, plan)
+                .map_err(|err| err.in_file(recipe.file()))?,
+            TaskSpec::CheckExists(path) => {
+                let Some(entry) = self.workspace.get_project_file(&path) else {
+                    return Err(Error::NoRuleToBuildTarget(path.to_string()));
+                };
+                PlanNode::source_file(task_id, entry.metadata.mtime)
+            }
+            TaskSpec::CheckExistsRelaxed(path) => match self.workspace.get_project_file(&path) {
+                Some(entry) => PlanNode::source_file(task_id, entry.metadata.mtime),
+                None => PlanNode::missing_file(task_id),
+            },
+            TaskSpec::Member { .. } => PlanNode::member_target(task_id),
+        };
+
+        // Dependencies have been inserted by now, so this maintains
+        // topological order.
+        plan.nodes.insert(task_id, node);
+        Ok(task_id)
+    }
+
+    fn plan_dependencies(
+        &self,
+        specs: Vec<TaskSpec<'a>>,
+        dependent: DepChainEntry<'_>,
+        plan: &mut Plan,
+    ) -> Result<Vec<TaskId>, Error> {
+        let mut dependencies = Vec::with_capacity(specs.len());
+        for spec in specs {
+            let task_id = self.plan_task(spec, DepChain::Ref(&dependent), plan)?;
+            if !dependencies.contains(&task_id) {
+                dependencies.push(task_id);
+            }
+        }
+        Ok(dependencies)
+    }
+
+    /// Plan the recipe of a task, like [`Self::evaluate_recipe()`], and add its
+    /// dependencies to the plan. Dependencies from a depfile that already
+    /// exists are included.
+    fn plan_recipe(
+        &self,
+        task_id: TaskId,
+        recipe: &ir::RecipeMatch<'_>,
+        dep_chain: DepChainEntry<'_>,
+        plan: &mut Plan,
+    ) -> Result<PlanNode, Error> {
+        let EvaluatedRecipe {
+            mut node,
+            mut dependency_specs,
+            depfile,
+            ..
+        } = self.evaluate_recipe(task_id, recipe)?;
+
+        if let Some((path, spec)) = depfile {
+            if let DepfileSpec::Recipe(depfile_recipe_match) = spec {
+                dependency_specs.push(TaskSpec::Recipe(ir::RecipeMatch::Build(
+                    depfile_recipe_match,
+                )));
+            }
+            if let Some(depfile_deps) = self.read_depfile_dependency_specs(&path)? {
+                dependency_specs.extend(depfile_deps);
+            }
         }
+
+        node.dependencies = self.plan_dependencies(dependency_specs, dep_chain, plan)?;
+        Ok(node)
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub(crate) enum RunCommand {
+/// Delay before retrying a failed command: 250ms after the first attempt,
+/// doubling after each subsequent attempt, up to 30s.
+fn retry_delay(attempt: usize) -> std::time::Duration {
+    const INITIAL: std::time::Duration = std::time::Duration::from_millis(250);
+    const MAX: std::time::Duration = std::time::Duration::from_secs(30);
+    let factor = 1u32 << (attempt - 1).min(16);
+    INITIAL.saturating_mul(factor).min(MAX)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RunCommand {
     Shell(ShellCommandLine),
     Write(Absolute<std::path::PathBuf>, Vec<u8>),
     // We don't know yet if the source file is in the workspace or output
     // directory, so we will resolve the path when running it.
     Copy(Absolute<werk_fs::PathBuf>, Absolute<std::path::PathBuf>),
+    /// Extract an archive into a directory, which is always in the output
+    /// directory. Like `Copy`, the archive is resolved when running.
+    Extract(Absolute<werk_fs::PathBuf>, Absolute<std::path::PathBuf>),
+    /// Download a URL to a path in the output directory, verifying its SHA-256
+    /// digest, which is lowercase hex.
+    Fetch(String, String, Absolute<std::path::PathBuf>),
+    /// Shell commands connected by pipes, with the standard input of the first
+    /// and the standard output of the last command optionally redirected to
+    /// files.
+    Pipeline(Vec<ShellCommandLine>, Redirection),
     Info(String),
     Warn(String),
     // Path is always in the output directory. They don't need to exist.
     Delete(Vec<Absolute<std::path::PathBuf>>),
+    /// Create directories and their parents. Paths are always in the output
+    /// directory.
+    CreateDir(Vec<Absolute<std::path::PathBuf>>),
+    /// Create empty files or update their modification time. Paths are always
+    /// in the output directory.
+    Touch(Vec<Absolute<std::path::PathBuf>>),
     SetCapture(bool),
+    /// Kill subsequent commands that run for longer than the duration.
+    SetTimeout(Option<std::time::Duration>),
     SetEnv(String, String),
     RemoveEnv(String),
+    /// Run subsequent commands in the directory, instead of the project root.
+    SetWorkingDir(Absolute<std::path::PathBuf>),
+    /// Apply the attributes of a `run` statement to the following commands,
+    /// until the matching `PopAttributes`.
+    PushAttributes(ir::Attributes),
+    PopAttributes,
+}
+
+/// Owns the terminal while an interactive command runs, and resumes the
+/// renderer when dropped.
+struct TerminalGuard<'a> {
+    render: &'a dyn crate::Render,
+    task_id: TaskId,
+    _lock: smol::lock::MutexGuard<'a, ()>,
+}
+
+impl Drop for TerminalGuard<'_> {
+    fn drop(&mut self) {
+        self.render.resume(self.task_id);
+    }
+}
+
+/// Settings that apply to the commands of a recipe while it runs, changed by
+/// `capture` and `timeout` statements and by attributes.
+#[derive(Clone, Copy)]
+struct CommandSettings {
+    capture: bool,
+    echo: Echo,
+    interactive: bool,
+    retry: u32,
+    timeout: Option<std::time::Duration>,
+    response_file: ResponseFile,
+}
+
+impl CommandSettings {
+    fn new(capture_by_default: bool, attributes: ir::Attributes) -> Self {
+        Self {
+            capture: capture_by_default && !attributes.no_capture,
+            echo: Echo::default().with_attributes(attributes),
+            interactive: attributes.interactive,
+            retry: attributes.retry,
+            timeout: None,
+            response_file: attributes.response_file.unwrap_or_default(),
+        }
+    }
+
+    /// Apply the attributes of a `run` statement.
+    fn apply_attributes(&mut self, attributes: ir::Attributes) {
+        self.capture &= !attributes.no_capture;
+        self.echo = self.echo.with_attributes(attributes);
+        self.interactive |= attributes.interactive;
+        if attributes.retry != 0 {
+            self.retry = attributes.retry;
+        }
+        if let Some(response_file) = attributes.response_file {
+            self.response_file = response_file;
+        }
+    }
+}
+
+impl RunCommand {
+    /// Attribute markers are not steps of their own, so they are not counted
+    /// when reporting progress.
+    fn is_step(&self) -> bool {
+        !matches!(
+            self,
+            RunCommand::PushAttributes(_) | RunCommand::PopAttributes
+        )
+    }
 }
 
 impl std::fmt::Display for RunCommand {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             RunCommand::Shell(shell_command_line) => shell_command_line.fmt(f),
+            RunCommand::Pipeline(command_lines, redirection) => {
+                for (i, command_line) in command_lines.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{command_line}")?;
+                    if i == 0 {
+                        if let Some(ref stdin) = redirection.stdin {
+                            write!(f, " < {}", stdin.display())?;
+                        }
+                    }
+                }
+                if let Some(ref stdout) = redirection.stdout {
+                    write!(f, " > {}", stdout.display())?;
+                }
+                Ok(())
+            }
             RunCommand::Write(path_buf, vec) => {
                 write!(f, "write {} ({} bytes)", path_buf.display(), vec.len())
             }
             RunCommand::Copy(from, to) => {
                 write!(f, "copy '{}' to '{}'", from, to.display())
             }
+            RunCommand::Extract(archive, dir) => {
+                write!(f, "extract '{}' to '{}'", archive, dir.display())
+            }
+            RunCommand::Fetch(url, _, dest) => {
+                write!(f, "fetch '{url}' to '{}'", dest.display())
+            }
             RunCommand::Info(message) => {
                 write!(f, "info \"{}\"", message.escape_default())
             }
@@ -973,23 +2999,43 @@ impl std::fmt::Display for RunCommand {
             }
             RunCommand::Delete(paths) => {
                 write!(f, "delete ")?;
-                if paths.len() == 1 {
-                    write!(f, "{}", paths[0].display())
-                } else {
-                    write!(f, "[")?;
-                    for (i, p) in paths.iter().enumerate() {
-                        if i != 0 {
-                            write!(f, ", ")?;
-                        }
-                        write!(f, "{}", p.display())?;
-                    }
-                    write!(f, "]")
-                }
+                fmt_paths(f, paths)
+            }
+            RunCommand::CreateDir(paths) => {
+                write!(f, "mkdir ")?;
+                fmt_paths(f, paths)
+            }
+            RunCommand::Touch(paths) => {
+                write!(f, "touch ")?;
+                fmt_paths(f, paths)
             }
             RunCommand::SetCapture(value) => write!(f, "set_capture = {value}"),
+            RunCommand::SetTimeout(Some(duration)) => write!(f, "set_timeout = {duration:?}"),
+            RunCommand::SetTimeout(None) => write!(f, "set_timeout = none"),
             RunCommand::SetEnv(key, value) => write!(f, "env {key} = {value}"),
             RunCommand::RemoveEnv(key) => write!(f, "env-remove {key}"),
+            RunCommand::SetWorkingDir(dir) => write!(f, "in-dir {}", dir.display()),
+            RunCommand::PushAttributes(attributes) => write!(f, "push attributes {attributes:?}"),
+            RunCommand::PopAttributes => write!(f, "pop attributes"),
+        }
+    }
+}
+
+fn fmt_paths(
+    f: &mut std::fmt::Formatter<'_>,
+    paths: &[Absolute<std::path::PathBuf>],
+) -> std::fmt::Result {
+    if paths.len() == 1 {
+        write!(f, "{}", paths[0].display())
+    } else {
+        write!(f, "[")?;
+        for (i, p) in paths.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", p.display())?;
         }
+        write!(f, "]")
     }
 }
 
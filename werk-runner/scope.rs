@@ -1,4 +1,7 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use ahash::HashMap;
+use werk_fs::Absolute;
 use werk_util::{Symbol, SymbolRegistryLock};
 
 use crate::{
@@ -17,12 +20,21 @@ pub struct GlobalVar {
 
 pub struct RootScope<'a> {
     pub workspace: &'a Workspace<'a>,
+    /// The namespace of the imported Werkfile being evaluated. Identifiers are
+    /// looked up in this namespace, so imported Werkfiles refer to their own
+    /// globals without qualification.
+    pub namespace: Option<Symbol>,
+    /// The `#[cache]` attributes of the global variable being evaluated, if
+    /// any. See [`Scope::shell_cache()`].
+    pub shell_cache: Option<&'a ir::ShellCache>,
 }
 
 pub struct TaskRecipeScope<'a> {
     parent: &'a RootScope<'a>,
     vars: LocalVariables,
     task_id: TaskId,
+    /// Whether the recipe refers to its temporary directory (`tmp`).
+    uses_tmp_dir: AtomicBool,
 }
 
 pub struct BuildRecipeScope<'a> {
@@ -32,6 +44,8 @@ pub struct BuildRecipeScope<'a> {
     recipe_match: &'a ir::BuildRecipeMatch<'a>,
     input_files: Value,
     output_file: Value,
+    /// Whether the recipe refers to its temporary directory (`tmp`).
+    uses_tmp_dir: AtomicBool,
 }
 
 pub struct SubexprScope<'a> {
@@ -56,9 +70,12 @@ pub enum Lookup {
     Implied,
     /// The stem of the current pattern. `{%}` in string interpolation.
     PatternStem,
-    /// The captured value of a one-of pattern in the current pattern. `{1}` in
-    /// string interpolation.
+    /// The captured value of a one-of pattern or character class in the
+    /// current pattern. `{1}` in string interpolation.
     CaptureGroup(u32),
+    /// The captured value of a named capture `(%name)` in the current
+    /// pattern. `{%name}` in string interpolation.
+    NamedCapture(Symbol),
     /// Lookup by identifier. `{ident}` in string interpolation.
     Ident(Symbol),
     /// The `^` special variable in build recipes. Cannot be shadowed.
@@ -132,18 +149,50 @@ pub trait Scope: Send + Sync {
     fn get(&self, name: Lookup) -> Option<LookupValue<'_>>;
     fn workspace(&self) -> &Workspace;
 
+    /// Collect the identifiers that can be looked up in this scope, innermost
+    /// first. Used to suggest similar names when an identifier is not found.
+    fn visible_names(&self, names: &mut Vec<Symbol>);
+
     fn task_id(&self) -> Option<TaskId>;
     fn render(&self) -> &dyn Render;
+    /// The namespace of the imported Werkfile being evaluated, or `None` for
+    /// the main Werkfile.
+    fn namespace(&self) -> Option<Symbol>;
 
     fn io(&self) -> &dyn Io {
         self.workspace().io()
     }
+
+    /// How the output of `shell` expressions is recorded in `.werk-cache`.
+    /// Only global variables with a `#[cache]` attribute record their output.
+    fn shell_cache(&self) -> Option<&ir::ShellCache> {
+        None
+    }
+
+    /// Whether `run` statements run their commands in the container of a
+    /// build recipe, looking up programs in the image instead of on the host.
+    fn in_container(&self) -> bool {
+        false
+    }
 }
 
 impl<'a> RootScope<'a> {
     #[inline]
     pub fn new(workspace: &'a Workspace) -> Self {
-        Self { workspace }
+        Self {
+            workspace,
+            namespace: None,
+            shell_cache: None,
+        }
+    }
+
+    #[inline]
+    pub fn with_namespace(workspace: &'a Workspace, namespace: Option<Symbol>) -> Self {
+        Self {
+            workspace,
+            namespace,
+            shell_cache: None,
+        }
     }
 }
 
@@ -155,6 +204,7 @@ impl<'a> TaskRecipeScope<'a> {
             parent: root,
             vars: LocalVariables::new(),
             task_id,
+            uses_tmp_dir: AtomicBool::new(false),
         }
     }
 
@@ -164,6 +214,12 @@ impl<'a> TaskRecipeScope<'a> {
         }
         self.vars.insert(name, value);
     }
+
+    /// The temporary directory of the recipe, if the recipe refers to it.
+    #[must_use]
+    pub fn used_tmp_dir(&self) -> Option<Absolute<std::path::PathBuf>> {
+        used_tmp_dir(self.parent.workspace, self.task_id, &self.uses_tmp_dir)
+    }
 }
 
 impl<'a> BuildRecipeScope<'a> {
@@ -180,7 +236,8 @@ impl<'a> BuildRecipeScope<'a> {
             task_id,
             recipe_match,
             input_files: Value::List(Vec::new()),
-            output_file: Value::String(recipe_match.target_file.to_string()),
+            output_file: Value::Path(recipe_match.target_file.to_path_buf()),
+            uses_tmp_dir: AtomicBool::new(false),
         }
     }
 
@@ -191,11 +248,23 @@ impl<'a> BuildRecipeScope<'a> {
         self.vars.insert(name, value);
     }
 
+    /// The temporary directory of the recipe, if the recipe refers to it.
+    #[must_use]
+    pub fn used_tmp_dir(&self) -> Option<Absolute<std::path::PathBuf>> {
+        used_tmp_dir(self.parent.workspace, self.task_id, &self.uses_tmp_dir)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn recipe(&self) -> &ir::BuildRecipe<'a> {
+        self.recipe_match.recipe
+    }
+
     pub fn push_input_file(&mut self, name: String) {
         let Value::List(ref mut input_files) = self.input_files else {
             unreachable!()
         };
-        input_files.push(Value::String(name));
+        input_files.push(input_file_value(name));
     }
 
     pub fn push_input_files(&mut self, names: &[String]) {
@@ -203,7 +272,7 @@ impl<'a> BuildRecipeScope<'a> {
             unreachable!()
         };
         for name in names {
-            input_files.push(Value::String(name.clone()));
+            input_files.push(input_file_value(name.clone()));
         }
     }
 }
@@ -406,6 +475,7 @@ pub fn default_global_constants() -> &'static HashMap<Symbol, Value> {
 pub struct SymCache {
     pub symbol_in: Symbol,
     pub symbol_out: Symbol,
+    pub symbol_tmp: Symbol,
     pub symbol_color: Symbol,
 }
 
@@ -417,6 +487,7 @@ impl SymCache {
             SymCache {
                 symbol_in: sym.insert("in"),
                 symbol_out: sym.insert("out"),
+                symbol_tmp: sym.insert("tmp"),
                 symbol_color: sym.insert("COLOR"),
             }
         })
@@ -430,7 +501,12 @@ impl Scope for RootScope<'_> {
             return None;
         };
 
-        let Some(global) = self.workspace.manifest.globals.get(&name) else {
+        let Some(global) = self
+            .workspace
+            .manifest
+            .globals
+            .get(&crate::qualify(self.namespace, name))
+        else {
             // Global build-time constants.
             if let Some(global_constant) = default_global_constants()
                 .get(&name)
@@ -454,6 +530,26 @@ impl Scope for RootScope<'_> {
         Some(LookupValue::Ref(&global.value.value, &global.value.used))
     }
 
+    fn visible_names(&self, names: &mut Vec<Symbol>) {
+        let prefix = self.namespace.map(|namespace| format!("{namespace}."));
+        names.extend(self.workspace.manifest.globals.keys().filter_map(|name| {
+            let name = match prefix {
+                Some(ref prefix) => name.as_str().strip_prefix(prefix.as_str())?,
+                None => name.as_str(),
+            };
+            // Globals of imported Werkfiles are not visible unqualified.
+            (!name.contains('.')).then(|| Symbol::new(name))
+        }));
+
+        let mut constants = default_global_constants()
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        constants.sort_unstable_by_key(|name| name.as_str());
+        names.extend(constants);
+        names.push(SymCache::get().symbol_color);
+    }
+
     #[inline]
     fn workspace(&self) -> &Workspace {
         self.workspace
@@ -468,6 +564,16 @@ impl Scope for RootScope<'_> {
     fn render(&self) -> &dyn Render {
         self.workspace.render
     }
+
+    #[inline]
+    fn namespace(&self) -> Option<Symbol> {
+        self.namespace
+    }
+
+    #[inline]
+    fn shell_cache(&self) -> Option<&ir::ShellCache> {
+        self.shell_cache
+    }
 }
 
 impl Scope for TaskRecipeScope<'_> {
@@ -478,12 +584,25 @@ impl Scope for TaskRecipeScope<'_> {
         };
 
         let Some(local) = self.vars.get(&name) else {
-            return self.parent.get(lookup);
+            return self.parent.get(lookup).or_else(|| {
+                tmp_dir_lookup(
+                    self.parent.workspace,
+                    self.task_id,
+                    &self.uses_tmp_dir,
+                    name,
+                )
+            });
         };
 
         Some(LookupValue::Ref(&local.value, &local.used))
     }
 
+    fn visible_names(&self, names: &mut Vec<Symbol>) {
+        names.extend(self.vars.keys().rev().copied());
+        names.push(SymCache::get().symbol_tmp);
+        self.parent.visible_names(names);
+    }
+
     #[inline]
     fn workspace(&self) -> &Workspace {
         self.parent.workspace
@@ -498,6 +617,51 @@ impl Scope for TaskRecipeScope<'_> {
     fn render(&self) -> &dyn Render {
         self.parent.workspace.render
     }
+
+    #[inline]
+    fn namespace(&self) -> Option<Symbol> {
+        self.parent.namespace
+    }
+}
+
+/// Look up the temporary directory of a recipe (`tmp`), which comes after
+/// variables with the same name, and remember that it was used.
+fn tmp_dir_lookup(
+    workspace: &Workspace,
+    task_id: TaskId,
+    uses_tmp_dir: &AtomicBool,
+    name: Symbol,
+) -> Option<LookupValue<'static>> {
+    if name != SymCache::get().symbol_tmp {
+        return None;
+    }
+    uses_tmp_dir.store(true, Ordering::Relaxed);
+    let dir = workspace.recipe_tmp_dir(task_id);
+    Some(LookupValue::Owned(Eval::inherent(Value::String(
+        dir.display().to_string(),
+    ))))
+}
+
+fn used_tmp_dir(
+    workspace: &Workspace,
+    task_id: TaskId,
+    uses_tmp_dir: &AtomicBool,
+) -> Option<Absolute<std::path::PathBuf>> {
+    uses_tmp_dir
+        .load(Ordering::Relaxed)
+        .then(|| workspace.recipe_tmp_dir(task_id))
+}
+
+/// Input files that are absolute workspace paths become path values. Other
+/// dependencies, like task names, stay strings.
+fn input_file_value(name: String) -> Value {
+    match werk_fs::Path::new(&name) {
+        Ok(path) if path.is_absolute() => match path.normalize() {
+            Ok(path) => Value::Path(path.into_owned()),
+            Err(_) => Value::String(name),
+        },
+        _ => Value::String(name),
+    }
 }
 
 impl Scope for BuildRecipeScope<'_> {
@@ -517,6 +681,12 @@ impl Scope for BuildRecipeScope<'_> {
                     group.to_owned(),
                 ))))
             }
+            Lookup::NamedCapture(name) => {
+                let capture = self.recipe_match.match_data.named_capture(name)?;
+                Some(LookupValue::Owned(Eval::inherent(Value::String(
+                    capture.to_owned(),
+                ))))
+            }
             Lookup::InputFile => Some(LookupValue::ValueRef(Eval::inherent(&self.input_files))),
             Lookup::OutputFile => Some(LookupValue::ValueRef(Eval::inherent(&self.output_file))),
             Lookup::Ident(name) => {
@@ -528,13 +698,31 @@ impl Scope for BuildRecipeScope<'_> {
                 }
 
                 let Some(local) = self.vars.get(&name) else {
-                    return self.parent.get(lookup);
+                    return self.parent.get(lookup).or_else(|| {
+                        tmp_dir_lookup(
+                            self.parent.workspace,
+                            self.task_id,
+                            &self.uses_tmp_dir,
+                            name,
+                        )
+                    });
                 };
                 Some(LookupValue::EvalRef(local))
             }
         }
     }
 
+    fn visible_names(&self, names: &mut Vec<Symbol>) {
+        let sym_cache = SymCache::get();
+        names.extend(self.vars.keys().rev().copied());
+        names.extend([
+            sym_cache.symbol_in,
+            sym_cache.symbol_out,
+            sym_cache.symbol_tmp,
+        ]);
+        self.parent.visible_names(names);
+    }
+
     #[inline]
     fn workspace(&self) -> &Workspace {
         self.parent.workspace
@@ -549,6 +737,16 @@ impl Scope for BuildRecipeScope<'_> {
     fn render(&self) -> &dyn Render {
         self.parent.workspace.render
     }
+
+    #[inline]
+    fn namespace(&self) -> Option<Symbol> {
+        self.parent.namespace
+    }
+
+    #[inline]
+    fn in_container(&self) -> bool {
+        self.recipe_match.recipe.container
+    }
 }
 
 impl Scope for SubexprScope<'_> {
@@ -560,6 +758,11 @@ impl Scope for SubexprScope<'_> {
         }
     }
 
+    #[inline]
+    fn visible_names(&self, names: &mut Vec<Symbol>) {
+        self.parent.visible_names(names);
+    }
+
     #[inline]
     fn workspace(&self) -> &Workspace {
         self.parent.workspace()
@@ -574,6 +777,21 @@ impl Scope for SubexprScope<'_> {
     fn render(&self) -> &dyn Render {
         self.parent.render()
     }
+
+    #[inline]
+    fn namespace(&self) -> Option<Symbol> {
+        self.parent.namespace()
+    }
+
+    #[inline]
+    fn shell_cache(&self) -> Option<&ir::ShellCache> {
+        self.parent.shell_cache()
+    }
+
+    #[inline]
+    fn in_container(&self) -> bool {
+        self.parent.in_container()
+    }
 }
 
 impl Scope for MatchScope<'_> {
@@ -592,11 +810,22 @@ impl Scope for MatchScope<'_> {
                     group.to_owned(),
                 ))))
             }
+            Lookup::NamedCapture(name) => {
+                let capture = self.pattern_match.named_capture(name)?;
+                Some(LookupValue::Owned(Eval::inherent(Value::String(
+                    capture.to_owned(),
+                ))))
+            }
             Lookup::Implied => Some(LookupValue::EvalRef(self.implied_value)),
             _ => self.parent.get(lookup),
         }
     }
 
+    #[inline]
+    fn visible_names(&self, names: &mut Vec<Symbol>) {
+        self.parent.visible_names(names);
+    }
+
     #[inline]
     fn workspace(&self) -> &Workspace {
         self.parent.workspace()
@@ -611,4 +840,19 @@ impl Scope for MatchScope<'_> {
     fn render(&self) -> &dyn Render {
         self.parent.render()
     }
+
+    #[inline]
+    fn namespace(&self) -> Option<Symbol> {
+        self.parent.namespace()
+    }
+
+    #[inline]
+    fn shell_cache(&self) -> Option<&ir::ShellCache> {
+        self.parent.shell_cache()
+    }
+
+    #[inline]
+    fn in_container(&self) -> bool {
+        self.parent.in_container()
+    }
 }
@@ -0,0 +1,429 @@
+use std::sync::Arc;
+
+use futures::future::LocalBoxFuture;
+use werk_fs::Absolute;
+use werk_util::{Diagnostic, DiagnosticError, DiagnosticFileRepository};
+
+use crate::{
+    ir, is_target_glob, BuildStatus, Error, ImportSources, Io, Plan, Render, Runner, Workspace,
+    WorkspaceSettings,
+};
+
+/// Cancels builds from outside of the runner, for example when a tool
+/// embedding werk is shutting down. Clones of a token share its state.
+///
+/// Once cancelled, running commands are killed and fail with
+/// [`Error::Cancelled`], and no new commands are started.
+#[derive(Clone, Debug)]
+pub struct CancellationToken {
+    sender: smol::channel::Sender<()>,
+    receiver: smol::channel::Receiver<()>,
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancellationToken {
+    #[must_use]
+    pub fn new() -> Self {
+        // Nothing is ever sent; closing the channel wakes up all receivers.
+        let (sender, receiver) = smol::channel::bounded(1);
+        Self { sender, receiver }
+    }
+
+    /// Cancel all builds using this token. This cannot be undone.
+    pub fn cancel(&self) {
+        self.sender.close();
+    }
+
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// Wait until the token is cancelled.
+    pub async fn cancelled(&self) {
+        _ = self.receiver.recv().await;
+    }
+}
+
+/// Results of searching `PATH` for programs, including programs that were not
+/// found. Clones share the results, so all workspaces created from the same
+/// [`WorkspaceSettings`], like the builds of a [`Session`], search `PATH` at
+/// most once per program.
+#[derive(Clone, Debug, Default)]
+pub struct WhichCache {
+    lookups: Arc<parking_lot::Mutex<ahash::HashMap<String, WhichResult>>>,
+}
+
+type WhichResult = Result<Absolute<std::path::PathBuf>, which::Error>;
+
+impl WhichCache {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget all results, so programs are searched for again. Useful when
+    /// `PATH` or the programs in it may have changed.
+    pub fn clear(&self) {
+        self.lookups.lock().clear();
+    }
+
+    pub(crate) fn which(&self, io: &dyn Io, command: &str) -> WhichResult {
+        if let Some(result) = self.lookups.lock().get(command) {
+            return result.clone();
+        }
+        let result = io.which(command);
+        self.lookups
+            .lock()
+            .insert(command.to_owned(), result.clone());
+        result
+    }
+}
+
+/// Notifies a [`Session`] when files change, driving [`Session::watch()`].
+/// The `werk` binary watches the filesystem, but embedding tools may use
+/// their own file watching, or trigger rebuilds in other ways.
+pub trait Watcher {
+    /// Wait until any of `paths` changes. These are the Werkfile, the
+    /// Werkfiles it imports, and the source files in the workspace. Returning
+    /// an error stops watching.
+    fn wait_for_change<'w>(
+        &'w mut self,
+        paths: &'w [Absolute<std::path::PathBuf>],
+    ) -> LocalBoxFuture<'w, std::io::Result<()>>;
+}
+
+/// The stage in which a [`Session`] failed.
+#[derive(Debug, Clone)]
+pub enum SessionErrorKind {
+    /// Reading the Werkfile, or writing the `.werk-cache`, failed.
+    Io,
+    /// A Werkfile could not be parsed.
+    Parse,
+    /// Loading imported Werkfiles, or evaluating `config` statements, failed.
+    Eval,
+    /// Evaluating the workspace, or building a target, failed.
+    Runner(Error),
+}
+
+/// An error from a [`Session`].
+///
+/// The diagnostic is rendered (without color) when the error occurs, because
+/// it refers to the source code of the Werkfile, which may change before the
+/// error is reported.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{diagnostic}")]
+pub struct SessionError {
+    pub kind: SessionErrorKind,
+    pub diagnostic: String,
+}
+
+impl SessionError {
+    fn parse<E: Diagnostic, R: DiagnosticFileRepository>(err: &DiagnosticError<'_, E, R>) -> Self {
+        Self {
+            kind: SessionErrorKind::Parse,
+            diagnostic: err.to_string(),
+        }
+    }
+
+    fn eval<E: Diagnostic, R: DiagnosticFileRepository>(err: &DiagnosticError<'_, E, R>) -> Self {
+        Self {
+            kind: SessionErrorKind::Eval,
+            diagnostic: err.to_string(),
+        }
+    }
+
+    fn runner<R: DiagnosticFileRepository>(err: DiagnosticError<'_, Error, R>) -> Self {
+        Self {
+            diagnostic: err.to_string(),
+            kind: SessionErrorKind::Runner(err.error),
+        }
+    }
+
+    fn io(path: &std::path::Path, err: &std::io::Error) -> Self {
+        Self {
+            kind: SessionErrorKind::Io,
+            diagnostic: format!("error accessing `{}`: {err}", path.display()),
+        }
+    }
+
+    /// True when, even though an error occurred, the `.werk-cache` file should
+    /// still be written. See [`Error::should_still_write_werk_cache()`].
+    #[must_use]
+    pub fn should_still_write_werk_cache(&self) -> bool {
+        match self.kind {
+            SessionErrorKind::Runner(ref err) => err.should_still_write_werk_cache(),
+            _ => false,
+        }
+    }
+}
+
+/// A Werkfile loaded by a tool embedding werk, like an IDE or a CI wrapper.
+///
+/// Workspaces borrow the source code of the Werkfile, so the session owns the
+/// source code, and workspaces are evaluated on demand by
+/// [`with_workspace()`](Self::with_workspace). Call
+/// [`reload()`](Self::reload) to pick up changes to the Werkfile. Imported
+/// Werkfiles are read again each time a workspace is evaluated.
+///
+/// The `config` statements of the Werkfile are applied to the settings of the
+/// session, except `out-dir`. Workspace members are not loaded.
+pub struct Session {
+    io: Arc<dyn Io>,
+    render: Arc<dyn Render>,
+    werkfile: Absolute<std::path::PathBuf>,
+    project_root: Absolute<std::path::PathBuf>,
+    settings: WorkspaceSettings,
+    source: String,
+}
+
+impl Session {
+    /// Read the Werkfile at `werkfile`. The directory containing it is the
+    /// workspace directory.
+    pub fn open(
+        io: Arc<dyn Io>,
+        render: Arc<dyn Render>,
+        werkfile: Absolute<std::path::PathBuf>,
+        settings: WorkspaceSettings,
+    ) -> Result<Self, SessionError> {
+        let source = read_werkfile(&*io, &werkfile)?;
+        let project_root = werkfile
+            .parent()
+            .expect("Werkfile path has no parent directory")
+            .to_path_buf();
+        Ok(Self {
+            io,
+            render,
+            werkfile,
+            project_root,
+            settings,
+            source,
+        })
+    }
+
+    /// Read the Werkfile again. Returns true if it changed.
+    pub fn reload(&mut self) -> Result<bool, SessionError> {
+        let source = read_werkfile(&*self.io, &self.werkfile)?;
+        if source == self.source {
+            return Ok(false);
+        }
+        self.source = source;
+        Ok(true)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn werkfile(&self) -> &Absolute<std::path::Path> {
+        &self.werkfile
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn settings(&self) -> &WorkspaceSettings {
+        &self.settings
+    }
+
+    /// The settings used for workspaces evaluated after this call, like
+    /// `--define` overrides and forced targets.
+    #[inline]
+    pub fn settings_mut(&mut self) -> &mut WorkspaceSettings {
+        &mut self.settings
+    }
+
+    /// The token that cancels builds in this session.
+    #[must_use]
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.settings.cancellation.clone()
+    }
+
+    /// Parse the Werkfile and its imports, evaluate the workspace, and pass it
+    /// to `f`. The future returned by `f` may borrow the workspace, but not
+    /// the caller's scope, so move any values it needs into it.
+    ///
+    /// The `.werk-cache` is not written automatically; call
+    /// [`Workspace::finalize()`] when done.
+    pub async fn with_workspace<T>(
+        &self,
+        f: impl for<'w> FnOnce(&'w Workspace<'w>) -> LocalBoxFuture<'w, T>,
+    ) -> Result<T, SessionError> {
+        let mut ast = werk_parser::parse_werk_with_diagnostics(&self.werkfile, &self.source)
+            .map_err(|err| SessionError::parse(&err))?;
+        let import_sources = ImportSources::load(&*self.io, &ast, &self.project_root)
+            .map_err(|err| SessionError::eval(&err))?;
+        ast.imports = import_sources
+            .parse()
+            .map_err(|err| SessionError::parse(&err))?;
+
+        let config =
+            ir::Config::new_with_diagnostics(&ast).map_err(|err| SessionError::eval(&err))?;
+        let mut settings = self.settings.clone();
+        settings.apply_config(&config);
+
+        let workspace = Workspace::new_with_diagnostics(
+            &ast,
+            &*self.io,
+            &*self.render,
+            self.project_root.clone(),
+            &settings,
+        )
+        .map_err(SessionError::runner)?;
+        Ok(f(&workspace).await)
+    }
+
+    /// Build or run `targets` in order, like the `werk` command line, and
+    /// write the `.werk-cache`. Targets may be globs, like `assets/*.ktx`.
+    /// Stops at the first target that fails.
+    pub async fn build(&self, targets: &[&str]) -> Result<Vec<BuildStatus>, SessionError> {
+        let targets = targets.iter().map(|&target| target.to_owned()).collect();
+        self.with_workspace(|workspace| Box::pin(build_targets(workspace, targets)))
+            .await?
+    }
+
+    /// Resolve the build plan for `target`, without running any recipe
+    /// commands. See [`Runner::plan()`].
+    pub async fn plan(&self, target: &str) -> Result<Plan, SessionError> {
+        let target = target.to_owned();
+        self.with_workspace(|workspace| {
+            Box::pin(async move {
+                Runner::new(workspace)
+                    .plan(&target)
+                    .map_err(SessionError::runner)
+            })
+        })
+        .await?
+    }
+
+    /// Build `targets`, then build them again whenever `watcher` reports a
+    /// change, reloading the Werkfile first. The result of each build is
+    /// passed to `on_build`. Returns when the session is cancelled, or when
+    /// `watcher` fails.
+    pub async fn watch(
+        &mut self,
+        targets: &[&str],
+        watcher: &mut dyn Watcher,
+        mut on_build: impl FnMut(Result<Vec<BuildStatus>, SessionError>),
+    ) -> Result<(), SessionError> {
+        let cancellation = self.cancellation_token();
+        // When the workspace cannot be evaluated, keep watching the files
+        // from the previous build, which include the Werkfile.
+        let mut paths = vec![self.werkfile.clone()];
+        let mut result = self.build_and_collect_paths(targets, &mut paths).await;
+
+        loop {
+            on_build(result);
+            if cancellation.is_cancelled() {
+                return Ok(());
+            }
+
+            let changed = smol::future::or(
+                async { Some(watcher.wait_for_change(&paths).await) },
+                async {
+                    cancellation.cancelled().await;
+                    None
+                },
+            );
+            match changed.await {
+                Some(Ok(())) => (),
+                Some(Err(err)) => return Err(SessionError::io(&self.werkfile, &err)),
+                None => return Ok(()),
+            }
+
+            // Reset any progress indicators between runs.
+            self.render.reset();
+
+            result = match self.reload() {
+                Ok(_) => self.build_and_collect_paths(targets, &mut paths).await,
+                Err(err) => Err(err),
+            };
+        }
+    }
+
+    async fn build_and_collect_paths(
+        &self,
+        targets: &[&str],
+        paths: &mut Vec<Absolute<std::path::PathBuf>>,
+    ) -> Result<Vec<BuildStatus>, SessionError> {
+        let werkfile = self.werkfile.clone();
+        let targets = targets.iter().map(|&target| target.to_owned()).collect();
+        let (result, new_paths) = self
+            .with_workspace(|workspace| {
+                Box::pin(async move {
+                    let mut paths = vec![werkfile];
+                    paths.extend(workspace.imports.iter().filter_map(|import| {
+                        Absolute::new_ref(import.document.origin)
+                            .ok()
+                            .map(ToOwned::to_owned)
+                    }));
+                    paths.extend(
+                        workspace
+                            .workspace_files()
+                            .filter(|(_, entry)| entry.metadata.is_file)
+                            .map(|(_, entry)| entry.path.clone()),
+                    );
+                    (build_targets(workspace, targets).await, paths)
+                })
+            })
+            .await?;
+        *paths = new_paths;
+        result
+    }
+}
+
+fn read_werkfile(
+    io: &dyn Io,
+    werkfile: &Absolute<std::path::Path>,
+) -> Result<String, SessionError> {
+    io.read_file(werkfile)
+        .and_then(|data| {
+            String::from_utf8(data)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+        })
+        .map_err(|err| SessionError::io(werkfile, &err))
+}
+
+async fn build_targets<'w>(
+    workspace: &'w Workspace<'w>,
+    targets: Vec<String>,
+) -> Result<Vec<BuildStatus>, SessionError> {
+    let runner = Runner::new(workspace);
+    let mut statuses = Vec::with_capacity(targets.len());
+    let mut result = Ok(());
+    for target in &targets {
+        let status = if is_target_glob(target) {
+            runner.build_glob(target).await
+        } else {
+            runner.build_or_run(target).await.map(|status| vec![status])
+        };
+        match status {
+            Ok(status) => statuses.extend(status),
+            Err(err) => {
+                result = Err(SessionError::runner(err));
+                break;
+            }
+        }
+    }
+    std::mem::drop(runner);
+
+    let write_cache = match result {
+        Ok(()) => true,
+        Err(ref err) => err.should_still_write_werk_cache(),
+    };
+    if write_cache {
+        workspace
+            .finalize()
+            .await
+            .map_err(|err| SessionError::io(workspace.output_directory(), &err))?;
+    }
+    result.map(|()| statuses)
+}
@@ -1,15 +1,16 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
     ffi::{OsStr, OsString},
+    fmt::Write as _,
 };
 
 use werk_fs::Absolute;
-use werk_parser::parser::Span;
+use werk_parser::{ast::QuoteStyle, parser::Span};
 use werk_util::Symbol;
 
-use crate::{eval::UsedVariable, EvalError, Value, Workspace};
+use crate::{eval::UsedVariable, EvalError, Io, Value, Workspace};
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct ShellCommandLine {
     /// The name of the program to run. Should be an absolute path, either from
     /// a `which` expression or an `<var>` interpolation when running an
@@ -43,6 +44,274 @@ impl std::fmt::Debug for ShellCommandLine {
     }
 }
 
+/// The longest command line that is passed to a program directly when the
+/// `response-file` attribute is set. Longer command lines pass their arguments
+/// in a response file instead. Windows limits command lines to 32767
+/// characters, and Linux limits single arguments to 128 KiB.
+pub const COMMAND_LINE_LIMIT: usize = if cfg!(windows) { 32_000 } else { 128 * 1024 };
+
+impl ShellCommandLine {
+    /// The length of the command line, counting a separator between the
+    /// program and each argument.
+    #[must_use]
+    pub fn command_line_len(&self) -> usize {
+        self.program.as_os_str().len()
+            + self
+                .arguments
+                .iter()
+                .map(|arg| arg.len() + 1)
+                .sum::<usize>()
+    }
+
+    /// Check the command line against the limits of the OS, which would
+    /// otherwise fail to spawn the program with an unhelpful error, like
+    /// `E2BIG` on Unix.
+    pub fn check_os_limits(&self) -> Result<(), CommandLineTooLong> {
+        let len = self.command_line_len();
+        if len > OS_COMMAND_LINE_MAX {
+            return Err(CommandLineTooLong::CommandLine {
+                len,
+                limit: OS_COMMAND_LINE_MAX,
+            });
+        }
+        if let Some(limit) = OS_ARGUMENT_MAX {
+            if let Some(len) = self
+                .arguments
+                .iter()
+                .map(String::len)
+                .find(|&len| len > limit)
+            {
+                return Err(CommandLineTooLong::Argument { len, limit });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The longest command line that the OS can spawn. Windows limits the whole
+/// command line to 32767 characters, and Unix limits the arguments and the
+/// environment together to `ARG_MAX`, which is 1 MiB on macOS and typically
+/// 2 MiB on Linux. The environment is not counted.
+const OS_COMMAND_LINE_MAX: usize = if cfg!(windows) {
+    32_767
+} else if cfg!(target_os = "macos") {
+    1024 * 1024
+} else {
+    2 * 1024 * 1024
+};
+
+/// The longest single argument that the OS can spawn. Linux limits each
+/// argument to 128 KiB, including the terminating NUL.
+const OS_ARGUMENT_MAX: Option<usize> = if cfg!(target_os = "linux") {
+    Some(128 * 1024 - 1)
+} else {
+    None
+};
+
+/// A command line that is too long for the OS to spawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum CommandLineTooLong {
+    #[error("command line is too long ({len} bytes, the limit is {limit})")]
+    CommandLine { len: usize, limit: usize },
+    #[error("command line argument is too long ({len} bytes, the limit is {limit})")]
+    Argument { len: usize, limit: usize },
+}
+
+/// How the arguments of a long command line are written to a response file,
+/// selected with `#[response-file = "..."]` on a recipe or `run` statement.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ResponseFile {
+    /// `"none"`: Always pass the arguments on the command line.
+    #[default]
+    None,
+    /// `"gnu"`: One argument per line, with whitespace, quotes, and
+    /// backslashes escaped by a backslash, as read by GCC, Clang, and GNU
+    /// binutils.
+    Gnu,
+    /// `"msvc"`: One argument per line, quoted like a Windows command line, as
+    /// read by MSVC and `clang-cl`.
+    Msvc,
+}
+
+impl ResponseFile {
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(ResponseFile::None),
+            "gnu" => Some(ResponseFile::Gnu),
+            "msvc" => Some(ResponseFile::Msvc),
+            _ => None,
+        }
+    }
+
+    /// The contents of a response file passing `arguments`.
+    #[must_use]
+    pub fn contents(self, arguments: &[String]) -> String {
+        let mut contents = String::new();
+        for arg in arguments {
+            match self {
+                ResponseFile::None => contents.push_str(arg),
+                ResponseFile::Gnu => gnu_quote(arg, &mut contents),
+                ResponseFile::Msvc => msvc_quote(arg, &mut contents),
+            }
+            contents.push('\n');
+        }
+        contents
+    }
+}
+
+fn gnu_quote(arg: &str, out: &mut String) {
+    if arg.is_empty() {
+        out.push_str("\"\"");
+    }
+    for ch in arg.chars() {
+        if ch.is_whitespace() || matches!(ch, '\\' | '"' | '\'') {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+}
+
+/// Quote an argument so `CommandLineToArgvW` reads it back unchanged.
+/// Backslashes are only special before a quote.
+fn msvc_quote(arg: &str, out: &mut String) {
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || c == '"') {
+        out.push_str(arg);
+        return;
+    }
+    out.push('"');
+    let mut backslashes = 0;
+    for ch in arg.chars() {
+        match ch {
+            '\\' => backslashes += 1,
+            '"' => {
+                // Escape the backslashes before the quote, and the quote.
+                out.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                out.extend(std::iter::repeat_n('\\', backslashes));
+                backslashes = 0;
+            }
+        }
+        if ch != '\\' {
+            out.push(ch);
+        }
+    }
+    // The closing quote must not be escaped by trailing backslashes.
+    out.extend(std::iter::repeat_n('\\', backslashes * 2));
+    out.push('"');
+}
+
+/// Escape a string for use in another language, for the `quote-sh`,
+/// `quote-cmd`, `quote-regex`, and `quote-c` interpolation operations.
+#[must_use]
+pub fn quote(style: QuoteStyle, s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    match style {
+        QuoteStyle::Sh => sh_quote(s, &mut out),
+        QuoteStyle::Cmd => cmd_quote(s, &mut out),
+        QuoteStyle::Regex => out.push_str(&regex::escape(s)),
+        QuoteStyle::C => c_quote(s, &mut out),
+    }
+    out
+}
+
+/// Quote a word for a POSIX shell. Words that only contain characters that
+/// are never special are left alone, and everything else is single-quoted.
+fn sh_quote(arg: &str, out: &mut String) {
+    let is_safe = |ch: char| {
+        ch.is_ascii_alphanumeric()
+            || matches!(
+                ch,
+                '_' | '@' | '%' | '+' | '=' | ':' | ',' | '.' | '/' | '-'
+            )
+    };
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        out.push_str(arg);
+        return;
+    }
+    out.push('\'');
+    for ch in arg.chars() {
+        if ch == '\'' {
+            // Close the quotes, add an escaped quote, and reopen them.
+            out.push_str("'\\''");
+        } else {
+            out.push(ch);
+        }
+    }
+    out.push('\'');
+}
+
+/// Quote an argument on a command line that is interpreted by `cmd.exe`
+/// before the program sees it. The argument is first quoted for
+/// `CommandLineToArgvW`, and then every character that `cmd.exe` treats
+/// specially is escaped with `^`, including the quotes, so it never matters
+/// whether `cmd.exe` thinks it is inside quotes.
+fn cmd_quote(arg: &str, out: &mut String) {
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    msvc_quote(arg, &mut quoted);
+    for ch in quoted.chars() {
+        if matches!(
+            ch,
+            '(' | ')' | '%' | '!' | '^' | '"' | '<' | '>' | '&' | '|'
+        ) {
+            out.push('^');
+        }
+        out.push(ch);
+    }
+}
+
+/// Quote a C string literal. Control characters are written as octal
+/// escapes, which unlike hex escapes can't swallow the following characters.
+fn c_quote(s: &str, out: &mut String) {
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if ch.is_ascii_control() => {
+                _ = write!(out, "\\{:03o}", u32::from(ch));
+            }
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+}
+
+/// Files that the standard input and output of a recipe command are
+/// redirected to, like `run "gzip -c" < "{in}" > "{out}"`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Redirection {
+    /// Read standard input from this file.
+    pub stdin: Option<Absolute<std::path::PathBuf>>,
+    /// Write standard output to this file, replacing its contents.
+    pub stdout: Option<Absolute<std::path::PathBuf>>,
+}
+
+impl Redirection {
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.stdin.is_none() && self.stdout.is_none()
+    }
+}
+
+impl std::fmt::Display for Redirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(ref stdin) = self.stdin {
+            write!(f, " < {}", stdin.display())?;
+        }
+        if let Some(ref stdout) = self.stdout {
+            write!(f, " > {}", stdout.display())?;
+        }
+        Ok(())
+    }
+}
+
 impl std::fmt::Display for ShellCommandLineBuilder {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut parts = self.parts.iter();
@@ -176,6 +445,16 @@ impl ShellCommandLineBuilder {
         self
     }
 
+    /// Start from a list of arguments that are already separated, like the
+    /// strings of an `args` expression. The first argument is the program.
+    #[must_use]
+    pub fn from_args(args: Vec<String>) -> Self {
+        Self {
+            parts: args,
+            ..Self::default()
+        }
+    }
+
     pub fn build(
         &mut self,
         span: Span,
@@ -203,16 +482,94 @@ impl ShellCommandLineBuilder {
             ))
         }
     }
+
+    /// Build a command line that runs in a container. Programs given by name
+    /// are not looked up on the host, but in the `PATH` of the image, by
+    /// running them with [`CONTAINER_ENV_PROGRAM`].
+    pub fn build_in_container(
+        &mut self,
+        span: Span,
+        workspace: &Workspace,
+    ) -> Result<(ShellCommandLine, Option<UsedVariable>), EvalError> {
+        match self.parts.first() {
+            Some(program)
+                if !program.contains(std::path::is_separator) && self.in_quotes.is_none() =>
+            {
+                let program = Absolute::new(std::path::PathBuf::from(CONTAINER_ENV_PROGRAM))
+                    .map_err(|_| EvalError::ContainerUnsupported(span))?;
+                Ok((
+                    ShellCommandLine {
+                        program,
+                        arguments: self.parts.drain(..).collect(),
+                    },
+                    None,
+                ))
+            }
+            _ => self.build(span, workspace),
+        }
+    }
 }
 
-#[derive(Default, Debug, Clone, PartialEq)]
+/// The program that looks up the programs of commands running in containers
+/// in the `PATH` of the image.
+pub const CONTAINER_ENV_PROGRAM: &str = "/usr/bin/env";
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Env {
     pub env: BTreeMap<OsString, OsString>,
     pub env_remove: BTreeSet<OsString>,
+    /// Start the child process with an empty environment instead of
+    /// inheriting the environment of werk.
+    pub clear: bool,
 }
 
+/// Environment variables that recipe commands inherit in reproducible mode.
+/// Everything else is removed.
+pub const REPRODUCIBLE_ENV_ALLOWLIST: &[&str] = &[
+    "PATH",
+    "HOME",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "SYSTEMROOT",
+    "SYSTEMDRIVE",
+    "WINDIR",
+    "COMSPEC",
+    "PATHEXT",
+    "USERPROFILE",
+];
+
+/// The `SOURCE_DATE_EPOCH` of reproducible mode when werk itself does not run
+/// with one: 1980-01-01, the earliest time that zip archives can represent.
+pub const DEFAULT_SOURCE_DATE_EPOCH: &str = "315532800";
+
 impl Env {
+    /// The environment of recipe commands in reproducible mode: only the
+    /// variables in [`REPRODUCIBLE_ENV_ALLOWLIST`] are inherited, the locale
+    /// and time zone are fixed, and `SOURCE_DATE_EPOCH` is set.
+    pub fn reproducible(io: &dyn Io) -> Self {
+        let mut env = Env {
+            clear: true,
+            ..Env::default()
+        };
+        for name in REPRODUCIBLE_ENV_ALLOWLIST {
+            if let Some(value) = io.read_env(name) {
+                env.env(name, value);
+            }
+        }
+        env.env("LC_ALL", "C");
+        env.env("TZ", "UTC");
+        env.env(
+            "SOURCE_DATE_EPOCH",
+            io.read_env("SOURCE_DATE_EPOCH")
+                .as_deref()
+                .unwrap_or(DEFAULT_SOURCE_DATE_EPOCH),
+        );
+        env
+    }
+
     pub fn merge_from(&mut self, other: &Self) {
+        self.clear |= other.clear;
         for k in &other.env_remove {
             self.env_remove(k);
         }
@@ -298,3 +655,74 @@ impl Env {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response_file_contents() {
+        let args = ["-c", "a b.c", "", "x\"y", "C:\\dir\\", "C:\\a\\\"b"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(
+            ResponseFile::Gnu.contents(&args),
+            "-c\na\\ b.c\n\"\"\nx\\\"y\nC:\\\\dir\\\\\nC:\\\\a\\\\\\\"b\n"
+        );
+        assert_eq!(
+            ResponseFile::Msvc.contents(&args),
+            "-c\n\"a b.c\"\n\"\"\n\"x\\\"y\"\nC:\\dir\\\n\"C:\\a\\\\\\\"b\"\n"
+        );
+    }
+
+    #[test]
+    fn quote_sh() {
+        let quote = |s| quote(QuoteStyle::Sh, s);
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("src/a-1.2_b.c"), "src/a-1.2_b.c");
+        assert_eq!(quote("--flag=a,b:c@d%e+f"), "--flag=a,b:c@d%e+f");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote("$HOME `id` ~ *"), "'$HOME `id` ~ *'");
+        assert_eq!(quote("a\nb"), "'a\nb'");
+        assert_eq!(quote("\u{e6}"), "'\u{e6}'");
+    }
+
+    #[test]
+    fn quote_cmd() {
+        let quote = |s| quote(QuoteStyle::Cmd, s);
+        assert_eq!(quote(""), r#"^"^""#);
+        assert_eq!(quote("abc"), "abc");
+        assert_eq!(quote(r"C:\dir\"), r"C:\dir\");
+        assert_eq!(quote("a b"), r#"^"a b^""#);
+        assert_eq!(quote("a\tb"), "^\"a\tb^\"");
+        // Quotes and the backslashes before them are escaped for
+        // `CommandLineToArgvW`, and then the quotes are escaped for `cmd.exe`.
+        assert_eq!(quote(r#"a"b"#), r#"^"a\^"b^""#);
+        assert_eq!(quote(r#"a\\"b"#), r#"^"a\\\\\^"b^""#);
+        // Trailing backslashes must not escape the closing quote.
+        assert_eq!(quote(r"C:\my dir\"), r#"^"C:\my dir\\^""#);
+        // Backslashes that are not before a quote are literal.
+        assert_eq!(quote(r"C:\my dir\a"), r#"^"C:\my dir\a^""#);
+        // Metacharacters of `cmd.exe`, inside and outside of quotes.
+        assert_eq!(quote("50%"), "50^%");
+        assert_eq!(quote("%PATH%"), "^%PATH^%");
+        assert_eq!(quote("!var!"), "^!var^!");
+        assert_eq!(quote("a&b|c"), "a^&b^|c");
+        assert_eq!(quote("(x)"), "^(x^)");
+        assert_eq!(quote("^"), "^^");
+        assert_eq!(quote("<in> out"), r#"^"^<in^> out^""#);
+        assert_eq!(quote("a & b"), r#"^"a ^& b^""#);
+    }
+
+    #[test]
+    fn quote_regex_and_c() {
+        assert_eq!(quote(QuoteStyle::Regex, "a.b*(c)"), r"a\.b\*\(c\)");
+        assert_eq!(quote(QuoteStyle::Regex, "abc"), "abc");
+        assert_eq!(quote(QuoteStyle::C, ""), r#""""#);
+        assert_eq!(
+            quote(QuoteStyle::C, "a\"b\\c\nd\te\r\u{1}2\u{7f}\u{e6}"),
+            r#""a\"b\\c\nd\te\r\0012\177æ""#
+        );
+    }
+}
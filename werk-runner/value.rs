@@ -1,11 +1,35 @@
 use std::future::Future;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+use werk_fs::Absolute;
+
+#[derive(Debug, Clone)]
 pub enum Value {
     List(Vec<Value>),
     String(String),
+    /// An abstract path in the workspace, like `/src/main.c`, produced by
+    /// `glob` and the `in` and `out` variables of build recipes. Paths are
+    /// always absolute and normalized, and use `/` as the separator on all
+    /// platforms.
+    ///
+    /// Interpolating a path with `{...}` produces the abstract path as a
+    /// string, and `<...>` resolves it to a native filesystem path. String
+    /// operations on a path produce a string.
+    Path(Absolute<werk_fs::PathBuf>),
 }
 
+/// Paths compare equal to strings containing the same abstract path.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::List(lhs), Value::List(rhs)) => lhs == rhs,
+            (Value::List(_), _) | (_, Value::List(_)) => false,
+            (lhs, rhs) => lhs.as_str() == rhs.as_str(),
+        }
+    }
+}
+
+impl Eq for Value {}
+
 impl From<String> for Value {
     #[inline]
     fn from(s: String) -> Self {
@@ -27,7 +51,38 @@ impl From<Vec<Value>> for Value {
     }
 }
 
+impl From<Absolute<werk_fs::PathBuf>> for Value {
+    #[inline]
+    fn from(path: Absolute<werk_fs::PathBuf>) -> Self {
+        Value::Path(path)
+    }
+}
+
 impl Value {
+    /// The string or abstract path, or `None` if this is a list.
+    #[inline]
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::List(_) => None,
+            Value::String(s) => Some(s),
+            Value::Path(path) => Some(path.as_str()),
+        }
+    }
+
+    /// Convert paths to strings, so they can be modified by string
+    /// operations. Lists are unchanged.
+    fn make_string(&mut self) -> Option<&mut String> {
+        if let Value::Path(path) = self {
+            *self = Value::String(path.as_str().to_owned());
+        }
+        match self {
+            Value::String(s) => Some(s),
+            Value::List(_) => None,
+            Value::Path(_) => unreachable!(),
+        }
+    }
+
     pub fn try_collect_strings_recursive<F, E>(self, mut f: F) -> Result<(), E>
     where
         F: FnMut(String) -> Result<(), E>,
@@ -44,6 +99,7 @@ impl Value {
                     Ok(())
                 }
                 Value::String(s) => f(s),
+                Value::Path(path) => f(path.into_inner().into()),
             }
         }
 
@@ -65,14 +121,15 @@ impl Value {
                 }
             }
             Value::String(s) => strings.push(s),
+            Value::Path(path) => strings.push(path.into_inner().into()),
         }
     }
 
     pub fn for_each_string_recursive<F>(&self, mut f: F)
     where
-        F: FnMut(&String),
+        F: FnMut(&str),
     {
-        fn for_each_string_recursive(this: &Value, f: &mut impl FnMut(&String)) {
+        fn for_each_string_recursive(this: &Value, f: &mut impl FnMut(&str)) {
             match this {
                 Value::List(v) => {
                     for item in v {
@@ -80,6 +137,7 @@ impl Value {
                     }
                 }
                 Value::String(s) => f(s),
+                Value::Path(path) => f(path.as_str()),
             }
         }
 
@@ -88,11 +146,11 @@ impl Value {
 
     pub fn try_for_each_string_recursive<F, E>(&self, mut f: F) -> Result<(), E>
     where
-        F: FnMut(&String) -> Result<(), E>,
+        F: FnMut(&str) -> Result<(), E>,
     {
         fn try_for_each_string_recursive<E>(
             this: &Value,
-            f: &mut impl FnMut(&String) -> Result<(), E>,
+            f: &mut impl FnMut(&str) -> Result<(), E>,
         ) -> Result<(), E> {
             match this {
                 Value::List(v) => {
@@ -102,6 +160,7 @@ impl Value {
                     Ok(())
                 }
                 Value::String(s) => f(s),
+                Value::Path(path) => f(path.as_str()),
             }
         }
 
@@ -128,6 +187,10 @@ impl Value {
                     *s = f(value)?;
                     Ok(())
                 }
+                Value::Path(path) => {
+                    *this = Value::String(f(path.as_str().to_owned())?);
+                    Ok(())
+                }
             }
         }
 
@@ -159,6 +222,7 @@ impl Value {
                     .await
                 }
                 Value::String(s) => f(s).await,
+                Value::Path(path) => f(path.as_str()).await,
             }
         }
 
@@ -180,7 +244,7 @@ impl Value {
                     }
                     Ok(())
                 }
-                Value::String(s) => f(s),
+                Value::String(_) | Value::Path(_) => f(this.make_string().expect("not a list")),
             }
         }
 
@@ -201,7 +265,7 @@ impl Value {
                         recursive_modify(item, f);
                     }
                 }
-                Value::String(s) => f(s),
+                Value::String(_) | Value::Path(_) => f(this.make_string().expect("not a list")),
             }
         }
 
@@ -217,10 +281,7 @@ impl Value {
 impl PartialEq<str> for Value {
     #[inline]
     fn eq(&self, other: &str) -> bool {
-        match self {
-            Value::String(s) => s == other,
-            Value::List(_) => false,
-        }
+        self.as_str() == Some(other)
     }
 }
 
@@ -239,7 +300,7 @@ where
     fn eq(&self, other: &[T]) -> bool {
         match self {
             Value::List(v) => v.iter().zip(other.iter()).all(|(a, b)| a == b),
-            Value::String(_) => false,
+            Value::String(_) | Value::Path(_) => false,
         }
     }
 }
@@ -268,6 +329,7 @@ impl std::fmt::Display for Value {
                 f.write_str("]")
             }
             Value::String(s) => f.write_str(s),
+            Value::Path(path) => f.write_str(path.as_str()),
         }
     }
 }
@@ -321,6 +383,7 @@ impl std::fmt::Display for DisplayFriendly<'_> {
             match value {
                 Value::List(vec) => list_with_ellipsis(vec, max_width),
                 Value::String(s) => string_with_ellipsis(s, max_width),
+                Value::Path(path) => string_with_ellipsis(path.as_str(), max_width),
             }
         }
 
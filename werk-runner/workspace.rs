@@ -1,19 +1,25 @@
-use ahash::HashMap;
+use ahash::{HashMap, HashSet};
 use indexmap::IndexMap;
 use parking_lot::Mutex;
-use std::{borrow::Cow, collections::hash_map};
+use std::{
+    borrow::Cow,
+    collections::{hash_map, BTreeMap},
+};
 use werk_fs::{Absolute, Normalize as _, PathError};
 use werk_parser::ast;
-use werk_util::{Diagnostic, DiagnosticError, Symbol};
+use werk_util::{Diagnostic, DiagnosticError, DiagnosticFileId, Symbol};
 
 use crate::{
-    cache::{Hash128, TargetOutdatednessCache, WerkCache},
+    cache::{Hash128, RecordedShellOutput, TargetOutdatednessCache, WerkCache},
     eval::{self, Eval, UsedVariable},
+    import_file_id,
     ir::{self, BuildRecipe, TaskRecipe},
-    DirEntry, Error, EvalError, GlobalVar, Io, Render, RootScope,
+    qualify, CancellationToken, DirEntry, Error, EvalError, GlobalVar, Io, Render, RootScope,
+    TaskId, WhichCache,
 };
 
 #[derive(Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct WorkspaceSettings {
     pub output_directory: Absolute<std::path::PathBuf>,
     /// Settings for globbing the workspace directory. Note that the
@@ -22,6 +28,12 @@ pub struct WorkspaceSettings {
     pub glob: GlobSettings,
     /// Command-line `--define` or `-D` arguments, overriding global variables.
     pub defines: HashMap<String, String>,
+    /// The profile selected with the `--profile` command-line argument,
+    /// taking precedence over `default_profile`.
+    pub profile: Option<String>,
+    /// The profile selected when `profile` is not set. Usually populated from
+    /// `config profile = "..."`.
+    pub default_profile: Option<String>,
     /// When true, the [`Runner`](crate::Runner) sets the `FORCE_COLOR` and
     /// `CLICOLOR_FORCE` environment variables to "1" when executing recipe
     /// commands (not when capturing their output in variables).
@@ -29,9 +41,110 @@ pub struct WorkspaceSettings {
     /// Number of jobs to execute in parallel. Default is 1. If below 1, this
     /// will automatically be clamped to 1.
     pub jobs: usize,
+    /// Pools limiting the number of concurrent commands of recipes that
+    /// declare them with `pool "<name>"`, independently of `jobs`. Usually
+    /// populated from `config pool.<name> = <depth>`.
+    pub pools: IndexMap<Symbol, usize>,
+    /// Remote hosts that run the commands of build recipes with a `host`
+    /// statement. Usually populated from `config host.<name> = "..."`.
+    pub hosts: IndexMap<Symbol, ir::RemoteHost>,
+    /// The program running the commands of build recipes with a `container`
+    /// statement. Default is [`DEFAULT_CONTAINER_RUNTIME`]. Usually populated
+    /// from `config container-runtime = "..."`.
+    pub container_runtime: Option<String>,
+    /// When the images of `container` statements are pulled. Usually
+    /// populated from `config container-pull = "..."`.
+    pub container_pull: ir::PullPolicy,
+    /// The interpreter of `run-shell` statements, which receives the script as
+    /// its last argument. Default is [`DEFAULT_SHELL`]. Usually populated from
+    /// `config shell = "..."`.
+    pub shell: Option<String>,
+    /// Directories containing source files, relative to the workspace
+    /// directory, in priority order. When a file exists in more than one of
+    /// them, the first one wins. Empty means the workspace directory itself.
+    /// Usually populated from `config source-dirs = [...]`.
+    pub source_dirs: Vec<String>,
+    /// Directories searched for programs by `which` before `PATH`, relative
+    /// to the workspace directory, in priority order. Directories that don't
+    /// exist are skipped. Usually populated from `config tool-paths = [...]`.
+    pub tool_paths: Vec<String>,
+    /// Programs that `which` resolves to a fixed path instead of searching for
+    /// them, corresponding to the `--tool` command-line argument.
+    pub tools: HashMap<String, Absolute<std::path::PathBuf>>,
+    /// Results of searching `PATH`, shared by all workspaces created from
+    /// these settings.
+    pub which_cache: WhichCache,
+    /// Report references to undefined identifiers as errors before evaluating
+    /// anything, and collect warnings about likely mistakes, like unused `let`
+    /// statements, in [`Workspace::warnings`]. Usually populated from `config
+    /// strict = true`.
+    pub strict: bool,
+    /// Create the parent directories of the target file of build recipes, and
+    /// of the destinations of `write` and `copy`, before running them. Default
+    /// is true. Usually populated from `config create-dirs = false`.
+    pub create_dirs: bool,
+    /// Run recipe commands in a scrubbed environment, see
+    /// [`Env::reproducible()`](crate::Env::reproducible). Usually populated
+    /// from `config reproducible = true`.
+    pub reproducible: bool,
+    /// The digest computed by `hash` expressions. Usually populated from
+    /// `config hash-algorithm = "..."`.
+    pub hash_algorithm: ir::HashAlgorithm,
+
+    /// Build recipes that should be rebuilt regardless of their outdatedness.
+    pub force: ForceRebuild,
+    /// Keep building everything that doesn't depend on a failed task, instead
+    /// of starting no new commands after the first failure. Corresponds to
+    /// the `--keep-going` command-line argument.
+    pub keep_going: bool,
 
     /// Insert artificial delay between executed commands. Useful for testing.
     pub artificial_delay: Option<std::time::Duration>,
+    /// Cancels the builds of the workspace. Once cancelled, running commands
+    /// are killed and no new commands are started.
+    pub cancellation: CancellationToken,
+}
+
+/// The interpreter of `run-shell` statements when none is configured.
+pub const DEFAULT_SHELL: &str = if cfg!(windows) { "cmd /C" } else { "sh -c" };
+
+/// The program running containers when none is configured.
+pub const DEFAULT_CONTAINER_RUNTIME: &str = "docker";
+
+/// The contents of an output file compared to the hash recorded in
+/// `.werk-cache` when it was last built. See [`Workspace::verify_outputs()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputStatus {
+    /// The file is unchanged since it was built.
+    Unchanged,
+    /// The file was changed after it was built.
+    Modified,
+    /// The file was deleted after it was built.
+    Missing,
+}
+
+/// Build targets that are considered outdated regardless of their
+/// outdatedness, corresponding to the `--force` command-line argument.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ForceRebuild {
+    /// Only rebuild outdated targets.
+    #[default]
+    Nothing,
+    /// Rebuild all targets.
+    All,
+    /// Rebuild specific targets.
+    Targets(HashSet<Absolute<werk_fs::PathBuf>>),
+}
+
+impl ForceRebuild {
+    #[must_use]
+    pub fn is_forced(&self, target: &Absolute<werk_fs::Path>) -> bool {
+        match self {
+            ForceRebuild::Nothing => false,
+            ForceRebuild::All => true,
+            ForceRebuild::Targets(targets) => targets.contains(target),
+        }
+    }
 }
 
 impl WorkspaceSettings {
@@ -41,9 +154,27 @@ impl WorkspaceSettings {
             output_directory: output_dir,
             glob: GlobSettings::default(),
             defines: HashMap::default(),
+            profile: None,
+            default_profile: None,
             force_color: false,
             jobs: 1,
+            pools: IndexMap::new(),
+            hosts: IndexMap::new(),
+            container_runtime: None,
+            container_pull: ir::PullPolicy::default(),
+            shell: None,
+            source_dirs: Vec::new(),
+            tool_paths: Vec::new(),
+            tools: HashMap::default(),
+            which_cache: WhichCache::new(),
+            strict: false,
+            create_dirs: true,
+            reproducible: false,
+            hash_algorithm: ir::HashAlgorithm::default(),
+            force: ForceRebuild::Nothing,
+            keep_going: false,
             artificial_delay: None,
+            cancellation: CancellationToken::new(),
         }
     }
 }
@@ -62,6 +193,9 @@ pub struct GlobSettings {
     pub git_ignore_from_parents: bool,
     /// Enables reading `.ignore` files, supported by `ripgrep` and The Silver Searcher. Enabled by default.
     pub dot_ignore: bool,
+    /// Read `.werkignore` files, which use the same syntax as `.gitignore`.
+    /// Enabled by default.
+    pub werk_ignore: bool,
     /// Explicit file name patterns to ignore in addition to gitignore and .ignore files.
     pub ignore_explicitly: globset::GlobSet,
 }
@@ -75,60 +209,207 @@ impl Default for GlobSettings {
             git_ignore_exclude: true,
             git_ignore_from_parents: true,
             dot_ignore: true,
+            werk_ignore: true,
             ignore_explicitly: globset::GlobSet::empty(),
         }
     }
 }
 
 impl WorkspaceSettings {
+    /// Apply the settings declared with `config` statements in the Werkfile,
+    /// except the output directory, which is relative to the workspace
+    /// directory and may be overridden by the front-end.
+    pub fn apply_config(&mut self, config: &ir::Config) -> &mut Self {
+        self.pools.clone_from(&config.pools);
+        self.hosts.clone_from(&config.hosts);
+        self.container_runtime.clone_from(&config.container_runtime);
+        self.container_pull = config.container_pull;
+        self.shell.clone_from(&config.shell);
+        self.source_dirs.clone_from(&config.source_dirs);
+        self.tool_paths.clone_from(&config.tool_paths);
+        self.default_profile.clone_from(&config.profile);
+        self.strict = config.strict;
+        self.create_dirs = config.create_dirs.unwrap_or(true);
+        self.reproducible = config.reproducible;
+        self.hash_algorithm = config.hash_algorithm;
+        let git = config.glob_ignore == ir::GlobIgnore::Git;
+        self.glob.git_ignore = git;
+        self.glob.git_ignore_global = git;
+        self.glob.git_ignore_exclude = git;
+        self.glob.git_ignore_from_parents = git;
+        self.glob.dot_ignore = git;
+        self.glob.werk_ignore = true;
+        self
+    }
+
+    /// Select a profile, replacing the global variables that it declares.
+    pub fn profile(&mut self, name: impl Into<String>) -> &mut Self {
+        self.profile = Some(name.into());
+        self
+    }
+
     /// Override a global variable in the root scope.
     pub fn define(&mut self, key: impl Into<String>, value: impl Into<String>) -> &mut Self {
         self.defines.insert(key.into(), value.into());
         self
     }
 
+    /// Make `which` resolve the program `name` to `path`, without searching
+    /// for it.
+    pub fn tool(
+        &mut self,
+        name: impl Into<String>,
+        path: Absolute<std::path::PathBuf>,
+    ) -> &mut Self {
+        self.tools.insert(name.into(), path);
+        self
+    }
+
+    /// Declare a pool that allows at most `depth` concurrent commands.
+    pub fn pool(&mut self, name: impl Into<Symbol>, depth: usize) -> &mut Self {
+        self.pools.insert(name.into(), depth);
+        self
+    }
+
     pub fn ignore_explicitly(&mut self, globset: globset::GlobSet) -> &mut Self {
         self.glob.ignore_explicitly = globset;
         self
     }
+
+    /// Rebuild all targets, regardless of their outdatedness.
+    pub fn force_all(&mut self) -> &mut Self {
+        self.force = ForceRebuild::All;
+        self
+    }
+
+    /// Rebuild `target`, regardless of its outdatedness. Does nothing if all
+    /// targets are already forced.
+    pub fn force_target(&mut self, target: Absolute<werk_fs::PathBuf>) -> &mut Self {
+        match self.force {
+            ForceRebuild::Nothing => {
+                self.force = ForceRebuild::Targets(HashSet::from_iter([target]));
+            }
+            ForceRebuild::All => (),
+            ForceRebuild::Targets(ref mut targets) => {
+                targets.insert(target);
+            }
+        }
+        self
+    }
 }
 
+#[allow(clippy::struct_excessive_bools)]
 pub struct Workspace<'a> {
     pub manifest: ir::Manifest<'a>,
     // Project root - note that the workspace only accesses this directory
     // through the `Io` trait, and never directly.
     project_root: Absolute<std::path::PathBuf>,
+    // Directories containing source files, in priority order. Contains only
+    // the project root unless `source-dirs` is configured.
+    source_dirs: Vec<Absolute<std::path::PathBuf>>,
     // Project root - note that the workspace only accesses this directory
     // through the `Io` trait, and never directly.
     output_directory: Absolute<std::path::PathBuf>,
+    // Directories searched for programs before `PATH`, in priority order.
+    tool_paths: Vec<Absolute<std::path::PathBuf>>,
+    tools: HashMap<String, Absolute<std::path::PathBuf>>,
+    which_cache: WhichCache,
     // Using IndexMap to ensure that the ordering of glob results is well-defined.
     workspace_files: IndexMap<Absolute<werk_fs::PathBuf>, DirEntry, ahash::RandomState>,
     /// The contents of `<out-dir>/.werk-cache.toml`.
     werk_cache: Mutex<WerkCache>,
     /// Caches of expensive runtime values (glob, which, env).
     runtime_caches: Mutex<Caches>,
+    /// Strings marked with `| secret`, longest first. See
+    /// [`Workspace::redact()`].
+    secrets: Mutex<Vec<String>>,
+    /// True when a build recipe has an `out` statement, and the workspace is
+    /// fully evaluated, so output locations must be looked up.
+    redirect_outputs: bool,
     /// Overridden global variables from the command line.
     pub defines: HashMap<Symbol, String>,
     pub force_color: bool,
+    pub force: ForceRebuild,
+    /// Keep building after a task fails.
+    pub keep_going: bool,
+    /// The interpreter of `run-shell` statements, like `sh -c`.
+    pub shell: String,
+    /// Create the parent directories of output files before running commands.
+    pub create_dirs: bool,
+    /// Run recipe commands in a scrubbed environment.
+    pub reproducible: bool,
+    /// The digest computed by `hash` expressions.
+    pub hash_algorithm: ir::HashAlgorithm,
+    /// Remote hosts that run the commands of build recipes.
+    pub(crate) hosts: IndexMap<Symbol, ir::RemoteHost>,
+    /// The program running containers, like `docker`.
+    pub(crate) container_runtime: String,
+    /// When the images of containers are pulled.
+    pub(crate) container_pull: ir::PullPolicy,
     pub io: &'a dyn Io,
     pub render: &'a dyn Render,
     pub(crate) runner_state: crate::runner::RunnerState,
     pub(crate) artificial_delay: Option<std::time::Duration>,
+    pub(crate) cancellation: CancellationToken,
 
     pub werkfile_path: std::path::PathBuf,
     pub werkfile_source: &'a str,
+    /// Werkfiles imported by the main Werkfile.
+    pub imports: &'a [werk_parser::Import<'a>],
+    /// Workspace members declared by the main Werkfile, which are built when
+    /// a recipe depends on a label like `//member:target`.
+    pub members: &'a [crate::Member<'a>],
+    /// Warnings found by static analysis in strict mode, to be reported by
+    /// the caller.
+    pub warnings: Vec<crate::check::Warning>,
 }
 
 #[derive(Default)]
 struct Caches {
-    glob_cache: HashMap<String, (Vec<Absolute<werk_fs::PathBuf>>, Hash128)>,
+    glob_cache: HashMap<Vec<String>, (Vec<Absolute<werk_fs::PathBuf>>, Hash128)>,
     which_cache: HashMap<String, Result<(Absolute<std::path::PathBuf>, Hash128), which::Error>>,
     env_cache: HashMap<String, (String, Hash128)>,
     build_recipe_hashes: HashMap<String, Hash128>,
+    /// Source files whose existence or contents influenced the build.
+    consulted_files: std::collections::BTreeSet<Absolute<std::path::PathBuf>>,
+    /// Locations of output files given by `out` statements.
+    output_locations: HashMap<Absolute<werk_fs::PathBuf>, Option<Absolute<werk_fs::PathBuf>>>,
 }
 
 pub const WERK_CACHE_FILENAME: &str = ".werk-cache";
 
+/// What secrets are replaced with in output. See [`Workspace::redact()`].
+pub const REDACTED: &str = "***";
+
+/// The directory in the output directory that holds the temporary directories
+/// of recipes.
+pub const WERK_TMP_DIRNAME: &str = ".werk-tmp";
+
+/// The directory in the output directory that holds the output of the commands
+/// of each task.
+pub const WERK_LOG_DIRNAME: &str = ".werk/log";
+
+/// The number of previous logs of a task kept next to its latest log.
+pub const NUM_ROTATED_LOGS: usize = 2;
+
+/// A file name that is unique to the task: the name of the task with special
+/// characters replaced, followed by a hash of the task.
+fn task_file_name(task_id: TaskId) -> String {
+    let name = task_id
+        .short_name()
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || matches!(ch, '.' | '-' | '_') {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    let hash = format!("{:032x}", compute_stable_hash(task_id.as_str()).0);
+    format!("{name}-{}", &hash[..8])
+}
+
 impl<'a> Workspace<'a> {
     pub fn new_with_diagnostics(
         ast: &'a werk_parser::Document<'a>,
@@ -141,6 +422,7 @@ impl<'a> Workspace<'a> {
             .map_err(|err| err.into_diagnostic_error(ast))
     }
 
+    #[expect(clippy::too_many_lines)]
     pub fn new(
         ast: &'a werk_parser::Document<'a>,
         io: &'a dyn Io,
@@ -150,27 +432,50 @@ impl<'a> Workspace<'a> {
     ) -> Result<Self, Error> {
         let werk_cache = read_workspace_cache(io, &settings.output_directory);
 
+        let source_dirs = if settings.source_dirs.is_empty() {
+            vec![project_root.clone()]
+        } else {
+            settings
+                .source_dirs
+                .iter()
+                .map(|dir| resolve_source_dir(io, &project_root, dir))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let tool_paths = settings
+            .tool_paths
+            .iter()
+            .filter_map(|dir| std::path::Path::join(&project_root, dir).normalize().ok())
+            .collect();
+
         let mut workspace_files =
             IndexMap::with_capacity_and_hasher(1024, ahash::RandomState::default());
 
-        for entry in io.glob_workspace(&project_root, &settings.glob)? {
-            if entry.path.file_name() == Some(WERK_CACHE_FILENAME.as_ref()) {
-                return Err(Error::ClobberedWorkspace(entry.path.into_inner()));
-            }
+        for source_dir in &source_dirs {
+            for entry in io.glob_workspace(source_dir, &settings.glob)? {
+                if entry.path.file_name() == Some(WERK_CACHE_FILENAME.as_ref()) {
+                    return Err(Error::ClobberedWorkspace(entry.path.into_inner()));
+                }
 
-            let path_in_project = match entry.path.unresolve(&project_root) {
-                Ok(path_in_project) => path_in_project,
-                // This should not be possible.
-                Err(err @ PathError::UnresolveBeyondRoot) => {
-                    return Err(Error::InvalidTargetPath(
-                        entry.path.display().to_string(),
-                        err,
-                    ))
+                let path_in_project = match entry.path.unresolve(source_dir) {
+                    Ok(path_in_project) => path_in_project,
+                    // This should not be possible.
+                    Err(err @ PathError::UnresolveBeyondRoot) => {
+                        return Err(Error::InvalidTargetPath(
+                            entry.path.display().to_string(),
+                            err,
+                        ))
+                    }
+                    Err(_) => continue,
+                };
+
+                // Files in earlier source directories take precedence.
+                if let indexmap::map::Entry::Vacant(vacant) = workspace_files.entry(path_in_project)
+                {
+                    tracing::trace!("Workspace file: {}", vacant.key());
+                    vacant.insert(entry);
                 }
-                Err(_) => continue,
-            };
-            tracing::trace!("Workspace file: {path_in_project}");
-            workspace_files.insert(path_in_project, entry);
+            }
         }
 
         // Deterministic workspace order to preserve the ordering of glob
@@ -182,40 +487,164 @@ impl<'a> Workspace<'a> {
         let mut workspace = Self {
             manifest,
             project_root,
+            source_dirs,
+            tool_paths,
+            tools: settings.tools.clone(),
+            which_cache: settings.which_cache.clone(),
             output_directory: settings.output_directory.clone(),
             workspace_files,
             werk_cache: Mutex::new(werk_cache),
-            runtime_caches: Mutex::new(Caches {
-                glob_cache: HashMap::default(),
-                which_cache: HashMap::default(),
-                env_cache: HashMap::default(),
-                build_recipe_hashes: HashMap::default(),
-            }),
+            runtime_caches: Mutex::new(Caches::default()),
+            secrets: Mutex::default(),
+            redirect_outputs: false,
             defines: settings
                 .defines
                 .iter()
                 .map(|(k, v)| (Symbol::new(k), v.clone()))
                 .collect(),
             force_color: settings.force_color,
+            force: settings.force.clone(),
+            keep_going: settings.keep_going,
+            shell: settings
+                .shell
+                .clone()
+                .unwrap_or_else(|| String::from(DEFAULT_SHELL)),
+            create_dirs: settings.create_dirs,
+            reproducible: settings.reproducible,
+            hash_algorithm: settings.hash_algorithm,
+            hosts: settings.hosts.clone(),
+            container_runtime: settings
+                .container_runtime
+                .clone()
+                .unwrap_or_else(|| String::from(DEFAULT_CONTAINER_RUNTIME)),
+            container_pull: settings.container_pull,
             io,
             render,
-            runner_state: crate::RunnerState::new(settings.jobs),
+            runner_state: crate::RunnerState::new(settings.jobs, &settings.pools),
             artificial_delay: settings.artificial_delay,
+            cancellation: settings.cancellation.clone(),
             werkfile_path: ast.origin.to_path_buf(),
             werkfile_source: ast.source,
+            imports: &ast.imports,
+            members: &[],
+            warnings: Vec::new(),
         };
 
+        if settings.strict {
+            workspace.warnings = crate::check::check_document(ast)?;
+        }
+
         // Manifest document is currently empty - populate it by evaluating the AST.
+        workspace.evaluate_profiles(ast)?;
+        if let Some(name) = settings
+            .profile
+            .as_ref()
+            .or(settings.default_profile.as_ref())
+        {
+            let name = Symbol::new(name);
+            if !workspace.manifest.profiles.contains_key(&name) {
+                return Err(Error::NoSuchProfile(name.to_string()));
+            }
+            workspace.manifest.profile = Some(name);
+        }
         workspace.evaluate_globals_and_recipes(ast)?;
+        if settings.strict {
+            let warnings = crate::check::check_build_recipes(&workspace.manifest);
+            workspace.warnings.extend(warnings);
+        }
 
         Ok(workspace)
     }
 
+    /// Gather the profiles declared in the main Werkfile, and check that they
+    /// only replace global variables that exist.
+    fn evaluate_profiles(&mut self, ast: &'a werk_parser::Document<'a>) -> Result<(), EvalError> {
+        for stmt in &ast.root.statements {
+            let ast::RootStmt::Profile(ref profile) = stmt.statement else {
+                continue;
+            };
+            let name = profile.name.ident;
+            if self.manifest.profiles.contains_key(&name) {
+                return Err(EvalError::DuplicateProfile(
+                    profile.name.span,
+                    name.to_string(),
+                ));
+            }
+            for let_stmt in &profile.body.statements {
+                let ident = &let_stmt.statement.ident;
+                if ast.root.find_global(ident.ident.as_str()).is_none() {
+                    return Err(EvalError::NoSuchProfileVariable(
+                        ident.span,
+                        ident.ident.to_string(),
+                    ));
+                }
+            }
+            let doc_comment = ast
+                .get_whitespace(stmt.ws_pre)
+                .trim()
+                .lines()
+                .next()
+                .unwrap_or("")
+                .to_string();
+            self.manifest.profiles.insert(
+                name,
+                ir::Profile {
+                    span: profile.span,
+                    name,
+                    doc_comment,
+                    ast: profile,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// The `let` statement replacing the global variable `name` in the main
+    /// Werkfile, if the selected profile declares one.
+    fn profile_global(&self, name: Symbol) -> Option<&'a ast::LetStmt<'a>> {
+        let profile = self.manifest.profiles.get(&self.manifest.profile?)?;
+        profile.ast.find_global(name.as_str())
+    }
+
     /// Evaluate global variables, tasks, and recipe patterns. Also gathers
     /// documentation for each global item.
+    ///
+    /// Imported Werkfiles are evaluated first, so their globals are available
+    /// to the Werkfiles importing them.
     fn evaluate_globals_and_recipes(
         &mut self,
         ast: &'a werk_parser::Document<'a>,
+    ) -> Result<(), EvalError> {
+        for (index, import) in ast.imports.iter().enumerate() {
+            let file = import_file_id(index);
+            self.evaluate_document(&import.document, Some(import.namespace), file)
+                .map_err(|err| err.in_file(file))?;
+        }
+        self.evaluate_document(ast, None, DiagnosticFileId::default())?;
+
+        // Warn about defines set on the command-line that have no effect.
+        for key in self.defines.keys() {
+            if !self.manifest.globals.contains_key(key) {
+                self.render.warning(None, &format!("Unused define: {key}"));
+            }
+        }
+
+        // Output locations can only be looked up once all globals are known.
+        self.redirect_outputs = self
+            .manifest
+            .build_recipes
+            .iter()
+            .any(|recipe| recipe.out.is_some());
+
+        Ok(())
+    }
+
+    #[expect(clippy::too_many_lines)]
+    fn evaluate_document(
+        &mut self,
+        ast: &'a werk_parser::Document<'a>,
+        namespace: Option<Symbol>,
+        file: DiagnosticFileId,
     ) -> Result<(), EvalError> {
         for stmt in &ast.root.statements {
             let doc_comment = ast
@@ -229,25 +658,67 @@ impl<'a> Workspace<'a> {
             match stmt.statement {
                 ast::RootStmt::Config(_) => {
                     // Ignore; these should be parsed by the front-end.
-                    continue;
+                    if namespace.is_some() {
+                        self.render.warning(
+                            None,
+                            &format!(
+                                "Ignoring `config` statement in imported Werkfile {}",
+                                ast.origin.display()
+                            ),
+                        );
+                    }
+                }
+                ast::RootStmt::Import(_) => {
+                    // Ignore; imported documents are loaded by the front-end.
+                }
+                ast::RootStmt::Member(_) => {
+                    // Ignore; members are loaded by the front-end.
+                    if namespace.is_some() {
+                        self.render.warning(
+                            None,
+                            &format!(
+                                "Ignoring `member` statement in imported Werkfile {}",
+                                ast.origin.display()
+                            ),
+                        );
+                    }
+                }
+                ast::RootStmt::Profile(_) => {
+                    // Gathered before evaluating globals, from the main
+                    // Werkfile only.
+                    if namespace.is_some() {
+                        self.render.warning(
+                            None,
+                            &format!(
+                                "Ignoring `profile` statement in imported Werkfile {}",
+                                ast.origin.display()
+                            ),
+                        );
+                    }
                 }
                 ast::RootStmt::Let(ref let_stmt) => {
+                    let name = qualify(namespace, let_stmt.ident.ident);
+                    let let_stmt = match namespace {
+                        None => self.profile_global(name).unwrap_or(let_stmt),
+                        Some(_) => let_stmt,
+                    };
                     let hash = compute_stable_semantic_hash(&let_stmt.value);
-                    if let Some(global_override) = self.defines.get(&let_stmt.ident.ident) {
+                    let shell_cache = ir::ShellCache::new(&let_stmt.attributes)?;
+                    if let Some(global_override) = self.defines.get(&name) {
                         tracing::trace!(
                             "Overriding global variable `{}` with `{}`",
-                            let_stmt.ident.ident,
+                            name,
                             global_override
                         );
                         self.manifest.globals.insert(
-                            let_stmt.ident.ident,
+                            name,
                             GlobalVar {
                                 value: Eval::using_vars(
                                     global_override.clone().into(),
                                     [
-                                        UsedVariable::Global(let_stmt.ident.ident, hash),
+                                        UsedVariable::Global(name, hash),
                                         UsedVariable::Define(
-                                            let_stmt.ident.ident,
+                                            name,
                                             compute_stable_hash(global_override),
                                         ),
                                     ],
@@ -256,14 +727,16 @@ impl<'a> Workspace<'a> {
                             },
                         );
                     } else {
-                        let scope = RootScope::new(self);
+                        let mut scope = RootScope::with_namespace(self, namespace);
+                        scope.shell_cache = shell_cache.as_ref();
                         let mut value = eval::eval_chain(&scope, &let_stmt.value)?;
-                        value
-                            .used
-                            .insert(UsedVariable::Global(let_stmt.ident.ident, hash));
-                        tracing::trace!("(global) let `{}` = {:?}", let_stmt.ident, value);
+                        value.used.insert(UsedVariable::Global(name, hash));
+                        tracing::trace!("(global) let `{}` = {:?}", name, value);
+                        let shown = self.redact_value(&value);
+                        self.render
+                            .did_evaluate_variable(None, name.as_str(), &shown);
                         self.manifest.globals.insert(
-                            let_stmt.ident.ident,
+                            name,
                             GlobalVar {
                                 value,
                                 comment: doc_comment,
@@ -272,48 +745,138 @@ impl<'a> Workspace<'a> {
                     }
                 }
                 ast::RootStmt::Task(ref command_recipe) => {
-                    let hash = compute_stable_semantic_hash(command_recipe);
-                    self.manifest.task_recipes.insert(
-                        command_recipe.name.ident.as_str(),
-                        TaskRecipe {
-                            span: command_recipe.span,
-                            name: command_recipe.name.ident,
-                            doc_comment,
-                            ast: command_recipe,
-                            hash,
-                        },
-                    );
+                    self.evaluate_task_recipe(command_recipe, namespace, file, doc_comment)?;
                 }
                 ast::RootStmt::Build(ref build_recipe) => {
-                    let hash = compute_stable_semantic_hash(build_recipe);
-                    let scope = RootScope::new(self);
-                    let mut pattern_builder =
-                        eval::eval_pattern_builder(&scope, &build_recipe.pattern)?.value;
-
-                    // TODO: Consider if it isn't better to do this while matching recipes.
-                    pattern_builder.ensure_absolute_path();
-
-                    self.manifest.build_recipes.push(BuildRecipe {
-                        span: build_recipe.span,
-                        pattern: pattern_builder.build(),
-                        doc_comment,
-                        ast: build_recipe,
-                        hash,
-                    });
+                    self.evaluate_build_recipe(build_recipe, namespace, file, doc_comment)?;
                 }
             }
         }
 
-        // Warn about defines set on the command-line that have no effect.
-        for key in self.defines.keys() {
-            if !self.manifest.globals.contains_key(key) {
-                self.render.warning(None, &format!("Unused define: {key}"));
+        Ok(())
+    }
+
+    fn evaluate_task_recipe(
+        &mut self,
+        command_recipe: &'a ast::CommandRecipe<'a>,
+        namespace: Option<Symbol>,
+        file: DiagnosticFileId,
+        doc_comment: String,
+    ) -> Result<(), EvalError> {
+        let name = qualify(namespace, command_recipe.name.ident);
+        let hash = compute_stable_semantic_hash(command_recipe);
+        let attributes =
+            ir::Attributes::new(&command_recipe.attributes, ir::AttributeTarget::TaskRecipe)?;
+        check_recipe_let_attributes(command_recipe.body.statements.iter().filter_map(|stmt| {
+            match stmt.statement {
+                ast::TaskRecipeStmt::Let(ref let_stmt) => Some(let_stmt),
+                _ => None,
             }
-        }
+        }))?;
+        self.manifest.task_recipes.insert(
+            name.as_str(),
+            TaskRecipe {
+                span: command_recipe.span,
+                name,
+                doc_comment,
+                ast: command_recipe,
+                hash,
+                namespace,
+                file,
+                attributes,
+                pool: self.pool_of(TaskRecipe::pool_of(command_recipe))?,
+            },
+        );
+        Ok(())
+    }
 
+    fn evaluate_build_recipe(
+        &mut self,
+        build_recipe: &'a ast::BuildRecipe<'a>,
+        namespace: Option<Symbol>,
+        file: DiagnosticFileId,
+        doc_comment: String,
+    ) -> Result<(), EvalError> {
+        let hash = compute_stable_semantic_hash(build_recipe);
+        let attributes =
+            ir::Attributes::new(&build_recipe.attributes, ir::AttributeTarget::BuildRecipe)?;
+        check_recipe_let_attributes(build_recipe.body.statements.iter().filter_map(|stmt| {
+            match stmt.statement {
+                ast::BuildRecipeStmt::Let(ref let_stmt) => Some(let_stmt),
+                _ => None,
+            }
+        }))?;
+        let pool = self.pool_of(BuildRecipe::pool_of(build_recipe))?;
+        let host = self.host_of(BuildRecipe::host_of(build_recipe))?;
+        let container = BuildRecipe::container_of(build_recipe);
+        if let Some(container) = container {
+            if cfg!(windows) {
+                return Err(EvalError::ContainerUnsupported(container.span));
+            }
+            if host.is_some() {
+                return Err(EvalError::ContainerOnHost(container.span));
+            }
+        }
+        let scope = RootScope::with_namespace(self, namespace);
+        let mut pattern_builder = eval::eval_pattern_builder(&scope, &build_recipe.pattern)?.value;
+
+        // TODO: Consider if it isn't better to do this while matching recipes.
+        pattern_builder.ensure_absolute_path();
+
+        let excludes = BuildRecipe::excludes_of(build_recipe)
+            .map(|exclude| {
+                let mut builder = eval::eval_pattern_builder(&scope, exclude)?.value;
+                builder.ensure_absolute_path();
+                Ok(builder.build())
+            })
+            .collect::<Result<Vec<_>, EvalError>>()?;
+
+        self.manifest.build_recipes.push(BuildRecipe {
+            span: build_recipe.span,
+            pattern: pattern_builder.build(),
+            doc_comment,
+            ast: build_recipe,
+            hash,
+            namespace,
+            file,
+            priority: BuildRecipe::priority_of(build_recipe),
+            attributes,
+            pool,
+            host,
+            container: container.is_some(),
+            out: BuildRecipe::out_of(build_recipe),
+            excludes,
+        });
         Ok(())
     }
 
+    /// Check that the pool named by a `pool` statement is declared.
+    fn pool_of(&self, pool: Option<&ast::ConfigString>) -> Result<Option<Symbol>, EvalError> {
+        let Some(ast::ConfigString(span, name)) = pool else {
+            return Ok(None);
+        };
+        let name = Symbol::new(name);
+        if !self.runner_state.has_pool(name) {
+            return Err(EvalError::UnknownPool(*span, name.to_string()));
+        }
+        Ok(Some(name))
+    }
+
+    /// Check that the host named by a `host` statement is declared.
+    fn host_of(&self, host: Option<&ast::ConfigString>) -> Result<Option<Symbol>, EvalError> {
+        let Some(ast::ConfigString(span, name)) = host else {
+            return Ok(None);
+        };
+        if !cfg!(feature = "ssh") {
+            return Err(EvalError::RemoteUnsupported(*span));
+        }
+        let name = Symbol::new(name);
+        if !self.hosts.contains_key(&name) {
+            return Err(EvalError::UnknownHost(*span, name.to_string()));
+        }
+        Ok(Some(name))
+    }
+
     #[inline]
     pub fn io(&self) -> &dyn Io {
         self.io
@@ -337,11 +900,37 @@ impl<'a> Workspace<'a> {
         &self.project_root
     }
 
+    /// Directories containing source files, in priority order.
+    #[inline]
+    pub fn source_dirs(&self) -> &[Absolute<std::path::PathBuf>] {
+        &self.source_dirs
+    }
+
     #[inline]
     pub fn output_directory(&self) -> &Absolute<std::path::Path> {
         &self.output_directory
     }
 
+    /// Directories searched for programs before `PATH`, from the
+    /// `tool-paths` config.
+    #[inline]
+    pub fn tool_paths(&self) -> &[Absolute<std::path::PathBuf>] {
+        &self.tool_paths
+    }
+
+    /// The programs looked up by `which` expressions so far, sorted by name,
+    /// with the path they resolved to.
+    pub fn programs(&self) -> Vec<(String, Result<Absolute<std::path::PathBuf>, which::Error>)> {
+        let state = self.runtime_caches.lock();
+        let mut programs = state
+            .which_cache
+            .iter()
+            .map(|(name, result)| (name.clone(), result.clone().map(|(path, _)| path)))
+            .collect::<Vec<_>>();
+        programs.sort_by(|a, b| a.0.cmp(&b.0));
+        programs
+    }
+
     pub fn is_in_output_directory(&self, path: &Absolute<std::path::Path>) -> bool {
         path.starts_with(&*self.output_directory)
     }
@@ -350,6 +939,19 @@ impl<'a> Workspace<'a> {
         self.workspace_files.get(path)
     }
 
+    /// Resolve an abstract path to the source file it refers to, or to the
+    /// first source directory if no source file exists.
+    #[must_use]
+    pub fn resolve_source_path(
+        &self,
+        path: &Absolute<werk_fs::Path>,
+    ) -> Absolute<std::path::PathBuf> {
+        match self.get_project_file(path) {
+            Some(entry) => entry.path.clone(),
+            None => path.resolve(&self.source_dirs[0]),
+        }
+    }
+
     pub fn get_existing_project_or_output_file(
         &self,
         path: &Absolute<werk_fs::Path>,
@@ -365,7 +967,7 @@ impl<'a> Workspace<'a> {
         &self,
         path: &Absolute<werk_fs::Path>,
     ) -> Result<Option<DirEntry>, Error> {
-        let fs_path = path.resolve(&self.output_directory);
+        let fs_path = self.output_location(path);
         match self.io.metadata(&fs_path) {
             Ok(metadata) => Ok(Some(DirEntry {
                 path: fs_path,
@@ -387,11 +989,243 @@ impl<'a> Workspace<'a> {
         &self,
         path: &werk_fs::Path,
     ) -> Result<Absolute<std::path::PathBuf>, PathError> {
-        path.resolve(&self.output_directory)
+        let path = path.absolutize(werk_fs::Path::ROOT)?;
+        Ok(self.output_location(&path))
+    }
+
+    /// Get the location of an output file in the output directory, taking
+    /// the `out` statement of the build recipe producing it into account.
+    #[must_use]
+    pub fn output_location(&self, path: &Absolute<werk_fs::Path>) -> Absolute<std::path::PathBuf> {
+        match self.redirected_output(path) {
+            Some(location) => location.resolve(&self.output_directory),
+            None => path.resolve(&self.output_directory),
+        }
+    }
+
+    fn redirected_output(
+        &self,
+        path: &Absolute<werk_fs::Path>,
+    ) -> Option<Absolute<werk_fs::PathBuf>> {
+        if !self.redirect_outputs {
+            return None;
+        }
+
+        if let Some(location) = self.runtime_caches.lock().output_locations.get(path) {
+            return location.clone();
+        }
+        // Guard against `out` statements that refer to their own output file.
+        self.runtime_caches
+            .lock()
+            .output_locations
+            .insert(path.to_path_buf(), None);
+
+        // Errors are reported when the recipe is executed.
+        let location = match self.manifest.match_build_recipe(path) {
+            Ok(Some(recipe_match)) => eval::eval_out_stmt(self, &recipe_match).ok().flatten(),
+            _ => None,
+        };
+        self.runtime_caches
+            .lock()
+            .output_locations
+            .insert(path.to_path_buf(), location.clone());
+        location
+    }
+
+    /// The temporary directory of a recipe, available as `tmp` in the recipe
+    /// body. The path is unique to the task, and the same in every run, so
+    /// commands using it do not make the recipe outdated.
+    #[must_use]
+    pub fn recipe_tmp_dir(&self, task_id: TaskId) -> Absolute<std::path::PathBuf> {
+        self.output_directory
+            .join(WERK_TMP_DIRNAME)
+            .and_then(|dir| dir.join(task_file_name(task_id)))
+            .expect("invalid temporary directory name")
+    }
+
+    /// The response file passing the arguments of a long command line, for
+    /// the command at `index` in the pipeline of a recipe's step. Response
+    /// files are next to the temporary directories of recipes.
+    #[must_use]
+    pub fn response_file_path(
+        &self,
+        task_id: TaskId,
+        step: usize,
+        index: usize,
+    ) -> Absolute<std::path::PathBuf> {
+        self.output_directory
+            .join(WERK_TMP_DIRNAME)
+            .and_then(|dir| dir.join(format!("{}.{step}.{index}.rsp", task_file_name(task_id))))
+            .expect("invalid response file name")
+    }
+
+    /// The file in `<out-dir>/.werk/log` that the output of the commands of a
+    /// task is written to.
+    #[must_use]
+    pub fn task_log_file(&self, task_id: TaskId) -> Absolute<std::path::PathBuf> {
+        self.output_directory
+            .join(WERK_LOG_DIRNAME)
+            .and_then(|dir| dir.join(format!("{}.log", task_file_name(task_id))))
+            .expect("invalid log file name")
+    }
+
+    /// Mark the strings of a value as secret, so they are redacted in commands,
+    /// messages, logs, and errors shown to the user.
+    pub fn add_secret(&self, value: &crate::Value) {
+        let mut secrets = self.secrets.lock();
+        value.for_each_string_recursive(|s| {
+            if !s.is_empty() && !secrets.iter().any(|secret| secret == s) {
+                secrets.push(s.to_owned());
+            }
+        });
+        // Longer secrets first, so a secret containing another secret is
+        // redacted as a whole.
+        secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+    }
+
+    /// Replace secrets in text shown to the user with [`REDACTED`].
+    pub fn redact<'s>(&self, text: &'s str) -> Cow<'s, str> {
+        let secrets = self.secrets.lock();
+        let mut text = Cow::Borrowed(text);
+        for secret in &*secrets {
+            if text.contains(secret.as_str()) {
+                text = Cow::Owned(text.replace(secret.as_str(), REDACTED));
+            }
+        }
+        text
+    }
+
+    /// Redact a line of output from a command, which may not be valid UTF-8.
+    pub fn redact_bytes<'s>(&self, bytes: &'s [u8]) -> Cow<'s, [u8]> {
+        if self.secrets.lock().is_empty() {
+            return Cow::Borrowed(bytes);
+        }
+        match self.redact(&String::from_utf8_lossy(bytes)) {
+            Cow::Owned(redacted) => Cow::Owned(redacted.into_bytes()),
+            Cow::Borrowed(_) => Cow::Borrowed(bytes),
+        }
+    }
+
+    /// Redact the strings of a value, like a variable shown with `--explain`.
+    pub fn redact_value<'v>(&self, value: &'v crate::Value) -> Cow<'v, crate::Value> {
+        let mut redacted = Cow::Borrowed(value);
+        value.for_each_string_recursive(|s| {
+            if matches!(self.redact(s), Cow::Owned(_)) {
+                redacted = Cow::Owned(value.clone());
+            }
+        });
+        if let Cow::Owned(ref mut value) = redacted {
+            value.recursive_modify(|s| {
+                if let Cow::Owned(r) = self.redact(s) {
+                    *s = r;
+                }
+            });
+        }
+        redacted
+    }
+
+    /// Redact the arguments of a command line before it is shown to the user.
+    pub fn redact_command_line<'c>(
+        &self,
+        command_line: &'c crate::ShellCommandLine,
+    ) -> Cow<'c, crate::ShellCommandLine> {
+        if !command_line
+            .arguments
+            .iter()
+            .any(|arg| matches!(self.redact(arg), Cow::Owned(_)))
+        {
+            return Cow::Borrowed(command_line);
+        }
+        Cow::Owned(crate::ShellCommandLine {
+            program: command_line.program.clone(),
+            arguments: command_line
+                .arguments
+                .iter()
+                .map(|arg| self.redact(arg).into_owned())
+                .collect(),
+        })
+    }
+
+    /// Redact the command lines and messages of a recipe command before it is
+    /// shown to the user, like in `werk query`.
+    pub fn redact_run_command<'c>(
+        &self,
+        command: &'c crate::RunCommand,
+    ) -> Cow<'c, crate::RunCommand> {
+        use crate::RunCommand;
+        let redact = |s: &str| self.redact(s).into_owned();
+        match command {
+            RunCommand::Shell(command_line) => match self.redact_command_line(command_line) {
+                Cow::Borrowed(_) => Cow::Borrowed(command),
+                Cow::Owned(command_line) => Cow::Owned(RunCommand::Shell(command_line)),
+            },
+            RunCommand::Pipeline(command_lines, redirection) => {
+                let redacted = command_lines
+                    .iter()
+                    .map(|command_line| self.redact_command_line(command_line))
+                    .collect::<Vec<_>>();
+                if redacted.iter().all(|line| matches!(line, Cow::Borrowed(_))) {
+                    return Cow::Borrowed(command);
+                }
+                Cow::Owned(RunCommand::Pipeline(
+                    redacted.into_iter().map(Cow::into_owned).collect(),
+                    redirection.clone(),
+                ))
+            }
+            RunCommand::Fetch(url, digest, dest) => match self.redact(url) {
+                Cow::Borrowed(_) => Cow::Borrowed(command),
+                Cow::Owned(url) => Cow::Owned(RunCommand::Fetch(url, digest.clone(), dest.clone())),
+            },
+            RunCommand::Info(message) => match self.redact(message) {
+                Cow::Borrowed(_) => Cow::Borrowed(command),
+                Cow::Owned(message) => Cow::Owned(RunCommand::Info(message)),
+            },
+            RunCommand::Warn(message) => match self.redact(message) {
+                Cow::Borrowed(_) => Cow::Borrowed(command),
+                Cow::Owned(message) => Cow::Owned(RunCommand::Warn(message)),
+            },
+            RunCommand::SetEnv(key, value) => {
+                if matches!(self.redact(key), Cow::Borrowed(_))
+                    && matches!(self.redact(value), Cow::Borrowed(_))
+                {
+                    return Cow::Borrowed(command);
+                }
+                Cow::Owned(RunCommand::SetEnv(redact(key), redact(value)))
+            }
+            _ => Cow::Borrowed(command),
+        }
+    }
+
+    /// Write the output of the commands of a task to its log file, returning
+    /// the path of the file. The logs of the previous [`NUM_ROTATED_LOGS`]
+    /// runs that wrote the file are kept as `<file>.1`, `<file>.2`, and so on,
+    /// newest first.
+    pub fn write_task_log(
+        &self,
+        task_id: TaskId,
+        output: &[u8],
+    ) -> std::io::Result<Absolute<std::path::PathBuf>> {
+        let path = self.task_log_file(task_id);
+        let rotated = |n: usize| {
+            self.output_directory
+                .join(WERK_LOG_DIRNAME)
+                .and_then(|dir| dir.join(format!("{}.log.{n}", task_file_name(task_id))))
+                .expect("invalid log file name")
+        };
+        for n in (1..=NUM_ROTATED_LOGS).rev() {
+            let from = if n == 1 { path.clone() } else { rotated(n - 1) };
+            match self.io.copy_file(&from, &rotated(n)) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+                _ => (),
+            }
+        }
+        self.io.create_parent_dirs(&path)?;
+        self.io.write_file(&path, output)?;
+        Ok(path)
     }
 
     pub fn create_output_parent_dirs(&self, path: &Absolute<werk_fs::Path>) -> Result<(), Error> {
-        let fs_path = path.resolve(&self.output_directory);
+        let fs_path = self.output_location(path);
         self.io.create_parent_dirs(&fs_path).map_err(Into::into)
     }
 
@@ -400,34 +1234,45 @@ impl<'a> Workspace<'a> {
         path: &Absolute<std::path::Path>,
     ) -> Result<Absolute<werk_fs::PathBuf>, PathError> {
         match path.unresolve(&self.output_directory) {
-            Ok(path) => Ok(path),
-            // The path is not in the output directory, try the project root.
-            Err(werk_fs::PathError::UnresolveBeyondRoot) => path.unresolve(&self.project_root),
-            Err(err) => Err(err),
+            Ok(path) => return Ok(path),
+            // The path is not in the output directory, try the source
+            // directories.
+            Err(werk_fs::PathError::UnresolveBeyondRoot) => (),
+            Err(err) => return Err(err),
+        }
+
+        for source_dir in &self.source_dirs {
+            match path.unresolve(source_dir) {
+                Err(werk_fs::PathError::UnresolveBeyondRoot) => (),
+                result => return result,
+            }
         }
+
+        Err(werk_fs::PathError::UnresolveBeyondRoot)
     }
 
+    /// Find the files in the workspace matching any of the glob patterns, and
+    /// none of the negated (`!`-prefixed) patterns. See [`GlobPatterns`].
     pub fn glob_workspace_files(
         &self,
-        pattern: &str,
+        patterns: &[String],
     ) -> Result<(Vec<Absolute<werk_fs::PathBuf>>, Hash128), globset::Error> {
         let mut state = self.runtime_caches.lock();
         let state = &mut *state;
-        match state.glob_cache.entry(pattern.to_owned()) {
+        match state.glob_cache.entry(patterns.to_vec()) {
             hash_map::Entry::Occupied(entry) => {
                 let (paths, hash) = entry.get();
                 Ok((paths.clone(), *hash))
             }
             hash_map::Entry::Vacant(entry) => {
-                let glob = globset::Glob::new(pattern)?;
-                let matcher = glob.compile_matcher();
+                let matcher = GlobPatterns::new(patterns)?;
 
                 // Note: Workspace files are already sorted.
                 let matches = self
                     .workspace_files
                     .iter()
                     .filter_map(|(path, entry)| {
-                        if entry.metadata.is_file && matcher.is_match(path.as_os_path()) {
+                        if entry.metadata.is_file && matcher.is_match(path) {
                             Some(path.clone())
                         } else {
                             None
@@ -443,6 +1288,37 @@ impl<'a> Workspace<'a> {
         }
     }
 
+    /// Like [`Workspace::glob_workspace_files()`], but also includes the
+    /// targets of build recipes that match the patterns, whether or not they
+    /// have been built yet. Only build recipes without a pattern stem can be
+    /// enumerated.
+    pub fn glob_workspace_and_output_files(
+        &self,
+        patterns: &[String],
+    ) -> Result<(Vec<Absolute<werk_fs::PathBuf>>, Hash128), globset::Error> {
+        let (mut matches, _) = self.glob_workspace_files(patterns)?;
+        let matcher = GlobPatterns::new(patterns)?;
+
+        for recipe in &self.manifest.build_recipes {
+            let Some(targets) = recipe.pattern.expand() else {
+                continue;
+            };
+            for target in targets {
+                let Ok(target) = Absolute::try_from(target.as_str()) else {
+                    continue;
+                };
+                if matcher.is_match(&target) {
+                    matches.push(target);
+                }
+            }
+        }
+
+        matches.sort_unstable();
+        matches.dedup();
+        let hash = compute_glob_hash(&matches);
+        Ok((matches, hash))
+    }
+
     pub fn which<'p>(
         &self,
         command: &'p str,
@@ -464,7 +1340,7 @@ impl<'a> Workspace<'a> {
                 .clone()
                 .map(|(path, hash)| (Cow::Owned(path), Some(hash))),
             hash_map::Entry::Vacant(entry) => {
-                let result = self.io.which(command).map(|path| {
+                let result = self.resolve_program(command).map(|path| {
                     let hash = compute_stable_hash(&path);
                     (path, hash)
                 });
@@ -475,6 +1351,38 @@ impl<'a> Workspace<'a> {
         }
     }
 
+    /// Find a program by name: `--tool` overrides take precedence, then
+    /// `tool-paths`, then `PATH`.
+    fn resolve_program(&self, command: &str) -> Result<Absolute<std::path::PathBuf>, which::Error> {
+        if let Some(path) = self.tools.get(command) {
+            return Ok(path.clone());
+        }
+
+        // Names containing a directory are not searched for.
+        if !command.contains(std::path::is_separator) {
+            let exe_suffix = std::env::consts::EXE_SUFFIX;
+            let exe_name = if command.ends_with(exe_suffix) {
+                Cow::Borrowed(command)
+            } else {
+                Cow::Owned(format!("{command}{exe_suffix}"))
+            };
+            for dir in &self.tool_paths {
+                let Ok(path) = dir.join(&*exe_name) else {
+                    continue;
+                };
+                if self
+                    .io
+                    .metadata(&path)
+                    .is_ok_and(|metadata| metadata.is_file)
+                {
+                    return Ok(path);
+                }
+            }
+        }
+
+        self.which_cache.which(self.io, command)
+    }
+
     pub fn env(&self, name: &str) -> (String, Hash128) {
         let mut state = self.runtime_caches.lock();
         let state = &mut *state;
@@ -508,6 +1416,92 @@ impl<'a> Workspace<'a> {
         }
     }
 
+    /// Record that a source file was used as an input, for
+    /// [`consulted_files()`](Self::consulted_files).
+    pub(crate) fn register_consulted_file(&self, path: &Absolute<std::path::Path>) {
+        let mut state = self.runtime_caches.lock();
+        if !state.consulted_files.contains(path) {
+            state.consulted_files.insert(path.to_owned());
+        }
+    }
+
+    /// The source files that were used as inputs by the recipes that have run
+    /// so far, or read by `read` expressions, in sorted order. Werkfiles are
+    /// not included.
+    pub fn consulted_files(&self) -> Vec<Absolute<std::path::PathBuf>> {
+        self.runtime_caches
+            .lock()
+            .consulted_files
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Targets recently built from the command line, most recent first, as
+    /// recorded in `.werk-cache`.
+    pub fn recent_targets(&self) -> Vec<String> {
+        self.werk_cache.lock().recent.clone()
+    }
+
+    /// Record `target` as the most recent target built from the command line.
+    /// Only the last few targets are kept.
+    pub fn record_recent_target(&self, target: &str) {
+        const MAX_RECENT_TARGETS: usize = 10;
+        let recent = &mut self.werk_cache.lock().recent;
+        recent.retain(|existing| existing != target);
+        recent.insert(0, target.to_owned());
+        recent.truncate(MAX_RECENT_TARGETS);
+    }
+
+    /// Hash the contents of an output file, as recorded in `.werk-cache` after
+    /// building it. Returns `None` if the file does not exist or is a
+    /// directory.
+    pub(crate) fn hash_output_file(&self, path: &Absolute<werk_fs::Path>) -> Option<Hash128> {
+        let entry = self.get_existing_output_file(path).ok()??;
+        if !entry.metadata.is_file {
+            return None;
+        }
+        let (len, file) = self.io.open_file(&entry.path).ok()?;
+        compute_stable_file_hash(len, file).ok()
+    }
+
+    /// Compare the output files built by earlier runs to the hashes of their
+    /// contents recorded in `.werk-cache`, in path order. Outputs without a
+    /// recorded hash, like stamp files, are not included.
+    pub fn verify_outputs(&self) -> Vec<(Absolute<werk_fs::PathBuf>, OutputStatus)> {
+        self.output_hashes()
+            .into_iter()
+            .map(|(path, recorded_hash)| {
+                let status = match self.hash_output_file(&path) {
+                    Some(hash) if hash == recorded_hash => OutputStatus::Unchanged,
+                    Some(_) => OutputStatus::Modified,
+                    None => OutputStatus::Missing,
+                };
+                (path, status)
+            })
+            .collect()
+    }
+
+    /// The hashes of the output files recorded in `.werk-cache`, including
+    /// the outputs built by the current runner.
+    pub fn output_hashes(&self) -> BTreeMap<Absolute<werk_fs::PathBuf>, Hash128> {
+        self.werk_cache
+            .lock()
+            .build
+            .iter()
+            .filter_map(|(path, cache)| Some((path.clone(), cache.output_hash?)))
+            .collect()
+    }
+
+    /// Forget the tasks built by earlier runners of this workspace and its
+    /// members, so the next [`Runner`](crate::Runner) builds them again.
+    pub fn forget_builds(&self) {
+        self.runner_state.forget_tasks();
+        for member in self.members {
+            member.workspace.forget_builds();
+        }
+    }
+
     pub(crate) fn take_build_target_cache(
         &self,
         path: &Absolute<werk_fs::Path>,
@@ -522,6 +1516,76 @@ impl<'a> Workspace<'a> {
     ) {
         self.werk_cache.lock().build.insert(path, cache);
     }
+
+    /// Record the estimated critical path of a build target in the cache, so
+    /// it can be prioritized in later runs.
+    pub(crate) fn set_build_target_critical_path(
+        &self,
+        path: &Absolute<werk_fs::Path>,
+        critical_path: std::time::Duration,
+    ) {
+        if let Some(cache) = self.werk_cache.lock().build.get_mut(path) {
+            cache.critical_path_ms = Some(crate::cache::duration_to_millis(critical_path));
+        }
+    }
+
+    /// Get the output of a `shell` expression recorded in `.werk-cache`, if it
+    /// is still fresh according to the `#[cache]` attributes of the variable.
+    pub(crate) fn recorded_shell_output(
+        &self,
+        command: &str,
+        policy: &ir::ShellCache,
+    ) -> Option<String> {
+        let cache = self.werk_cache.lock();
+        let recorded = cache.shell.get(command)?;
+        recorded
+            .is_fresh(policy, unix_time_now())
+            .then(|| recorded.stdout.clone())
+    }
+
+    pub(crate) fn record_shell_output(
+        &self,
+        command: String,
+        stdout: String,
+        policy: &ir::ShellCache,
+    ) {
+        self.werk_cache.lock().shell.insert(
+            command,
+            RecordedShellOutput {
+                stdout,
+                recorded_at: unix_time_now(),
+                key: policy.key.clone(),
+            },
+        );
+    }
+}
+
+/// Only global `let` statements accept attributes.
+fn check_recipe_let_attributes<'b>(
+    let_stmts: impl IntoIterator<Item = &'b ast::LetStmt<'b>>,
+) -> Result<(), EvalError> {
+    for let_stmt in let_stmts {
+        if ir::ShellCache::new(&let_stmt.attributes)?.is_some() {
+            let attribute = &let_stmt.attributes[0];
+            let name = if attribute.name.ident == "cache-key" {
+                "cache-key"
+            } else {
+                "cache"
+            };
+            return Err(EvalError::AttributeNotAllowed(
+                attribute.span,
+                name,
+                "`let` statements in recipes",
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn unix_time_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
 }
 
 pub(crate) fn compute_stable_hash<T: std::hash::Hash + ?Sized>(value: &T) -> Hash128 {
@@ -530,6 +1594,38 @@ pub(crate) fn compute_stable_hash<T: std::hash::Hash + ?Sized>(value: &T) -> Has
     hasher.finish()
 }
 
+/// Hash the contents of a file of the given length, streaming them through a
+/// buffer. The hash is the same as [`compute_stable_hash()`] of the contents
+/// as a byte slice. Fails if the file does not have the expected length, for
+/// example because it changed while it was being read.
+pub(crate) fn compute_stable_file_hash(
+    len: u64,
+    file: impl std::io::Read,
+) -> std::io::Result<Hash128> {
+    use std::{hash::Hasher as _, io::BufRead as _};
+
+    let mut hasher = rustc_stable_hash::StableSipHasher128::new();
+    // The length prefix written by the `Hash` implementation of `[u8]`.
+    let expected = usize::try_from(len).map_err(std::io::Error::other)?;
+    hasher.write_usize(expected);
+    let mut reader = std::io::BufReader::with_capacity(64 * 1024, file);
+    let mut read = 0;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        hasher.write(buf);
+        read += buf.len();
+        let consumed = buf.len();
+        reader.consume(consumed);
+    }
+    if read != expected {
+        return Err(std::io::Error::other("file changed while it was hashed"));
+    }
+    Ok(hasher.finish())
+}
+
 pub(crate) fn compute_stable_semantic_hash<T: werk_parser::SemanticHash + ?Sized>(
     value: &T,
 ) -> Hash128 {
@@ -538,6 +1634,66 @@ pub(crate) fn compute_stable_semantic_hash<T: werk_parser::SemanticHash + ?Sized
     hasher.finish()
 }
 
+/// Resolve a `source-dirs` entry relative to the project root. The directory
+/// may be outside of the project root.
+fn resolve_source_dir(
+    io: &dyn Io,
+    project_root: &Absolute<std::path::Path>,
+    dir: &str,
+) -> Result<Absolute<std::path::PathBuf>, Error> {
+    let path = std::path::Path::join(project_root, dir);
+    let Ok(path) = path.normalize() else {
+        return Err(Error::InvalidSourceDir(dir.to_owned()));
+    };
+    match io.metadata(&path) {
+        Ok(metadata) if !metadata.is_file => Ok(path),
+        _ => Err(Error::InvalidSourceDir(dir.to_owned())),
+    }
+}
+
+/// A compiled set of glob patterns, as used by `glob`, `glob-out`, and target
+/// globs on the command line.
+///
+/// Patterns are matched against absolute workspace paths. `*`, `?`, and
+/// character classes never match `/`, while `**` matches any number of path
+/// components. Patterns starting with `!` exclude paths, regardless of the
+/// order in which they appear. When all patterns are negated, nothing matches.
+pub(crate) struct GlobPatterns {
+    include: globset::GlobSet,
+    exclude: globset::GlobSet,
+}
+
+impl GlobPatterns {
+    pub fn new(patterns: &[String]) -> Result<Self, globset::Error> {
+        let mut include = globset::GlobSetBuilder::new();
+        let mut exclude = globset::GlobSetBuilder::new();
+        for pattern in patterns {
+            let (set, pattern) = match pattern.strip_prefix('!') {
+                Some(pattern) => (&mut exclude, pattern),
+                None => (&mut include, pattern.as_str()),
+            };
+            let pattern = if pattern.starts_with('/') {
+                Cow::Borrowed(pattern)
+            } else {
+                Cow::Owned(format!("/{pattern}"))
+            };
+            let glob = globset::GlobBuilder::new(&pattern)
+                .literal_separator(true)
+                .build()?;
+            set.add(glob);
+        }
+        Ok(Self {
+            include: include.build()?,
+            exclude: exclude.build()?,
+        })
+    }
+
+    pub fn is_match(&self, path: &werk_fs::Path) -> bool {
+        let path = path.as_os_path();
+        self.include.is_match(path) && !self.exclude.is_match(path)
+    }
+}
+
 fn compute_glob_hash(files: &[Absolute<werk_fs::PathBuf>]) -> Hash128 {
     compute_stable_hash(files)
 }
@@ -603,11 +1759,13 @@ fn write_workspace_cache(
         }
     };
 
-    if let Some(build) = doc.get_mut("build") {
-        let build = make_table(build).expect("build is not a table");
-        build.set_implicit(true);
-        for (_target, target_info) in build.iter_mut() {
-            make_table(target_info);
+    for section in ["build", "shell"] {
+        if let Some(entries) = doc.get_mut(section) {
+            let entries = make_table(entries).expect("section is not a table");
+            entries.set_implicit(true);
+            for (_key, entry) in entries.iter_mut() {
+                make_table(entry);
+            }
         }
     }
 
@@ -633,20 +1791,56 @@ fn write_workspace_cache(
     }
 }
 
-impl<'a> werk_util::DiagnosticFileRepository for &'a Workspace<'a> {
-    #[inline]
-    fn get_source(
+impl Workspace<'_> {
+    /// The source code of the main Werkfile, one of its imports, or one of the
+    /// Werkfiles of a workspace member.
+    pub(crate) fn get_werkfile_source(
         &self,
         id: werk_util::DiagnosticFileId,
     ) -> Option<werk_util::DiagnosticSource<'_>> {
-        // TODO: Multiple input files
+        if let (Some(member), file) = crate::split_member_file_id(id) {
+            return self
+                .members
+                .get(member)?
+                .workspace
+                .get_werkfile_source(file);
+        }
         if id.0 == 0 {
             Some(werk_util::DiagnosticSource::new(
                 &self.werkfile_path,
                 self.werkfile_source,
             ))
         } else {
-            None
+            let import = self.imports.get(id.0 as usize - 1)?;
+            Some(werk_util::DiagnosticSource::new(
+                import.document.origin,
+                import.document.source,
+            ))
+        }
+    }
+}
+
+impl<'a> werk_util::DiagnosticFileRepository for &'a Workspace<'a> {
+    #[inline]
+    fn get_source(
+        &self,
+        id: werk_util::DiagnosticFileId,
+    ) -> Option<werk_util::DiagnosticSource<'_>> {
+        self.get_werkfile_source(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_hash_matches_slice_hash() {
+        for len in [0, 1, 100, 64 * 1024, 200_000] {
+            let contents = (0u8..=250).cycle().take(len).collect::<Vec<u8>>();
+            let streamed = compute_stable_file_hash(contents.len() as u64, &*contents).unwrap();
+            assert_eq!(streamed, compute_stable_hash(&contents), "length {len}");
         }
+        assert!(compute_stable_file_hash(4, &b"abc"[..]).is_err());
     }
 }
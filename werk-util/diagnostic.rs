@@ -1,5 +1,7 @@
 use std::ops::Range;
 
+use crate::{LineColumn, LineIndex};
+
 #[derive(Clone, Copy)]
 pub struct DiagnosticError<'a, T, R> {
     pub repository: R,
@@ -80,6 +82,14 @@ impl<'a> DiagnosticSource<'a> {
             source,
         }
     }
+
+    /// The 1-based line and column of a byte offset in the source. Columns are
+    /// counted in chars.
+    #[must_use]
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let LineColumn { line, column } = LineIndex::new(self.source).line_column(offset);
+        (line, column)
+    }
 }
 
 pub trait DiagnosticFileRepository {
@@ -103,6 +113,11 @@ pub trait Diagnostic {
     /// Error ID.
     fn id(&self) -> u32;
 
+    /// The stable error code, like `P1001` or `R0024`.
+    fn code(&self) -> String {
+        format!("{}{:04}", self.id_prefix(), self.id())
+    }
+
     /// The title of the error (potentially with fewer details).
     fn title(&self) -> String;
 
@@ -181,7 +196,7 @@ pub fn render_diagnostic_default<T: Diagnostic + ?Sized>(
     renderer: &annotate_snippets::Renderer,
 ) -> std::fmt::Result {
     let level = diag.level();
-    let id = format!("{}{:04}", diag.id_prefix(), diag.id());
+    let id = diag.code();
     let title = diag.title();
     let diagnostic = diag.snippet();
     let context_snippets = diag.context_snippets();
@@ -228,3 +243,170 @@ pub fn render_diagnostic_default<T: Diagnostic + ?Sized>(
     let rendered = renderer.render(message);
     std::fmt::Display::fmt(&rendered, f)
 }
+
+/// A diagnostic flattened into its code, message, and location, for
+/// machine-readable output.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct DiagnosticSummary {
+    pub code: String,
+    pub level: &'static str,
+    pub message: String,
+    /// The label of the main annotation, when it adds to the message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// 1-based line and column of the start of the main annotation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    /// 1-based line and column of the end of the main annotation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_column: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub help: Vec<String>,
+}
+
+impl DiagnosticSummary {
+    pub fn new<T: Diagnostic + ?Sized>(
+        diag: &T,
+        source_files: &dyn DiagnosticFileRepository,
+    ) -> Self {
+        let mut summary = DiagnosticSummary {
+            code: diag.code(),
+            level: level_name(diag.level()),
+            message: diag.title(),
+            label: None,
+            file: None,
+            line: None,
+            column: None,
+            end_line: None,
+            end_column: None,
+            help: diag.help(),
+        };
+        if let Some(snippet) = diag.snippet() {
+            if !snippet.message.is_empty() && snippet.message != summary.message {
+                summary.label = Some(snippet.message);
+            }
+            if let Some(source) = source_files.get_source(snippet.file_id) {
+                let range = LineIndex::new(source.source).range(snippet.span);
+                summary.file = Some(source.file.to_owned());
+                summary.line = Some(range.start.line);
+                summary.column = Some(range.start.column);
+                summary.end_line = Some(range.end.line);
+                summary.end_column = Some(range.end.column);
+            }
+        }
+        summary
+    }
+}
+
+/// One line per diagnostic, in the `file:line:column: level[code]: message`
+/// format understood by editors and CI annotations.
+impl std::fmt::Display for DiagnosticSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(ref file) = self.file {
+            write!(f, "{file}:")?;
+            if let (Some(line), Some(column)) = (self.line, self.column) {
+                write!(f, "{line}:{column}:")?;
+            }
+            f.write_str(" ")?;
+        }
+        write!(f, "{}[{}]: {}", self.level, self.code, self.message)?;
+        if let Some(ref label) = self.label {
+            write!(f, ": {label}")?;
+        }
+        Ok(())
+    }
+}
+
+fn level_name(level: annotate_snippets::Level) -> &'static str {
+    match level {
+        annotate_snippets::Level::Error => "error",
+        annotate_snippets::Level::Warning => "warning",
+        annotate_snippets::Level::Info => "info",
+        annotate_snippets::Level::Note => "note",
+        annotate_snippets::Level::Help => "help",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestDiagnostic;
+
+    impl Diagnostic for TestDiagnostic {
+        fn id_prefix(&self) -> &'static str {
+            "E"
+        }
+
+        fn level(&self) -> annotate_snippets::Level {
+            annotate_snippets::Level::Error
+        }
+
+        fn id(&self) -> u32 {
+            42
+        }
+
+        fn title(&self) -> String {
+            String::from("no such identifier")
+        }
+
+        fn snippet(&self) -> Option<DiagnosticSnippet> {
+            Some(DiagnosticSnippet {
+                file_id: DiagnosticFileId(0),
+                span: 18..22,
+                message: String::from("`cfla` not found"),
+                info: Vec::new(),
+            })
+        }
+
+        fn context_snippets(&self) -> Vec<DiagnosticSnippet> {
+            Vec::new()
+        }
+
+        fn help(&self) -> Vec<String> {
+            vec![String::from("did you mean `cflags`?")]
+        }
+    }
+
+    #[test]
+    fn line_column() {
+        let source = DiagnosticSource {
+            file: "Werkfile",
+            source: "let a = 1\nlet \u{e6}b = 2\n",
+        };
+        assert_eq!(source.line_column(0), (1, 1));
+        assert_eq!(source.line_column(4), (1, 5));
+        assert_eq!(source.line_column(10), (2, 1));
+        assert_eq!(source.line_column(16), (2, 6));
+        assert_eq!(source.line_column(1000), (3, 1));
+    }
+
+    #[test]
+    fn summary() {
+        let source = DiagnosticSource {
+            file: "Werkfile",
+            source: "let a = 1\nlet b = cfla\n",
+        };
+        let summary = DiagnosticSummary::new(&TestDiagnostic, &source);
+        assert_eq!(summary.code, "E0042");
+        assert_eq!(
+            (
+                summary.line,
+                summary.column,
+                summary.end_line,
+                summary.end_column
+            ),
+            (Some(2), Some(9), Some(2), Some(13))
+        );
+        assert_eq!(
+            summary.to_string(),
+            "Werkfile:2:9: error[E0042]: no such identifier: `cfla` not found"
+        );
+    }
+}
@@ -1,5 +1,11 @@
 mod diagnostic;
+mod line_index;
+mod sha256;
+mod suggest;
 mod symbol;
 
 pub use diagnostic::*;
+pub use line_index::*;
+pub use sha256::*;
+pub use suggest::*;
 pub use symbol::*;
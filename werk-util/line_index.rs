@@ -0,0 +1,132 @@
+use std::ops::Range;
+
+/// 1-based line and column in a source file. Columns are counted in chars.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineColumn {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for LineColumn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Converts byte offsets in a source file to lines and columns, and back. The
+/// start of each line is computed once, so lookups don't scan the source.
+#[derive(Clone, Debug)]
+pub struct LineIndex<'a> {
+    source: &'a str,
+    /// Byte offset of the start of each line. The first line starts at 0.
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    #[must_use]
+    pub fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    /// The number of lines. A trailing newline starts an empty last line.
+    #[must_use]
+    pub fn num_lines(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The line and column of a byte offset. Offsets past the end of the
+    /// source are at the end of the source, and offsets inside a UTF-8
+    /// sequence are at the start of its char.
+    #[must_use]
+    pub fn line_column(&self, offset: usize) -> LineColumn {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        LineColumn {
+            line,
+            column: self.source[line_start..offset].chars().count() + 1,
+        }
+    }
+
+    /// The line and column of the start and end of a byte range, like a
+    /// `Span`.
+    #[must_use]
+    pub fn range(&self, range: impl Into<Range<usize>>) -> Range<LineColumn> {
+        let range = range.into();
+        self.line_column(range.start)..self.line_column(range.end)
+    }
+
+    /// The byte offset of a line and column. The column may be one past the
+    /// last char of the line. Returns `None` if the line or column does not
+    /// exist.
+    #[must_use]
+    pub fn offset(&self, line_column: LineColumn) -> Option<usize> {
+        let line = self.line(line_column.line)?;
+        let column = line_column.column.checked_sub(1)?;
+        let start = self.line_starts[line_column.line - 1];
+        let chars = self.source[line.clone()]
+            .char_indices()
+            .map(|(index, _)| index);
+        chars
+            .chain(std::iter::once(line.len()))
+            .nth(column)
+            .map(|index| start + index)
+    }
+
+    /// The byte range of a 1-based line, without the newline.
+    #[must_use]
+    pub fn line(&self, line: usize) -> Option<Range<usize>> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.source.len(), |next| next - 1);
+        Some(start..end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lc(line: usize, column: usize) -> LineColumn {
+        LineColumn { line, column }
+    }
+
+    #[test]
+    fn line_column() {
+        let index = LineIndex::new("let a = 1\nlet \u{e6}b = 2\n");
+        assert_eq!(index.num_lines(), 3);
+        assert_eq!(index.line_column(0), lc(1, 1));
+        assert_eq!(index.line_column(9), lc(1, 10));
+        assert_eq!(index.line_column(10), lc(2, 1));
+        assert_eq!(index.line_column(15), lc(2, 5));
+        assert_eq!(index.line_column(16), lc(2, 6));
+        assert_eq!(index.line_column(17), lc(2, 7));
+        assert_eq!(index.line_column(1000), lc(3, 1));
+        assert_eq!(index.range(4..16), lc(1, 5)..lc(2, 6));
+    }
+
+    #[test]
+    fn offset() {
+        let source = "let a = 1\nlet \u{e6}b = 2\n";
+        let index = LineIndex::new(source);
+        for offset in (0..=source.len()).filter(|&offset| source.is_char_boundary(offset)) {
+            assert_eq!(index.offset(index.line_column(offset)), Some(offset));
+        }
+        assert_eq!(index.offset(lc(1, 11)), None);
+        assert_eq!(index.offset(lc(4, 1)), None);
+        assert_eq!(index.offset(lc(0, 1)), None);
+        assert_eq!(index.offset(lc(1, 0)), None);
+        assert_eq!(index.line(2), Some(10..21));
+    }
+}
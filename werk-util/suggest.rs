@@ -0,0 +1,77 @@
+/// Find the candidate that is most similar to `name`, for "did you mean"
+/// suggestions in error messages.
+///
+/// Candidates are compared by edit distance, and only candidates that are
+/// reasonably close to `name` are considered. If several candidates are
+/// equally close, the first one wins.
+pub fn did_you_mean<'a, I, S>(name: &str, candidates: I) -> Option<&'a S>
+where
+    I: IntoIterator<Item = &'a S>,
+    S: AsRef<str> + ?Sized + 'a,
+{
+    let max_distance = std::cmp::max(name.chars().count(), 3) / 3;
+    let mut best: Option<(usize, &'a S)> = None;
+    for candidate in candidates {
+        if candidate.as_ref() == name {
+            continue;
+        }
+        let distance = edit_distance(name, candidate.as_ref());
+        if distance > max_distance {
+            continue;
+        }
+        if best.is_none_or(|(best_distance, _)| distance < best_distance) {
+            best = Some((distance, candidate));
+        }
+    }
+    best.map(|(_, candidate)| candidate)
+}
+
+/// Edit distance between two strings, counted in chars. Insertions,
+/// deletions, substitutions, and transpositions of adjacent chars each count
+/// as one edit.
+#[must_use]
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev_prev = vec![0; b.len() + 1];
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (prev[j - 1] + cost)
+                .min(prev[j] + 1)
+                .min(current[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(prev_prev[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut prev_prev, &mut prev);
+        std::mem::swap(&mut prev, &mut current);
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("cflag", "cflags"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("rnu", "run"), 1);
+    }
+
+    #[test]
+    fn suggestion() {
+        let keys = ["out-dir", "edition", "print-commands", "default"];
+        assert_eq!(did_you_mean("out_dir", &keys), Some(&"out-dir"));
+        assert_eq!(did_you_mean("edtion", &keys), Some(&"edition"));
+        assert_eq!(did_you_mean("foo", &keys), None);
+        assert_eq!(did_you_mean("default", &keys), None);
+    }
+}